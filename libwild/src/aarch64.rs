@@ -1,9 +1,12 @@
 use crate::arch::Arch;
+use crate::arch::PltFeatures;
+use crate::elf::AARCH64_PAC_PLT_ENTRY_SIZE;
 use crate::elf::DEFAULT_AARCH64_PAGE_IGNORED_MASK;
 use crate::elf::DEFAULT_AARCH64_PAGE_MASK;
 use crate::elf::DEFAULT_AARCH64_PAGE_SIZE;
 use crate::elf::PLT_ENTRY_SIZE;
 use crate::resolution::ValueFlags;
+use crate::thunks::ThunkInfo;
 use anyhow::anyhow;
 use anyhow::Result;
 use linker_utils::aarch64::relocation_type_from_raw;
@@ -25,8 +28,47 @@ const PLT_ENTRY_TEMPLATE: &[u8] = &[
     0x1f, 0x20, 0x03, 0xd5, // nop
 ];
 
+const BTI_PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0x5f, 0x24, 0x03, 0xd5, // bti c
+    0x10, 0x00, 0x00, 0x90, // adrp x16, page(&(.got.plt[n]))
+    0x11, 0x02, 0x40, 0xf9, // ldr x17, [x16, offset(&(.got.plt[n]))]
+    0x20, 0x02, 0x1f, 0xd6, // br x17
+];
+
+const PAC_PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0x10, 0x00, 0x00, 0x90, // adrp x16, page(&(.got.plt[n]))
+    0x11, 0x02, 0x40, 0xf9, // ldr x17, [x16, offset(&(.got.plt[n]))]
+    0x10, 0x02, 0x00, 0x91, // add x16, x16, offset(&(.got.plt[n]))
+    0x9f, 0x21, 0x03, 0xd5, // autia1716
+    0x20, 0x02, 0x1f, 0xd6, // br x17
+    0x1f, 0x20, 0x03, 0xd5, // nop
+];
+
+const BTI_PAC_PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0x5f, 0x24, 0x03, 0xd5, // bti c
+    0x10, 0x00, 0x00, 0x90, // adrp x16, page(&(.got.plt[n]))
+    0x11, 0x02, 0x40, 0xf9, // ldr x17, [x16, offset(&(.got.plt[n]))]
+    0x10, 0x02, 0x00, 0x91, // add x16, x16, offset(&(.got.plt[n]))
+    0x9f, 0x21, 0x03, 0xd5, // autia1716
+    0x20, 0x02, 0x1f, 0xd6, // br x17
+];
+
+/// A range-extension thunk. x16 is the intra-procedure-call scratch register, so is free for us to
+/// use when going via a thunk.
+const THUNK_TEMPLATE: &[u8] = &[
+    0x10, 0x00, 0x00, 0x90, // adrp x16, page(target)
+    0x10, 0x02, 0x00, 0x91, // add x16, x16, offset(target)
+    0x00, 0x02, 0x1f, 0xd6, // br x16
+];
+
+/// How far `b` and `bl` can reach in either direction.
+const BRANCH_RANGE: u64 = 1 << 27;
+
 const _ASSERTS: () = {
     assert!(PLT_ENTRY_TEMPLATE.len() as u64 == PLT_ENTRY_SIZE);
+    assert!(BTI_PLT_ENTRY_TEMPLATE.len() as u64 == PLT_ENTRY_SIZE);
+    assert!(PAC_PLT_ENTRY_TEMPLATE.len() as u64 == AARCH64_PAC_PLT_ENTRY_SIZE);
+    assert!(BTI_PAC_PLT_ENTRY_TEMPLATE.len() as u64 == AARCH64_PAC_PLT_ENTRY_SIZE);
 };
 
 impl crate::arch::Arch for AArch64 {
@@ -55,16 +97,41 @@ impl crate::arch::Arch for AArch64 {
         aarch64_rel_type_to_string(r_type)
     }
 
+    fn merge_e_flags(_input_flags: impl Iterator<Item = u32>) -> crate::error::Result<u32> {
+        Ok(0)
+    }
+
     fn write_plt_entry(
         plt_entry: &mut [u8],
         got_address: u64,
         plt_address: u64,
+        _got_base: u64,
+        features: PltFeatures,
     ) -> crate::error::Result {
         // TODO: For simplicity, we assume now the PLT entry precedes the GOT entry, so we can
         // make the offset calculation in the unsigned type.
         debug_assert!(plt_address < got_address);
 
-        plt_entry.copy_from_slice(PLT_ENTRY_TEMPLATE);
+        let template = match (features.bti, features.pac) {
+            (false, false) => PLT_ENTRY_TEMPLATE,
+            (true, false) => BTI_PLT_ENTRY_TEMPLATE,
+            (false, true) => PAC_PLT_ENTRY_TEMPLATE,
+            (true, true) => BTI_PAC_PLT_ENTRY_TEMPLATE,
+        };
+        plt_entry.copy_from_slice(template);
+
+        // The BTI landing pad, if present, shifts the rest of the entry along by one instruction.
+        let plt_entry = if features.bti {
+            &mut plt_entry[4..]
+        } else {
+            plt_entry
+        };
+        let plt_address = if features.bti {
+            plt_address + 4
+        } else {
+            plt_address
+        };
+
         let plt_page_address = plt_address & DEFAULT_AARCH64_PAGE_IGNORED_MASK;
         let offset = got_address.wrapping_sub(plt_page_address);
         anyhow::ensure!(offset < (1 << 32), "PLT is more than 4GiB away from GOT");
@@ -80,6 +147,74 @@ impl crate::arch::Arch for AArch64 {
             false,
             &mut plt_entry[4..8],
         );
+        if features.pac {
+            // x16 is used as the modifier when authenticating, so needs to hold the address of the
+            // GOT entry.
+            RelocationInstruction::Add.write_to_value(
+                offset & DEFAULT_AARCH64_PAGE_MASK,
+                false,
+                &mut plt_entry[8..12],
+            );
+        }
+        Ok(())
+    }
+
+    fn thunk_info() -> Option<ThunkInfo> {
+        Some(ThunkInfo {
+            size: THUNK_TEMPLATE.len() as u64,
+            min_branch_range: BRANCH_RANGE,
+        })
+    }
+
+    fn branch_range(r_type: u32) -> Option<u64> {
+        match r_type {
+            object::elf::R_AARCH64_CALL26 | object::elf::R_AARCH64_JUMP26 => Some(BRANCH_RANGE),
+            _ => None,
+        }
+    }
+
+    fn got_relocation_range(r_type: u32) -> Option<std::ops::Range<i64>> {
+        match r_type {
+            // Used by -mcmodel=tiny.
+            object::elf::R_AARCH64_GOT_LD_PREL19
+            | object::elf::R_AARCH64_TLSIE_LD_GOTTPREL_PREL19
+            | object::elf::R_AARCH64_TLSDESC_LD_PREL19 => Some(-(1 << 20)..1 << 20),
+            // Used by -fpic.
+            object::elf::R_AARCH64_LD64_GOTPAGE_LO15 | object::elf::R_AARCH64_LD64_GOTOFF_LO15 => {
+                Some(0..1 << 15)
+            }
+            object::elf::R_AARCH64_ADR_GOT_PAGE
+            | object::elf::R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21
+            | object::elf::R_AARCH64_TLSGD_ADR_PAGE21
+            | object::elf::R_AARCH64_TLSDESC_ADR_PAGE21 => Some(-(1 << 32)..1 << 32),
+            _ => None,
+        }
+    }
+
+    fn write_thunk(
+        thunk: &mut [u8],
+        thunk_address: u64,
+        target_address: u64,
+    ) -> crate::error::Result {
+        thunk.copy_from_slice(THUNK_TEMPLATE);
+        let page_offset = (target_address & DEFAULT_AARCH64_PAGE_IGNORED_MASK)
+            .wrapping_sub(thunk_address & DEFAULT_AARCH64_PAGE_IGNORED_MASK)
+            as i64;
+        anyhow::ensure!(
+            page_offset.unsigned_abs() < (1 << 32),
+            "Branch target is more than 4GiB away from its thunk"
+        );
+        RelocationInstruction::Adr.write_to_value(
+            // The immediate value represents a signed 21-bit distance in pages.
+            (page_offset >> 12) as u64 & ((1 << 21) - 1),
+            false,
+            &mut thunk[0..4],
+        );
+        RelocationInstruction::Add.write_to_value(
+            target_address & DEFAULT_AARCH64_PAGE_MASK,
+            false,
+            &mut thunk[4..8],
+        );
         Ok(())
     }
 }
@@ -105,6 +240,9 @@ impl crate::arch::Relaxation for Relaxation {
     {
         let mut relocation = AArch64::relocation_from_raw(relocation_kind).unwrap();
         let can_bypass_got = value_flags.contains(ValueFlags::CAN_BYPASS_GOT);
+        let is_absolute = value_flags.contains(ValueFlags::ABSOLUTE)
+            && !value_flags.contains(ValueFlags::DYNAMIC);
+        let is_executable = output_kind.is_executable();
 
         // IFuncs cannot be referenced directly, they always need to go via the GOT.
         if value_flags.contains(ValueFlags::IFUNC) {
@@ -131,6 +269,11 @@ impl crate::arch::Relaxation for Relaxation {
         // because fetching it contains potential error paths, the optimiser probably can't optimise
         // away fetching it.
 
+        // When we go from a GOT load to computing the address relative to the instruction, the
+        // value of an absolute symbol would be wrong if the output gets loaded at a different
+        // address.
+        let can_compute_address = can_bypass_got && !(is_absolute && output_kind.is_relocatable());
+
         match relocation_kind {
             object::elf::R_AARCH64_CALL26 | object::elf::R_AARCH64_JUMP26 if can_bypass_got => {
                 relocation.kind = RelocationKind::Relative;
@@ -140,27 +283,46 @@ impl crate::arch::Relaxation for Relaxation {
                 });
             }
 
-            object::elf::R_AARCH64_TLSDESC_ADR_PAGE21 if can_bypass_got => {
+            // GOT load -> address computation. The adrp stays as is, but now gets the page of the
+            // symbol rather than of the GOT entry and the ldr from the GOT becomes an add. Both
+            // relocations of the pair reference the same symbol, so are relaxed together.
+            object::elf::R_AARCH64_ADR_GOT_PAGE if can_compute_address => {
+                return Some(Relaxation {
+                    kind: RelaxationKind::NoOp,
+                    rel_info: relocation_type_from_raw(object::elf::R_AARCH64_ADR_PREL_PG_HI21)
+                        .unwrap(),
+                });
+            }
+            object::elf::R_AARCH64_LD64_GOT_LO12_NC if can_compute_address => {
+                return Some(Relaxation {
+                    kind: RelaxationKind::LdrToAdd,
+                    rel_info: relocation_type_from_raw(object::elf::R_AARCH64_ADD_ABS_LO12_NC)
+                        .unwrap(),
+                });
+            }
+
+            // TLSDESC -> local exec.
+            object::elf::R_AARCH64_TLSDESC_ADR_PAGE21 if can_bypass_got && is_executable => {
                 // TODO: check we met all consecutive 4 instructions!
                 return Some(Relaxation {
                     kind: RelaxationKind::ReplaceWithNop,
                     rel_info: relocation_type_from_raw(object::elf::R_AARCH64_NONE).unwrap(),
                 });
             }
-            object::elf::R_AARCH64_TLSDESC_LD64_LO12 if can_bypass_got => {
+            object::elf::R_AARCH64_TLSDESC_LD64_LO12 if can_bypass_got && is_executable => {
                 return Some(Relaxation {
                     kind: RelaxationKind::ReplaceWithNop,
                     rel_info: relocation_type_from_raw(object::elf::R_AARCH64_NONE).unwrap(),
                 });
             }
-            object::elf::R_AARCH64_TLSDESC_ADD_LO12 if can_bypass_got => {
+            object::elf::R_AARCH64_TLSDESC_ADD_LO12 if can_bypass_got && is_executable => {
                 return Some(Relaxation {
                     kind: RelaxationKind::MovzX0Lsl16,
                     rel_info: relocation_type_from_raw(object::elf::R_AARCH64_TLSLE_MOVW_TPREL_G1)
                         .unwrap(),
                 });
             }
-            object::elf::R_AARCH64_TLSDESC_CALL if can_bypass_got => {
+            object::elf::R_AARCH64_TLSDESC_CALL if can_bypass_got && is_executable => {
                 return Some(Relaxation {
                     kind: RelaxationKind::MovkX0,
                     rel_info: relocation_type_from_raw(
@@ -169,14 +331,98 @@ impl crate::arch::Relaxation for Relaxation {
                     .unwrap(),
                 });
             }
-            object::elf::R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21 if can_bypass_got => {
+
+            // TLSDESC -> initial exec. The descriptor load becomes a load of the TP offset from
+            // the GOT and the call becomes a nop.
+            object::elf::R_AARCH64_TLSDESC_ADR_PAGE21 if is_executable => {
+                return Some(Relaxation {
+                    kind: RelaxationKind::NoOp,
+                    rel_info: relocation_type_from_raw(
+                        object::elf::R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21,
+                    )
+                    .unwrap(),
+                });
+            }
+            object::elf::R_AARCH64_TLSDESC_LD64_LO12 if is_executable => {
+                return Some(Relaxation {
+                    kind: RelaxationKind::LdrX0,
+                    rel_info: relocation_type_from_raw(
+                        object::elf::R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC,
+                    )
+                    .unwrap(),
+                });
+            }
+            object::elf::R_AARCH64_TLSDESC_ADD_LO12 | object::elf::R_AARCH64_TLSDESC_CALL
+                if is_executable =>
+            {
+                return Some(Relaxation {
+                    kind: RelaxationKind::ReplaceWithNop,
+                    rel_info: relocation_type_from_raw(object::elf::R_AARCH64_NONE).unwrap(),
+                });
+            }
+
+            // General dynamic -> local exec. The adrp, add, bl __tls_get_addr, nop sequence is
+            // replaced with movz, movk, mrs, add.
+            object::elf::R_AARCH64_TLSGD_ADR_PAGE21
+                if can_bypass_got
+                    && is_executable
+                    && is_tls_get_addr_call(section_bytes, offset + 8) =>
+            {
+                return Some(Relaxation {
+                    kind: RelaxationKind::MovzX0Lsl16,
+                    rel_info: relocation_type_from_raw(object::elf::R_AARCH64_TLSLE_MOVW_TPREL_G1)
+                        .unwrap(),
+                });
+            }
+            object::elf::R_AARCH64_TLSGD_ADD_LO12_NC
+                if can_bypass_got
+                    && is_executable
+                    && is_tls_get_addr_call(section_bytes, offset + 4) =>
+            {
+                return Some(Relaxation {
+                    kind: RelaxationKind::MovkX0AddThreadPointer,
+                    rel_info: relocation_type_from_raw(
+                        object::elf::R_AARCH64_TLSLE_MOVW_TPREL_G0_NC,
+                    )
+                    .unwrap(),
+                });
+            }
+
+            // General dynamic -> initial exec.
+            object::elf::R_AARCH64_TLSGD_ADR_PAGE21
+                if is_executable && is_tls_get_addr_call(section_bytes, offset + 8) =>
+            {
+                return Some(Relaxation {
+                    kind: RelaxationKind::NoOp,
+                    rel_info: relocation_type_from_raw(
+                        object::elf::R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21,
+                    )
+                    .unwrap(),
+                });
+            }
+            object::elf::R_AARCH64_TLSGD_ADD_LO12_NC
+                if is_executable && is_tls_get_addr_call(section_bytes, offset + 4) =>
+            {
+                return Some(Relaxation {
+                    kind: RelaxationKind::LdrX0AddThreadPointer,
+                    rel_info: relocation_type_from_raw(
+                        object::elf::R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC,
+                    )
+                    .unwrap(),
+                });
+            }
+
+            // Initial exec -> local exec.
+            object::elf::R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21 if can_bypass_got && is_executable => {
                 return Some(Relaxation {
                     kind: RelaxationKind::MovzXnLsl16,
                     rel_info: relocation_type_from_raw(object::elf::R_AARCH64_TLSLE_MOVW_TPREL_G1)
                         .unwrap(),
                 });
             }
-            object::elf::R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC if can_bypass_got => {
+            object::elf::R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC
+                if can_bypass_got && is_executable =>
+            {
                 return Some(Relaxation {
                     kind: RelaxationKind::MovkXn,
                     rel_info: relocation_type_from_raw(
@@ -200,7 +446,7 @@ impl crate::arch::Relaxation for Relaxation {
         self.rel_info
     }
 
-    fn debug_kind(&self) -> impl std::fmt::Debug {
+    fn debug_kind(&self) -> &dyn std::fmt::Debug {
         &self.kind
     }
 
@@ -208,3 +454,359 @@ impl crate::arch::Relaxation for Relaxation {
         self.kind.next_modifier()
     }
 }
+
+/// Returns whether `offset` is the start of a `bl` (which will be the call to `__tls_get_addr`)
+/// followed by a `nop`. This is the sequence that the compiler emits for general dynamic TLS
+/// accesses and which we need to replace when relaxing.
+fn is_tls_get_addr_call(section_bytes: &[u8], offset: usize) -> bool {
+    const BL_MASK: u32 = 0xfc00_0000;
+    const BL: u32 = 0x9400_0000;
+    const NOP: u32 = 0xd503_201f;
+
+    let Some(bytes) = section_bytes.get(offset..offset + 8) else {
+        return false;
+    };
+    let call = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+    let next = u32::from_le_bytes(bytes[4..].try_into().unwrap());
+    call & BL_MASK == BL && next == NOP
+}
+
+#[test]
+fn test_got_relaxation() {
+    use crate::arch::Relaxation as _;
+    use crate::args::OutputKind;
+    use crate::args::RelocationModel;
+
+    // ldr x1, [x2, #8]
+    let ldr = [0x41, 0x04, 0x40, 0xf9];
+    let relax = |value_flags| {
+        Relaxation::new(
+            object::elf::R_AARCH64_LD64_GOT_LO12_NC,
+            &ldr,
+            0,
+            value_flags,
+            OutputKind::StaticExecutable(RelocationModel::Relocatable),
+            shf::EXECINSTR,
+        )
+    };
+
+    let r = relax(ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT).unwrap();
+    let mut out = ldr;
+    r.apply(&mut out, &mut 0, &mut 0);
+    // add x1, x2, #0
+    assert_eq!(out, [0x41, 0x00, 0x00, 0x91]);
+    assert_eq!(r.rel_info().kind, RelocationKind::AbsoluteAArch64);
+
+    // An absolute symbol's value can't be computed relative to the instruction in a PIE.
+    assert!(relax(ValueFlags::ABSOLUTE | ValueFlags::CAN_BYPASS_GOT).is_none());
+    assert!(relax(ValueFlags::ADDRESS).is_none());
+}
+
+/// Relaxes each relocation in `relocations`, which are (offset, r_type) pairs, applies the
+/// relaxations to a copy of `code` and returns the result along with the new relocation types.
+#[cfg(test)]
+fn relax_sequence(
+    code: &[u8],
+    relocations: &[(u64, u32)],
+    value_flags: ValueFlags,
+    output_kind: crate::args::OutputKind,
+) -> Option<(Vec<u8>, Vec<RelocationKind>)> {
+    use crate::arch::Relaxation as _;
+
+    let mut out = code.to_vec();
+    let mut kinds = Vec::new();
+    for &(offset, r_type) in relocations {
+        let r = Relaxation::new(
+            r_type,
+            code,
+            offset,
+            value_flags,
+            output_kind,
+            shf::EXECINSTR,
+        )?;
+        let mut offset = offset;
+        r.apply(&mut out, &mut offset, &mut 0);
+        kinds.push(r.rel_info().kind);
+    }
+    Some((out, kinds))
+}
+
+#[test]
+fn test_tls_gd_relaxation() {
+    use crate::args::OutputKind;
+    use crate::args::RelocationModel;
+
+    let code = [
+        0x00, 0x00, 0x00, 0x90, // adrp x0, :tlsgd:var
+        0x00, 0x00, 0x00, 0x91, // add x0, x0, :tlsgd_lo12:var
+        0x00, 0x00, 0x00, 0x94, // bl __tls_get_addr
+        0x1f, 0x20, 0x03, 0xd5, // nop
+    ];
+    let relocations = [
+        (0, object::elf::R_AARCH64_TLSGD_ADR_PAGE21),
+        (4, object::elf::R_AARCH64_TLSGD_ADD_LO12_NC),
+    ];
+    let exe = OutputKind::StaticExecutable(RelocationModel::Relocatable);
+    let kind = |r_type| relocation_type_from_raw(r_type).unwrap().kind;
+
+    // General dynamic -> local exec.
+    let (out, kinds) =
+        relax_sequence(&code, &relocations, ValueFlags::CAN_BYPASS_GOT, exe).unwrap();
+    assert_eq!(
+        out,
+        [
+            0x00, 0x00, 0xa0, 0xd2, // movz x0, #0, lsl #16
+            0x00, 0x00, 0x80, 0xf2, // movk x0, #0
+            0x41, 0xd0, 0x3b, 0xd5, // mrs x1, tpidr_el0
+            0x20, 0x00, 0x00, 0x8b, // add x0, x1, x0
+        ]
+    );
+    assert_eq!(
+        kinds,
+        [
+            kind(object::elf::R_AARCH64_TLSLE_MOVW_TPREL_G1),
+            kind(object::elf::R_AARCH64_TLSLE_MOVW_TPREL_G0_NC),
+        ]
+    );
+
+    // General dynamic -> initial exec.
+    let (out, kinds) = relax_sequence(&code, &relocations, ValueFlags::DYNAMIC, exe).unwrap();
+    assert_eq!(
+        out,
+        [
+            0x00, 0x00, 0x00, 0x90, // adrp x0, :gottprel:var
+            0x00, 0x00, 0x40, 0xf9, // ldr x0, [x0, :gottprel_lo12:var]
+            0x41, 0xd0, 0x3b, 0xd5, // mrs x1, tpidr_el0
+            0x20, 0x00, 0x00, 0x8b, // add x0, x1, x0
+        ]
+    );
+    assert_eq!(
+        kinds,
+        [
+            kind(object::elf::R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21),
+            kind(object::elf::R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC),
+        ]
+    );
+
+    // The relocation on the call to `__tls_get_addr` needs to be skipped, since we replaced the
+    // call.
+    let r = <Relaxation as crate::arch::Relaxation>::new(
+        object::elf::R_AARCH64_TLSGD_ADD_LO12_NC,
+        &code,
+        4,
+        ValueFlags::CAN_BYPASS_GOT,
+        exe,
+        shf::EXECINSTR,
+    )
+    .unwrap();
+    assert_eq!(
+        crate::arch::Relaxation::next_modifier(&r),
+        RelocationModifier::SkipNextRelocation
+    );
+
+    // Shared objects need to keep using `__tls_get_addr`.
+    assert!(relax_sequence(
+        &code,
+        &relocations,
+        ValueFlags::CAN_BYPASS_GOT,
+        OutputKind::SharedObject
+    )
+    .is_none());
+
+    // If the add isn't followed by a call and a nop, then it isn't a sequence we know how to
+    // rewrite.
+    let mut unexpected = code;
+    unexpected[12..16].copy_from_slice(&[0x00, 0x00, 0x00, 0x91]);
+    assert!(relax_sequence(&unexpected, &relocations, ValueFlags::CAN_BYPASS_GOT, exe).is_none());
+}
+
+#[test]
+fn test_tlsdesc_relaxation() {
+    use crate::args::OutputKind;
+    use crate::args::RelocationModel;
+
+    let code = [
+        0x00, 0x00, 0x00, 0x90, // adrp x0, :tlsdesc:var
+        0x01, 0x00, 0x40, 0xf9, // ldr x1, [x0, :tlsdesc_lo12:var]
+        0x00, 0x00, 0x00, 0x91, // add x0, x0, :tlsdesc_lo12:var
+        0x20, 0x00, 0x3f, 0xd6, // blr x1
+    ];
+    let relocations = [
+        (0, object::elf::R_AARCH64_TLSDESC_ADR_PAGE21),
+        (4, object::elf::R_AARCH64_TLSDESC_LD64_LO12),
+        (8, object::elf::R_AARCH64_TLSDESC_ADD_LO12),
+        (12, object::elf::R_AARCH64_TLSDESC_CALL),
+    ];
+    let exe = OutputKind::DynamicExecutable(RelocationModel::Relocatable);
+    let kind = |r_type| relocation_type_from_raw(r_type).unwrap().kind;
+
+    // TLSDESC -> local exec.
+    let (out, kinds) =
+        relax_sequence(&code, &relocations, ValueFlags::CAN_BYPASS_GOT, exe).unwrap();
+    assert_eq!(
+        out,
+        [
+            0x1f, 0x20, 0x03, 0xd5, // nop
+            0x1f, 0x20, 0x03, 0xd5, // nop
+            0x00, 0x00, 0xa0, 0xd2, // movz x0, #0, lsl #16
+            0x00, 0x00, 0x80, 0xf2, // movk x0, #0
+        ]
+    );
+    assert_eq!(
+        kinds,
+        [
+            kind(object::elf::R_AARCH64_NONE),
+            kind(object::elf::R_AARCH64_NONE),
+            kind(object::elf::R_AARCH64_TLSLE_MOVW_TPREL_G1),
+            kind(object::elf::R_AARCH64_TLSLE_MOVW_TPREL_G0_NC),
+        ]
+    );
+
+    // TLSDESC -> initial exec.
+    let (out, kinds) = relax_sequence(&code, &relocations, ValueFlags::DYNAMIC, exe).unwrap();
+    assert_eq!(
+        out,
+        [
+            0x00, 0x00, 0x00, 0x90, // adrp x0, :gottprel:var
+            0x00, 0x00, 0x40, 0xf9, // ldr x0, [x0, :gottprel_lo12:var]
+            0x1f, 0x20, 0x03, 0xd5, // nop
+            0x1f, 0x20, 0x03, 0xd5, // nop
+        ]
+    );
+    assert_eq!(
+        kinds,
+        [
+            kind(object::elf::R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21),
+            kind(object::elf::R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC),
+            kind(object::elf::R_AARCH64_NONE),
+            kind(object::elf::R_AARCH64_NONE),
+        ]
+    );
+
+    assert!(relax_sequence(
+        &code,
+        &relocations,
+        ValueFlags::CAN_BYPASS_GOT,
+        OutputKind::SharedObject
+    )
+    .is_none());
+}
+
+#[test]
+fn test_tls_ie_relaxation() {
+    use crate::args::OutputKind;
+    use crate::args::RelocationModel;
+
+    let code = [
+        0x03, 0x00, 0x00, 0x90, // adrp x3, :gottprel:var
+        0x63, 0x00, 0x40, 0xf9, // ldr x3, [x3, :gottprel_lo12:var]
+    ];
+    let relocations = [
+        (0, object::elf::R_AARCH64_TLSIE_ADR_GOTTPREL_PAGE21),
+        (4, object::elf::R_AARCH64_TLSIE_LD64_GOTTPREL_LO12_NC),
+    ];
+    let exe = OutputKind::StaticExecutable(RelocationModel::NonRelocatable);
+
+    // Initial exec -> local exec. The destination register is preserved.
+    let (out, _) = relax_sequence(&code, &relocations, ValueFlags::CAN_BYPASS_GOT, exe).unwrap();
+    assert_eq!(
+        out,
+        [
+            0x03, 0x00, 0xa0, 0xd2, // movz x3, #0, lsl #16
+            0x03, 0x00, 0x80, 0xf2, // movk x3, #0
+        ]
+    );
+
+    assert!(relax_sequence(&code, &relocations, ValueFlags::DYNAMIC, exe).is_none());
+}
+
+#[test]
+fn test_write_plt_entry() {
+    // The GOT entry is 0x2_0018 bytes after the start of the page containing the PLT entry.
+    let got_address = 0x43_0018;
+    let plt_address = 0x41_0020;
+    let write = |features, size| {
+        let mut entry = vec![0; size as usize];
+        AArch64::write_plt_entry(&mut entry, got_address, plt_address, 0, features).unwrap();
+        entry
+    };
+
+    assert_eq!(
+        write(PltFeatures::default(), PLT_ENTRY_SIZE),
+        [
+            0x10, 0x01, 0x00, 0x90, // adrp x16, 0x430000
+            0x11, 0x0e, 0x40, 0xf9, // ldr x17, [x16, #0x18]
+            0x20, 0x02, 0x1f, 0xd6, // br x17
+            0x1f, 0x20, 0x03, 0xd5, // nop
+        ]
+    );
+
+    // The landing pad moves everything along by an instruction, but the address of the adrp is
+    // still on the same page.
+    let bti = PltFeatures {
+        bti: true,
+        ..PltFeatures::default()
+    };
+    assert_eq!(
+        write(bti, PLT_ENTRY_SIZE),
+        [
+            0x5f, 0x24, 0x03, 0xd5, // bti c
+            0x10, 0x01, 0x00, 0x90, // adrp x16, 0x430000
+            0x11, 0x0e, 0x40, 0xf9, // ldr x17, [x16, #0x18]
+            0x20, 0x02, 0x1f, 0xd6, // br x17
+        ]
+    );
+
+    let pac = PltFeatures {
+        pac: true,
+        ..PltFeatures::default()
+    };
+    assert_eq!(
+        write(pac, AARCH64_PAC_PLT_ENTRY_SIZE),
+        [
+            0x10, 0x01, 0x00, 0x90, // adrp x16, 0x430000
+            0x11, 0x0e, 0x40, 0xf9, // ldr x17, [x16, #0x18]
+            0x10, 0x62, 0x00, 0x91, // add x16, x16, #0x18
+            0x9f, 0x21, 0x03, 0xd5, // autia1716
+            0x20, 0x02, 0x1f, 0xd6, // br x17
+            0x1f, 0x20, 0x03, 0xd5, // nop
+        ]
+    );
+
+    let bti_pac = PltFeatures {
+        bti: true,
+        pac: true,
+        ..PltFeatures::default()
+    };
+    assert_eq!(
+        write(bti_pac, AARCH64_PAC_PLT_ENTRY_SIZE),
+        [
+            0x5f, 0x24, 0x03, 0xd5, // bti c
+            0x10, 0x01, 0x00, 0x90, // adrp x16, 0x430000
+            0x11, 0x0e, 0x40, 0xf9, // ldr x17, [x16, #0x18]
+            0x10, 0x62, 0x00, 0x91, // add x16, x16, #0x18
+            0x9f, 0x21, 0x03, 0xd5, // autia1716
+            0x20, 0x02, 0x1f, 0xd6, // br x17
+        ]
+    );
+}
+
+#[test]
+fn test_write_thunk() {
+    let mut thunk = [0; 12];
+    AArch64::write_thunk(&mut thunk, 0x10_0ff8, 0x900_1234).unwrap();
+    assert_eq!(
+        thunk,
+        [
+            0x10, 0x78, 0x04, 0xb0, // adrp x16, 0x9001000
+            0x10, 0xd2, 0x08, 0x91, // add x16, x16, #0x234
+            0x00, 0x02, 0x1f, 0xd6, // br x16
+        ]
+    );
+
+    // Backwards.
+    AArch64::write_thunk(&mut thunk, 0x900_1234, 0x10_0ff8).unwrap();
+    assert_eq!(&thunk[0..4], [0xf0, 0x87, 0xfb, 0xf0]);
+
+    assert!(AArch64::write_thunk(&mut thunk, 0, 1 << 33).is_err());
+}
@@ -0,0 +1,336 @@
+//! Workarounds for Cortex-A53 errata that linkers are expected to fix, since code built for
+//! generic AArch64 might run on affected CPUs. Both are enabled by flags, which compiler drivers on
+//! some distributions pass by default.
+//!
+//! Erratum 843419 (`--fix-cortex-a53-843419`): an `adrp` in one of the last two instruction slots of
+//! a 4KiB page, followed by a load or store, then optionally one other instruction that isn't a
+//! branch, then a load or store that uses the register written by the `adrp` as its base, can
+//! access the wrong address.
+//!
+//! Erratum 835769 (`--fix-cortex-a53-835769`): a 64-bit multiply-accumulate immediately after a load
+//! or store can produce the wrong result.
+//!
+//! In both cases, we move the last instruction of the sequence into a patch that we place after the
+//! section, followed by a branch back. The original instruction gets replaced by a branch to the
+//! patch. Like other linkers, we don't try to rule out every sequence that the erratum can't affect,
+//! since patching an unaffected sequence is harmless. Only code marked by `$x` mapping symbols is
+//! scanned, since sections can contain data that happens to look like an affected sequence.
+
+use crate::arch::Architecture;
+use crate::args::Args;
+use crate::elf::File;
+use crate::error::Result;
+use object::LittleEndian;
+use std::ops::Range;
+
+/// The size of a patch: the instruction that we moved and a branch back.
+pub(crate) const PATCH_SIZE: u64 = 8;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+/// Which errata we're working around.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ErrataFixes {
+    erratum_843419: bool,
+    erratum_835769: bool,
+}
+
+impl ErrataFixes {
+    /// Returns the fixes requested by `args`, or None if there aren't any.
+    pub(crate) fn from_args(args: &Args) -> Option<ErrataFixes> {
+        if args.arch() != Architecture::AArch64
+            || !(args.fix_cortex_a53_843419 || args.fix_cortex_a53_835769)
+        {
+            return None;
+        }
+        Some(ErrataFixes {
+            erratum_843419: args.fix_cortex_a53_843419,
+            erratum_835769: args.fix_cortex_a53_835769,
+        })
+    }
+}
+
+/// Returns the ranges of each section of `object` that contain code, as marked by mapping symbols.
+/// Indexed by section index.
+pub(crate) fn code_ranges(object: &File) -> Result<Vec<Vec<Range<u64>>>> {
+    let mut mapping_symbols: Vec<Vec<(u64, bool)>> = vec![Vec::new(); object.sections.len()];
+    for (symbol_index, symbol) in object.symbols.enumerate() {
+        let name = object.symbol_name(symbol)?;
+        let is_code = name == b"$x" || name.starts_with(b"$x.");
+        if !is_code && name != b"$d" && !name.starts_with(b"$d.") {
+            continue;
+        }
+        if let Some(section_index) = object.symbol_section(symbol, symbol_index)? {
+            mapping_symbols[section_index.0].push((symbol.st_value.get(LittleEndian), is_code));
+        }
+    }
+    Ok(mapping_symbols
+        .into_iter()
+        .enumerate()
+        .map(|(section_index, mut symbols)| {
+            if symbols.is_empty() {
+                return Vec::new();
+            }
+            symbols.sort_by_key(|(offset, _)| *offset);
+            let section_size = object
+                .section(object::SectionIndex(section_index))
+                .and_then(|section| object.section_size(section))
+                .unwrap_or(0);
+            let mut ranges: Vec<Range<u64>> = Vec::new();
+            let mut code_start = None;
+            for (offset, is_code) in symbols {
+                match (code_start, is_code) {
+                    (None, true) => code_start = Some(offset),
+                    (Some(start), false) => {
+                        ranges.push(start..offset);
+                        code_start = None;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(start) = code_start {
+                ranges.push(start..section_size);
+            }
+            ranges
+        })
+        .collect())
+}
+
+/// Calls `move_instruction` with the offset of each instruction in `code` that needs to be moved
+/// into a patch. `code_ranges` are the parts of `code` that contain instructions and
+/// `section_address` is the address at which `code` will be placed.
+pub(crate) fn find_instructions_to_move(
+    fixes: ErrataFixes,
+    code: &[u8],
+    code_ranges: &[Range<u64>],
+    section_address: u64,
+    mut move_instruction: impl FnMut(u64),
+) {
+    for range in code_ranges {
+        // Instructions are always 4-byte aligned.
+        let start = range.start.next_multiple_of(4);
+        let end = range.end.min(code.len() as u64);
+        if start >= end {
+            continue;
+        }
+        if fixes.erratum_843419 {
+            find_843419(code, start..end, section_address, &mut move_instruction);
+        }
+        if fixes.erratum_835769 {
+            find_835769(code, start..end, &mut move_instruction);
+        }
+    }
+}
+
+fn find_843419(
+    code: &[u8],
+    range: Range<u64>,
+    section_address: u64,
+    move_instruction: &mut impl FnMut(u64),
+) {
+    // Skip to the first instruction that's in one of the last two slots of a page.
+    let mut offset = range.start;
+    let page_offset = (section_address + offset) % PAGE_SIZE;
+    if page_offset < PAGE_SIZE - 8 {
+        offset += PAGE_SIZE - 8 - page_offset;
+    }
+    while offset + 12 <= range.end {
+        let adrp = instruction_at(code, offset);
+        let second = instruction_at(code, offset + 4);
+        let third = instruction_at(code, offset + 8);
+        if is_843419_sequence(adrp, second, third) {
+            move_instruction(offset + 8);
+        } else if offset + 16 <= range.end && !is_branch(third) {
+            let fourth = instruction_at(code, offset + 12);
+            if is_843419_sequence(adrp, second, fourth) {
+                move_instruction(offset + 12);
+            }
+        }
+        // Go to the other slot at the end of this page, or to the first slot of the next page.
+        if (section_address + offset) % PAGE_SIZE == PAGE_SIZE - 8 {
+            offset += 4;
+        } else {
+            offset += PAGE_SIZE - 4;
+        }
+    }
+}
+
+fn find_835769(code: &[u8], range: Range<u64>, move_instruction: &mut impl FnMut(u64)) {
+    let mut offset = range.start;
+    while offset + 8 <= range.end {
+        if is_load_store(instruction_at(code, offset))
+            && is_multiply_accumulate_64(instruction_at(code, offset + 4))
+        {
+            move_instruction(offset + 4);
+        }
+        offset += 4;
+    }
+}
+
+/// Moves the instruction at `instruction_offset` in `section` into a patch at `patch_offset`, which
+/// must be within `section`, and makes the patch branch back to the following instruction.
+pub(crate) fn write_patch(section: &mut [u8], instruction_offset: u64, patch_offset: u64) {
+    let instruction = instruction_at(section, instruction_offset);
+    write_instruction(section, patch_offset, instruction);
+    write_instruction(
+        section,
+        patch_offset + 4,
+        branch(patch_offset + 4, instruction_offset + 4),
+    );
+    write_instruction(
+        section,
+        instruction_offset,
+        branch(instruction_offset, patch_offset),
+    );
+}
+
+fn is_843419_sequence(adrp: u32, second: u32, last: u32) -> bool {
+    is_adrp(adrp)
+        && is_load_store(second)
+        && is_load_store_unsigned_immediate(last)
+        && base_register(last) == adrp & 0x1f
+}
+
+fn is_adrp(instruction: u32) -> bool {
+    instruction & 0x9f00_0000 == 0x9000_0000
+}
+
+/// Returns whether the instruction is in the loads and stores encoding group, which includes SIMD
+/// and floating-point loads and stores.
+fn is_load_store(instruction: u32) -> bool {
+    instruction & 0x0a00_0000 == 0x0800_0000
+}
+
+fn is_load_store_unsigned_immediate(instruction: u32) -> bool {
+    instruction & 0x3b00_0000 == 0x3900_0000
+}
+
+fn base_register(instruction: u32) -> u32 {
+    (instruction >> 5) & 0x1f
+}
+
+fn is_branch(instruction: u32) -> bool {
+    // b, bl
+    instruction & 0x7c00_0000 == 0x1400_0000
+        // b.cond
+        || instruction & 0xff00_0010 == 0x5400_0000
+        // cbz, cbnz, tbz, tbnz
+        || instruction & 0x7c00_0000 == 0x3400_0000
+        // br, blr, ret and other branches to registers
+        || instruction & 0xfe00_0000 == 0xd600_0000
+}
+
+/// Returns whether the instruction is a 64-bit multiply-accumulate, i.e. madd, msub, smaddl,
+/// smsubl, umaddl or umsubl. mul and friends are encoded as these with xzr as the accumulator, so
+/// aren't affected.
+fn is_multiply_accumulate_64(instruction: u32) -> bool {
+    instruction & 0xff00_0000 == 0x9b00_0000
+        && matches!((instruction >> 21) & 0x7, 0 | 1 | 5)
+        && (instruction >> 10) & 0x1f != 0x1f
+}
+
+/// Returns a `b` instruction at `from` that branches to `to`.
+fn branch(from: u64, to: u64) -> u32 {
+    let delta = to.wrapping_sub(from) as i64 >> 2;
+    0x1400_0000 | (delta as u32 & 0x03ff_ffff)
+}
+
+fn instruction_at(code: &[u8], offset: u64) -> u32 {
+    let offset = offset as usize;
+    u32::from_le_bytes(code[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_instruction(code: &mut [u8], offset: u64, instruction: u32) {
+    let offset = offset as usize;
+    code[offset..offset + 4].copy_from_slice(&instruction.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADRP_X0: u32 = 0x9000_0000;
+    const LDR_X1_X2: u32 = 0xf940_0041;
+    const LDR_X3_X0_8: u32 = 0xf940_0403;
+    const MOV_X4_X5: u32 = 0xaa05_03e4;
+    const MADD_X0_X1_X2_X3: u32 = 0x9b02_0c20;
+    const MUL_X0_X1_X2: u32 = 0x9b02_7c20;
+    const NOP: u32 = 0xd503_201f;
+
+    fn code(instructions: &[u32]) -> Vec<u8> {
+        instructions.iter().flat_map(|i| i.to_le_bytes()).collect()
+    }
+
+    fn find(
+        fix_843419: bool,
+        fix_835769: bool,
+        instructions: &[u32],
+        section_address: u64,
+    ) -> Vec<u64> {
+        let fixes = ErrataFixes {
+            erratum_843419: fix_843419,
+            erratum_835769: fix_835769,
+        };
+        let code = code(instructions);
+        let mut found = Vec::new();
+        find_instructions_to_move(
+            fixes,
+            &code,
+            std::slice::from_ref(&(0..code.len() as u64)),
+            section_address,
+            |offset| found.push(offset),
+        );
+        found
+    }
+
+    #[test]
+    fn test_843419() {
+        let three = [ADRP_X0, LDR_X1_X2, LDR_X3_X0_8, NOP];
+        assert_eq!(find(true, false, &three, 0x1ff8), vec![8]);
+        assert_eq!(find(true, false, &three, 0x1ffc), vec![8]);
+        assert_eq!(find(true, false, &three, 0x1ff0), Vec::<u64>::new());
+        assert_eq!(find(true, false, &three, 0x1000), Vec::<u64>::new());
+        assert_eq!(find(false, false, &three, 0x1ff8), Vec::<u64>::new());
+
+        let four = [NOP, ADRP_X0, LDR_X1_X2, MOV_X4_X5, LDR_X3_X0_8];
+        assert_eq!(find(true, false, &four, 0x1ff8), vec![16]);
+        let four_with_branch = [NOP, ADRP_X0, LDR_X1_X2, 0x1400_0002, LDR_X3_X0_8];
+        assert_eq!(
+            find(true, false, &four_with_branch, 0x1ff8),
+            Vec::<u64>::new()
+        );
+    }
+
+    #[test]
+    fn test_835769() {
+        assert_eq!(
+            find(false, true, &[LDR_X1_X2, MADD_X0_X1_X2_X3], 0),
+            vec![4]
+        );
+        assert_eq!(
+            find(false, true, &[LDR_X1_X2, MUL_X0_X1_X2], 0),
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            find(false, true, &[MOV_X4_X5, MADD_X0_X1_X2_X3], 0),
+            Vec::<u64>::new()
+        );
+    }
+
+    #[test]
+    fn test_write_patch() {
+        let mut section = code(&[ADRP_X0, LDR_X1_X2, LDR_X3_X0_8, NOP, 0, 0]);
+        write_patch(&mut section, 8, 16);
+        assert_eq!(
+            section,
+            code(&[
+                ADRP_X0,
+                LDR_X1_X2,
+                0x1400_0002, // b 16
+                NOP,
+                LDR_X3_X0_8,
+                0x17ff_fffe, // b 12
+            ])
+        );
+    }
+}
@@ -39,6 +39,7 @@ pub(crate) const PAGE: Alignment = Alignment { exponent: 12 };
 pub(crate) const PLT: Alignment = Alignment { exponent: 4 };
 
 pub(crate) const VERSION_R: Alignment = Alignment { exponent: 3 };
+pub(crate) const VERSION_D: Alignment = Alignment { exponent: 3 };
 pub(crate) const VERSYM: Alignment = Alignment { exponent: 1 };
 
 pub(crate) const USIZE: Alignment = Alignment { exponent: 3 };
@@ -46,6 +47,8 @@ pub(crate) const USIZE: Alignment = Alignment { exponent: 3 };
 pub(crate) const EH_FRAME_HDR: Alignment = Alignment { exponent: 2 };
 pub(crate) const NOTE_GNU_PROPERTY: Alignment = Alignment { exponent: 3 };
 pub(crate) const NOTE_GNU_BUILD_ID: Alignment = Alignment { exponent: 2 };
+pub(crate) const GNU_BUILD_ATTRIBUTES: Alignment = Alignment { exponent: 2 };
+pub(crate) const GNU_DEBUGLINK: Alignment = Alignment { exponent: 2 };
 
 impl Alignment {
     pub(crate) fn new(raw: u64) -> Result<Self> {
@@ -61,6 +64,17 @@ impl Alignment {
         })
     }
 
+    /// Returns the alignment for pages of the supplied size. Unlike `new`, this permits alignments
+    /// that are larger than we support for sections.
+    pub(crate) fn page(size: u64) -> Result<Self> {
+        if !size.is_power_of_two() {
+            bail!("Page size 0x{size:x} is not a power of two");
+        }
+        Ok(Alignment {
+            exponent: size.trailing_zeros() as u8,
+        })
+    }
+
     pub(crate) fn value(self) -> u64 {
         1 << self.exponent
     }
@@ -95,6 +109,12 @@ impl Alignment {
         }
         mem_offset + adjustment
     }
+
+    /// Returns the smallest value that is >= `file_offset` and has the same modulo as
+    /// `mem_offset`.
+    pub(crate) fn align_file_offset_modulo(self, mem_offset: u64, file_offset: u64) -> u64 {
+        file_offset + (mem_offset.wrapping_sub(file_offset) & self.mask())
+    }
 }
 
 impl Display for Alignment {
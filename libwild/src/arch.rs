@@ -3,12 +3,14 @@
 use crate::args::OutputKind;
 use crate::error::Result;
 use crate::resolution::ValueFlags;
+use crate::thunks::ThunkInfo;
 use anyhow::bail;
 use linker_utils::elf::DynamicRelocationKind;
 use linker_utils::elf::RelocationKindInfo;
 use linker_utils::elf::SectionFlags;
 use linker_utils::relaxation::RelocationModifier;
 use std::borrow::Cow;
+use std::ops::Range;
 use std::str::FromStr;
 
 pub(crate) trait Arch {
@@ -21,18 +23,101 @@ pub(crate) trait Arch {
     fn get_dynamic_relocation_type(relocation: DynamicRelocationKind) -> u32;
 
     // Write PLT entry for the architecture.
-    fn write_plt_entry(plt_entry: &mut [u8], got_address: u64, plt_address: u64) -> Result;
+    fn write_plt_entry(
+        plt_entry: &mut [u8],
+        got_address: u64,
+        plt_address: u64,
+        got_base: u64,
+        features: PltFeatures,
+    ) -> Result;
+
+    // Write a lazy-binding PLT entry. Until the dynamic loader binds the function, the GOT entry
+    // points back into the PLT entry, which then passes `rela_plt_index` to the PLT header. Returns
+    // the address that the GOT entry should initially contain. If the PLT uses .plt.sec, then the
+    // entry written here is only reached via the GOT and the entry in .plt.sec is written by
+    // `write_plt_entry`.
+    fn write_lazy_plt_entry(
+        _plt_entry: &mut [u8],
+        _got_address: u64,
+        _plt_address: u64,
+        _plt_header_address: u64,
+        _rela_plt_index: u64,
+        _features: PltFeatures,
+    ) -> Result<u64> {
+        bail!("Lazy binding is not supported on this architecture");
+    }
+
+    // Write the PLT header that lazy-binding PLT entries jump to. `got_address` is the address of
+    // the GOT entries reserved for the dynamic loader.
+    fn write_plt_header(
+        _plt_header: &mut [u8],
+        _got_address: u64,
+        _plt_header_address: u64,
+    ) -> Result {
+        bail!("Lazy binding is not supported on this architecture");
+    }
 
     // Make architecture-specific parsing of the relocation types.
     fn relocation_from_raw(r_type: u32) -> Result<RelocationKindInfo>;
 
     // Get string representation of a relocation specific for the architecture.
     fn rel_type_to_string(r_type: u32) -> Cow<'static, str>;
+
+    // Get the flags for the output's ELF header from the flags of each input object.
+    fn merge_e_flags(input_flags: impl Iterator<Item = u32>) -> Result<u32>;
+
+    // Get information about range-extension thunks, or None if the architecture doesn't use them.
+    fn thunk_info() -> Option<ThunkInfo> {
+        None
+    }
+
+    // Get how far a branch using the supplied relocation type can reach in either direction, or
+    // None if the relocation isn't a branch that can be redirected via a range-extension thunk.
+    fn branch_range(_r_type: u32) -> Option<u64> {
+        None
+    }
+
+    // Get how far a branch using the supplied relocation type can reach in either direction, for
+    // branches that we don't redirect via thunks, so that we can report targets that are out of
+    // range rather than writing a truncated offset.
+    fn fixed_branch_range(_r_type: u32) -> Option<u64> {
+        None
+    }
+
+    // Write a range-extension thunk at `thunk_address` that jumps to `target_address`.
+    fn write_thunk(_thunk: &mut [u8], _thunk_address: u64, _target_address: u64) -> Result {
+        bail!("Range-extension thunks are not supported on this architecture");
+    }
+
+    // Get the range of values that a relocation referencing the GOT can hold, for relocations that
+    // write into an instruction field that would otherwise silently drop the bits of an offset
+    // that's too large. Returns None if the relocation doesn't need checking.
+    fn got_relocation_range(_r_type: u32) -> Option<Range<i64>> {
+        None
+    }
+}
+
+/// Control-flow protection features that PLT entries need to support. These are determined from
+/// the merged GNU property notes and from command-line flags.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PltFeatures {
+    /// Start each PLT entry with a BTI landing pad (AArch64).
+    pub(crate) bti: bool,
+
+    /// Authenticate the GOT entry before branching to it (AArch64).
+    pub(crate) pac: bool,
+
+    /// Start each PLT entry with an endbr64 (x86-64). With lazy binding, this requires the use of
+    /// .plt.sec.
+    pub(crate) ibt: bool,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub(crate) enum Architecture {
     X86_64,
     AArch64,
+    RiscV64,
+    PowerPc64,
 }
 
 impl FromStr for Architecture {
@@ -42,11 +127,269 @@ impl FromStr for Architecture {
         match s {
             "elf_x86_64" => Ok(Architecture::X86_64),
             "aarch64elf" | "aarch64linux" => Ok(Architecture::AArch64),
+            "elf64lriscv" => Ok(Architecture::RiscV64),
+            "elf64lppc" => Ok(Architecture::PowerPc64),
+            "elf_i386" | "elf32_x86_64" | "armelf" | "armelf_linux_eabi" | "armelfb"
+            | "armelfb_linux_eabi" => {
+                bail!("-m {s} requires 32 bit ELF output, which is not yet supported")
+            }
             _ => bail!("-m {s} is not yet supported"),
         }
     }
 }
 
+impl std::fmt::Display for Architecture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Architecture::X86_64 => "x86_64",
+            Architecture::AArch64 => "AArch64",
+            Architecture::RiscV64 => "RISC-V",
+            Architecture::PowerPc64 => "PowerPC64",
+        })
+    }
+}
+
+impl Architecture {
+    /// Returns the architecture of objects with the supplied ELF `e_machine`, if it's one that we
+    /// can link.
+    pub(crate) fn from_e_machine(e_machine: u16) -> Option<Self> {
+        match e_machine {
+            object::elf::EM_X86_64 => Some(Architecture::X86_64),
+            object::elf::EM_AARCH64 => Some(Architecture::AArch64),
+            object::elf::EM_RISCV => Some(Architecture::RiscV64),
+            object::elf::EM_PPC64 => Some(Architecture::PowerPc64),
+            _ => None,
+        }
+    }
+
+    /// Returns how far the dynamic thread pointer is from the start of a module's TLS block. RISC-V
+    /// and PowerPC64 bias it so that more of the block can be reached with a signed offset.
+    pub(crate) fn tls_dtv_offset(self) -> u64 {
+        match self {
+            Architecture::X86_64 | Architecture::AArch64 => 0,
+            Architecture::RiscV64 => 0x800,
+            Architecture::PowerPc64 => 0x8000,
+        }
+    }
+
+    /// Returns how far the base address used by GOT-relative relocations is from the start of the
+    /// GOT. On PowerPC64, this base is the TOC pointer.
+    pub(crate) fn got_base_offset(self) -> u64 {
+        match self {
+            Architecture::PowerPc64 => linker_utils::ppc64::TOC_BASE_OFFSET,
+            Architecture::X86_64 | Architecture::AArch64 | Architecture::RiscV64 => 0,
+        }
+    }
+}
+
+/// Evaluates `$body` with `$A` naming the type that implements `Arch` for the architecture `$arch`.
+macro_rules! with_arch {
+    ($arch:expr, $A:ident => $body:expr) => {
+        match $arch {
+            Architecture::X86_64 => {
+                type $A = crate::x86_64::X86_64;
+                $body
+            }
+            Architecture::AArch64 => {
+                type $A = crate::aarch64::AArch64;
+                $body
+            }
+            Architecture::RiscV64 => {
+                type $A = crate::riscv64::RiscV64;
+                $body
+            }
+            Architecture::PowerPc64 => {
+                type $A = crate::ppc64::PowerPc64;
+                $body
+            }
+        }
+    };
+}
+
+/// The architecture is picked at runtime, so rather than the linker being generic over `Arch`,
+/// these methods call through to the `Arch` implementation for the architecture in question. See
+/// `Arch` for what each of them does.
+impl Architecture {
+    pub(crate) fn elf_header_arch_magic(self) -> u16 {
+        with_arch!(self, A => A::elf_header_arch_magic())
+    }
+
+    pub(crate) fn get_dynamic_relocation_type(self, relocation: DynamicRelocationKind) -> u32 {
+        with_arch!(self, A => A::get_dynamic_relocation_type(relocation))
+    }
+
+    pub(crate) fn write_plt_entry(
+        self,
+        plt_entry: &mut [u8],
+        got_address: u64,
+        plt_address: u64,
+        got_base: u64,
+        features: PltFeatures,
+    ) -> Result {
+        with_arch!(self, A => A::write_plt_entry(
+            plt_entry,
+            got_address,
+            plt_address,
+            got_base,
+            features,
+        ))
+    }
+
+    pub(crate) fn write_lazy_plt_entry(
+        self,
+        plt_entry: &mut [u8],
+        got_address: u64,
+        plt_address: u64,
+        plt_header_address: u64,
+        rela_plt_index: u64,
+        features: PltFeatures,
+    ) -> Result<u64> {
+        with_arch!(self, A => A::write_lazy_plt_entry(
+            plt_entry,
+            got_address,
+            plt_address,
+            plt_header_address,
+            rela_plt_index,
+            features,
+        ))
+    }
+
+    pub(crate) fn write_plt_header(
+        self,
+        plt_header: &mut [u8],
+        got_address: u64,
+        plt_header_address: u64,
+    ) -> Result {
+        with_arch!(self, A => A::write_plt_header(plt_header, got_address, plt_header_address))
+    }
+
+    pub(crate) fn relocation_from_raw(self, r_type: u32) -> Result<RelocationKindInfo> {
+        with_arch!(self, A => A::relocation_from_raw(r_type))
+    }
+
+    pub(crate) fn rel_type_to_string(self, r_type: u32) -> Cow<'static, str> {
+        with_arch!(self, A => A::rel_type_to_string(r_type))
+    }
+
+    pub(crate) fn merge_e_flags(self, input_flags: impl Iterator<Item = u32>) -> Result<u32> {
+        with_arch!(self, A => A::merge_e_flags(input_flags))
+    }
+
+    pub(crate) fn thunk_info(self) -> Option<ThunkInfo> {
+        with_arch!(self, A => A::thunk_info())
+    }
+
+    pub(crate) fn branch_range(self, r_type: u32) -> Option<u64> {
+        with_arch!(self, A => A::branch_range(r_type))
+    }
+
+    pub(crate) fn fixed_branch_range(self, r_type: u32) -> Option<u64> {
+        with_arch!(self, A => A::fixed_branch_range(r_type))
+    }
+
+    pub(crate) fn write_thunk(
+        self,
+        thunk: &mut [u8],
+        thunk_address: u64,
+        target_address: u64,
+    ) -> Result {
+        with_arch!(self, A => A::write_thunk(thunk, thunk_address, target_address))
+    }
+
+    pub(crate) fn got_relocation_range(self, r_type: u32) -> Option<Range<i64>> {
+        with_arch!(self, A => A::got_relocation_range(r_type))
+    }
+
+    /// Tries to create a relaxation for the relocation of the specified kind. See
+    /// `Relaxation::new`.
+    pub(crate) fn new_relaxation(
+        self,
+        relocation_kind: u32,
+        section_bytes: &[u8],
+        offset_in_section: u64,
+        value_flags: ValueFlags,
+        output_kind: OutputKind,
+        section_flags: SectionFlags,
+    ) -> Option<ArchRelaxation> {
+        with_arch!(self, A => <A as Arch>::Relaxation::new(
+            relocation_kind,
+            section_bytes,
+            offset_in_section,
+            value_flags,
+            output_kind,
+            section_flags,
+        )
+        .map(ArchRelaxation::from))
+    }
+}
+
+/// A relaxation for whichever architecture we're linking for.
+#[derive(Debug)]
+pub(crate) enum ArchRelaxation {
+    X86_64(crate::x86_64::Relaxation),
+    AArch64(crate::aarch64::Relaxation),
+    RiscV64(crate::riscv64::Relaxation),
+    PowerPc64(crate::ppc64::Relaxation),
+}
+
+macro_rules! with_relaxation {
+    ($relaxation:expr, $r:ident => $body:expr) => {
+        match $relaxation {
+            ArchRelaxation::X86_64($r) => $body,
+            ArchRelaxation::AArch64($r) => $body,
+            ArchRelaxation::RiscV64($r) => $body,
+            ArchRelaxation::PowerPc64($r) => $body,
+        }
+    };
+}
+
+impl ArchRelaxation {
+    pub(crate) fn apply(
+        &self,
+        section_bytes: &mut [u8],
+        offset_in_section: &mut u64,
+        addend: &mut i64,
+    ) {
+        with_relaxation!(self, r => r.apply(section_bytes, offset_in_section, addend));
+    }
+
+    pub(crate) fn rel_info(&self) -> RelocationKindInfo {
+        with_relaxation!(self, r => r.rel_info())
+    }
+
+    pub(crate) fn debug_kind(&self) -> &dyn std::fmt::Debug {
+        with_relaxation!(self, r => r.debug_kind())
+    }
+
+    pub(crate) fn next_modifier(&self) -> RelocationModifier {
+        with_relaxation!(self, r => r.next_modifier())
+    }
+}
+
+impl From<crate::x86_64::Relaxation> for ArchRelaxation {
+    fn from(relaxation: crate::x86_64::Relaxation) -> Self {
+        ArchRelaxation::X86_64(relaxation)
+    }
+}
+
+impl From<crate::aarch64::Relaxation> for ArchRelaxation {
+    fn from(relaxation: crate::aarch64::Relaxation) -> Self {
+        ArchRelaxation::AArch64(relaxation)
+    }
+}
+
+impl From<crate::riscv64::Relaxation> for ArchRelaxation {
+    fn from(relaxation: crate::riscv64::Relaxation) -> Self {
+        ArchRelaxation::RiscV64(relaxation)
+    }
+}
+
+impl From<crate::ppc64::Relaxation> for ArchRelaxation {
+    fn from(relaxation: crate::ppc64::Relaxation) -> Self {
+        ArchRelaxation::PowerPc64(relaxation)
+    }
+}
+
 pub(crate) trait Relaxation {
     /// Tries to create a relaxation for the relocation of the specified kind, to be applied at the
     /// specified offset in the supplied section.
@@ -65,7 +408,7 @@ pub(crate) trait Relaxation {
 
     fn rel_info(&self) -> RelocationKindInfo;
 
-    fn debug_kind(&self) -> impl std::fmt::Debug;
+    fn debug_kind(&self) -> &dyn std::fmt::Debug;
 
     fn next_modifier(&self) -> RelocationModifier;
 }
@@ -0,0 +1,50 @@
+//! Merging of the architecture attribute sections that record which ISA extensions and ABI
+//! variants each object was compiled for. On RISC-V these are in `.riscv.attributes`. Disassemblers
+//! and debuggers use the attributes of the output to decide which instructions it may contain, so
+//! we can't just keep those of the first input. Instead we combine the attributes of every object
+//! that we load, erroring if any of them are incompatible, and write a single section.
+//!
+//! The format and the rules for combining each attribute are in `linker_utils::riscv_attributes`.
+
+use crate::arch::Architecture;
+use crate::elf::File;
+use crate::error::Result;
+use crate::input_data::InputRef;
+use anyhow::anyhow;
+use linker_utils::elf::secnames::RISCV_ATTRIBUTES_SECTION_NAME;
+use linker_utils::riscv_attributes::Attributes;
+
+/// Returns the contents of the attributes section of `object`, or an empty slice if it doesn't
+/// have one or if `arch` doesn't use them.
+pub(crate) fn section_data<'data>(object: &File<'data>, arch: Architecture) -> Result<&'data [u8]> {
+    if arch != Architecture::RiscV64 {
+        return Ok(&[]);
+    }
+    for section in object.sections.iter() {
+        if object.section_name(section)? == RISCV_ATTRIBUTES_SECTION_NAME {
+            return object.raw_section_data(section);
+        }
+    }
+    Ok(&[])
+}
+
+/// Merges the attribute sections of `inputs`, returning the contents of the section to write to
+/// the output. This is empty if none of the inputs had any attributes.
+pub(crate) fn merge<'a, 'data: 'a>(
+    inputs: impl Iterator<Item = (&'a InputRef<'data>, &'data [u8])>,
+) -> Result<Vec<u8>> {
+    let parsed = inputs
+        .filter(|(_, data)| !data.is_empty())
+        .map(|(input, data)| {
+            let attributes = Attributes::parse(data)
+                .map_err(|error| anyhow!("Failed to parse attributes of `{input}`: {error}"))?;
+            Ok((input, attributes))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let merged = Attributes::merge(parsed.iter().map(|(input, attributes)| (input, attributes)))
+        .map_err(|error| anyhow!("{error}"))?;
+    if merged.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(merged.to_bytes())
+}
@@ -115,7 +115,8 @@ impl<'data> ArchiveIterator<'data> {
         let ident = ident.trim();
         let entry_data = &self.data[..size];
         let entry = match ident {
-            "/" => ArchiveEntry::Symbols(SymbolTable { data: entry_data }),
+            // Archives whose offsets don't fit in 32 bits use a symbol table with 64 bit offsets.
+            "/" | "/SYM64/" => ArchiveEntry::Symbols(SymbolTable { data: entry_data }),
             "//" => ArchiveEntry::Filenames(ExtendedFilenames { data: entry_data }),
             _ => ArchiveEntry::Regular(ArchiveContent {
                 ident,
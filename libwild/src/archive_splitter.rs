@@ -26,10 +26,12 @@ pub fn split_archives<'data>(input_data: &'data InputData) -> Result<Vec<InputBy
                 FileKind::Archive => {
                     let mut extended_filenames = None;
                     let mut outputs = Vec::new();
+                    let mut has_symbol_table = false;
                     for entry in ArchiveIterator::from_archive_bytes(f.data())? {
                         let entry = entry?;
                         match entry {
                             ArchiveEntry::Symbols(_) => {
+                                has_symbol_table = true;
                                 // We used to read the symbol table from the archive, but when you're linking
                                 // lots of archives and discarding very few, it turns out it's faster to just
                                 // ignore the symbol table and eagerly read the objects.
@@ -52,6 +54,15 @@ pub fn split_archives<'data>(input_data: &'data InputData) -> Result<Vec<InputBy
                             }
                         }
                     }
+                    if !has_symbol_table && !outputs.is_empty() {
+                        // We don't need the symbol table since we read all the members anyway,
+                        // but other linkers will refuse to use the archive, so it's worth letting
+                        // the user know.
+                        crate::error::warning(&format!(
+                            "Archive `{}` has no symbol index. Run ranlib on it to add one",
+                            f.filename.display()
+                        ));
+                    }
                     Ok(outputs)
                 }
                 _ => Ok(vec![InputBytes {
@@ -0,0 +1,92 @@
+//! Optionally writes, for each input archive, how many members it contained and how many of those
+//! were extracted. To use this, pass `--print-archive-stats=/path/to/file.txt`. An archive with no
+//! extracted members contributes nothing to the link and can be removed from the command line.
+//!
+//! The output uses the same format as LLD, with columns separated by tabs:
+//!
+//! ```text
+//! members  extracted  archive
+//! 12       3          /usr/lib/x86_64-linux-gnu/libc.a
+//! ```
+
+use crate::args::Args;
+use crate::error::Result;
+use crate::grouping::Group;
+use crate::parsing::ParsedInput;
+use crate::resolution::ResolvedFile;
+use crate::resolution::ResolvedGroup;
+use anyhow::Context as _;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub(crate) fn maybe_write_archive_stats(
+    groups: &[Group],
+    resolved_groups: &[ResolvedGroup],
+    args: &Args,
+) -> Result {
+    let Some(stats_file) = args.print_archive_stats.as_ref() else {
+        return Ok(());
+    };
+    write_archive_stats(groups, resolved_groups, stats_file).with_context(|| {
+        format!(
+            "Failed to write archive stats to `{}`",
+            stats_file.display()
+        )
+    })
+}
+
+struct ArchiveStats<'data> {
+    path: &'data Path,
+    members: u64,
+    extracted: u64,
+}
+
+fn write_archive_stats(
+    groups: &[Group],
+    resolved_groups: &[ResolvedGroup],
+    stats_file: &Path,
+) -> Result {
+    use std::io::Write as _;
+
+    // Archives are reported in the order in which they were first encountered on the command line.
+    let mut archives: Vec<ArchiveStats> = Vec::new();
+    let mut index_by_path = HashMap::new();
+    for (group, resolved_group) in groups.iter().zip(resolved_groups) {
+        for (file, resolved_file) in group.files.iter().zip(&resolved_group.files) {
+            let ParsedInput::Object(obj) = file else {
+                continue;
+            };
+            if obj.input.entry.is_none() {
+                continue;
+            }
+            let path = obj.input.file.filename.as_path();
+            let index = *index_by_path.entry(path).or_insert_with(|| {
+                archives.push(ArchiveStats {
+                    path,
+                    members: 0,
+                    extracted: 0,
+                });
+                archives.len() - 1
+            });
+            let stats = &mut archives[index];
+            stats.members += 1;
+            if matches!(resolved_file, ResolvedFile::Object(_)) {
+                stats.extracted += 1;
+            }
+        }
+    }
+
+    let mut out = std::io::BufWriter::new(std::fs::File::create(stats_file)?);
+    writeln!(out, "members\textracted\tarchive")?;
+    for stats in &archives {
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            stats.members,
+            stats.extracted,
+            stats.path.display()
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -12,22 +12,28 @@
 //! Basically, we need to be able to parse arguments in the same way as the other linkers on the
 //! platform that we're targeting.
 
+use crate::alignment::Alignment;
 use crate::arch::Architecture;
 use crate::error::Result;
 use crate::input_data::FileId;
+use crate::linker_script::SymbolAssignment;
 use crate::save_dir::SaveDir;
 use anyhow::bail;
 use anyhow::ensure;
 use anyhow::Context as _;
+use linker_utils::elf::RelocationKind;
 use std::num::NonZeroUsize;
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::atomic::AtomicI64;
 use std::sync::Arc;
+use std::sync::OnceLock;
 
 pub(crate) struct Args {
-    pub(crate) arch: Architecture,
+    /// The architecture that we're linking for. Set by `-m`, otherwise filled in from the first
+    /// ELF input once we've opened our inputs. Read it via `arch()`.
+    pub(crate) arch: OnceLock<Architecture>,
     pub(crate) lib_search_path: Vec<Box<Path>>,
     pub(crate) inputs: Vec<Input>,
     pub(crate) output: Arc<Path>,
@@ -35,6 +41,7 @@ pub(crate) struct Args {
     pub(crate) num_threads: NonZeroUsize,
     pub(crate) strip_all: bool,
     pub(crate) strip_debug: bool,
+    pub(crate) discard_locals: DiscardLocals,
     pub(crate) prepopulate_maps: bool,
     pub(crate) sym_info: Option<String>,
     pub(crate) merge_strings: bool,
@@ -42,6 +49,18 @@ pub(crate) struct Args {
     pub(crate) time_phases: bool,
     pub(crate) validate_output: bool,
     pub(crate) version_script_path: Option<PathBuf>,
+
+    /// Whether to give exported symbols that don't otherwise get a version one named after our
+    /// soname. Set by `--default-symver`.
+    pub(crate) default_symver: bool,
+
+    /// Whether to give symbols that we import from unversioned shared objects a version named
+    /// after the soname of the object. Set by `--default-imported-symver`.
+    pub(crate) default_imported_symver: bool,
+
+    /// Whether the version script may name symbols that aren't defined. Cleared by
+    /// `--no-undefined-version`.
+    pub(crate) undefined_version: bool,
     pub(crate) debug_address: Option<u64>,
     pub(crate) write_layout: bool,
     pub(crate) should_write_eh_frame_hdr: bool,
@@ -53,8 +72,54 @@ pub(crate) struct Args {
     pub(crate) should_fork: bool,
     pub(crate) build_id: BuildIdOption,
     pub(crate) file_write_mode: FileWriteMode,
+    pub(crate) mmap_output: MmapOutput,
     pub(crate) no_undefined: bool,
 
+    /// Symbols that we don't report if they're undefined. Set by `--ignore-unresolved-symbol`.
+    pub(crate) ignore_unresolved_symbols: Vec<String>,
+
+    /// Symbols to treat as referenced, so that archive members that define them get loaded. Set by
+    /// `-u` / `--undefined`.
+    pub(crate) undefined: Vec<String>,
+
+    /// Like `undefined`, but it's an error if these don't end up defined. Set by
+    /// `--require-defined`.
+    pub(crate) require_defined: Vec<String>,
+
+    /// If set, we report references to glibc symbol versions newer than this.
+    pub(crate) max_glibc_version: Option<MaxGlibcVersion>,
+
+    /// Input paths to replace, checked in order before any input files are opened.
+    pub(crate) remap_inputs: Vec<RemapInput>,
+
+    /// Archives whose symbols we don't export. Set by `--exclude-libs`.
+    pub(crate) exclude_libs: ExcludeLibs,
+
+    /// The order in which common symbols are placed in `.bss`.
+    pub(crate) sort_common: SortCommon,
+
+    /// Whether to warn when a common symbol is merged with, or overridden by, another symbol with
+    /// the same name. Set by `--warn-common`.
+    pub(crate) warn_common: bool,
+
+    /// Input sections that get an output section each rather than sharing one with other input
+    /// sections of the same name.
+    pub(crate) unique_sections: UniqueSections,
+
+    /// How much extra stack space split-stack functions request when they call functions that
+    /// aren't split-stack.
+    pub(crate) split_stack_adjust_size: u32,
+
+    /// Whether to add a string identifying the linker to the .comment section.
+    pub(crate) enable_linker_version: bool,
+
+    /// Whether to omit the section header table, along with all non-allocated sections, which
+    /// nothing could find without it. Set by `-z nosectionheader`.
+    pub(crate) strip_section_headers: bool,
+
+    /// A peak memory budget in bytes. See `memory_budget` for what we do with it.
+    pub(crate) max_memory: Option<u64>,
+
     /// If set, GC stats will be written to the specified filename.
     pub(crate) write_gc_stats: Option<PathBuf>,
 
@@ -64,8 +129,132 @@ pub(crate) struct Args {
 
     pub(crate) verbose_gc_stats: bool,
 
+    /// If set, write how many members of each archive were extracted to this file.
+    pub(crate) print_archive_stats: Option<PathBuf>,
+
+    /// If set, write an interface stub describing the symbols exported by the shared object that
+    /// we're linking to this file.
+    pub(crate) interface_stub: Option<PathBuf>,
+
+    /// If set, write a stub shared object containing just the symbols exported by the shared
+    /// object that we're linking to this file.
+    pub(crate) interface_stub_so: Option<PathBuf>,
+
+    /// If set, write a report of the worst-case stack usage of each function to this file.
+    pub(crate) stack_report: Option<PathBuf>,
+
+    /// If set, write a breakdown of the size of the output by section, input file and crate to
+    /// this file.
+    pub(crate) size_report: Option<PathBuf>,
+
+    pub(crate) size_report_format: SizeReportFormat,
+
+    /// The format of the output file. Set with `--oformat`.
+    pub(crate) output_format: OutputFormat,
+
+    /// If set, write debug info to this file rather than to the output, which instead gets a
+    /// `.gnu_debuglink` section that refers to it.
+    pub(crate) separate_debug_file: Option<PathBuf>,
+
+    /// Whether to copy the relocations from our inputs into the output, for use by post-link
+    /// optimisers. Set by `--emit-relocs` or `-q`.
+    pub(crate) emit_relocs: bool,
+
+    /// How to lay out the loadable parts of the output. Set by `-n` and `-N`.
+    pub(crate) magic: Magic,
+
+    /// The alignment of loadable segments. Set by `-z max-page-size`.
+    pub(crate) max_page_size: Alignment,
+
+    /// The page size that we pad the end of the RELRO segment to. Set by `-z common-page-size`.
+    pub(crate) common_page_size: Alignment,
+
+    /// Whether to make data that's only written by dynamic relocations read-only once they've been
+    /// applied, by emitting a `PT_GNU_RELRO` segment. Set by `-z relro` and `-z norelro`.
+    pub(crate) relro: bool,
+
+    /// Which loadable segments start on a page boundary in the file.
+    pub(crate) separate_segments: SeparateSegments,
+
+    /// The address at which to start the output, overriding the default. Set by `--image-base` or
+    /// `-Ttext-segment`.
+    pub(crate) image_base: Option<u64>,
+
+    /// Output sections to place at fixed addresses, by name. Set by `--section-start`, `-Ttext`,
+    /// `-Tdata` and `-Tbss`.
+    pub(crate) section_starts: Vec<(String, u64)>,
+
+    /// Symbols defined with `--defsym`, in the order given.
+    pub(crate) defsyms: Vec<SymbolAssignment>,
+
+    /// If set, print which input files were needed by which other input files and why.
+    pub(crate) print_dependencies: bool,
+
+    /// A raw profile, e.g. from `perf script`, used to group hot functions together.
+    pub(crate) profile_path: Option<PathBuf>,
+
+    /// If set, perform the whole link, but don't write the output file.
+    pub(crate) dry_run: bool,
+
+    /// If set, print which symbols needed GOT and/or PLT entries and why.
+    pub(crate) print_got_usage: bool,
+
+    /// If set, print how many relocations of each kind were relaxed and which weren't and why.
+    pub(crate) print_relaxations: bool,
+
+    /// The maximum number of errors to report. `None` means unlimited.
+    pub(crate) error_limit: Option<usize>,
+
     pub(crate) print_allocations: Option<FileId>,
     pub(crate) execstack: bool,
+    pub(crate) memory_seal: bool,
+
+    /// AArch64 only. Mark the output as BTI-compatible even if some inputs aren't.
+    pub(crate) force_bti: bool,
+
+    /// Whether -z pack-relative-relocs was given. We don't write RELR, so this only lets us reject
+    /// inputs that would need .relr.auth.dyn.
+    pub(crate) pack_relative_relocs: bool,
+
+    /// AArch64 only. Use PLT entries that authenticate the GOT entry before branching to it.
+    pub(crate) pac_plt: bool,
+
+    /// Set by `-z lazy`. Bind calls to functions in shared objects the first time they're made rather
+    /// than when the output is loaded. See `lazy_binding`.
+    pub(crate) lazy: bool,
+
+    /// x86-64 only. Set by `-z ibtplt` and `-z noibtplt` to override whether PLT entries start with
+    /// an endbr64. By default, they do only if the output is marked as IBT-compatible.
+    pub(crate) ibt_plt: Option<bool>,
+
+    /// x86-64 only. Mark the output as IBT or shadow stack compatible even if some inputs aren't.
+    /// Set by `-z force-ibt` and `-z shstk`.
+    pub(crate) force_ibt: bool,
+    pub(crate) force_shstk: bool,
+
+    /// x86-64 only. What to do about inputs that aren't marked as IBT and shadow stack compatible.
+    /// Set by `-z cet-report`.
+    pub(crate) cet_report: CetReport,
+
+    /// x86-64 only. Mark the output as compatible with Linear Address Masking.
+    pub(crate) lam_u48: bool,
+    pub(crate) lam_u57: bool,
+
+    /// x86-64 only. If set, the value of GNU_PROPERTY_X86_ISA_1_NEEDED in the output, replacing
+    /// whatever the inputs declared. Set by `-z x86-64-v2` etc.
+    pub(crate) x86_isa_needed: Option<u32>,
+
+    /// AArch64 only. How the dynamic loader should enable memory tagging, if at all. See `memtag`.
+    pub(crate) memtag_mode: MemtagMode,
+    pub(crate) memtag_heap: bool,
+    pub(crate) memtag_stack: bool,
+
+    /// AArch64 only. Whether to work around Cortex-A53 errata. See `aarch64_errata`.
+    pub(crate) fix_cortex_a53_843419: bool,
+    pub(crate) fix_cortex_a53_835769: bool,
+
+    pub(crate) relax: RelaxOptions,
+
     pub(crate) verify_allocation_consistency: bool,
 
     output_kind: Option<OutputKind>,
@@ -73,6 +262,69 @@ pub(crate) struct Args {
     relocation_model: RelocationModel,
 }
 
+/// Which kinds of relaxation we're permitted to perform. Everything is enabled by default. Disabling
+/// relaxations is mostly useful for debugging codegen issues and for comparing our output with
+/// that of other linkers.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RelaxOptions {
+    /// Relaxations that avoid going via the GOT, e.g. `mov foo@GOTPCREL(%rip)` to `lea
+    /// foo(%rip)`. Disabled by `--no-relax-gotpcrelx`.
+    pub(crate) got: bool,
+
+    /// Relaxations of TLS access models, e.g. general dynamic to local exec. Disabled by
+    /// `--no-relax-tls`.
+    pub(crate) tls: bool,
+
+    /// Relaxations that make code smaller by deleting bytes, e.g. RISC-V `auipc+jalr` to `jal`.
+    /// Only disabled by `--no-relax`.
+    pub(crate) code: bool,
+
+    /// Relaxations of RISC-V accesses to data near `__global_pointer$` to use gp as their base,
+    /// which also deletes bytes, so needs `code` too. Disabled by `--no-relax-gp`.
+    pub(crate) gp: bool,
+}
+
+impl RelaxOptions {
+    pub(crate) const ALL: RelaxOptions = RelaxOptions {
+        got: true,
+        tls: true,
+        code: true,
+        gp: true,
+    };
+
+    pub(crate) const NONE: RelaxOptions = RelaxOptions {
+        got: false,
+        tls: false,
+        code: false,
+        gp: false,
+    };
+
+    /// Returns whether we may relax a relocation of the supplied kind. Changes that are needed for
+    /// correctness rather than performance, such as making references to ifuncs go via the PLT,
+    /// aren't affected by these options.
+    pub(crate) fn allows(self, kind: RelocationKind) -> bool {
+        match kind {
+            RelocationKind::Got | RelocationKind::GotRelative | RelocationKind::GotRelGotBase => {
+                self.got
+            }
+            RelocationKind::TlsGd
+            | RelocationKind::TlsGdGot
+            | RelocationKind::TlsGdGotBase
+            | RelocationKind::TlsLd
+            | RelocationKind::TlsLdGot
+            | RelocationKind::TlsLdGotBase
+            | RelocationKind::GotTpOff
+            | RelocationKind::GotTpOffGot
+            | RelocationKind::GotTpOffGotBase
+            | RelocationKind::TlsDesc
+            | RelocationKind::TlsDescGot
+            | RelocationKind::TlsDescGotBase
+            | RelocationKind::TlsDescCall => self.tls,
+            _ => true,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum BuildIdOption {
     None,
@@ -88,6 +340,9 @@ pub(crate) enum Action {
 
     /// Print the linker version.
     Version,
+
+    /// Print what we'd do with each relocation in an object. See `explain_reloc`.
+    ExplainReloc(crate::explain_reloc::ExplainRelocArgs),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -95,6 +350,10 @@ pub(crate) enum OutputKind {
     StaticExecutable(RelocationModel),
     DynamicExecutable(RelocationModel),
     SharedObject,
+
+    /// An object that can be used as input to a later link (`-r`). Sections are concatenated and
+    /// relocations are carried across into the output rather than being applied.
+    RelocatableObject,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -115,6 +374,229 @@ pub(crate) enum FileWriteMode {
     UpdateInPlace,
 }
 
+#[derive(Debug)]
+pub(crate) struct RemapInput {
+    /// A glob that is matched against the path of each input after library search.
+    pub(crate) pattern: String,
+
+    /// The path that we open instead.
+    pub(crate) replacement: PathBuf,
+}
+
+impl RemapInput {
+    fn new(pattern: &str, replacement: &str) -> Self {
+        Self {
+            pattern: pattern.to_owned(),
+            replacement: PathBuf::from(replacement),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    Elf,
+
+    /// Just the bytes of the loadable sections, as they'd appear in memory, with no headers. Used
+    /// for bootloaders and other bare-metal images. See `binary_output`.
+    Binary,
+}
+
+/// How we lay out the loadable parts of the output. The names come from the a.out formats that
+/// originally used these layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Magic {
+    /// Each segment is page aligned, so that it can be mapped with its own permissions.
+    Demand,
+
+    /// Segments aren't page aligned and the headers aren't loaded. Everything that we load goes in
+    /// a single segment. This gives a compact image, as expected by bare-metal and bootloader
+    /// builds.
+    Nmagic,
+
+    /// Like `Nmagic`, but the text is also writable.
+    Omagic,
+}
+
+/// Which loadable segments get their own pages in the file, rather than sharing pages with their
+/// neighbours. Set by `-z separate-code`, `-z noseparate-code` and `-z separate-loadable-segments`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SeparateSegments {
+    /// Segments are packed together in the file. This gives the smallest output, but the pages
+    /// either side of the code may be mapped executable.
+    None,
+
+    /// The executable segment starts and ends on a page boundary in the file, so nothing else gets
+    /// mapped executable.
+    Code,
+
+    /// Every loadable segment starts on a page boundary in the file.
+    Loadable,
+}
+
+/// Which local symbols we leave out of the symbol table. Set by `--discard-none`,
+/// `--discard-locals` (`-X`) and `--discard-all` (`-x`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DiscardLocals {
+    None,
+
+    /// Temporary symbols, i.e. those with names starting with `.L`, which are generally labels
+    /// emitted by the compiler.
+    Temporary,
+
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SizeReportFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CetReport {
+    None,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MemtagMode {
+    None,
+    Sync,
+    Async,
+}
+
+/// How to order common symbols, which is by alignment, since that's what minimises padding. We
+/// can't sort common symbols separately from the `.bss` input sections that they share an output
+/// section with, so this also determines the order of those sections.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortCommon {
+    #[default]
+    Descending,
+    Ascending,
+}
+
+/// Which input sections should be placed in output sections of their own. Set by `--unique`.
+#[derive(Debug, Default)]
+pub(crate) struct UniqueSections {
+    /// Whether `--unique` was passed without a pattern. This applies to all sections that we
+    /// don't have a built-in output section for, other than debug info.
+    pub(crate) orphans: bool,
+
+    /// Globs passed via `--unique=<pattern>`. These are matched against input section names and
+    /// apply to all sections, including those that would otherwise go into a built-in section such
+    /// as `.text`.
+    pub(crate) patterns: Vec<String>,
+}
+
+impl UniqueSections {
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.orphans && self.patterns.is_empty()
+    }
+
+    pub(crate) fn matches_name(&self, section_name: &[u8]) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| crate::glob::glob_matches(pattern.as_bytes(), section_name))
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ExcludeLibs {
+    /// Set by `--exclude-libs=ALL`, which excludes every archive.
+    all: bool,
+
+    /// Archive names, with or without their `.a` suffix.
+    names: Vec<String>,
+}
+
+impl ExcludeLibs {
+    /// Adds the archives named in an argument to `--exclude-libs`. As with GNU ld, names may be
+    /// separated by commas or colons.
+    fn add(&mut self, list: &str) {
+        for name in list.split([',', ':']).filter(|name| !name.is_empty()) {
+            if name == "ALL" {
+                self.all = true;
+            } else {
+                self.names.push(name.to_owned());
+            }
+        }
+    }
+
+    /// Returns whether symbols defined by members of the archive at `archive_path` shouldn't be
+    /// exported. Only the file name of the archive is compared, not its directory.
+    pub(crate) fn excludes(&self, archive_path: &Path) -> bool {
+        if self.all {
+            return true;
+        }
+        let Some(file_name) = archive_path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        self.names.iter().any(|name| {
+            file_name
+                .strip_prefix(name.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest == ".a")
+        })
+    }
+}
+
+/// Reads a file for `--remap-inputs-file`. Each line contains a glob and a replacement path
+/// separated by whitespace. Comments start with `#`.
+fn read_remap_inputs_file(path: &Path, remap_inputs: &mut Vec<RemapInput>) -> Result {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read remap file `{}`", path.display()))?;
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line
+            .split_once('#')
+            .map_or(line, |(before, _)| before)
+            .trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(pattern), Some(replacement), None) = (parts.next(), parts.next(), parts.next())
+        else {
+            bail!(
+                "{}:{}: Expected a glob followed by a replacement path",
+                path.display(),
+                line_number + 1
+            );
+        };
+        remap_inputs.push(RemapInput::new(pattern, replacement));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub(crate) struct MaxGlibcVersion {
+    /// The components of the version, e.g. `[2, 17]`.
+    pub(crate) version: Vec<u32>,
+
+    /// Whether references to newer versions are only a warning rather than an error.
+    pub(crate) warn_only: bool,
+}
+
+impl MaxGlibcVersion {
+    fn parse(s: &str, warn_only: bool) -> Result<Self> {
+        let version = crate::glibc_versions::parse_version_number(s)
+            .with_context(|| format!("Invalid glibc version `{s}`"))?;
+        Ok(Self { version, warn_only })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MmapOutput {
+    /// Write via mmap unless the output file is on a filesystem where mmap is known to be
+    /// unreliable or slow, e.g. NFS, overlayfs or FUSE.
+    Auto,
+
+    /// Always write via mmap, falling back to a buffered write only if mmap fails.
+    Always,
+
+    /// Build the output in memory, then write it with a single positioned write.
+    Never,
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub(crate) struct Modifiers {
     /// Whether shared objects should only be linked if they're referenced.
@@ -122,6 +604,10 @@ pub(crate) struct Modifiers {
 
     /// Whether we're currently allowed to link against shared libraries.
     pub(crate) allow_shared: bool,
+
+    /// Whether only the symbols of this input are used, with their values taken as absolute
+    /// addresses. Set for inputs named by `--just-symbols`.
+    pub(crate) just_symbols: bool,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -146,6 +632,14 @@ pub const WRITE_TRACE_ENV: &str = "WILD_WRITE_TRACE";
 pub const REFERENCE_LINKER_ENV: &str = "WILD_REFERENCE_LINKER";
 pub(crate) const FILES_PER_GROUP_ENV: &str = "WILD_FILES_PER_GROUP";
 
+/// Extra arguments that are inserted before those on the command line. e.g. setting this to
+/// `--stats` would enable stats for every link without needing to change any build systems.
+pub const DEFAULT_FLAGS_ENV: &str = "WILD_FLAGS";
+
+/// A file containing extra arguments that are inserted before those on the command line, but after
+/// those from `WILD_FLAGS`.
+pub const DEFAULT_FLAGS_FILE_ENV: &str = "WILD_FLAGS_FILE";
+
 /// Set this environment variable if you get a failure during writing due to too much or too little
 /// space being allocated to some section. When set, each time we allocate during layout, we'll
 /// check that what we're doing is consistent with writing and fail in a more easy to debug way. i.e
@@ -167,39 +661,29 @@ const SILENTLY_IGNORED_FLAGS: &[&str] = &[
     // built-in search paths. Perhaps we should?
     "nostdlib",
     // TODO
-    "no-undefined-version",
     "export-dynamic",
     "fatal-warnings",
     "color-diagnostics",
-    "undefined-version",
-    "sort-common",
-    "no-relax",
     "enable-new-dtags",
 ];
 
-const IGNORED_FLAGS: &[&str] = &[
-    "gdb-index",
-    "disable-new-dtags",
-    "fix-cortex-a53-835769",
-    "fix-cortex-a53-843419",
-    "no-export-dynamic",
-];
+const IGNORED_FLAGS: &[&str] = &["gdb-index", "disable-new-dtags", "no-export-dynamic"];
+
+/// The number of errors that we report before stopping, unless overridden by `--error-limit`.
+const DEFAULT_ERROR_LIMIT: usize = 20;
 
 // These flags map to the default behavior of the linker.
 const DEFAULT_FLAGS: &[&str] = &[
     "no-call-graph-profile-sort",
-    "relax",
     "no-copy-dt-needed-entries",
     "no-add-needed",
-    "discard-locals",
-    "X",  // alias for --discard-locals
     "EL", // little endian
 ];
 
 // Parse the supplied input arguments, which should not include the program name.
 pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Result<Action> {
     let mut args = Args {
-        arch: default_target_arch(),
+        arch: OnceLock::new(),
 
         lib_search_path: Vec::new(),
         inputs: Vec::new(),
@@ -211,6 +695,7 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
         num_threads: crate::threading::available_parallelism(),
         strip_all: false,
         strip_debug: false,
+        discard_locals: DiscardLocals::Temporary,
         // For now, we default to --gc-sections. This is different to other linkers, but other than
         // being different, there doesn't seem to be any downside to doing this. We don't currently do
         // any less work if we're not GCing sections, but do end up writing more, so --no-gc-sections
@@ -232,22 +717,80 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
             .map(FileId::from_encoded),
         relocation_model: RelocationModel::NonRelocatable,
         version_script_path: None,
+        default_symver: false,
+        default_imported_symver: false,
+        undefined_version: true,
         debug_address: None,
         should_write_eh_frame_hdr: false,
         write_gc_stats: None,
+        print_archive_stats: None,
+        interface_stub: None,
+        interface_stub_so: None,
+        stack_report: None,
+        size_report: None,
+        size_report_format: SizeReportFormat::Text,
+        output_format: OutputFormat::Elf,
+        separate_debug_file: None,
+        emit_relocs: false,
+        magic: Magic::Demand,
+        max_page_size: crate::alignment::PAGE,
+        common_page_size: crate::alignment::PAGE,
+        relro: true,
+        separate_segments: SeparateSegments::None,
+        image_base: None,
+        section_starts: Vec::new(),
+        defsyms: Vec::new(),
+        print_dependencies: false,
+        profile_path: None,
+        dry_run: false,
         gc_stats_ignore: Vec::new(),
         verbose_gc_stats: false,
+        print_got_usage: false,
+        print_relaxations: false,
+        error_limit: Some(DEFAULT_ERROR_LIMIT),
         rpaths: Vec::new(),
         soname: None,
         execstack: false,
+        memory_seal: false,
+        force_bti: false,
+        pack_relative_relocs: false,
+        pac_plt: false,
+        lazy: false,
+        ibt_plt: None,
+        force_ibt: false,
+        force_shstk: false,
+        cet_report: CetReport::None,
+        memtag_mode: MemtagMode::None,
+        memtag_heap: false,
+        memtag_stack: false,
+        fix_cortex_a53_843419: false,
+        fix_cortex_a53_835769: false,
+        lam_u48: false,
+        lam_u57: false,
+        x86_isa_needed: None,
+        relax: RelaxOptions::ALL,
         should_fork: true,
         file_write_mode: FileWriteMode::UnlinkAndReplace,
+        mmap_output: MmapOutput::Auto,
+        max_memory: None,
+        enable_linker_version: true,
+        strip_section_headers: false,
+        split_stack_adjust_size: crate::split_stack::DEFAULT_SPLIT_STACK_ADJUST_SIZE,
+        remap_inputs: Vec::new(),
+        exclude_libs: ExcludeLibs::default(),
+        unique_sections: UniqueSections::default(),
+        sort_common: SortCommon::default(),
+        warn_common: false,
+        max_glibc_version: None,
         build_id: BuildIdOption::None,
         files_per_group: std::env::var(FILES_PER_GROUP_ENV)
             .ok()
             .map(|s| s.parse())
             .transpose()?,
         no_undefined: false,
+        ignore_unresolved_symbols: Vec::new(),
+        undefined: Vec::new(),
+        require_defined: Vec::new(),
     };
 
     let mut action = None;
@@ -284,12 +827,74 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
         };
         let mut handle_z_option = |arg: &str| -> Result {
             match arg {
-                "now" => {}
+                "now" => args.lazy = false,
+                "lazy" => args.lazy = true,
                 "origin" => {}
-                "norelro" => {}
+                "relro" => args.relro = true,
+                "norelro" => args.relro = false,
                 "notext" => {}
                 "execstack" => args.execstack = true,
                 "noexecstack" => args.execstack = false,
+                "memory-seal" => args.memory_seal = true,
+                "nomemory-seal" => args.memory_seal = false,
+                "force-bti" => args.force_bti = true,
+                "pack-relative-relocs" => {
+                    warn_unsupported("-z pack-relative-relocs")?;
+                    args.pack_relative_relocs = true;
+                }
+                "nopack-relative-relocs" => args.pack_relative_relocs = false,
+                "pac-plt" => args.pac_plt = true,
+                "ibtplt" => args.ibt_plt = Some(true),
+                "noibtplt" => args.ibt_plt = Some(false),
+                "force-ibt" => args.force_ibt = true,
+                "shstk" => args.force_shstk = true,
+                "lam-u48" => args.lam_u48 = true,
+                "lam-u57" => args.lam_u57 = true,
+                "x86-64-baseline" => {
+                    args.x86_isa_needed = Some(object::elf::GNU_PROPERTY_X86_ISA_1_BASELINE);
+                }
+                "x86-64-v2" => args.x86_isa_needed = Some(object::elf::GNU_PROPERTY_X86_ISA_1_V2),
+                "x86-64-v3" => args.x86_isa_needed = Some(object::elf::GNU_PROPERTY_X86_ISA_1_V3),
+                "x86-64-v4" => args.x86_isa_needed = Some(object::elf::GNU_PROPERTY_X86_ISA_1_V4),
+                "defs" => args.no_undefined = true,
+                "undefs" => args.no_undefined = false,
+                "sectionheader" => args.strip_section_headers = false,
+                "nosectionheader" => {
+                    args.strip_section_headers = true;
+                    args.strip_all = true;
+                    args.strip_debug = true;
+                }
+                "separate-code" => args.separate_segments = SeparateSegments::Code,
+                "noseparate-code" => args.separate_segments = SeparateSegments::None,
+                "separate-loadable-segments" => {
+                    args.separate_segments = SeparateSegments::Loadable;
+                }
+                "memtag-heap" => args.memtag_heap = true,
+                "memtag-stack" => args.memtag_stack = true,
+                _ if arg.starts_with("cet-report=") => {
+                    args.cet_report = match &arg["cet-report=".len()..] {
+                        "none" => CetReport::None,
+                        "warning" => CetReport::Warning,
+                        "error" => CetReport::Error,
+                        other => bail!("Invalid -z cet-report `{other}`"),
+                    };
+                }
+                _ if arg.starts_with("max-page-size=") => {
+                    args.max_page_size = parse_page_size(&arg["max-page-size=".len()..])
+                        .context("Invalid -z max-page-size")?;
+                }
+                _ if arg.starts_with("common-page-size=") => {
+                    args.common_page_size = parse_page_size(&arg["common-page-size=".len()..])
+                        .context("Invalid -z common-page-size")?;
+                }
+                _ if arg.starts_with("memtag-mode=") => {
+                    args.memtag_mode = match &arg["memtag-mode=".len()..] {
+                        "none" => MemtagMode::None,
+                        "sync" => MemtagMode::Sync,
+                        "async" => MemtagMode::Async,
+                        other => bail!("Invalid -z memtag-mode `{other}`"),
+                    };
+                }
                 _ => {
                     warn_unsupported(&format!("-z {arg}"))?;
                     // TODO: Handle these
@@ -313,10 +918,95 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
                 search_first: None,
                 modifiers: *modifier_stack.last().unwrap(),
             });
+        } else if arg == "-R" || long_arg_eq("just-symbols") {
+            let path = input
+                .next()
+                .context("Missing argument to --just-symbols")?
+                .as_ref()
+                .to_owned();
+            // Other linkers treat `-R` with a directory as `-rpath`.
+            if arg == "-R" && Path::new(&path).is_dir() {
+                args.rpaths.push(path);
+            } else {
+                save_dir.handle_file(&path)?;
+                args.inputs
+                    .push(just_symbols_input(&path, *modifier_stack.last().unwrap()));
+            }
+        } else if let Some(rest) = long_arg_split_prefix("just-symbols=") {
+            save_dir.handle_file(rest)?;
+            args.inputs
+                .push(just_symbols_input(rest, *modifier_stack.last().unwrap()));
         } else if long_arg_eq("static") || long_arg_eq("Bstatic") {
             modifier_stack.last_mut().unwrap().allow_shared = false;
         } else if long_arg_eq("Bdynamic") {
             modifier_stack.last_mut().unwrap().allow_shared = true;
+        } else if arg == "-n" || long_arg_eq("nmagic") || arg == "-N" || long_arg_eq("omagic") {
+            args.magic = if arg == "-n" || long_arg_eq("nmagic") {
+                Magic::Nmagic
+            } else {
+                Magic::Omagic
+            };
+            // Shared objects need to be mapped page by page, so they can't be used by these
+            // layouts.
+            modifier_stack.last_mut().unwrap().allow_shared = false;
+        } else if long_arg_eq("no-omagic") {
+            args.magic = Magic::Demand;
+        } else if let Some((name, value)) = strip_option(arg)
+            .and_then(|a| a.strip_prefix('T'))
+            .and_then(split_address_option)
+        {
+            let value = match value {
+                Some(v) => v.to_owned(),
+                None => input
+                    .next()
+                    .with_context(|| format!("Missing argument to -T{name}"))?
+                    .as_ref()
+                    .to_owned(),
+            };
+            let address =
+                parse_hex(&value).with_context(|| format!("Invalid address for -T{name}"))?;
+            if name == "text-segment" {
+                args.image_base = Some(address);
+            } else {
+                args.section_starts.push((format!(".{name}"), address));
+            }
+        } else if let Some(rest) = long_arg_split_prefix("section-start=") {
+            let (name, address) = rest
+                .split_once('=')
+                .context("--section-start requires an argument of the form SECTION=ADDRESS")?;
+            let address = parse_hex(address)
+                .with_context(|| format!("Invalid address for --section-start {name}"))?;
+            args.section_starts.push((name.to_owned(), address));
+        } else if arg.starts_with("-T")
+            || long_arg_eq("script")
+            || long_arg_split_prefix("script=").is_some()
+        {
+            let script = match arg
+                .strip_prefix("-T")
+                .filter(|script| !script.is_empty())
+                .or_else(|| long_arg_split_prefix("script="))
+            {
+                Some(script) => script.to_owned(),
+                None => input
+                    .next()
+                    .with_context(|| format!("Missing argument to {arg}"))?
+                    .as_ref()
+                    .to_owned(),
+            };
+            save_dir.handle_file(&script)?;
+            args.inputs.push(Input {
+                spec: InputSpec::File(Box::from(Path::new(&script))),
+                search_first: None,
+                modifiers: *modifier_stack.last().unwrap(),
+            });
+        } else if long_arg_eq("defsym") {
+            let value = input.next().context("Missing argument to --defsym")?;
+            args.defsyms
+                .push(crate::linker_script::parse_defsym(value.as_ref())?);
+        } else if let Some(rest) = long_arg_split_prefix("defsym=") {
+            args.defsyms.push(crate::linker_script::parse_defsym(rest)?);
+        } else if let Some(rest) = long_arg_split_prefix("image-base=") {
+            args.image_base = Some(parse_number(rest).context("Invalid --image-base")?);
         } else if arg == "-o" {
             args.output = input
                 .next()
@@ -363,6 +1053,11 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
         } else if long_arg_eq("threads") {
             // Default behaviour (multiple threads)
             args.num_threads = crate::threading::available_parallelism();
+        } else if let Some(rest) = long_arg_split_prefix("error-limit=") {
+            let limit = rest
+                .parse::<usize>()
+                .with_context(|| format!("Invalid --error-limit `{rest}`"))?;
+            args.error_limit = (limit != 0).then_some(limit);
         } else if let Some(rest) = long_arg_split_prefix("thread-count=") {
             args.num_threads = NonZeroUsize::try_from(rest.parse::<usize>()?)?;
         } else if long_arg_eq("no-threads") {
@@ -372,20 +1067,80 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
             args.strip_debug = true;
         } else if long_arg_eq("strip-debug") || arg == "-S" {
             args.strip_debug = true;
+        } else if long_arg_eq("discard-none") {
+            args.discard_locals = DiscardLocals::None;
+        } else if long_arg_eq("discard-locals") || arg == "-X" {
+            args.discard_locals = DiscardLocals::Temporary;
+        } else if long_arg_eq("discard-all") || arg == "-x" {
+            args.discard_locals = DiscardLocals::All;
         } else if long_arg_eq("gc-sections") {
             args.gc_sections = true;
         } else if long_arg_eq("no-gc-sections") {
             args.gc_sections = false;
         } else if long_arg_eq("no-fork") {
             args.should_fork = false;
+        } else if long_arg_eq("mmap-output") {
+            args.mmap_output = MmapOutput::Always;
+        } else if long_arg_eq("no-mmap-output") {
+            args.mmap_output = MmapOutput::Never;
+        } else if let Some(rest) = long_arg_split_prefix("max-glibc-version=") {
+            args.max_glibc_version = Some(MaxGlibcVersion::parse(rest, false)?);
+        } else if let Some(rest) = long_arg_split_prefix("warn-max-glibc-version=") {
+            args.max_glibc_version = Some(MaxGlibcVersion::parse(rest, true)?);
+        } else if long_arg_eq("thinlto-index-only")
+            || long_arg_split_prefix("thinlto-index-only=").is_some()
+            || long_arg_eq("thinlto-emit-imports-files")
+            || long_arg_split_prefix("thinlto-prefix-replace=").is_some()
+        {
+            // These need the summaries from LLVM bitcode inputs, which we can't read. Failing here
+            // is better than producing an empty index, which would make the build system's backend
+            // compiles fail in confusing ways.
+            bail!("{arg} is not supported, since wild doesn't yet support LTO");
+        } else if let Some(rest) = long_arg_split_prefix("remap-inputs=") {
+            let (pattern, replacement) = rest
+                .split_once('=')
+                .with_context(|| format!("Expected --remap-inputs=<glob>=<file>, got `{rest}`"))?;
+            args.remap_inputs
+                .push(RemapInput::new(pattern, replacement));
+        } else if long_arg_eq("exclude-libs") {
+            let libs = input.next().context("Missing argument to --exclude-libs")?;
+            args.exclude_libs.add(libs.as_ref());
+        } else if let Some(rest) = long_arg_split_prefix("exclude-libs=") {
+            args.exclude_libs.add(rest);
+        } else if let Some(rest) = long_arg_split_prefix("remap-inputs-file=") {
+            read_remap_inputs_file(Path::new(rest), &mut args.remap_inputs)?;
+        } else if long_arg_eq("warn-common") {
+            args.warn_common = true;
+        } else if long_arg_eq("sort-common") {
+            args.sort_common = SortCommon::Descending;
+        } else if let Some(rest) = long_arg_split_prefix("sort-common=") {
+            args.sort_common = match rest {
+                "descending" => SortCommon::Descending,
+                "ascending" => SortCommon::Ascending,
+                other => bail!("Invalid --sort-common `{other}`"),
+            };
+        } else if long_arg_eq("unique") {
+            args.unique_sections.orphans = true;
+        } else if let Some(rest) = long_arg_split_prefix("unique=") {
+            args.unique_sections.patterns.push(rest.to_owned());
+        } else if let Some(rest) = long_arg_split_prefix("split-stack-adjust-size=") {
+            args.split_stack_adjust_size = u32::try_from(parse_number(rest)?)
+                .with_context(|| format!("Invalid --split-stack-adjust-size `{rest}`"))?;
+        } else if long_arg_eq("enable-linker-version") {
+            args.enable_linker_version = true;
+        } else if long_arg_eq("disable-linker-version") {
+            args.enable_linker_version = false;
+        } else if let Some(rest) = long_arg_split_prefix("max-memory=") {
+            args.max_memory =
+                Some(parse_size(rest).with_context(|| format!("Invalid --max-memory `{rest}`"))?);
         } else if long_arg_eq("update-in-place") {
             args.file_write_mode = FileWriteMode::UpdateInPlace;
         } else if arg == "-m" {
             let arg_value = input.next().context("Missing argument to -m")?;
             let arg_value = arg_value.as_ref();
-            args.arch = Architecture::from_str(arg_value)?;
+            args.arch = OnceLock::from(Architecture::from_str(arg_value)?);
         } else if let Some(arg_value) = arg.strip_prefix("-m") {
-            args.arch = Architecture::from_str(arg_value)?;
+            args.arch = OnceLock::from(Architecture::from_str(arg_value)?);
         } else if long_arg_eq("EB") {
             bail!("Big-endian target is not supported");
         } else if arg == "-z" {
@@ -422,6 +1177,14 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
         } else if let Some(script) = long_arg_split_prefix("version-script=") {
             save_dir.handle_file(script)?;
             args.version_script_path = Some(PathBuf::from(script));
+        } else if long_arg_eq("default-symver") {
+            args.default_symver = true;
+        } else if long_arg_eq("default-imported-symver") {
+            args.default_imported_symver = true;
+        } else if long_arg_eq("no-undefined-version") {
+            args.undefined_version = false;
+        } else if long_arg_eq("undefined-version") {
+            args.undefined_version = true;
         } else if long_arg_eq("rpath") {
             args.rpaths.push(
                 input
@@ -441,7 +1204,15 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
         } else if long_arg_eq("eh-frame-hdr") {
             args.should_write_eh_frame_hdr = true;
         } else if long_arg_eq("shared") {
+            if args.output_kind == Some(OutputKind::RelocatableObject) {
+                bail!("-r and -shared may not be used together");
+            }
             args.output_kind = Some(OutputKind::SharedObject);
+        } else if arg == "-r" || arg == "-i" || long_arg_eq("relocatable") {
+            if args.output_kind == Some(OutputKind::SharedObject) {
+                bail!("-r and -shared may not be used together");
+            }
+            args.output_kind = Some(OutputKind::RelocatableObject);
         } else if let Some(rest) = long_arg_split_prefix("soname=") {
             args.soname = Some(rest.to_owned());
         } else if long_arg_eq("soname") {
@@ -472,12 +1243,66 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
             args.write_trace = true;
         } else if let Some(rest) = long_arg_split_prefix("write-gc-stats=") {
             args.write_gc_stats = Some(PathBuf::from(rest));
+        } else if let Some(rest) = long_arg_split_prefix("profile=") {
+            args.profile_path = Some(PathBuf::from(rest));
+        } else if let Some(rest) = long_arg_split_prefix("print-archive-stats=") {
+            args.print_archive_stats = Some(PathBuf::from(rest));
+        } else if let Some(rest) = long_arg_split_prefix("interface-stub=") {
+            args.interface_stub = Some(PathBuf::from(rest));
+        } else if let Some(rest) = long_arg_split_prefix("interface-stub-so=") {
+            args.interface_stub_so = Some(PathBuf::from(rest));
+        } else if let Some(rest) = long_arg_split_prefix("stack-report=") {
+            args.stack_report = Some(PathBuf::from(rest));
+        } else if let Some(rest) = long_arg_split_prefix("size-report=") {
+            args.size_report = Some(PathBuf::from(rest));
+        } else if let Some(rest) = long_arg_split_prefix("size-report-format=") {
+            args.size_report_format = match rest {
+                "text" => SizeReportFormat::Text,
+                "json" => SizeReportFormat::Json,
+                other => bail!("Unknown --size-report-format `{other}`"),
+            };
         } else if let Some(rest) = long_arg_split_prefix("gc-stats-ignore=") {
             args.gc_stats_ignore.push(rest.to_owned());
         } else if long_arg_eq("version") || arg == "-v" {
             action = Some(Action::Version);
         } else if long_arg_eq("verbose-gc-stats") {
             args.verbose_gc_stats = true;
+        } else if long_arg_eq("oformat") {
+            let format = input.next().context("Missing argument to --oformat")?;
+            args.output_format = parse_output_format(format.as_ref())?;
+        } else if let Some(rest) = long_arg_split_prefix("oformat=") {
+            args.output_format = parse_output_format(rest)?;
+        } else if long_arg_eq("separate-debug-file") {
+            // We don't yet know the output name, so we fill in the default path later.
+            args.separate_debug_file = Some(PathBuf::new());
+        } else if let Some(rest) = long_arg_split_prefix("separate-debug-file=") {
+            args.separate_debug_file = Some(PathBuf::from(rest));
+        } else if arg == "-q" || long_arg_eq("emit-relocs") {
+            args.emit_relocs = true;
+        } else if long_arg_eq("dry-run") {
+            args.dry_run = true;
+        } else if long_arg_eq("print-dependencies") {
+            args.print_dependencies = true;
+        } else if long_arg_eq("print-got-usage") {
+            args.print_got_usage = true;
+        } else if long_arg_eq("print-relaxations") {
+            args.print_relaxations = true;
+        } else if long_arg_eq("fix-cortex-a53-843419") {
+            args.fix_cortex_a53_843419 = true;
+        } else if long_arg_eq("fix-cortex-a53-835769") {
+            args.fix_cortex_a53_835769 = true;
+        } else if long_arg_eq("relax") {
+            args.relax = RelaxOptions::ALL;
+        } else if long_arg_eq("no-relax") {
+            args.relax = RelaxOptions::NONE;
+        } else if long_arg_eq("no-relax-gotpcrelx") {
+            args.relax.got = false;
+        } else if long_arg_eq("no-relax-tls") {
+            args.relax.tls = false;
+        } else if long_arg_eq("relax-gp") {
+            args.relax.gp = true;
+        } else if long_arg_eq("no-relax-gp") {
+            args.relax.gp = false;
         } else if let Some(rest) = long_arg_split_prefix("debug-address=") {
             args.debug_address = Some(parse_number(rest).context("Invalid --debug-address")?);
         } else if let Some(rest) = long_arg_split_prefix("debug-fuel=") {
@@ -487,6 +1312,36 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
             args.num_threads = NonZeroUsize::new(1).unwrap();
         } else if long_arg_eq("no-undefined") {
             args.no_undefined = true;
+        } else if long_arg_eq("ignore-unresolved-symbol") {
+            args.ignore_unresolved_symbols.push(
+                input
+                    .next()
+                    .context("Missing argument to --ignore-unresolved-symbol")?
+                    .as_ref()
+                    .to_owned(),
+            );
+        } else if let Some(rest) = long_arg_split_prefix("ignore-unresolved-symbol=") {
+            args.ignore_unresolved_symbols.push(rest.to_owned());
+        } else if arg == "-u" || long_arg_eq("undefined") {
+            args.undefined.push(
+                input
+                    .next()
+                    .context("Missing argument to --undefined")?
+                    .as_ref()
+                    .to_owned(),
+            );
+        } else if let Some(rest) = long_arg_split_prefix("undefined=") {
+            args.undefined.push(rest.to_owned());
+        } else if long_arg_eq("require-defined") {
+            args.require_defined.push(
+                input
+                    .next()
+                    .context("Missing argument to --require-defined")?
+                    .as_ref()
+                    .to_owned(),
+            );
+        } else if let Some(rest) = long_arg_split_prefix("require-defined=") {
+            args.require_defined.push(rest.to_owned());
         } else if let Some(path) = arg.strip_prefix('@') {
             if input.next().is_some() || arg_num > 1 {
                 bail!("Mixing of @{{filename}} and regular arguments isn't supported");
@@ -506,6 +1361,8 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
         {
         } else if long_arg_split_prefix("sysroot=").is_some() {
             warn_unsupported("--sysroot")?;
+        } else if let Some(rest) = arg.strip_prefix("-u").filter(|rest| !rest.starts_with('-')) {
+            args.undefined.push(rest.to_owned());
         } else if arg.starts_with('-') {
             unrecognised.push(format!("`{arg}`"));
         } else {
@@ -524,6 +1381,84 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
 
     save_dir.finish()?;
 
+    if (args.interface_stub.is_some() || args.interface_stub_so.is_some())
+        && args.output_kind() != OutputKind::SharedObject
+    {
+        bail!("--interface-stub and --interface-stub-so can only be used when linking a shared object");
+    }
+
+    if args.memtag_mode == MemtagMode::None && (args.memtag_heap || args.memtag_stack) {
+        bail!("-z memtag-heap and -z memtag-stack require -z memtag-mode=sync or async");
+    }
+
+    if args.output_format == OutputFormat::Binary
+        && matches!(
+            args.output_kind(),
+            OutputKind::SharedObject | OutputKind::RelocatableObject
+        )
+    {
+        bail!("--oformat binary can only be used when linking an executable");
+    }
+
+    if let Some(path) = args.separate_debug_file.as_mut() {
+        if args.strip_debug {
+            bail!("--separate-debug-file can't be used with --strip-debug or --strip-all");
+        }
+        if args.output_format == OutputFormat::Binary
+            || args.output_kind == Some(OutputKind::RelocatableObject)
+        {
+            bail!("--separate-debug-file can't be used with -r or --oformat binary");
+        }
+        if path.as_os_str().is_empty() {
+            let mut default_path = args.output.as_os_str().to_owned();
+            default_path.push(".debug");
+            *path = PathBuf::from(default_path);
+        }
+    }
+
+    if args.magic != Magic::Demand && args.output_kind == Some(OutputKind::SharedObject) {
+        bail!("-n and -N can't be used with -shared");
+    }
+
+    // Sections in a relocatable object don't have addresses.
+    if args.output_kind == Some(OutputKind::RelocatableObject) {
+        args.image_base = None;
+        args.section_starts.clear();
+    }
+
+    if args.emit_relocs {
+        if args.strip_all || args.strip_section_headers {
+            bail!("--emit-relocs can't be used with -s or -z nosectionheader");
+        }
+        if args.output_format == OutputFormat::Binary {
+            bail!("--emit-relocs can't be used with --oformat binary");
+        }
+        // A relocatable object keeps its relocations anyway.
+        if args.output_kind == Some(OutputKind::RelocatableObject) {
+            args.emit_relocs = false;
+        }
+    }
+
+    if args.output_kind == Some(OutputKind::RelocatableObject) {
+        if args.relocation_model == RelocationModel::Relocatable {
+            bail!("-r and -pie may not be used together");
+        }
+        if args.strip_section_headers {
+            bail!("-r requires section headers, so can't be used with -z nosectionheader");
+        }
+        if args.strip_all {
+            bail!("-r requires a symbol table, so can't be used with -s");
+        }
+        // Without an entry point, there's nothing to start garbage collection from, so every
+        // section is kept. Merging strings would require us to rewrite relocations that point into
+        // the merged sections, so we leave that to the final link.
+        args.gc_sections = false;
+        args.merge_strings = false;
+        args.should_write_eh_frame_hdr = false;
+    }
+
+    crate::memory_budget::apply(&mut args);
+
     if let Some(a) = action {
         return Ok(a);
     }
@@ -531,7 +1466,7 @@ pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Resul
     Ok(Action::Link(args))
 }
 
-const fn default_target_arch() -> Architecture {
+pub(crate) const fn default_target_arch() -> Architecture {
     // We default to targeting the architecture that we're running on. We don't support running on
     // architectures that we can't target.
     #[cfg(target_arch = "x86_64")]
@@ -542,12 +1477,61 @@ const fn default_target_arch() -> Architecture {
     {
         Architecture::AArch64
     }
+    #[cfg(target_arch = "riscv64")]
+    {
+        Architecture::RiscV64
+    }
+    #[cfg(target_arch = "powerpc64")]
+    {
+        Architecture::PowerPc64
+    }
 }
 
-fn parse_from_argument_file(path: &Path) -> Result<Action> {
+/// Parses the linker's command line, inserting any default arguments supplied via environment
+/// variables before the arguments that were actually passed.
+pub(crate) fn parse_command_line<S: AsRef<str>, I: Iterator<Item = S>>(input: I) -> Result<Action> {
+    let mut input = input.peekable();
+    if input
+        .peek()
+        .is_some_and(|arg| arg.as_ref() == crate::explain_reloc::COMMAND_NAME)
+    {
+        input.next();
+        return crate::explain_reloc::parse_args(input).map(Action::ExplainReloc);
+    }
+
+    let mut defaults = Vec::new();
+    if let Ok(flags) = std::env::var(DEFAULT_FLAGS_ENV) {
+        defaults.extend(
+            arguments_from_string(&flags)
+                .with_context(|| format!("Failed to parse {DEFAULT_FLAGS_ENV}"))?,
+        );
+    }
+    if let Some(path) = std::env::var_os(DEFAULT_FLAGS_FILE_ENV) {
+        defaults.extend(read_argument_file(Path::new(&path))?);
+    }
+    if defaults.is_empty() {
+        return parse(input);
+    }
+
+    let mut args = input.map(|a| a.as_ref().to_owned()).collect::<Vec<_>>();
+    // Mixing an argument file with other arguments isn't supported, so we need to expand it
+    // ourselves in order to be able to add our defaults.
+    if let [arg] = args.as_slice() {
+        if let Some(path) = arg.strip_prefix('@') {
+            args = read_argument_file(Path::new(path))?;
+        }
+    }
+    parse(defaults.into_iter().chain(args))
+}
+
+fn read_argument_file(path: &Path) -> Result<Vec<String>> {
     let contents = std::fs::read_to_string(path)
         .with_context(|| format!("Failed to read arguments from file `{}`", path.display()))?;
-    parse(arguments_from_string(&contents)?.into_iter())
+    arguments_from_string(&contents)
+}
+
+fn parse_from_argument_file(path: &Path) -> Result<Action> {
+    parse(read_argument_file(path)?.into_iter())
 }
 
 impl Args {
@@ -559,7 +1543,9 @@ impl Args {
     }
 
     pub(crate) fn base_address(&self) -> u64 {
-        if self.is_relocatable() {
+        if let Some(image_base) = self.image_base {
+            image_base
+        } else if self.is_relocatable() || self.output_kind() == OutputKind::RelocatableObject {
             0
         } else {
             crate::elf::NON_PIE_START_MEM_ADDRESS
@@ -594,7 +1580,11 @@ impl Args {
     /// Returns how we should handle TLS relocations like TLSLD and TLSGD.
     pub(crate) fn tls_mode(&self) -> crate::layout::TlsMode {
         match self.output_kind() {
-            OutputKind::StaticExecutable(_) => crate::layout::TlsMode::LocalExec,
+            // Relocatable objects keep their TLS relocations as-is, so we don't need to reserve
+            // anything for them.
+            OutputKind::StaticExecutable(_) | OutputKind::RelocatableObject => {
+                crate::layout::TlsMode::LocalExec
+            }
             OutputKind::DynamicExecutable(_) | OutputKind::SharedObject => {
                 crate::layout::TlsMode::Preserve
             }
@@ -639,6 +1629,45 @@ impl Args {
         self.should_fork
     }
 
+    /// Returns the architecture that we're linking for. This is only known once we've opened our
+    /// inputs, unless it was given with `-m`.
+    pub(crate) fn arch(&self) -> Architecture {
+        *self
+            .arch
+            .get()
+            .expect("Architecture was used before it was determined")
+    }
+
+    /// Returns the size of each entry in .plt.got. PAC PLT entries need extra instructions to
+    /// authenticate the GOT entry, so are larger than the default.
+    pub(crate) fn plt_entry_size(&self) -> u64 {
+        if matches!(self.arch(), Architecture::AArch64) && self.pac_plt {
+            crate::elf::AARCH64_PAC_PLT_ENTRY_SIZE
+        } else if matches!(self.arch(), Architecture::PowerPc64) {
+            crate::elf::PPC64_PLT_ENTRY_SIZE
+        } else {
+            crate::elf::PLT_ENTRY_SIZE
+        }
+    }
+
+    /// Returns whether we're going to emit a lazy-binding PLT. This is currently only supported on
+    /// x86-64, so on other architectures `-z lazy` has no effect and everything gets bound when the
+    /// output is loaded. Static executables don't have a dynamic loader that could do the binding.
+    pub(crate) fn lazy_binding(&self) -> bool {
+        self.lazy
+            && matches!(self.arch(), Architecture::X86_64)
+            && matches!(
+                self.output_kind(),
+                OutputKind::DynamicExecutable(_) | OutputKind::SharedObject
+            )
+    }
+
+    /// Returns whether we copy relocations from our inputs into the output rather than only
+    /// applying them. This is the case for `-r` and `--emit-relocs`.
+    pub(crate) fn keeps_input_relocations(&self) -> bool {
+        self.emit_relocs || self.output_kind() == OutputKind::RelocatableObject
+    }
+
     pub(crate) fn output_kind(&self) -> OutputKind {
         self.output_kind.unwrap_or({
             if self.is_dynamic_executable {
@@ -658,18 +1687,86 @@ fn parse_number(s: &str) -> Result<u64> {
     }
 }
 
+fn parse_page_size(s: &str) -> Result<Alignment> {
+    Alignment::page(parse_number(s)?)
+}
+
+/// Parses a section address. For compatibility with GNU ld, these are always hexadecimal, even
+/// without a `0x` prefix.
+fn parse_hex(s: &str) -> Result<u64> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    Ok(u64::from_str_radix(digits, 16)?)
+}
+
+/// Splits an option like `-Ttext=0x1000`, with the `-T` already removed, into the option name and
+/// its value if the value was supplied in the same argument. Returns `None` if the option isn't one
+/// that sets an address.
+fn split_address_option(option: &str) -> Option<(&str, Option<&str>)> {
+    let (name, value) = match option.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (option, None),
+    };
+    ["text", "data", "bss", "text-segment"]
+        .contains(&name)
+        .then_some((name, value))
+}
+
+/// Parses the BFD target name passed to `--oformat`. We only write 64 bit little-endian ELF, so we
+/// accept any name for that and leave it to `-m` or the inputs to determine the architecture.
+fn parse_output_format(name: &str) -> Result<OutputFormat> {
+    match name {
+        "binary" => Ok(OutputFormat::Binary),
+        _ if name.starts_with("elf64-") && !name.starts_with("elf64-big") => Ok(OutputFormat::Elf),
+        _ if name.starts_with("elf32-") => {
+            bail!("--oformat {name} requires 32 bit ELF output, which is not yet supported")
+        }
+        _ => bail!("--oformat {name} is not yet supported"),
+    }
+}
+
+/// Parses a size in bytes, optionally followed by a K, M or G suffix.
+fn parse_size(s: &str) -> Result<u64> {
+    let (number, multiplier) = match s.as_bytes().last() {
+        Some(b'k' | b'K') => (&s[..s.len() - 1], 1 << 10),
+        Some(b'm' | b'M') => (&s[..s.len() - 1], 1 << 20),
+        Some(b'g' | b'G') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    parse_number(number)?
+        .checked_mul(multiplier)
+        .context("Size is too large")
+}
+
+fn just_symbols_input(path: &str, modifiers: Modifiers) -> Input {
+    Input {
+        spec: InputSpec::File(Box::from(Path::new(path))),
+        search_first: None,
+        modifiers: Modifiers {
+            just_symbols: true,
+            ..modifiers
+        },
+    }
+}
+
 impl Default for Modifiers {
     fn default() -> Self {
         Self {
             as_needed: false,
             allow_shared: true,
+            just_symbols: false,
         }
     }
 }
 
 impl OutputKind {
     pub(crate) fn is_executable(self) -> bool {
-        !matches!(self, OutputKind::SharedObject)
+        matches!(
+            self,
+            OutputKind::StaticExecutable(_) | OutputKind::DynamicExecutable(_)
+        )
     }
 
     pub(crate) fn is_static_executable(self) -> bool {
@@ -697,7 +1794,11 @@ impl OutputKind {
     }
 
     fn needs_dynamic(self) -> bool {
-        self != OutputKind::StaticExecutable(RelocationModel::NonRelocatable)
+        !matches!(
+            self,
+            OutputKind::StaticExecutable(RelocationModel::NonRelocatable)
+                | OutputKind::RelocatableObject
+        )
     }
 }
 
@@ -783,7 +1884,11 @@ fn warn_unsupported(opt: &str) -> Result {
 mod tests {
     use super::SILENTLY_IGNORED_FLAGS;
     use crate::args::Action;
+    use crate::args::DiscardLocals;
     use crate::args::InputSpec;
+    use crate::args::Magic;
+    use crate::args::OutputFormat;
+    use crate::args::OutputKind;
     use itertools::Itertools;
     use std::num::NonZeroUsize;
     use std::path::Path;
@@ -959,6 +2064,294 @@ mod tests {
         assert!(arguments_from_string("foo\"").is_err());
     }
 
+    #[test]
+    fn test_parse_size() {
+        use super::parse_size;
+
+        assert_eq!(parse_size("1000").unwrap(), 1000);
+        assert_eq!(parse_size("0x10").unwrap(), 16);
+        assert_eq!(parse_size("4k").unwrap(), 4096);
+        assert_eq!(parse_size("2M").unwrap(), 2 << 20);
+        assert_eq!(parse_size("3G").unwrap(), 3 << 30);
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("99999999999G").is_err());
+    }
+
+    #[test]
+    fn test_relocatable_output() {
+        let parse = |args: &[&str]| super::parse(args.iter());
+
+        let Action::Link(args) = parse(&["-r", "a.o", "b.o", "-o", "ab.o"]).unwrap() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(args.output_kind(), OutputKind::RelocatableObject);
+        assert!(!args.output_kind().is_executable());
+        assert!(!args.gc_sections);
+        assert!(!args.merge_strings);
+
+        assert!(parse(&["--relocatable", "--gc-sections", "a.o"]).is_ok());
+        assert!(parse(&["-r", "-shared", "a.o"]).is_err());
+        assert!(parse(&["-shared", "-r", "a.o"]).is_err());
+        assert!(parse(&["-r", "-pie", "a.o"]).is_err());
+        assert!(parse(&["-r", "-s", "a.o"]).is_err());
+    }
+
+    #[test]
+    fn test_output_format() {
+        let parse = |args: &[&str]| super::parse(args.iter());
+        let output_format = |args: &[&str]| match parse(args).unwrap() {
+            Action::Link(args) => args.output_format,
+            _ => panic!("Unexpected action"),
+        };
+
+        assert_eq!(output_format(&["a.o"]), OutputFormat::Elf);
+        assert_eq!(
+            output_format(&["--oformat", "binary", "a.o"]),
+            OutputFormat::Binary
+        );
+        assert_eq!(
+            output_format(&["--oformat=elf64-x86-64", "a.o"]),
+            OutputFormat::Elf
+        );
+        assert_eq!(
+            output_format(&["-oformat=elf64-littleaarch64", "a.o"]),
+            OutputFormat::Elf
+        );
+        assert!(parse(&["--oformat=elf64-bigaarch64", "a.o"]).is_err());
+        assert!(parse(&["--oformat=elf32-i386", "a.o"]).is_err());
+        assert!(parse(&["--oformat=srec", "a.o"]).is_err());
+        assert!(parse(&["--oformat=binary", "-shared", "a.o"]).is_err());
+        assert!(parse(&["--oformat=binary", "-r", "a.o"]).is_err());
+    }
+
+    #[test]
+    fn test_separate_debug_file() {
+        let parse = |args: &[&str]| super::parse(args.iter());
+        let separate_debug_file = |args: &[&str]| match parse(args).unwrap() {
+            Action::Link(args) => args.separate_debug_file,
+            _ => panic!("Unexpected action"),
+        };
+
+        assert_eq!(separate_debug_file(&["a.o", "-o", "out"]), None);
+        assert_eq!(
+            separate_debug_file(&["--separate-debug-file", "a.o", "-o", "out"]),
+            Some(PathBuf::from("out.debug"))
+        );
+        assert_eq!(
+            separate_debug_file(&["--separate-debug-file=dbg/out.dbg", "-o", "out", "a.o"]),
+            Some(PathBuf::from("dbg/out.dbg"))
+        );
+        assert!(parse(&["--separate-debug-file", "-S", "a.o"]).is_err());
+        assert!(parse(&["--separate-debug-file", "-r", "a.o"]).is_err());
+    }
+
+    #[test]
+    fn test_emit_relocs() {
+        let parse = |args: &[&str]| super::parse(args.iter());
+        let emit_relocs = |args: &[&str]| match parse(args).unwrap() {
+            Action::Link(args) => (args.emit_relocs, args.keeps_input_relocations()),
+            _ => panic!("Unexpected action"),
+        };
+
+        assert_eq!(emit_relocs(&["a.o"]), (false, false));
+        assert_eq!(emit_relocs(&["--emit-relocs", "a.o"]), (true, true));
+        assert_eq!(emit_relocs(&["-q", "a.o"]), (true, true));
+        assert_eq!(emit_relocs(&["-q", "-r", "a.o"]), (false, true));
+        assert!(parse(&["--emit-relocs", "-s", "a.o"]).is_err());
+        assert!(parse(&["-q", "--oformat=binary", "a.o"]).is_err());
+    }
+
+    #[test]
+    fn test_magic() {
+        let parse = |args: &[&str]| super::parse(args.iter());
+        let magic = |args: &[&str]| match parse(args).unwrap() {
+            Action::Link(args) => (args.magic, args.inputs[0].modifiers.allow_shared),
+            _ => panic!("Unexpected action"),
+        };
+
+        assert_eq!(magic(&["-lfoo"]), (Magic::Demand, true));
+        assert_eq!(magic(&["-n", "-lfoo"]), (Magic::Nmagic, false));
+        assert_eq!(magic(&["--omagic", "-lfoo"]), (Magic::Omagic, false));
+        assert_eq!(
+            magic(&["-N", "--no-omagic", "-lfoo"]),
+            (Magic::Demand, false)
+        );
+        assert!(parse(&["-N", "-shared", "a.o"]).is_err());
+    }
+
+    #[test]
+    fn test_exclude_libs() {
+        let exclude_libs = |args: &[&str]| match super::parse(args.iter()).unwrap() {
+            Action::Link(args) => args.exclude_libs,
+            _ => panic!("Unexpected action"),
+        };
+        let excluded = exclude_libs(&[
+            "--exclude-libs=libfoo.a,libbar",
+            "--exclude-libs",
+            "libbaz.a:",
+            "a.o",
+        ]);
+        assert!(excluded.excludes(Path::new("/usr/lib/libfoo.a")));
+        assert!(excluded.excludes(Path::new("libbar.a")));
+        assert!(excluded.excludes(Path::new("lib/libbaz.a")));
+        assert!(!excluded.excludes(Path::new("libfoo.a.b")));
+        assert!(!excluded.excludes(Path::new("libbarx.a")));
+        assert!(!excluded.excludes(Path::new("/libfoo/libqux.a")));
+
+        let all = exclude_libs(&["--exclude-libs", "ALL", "a.o"]);
+        assert!(all.excludes(Path::new("libqux.a")));
+    }
+
+    #[test]
+    fn test_discard_locals() {
+        let discard_locals = |args: &[&str]| match super::parse(args.iter()).unwrap() {
+            Action::Link(args) => args.discard_locals,
+            _ => panic!("Unexpected action"),
+        };
+        assert_eq!(discard_locals(&["a.o"]), DiscardLocals::Temporary);
+        assert_eq!(discard_locals(&["-x", "a.o"]), DiscardLocals::All);
+        assert_eq!(
+            discard_locals(&["--discard-all", "-X", "a.o"]),
+            DiscardLocals::Temporary
+        );
+        assert_eq!(
+            discard_locals(&["-x", "--discard-none", "a.o"]),
+            DiscardLocals::None
+        );
+    }
+
+    #[test]
+    fn test_section_starts() {
+        let args = match super::parse(
+            [
+                "-Ttext=0x10000",
+                "-Tdata",
+                "20000",
+                "--section-start=.foo=0X3000",
+                "-Ttext-segment=0x200000",
+                "a.o",
+            ]
+            .iter(),
+        )
+        .unwrap()
+        {
+            Action::Link(args) => args,
+            _ => panic!("Unexpected action"),
+        };
+        assert_eq!(
+            args.section_starts,
+            [
+                (".text".to_owned(), 0x10000),
+                (".data".to_owned(), 0x20000),
+                (".foo".to_owned(), 0x3000),
+            ]
+        );
+        assert_eq!(args.base_address(), 0x200000);
+        assert!(super::parse(["--section-start=.foo", "a.o"].iter()).is_err());
+        assert!(super::parse(["-Tbss=xyz", "a.o"].iter()).is_err());
+    }
+
+    #[test]
+    fn test_scripts() {
+        let args = match super::parse(
+            [
+                "-T",
+                "a.ld",
+                "-Tb.ld",
+                "--script",
+                "c.ld",
+                "--script=d.ld",
+                "-Ttext=0x1000",
+                "e.o",
+            ]
+            .iter(),
+        )
+        .unwrap()
+        {
+            Action::Link(args) => args,
+            _ => panic!("Unexpected action"),
+        };
+        let inputs = args
+            .inputs
+            .iter()
+            .map(|input| match &input.spec {
+                InputSpec::File(path) => path.to_str().unwrap(),
+                InputSpec::Lib(_) => panic!("Unexpected library input"),
+            })
+            .collect_vec();
+        assert_eq!(inputs, ["a.ld", "b.ld", "c.ld", "d.ld", "e.o"]);
+        assert_eq!(args.section_starts, [(".text".to_owned(), 0x1000)]);
+        assert!(super::parse(["e.o", "-T"].iter()).is_err());
+    }
+
+    #[test]
+    fn test_defsym() {
+        let args = match super::parse(
+            [
+                "--defsym=end=origin+0x1000",
+                "--defsym",
+                "start = 4K",
+                "a.o",
+            ]
+            .iter(),
+        )
+        .unwrap()
+        {
+            Action::Link(args) => args,
+            _ => panic!("Unexpected action"),
+        };
+        let names = args.defsyms.iter().map(|a| a.name.as_str()).collect_vec();
+        assert_eq!(names, ["end", "start"]);
+        assert!(super::parse(["--defsym=end", "a.o"].iter()).is_err());
+        assert!(super::parse(["--defsym=.=0x1000", "a.o"].iter()).is_err());
+        assert!(super::parse(["--defsym=a=1 2", "a.o"].iter()).is_err());
+    }
+
+    #[test]
+    fn test_undefined() {
+        let args = match super::parse(
+            [
+                "-u",
+                "a",
+                "-ub",
+                "--undefined",
+                "c",
+                "--undefined=d",
+                "--require-defined",
+                "e",
+                "--require-defined=f",
+                "a.o",
+            ]
+            .iter(),
+        )
+        .unwrap()
+        {
+            Action::Link(args) => args,
+            _ => panic!("Unexpected action"),
+        };
+        assert_eq!(args.undefined, ["a", "b", "c", "d"]);
+        assert_eq!(args.require_defined, ["e", "f"]);
+        assert!(super::parse(["a.o", "-u"].iter()).is_err());
+    }
+
+    #[test]
+    fn test_just_symbols() {
+        let args =
+            match super::parse(["-R", "kernel", "--just-symbols=image", "-R", "/", "a.o"].iter())
+                .unwrap()
+            {
+                Action::Link(args) => args,
+                _ => panic!("Unexpected action"),
+            };
+        let just_symbols = args
+            .inputs
+            .iter()
+            .map(|input| input.modifiers.just_symbols)
+            .collect_vec();
+        assert_eq!(just_symbols, [true, true, false]);
+        assert_eq!(args.rpaths, ["/"]);
+    }
+
     #[test]
     fn test_ignored_flags() {
         for flag in SILENTLY_IGNORED_FLAGS {
@@ -0,0 +1,69 @@
+//! Writing of raw binary output (`--oformat binary`). This is for bootloaders, firmware and other
+//! bare-metal images that get copied straight into memory, so there are no headers. The file
+//! contains the bytes of each loadable section at its offset from the lowest load address, with
+//! any gaps between sections filled with zeros. Sections without any data in the file, such as
+//! `.bss`, are left for the program to clear, so aren't written even if they're at the end.
+//!
+//! We lay out and write a regular ELF image in memory, then copy those sections out of it. Each
+//! section goes at its load address, which differs from its address if a linker script puts it in
+//! a memory region with `AT>`, e.g. `.data` that startup code copies from flash to RAM.
+
+use crate::error::Result;
+use crate::layout::Layout;
+use crate::output_section_id;
+use crate::storage::StorageModel;
+use anyhow::Context;
+use std::path::Path;
+
+/// Writes the loadable sections of `elf_image`, which is the ELF file for `layout`, to `path`.
+pub(crate) fn write<S: StorageModel>(layout: &Layout<S>, elf_image: &[u8], path: &Path) -> Result {
+    let mut sections = layout
+        .output_sections
+        .ids_with_info()
+        .filter_map(|(section_id, _)| {
+            if [
+                output_section_id::FILE_HEADER,
+                output_section_id::PROGRAM_HEADERS,
+                output_section_id::SECTION_HEADERS,
+            ]
+            .contains(&section_id)
+                || layout
+                    .output_sections
+                    .load_segment_for(section_id)
+                    .is_none()
+                || !layout.output_sections.has_data_in_file(section_id)
+            {
+                return None;
+            }
+            let section_layout = layout.section_layouts.get(section_id);
+            let load_address = *layout.section_load_addresses.get(section_id);
+            (section_layout.file_size > 0).then_some((load_address, section_layout))
+        })
+        .collect::<Vec<_>>();
+    sections.sort_by_key(|(load_address, _)| *load_address);
+
+    let (Some((start, _)), Some(end)) = (
+        sections.first().copied(),
+        sections
+            .iter()
+            .map(|(load_address, section)| load_address + section.file_size as u64)
+            .max(),
+    ) else {
+        return std::fs::write(path, [])
+            .with_context(|| format!("Failed to write `{}`", path.display()));
+    };
+    let size = usize::try_from(end - start).with_context(|| {
+        format!("Binary output spanning {start:#x}..{end:#x} is too large for this host")
+    })?;
+
+    let mut out = vec![0; size];
+    for (load_address, section) in sections {
+        let offset = (load_address - start) as usize;
+        let bytes = elf_image
+            .get(section.file_offset..section.file_offset + section.file_size)
+            .context("Section extends past the end of the ELF image")?;
+        out[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    std::fs::write(path, out).with_context(|| format!("Failed to write `{}`", path.display()))
+}
@@ -0,0 +1,363 @@
+//! Merging of the build attribute notes in `.gnu.build.attributes` that annobin adds to objects to
+//! record how they were compiled. See the Watermark specification for details of the format:
+//! https://fedoraproject.org/wiki/Toolchain/Watermark
+//!
+//! Notes are organised into groups, each of which applies to a range of addresses. The descriptor
+//! of the first note of a group holds the start and end of the range, while the notes that follow
+//! have empty descriptors and apply to the same range. Every object compiled with the same flags
+//! has the same few dozen notes, so concatenating the input sections, as we would for other notes,
+//! makes large links produce megabytes of duplicates. Instead, we drop groups whose code was
+//! discarded, drop duplicate notes within a group and join groups with identical notes whose
+//! ranges are adjacent in the output.
+
+use crate::elf::File;
+use crate::error::Result;
+use crate::input_data::FileId;
+use crate::part_id::PartId;
+use anyhow::bail;
+use itertools::Itertools;
+use linker_utils::elf::secnames::GNU_BUILD_ATTRIBUTES_SECTION_NAME;
+use object::read::elf::Rela as _;
+use object::LittleEndian;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// The size of a note header, which is the name size, the descriptor size and the type.
+const NOTE_HEADER_SIZE: u64 = 12;
+
+/// The size of the descriptor of a note that starts a group, which holds two 64 bit addresses.
+const RANGE_DESCRIPTOR_SIZE: u64 = 16;
+
+/// A location within an input section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Location {
+    pub(crate) file_id: FileId,
+    pub(crate) section_index: object::SectionIndex,
+    pub(crate) offset: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Note<'data> {
+    n_type: u32,
+
+    /// The name of the note, which encodes the attribute and its value. Includes the terminating
+    /// null byte, if any.
+    name: &'data [u8],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NoteGroup<'data> {
+    /// The addresses to which the notes apply. `None` for any notes that come before the first
+    /// note with a range.
+    pub(crate) range: Option<Range<Location>>,
+    notes: Vec<Note<'data>>,
+}
+
+/// Where a loaded input section will be placed in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct SectionPosition {
+    pub(crate) part_id: PartId,
+
+    /// The number of non-empty sections that are placed before this one in the same part.
+    pub(crate) index_in_part: u64,
+
+    pub(crate) size: u64,
+}
+
+/// Reads the build attribute notes from `object`. Notes whose range can't be resolved to a location
+/// in a section of `object` are dropped.
+pub(crate) fn parse_object<'data>(
+    object: &File<'data>,
+    file_id: FileId,
+) -> Result<Vec<NoteGroup<'data>>> {
+    let mut groups = Vec::new();
+    for (section_index, section) in object.sections.enumerate() {
+        if object.section_name(section)? != GNU_BUILD_ATTRIBUTES_SECTION_NAME {
+            continue;
+        }
+        let data = object.raw_section_data(section)?;
+        let relocations = object.relocations(section_index)?;
+        let location = |offset: u64| -> Result<Option<Location>> {
+            let e = LittleEndian;
+            let Some(rel) = relocations.iter().find(|rel| rel.r_offset(e) == offset) else {
+                return Ok(None);
+            };
+            let Some(symbol_index) = rel.symbol(e, false) else {
+                return Ok(None);
+            };
+            let symbol = object.symbol(symbol_index)?;
+            let Some(section_index) = object.symbol_section(symbol, symbol_index)? else {
+                return Ok(None);
+            };
+            let implicit_addend = read_u64(data, offset)?;
+            Ok(Some(Location {
+                file_id,
+                section_index,
+                offset: symbol
+                    .st_value
+                    .get(e)
+                    .wrapping_add(rel.r_addend(e) as u64)
+                    .wrapping_add(implicit_addend),
+            }))
+        };
+
+        // Whether the current group applies to code that we know about. If it doesn't, then we
+        // drop the group.
+        let mut current_is_valid = true;
+        let mut offset = 0;
+        while offset < data.len() as u64 {
+            let name_size = u64::from(read_u32(data, offset)?);
+            let desc_size = u64::from(read_u32(data, offset + 4)?);
+            let n_type = read_u32(data, offset + 8)?;
+            let name_offset = offset + NOTE_HEADER_SIZE;
+            let desc_offset = name_offset + name_size.next_multiple_of(4);
+            let name = data
+                .get(name_offset as usize..(name_offset + name_size) as usize)
+                .ok_or_else(|| anyhow::anyhow!("Truncated note at offset 0x{offset:x}"))?;
+            let note = Note { n_type, name };
+            match desc_size {
+                0 => {
+                    if groups.is_empty() {
+                        groups.push(NoteGroup {
+                            range: None,
+                            notes: Vec::new(),
+                        });
+                    }
+                    if current_is_valid {
+                        groups.last_mut().unwrap().notes.push(note);
+                    }
+                }
+                RANGE_DESCRIPTOR_SIZE => {
+                    let start = location(desc_offset)?;
+                    let end = location(desc_offset + 8)?;
+                    current_is_valid = false;
+                    if let (Some(start), Some(end)) = (start, end) {
+                        current_is_valid = true;
+                        groups.push(NoteGroup {
+                            range: Some(start..end),
+                            notes: vec![note],
+                        });
+                    }
+                }
+                other => bail!("Unsupported build attribute note with descriptor size {other}"),
+            }
+            offset = desc_offset + desc_size.next_multiple_of(4);
+        }
+    }
+    Ok(groups)
+}
+
+/// Merges `groups`, which come from all of our inputs. `positions` must have an entry for every
+/// section referenced by the groups.
+pub(crate) fn merge<'data>(
+    groups: Vec<NoteGroup<'data>>,
+    positions: &HashMap<(FileId, object::SectionIndex), SectionPosition>,
+) -> Vec<NoteGroup<'data>> {
+    let sort_key = |location: &Location| {
+        let position = positions[&(location.file_id, location.section_index)];
+        (position.part_id, position.index_in_part, location.offset)
+    };
+
+    let mut unranged_notes = Vec::new();
+    let mut ranged = Vec::new();
+    for group in groups {
+        let notes = group.notes.into_iter().unique().collect_vec();
+        match group.range {
+            Some(range) => ranged.push(NoteGroup {
+                range: Some(range),
+                notes,
+            }),
+            None => unranged_notes.extend(notes),
+        }
+    }
+    ranged.sort_by_cached_key(|group| group.range.as_ref().map(|range| sort_key(&range.start)));
+
+    let mut merged = Vec::new();
+    if !unranged_notes.is_empty() {
+        merged.push(NoteGroup {
+            range: None,
+            notes: unranged_notes.into_iter().unique().collect(),
+        });
+    }
+
+    // For each distinct list of notes, the index in `merged` of the last group with those notes.
+    let mut last_with_notes: HashMap<Vec<Note>, usize> = HashMap::new();
+    for group in ranged {
+        let range = group.range.clone().unwrap();
+        if let Some(&index) = last_with_notes.get(&group.notes) {
+            let previous_range = merged[index].range.as_mut().unwrap();
+            if follows(&previous_range.end, &range.start, positions) {
+                if sort_key(&range.end) > sort_key(&previous_range.end) {
+                    previous_range.end = range.end;
+                }
+                continue;
+            }
+        }
+        last_with_notes.insert(group.notes.clone(), merged.len());
+        merged.push(group);
+    }
+    merged
+}
+
+/// Returns whether `start` is at or before `end`, or is at the start of the section that comes
+/// after the one `end` is at the end of. In either case, a range ending at `end` can be extended to
+/// cover a range starting at `start` without covering any other input sections.
+fn follows(
+    end: &Location,
+    start: &Location,
+    positions: &HashMap<(FileId, object::SectionIndex), SectionPosition>,
+) -> bool {
+    if (end.file_id, end.section_index) == (start.file_id, start.section_index) {
+        return start.offset <= end.offset;
+    }
+    let end_position = positions[&(end.file_id, end.section_index)];
+    let start_position = positions[&(start.file_id, start.section_index)];
+    end_position.part_id == start_position.part_id
+        && end_position.index_in_part + 1 == start_position.index_in_part
+        && end.offset >= end_position.size
+        && start.offset == 0
+}
+
+/// Returns the number of bytes needed to write `groups`.
+pub(crate) fn encoded_size(groups: &[NoteGroup]) -> u64 {
+    groups
+        .iter()
+        .map(|group| {
+            let notes_size: u64 = group
+                .notes
+                .iter()
+                .map(|note| NOTE_HEADER_SIZE + (note.name.len() as u64).next_multiple_of(4))
+                .sum();
+            notes_size + group.range.as_ref().map_or(0, |_| RANGE_DESCRIPTOR_SIZE)
+        })
+        .sum()
+}
+
+/// Writes `groups` to `out`, which must be exactly `encoded_size` bytes. `address` is called to
+/// get the output address of each location.
+pub(crate) fn write(
+    groups: &[NoteGroup],
+    out: &mut [u8],
+    address: impl Fn(&Location) -> Result<u64>,
+) -> Result {
+    let mut out = out;
+    let mut write_bytes = |bytes: &[u8]| -> Result {
+        let Some((dest, rest)) = std::mem::take(&mut out).split_at_mut_checked(bytes.len()) else {
+            bail!("Insufficient space allocated for build attribute notes");
+        };
+        dest.copy_from_slice(bytes);
+        out = rest;
+        Ok(())
+    };
+    for group in groups {
+        let range = group
+            .range
+            .as_ref()
+            .map(|range| {
+                Ok::<_, crate::error::Error>((address(&range.start)?, address(&range.end)?))
+            })
+            .transpose()?;
+        for (i, note) in group.notes.iter().enumerate() {
+            let desc_size = match range {
+                Some(_) if i == 0 => RANGE_DESCRIPTOR_SIZE,
+                _ => 0,
+            };
+            write_bytes(&(note.name.len() as u32).to_le_bytes())?;
+            write_bytes(&(desc_size as u32).to_le_bytes())?;
+            write_bytes(&note.n_type.to_le_bytes())?;
+            write_bytes(note.name)?;
+            let padding = note.name.len().next_multiple_of(4) - note.name.len();
+            write_bytes(&[0; 3][..padding])?;
+            if let (Some((start, end)), 0) = (range, i) {
+                write_bytes(&start.to_le_bytes())?;
+                write_bytes(&end.to_le_bytes())?;
+            }
+        }
+    }
+    if !out.is_empty() {
+        bail!("Allocated too much space for build attribute notes");
+    }
+    Ok(())
+}
+
+fn read_u32(data: &[u8], offset: u64) -> Result<u32> {
+    let Some(bytes) = data
+        .get(offset as usize..)
+        .and_then(|rest| rest.first_chunk::<4>())
+    else {
+        bail!("Truncated note at offset 0x{offset:x}");
+    };
+    Ok(u32::from_le_bytes(*bytes))
+}
+
+fn read_u64(data: &[u8], offset: u64) -> Result<u64> {
+    let Some(bytes) = data
+        .get(offset as usize..)
+        .and_then(|rest| rest.first_chunk::<8>())
+    else {
+        bail!("Truncated note at offset 0x{offset:x}");
+    };
+    Ok(u64::from_le_bytes(*bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let location = |file, offset| Location {
+            file_id: FileId::new(0, file),
+            section_index: object::SectionIndex(1),
+            offset,
+        };
+        let note = |name| Note {
+            n_type: 0x100,
+            name,
+        };
+        let group = |file, range: Range<u64>, notes: &[&'static [u8]]| NoteGroup {
+            range: Some(location(file, range.start)..location(file, range.end)),
+            notes: notes.iter().map(|name| note(name)).collect(),
+        };
+        // The sections from files 1, 2 and 4 are consecutive. Those from file 3 are elsewhere.
+        let positions = [(1, 0, 0x20), (2, 1, 0x10), (3, 0, 0x40), (4, 2, 0x10)]
+            .into_iter()
+            .map(|(file, index_in_part, size)| {
+                let part_id = PartId::from_usize(if file == 3 { 1 } else { 0 });
+                (
+                    (FileId::new(0, file), object::SectionIndex(1)),
+                    SectionPosition {
+                        part_id,
+                        index_in_part,
+                        size,
+                    },
+                )
+            })
+            .collect();
+        let merged = merge(
+            vec![
+                group(1, 0..0x20, &[b"a\0", b"b\0", b"b\0"]),
+                group(2, 0..0x10, &[b"a\0", b"b\0"]),
+                group(3, 0..0x40, &[b"a\0", b"b\0"]),
+                group(4, 0..0x8, &[b"a\0", b"c\0"]),
+            ],
+            &positions,
+        );
+        let joined = NoteGroup {
+            range: Some(location(1, 0)..location(2, 0x10)),
+            ..group(1, 0..0x20, &[b"a\0", b"b\0"])
+        };
+        assert_eq!(
+            merged,
+            [
+                joined,
+                group(4, 0..0x8, &[b"a\0", b"c\0"]),
+                group(3, 0..0x40, &[b"a\0", b"b\0"]),
+            ]
+        );
+
+        let mut out = vec![0; encoded_size(&merged) as usize];
+        write(&merged, &mut out, |location| Ok(location.offset)).unwrap();
+        assert_eq!(out.len(), 3 * (2 * 16 + 16));
+    }
+}
@@ -0,0 +1,85 @@
+//! Optionally prints the dependencies between input files. To use this, pass
+//! `--print-dependencies`. For each undefined symbol in each loaded input, we print the file that
+//! referenced the symbol, the file that provided its definition and the name of the symbol. This
+//! covers regular objects, archive members and shared objects, so it shows not just why archive
+//! members were extracted, but also whether a shared object is needed and by whom.
+//!
+//! Each line contains three tab-separated fields, which makes the output easy to turn into a graph:
+//!
+//! ```text
+//! main.o  libfoo.a @ foo.o  foo
+//! ```
+//!
+//! Symbols that remain undefined are reported with `<undefined>` as the providing file.
+
+use crate::args::Args;
+use crate::error::Result;
+use crate::parsing::ParsedInput;
+use crate::resolution::ResolvedFile;
+use crate::resolution::ResolvedGroup;
+use crate::storage::StorageModel;
+use crate::symbol_db::SymbolDb;
+use crate::symbol_db::SymbolId;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+use std::io::Write as _;
+
+pub(crate) fn maybe_print_dependencies<S: StorageModel>(
+    groups: &[ResolvedGroup],
+    symbol_db: &SymbolDb<S>,
+    args: &Args,
+) -> Result {
+    if !args.print_dependencies {
+        return Ok(());
+    }
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    writeln!(out, "# referenced-by\tprovided-by\tsymbol")?;
+    for group in groups {
+        for file in &group.files {
+            let ResolvedFile::Object(obj) = file else {
+                continue;
+            };
+            for (sym_index, sym) in obj.object.symbols.enumerate() {
+                if sym_index.0 == 0 || !sym.is_undefined(LittleEndian) {
+                    continue;
+                }
+                let symbol_id = obj.symbol_id_range.input_to_id(sym_index);
+                let definition = symbol_db.definition(symbol_id);
+                let provider_id = symbol_db.file_id_for_symbol(definition);
+                if provider_id == obj.file_id {
+                    write_dependency(&mut out, obj, "<undefined>", symbol_db, symbol_id)?;
+                    continue;
+                }
+                let provider = symbol_db.file(provider_id);
+                if let ParsedInput::Object(provider_obj) = provider {
+                    let provider_sym = provider_obj
+                        .object
+                        .symbol(definition.to_input(provider_obj.symbol_id_range))?;
+                    if provider_sym.is_undefined(LittleEndian) {
+                        // The definition was canonicalised to a reference in another file.
+                        write_dependency(&mut out, obj, "<undefined>", symbol_db, symbol_id)?;
+                        continue;
+                    }
+                }
+                write_dependency(&mut out, obj, provider, symbol_db, symbol_id)?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn write_dependency<S: StorageModel>(
+    out: &mut impl std::io::Write,
+    referenced_by: impl std::fmt::Display,
+    provided_by: impl std::fmt::Display,
+    symbol_db: &SymbolDb<S>,
+    symbol_id: SymbolId,
+) -> Result {
+    writeln!(
+        out,
+        "{referenced_by}\t{provided_by}\t{}",
+        symbol_db.symbol_name_for_display(symbol_id)
+    )?;
+    Ok(())
+}
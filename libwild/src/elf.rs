@@ -10,9 +10,11 @@ use linker_utils::elf::extract_bits;
 use linker_utils::elf::sht;
 use linker_utils::elf::BitMask;
 use linker_utils::elf::PageMask;
+use linker_utils::elf::RelocationInstruction;
 use linker_utils::elf::RelocationSize;
 use linker_utils::elf::SectionType;
 use object::read::elf::CompressionHeader;
+use object::read::elf::Dyn as _;
 use object::read::elf::FileHeader as _;
 use object::read::elf::ProgramHeader as _;
 use object::read::elf::RelocationSections;
@@ -31,6 +33,12 @@ pub const NON_PIE_START_MEM_ADDRESS: u64 = 0x400_000;
 
 pub(crate) const TLS_MODULE_BASE_SYMBOL_NAME: &str = "_TLS_MODULE_BASE_";
 
+/// The symbol that PowerPC64 code uses to compute its TOC pointer.
+pub(crate) const TOC_SYMBOL_NAME: &str = ".TOC.";
+
+/// The symbol that RISC-V startup code loads into gp.
+pub(crate) const GLOBAL_POINTER_SYMBOL_NAME: &str = "__global_pointer$";
+
 pub(crate) type FileHeader = object::elf::FileHeader64<LittleEndian>;
 pub(crate) type ProgramHeader = object::elf::ProgramHeader64<LittleEndian>;
 pub(crate) type SectionHeader = object::elf::SectionHeader64<LittleEndian>;
@@ -41,6 +49,8 @@ pub(crate) type Rela = object::elf::Rela64<LittleEndian>;
 pub(crate) type GnuHashHeader = object::elf::GnuHashHeader<LittleEndian>;
 pub(crate) type Verneed = object::elf::Verneed<LittleEndian>;
 pub(crate) type Vernaux = object::elf::Vernaux<LittleEndian>;
+pub(crate) type Verdef = object::elf::Verdef<LittleEndian>;
+pub(crate) type Verdaux = object::elf::Verdaux<LittleEndian>;
 pub(crate) type Versym = object::elf::Versym<LittleEndian>;
 pub(crate) type VerdefIterator<'data> = object::read::elf::VerdefIterator<'data, FileHeader>;
 pub(crate) type NoteHeader = object::elf::NoteHeader64<LittleEndian>;
@@ -59,6 +69,9 @@ pub(crate) struct File<'data> {
 
     /// An iterator over the version definitions and the corresponding linked string table index.
     pub(crate) verdef: Option<(VerdefIterator<'data>, object::SectionIndex)>,
+
+    /// Whether we only use the symbols of this file. See `keep_just_symbols`.
+    pub(crate) just_symbols: bool,
 }
 
 impl<'data> File<'data> {
@@ -109,9 +122,26 @@ impl<'data> File<'data> {
             program_headers,
             versym,
             verdef,
+            just_symbols: false,
         })
     }
 
+    /// Drops our sections, keeping just our symbols, whose values are then used as absolute
+    /// addresses. This is for `--just-symbols`, where the file is an image that is loaded
+    /// separately.
+    pub(crate) fn keep_just_symbols(&mut self) {
+        self.sections = SectionTable::default();
+        self.relocations = RelocationSections::default();
+        self.just_symbols = true;
+    }
+
+    pub(crate) fn e_machine(&self) -> u16 {
+        let header: &FileHeader = object::from_bytes(&self.data[..size_of::<FileHeader>()])
+            .unwrap()
+            .0;
+        header.e_machine.get(LittleEndian)
+    }
+
     pub(crate) fn section(&self, index: object::SectionIndex) -> Result<&'data SectionHeader> {
         Ok(self.sections.section(index)?)
     }
@@ -201,6 +231,14 @@ impl<'data> File<'data> {
         };
         let rela_section = self.sections.section(rela_index)?;
         let Some((rela, _)) = rela_section.rela(LittleEndian, self.data)? else {
+            // We only support RELA. REL-format relocations store their addends in the section data,
+            // so ignoring them would silently produce an incorrect output.
+            if SectionType::from_header(rela_section) == sht::REL {
+                bail!(
+                    "Section `{}` uses REL-format relocations, which are not supported",
+                    String::from_utf8_lossy(self.section_name(self.section(index)?)?)
+                );
+            }
             return Ok(&[]);
         };
         Ok(rela)
@@ -219,9 +257,33 @@ impl<'data> File<'data> {
         symbol: &Symbol,
         index: object::SymbolIndex,
     ) -> Result<Option<object::SectionIndex>> {
+        if self.just_symbols {
+            return Ok(None);
+        }
         Ok(self.symbols.symbol_section(LittleEndian, symbol, index)?)
     }
 
+    /// Returns the name of each version that we define, indexed by version index.
+    pub(crate) fn version_names(&self) -> Result<Vec<Option<&'data [u8]>>> {
+        let e = LittleEndian;
+        let mut names = Vec::new();
+        let Some((mut verdef_iterator, link)) = self.verdef.clone() else {
+            return Ok(names);
+        };
+        let strings = self.sections.strings(e, self.data, link)?;
+        while let Some((verdef, mut aux_iterator)) = verdef_iterator.next()? {
+            let version_index = usize::from(verdef.vd_ndx.get(e));
+            let Some(aux) = aux_iterator.next()? else {
+                continue;
+            };
+            if names.len() <= version_index {
+                names.resize(version_index + 1, None);
+            }
+            names[version_index] = Some(aux.name(e, strings)?);
+        }
+        Ok(names)
+    }
+
     pub(crate) fn dynamic_tags(&self) -> Result<&'data [DynamicEntry]> {
         let e = LittleEndian;
         for header in self.program_headers {
@@ -236,6 +298,23 @@ impl<'data> File<'data> {
         }
         Ok(&[])
     }
+
+    /// Returns the strings that dynamic tags of the supplied type refer to, e.g. the names of the
+    /// libraries in `DT_NEEDED` entries.
+    pub(crate) fn dynamic_tag_strings(&self, tag: u32) -> Result<Vec<&'data [u8]>> {
+        let e = LittleEndian;
+        self.dynamic_tags()?
+            .iter()
+            .filter(|entry| entry.d_tag(e) as u32 == tag)
+            .map(|entry| {
+                let value = entry.d_val(e);
+                self.symbols
+                    .strings()
+                    .get(value as u32)
+                    .map_err(|()| anyhow!("Invalid string 0x{value:x} in dynamic tag 0x{tag:x}"))
+            })
+            .collect()
+    }
 }
 
 fn decompress_into(
@@ -362,8 +441,20 @@ pub(crate) const COMPRESSION_HEADER_SIZE: usize =
 
 pub(crate) const GOT_ENTRY_SIZE: u64 = 0x8;
 // TODO: Right now, both x86_64 and AArch64 have 16 byte long entries, but
-// the size should be generic over A: Arch.
+// the size should depend on the architecture.
 pub(crate) const PLT_ENTRY_SIZE: u64 = 0x10;
+/// The size of AArch64 PLT entries when `-z pac-plt` is used.
+pub(crate) const AARCH64_PAC_PLT_ENTRY_SIZE: u64 = 0x18;
+/// The size of x86-64 PLT entries that don't start with an endbr64.
+pub(crate) const X86_64_COMPACT_PLT_ENTRY_SIZE: u64 = 0x8;
+/// The size of PowerPC64 PLT call stubs, which don't fit in `PLT_ENTRY_SIZE`.
+pub(crate) const PPC64_PLT_ENTRY_SIZE: u64 = 0x20;
+/// The size of the PLT header that lazy-binding PLT entries jump to in order to call into the
+/// dynamic loader.
+pub(crate) const PLT_HEADER_SIZE: u64 = 0x10;
+/// The number of GOT entries reserved at the start of the GOT for lazy binding. The first holds the
+/// address of the dynamic section, the other two are filled in by the dynamic loader.
+pub(crate) const NUM_LAZY_GOT_RESERVED_ENTRIES: u64 = 3;
 pub(crate) const RELA_ENTRY_SIZE: u64 = 0x18;
 
 pub(crate) const SYMTAB_ENTRY_SIZE: u64 = size_of::<SymtabEntry>() as u64;
@@ -378,6 +469,35 @@ const _ASSERTS: () = {
 pub(crate) const GNU_NOTE_NAME: &[u8] = b"GNU\0";
 pub(crate) const GNU_NOTE_PROPERTY_ENTRY_SIZE: usize = 16;
 
+/// The size of a property with no data, i.e. just `pr_type` and `pr_datasz`.
+pub(crate) const GNU_NOTE_PROPERTY_HEADER_SIZE: usize = 8;
+
+/// The size of the AArch64 PAuth core info property, which holds a 64 bit platform and a 64 bit
+/// version.
+pub(crate) const GNU_NOTE_PAUTH_CORE_INFO_SIZE: usize = GNU_NOTE_PROPERTY_HEADER_SIZE + 16;
+
+/// Marks the output as opting into sealing of its memory mappings (mseal) by the runtime loader.
+/// This property has no data. Not yet defined by the object crate.
+pub(crate) const GNU_PROPERTY_MEMORY_SEAL: u32 = 3;
+
+/// Marks the output as compatible with Linear Address Masking using 48 or 57 bit user-space
+/// addresses. These are bits of GNU_PROPERTY_X86_FEATURE_1_AND. Not yet defined by the object crate.
+pub(crate) const GNU_PROPERTY_X86_FEATURE_1_LAM_U48: u32 = 1 << 2;
+pub(crate) const GNU_PROPERTY_X86_FEATURE_1_LAM_U57: u32 = 1 << 3;
+
+/// Dynamic tags indicating that the PLT entries start with a BTI landing pad and/or authenticate
+/// the GOT entry. Not yet defined by the object crate.
+pub(crate) const DT_AARCH64_BTI_PLT: u32 = 0x7000_0001;
+pub(crate) const DT_AARCH64_PAC_PLT: u32 = 0x7000_0003;
+
+/// Dynamic tags that tell the dynamic loader how to use AArch64 memory tagging and where to find
+/// the descriptors of tagged globals. Not yet defined by the object crate.
+pub(crate) const DT_AARCH64_MEMTAG_MODE: u32 = 0x7000_0009;
+pub(crate) const DT_AARCH64_MEMTAG_HEAP: u32 = 0x7000_000b;
+pub(crate) const DT_AARCH64_MEMTAG_STACK: u32 = 0x7000_000c;
+pub(crate) const DT_AARCH64_MEMTAG_GLOBALS: u32 = 0x7000_000d;
+pub(crate) const DT_AARCH64_MEMTAG_GLOBALSSZ: u32 = 0x7000_000f;
+
 /// For additional information on Elf_Prop, see
 /// Linux Extensions to gABI at https://gitlab.com/x86-psABIs/Linux-ABI.
 ///
@@ -456,6 +576,17 @@ pub(crate) fn write_relocation_to_buffer(
                 byte_size <= output.len(),
                 "Relocation outside of bounds of section"
             );
+            // We don't know whether the field is signed or unsigned, so accept anything that fits
+            // as either. This still catches references that are too far away, e.g. because the
+            // output is larger than 4 GiB.
+            if byte_size < size_of::<u64>() {
+                let bits = byte_size as u32 * 8;
+                let signed = value as i64;
+                ensure!(
+                    value < 1 << bits || (signed < 0 && signed >= -(1 << (bits - 1))),
+                    "Relocation value 0x{value:x} doesn't fit in {byte_size} bytes"
+                );
+            }
             let value_bytes = value.to_le_bytes();
             output[..byte_size].copy_from_slice(&value_bytes[..byte_size]);
         }
@@ -465,13 +596,52 @@ pub(crate) fn write_relocation_to_buffer(
         }) => {
             let extracted_value = extract_bits(value, range.start, range.end);
             let negative = (value as i64) < 0;
-            insn.write_to_value(extracted_value, negative, &mut output[..4]);
+            insn.write_to_value(extracted_value, negative, output);
         }
     }
 
     Ok(())
 }
 
+/// Reads the value that a relocation of the supplied size would overwrite. This is needed for
+/// relocations that adjust the existing value rather than replacing it.
+pub(crate) fn read_relocation_from_buffer(size: RelocationSize, input: &[u8]) -> Result<u64> {
+    match size {
+        RelocationSize::ByteSize(byte_size) => {
+            let bytes = input
+                .get(..byte_size)
+                .context("Relocation outside of bounds of section")?;
+            let mut value_bytes = [0; 8];
+            value_bytes[..byte_size].copy_from_slice(bytes);
+            Ok(u64::from_le_bytes(value_bytes))
+        }
+        RelocationSize::BitMasking(BitMask {
+            instruction: RelocationInstruction::RiscV(insn),
+            ..
+        }) => insn
+            .read_value(input)
+            .with_context(|| format!("Cannot read existing value of {insn:?}")),
+        RelocationSize::BitMasking(BitMask { instruction, .. }) => {
+            bail!("Cannot read existing value of {instruction:?}")
+        }
+    }
+}
+
+/// Returns `value` truncated to the number of bits that a relocation of the supplied size writes.
+/// Arithmetic relocations are computed modulo the size of their field, so intermediate values
+/// needn't fit.
+pub(crate) fn truncate_relocation_value(size: RelocationSize, value: u64) -> u64 {
+    let bits = match size {
+        RelocationSize::ByteSize(byte_size) => byte_size as u32 * 8,
+        RelocationSize::BitMasking(BitMask { range, .. }) => range.end - range.start,
+    };
+    if bits >= u64::BITS {
+        value
+    } else {
+        value & ((1 << bits) - 1)
+    }
+}
+
 pub(crate) fn slice_from_all_bytes_mut<T: object::Pod>(data: &mut [u8]) -> &mut [T] {
     object::slice_from_bytes_mut(data, data.len() / size_of::<T>())
         .unwrap()
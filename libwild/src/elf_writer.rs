@@ -1,19 +1,25 @@
 use self::elf::get_page_mask;
 use self::elf::NoteHeader;
 use self::elf::NoteProperty;
-use self::elf::GNU_NOTE_PROPERTY_ENTRY_SIZE;
+use self::elf::GNU_NOTE_PROPERTY_HEADER_SIZE;
+use self::elf::GNU_PROPERTY_MEMORY_SEAL;
 use self::elf::TLS_MODULE_BASE_SYMBOL_NAME;
 use crate::alignment;
-use crate::arch::Arch;
-use crate::arch::Relaxation as _;
+use crate::arch::Architecture;
+use crate::arch::PltFeatures;
 use crate::args::Args;
 use crate::args::BuildIdOption;
 use crate::args::FileWriteMode;
+use crate::args::Magic;
+use crate::args::MmapOutput;
+use crate::args::OutputFormat;
 use crate::args::OutputKind;
 use crate::args::WRITE_VERIFY_ALLOCATIONS_ENV;
 use crate::debug_assert_bail;
 use crate::elf;
+use crate::elf::read_relocation_from_buffer;
 use crate::elf::slice_from_all_bytes_mut;
+use crate::elf::truncate_relocation_value;
 use crate::elf::write_relocation_to_buffer;
 use crate::elf::DynamicEntry;
 use crate::elf::EhFrameHdr;
@@ -28,7 +34,10 @@ use crate::elf::Verneed;
 use crate::elf::Versym;
 use crate::elf::GNU_NOTE_NAME;
 use crate::error::Result;
+use crate::input_data::PRELUDE_FILE_ID;
 use crate::layout::compute_allocations;
+use crate::layout::gnu_property_notes_descriptor_size;
+use crate::layout::is_lazily_bound;
 use crate::layout::DynamicLayout;
 use crate::layout::EpilogueLayout;
 use crate::layout::FileLayout;
@@ -36,9 +45,11 @@ use crate::layout::GroupLayout;
 use crate::layout::HeaderInfo;
 use crate::layout::InternalSymbols;
 use crate::layout::Layout;
+use crate::layout::LazyPltHeader;
 use crate::layout::NonAddressableCounts;
 use crate::layout::ObjectLayout;
 use crate::layout::OutputRecordLayout;
+use crate::layout::PltLayout;
 use crate::layout::PreludeLayout;
 use crate::layout::Resolution;
 use crate::layout::ResolutionFlags;
@@ -50,17 +61,22 @@ use crate::output_section_id::OutputSectionId;
 use crate::output_section_id::OutputSections;
 use crate::output_section_map::OutputSectionMap;
 use crate::output_section_part_map::OutputSectionPartMap;
+use crate::parsing::InternalSymDefInfo;
+use crate::parsing::ParsedInput;
 use crate::part_id;
-use crate::program_segments::STACK;
+use crate::relaxation_stats::RelaxationStats;
 use crate::resolution::SectionSlot;
 use crate::resolution::ValueFlags;
+use crate::separate_debug::DebugSplit;
 use crate::sharding::ShardKey;
 use crate::slice::slice_take_prefix_mut;
 use crate::slice::take_first_mut;
 use crate::storage::StorageModel;
 use crate::string_merging::get_merged_string_output_address;
 use crate::symbol_db::SymbolDb;
+use crate::symbol_db::SymbolId;
 use crate::threading::prelude::*;
+use crate::thunks::ThunkTarget;
 use ahash::AHashMap;
 use anyhow::anyhow;
 use anyhow::bail;
@@ -70,30 +86,40 @@ use linker_utils::elf::secnames::DEBUG_RANGES_SECTION_NAME;
 use linker_utils::elf::secnames::DYNSYM_SECTION_NAME_STR;
 use linker_utils::elf::shf;
 use linker_utils::elf::sht;
+use linker_utils::elf::BitMask;
 use linker_utils::elf::DynamicRelocationKind;
+use linker_utils::elf::RelocationInstruction;
 use linker_utils::elf::RelocationKind;
+use linker_utils::elf::RelocationKindInfo;
+use linker_utils::elf::RelocationSize;
 use linker_utils::elf::SectionFlags;
+use linker_utils::elf::SectionType;
+use linker_utils::ppc64::local_entry_offset;
 use linker_utils::relaxation::RelocationModifier;
+use linker_utils::riscv64::RiscVInstruction;
 use memmap2::MmapOptions;
+use object::elf::GNU_PROPERTY_AARCH64_FEATURE_PAUTH;
 use object::elf::NT_GNU_BUILD_ID;
 use object::elf::NT_GNU_PROPERTY_TYPE_0;
 use object::from_bytes_mut;
+use object::read::elf::FileHeader as _;
 use object::read::elf::Rela;
 use object::read::elf::Sym as _;
 use object::LittleEndian;
 use std::fmt::Display;
 use std::io::Write;
-use std::marker::PhantomData;
 use std::ops::BitAnd;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::ops::Range;
 use std::ops::Sub;
+use std::os::unix::fs::FileExt as _;
 use std::path::Path;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
+use std::sync::Mutex;
 use tracing::debug_span;
 use tracing::instrument;
 use uuid::Uuid;
@@ -118,6 +144,9 @@ pub struct Output {
     path: Arc<Path>,
     creator: FileCreator,
     file_write_mode: FileWriteMode,
+    mmap_output: MmapOutput,
+    dry_run: bool,
+    output_format: OutputFormat,
 }
 
 enum FileCreator {
@@ -127,13 +156,24 @@ enum FileCreator {
     },
     Regular {
         file_size: Option<u64>,
+        debug_split: Option<DebugSplit>,
     },
 }
 
 pub(crate) struct SizedOutput {
-    file: std::fs::File,
+    /// The file that we're writing to. None if this is a dry run, in which case we write only to
+    /// memory.
+    file: Option<std::fs::File>,
     out: OutputBuffer,
     path: Arc<Path>,
+
+    /// The file that we're writing debug info to, if it's going in a separate file.
+    debug: Option<Box<DebugOutput>>,
+}
+
+struct DebugOutput {
+    output: SizedOutput,
+    split: DebugSplit,
 }
 
 enum OutputBuffer {
@@ -142,13 +182,20 @@ enum OutputBuffer {
 }
 
 impl OutputBuffer {
-    fn new(file: &std::fs::File, file_size: u64) -> Self {
-        Self::new_mmapped(file, file_size)
-            .unwrap_or_else(|| Self::InMemory(vec![0; file_size as usize]))
+    fn new(file: &std::fs::File, file_size: usize, mmap_output: MmapOutput) -> Self {
+        let use_mmap = match mmap_output {
+            MmapOutput::Auto => !crate::fs::is_mmap_unfriendly(file),
+            MmapOutput::Always => true,
+            MmapOutput::Never => false,
+        };
+        use_mmap
+            .then(|| Self::new_mmapped(file, file_size))
+            .flatten()
+            .unwrap_or_else(|| Self::InMemory(vec![0; file_size]))
     }
 
-    fn new_mmapped(file: &std::fs::File, file_size: u64) -> Option<Self> {
-        file.set_len(file_size).ok()?;
+    fn new_mmapped(file: &std::fs::File, file_size: usize) -> Option<Self> {
+        file.set_len(file_size as u64).ok()?;
         let mmap = unsafe { MmapOptions::new().map_mut(file) }.ok()?;
         Some(Self::Mmap(mmap))
     }
@@ -183,7 +230,9 @@ struct SectionAllocation {
 
 impl Output {
     pub(crate) fn new(args: &Args) -> Output {
-        if args.num_threads.get() > 1 {
+        // When writing a raw binary, we build the ELF image in memory, then write out just the
+        // loadable parts of it, so there's no point creating the file in advance.
+        if args.num_threads.get() > 1 && !args.dry_run && args.output_format == OutputFormat::Elf {
             let (sized_output_sender, sized_output_recv) = std::sync::mpsc::channel();
             Output {
                 path: args.output.clone(),
@@ -192,17 +241,30 @@ impl Output {
                     sized_output_recv,
                 },
                 file_write_mode: args.file_write_mode,
+                mmap_output: args.mmap_output,
+                dry_run: false,
+                output_format: args.output_format,
             }
         } else {
             Output {
                 path: args.output.clone(),
-                creator: FileCreator::Regular { file_size: None },
+                creator: FileCreator::Regular {
+                    file_size: None,
+                    debug_split: None,
+                },
                 file_write_mode: args.file_write_mode,
+                mmap_output: args.mmap_output,
+                dry_run: args.dry_run,
+                output_format: args.output_format,
             }
         }
     }
 
-    pub(crate) fn set_size(&mut self, size: u64) {
+    /// Sets the size of the output, which is then created. If `debug_split` is set, the debug info
+    /// goes in a separate file, which is created too.
+    pub(crate) fn set_size(&mut self, size: u64, debug_split: Option<DebugSplit>) {
+        // On a dry run, we don't write any files, so there's no point splitting the output.
+        let debug_split = debug_split.filter(|_| !self.dry_run);
         match &mut self.creator {
             FileCreator::Background {
                 sized_output_sender,
@@ -214,6 +276,7 @@ impl Output {
                 let path = self.path.clone();
 
                 let write_mode = self.file_write_mode;
+                let mmap_output = self.mmap_output;
 
                 crate::threading::spawn(move || {
                     if write_mode == FileWriteMode::UnlinkAndReplace {
@@ -239,25 +302,32 @@ impl Output {
                     }
 
                     // Create the output file.
-                    let sized_output = SizedOutput::new(path, size, write_mode);
+                    let sized_output =
+                        SizedOutput::new_split(path, size, debug_split, write_mode, mmap_output);
 
                     // Pass it to the main thread, so that it can start writing it once layout finishes.
                     let _ = sender.send(sized_output);
                 });
             }
-            FileCreator::Regular { file_size } => *file_size = Some(size),
+            FileCreator::Regular {
+                file_size,
+                debug_split: split,
+            } => {
+                *file_size = Some(size);
+                *split = debug_split;
+            }
         }
     }
 
     #[tracing::instrument(skip_all, name = "Write output file")]
-    pub fn write<'data, 'symbol_db, S: StorageModel, A: Arch>(
+    pub fn write<'data, 'symbol_db, S: StorageModel>(
         &mut self,
         layout: &Layout<'data, 'symbol_db, S>,
     ) -> Result<SizedOutput> {
         if layout.args().write_layout {
             write_layout(layout)?;
         }
-        let mut sized_output = match &self.creator {
+        let mut sized_output = match &mut self.creator {
             FileCreator::Background {
                 sized_output_sender,
                 sized_output_recv,
@@ -265,25 +335,57 @@ impl Output {
                 assert!(sized_output_sender.is_none(), "set_size was never called");
                 wait_for_sized_output(sized_output_recv)?
             }
-            FileCreator::Regular { file_size } => {
-                delete_old_output(&self.path);
+            FileCreator::Regular {
+                file_size,
+                debug_split,
+            } => {
                 let file_size = file_size.context("set_size was never called")?;
-                self.create_file_non_lazily(file_size)?
+                let debug_split = debug_split.take();
+                if self.dry_run || self.output_format == OutputFormat::Binary {
+                    SizedOutput::in_memory(self.path.clone(), file_size)?
+                } else {
+                    delete_old_output(&self.path);
+                    self.create_file_non_lazily(file_size, debug_split)?
+                }
             }
         };
-        sized_output.write::<S, A>(layout)?;
-        sized_output.flush()?;
+        sized_output.write::<S>(layout)?;
+        sized_output.flush(layout.args().output_kind())?;
+        if self.dry_run {
+            print_dry_run_report(layout, sized_output.out.len())?;
+        } else if self.output_format == OutputFormat::Binary {
+            delete_old_output(&self.path);
+            crate::binary_output::write(layout, &sized_output.out, &self.path)?;
+        }
         // This triggers writing our .trace file if any. See output_trace module.
         tracing::trace!(output_write_complete = true);
         Ok(sized_output)
     }
 
     #[tracing::instrument(skip_all, name = "Create output file")]
-    fn create_file_non_lazily(&mut self, file_size: u64) -> Result<SizedOutput> {
-        SizedOutput::new(self.path.clone(), file_size, self.file_write_mode)
+    fn create_file_non_lazily(
+        &mut self,
+        file_size: u64,
+        debug_split: Option<DebugSplit>,
+    ) -> Result<SizedOutput> {
+        SizedOutput::new_split(
+            self.path.clone(),
+            file_size,
+            debug_split,
+            self.file_write_mode,
+            self.mmap_output,
+        )
     }
 }
 
+/// We write the whole output via a single buffer, so it needs to fit in our address space. This
+/// only matters for 32 bit hosts.
+fn addressable_file_size(file_size: u64) -> Result<usize> {
+    usize::try_from(file_size).map_err(|_| {
+        anyhow!("Output file size of {file_size} bytes exceeds the address space of this host")
+    })
+}
+
 /// Delete the old output file. Note, this is only used when running from a single thread.
 #[tracing::instrument(skip_all, name = "Delete old output")]
 fn delete_old_output(path: &Path) {
@@ -296,7 +398,13 @@ fn wait_for_sized_output(sized_output_recv: &Receiver<Result<SizedOutput>>) -> R
 }
 
 impl SizedOutput {
-    fn new(path: Arc<Path>, file_size: u64, write_mode: FileWriteMode) -> Result<SizedOutput> {
+    fn new(
+        path: Arc<Path>,
+        file_size: u64,
+        write_mode: FileWriteMode,
+        mmap_output: MmapOutput,
+    ) -> Result<SizedOutput> {
+        let file_size = addressable_file_size(file_size)?;
         let mut open_options = std::fs::OpenOptions::new();
         match write_mode {
             FileWriteMode::UnlinkAndReplace => {
@@ -312,25 +420,119 @@ impl SizedOutput {
             .create(true)
             .open(&path)
             .with_context(|| format!("Failed to open `{}`", path.display()))?;
-        let out = OutputBuffer::new(&file, file_size);
-        Ok(SizedOutput { file, out, path })
+        let out = OutputBuffer::new(&file, file_size, mmap_output);
+        Ok(SizedOutput {
+            file: Some(file),
+            out,
+            path,
+            debug: None,
+        })
+    }
+
+    /// Creates our output, which would be `file_size` bytes if we weren't splitting the debug info
+    /// out of it as per `debug_split`.
+    fn new_split(
+        path: Arc<Path>,
+        file_size: u64,
+        debug_split: Option<DebugSplit>,
+        write_mode: FileWriteMode,
+        mmap_output: MmapOutput,
+    ) -> Result<SizedOutput> {
+        let Some(split) = debug_split else {
+            return SizedOutput::new(path, file_size, write_mode, mmap_output);
+        };
+        let mut sized_output =
+            SizedOutput::new(path, split.debug_start as u64, write_mode, mmap_output)?;
+        if write_mode == FileWriteMode::UnlinkAndReplace {
+            // A debugger might have the old debug file mapped, so we shouldn't truncate it.
+            let _ = std::fs::remove_file(&split.path);
+        }
+        let output = SizedOutput::new(
+            split.path.clone(),
+            split.file_size as u64,
+            write_mode,
+            mmap_output,
+        )?;
+        sized_output.debug = Some(Box::new(DebugOutput { output, split }));
+        Ok(sized_output)
+    }
+
+    fn in_memory(path: Arc<Path>, file_size: u64) -> Result<SizedOutput> {
+        Ok(SizedOutput {
+            file: None,
+            out: OutputBuffer::InMemory(vec![0; addressable_file_size(file_size)?]),
+            path,
+            debug: None,
+        })
+    }
+
+    /// Splits our output, including the debug file if any, into buffers for each section.
+    fn section_buffers<S: StorageModel>(
+        &mut self,
+        layout: &Layout<S>,
+    ) -> OutputSectionMap<&mut [u8]> {
+        let debug = self.debug.as_mut().map(|debug| {
+            (
+                debug.split.debug_start,
+                &mut debug.output.out[debug.split.debug_offset..],
+            )
+        });
+        split_output_into_sections(layout, &mut self.out, debug)
     }
 
-    pub(crate) fn write<S: StorageModel, A: Arch>(&mut self, layout: &Layout<S>) -> Result {
-        self.write_file_contents::<S, A>(layout)?;
+    pub(crate) fn write<S: StorageModel>(&mut self, layout: &Layout<S>) -> Result {
+        self.fill_sections(layout);
+        self.write_file_contents::<S>(layout)?;
         if layout.args().validate_output {
             crate::validation::validate_bytes(layout, &self.out)?;
         }
 
         if layout.args().should_write_eh_frame_hdr {
-            let mut section_buffers = split_output_into_sections(layout, &mut self.out);
+            let mut section_buffers = self.section_buffers(layout);
             sort_eh_frame_hdr_entries(section_buffers.get_mut(output_section_id::EH_FRAME_HDR));
         }
 
+        if !layout.memtag_globals.is_empty() {
+            let mut section_buffers = self.section_buffers(layout);
+            let out = section_buffers.get_mut(output_section_id::MEMTAG_GLOBALS_DYNAMIC);
+            // We allocated space for the largest possible descriptors. Any left over is zero.
+            out.get_mut(..layout.memtag_globals.len())
+                .ok_or_else(|| insufficient_allocation(".memtag.globals.dynamic"))?
+                .copy_from_slice(&layout.memtag_globals);
+        }
+
         self.write_gnu_build_id_note(&layout.args().build_id, layout)?;
+        if let Some(debug) = self.debug.as_mut() {
+            crate::separate_debug::finish_debug_file(
+                &debug.split,
+                &mut self.out,
+                &mut debug.output.out,
+            )?;
+        }
         Ok(())
     }
 
+    /// Fills sections that have a fill pattern. This needs to be done before we write anything
+    /// else, since whatever isn't subsequently overwritten is what gets filled.
+    fn fill_sections<S: StorageModel>(&mut self, layout: &Layout<S>) {
+        if layout
+            .output_sections
+            .ids_with_info()
+            .all(|(_, info)| info.fill.is_none())
+        {
+            return;
+        }
+        let mut section_buffers = self.section_buffers(layout);
+        for (section_id, info) in layout.output_sections.ids_with_info() {
+            if let Some(pattern) = info.fill {
+                let out = section_buffers.get_mut(section_id);
+                for (byte, fill) in out.iter_mut().zip(pattern.iter().cycle()) {
+                    *byte = *fill;
+                }
+            }
+        }
+    }
+
     fn write_gnu_build_id_note<S: StorageModel>(
         &mut self,
         build_id_option: &BuildIdOption,
@@ -351,7 +553,7 @@ impl SizedOutput {
             BuildIdOption::None => return Ok(()),
         };
 
-        let mut buffers = split_output_into_sections(layout, &mut self.out);
+        let mut buffers = self.section_buffers(layout);
         let e = LittleEndian;
         let (note_header, mut rest) =
             from_bytes_mut::<NoteHeader>(buffers.get_mut(output_section_id::NOTE_GNU_BUILD_ID))
@@ -370,34 +572,61 @@ impl SizedOutput {
 
     #[instrument(skip_all, name = "Compute build ID")]
     fn compute_hash(&self) -> blake3::Hash {
-        blake3::Hasher::new().update_rayon(&self.out).finalize()
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_rayon(&self.out);
+        if let Some(debug) = &self.debug {
+            hasher.update_rayon(&debug.output.out[debug.split.debug_offset..]);
+        }
+        hasher.finalize()
     }
 
-    fn flush(&mut self) -> Result {
-        match &self.out {
-            OutputBuffer::Mmap(_) => {}
-            OutputBuffer::InMemory(bytes) => self
-                .file
-                .write_all(bytes)
-                .with_context(|| format!("Failed to write to {}", self.path.display()))?,
+    /// Writes out anything not yet written, then makes the file executable unless `output_kind`
+    /// is a relocatable object, which the system linkers leave non-executable.
+    fn flush(&mut self, output_kind: OutputKind) -> Result {
+        if let Some(debug) = self.debug.as_mut() {
+            debug.output.write_out()?;
         }
+        self.write_out()?;
 
         // Making the file executable is best-effort only. For example if we're writing to a pipe or
         // something, it isn't going to work and that's OK.
-        let _ = crate::fs::make_executable(&self.file);
+        if let Some(file) = self.file.as_mut() {
+            if output_kind != OutputKind::RelocatableObject {
+                let _ = crate::fs::make_executable(file);
+            }
+        }
+
+        Ok(())
+    }
 
+    /// Writes out anything that we wrote to memory rather than to a mapping of the file.
+    fn write_out(&mut self) -> Result {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        match &self.out {
+            OutputBuffer::Mmap(_) => {}
+            OutputBuffer::InMemory(bytes) => {
+                // Set the length first, since if we're updating in place, the old file may have
+                // been longer. This is a no-op for pipes etc, so errors are ignored.
+                let _ = file.set_len(bytes.len() as u64);
+                file.write_all_at(bytes, 0)
+                    .with_context(|| format!("Failed to write to {}", self.path.display()))?;
+            }
+        }
         Ok(())
     }
 
     #[tracing::instrument(skip_all, name = "Write data to file")]
-    pub(crate) fn write_file_contents<'data, 'symbol_db, S: StorageModel, A: Arch>(
+    pub(crate) fn write_file_contents<'data, 'symbol_db, S: StorageModel>(
         &mut self,
         layout: &Layout<'data, 'symbol_db, S>,
     ) -> Result {
-        let mut section_buffers = split_output_into_sections(layout, &mut self.out);
+        let mut section_buffers = self.section_buffers(layout);
 
         let mut writable_buckets = split_buffers_by_alignment(&mut section_buffers, layout);
         let groups_and_buffers = split_output_by_group(layout, &mut writable_buckets);
+        let relaxation_stats = Mutex::new(RelaxationStats::default());
         groups_and_buffers
             .into_par_iter()
             .try_for_each(|(group, mut buffers)| -> Result {
@@ -407,18 +636,30 @@ impl SizedOutput {
                     group.strtab_start_offset,
                     &mut buffers,
                     group.eh_frame_start_address,
+                    group.rela_plt_start_address,
+                    group.plt_got_start_address,
                 );
 
                 for file in &group.files {
-                    file.write::<S, A>(&mut buffers, &mut table_writer, layout)
+                    file.write::<S>(&mut buffers, &mut table_writer, layout)
                         .with_context(|| format!("Failed copying from {file} to output file"))?;
                 }
                 table_writer
                     .validate_empty(&group.mem_sizes)
                     .with_context(|| format!("validate_empty failed for {group}"))?;
+                if let Some(stats) = table_writer.relaxation_stats.take() {
+                    relaxation_stats.lock().unwrap().merge(stats);
+                }
                 Ok(())
             })?;
 
+        if layout.args().print_relaxations {
+            relaxation_stats
+                .into_inner()
+                .unwrap()
+                .print(layout.args().arch());
+        }
+
         for (output_section_id, section) in layout.output_sections.ids_with_info() {
             let relocations = layout
                 .relocation_statistics
@@ -471,9 +712,12 @@ fn split_output_by_group<'layout, 'data, 'symbol_db, 'out, S: StorageModel>(
         .collect()
 }
 
+/// Splits `data` into buffers for each section. If `debug` is set, then sections from its offset
+/// onwards are instead taken from the supplied debug file buffer.
 fn split_output_into_sections<'out, S: StorageModel>(
     layout: &Layout<S>,
     mut data: &'out mut [u8],
+    mut debug: Option<(usize, &'out mut [u8])>,
 ) -> OutputSectionMap<&'out mut [u8]> {
     let mut section_allocations = Vec::with_capacity(layout.section_layouts.len());
     layout.section_layouts.for_each(|id, s| {
@@ -491,6 +735,10 @@ fn split_output_into_sections<'out, S: StorageModel>(
     let mut section_data = OutputSectionMap::with_size(section_allocations.len());
     let mut offset = 0;
     for a in section_allocations {
+        if let Some((debug_start, debug_data)) = debug.take_if(|(start, _)| a.offset >= *start) {
+            data = debug_data;
+            offset = debug_start;
+        }
         let Some(padding) = a.offset.checked_sub(offset) else {
             panic!(
                 "Offsets went backward when splitting output file {offset} to {}",
@@ -533,18 +781,23 @@ fn write_program_headers<S: StorageModel>(
 ) -> Result {
     for segment_layout in &layout.segment_layouts.segments {
         let segment_sizes = &segment_layout.sizes;
-        let segment_id = segment_layout.id;
+        let segment_type = segment_layout.segment_type;
         let segment_header = program_headers_out.take_header()?;
+        let is_paged = layout.args().magic == Magic::Demand;
         let mut alignment = segment_sizes.alignment;
-        if segment_id.segment_type() == object::elf::PT_LOAD {
-            alignment = alignment.max(crate::alignment::PAGE);
+        if segment_type == object::elf::PT_LOAD && is_paged {
+            alignment = alignment.max(layout.args().max_page_size);
         }
         let e = LittleEndian;
-        segment_header.p_type.set(e, segment_id.segment_type());
+        segment_header.p_type.set(e, segment_type);
 
         // Support executable stack (Wild defaults to non-executable stack)
-        let mut segment_flags = segment_id.segment_flags();
-        if segment_id == STACK && layout.args().execstack {
+        let mut segment_flags = segment_layout.segment_flags;
+        if segment_type == object::elf::PT_LOAD && !is_paged {
+            // Our single loadable segment contains code as well as writable data.
+            segment_flags = object::elf::PF_R | object::elf::PF_W | object::elf::PF_X;
+        }
+        if segment_type == object::elf::PT_GNU_STACK && layout.args().execstack {
             segment_flags |= object::elf::PF_X;
         }
         segment_header.p_flags.set(e, segment_flags);
@@ -552,7 +805,9 @@ fn write_program_headers<S: StorageModel>(
             .p_offset
             .set(e, segment_sizes.file_offset as u64);
         segment_header.p_vaddr.set(e, segment_sizes.mem_offset);
-        segment_header.p_paddr.set(e, segment_sizes.mem_offset);
+        segment_header
+            .p_paddr
+            .set(e, segment_layout.physical_address);
         segment_header
             .p_filesz
             .set(e, segment_sizes.file_size as u64);
@@ -562,13 +817,32 @@ fn write_program_headers<S: StorageModel>(
     Ok(())
 }
 
-fn populate_file_header<S: StorageModel, A: Arch>(
+/// Returns the e_flags for our output, which are derived from those of our input objects.
+pub(crate) fn output_e_flags<S: StorageModel>(layout: &Layout<S>) -> Result<u32> {
+    let e = LittleEndian;
+    layout.args().arch().merge_e_flags(
+        layout
+            .group_layouts
+            .iter()
+            .flat_map(|group| &group.files)
+            .filter_map(|file| match file {
+                FileLayout::Object(obj) => FileHeader::parse(obj.object.data).ok(),
+                _ => None,
+            })
+            .map(|header| header.e_flags(e)),
+    )
+}
+
+fn populate_file_header<S: StorageModel>(
     layout: &Layout<S>,
     header_info: &HeaderInfo,
     header: &mut FileHeader,
 ) -> Result {
     let args = layout.args();
-    let ty = if args.output_kind().is_relocatable() {
+    let is_relocatable_output = args.output_kind() == OutputKind::RelocatableObject;
+    let ty = if is_relocatable_output {
+        object::elf::ET_REL
+    } else if args.output_kind().is_relocatable() {
         object::elf::ET_DYN
     } else {
         object::elf::ET_EXEC
@@ -582,20 +856,41 @@ fn populate_file_header<S: StorageModel, A: Arch>(
     header.e_ident.abi_version = 0;
     header.e_ident.padding = Default::default();
     header.e_type.set(e, ty);
-    header.e_machine.set(e, A::elf_header_arch_magic());
+    header.e_machine.set(e, args.arch().elf_header_arch_magic());
     header.e_version.set(e, u32::from(object::elf::EV_CURRENT));
     header.e_entry.set(e, layout.entry_symbol_address()?);
-    header.e_phoff.set(e, elf::PHEADER_OFFSET);
-    header.e_shoff.set(
+    header.e_phoff.set(
         e,
-        u64::from(elf::FILE_HEADER_SIZE) + header_info.program_headers_size(),
+        if is_relocatable_output {
+            0
+        } else {
+            elf::PHEADER_OFFSET
+        },
     );
-    header.e_flags.set(e, 0);
+    header.e_flags.set(e, output_e_flags::<S>(layout)?);
     header.e_ehsize.set(e, elf::FILE_HEADER_SIZE);
-    header.e_phentsize.set(e, elf::PROGRAM_HEADER_SIZE);
+    header.e_phentsize.set(
+        e,
+        if is_relocatable_output {
+            0
+        } else {
+            elf::PROGRAM_HEADER_SIZE
+        },
+    );
     header
         .e_phnum
-        .set(e, header_info.active_segment_ids.len() as u16);
+        .set(e, header_info.num_program_headers as u16);
+    if args.strip_section_headers {
+        header.e_shoff.set(e, 0);
+        header.e_shentsize.set(e, 0);
+        header.e_shnum.set(e, 0);
+        header.e_shstrndx.set(e, 0);
+        return Ok(());
+    }
+    header.e_shoff.set(
+        e,
+        u64::from(elf::FILE_HEADER_SIZE) + header_info.program_headers_size(),
+    );
     header.e_shentsize.set(e, elf::SECTION_HEADER_SIZE);
     header
         .e_shnum
@@ -611,18 +906,18 @@ fn populate_file_header<S: StorageModel, A: Arch>(
 }
 
 impl<'data> FileLayout<'data> {
-    fn write<'symbol_db, S: StorageModel, A: Arch>(
+    fn write<'symbol_db, S: StorageModel>(
         &self,
         buffers: &mut OutputSectionPartMap<&mut [u8]>,
         table_writer: &mut TableWriter,
         layout: &Layout<'data, 'symbol_db, S>,
     ) -> Result {
         match self {
-            FileLayout::Object(s) => s.write_file::<S, A>(buffers, table_writer, layout)?,
-            FileLayout::Prelude(s) => s.write_file::<S, A>(buffers, table_writer, layout)?,
-            FileLayout::Epilogue(s) => s.write_file::<S, A>(buffers, table_writer, layout)?,
+            FileLayout::Object(s) => s.write_file::<S>(buffers, table_writer, layout)?,
+            FileLayout::Prelude(s) => s.write_file::<S>(buffers, table_writer, layout)?,
+            FileLayout::Epilogue(s) => s.write_file::<S>(buffers, table_writer, layout)?,
             FileLayout::NotLoaded => {}
-            FileLayout::Dynamic(s) => s.write_file::<S, A>(table_writer, layout)?,
+            FileLayout::Dynamic(s) => s.write_file::<S>(table_writer, layout)?,
         }
         Ok(())
     }
@@ -685,10 +980,36 @@ impl<'out> VersionWriter<'out> {
 
 struct TableWriter<'data, 'layout, 'out> {
     output_kind: OutputKind,
+    arch: Architecture,
+    plt_layout: PltLayout,
+
+    /// The base address used by GOT-relative relocations.
+    got_base: u64,
+
     got: &'out mut [u64],
     plt_got: &'out mut [u8],
+    plt_sec: &'out mut [u8],
     rela_plt: &'out mut [elf::Rela],
+
+    /// The address of the next entry that we'll write in .plt.got.
+    next_plt_got_address: u64,
+
+    /// The address of the PLT header that lazy PLT entries jump to. Only set if we're doing lazy
+    /// binding.
+    plt_header_address: Option<u64>,
+
+    /// The index in .rela.plt of the next entry that we'll write. Lazy PLT entries need to pass this
+    /// to the dynamic loader.
+    next_rela_plt_index: u64,
+
     tls: Range<u64>,
+
+    /// The address that the thread pointer points to in an executable.
+    thread_pointer: u64,
+
+    /// How far each module's dynamic thread pointer is from the start of its TLS block.
+    tls_dtv_offset: u64,
+
     rela_dyn_relative: &'out mut [crate::elf::Rela],
     rela_dyn_general: &'out mut [crate::elf::Rela],
     dynsym_writer: SymbolTableWriter<'data, 'layout, 'out>,
@@ -702,6 +1023,14 @@ struct TableWriter<'data, 'layout, 'out> {
 
     dynamic: DynamicEntriesWriter<'out>,
     version_writer: VersionWriter<'out>,
+
+    /// Present only if we're going to print statistics about relaxations.
+    relaxation_stats: Option<RelaxationStats>,
+
+    /// The values computed by the high-part relocations in the section that we're currently
+    /// applying relocations to, keyed by the address of the high part. Used by `PairedLow`
+    /// relocations, which reference the high part.
+    high_part_values: AHashMap<u64, u64>,
 }
 
 impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
@@ -711,29 +1040,54 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
         strtab_start_offset: u32,
         buffers: &mut OutputSectionPartMap<&'out mut [u8]>,
         eh_frame_start_address: u64,
+        rela_plt_start_address: u64,
+        plt_got_start_address: u64,
     ) -> TableWriter<'data, 'layout, 'out> {
         let dynsym_writer =
             SymbolTableWriter::new_dynamic(dynstr_start_offset, buffers, &layout.output_sections);
         let debug_symbol_writer =
             SymbolTableWriter::new(strtab_start_offset, buffers, &layout.output_sections);
 
-        Self::new(
+        let mut table_writer = Self::new(
             layout.args().output_kind(),
+            layout.plt_layout,
+            layout.got_base(),
             layout.tls_start_address()..layout.tls_end_address(),
+            layout.thread_pointer_address(),
+            layout.args().arch(),
             buffers,
             dynsym_writer,
             debug_symbol_writer,
             eh_frame_start_address,
-        )
+            layout.prelude().lazy_plt_header.map(|h| h.plt_address),
+            (rela_plt_start_address
+                - layout
+                    .section_layouts
+                    .get(output_section_id::RELA_PLT)
+                    .mem_offset)
+                / elf::RELA_ENTRY_SIZE,
+            plt_got_start_address,
+        );
+        if layout.args().print_relaxations {
+            table_writer.relaxation_stats = Some(RelaxationStats::default());
+        }
+        table_writer
     }
 
     fn new(
         output_kind: OutputKind,
+        plt_layout: PltLayout,
+        got_base: u64,
         tls: Range<u64>,
+        thread_pointer: u64,
+        arch: Architecture,
         buffers: &mut OutputSectionPartMap<&'out mut [u8]>,
         dynsym_writer: SymbolTableWriter<'data, 'layout, 'out>,
         debug_symbol_writer: SymbolTableWriter<'data, 'layout, 'out>,
         eh_frame_start_address: u64,
+        plt_header_address: Option<u64>,
+        next_rela_plt_index: u64,
+        next_plt_got_address: u64,
     ) -> TableWriter<'data, 'layout, 'out> {
         let eh_frame = buffers.take(part_id::EH_FRAME);
         let eh_frame_hdr = buffers.take(part_id::EH_FRAME_HDR);
@@ -745,10 +1099,19 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
 
         TableWriter {
             output_kind,
+            arch,
+            plt_layout,
+            got_base,
             got: bytemuck::cast_slice_mut(buffers.take(part_id::GOT)),
             plt_got: buffers.take(part_id::PLT_GOT),
+            plt_sec: buffers.take(part_id::PLT_SEC),
+            next_plt_got_address,
             rela_plt: slice_from_all_bytes_mut(buffers.take(part_id::RELA_PLT)),
+            plt_header_address,
+            next_rela_plt_index,
             tls,
+            thread_pointer,
+            tls_dtv_offset: arch.tls_dtv_offset(),
             rela_dyn_relative: slice_from_all_bytes_mut(buffers.take(part_id::RELA_DYN_RELATIVE)),
             rela_dyn_general: slice_from_all_bytes_mut(buffers.take(part_id::RELA_DYN_GENERAL)),
             dynsym_writer,
@@ -758,10 +1121,12 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
             eh_frame_hdr,
             dynamic,
             version_writer,
+            relaxation_stats: None,
+            high_part_values: AHashMap::new(),
         }
     }
 
-    fn process_resolution<A: Arch>(&mut self, res: &Resolution) -> Result {
+    fn process_resolution(&mut self, res: &Resolution) -> Result {
         let is_copy_relocation = res
             .resolution_flags
             .contains(ResolutionFlags::COPY_RELOCATION);
@@ -769,7 +1134,8 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
             self.write_rela_dyn_general(
                 res.raw_value,
                 res.dynamic_symbol_index()?,
-                A::get_dynamic_relocation_type(DynamicRelocationKind::Copy),
+                self.arch
+                    .get_dynamic_relocation_type(DynamicRelocationKind::Copy),
                 0,
             )?;
         }
@@ -781,13 +1147,13 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
 
         // For TLS variables, we'll generally only have one of these, but we might have both.
         if resolution_flags.contains(ResolutionFlags::GOT_TLS_OFFSET) {
-            self.process_got_tls_offset::<A>(res, got_address)?;
+            self.process_got_tls_offset(res, got_address)?;
             got_address += crate::elf::GOT_ENTRY_SIZE;
         }
         if resolution_flags.contains(ResolutionFlags::GOT_TLS_MODULE) {
-            return self.process_got_tls_mod::<A>(res, got_address);
+            return self.process_got_tls_mod(res, got_address);
         } else if resolution_flags.contains(ResolutionFlags::GOT_TLS_DESCRIPTOR) {
-            return self.process_got_tls_descriptor::<A>(res, got_address);
+            return self.process_got_tls_descriptor(res, got_address);
         }
         if resolution_flags.contains(ResolutionFlags::GOT_TLS_OFFSET) {
             return Ok(());
@@ -795,34 +1161,52 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
 
         let got_entry = self.take_next_got_entry()?;
 
+        if is_lazily_bound(res.value_flags, resolution_flags, self.plt_layout) {
+            let plt_address = res.plt_address.context("Missing PLT entry")?.get();
+            *got_entry = self.write_lazy_plt_entry(got_address)?;
+            if self.plt_layout.uses_plt_sec() {
+                self.write_plt_sec_entry(got_address, plt_address)?;
+            }
+            self.write_jump_slot_relocation(got_address, res.dynamic_symbol_index()?)?;
+            return Ok(());
+        }
+
+        if resolution_flags.contains(ResolutionFlags::CANONICAL_PLT) {
+            // Our dynamic symbol points at the PLT entry, so a GLOB_DAT would resolve to the PLT
+            // entry itself. A JUMP_SLOT relocation ignores our symbol.
+            let plt_address = res.plt_address.context("Missing PLT entry")?.get();
+            self.write_jump_slot_relocation(got_address, res.dynamic_symbol_index()?)?;
+            return self.write_plt_entry(got_address, plt_address);
+        }
+
         if (res.value_flags.contains(ValueFlags::DYNAMIC) && !is_copy_relocation)
             || (resolution_flags.contains(ResolutionFlags::EXPORT_DYNAMIC)
                 && !res.value_flags.contains(ValueFlags::CAN_BYPASS_GOT))
                 && !res.value_flags.contains(ValueFlags::IFUNC)
         {
             debug_assert_bail!(
-                *compute_allocations(res, self.output_kind).get(part_id::RELA_DYN_GENERAL) > 0,
+                *self.compute_allocations(res).get(part_id::RELA_DYN_GENERAL) > 0,
                 "Tried to write glob-dat with no allocation. {}",
                 ResFlagsDisplay(res)
             );
-            self.write_dynamic_symbol_relocation::<A>(got_address, 0, res.dynamic_symbol_index()?)?;
+            self.write_dynamic_symbol_relocation(got_address, 0, res.dynamic_symbol_index()?)?;
         } else if res.value_flags.contains(ValueFlags::IFUNC) {
-            self.write_ifunc_relocation::<A>(res)?;
+            self.write_ifunc_relocation(res)?;
         } else {
             *got_entry = res.raw_value;
             if (res.value_flags.contains(ValueFlags::ADDRESS) || is_copy_relocation)
                 && self.output_kind.is_relocatable()
             {
-                self.write_address_relocation::<A>(got_address, res.raw_value as i64)?;
+                self.write_address_relocation(got_address, res.raw_value as i64)?;
             }
         }
         if let Some(plt_address) = res.plt_address {
-            self.write_plt_entry::<A>(got_address, plt_address.get())?;
+            self.write_plt_entry(got_address, plt_address.get())?;
         }
         Ok(())
     }
 
-    fn process_got_tls_offset<A: Arch>(&mut self, res: &Resolution, got_address: u64) -> Result {
+    fn process_got_tls_offset(&mut self, res: &Resolution, got_address: u64) -> Result {
         let got_entry = self.take_next_got_entry()?;
         if res.value_flags.contains(ValueFlags::DYNAMIC)
             || (res
@@ -830,7 +1214,7 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
                 .contains(ResolutionFlags::EXPORT_DYNAMIC)
                 && !res.value_flags.contains(ValueFlags::CAN_BYPASS_GOT))
         {
-            return self.write_tpoff_relocation::<A>(got_address, res.dynamic_symbol_index()?, 0);
+            return self.write_tpoff_relocation(got_address, res.dynamic_symbol_index()?, 0);
         }
         let address = res.raw_value;
         if address == 0 {
@@ -845,37 +1229,36 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
             );
         }
         if self.output_kind.is_executable() {
-            // Convert the address to an offset relative to the TCB which is the end of the
-            // TLS segment.
-            *got_entry = address.wrapping_sub(self.tls.end);
+            // Convert the address to an offset relative to the thread pointer.
+            *got_entry = address.wrapping_sub(self.thread_pointer);
         } else {
             debug_assert_bail!(
-                *compute_allocations(res, self.output_kind).get(part_id::RELA_DYN_GENERAL) > 0,
+                *self.compute_allocations(res).get(part_id::RELA_DYN_GENERAL) > 0,
                 "Tried to write tpoff with no allocation. {}",
                 ResFlagsDisplay(res)
             );
-            self.write_tpoff_relocation::<A>(got_address, 0, address.sub(self.tls.start) as i64)?;
+            self.write_tpoff_relocation(got_address, 0, address.sub(self.tls.start) as i64)?;
         }
         Ok(())
     }
 
-    fn process_got_tls_mod<A: Arch>(&mut self, res: &Resolution, got_address: u64) -> Result {
+    fn process_got_tls_mod(&mut self, res: &Resolution, got_address: u64) -> Result {
         let got_entry = self.take_next_got_entry()?;
         if self.output_kind.is_executable() {
             *got_entry = elf::CURRENT_EXE_TLS_MOD;
         } else {
             let dynamic_symbol_index = res.dynamic_symbol_index.map_or(0, std::num::NonZero::get);
             debug_assert_bail!(
-                *compute_allocations(res, self.output_kind).get(part_id::RELA_DYN_GENERAL) > 0,
+                *self.compute_allocations(res).get(part_id::RELA_DYN_GENERAL) > 0,
                 "Tried to write dtpmod with no allocation. {}",
                 ResFlagsDisplay(res)
             );
-            self.write_dtpmod_relocation::<A>(got_address, dynamic_symbol_index)?;
+            self.write_dtpmod_relocation(got_address, dynamic_symbol_index)?;
         }
         let offset_entry = self.take_next_got_entry()?;
         if let Some(dynamic_symbol_index) = res.dynamic_symbol_index {
             if !res.value_flags.contains(ValueFlags::CAN_BYPASS_GOT) {
-                self.write_dtpoff_relocation::<A>(
+                self.write_dtpoff_relocation(
                     got_address + crate::elf::TLS_OFFSET_OFFSET,
                     dynamic_symbol_index.get(),
                 )?;
@@ -884,15 +1267,11 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
         }
         // Convert the address to an offset within the TLS segment
         let address = res.address()?;
-        *offset_entry = address - self.tls.start;
+        *offset_entry = (address - self.tls.start).wrapping_sub(self.tls_dtv_offset);
         Ok(())
     }
 
-    fn process_got_tls_descriptor<A: Arch>(
-        &mut self,
-        res: &Resolution,
-        got_address: u64,
-    ) -> Result {
+    fn process_got_tls_descriptor(&mut self, res: &Resolution, got_address: u64) -> Result {
         // TLS descriptor occupies 2 entries
         self.take_next_got_entry()?;
         self.take_next_got_entry()?;
@@ -904,7 +1283,7 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
 
         let dynamic_symbol_index = res.dynamic_symbol_index.map_or(0, std::num::NonZero::get);
         debug_assert_bail!(
-            *compute_allocations(res, self.output_kind).get(part_id::RELA_DYN_GENERAL) > 0,
+            *self.compute_allocations(res).get(part_id::RELA_DYN_GENERAL) > 0,
             "Tried to write TLS descriptor with no allocation. {}",
             ResFlagsDisplay(res)
         );
@@ -913,23 +1292,84 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
         } else {
             0
         };
-        self.write_tls_descriptor_relocation::<A>(got_address, dynamic_symbol_index, addend)?;
+        self.write_tls_descriptor_relocation(got_address, dynamic_symbol_index, addend)?;
 
         Ok(())
     }
 
-    fn write_plt_entry<A: Arch>(&mut self, got_address: u64, plt_address: u64) -> Result {
+    fn write_plt_entry(&mut self, got_address: u64, plt_address: u64) -> Result {
         let plt_entry = self.take_plt_got_entry()?;
-        A::write_plt_entry(plt_entry, got_address, plt_address)
+        self.arch.write_plt_entry(
+            plt_entry,
+            got_address,
+            plt_address,
+            self.got_base,
+            self.plt_layout.features,
+        )
+    }
+
+    fn write_lazy_plt_entry(&mut self, got_address: u64) -> Result<u64> {
+        let plt_header_address = self
+            .plt_header_address
+            .context("Missing PLT header for lazy binding")?;
+        let rela_plt_index = self.next_rela_plt_index;
+        let plt_address = self.next_plt_got_address;
+        let plt_entry = self.take_plt_got_entry()?;
+        self.arch.write_lazy_plt_entry(
+            plt_entry,
+            got_address,
+            plt_address,
+            plt_header_address,
+            rela_plt_index,
+            self.plt_layout.features,
+        )
+    }
+
+    /// Writes the entry in .plt.sec that calls a lazily bound function. The corresponding entry in
+    /// .plt.got is only reached via the GOT.
+    fn write_plt_sec_entry(&mut self, got_address: u64, plt_address: u64) -> Result {
+        if self.plt_sec.len() < elf::PLT_ENTRY_SIZE as usize {
+            bail!("Didn't allocate enough space in .plt.sec");
+        }
+        let plt_entry = slice_take_prefix_mut(&mut self.plt_sec, elf::PLT_ENTRY_SIZE as usize);
+        self.arch.write_plt_entry(
+            plt_entry,
+            got_address,
+            plt_address,
+            self.got_base,
+            self.plt_layout.features,
+        )
+    }
+
+    /// Writes the PLT header and the GOT entries that it uses. The first GOT entry holds the
+    /// address of the dynamic section. The dynamic loader fills in the other two.
+    fn write_plt_header(&mut self, header: &LazyPltHeader, dynamic_address: u64) -> Result {
+        *self.take_next_got_entry()? = dynamic_address;
+        for _ in 1..elf::NUM_LAZY_GOT_RESERVED_ENTRIES {
+            *self.take_next_got_entry()? = 0;
+        }
+        if self.plt_got.len() < elf::PLT_HEADER_SIZE as usize {
+            bail!("Didn't allocate enough space in .plt.got");
+        }
+        let plt_header = slice_take_prefix_mut(&mut self.plt_got, elf::PLT_HEADER_SIZE as usize);
+        self.next_plt_got_address += elf::PLT_HEADER_SIZE;
+        self.arch
+            .write_plt_header(plt_header, header.got_address, header.plt_address)
+    }
+
+    fn compute_allocations(&self, res: &Resolution) -> OutputSectionPartMap<u64> {
+        compute_allocations(res, self.output_kind, self.plt_layout)
     }
 
     fn take_plt_got_entry(&mut self) -> Result<&'out mut [u8]> {
-        if self.plt_got.len() < elf::PLT_ENTRY_SIZE as usize {
+        let entry_size = self.plt_layout.entry_size;
+        if self.plt_got.len() < entry_size as usize {
             bail!("Didn't allocate enough space in .plt.got");
         }
+        self.next_plt_got_address += entry_size;
         Ok(slice_take_prefix_mut(
             &mut self.plt_got,
-            elf::PLT_ENTRY_SIZE as usize,
+            entry_size as usize,
         ))
     }
 
@@ -970,12 +1410,18 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
                 *mem_sizes.get(part_id::EH_FRAME_HDR),
             ));
         }
+        if !self.plt_sec.is_empty() {
+            return Err(excessive_allocation(
+                ".plt.sec",
+                self.plt_sec.len() as u64,
+                *mem_sizes.get(part_id::PLT_SEC),
+            ));
+        }
         Ok(())
     }
 
-    fn write_ifunc_relocation<A: Arch>(&mut self, res: &Resolution) -> Result {
-        let out = slice_take_prefix_mut(&mut self.rela_plt, 1);
-        let out = &mut out[0];
+    fn write_ifunc_relocation(&mut self, res: &Resolution) -> Result {
+        let out = self.take_rela_plt()?;
         let e = LittleEndian;
         out.r_addend.set(e, res.raw_value as i64);
         let got_address = res
@@ -985,27 +1431,45 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
         out.r_offset.set(e, got_address);
         out.r_info.set(
             e,
-            u64::from(A::get_dynamic_relocation_type(
-                DynamicRelocationKind::Irelative,
-            )),
+            u64::from(
+                self.arch
+                    .get_dynamic_relocation_type(DynamicRelocationKind::Irelative),
+            ),
         );
         Ok(())
     }
 
-    fn write_dtpmod_relocation<A: Arch>(
-        &mut self,
-        place: u64,
-        dynamic_symbol_index: u32,
-    ) -> Result {
+    fn write_jump_slot_relocation(&mut self, place: u64, dynamic_symbol_index: u32) -> Result {
+        let rela = self.take_rela_plt()?;
+        rela.r_offset.set(LittleEndian, place);
+        rela.r_addend.set(LittleEndian, 0);
+        rela.set_r_info(
+            LittleEndian,
+            false,
+            dynamic_symbol_index,
+            self.arch
+                .get_dynamic_relocation_type(DynamicRelocationKind::JumpSlot),
+        );
+        Ok(())
+    }
+
+    fn take_rela_plt(&mut self) -> Result<&'out mut elf::Rela> {
+        self.next_rela_plt_index += 1;
+        crate::slice::take_first_mut(&mut self.rela_plt)
+            .ok_or_else(|| insufficient_allocation(".rela.plt"))
+    }
+
+    fn write_dtpmod_relocation(&mut self, place: u64, dynamic_symbol_index: u32) -> Result {
         self.write_rela_dyn_general(
             place,
             dynamic_symbol_index,
-            A::get_dynamic_relocation_type(DynamicRelocationKind::DtpMod),
+            self.arch
+                .get_dynamic_relocation_type(DynamicRelocationKind::DtpMod),
             0,
         )
     }
 
-    fn write_tls_descriptor_relocation<A: Arch>(
+    fn write_tls_descriptor_relocation(
         &mut self,
         place: u64,
         dynamic_symbol_index: u32,
@@ -1014,25 +1478,23 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
         self.write_rela_dyn_general(
             place,
             dynamic_symbol_index,
-            A::get_dynamic_relocation_type(DynamicRelocationKind::TlsDesc),
+            self.arch
+                .get_dynamic_relocation_type(DynamicRelocationKind::TlsDesc),
             addend,
         )
     }
 
-    fn write_dtpoff_relocation<A: Arch>(
-        &mut self,
-        place: u64,
-        dynamic_symbol_index: u32,
-    ) -> Result {
+    fn write_dtpoff_relocation(&mut self, place: u64, dynamic_symbol_index: u32) -> Result {
         self.write_rela_dyn_general(
             place,
             dynamic_symbol_index,
-            A::get_dynamic_relocation_type(DynamicRelocationKind::DtpOff),
+            self.arch
+                .get_dynamic_relocation_type(DynamicRelocationKind::DtpOff),
             0,
         )
     }
 
-    fn write_tpoff_relocation<A: Arch>(
+    fn write_tpoff_relocation(
         &mut self,
         place: u64,
         dynamic_symbol_index: u32,
@@ -1041,12 +1503,13 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
         self.write_rela_dyn_general(
             place,
             dynamic_symbol_index,
-            A::get_dynamic_relocation_type(DynamicRelocationKind::TpOff),
+            self.arch
+                .get_dynamic_relocation_type(DynamicRelocationKind::TpOff),
             addend,
         )
     }
 
-    fn write_address_relocation<A: Arch>(&mut self, place: u64, relative_address: i64) -> Result {
+    fn write_address_relocation(&mut self, place: u64, relative_address: i64) -> Result {
         debug_assert_bail!(
             self.output_kind.is_relocatable(),
             "write_address_relocation called when output is not relocatable"
@@ -1058,12 +1521,33 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
         rela.r_addend.set(e, relative_address);
         rela.r_info.set(
             e,
-            A::get_dynamic_relocation_type(DynamicRelocationKind::Relative).into(),
+            self.arch
+                .get_dynamic_relocation_type(DynamicRelocationKind::Relative)
+                .into(),
+        );
+        Ok(())
+    }
+
+    /// Writes a relocation for the dynamic loader to sign a pointer to a non-dynamic symbol. These
+    /// go with our non-relative relocations, since DT_RELACOUNT mustn't count them.
+    fn write_auth_relative_relocation(&mut self, place: u64, address: i64) -> Result {
+        debug_assert_bail!(
+            self.output_kind.is_relocatable(),
+            "write_auth_relative_relocation called when output is not relocatable"
+        );
+        let rela = self.take_rela_dyn()?;
+        rela.r_offset.set(LittleEndian, place);
+        rela.r_addend.set(LittleEndian, address);
+        rela.set_r_info(
+            LittleEndian,
+            false,
+            0,
+            linker_utils::aarch64::R_AARCH64_AUTH_RELATIVE,
         );
         Ok(())
     }
 
-    fn write_dynamic_symbol_relocation<A: Arch>(
+    fn write_dynamic_symbol_relocation(
         &mut self,
         place: u64,
         addend: i64,
@@ -1075,15 +1559,13 @@ impl<'data, 'layout, 'out> TableWriter<'data, 'layout, 'out> {
             "Tried to write dynamic relocation with non-relocatable output"
         );
         let e = LittleEndian;
+        let r_type = self
+            .arch
+            .get_dynamic_relocation_type(DynamicRelocationKind::DynamicSymbol);
         let rela = self.take_rela_dyn()?;
         rela.r_offset.set(e, place);
         rela.r_addend.set(e, addend);
-        rela.set_r_info(
-            LittleEndian,
-            false,
-            symbol_index,
-            A::get_dynamic_relocation_type(DynamicRelocationKind::DynamicSymbol),
-        );
+        rela.set_r_info(LittleEndian, false, symbol_index, r_type);
         Ok(())
     }
 
@@ -1221,6 +1703,11 @@ impl<'data, 'layout, 'out> SymbolTableWriter<'data, 'layout, 'out> {
         let entry = self.define_symbol(is_local, shndx, value, size, name)?;
         entry.st_info = sym.st_info();
         entry.st_other = sym.st_other();
+        if shndx == object::elf::SHN_UNDEF {
+            // The visibility of a definition in a shared object doesn't apply to our references to
+            // it. An undefined protected symbol would need to be defined by our own output.
+            entry.st_other = (entry.st_other & !0x3) | object::elf::STV_DEFAULT;
+        }
         Ok(())
     }
 
@@ -1235,6 +1722,21 @@ impl<'data, 'layout, 'out> SymbolTableWriter<'data, 'layout, 'out> {
         Ok(())
     }
 
+    /// Defines a local symbol for the start of the section with the supplied index. Section symbols
+    /// don't have names, so unlike other symbols, we don't write anything to the string table.
+    fn define_section_symbol(&mut self, shndx: u16, value: u64) -> Result {
+        let entry = take_first_mut(&mut self.local_entries)
+            .context("Insufficient .symtab local entries allocated for section symbols")?;
+        let e = LittleEndian;
+        entry.st_name.set(e, 0);
+        entry.st_info = (object::elf::STB_LOCAL << 4) | object::elf::STT_SECTION;
+        entry.st_other = 0;
+        entry.st_shndx.set(e, shndx);
+        entry.st_value.set(e, value);
+        entry.st_size.set(e, 0);
+        Ok(())
+    }
+
     fn define_symbol(
         &mut self,
         is_local: bool,
@@ -1300,7 +1802,7 @@ impl<'data, 'layout, 'out> SymbolTableWriter<'data, 'layout, 'out> {
 }
 
 impl<'data> ObjectLayout<'data> {
-    fn write_file<'symbol_db, S: StorageModel, A: Arch>(
+    fn write_file<'symbol_db, S: StorageModel>(
         &self,
         buffers: &mut OutputSectionPartMap<&mut [u8]>,
         table_writer: &mut TableWriter,
@@ -1311,13 +1813,13 @@ impl<'data> ObjectLayout<'data> {
         for sec in &self.sections {
             match sec {
                 SectionSlot::Loaded(sec) => {
-                    self.write_section::<S, A>(layout, sec, buffers, table_writer)?;
+                    self.write_section::<S>(layout, sec, buffers, table_writer)?;
                 }
                 SectionSlot::LoadedDebugInfo(sec) => {
-                    self.write_debug_section::<S, A>(layout, sec, buffers)?;
+                    self.write_debug_section::<S>(layout, sec, buffers)?;
                 }
                 SectionSlot::EhFrameData(section_index) => {
-                    self.write_eh_frame_data::<S, A>(*section_index, layout, table_writer)?;
+                    self.write_eh_frame_data::<S>(*section_index, layout, table_writer)?;
                 }
                 _ => (),
             }
@@ -1325,7 +1827,7 @@ impl<'data> ObjectLayout<'data> {
         for (symbol_id, resolution) in layout.resolutions_in_range(self.symbol_id_range) {
             let _span = tracing::trace_span!("Symbol", %symbol_id).entered();
             if let Some(res) = resolution {
-                table_writer.process_resolution::<A>(res).with_context(|| {
+                table_writer.process_resolution(res).with_context(|| {
                     format!(
                         "Failed to process `{}` with resolution {res:?}",
                         layout.symbol_debug(symbol_id)
@@ -1358,15 +1860,48 @@ impl<'data> ObjectLayout<'data> {
         Ok(())
     }
 
-    fn write_section<'symbol_db, S: StorageModel, A: Arch>(
+    fn write_section<'symbol_db, S: StorageModel>(
         &self,
         layout: &Layout<'data, 'symbol_db, S>,
         sec: &Section,
         buffers: &mut OutputSectionPartMap<&mut [u8]>,
         table_writer: &mut TableWriter,
     ) -> Result {
+        if layout.args().output_kind() == OutputKind::RelocatableObject {
+            let object_section = self.object.section(sec.index)?;
+            if SectionType::from_header(object_section) == sht::GROUP {
+                let out = self.section_buffer(layout, sec, buffers)?;
+                return crate::relocatable_object::write_group(out, self, object_section, layout)
+                    .with_context(|| {
+                        format!(
+                            "Failed to copy section group `{}` of {}",
+                            self.object.section_display_name(sec.index),
+                            self.input
+                        )
+                    });
+            }
+        }
         let out = self.write_section_raw(layout, sec, buffers)?;
-        self.apply_relocations::<S, A>(out, sec, layout, table_writer)
+        if layout.args().keeps_input_relocations() {
+            // The relocations that we copy need to be adjusted to fit our output. For a relocatable
+            // object, the relocations are left for the final link to apply.
+            let object_section = self.object.section(sec.index)?;
+            if SectionType::from_header(object_section) == sht::RELA {
+                crate::relocatable_object::write_relocations(out, self, object_section, layout)
+                    .with_context(|| {
+                        format!(
+                            "Failed to copy relocations from `{}` of {}",
+                            self.object.section_display_name(sec.index),
+                            self.input
+                        )
+                    })?;
+                return Ok(());
+            }
+        }
+        if layout.args().output_kind() == OutputKind::RelocatableObject {
+            return Ok(());
+        }
+        self.apply_relocations::<S>(out, sec, layout, table_writer)
             .with_context(|| {
                 format!(
                     "Failed to apply relocations in section `{}` of {}",
@@ -1382,14 +1917,17 @@ impl<'data> ObjectLayout<'data> {
         Ok(())
     }
 
-    fn write_debug_section<'symbol_db, S: StorageModel, A: Arch>(
+    fn write_debug_section<'symbol_db, S: StorageModel>(
         &self,
         layout: &Layout<'data, 'symbol_db, S>,
         sec: &Section,
         buffers: &mut OutputSectionPartMap<&mut [u8]>,
     ) -> Result {
         let out = self.write_section_raw(layout, sec, buffers)?;
-        self.apply_debug_relocations::<S, A>(out, sec, layout)
+        if layout.args().output_kind() == OutputKind::RelocatableObject {
+            return Ok(());
+        }
+        self.apply_debug_relocations::<S>(out, sec, layout)
             .with_context(|| {
                 format!(
                     "Failed to apply relocations in section `{}` of {}",
@@ -1405,6 +1943,34 @@ impl<'data> ObjectLayout<'data> {
         layout: &Layout<'data, 'symbol_db, S>,
         sec: &Section,
         buffers: &'out mut OutputSectionPartMap<&mut [u8]>,
+    ) -> Result<&'out mut [u8]> {
+        let out = self.section_buffer(layout, sec, buffers)?;
+        if layout
+            .output_sections
+            .has_data_in_file(sec.output_section_id())
+        {
+            let object_section = self.object.section(sec.index)?;
+            if let Some(relaxations) = self.relaxations.get(sec.index) {
+                relaxations.copy_data(
+                    self.object.raw_section_data(object_section)?,
+                    &mut out[..sec.size as usize],
+                )?;
+            } else {
+                let section_size = self.object.section_size(object_section)?;
+                self.object
+                    .copy_section_data(object_section, &mut out[..section_size as usize])?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Returns the part of our output section's buffer that belongs to `sec`, without copying
+    /// anything into it.
+    fn section_buffer<'symbol_db, 'out, S: StorageModel>(
+        &self,
+        layout: &Layout<'data, 'symbol_db, S>,
+        sec: &Section,
+        buffers: &'out mut OutputSectionPartMap<&mut [u8]>,
     ) -> Result<&'out mut [u8]> {
         if layout
             .output_sections
@@ -1420,12 +1986,9 @@ impl<'data> ObjectLayout<'data> {
                 );
             }
             let out = slice_take_prefix_mut(section_buffer, allocation_size);
-            // Cut off any padding so that our output buffer is the size of our input buffer.
-            let object_section = self.object.section(sec.index)?;
-            let section_size = self.object.section_size(object_section)?;
-            let out: &'out mut [u8] = &mut out[..section_size as usize];
-            self.object.copy_section_data(object_section, out)?;
-            Ok(out)
+            // Cut off any padding so that our output buffer is the size of our input buffer plus
+            // any thunks that follow it.
+            Ok(&mut out[..sec.size_with_thunks() as usize])
         } else {
             Ok(&mut [])
         }
@@ -1437,6 +2000,8 @@ impl<'data> ObjectLayout<'data> {
         symbol_writer: &mut SymbolTableWriter,
         layout: &Layout<'data, 'symbol_db, S>,
     ) -> Result {
+        let is_relocatable_output = layout.args().output_kind() == OutputKind::RelocatableObject;
+        let keeps_input_relocations = layout.args().keeps_input_relocations();
         for ((sym_index, sym), sym_state) in self
             .object
             .symbols
@@ -1454,6 +2019,36 @@ impl<'data> ObjectLayout<'data> {
                 &self.sections,
             ) {
                 let e = LittleEndian;
+                let downgraded;
+                let sym = if info.is_local && !sym.is_local() {
+                    downgraded = downgraded_to_local(sym);
+                    &downgraded
+                } else {
+                    sym
+                };
+                let resized;
+                let sym = if let Some(size) = self.relaxed_symbol_size(sym_index)? {
+                    resized = with_size(sym, size);
+                    &resized
+                } else {
+                    sym
+                };
+                if (keeps_input_relocations && sym.is_undefined(e))
+                    || (is_relocatable_output && sym.is_common(e))
+                {
+                    // Leave these for the final link to resolve or allocate.
+                    let (shndx, value) = if sym.is_common(e) {
+                        let alignment = layout.symbol_db.common_alignments.get(&symbol_id).copied();
+                        (
+                            object::elf::SHN_COMMON,
+                            alignment.unwrap_or(sym.st_value(e)),
+                        )
+                    } else {
+                        (object::elf::SHN_UNDEF, sym.st_value(e))
+                    };
+                    symbol_writer.copy_symbol_shndx(sym, info.name, shndx, value)?;
+                    continue;
+                }
                 let section_id = if let Some(section_index) =
                     self.object.symbol_section(sym, sym_index)?
                 {
@@ -1465,7 +2060,7 @@ impl<'data> ObjectLayout<'data> {
                     }
                 } else if sym.is_common(e) {
                     output_section_id::BSS
-                } else if sym.is_absolute(e) {
+                } else if sym.is_absolute(e) || self.object.just_symbols {
                     symbol_writer
                         .copy_absolute_symbol(sym, info.name)
                         .with_context(|| {
@@ -1482,7 +2077,10 @@ impl<'data> ObjectLayout<'data> {
                     bail!("Missing resolution for {}", layout.symbol_debug(symbol_id));
                 };
                 let mut symbol_value = res.value_for_symbol_table();
-                if sym.st_type() == object::elf::STT_TLS {
+                if is_relocatable_output {
+                    symbol_value =
+                        crate::relocatable_object::symbol_value(layout, section_id, symbol_value);
+                } else if sym.st_type() == object::elf::STT_TLS {
                     let tls_start_address = layout.segment_layouts.tls_start_address.context(
                         "Writing TLS variable to symtab, but we don't have a TLS segment",
                     )?;
@@ -1498,7 +2096,7 @@ impl<'data> ObjectLayout<'data> {
         Ok(())
     }
 
-    fn apply_relocations<'symbol_db, S: StorageModel, A: Arch>(
+    fn apply_relocations<'symbol_db, S: StorageModel>(
         &self,
         out: &mut [u8],
         section: &Section,
@@ -1517,17 +2115,29 @@ impl<'data> ObjectLayout<'data> {
             .relocation_statistics
             .get(section.part_id.output_section_id())
             .fetch_add(relocations.len() as u64, Relaxed);
+        table_writer.high_part_values.clear();
+        let relaxations = self.relaxations.get(section.index);
         for rel in relocations {
             if modifier == RelocationModifier::SkipNextRelocation {
                 modifier = RelocationModifier::Normal;
                 continue;
             }
-            let offset_in_section = rel.r_offset.get(LittleEndian);
-            modifier = apply_relocation::<S, A>(
+            // Relaxation may have deleted the instruction that the relocation applied to or
+            // rewritten it into one that needs a different relocation.
+            let (offset_in_section, rel) = match relaxations {
+                Some(relaxations) => match relaxations.relax_relocation(rel, out) {
+                    Some(relaxed) => relaxed,
+                    None => continue,
+                },
+                None => (rel.r_offset.get(LittleEndian), *rel),
+            };
+            let rel = &rel;
+            modifier = apply_relocation::<S>(
                 self,
                 offset_in_section,
                 rel,
                 SectionInfo {
+                    section_index: Some(section.index),
                     section_address,
                     is_writable: section.is_writable,
                     section_flags,
@@ -1539,14 +2149,26 @@ impl<'data> ObjectLayout<'data> {
             .with_context(|| {
                 format!(
                     "Failed to apply {} at offset 0x{offset_in_section:x}",
-                    self.display_relocation::<S, A>(rel, layout)
+                    self.display_relocation::<S>(rel, layout)
                 )
             })?;
         }
+        for (instruction_offset, patch_offset) in self.thunks.moved_instructions(section.index) {
+            crate::aarch64_errata::write_patch(out, instruction_offset, patch_offset);
+        }
+        if section_flags.contains(shf::EXECINSTR) {
+            crate::split_stack::adjust_calls_to_non_split(
+                self,
+                section,
+                section_address,
+                out,
+                layout,
+            )?;
+        }
         Ok(())
     }
 
-    fn apply_debug_relocations<'symbol_db, S: StorageModel, A: Arch>(
+    fn apply_debug_relocations<'symbol_db, S: StorageModel>(
         &self,
         out: &mut [u8],
         section: &Section,
@@ -1574,25 +2196,18 @@ impl<'data> ObjectLayout<'data> {
             .fetch_add(relocations.len() as u64, Relaxed);
         for rel in relocations {
             let offset_in_section = rel.r_offset.get(LittleEndian);
-            apply_debug_relocation::<S, A>(
-                self,
-                offset_in_section,
-                rel,
-                layout,
-                tombstone_value,
-                out,
-            )
-            .with_context(|| {
-                format!(
-                    "Failed to apply {} at offset 0x{offset_in_section:x}",
-                    self.display_relocation::<S, A>(rel, layout)
-                )
-            })?;
+            apply_debug_relocation::<S>(self, offset_in_section, rel, layout, tombstone_value, out)
+                .with_context(|| {
+                    format!(
+                        "Failed to apply {} at offset 0x{offset_in_section:x}",
+                        self.display_relocation::<S>(rel, layout)
+                    )
+                })?;
         }
         Ok(())
     }
 
-    fn write_eh_frame_data<'symbol_db, S: StorageModel, A: Arch>(
+    fn write_eh_frame_data<'symbol_db, S: StorageModel>(
         &self,
         eh_frame_section_index: object::SectionIndex,
         layout: &Layout<'data, 'symbol_db, S>,
@@ -1638,19 +2253,26 @@ impl<'data> ObjectLayout<'data> {
                     if rel_offset < next_input_pos as u64 {
                         let is_pc_begin =
                             (rel_offset as usize - input_pos) == elf::FDE_PC_BEGIN_OFFSET;
-
-                        if is_pc_begin {
-                            let Some(index) = rel.symbol(e, false) else {
-                                bail!("Unexpected absolute relocation in .eh_frame pc-begin");
-                            };
+                        // A relocatable object has no symbol for the pc-begin of a function that
+                        // was discarded, e.g. as part of a duplicate COMDAT group. As when laying
+                        // out, we drop such FDEs.
+                        let pc_begin_symbol = if is_pc_begin {
+                            rel.symbol(e, false)
+                        } else {
+                            None
+                        };
+
+                        if let Some(index) = pc_begin_symbol {
                             let elf_symbol = &self.object.symbol(index)?;
                             let Some(section_index) =
                                 self.object.symbol_section(elf_symbol, index)?
                             else {
                                 bail!(".eh_frame pc-begin refers to symbol that's not defined in file");
                             };
-                            let offset_in_section =
-                                (elf_symbol.st_value(e) as i64 + rel.r_addend.get(e)) as u64;
+                            let offset_in_section = self.relaxations.output_offset(
+                                section_index,
+                                (elf_symbol.st_value(e) as i64 + rel.r_addend.get(e)) as u64,
+                            );
                             if let Some(section_address) =
                                 self.section_resolutions[section_index.0].address()
                             {
@@ -1699,11 +2321,12 @@ impl<'data> ObjectLayout<'data> {
                         // This relocation belongs to the next entry.
                         break;
                     }
-                    apply_relocation::<S, A>(
+                    apply_relocation::<S>(
                         self,
                         rel_offset - input_pos as u64,
                         rel,
                         SectionInfo {
+                            section_index: None,
                             section_address: output_pos as u64
                                 + table_writer.eh_frame_start_address,
                             is_writable: false,
@@ -1716,7 +2339,7 @@ impl<'data> ObjectLayout<'data> {
                     .with_context(|| {
                         format!(
                             "Failed to apply eh_frame {}",
-                            self.display_relocation::<S, A>(rel, layout)
+                            self.display_relocation::<S>(rel, layout)
                         )
                     })?;
                     relocations.next();
@@ -1751,34 +2374,35 @@ impl<'data> ObjectLayout<'data> {
         Ok(())
     }
 
-    fn display_relocation<'a, 'symbol_db, S: StorageModel, A: Arch>(
+    fn display_relocation<'a, 'symbol_db, S: StorageModel>(
         &'a self,
         rel: &'a elf::Rela,
         layout: &'a Layout<'data, 'symbol_db, S>,
-    ) -> DisplayRelocation<'a, 'data, S, A> {
-        DisplayRelocation::<'a, 'data, S, A> {
+    ) -> DisplayRelocation<'a, 'data, S> {
+        DisplayRelocation::<'a, 'data, S> {
             rel,
             symbol_db: layout.symbol_db,
             object: self,
-            phantom: PhantomData,
         }
     }
 }
 
-struct DisplayRelocation<'a, 'data, S: StorageModel, A: Arch> {
+struct DisplayRelocation<'a, 'data, S: StorageModel> {
     rel: &'a elf::Rela,
     symbol_db: &'a SymbolDb<'data, S>,
     object: &'a ObjectLayout<'a>,
-    phantom: PhantomData<A>,
 }
 
-impl<S: StorageModel, A: Arch> Display for DisplayRelocation<'_, '_, S, A> {
+impl<S: StorageModel> Display for DisplayRelocation<'_, '_, S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let e = LittleEndian;
         write!(
             f,
             "relocation of type {} to ",
-            A::rel_type_to_string(self.rel.r_type(e, false))
+            self.symbol_db
+                .args
+                .arch()
+                .rel_type_to_string(self.rel.r_type(e, false))
         )?;
         match self.rel.symbol(e, false) {
             None => write!(f, "absolute")?,
@@ -1793,6 +2417,8 @@ impl<S: StorageModel, A: Arch> Display for DisplayRelocation<'_, '_, S, A> {
 
 #[derive(Clone, Copy)]
 struct SectionInfo {
+    /// The index of the input section. None for .eh_frame, which we write one frame at a time.
+    section_index: Option<object::SectionIndex>,
     section_address: u64,
     is_writable: bool,
     section_flags: SectionFlags,
@@ -1801,7 +2427,7 @@ struct SectionInfo {
 /// Applies the relocation `rel` at `offset_in_section`, where the section bytes are `out`. See "ELF
 /// Handling For Thread-Local Storage" for details about some of the TLS-related relocations and
 /// transformations that are applied.
-fn apply_relocation<S: StorageModel, A: Arch>(
+fn apply_relocation<S: StorageModel>(
     object_layout: &ObjectLayout,
     mut offset_in_section: u64,
     rel: &elf::Rela,
@@ -1820,9 +2446,15 @@ fn apply_relocation<S: StorageModel, A: Arch>(
     .entered();
 
     let e = LittleEndian;
-    let symbol_index = rel
-        .symbol(e, false)
-        .context("Unsupported absolute relocation")?;
+    let arch = table_writer.arch;
+    let r_type = rel.r_type(e, false);
+    let Some(symbol_index) = rel.symbol(e, false) else {
+        // Some relocations, e.g. R_RISCV_RELAX, are just hints and don't reference a symbol.
+        if arch.relocation_from_raw(r_type)?.kind == RelocationKind::None {
+            return Ok(RelocationModifier::Normal);
+        }
+        bail!("Unsupported absolute relocation");
+    };
     let local_symbol_id = object_layout.symbol_id_range.input_to_id(symbol_index);
     let resolution = layout
         .merged_symbol_resolution(local_symbol_id)
@@ -1837,25 +2469,39 @@ fn apply_relocation<S: StorageModel, A: Arch>(
     let resolution_flags = resolution.resolution_flags;
     let mut addend = rel.r_addend.get(e);
     let mut next_modifier = RelocationModifier::Normal;
-    let r_type = rel.r_type(e, false);
     let rel_info;
     let output_kind = layout.args().output_kind();
     let symbol_name = layout.symbol_db.symbol_name(local_symbol_id)?;
 
-    let relaxation = A::Relaxation::new(
-        r_type,
-        out,
-        offset_in_section,
-        value_flags,
-        output_kind,
-        section_info.section_flags,
-    );
+    let relaxation = if layout
+        .args()
+        .relax
+        .allows(arch.relocation_from_raw(r_type)?.kind)
+    {
+        arch.new_relaxation(
+            r_type,
+            out,
+            offset_in_section,
+            value_flags,
+            output_kind,
+            section_info.section_flags,
+        )
+    } else {
+        None
+    };
     if let Some(relaxation) = &relaxation {
         rel_info = relaxation.rel_info();
         relaxation.apply(out, &mut offset_in_section, &mut addend);
         next_modifier = relaxation.next_modifier();
     } else {
-        rel_info = A::relocation_from_raw(r_type)?;
+        rel_info = arch.relocation_from_raw(r_type)?;
+    }
+    if let Some(stats) = table_writer.relaxation_stats.as_mut() {
+        if let Some(relaxation) = &relaxation {
+            stats.record_applied(r_type, relaxation.debug_kind());
+        } else {
+            stats.record_not_applied(r_type, value_flags, layout.args())?;
+        }
     }
 
     // Compute place to which IP-relative relocations will be relative. This is different to
@@ -1866,7 +2512,7 @@ fn apply_relocation<S: StorageModel, A: Arch>(
     let value = match rel_info.kind {
         RelocationKind::Absolute => {
             assert!(rel_info.mask.is_none());
-            write_absolute_relocation::<S, A>(
+            write_absolute_relocation::<S>(
                 table_writer,
                 resolution,
                 place,
@@ -1877,6 +2523,41 @@ fn apply_relocation<S: StorageModel, A: Arch>(
                 layout,
             )?
         }
+        RelocationKind::AuthAbsolute => {
+            write_auth_relocation(
+                table_writer,
+                resolution,
+                place,
+                addend,
+                symbol_index,
+                object_layout,
+                layout,
+            )?;
+            // The place holds the signing schema, which the dynamic loader reads, so we leave it
+            // alone.
+            return Ok(next_modifier);
+        }
+        RelocationKind::SymbolSize => {
+            let definition = layout.symbol_db.definition(local_symbol_id);
+            if crate::layout::is_symbol_size_dynamic(
+                layout.symbol_db.local_symbol_value_flags(definition),
+                output_kind,
+            ) {
+                // The size relocation types double as dynamic relocation types.
+                table_writer.write_rela_dyn_general(
+                    place,
+                    resolution.dynamic_symbol_index()?,
+                    r_type,
+                    addend,
+                )?;
+                0
+            } else {
+                layout
+                    .symbol_db
+                    .symbol_st_size(definition)?
+                    .wrapping_add(addend as u64)
+            }
+        }
         RelocationKind::AbsoluteAArch64 => resolution
             .value_with_addend(
                 addend,
@@ -1910,10 +2591,43 @@ fn apply_relocation<S: StorageModel, A: Arch>(
             .got_address()?
             .bitand(mask.got_entry)
             .wrapping_add(addend as u64),
+        RelocationKind::RelativeLocalEntry => resolution
+            .value_with_addend(
+                addend,
+                symbol_index,
+                object_layout,
+                &layout.merged_strings,
+                &layout.merged_string_start_addresses,
+            )?
+            .wrapping_add(local_entry_offset(
+                layout
+                    .symbol_db
+                    .symbol_st_other(layout.symbol_db.definition(local_symbol_id))?,
+            ))
+            .wrapping_sub(place),
         RelocationKind::SymRelGotBase => resolution
-            .value()
+            .value_with_addend(
+                addend,
+                symbol_index,
+                object_layout,
+                &layout.merged_strings,
+                &layout.merged_string_start_addresses,
+            )?
             .bitand(mask.symbol_plus_addend)
             .wrapping_sub(layout.got_base().bitand(mask.got)),
+        RelocationKind::SymRelGlobalPointer => resolution
+            .value_with_addend(
+                addend,
+                symbol_index,
+                object_layout,
+                &layout.merged_strings,
+                &layout.merged_string_start_addresses,
+            )?
+            .wrapping_sub(
+                layout
+                    .global_pointer()
+                    .context("gp-relative relocation, but `__global_pointer$` isn't defined")?,
+            ),
         RelocationKind::PltRelGotBase => resolution
             .plt_address()?
             .wrapping_sub(layout.got_base().bitand(mask.got)),
@@ -1959,10 +2673,21 @@ fn apply_relocation<S: StorageModel, A: Arch>(
             .bitand(mask.got_entry)
             .wrapping_add(addend as u64)
             .wrapping_sub(layout.got_base().bitand(mask.got)),
-        RelocationKind::DtpOff if output_kind == OutputKind::SharedObject => resolution
-            .value()
-            .sub(layout.tls_start_address())
-            .wrapping_add(addend as u64),
+        // We don't relax local-dynamic TLS accesses on RISC-V or PowerPC64, so their DTP-relative
+        // offsets are always relative to the TLS block.
+        RelocationKind::DtpOff
+            if output_kind == OutputKind::SharedObject
+                || matches!(
+                    layout.args().arch(),
+                    Architecture::RiscV64 | Architecture::PowerPc64
+                ) =>
+        {
+            resolution
+                .value()
+                .sub(layout.tls_start_address())
+                .wrapping_sub(layout.args().arch().tls_dtv_offset())
+                .wrapping_add(addend as u64)
+        }
         RelocationKind::DtpOff => resolution
             .value()
             .wrapping_sub(layout.tls_end_address())
@@ -1983,7 +2708,7 @@ fn apply_relocation<S: StorageModel, A: Arch>(
             .wrapping_sub(layout.got_base().bitand(mask.got)),
         RelocationKind::TpOff => resolution
             .value()
-            .wrapping_sub(layout.tls_end_address())
+            .wrapping_sub(layout.thread_pointer_address())
             .wrapping_add(addend as u64),
         RelocationKind::TpOffAArch64 => resolution
             .value()
@@ -2003,21 +2728,135 @@ fn apply_relocation<S: StorageModel, A: Arch>(
             .bitand(mask.got_entry)
             .wrapping_add(addend as u64)
             .wrapping_sub(layout.got_base().bitand(mask.got)),
+        RelocationKind::AbsoluteSet => resolution.value_with_addend(
+            addend,
+            symbol_index,
+            object_layout,
+            &layout.merged_strings,
+            &layout.merged_string_start_addresses,
+        )?,
+        RelocationKind::AbsoluteAddition | RelocationKind::AbsoluteSubtraction => {
+            adjust_existing_value(
+                rel_info,
+                resolution.value_with_addend(
+                    addend,
+                    symbol_index,
+                    object_layout,
+                    &layout.merged_strings,
+                    &layout.merged_string_start_addresses,
+                )?,
+                &out[offset_in_section as usize..],
+            )?
+        }
+        RelocationKind::PairedLow => {
+            let high_part_address = resolution.value();
+            *table_writer
+                .high_part_values
+                .get(&high_part_address)
+                .with_context(|| {
+                    format!(
+                        "No high-part relocation found at 0x{high_part_address:x} for {}",
+                        layout.symbol_debug(local_symbol_id)
+                    )
+                })?
+        }
         RelocationKind::None | RelocationKind::TlsDescCall => 0,
     };
 
+    // If layout found that the branch might not be able to reach its target, then we branch to a
+    // thunk placed after the section instead, which then jumps to the target.
+    let thunk_offset = section_info.section_index.and_then(|section_index| {
+        arch.branch_range(r_type)?;
+        object_layout.thunks.get(&ThunkTarget {
+            section_index,
+            symbol_id: layout.symbol_db.definition(local_symbol_id),
+            addend: rel.r_addend.get(e),
+        })
+    });
+    let value = if let Some(thunk_offset) = thunk_offset {
+        let thunk_size = arch
+            .thunk_info()
+            .context("Thunk allocated on architecture without thunks")?
+            .size;
+        let thunk_address = section_address + thunk_offset;
+        arch.write_thunk(
+            &mut out[thunk_offset as usize..][..thunk_size as usize],
+            thunk_address,
+            place.wrapping_add(value),
+        )?;
+        thunk_address.wrapping_sub(place)
+    } else {
+        value
+    };
+
+    if is_high_part(rel_info) {
+        table_writer.high_part_values.insert(place, value);
+    }
+
     if let Some(relaxation) = relaxation {
         tracing::trace!(kind = ?relaxation.debug_kind(), %value_flags, %resolution_flags, ?rel_info.kind, value, value_hex = %HexU64::new(value), %symbol_name, "relaxation applied");
     } else {
         tracing::trace!(%value_flags, %resolution_flags, ?rel_info.kind, value, value_hex = %HexU64::new(value), %symbol_name, "relocation applied");
     }
 
+    if let Some(range) = arch
+        .branch_range(r_type)
+        .or_else(|| arch.fixed_branch_range(r_type))
+    {
+        let target = place.wrapping_add(value);
+        if !crate::thunks::is_in_range(place, target, range) {
+            bail!(
+                "`{symbol_name}` at 0x{target:x} is out of range of {} at 0x{place:x}",
+                arch.rel_type_to_string(r_type),
+            );
+        }
+    }
+
+    if references_got(rel_info.kind)
+        && arch
+            .got_relocation_range(r_type)
+            .is_some_and(|range| !range.contains(&(value as i64)))
+    {
+        let got_size = layout.section_layouts.get(output_section_id::GOT).mem_size;
+        bail!(
+            "GOT entry for `{symbol_name}` is out of range of {} at 0x{place:x}. The GOT has {} \
+             entries ({got_size} bytes), which is more than the code model used to compile the \
+             object can address. Multiple GOTs aren't supported, so recompile with a code model \
+             that allows a larger GOT, e.g. -fPIC rather than -fpic, or on AArch64, \
+             -mcmodel=small rather than -mcmodel=tiny",
+            arch.rel_type_to_string(r_type),
+            got_size / crate::elf::GOT_ENTRY_SIZE,
+        );
+    }
+
     write_relocation_to_buffer(rel_info.size, value, &mut out[offset_in_section as usize..])?;
 
     Ok(next_modifier)
 }
 
-fn apply_debug_relocation<S: StorageModel, A: Arch>(
+/// Returns whether relocations of the supplied kind reference an entry in the GOT.
+fn references_got(kind: RelocationKind) -> bool {
+    matches!(
+        kind,
+        RelocationKind::Got
+            | RelocationKind::GotRelative
+            | RelocationKind::GotRelGotBase
+            | RelocationKind::TlsGd
+            | RelocationKind::TlsGdGot
+            | RelocationKind::TlsGdGotBase
+            | RelocationKind::TlsLd
+            | RelocationKind::TlsLdGot
+            | RelocationKind::TlsLdGotBase
+            | RelocationKind::GotTpOff
+            | RelocationKind::GotTpOffGot
+            | RelocationKind::GotTpOffGotBase
+            | RelocationKind::TlsDesc
+            | RelocationKind::TlsDescGot
+            | RelocationKind::TlsDescGotBase
+    )
+}
+
+fn apply_debug_relocation<S: StorageModel>(
     object_layout: &ObjectLayout,
     offset_in_section: u64,
     rel: &elf::Rela,
@@ -2026,6 +2865,12 @@ fn apply_debug_relocation<S: StorageModel, A: Arch>(
     out: &mut [u8],
 ) -> Result<()> {
     let e = LittleEndian;
+    let r_type = rel.r_type(e, false);
+    let rel_info = layout.args().arch().relocation_from_raw(r_type)?;
+    // Relocatable objects have these where the debug info referred to a discarded section.
+    if rel_info.kind == RelocationKind::None {
+        return Ok(());
+    }
     let symbol_index = rel
         .symbol(e, false)
         .context("Unsupported absolute relocation")?;
@@ -2033,15 +2878,19 @@ fn apply_debug_relocation<S: StorageModel, A: Arch>(
     let section_index = object_layout.object.symbol_section(sym, symbol_index)?;
 
     let addend = rel.r_addend.get(e);
-    let r_type = rel.r_type(e, false);
-    let rel_info = A::relocation_from_raw(r_type)?;
 
     let resolution = layout
         .merged_symbol_resolution(object_layout.symbol_id_range.input_to_id(symbol_index))
         .or_else(|| {
-            section_index.and_then(|section_index| {
-                object_layout.section_resolutions[section_index.0].full_resolution()
-            })
+            // Symbols that are only referenced from debug info, e.g. the unnamed labels that
+            // RISC-V line tables use, don't have resolutions of their own.
+            let section_index = section_index?;
+            let mut resolution =
+                object_layout.section_resolutions[section_index.0].full_resolution()?;
+            resolution.raw_value += object_layout
+                .relaxations
+                .output_offset(section_index, sym.st_value(e));
+            Some(resolution)
         });
 
     let value = if let Some(resolution) = resolution {
@@ -2053,10 +2902,42 @@ fn apply_debug_relocation<S: StorageModel, A: Arch>(
                 &layout.merged_strings,
                 &layout.merged_string_start_addresses,
             )?,
+            RelocationKind::DtpOff
+                if matches!(
+                    layout.args().arch(),
+                    Architecture::RiscV64 | Architecture::PowerPc64
+                ) =>
+            {
+                resolution
+                    .value()
+                    .wrapping_sub(layout.tls_start_address())
+                    .wrapping_sub(layout.args().arch().tls_dtv_offset())
+                    .wrapping_add(addend as u64)
+            }
             RelocationKind::DtpOff => resolution
                 .value()
                 .wrapping_sub(layout.tls_end_address())
                 .wrapping_add(addend as u64),
+            RelocationKind::AbsoluteSet => resolution.value_with_addend(
+                addend,
+                symbol_index,
+                object_layout,
+                &layout.merged_strings,
+                &layout.merged_string_start_addresses,
+            )?,
+            RelocationKind::AbsoluteAddition | RelocationKind::AbsoluteSubtraction => {
+                adjust_existing_value(
+                    rel_info,
+                    resolution.value_with_addend(
+                        addend,
+                        symbol_index,
+                        object_layout,
+                        &layout.merged_strings,
+                        &layout.merged_string_start_addresses,
+                    )?,
+                    &out[offset_in_section as usize..],
+                )?
+            }
             kind => bail!("Unsupported debug relocation kind {kind:?}"),
         }
     } else if let Some(section_index) = section_index {
@@ -2083,7 +2964,35 @@ fn apply_debug_relocation<S: StorageModel, A: Arch>(
     Ok(())
 }
 
-fn write_absolute_relocation<S: StorageModel, A: Arch>(
+/// Returns the result of adding or subtracting `symbol_value` to or from the value that's already
+/// at the place, which is how RISC-V computes the difference between two labels.
+fn adjust_existing_value(
+    rel_info: RelocationKindInfo,
+    symbol_value: u64,
+    existing: &[u8],
+) -> Result<u64> {
+    let existing = read_relocation_from_buffer(rel_info.size, existing)?;
+    let value = if rel_info.kind == RelocationKind::AbsoluteAddition {
+        existing.wrapping_add(symbol_value)
+    } else {
+        existing.wrapping_sub(symbol_value)
+    };
+    Ok(truncate_relocation_value(rel_info.size, value))
+}
+
+/// Returns whether a relocation computes the high part of a value, the low part of which will be
+/// filled in by a separate `PairedLow` relocation.
+fn is_high_part(rel_info: RelocationKindInfo) -> bool {
+    matches!(
+        rel_info.size,
+        RelocationSize::BitMasking(BitMask {
+            instruction: RelocationInstruction::RiscV(RiscVInstruction::UType),
+            ..
+        })
+    )
+}
+
+fn write_absolute_relocation<S: StorageModel>(
     table_writer: &mut TableWriter,
     resolution: Resolution,
     place: u64,
@@ -2094,7 +3003,7 @@ fn write_absolute_relocation<S: StorageModel, A: Arch>(
     layout: &Layout<S>,
 ) -> Result<u64> {
     if resolution.value_flags.contains(ValueFlags::DYNAMIC) && section_info.is_writable {
-        table_writer.write_dynamic_symbol_relocation::<A>(
+        table_writer.write_dynamic_symbol_relocation(
             place,
             addend,
             resolution.dynamic_symbol_index()?,
@@ -2108,7 +3017,7 @@ fn write_absolute_relocation<S: StorageModel, A: Arch>(
             &layout.merged_strings,
             &layout.merged_string_start_addresses,
         )?;
-        table_writer.write_address_relocation::<A>(place, address as i64)?;
+        table_writer.write_address_relocation(place, address as i64)?;
         Ok(0)
     } else if resolution.value_flags.contains(ValueFlags::IFUNC) {
         Ok(resolution.plt_address()?.wrapping_add(addend as u64))
@@ -2123,8 +3032,35 @@ fn write_absolute_relocation<S: StorageModel, A: Arch>(
     }
 }
 
+fn write_auth_relocation<S: StorageModel>(
+    table_writer: &mut TableWriter,
+    resolution: Resolution,
+    place: u64,
+    addend: i64,
+    symbol_index: object::SymbolIndex,
+    object_layout: &ObjectLayout,
+    layout: &Layout<S>,
+) -> Result {
+    if resolution.value_flags.contains(ValueFlags::DYNAMIC) {
+        return table_writer.write_rela_dyn_general(
+            place,
+            resolution.dynamic_symbol_index()?,
+            linker_utils::aarch64::R_AARCH64_AUTH_ABS64,
+            addend,
+        );
+    }
+    let address = resolution.value_with_addend(
+        addend,
+        symbol_index,
+        object_layout,
+        &layout.merged_strings,
+        &layout.merged_string_start_addresses,
+    )?;
+    table_writer.write_auth_relative_relocation(place, address as i64)
+}
+
 impl PreludeLayout {
-    fn write_file<S: StorageModel, A: Arch>(
+    fn write_file<S: StorageModel>(
         &self,
         buffers: &mut OutputSectionPartMap<&mut [u8]>,
         table_writer: &mut TableWriter,
@@ -2133,17 +3069,21 @@ impl PreludeLayout {
         let header: &mut FileHeader = from_bytes_mut(buffers.get_mut(part_id::FILE_HEADER))
             .map_err(|_| anyhow!("Invalid file header allocation"))?
             .0;
-        populate_file_header::<S, A>(layout, &self.header_info, header)?;
+        populate_file_header::<S>(layout, &self.header_info, header)?;
 
         let mut program_headers =
             ProgramHeaderWriter::new(buffers.get_mut(part_id::PROGRAM_HEADERS));
         write_program_headers(&mut program_headers, layout)?;
 
-        write_section_headers(buffers.get_mut(part_id::SECTION_HEADERS), layout);
-
-        write_section_header_strings(buffers.get_mut(part_id::SHSTRTAB), &layout.output_sections);
+        if !layout.args().strip_section_headers {
+            write_section_headers(buffers.get_mut(part_id::SECTION_HEADERS), layout);
+            write_section_header_strings(
+                buffers.get_mut(part_id::SHSTRTAB),
+                &layout.output_sections,
+            );
+        }
 
-        self.write_plt_got_entries::<S, A>(layout, table_writer)?;
+        self.write_plt_got_entries::<S>(layout, table_writer)?;
 
         if !layout.args().strip_all {
             self.write_symbol_table_entries(&mut table_writer.debug_symbol_writer, layout)?;
@@ -2215,15 +3155,25 @@ impl PreludeLayout {
             .copy_from_slice(self.identity.as_bytes());
     }
 
-    fn write_plt_got_entries<S: StorageModel, A: Arch>(
+    fn write_plt_got_entries<S: StorageModel>(
         &self,
         layout: &Layout<S>,
         table_writer: &mut TableWriter,
     ) -> Result {
+        if let Some(header) = &self.lazy_plt_header {
+            table_writer.write_plt_header(
+                header,
+                layout
+                    .section_layouts
+                    .get(output_section_id::DYNAMIC)
+                    .mem_offset,
+            )?;
+        }
+
         // Write a pair of GOT entries for use by any TLSLD or TLSGD relocations.
         if let Some(got_address) = self.tlsld_got_entry {
             if layout.args().output_kind().is_executable() {
-                table_writer.process_resolution::<A>(&Resolution {
+                table_writer.process_resolution(&Resolution {
                     raw_value: crate::elf::CURRENT_EXE_TLS_MOD,
                     dynamic_symbol_index: None,
                     got_address: Some(got_address),
@@ -2233,9 +3183,9 @@ impl PreludeLayout {
                 })?;
             } else {
                 table_writer.take_next_got_entry()?;
-                table_writer.write_dtpmod_relocation::<A>(got_address.get(), 0)?;
+                table_writer.write_dtpmod_relocation(got_address.get(), 0)?;
             }
-            table_writer.process_resolution::<A>(&Resolution {
+            table_writer.process_resolution(&Resolution {
                 raw_value: 0,
                 dynamic_symbol_index: None,
                 got_address: Some(got_address.saturating_add(elf::GOT_ENTRY_SIZE)),
@@ -2245,11 +3195,7 @@ impl PreludeLayout {
             })?;
         }
 
-        write_internal_symbols_plt_got_entries::<S, A>(
-            &self.internal_symbols,
-            table_writer,
-            layout,
-        )?;
+        write_internal_symbols_plt_got_entries::<S>(&self.internal_symbols, table_writer, layout)?;
         Ok(())
     }
 
@@ -2261,6 +3207,21 @@ impl PreludeLayout {
         // Define symbol 0. This needs to be a null placeholder.
         symbol_writer.define_symbol(true, 0, 0, 0, &[])?;
 
+        if layout.args().keeps_input_relocations() {
+            let mut values =
+                vec![0; usize::from(self.header_info.num_output_sections_with_content)];
+            for (section_id, _) in layout.output_sections.ids_with_info() {
+                if let Some(index) = layout.output_sections.output_index_of_section(section_id) {
+                    values[usize::from(index)] =
+                        crate::relocatable_object::section_symbol_value(layout, section_id);
+                }
+            }
+            for section_index in 1..self.header_info.num_output_sections_with_content {
+                symbol_writer
+                    .define_section_symbol(section_index, values[usize::from(section_index)])?;
+            }
+        }
+
         let internal_symbols = &self.internal_symbols;
 
         write_internal_symbols(internal_symbols, layout, symbol_writer)?;
@@ -2294,9 +3255,12 @@ fn write_epilogue_dynamic_entries<S: StorageModel>(
     let inputs = DynamicEntryInputs {
         args: layout.args(),
         has_static_tls: layout.has_static_tls,
+        has_unique_symbols: layout.has_unique_symbols,
+        plt_features: layout.plt_layout.features,
         section_layouts: &layout.section_layouts,
         section_part_layouts: &layout.section_part_layouts,
         non_addressable_counts: layout.non_addressable_counts,
+        memtag_globals_size: layout.memtag_globals.len() as u64,
     };
 
     for writer in EPILOGUE_DYNAMIC_ENTRY_WRITERS {
@@ -2307,17 +3271,13 @@ fn write_epilogue_dynamic_entries<S: StorageModel>(
 }
 
 impl EpilogueLayout<'_> {
-    fn write_file<S: StorageModel, A: Arch>(
+    fn write_file<S: StorageModel>(
         &self,
         buffers: &mut OutputSectionPartMap<&mut [u8]>,
         table_writer: &mut TableWriter,
         layout: &Layout<S>,
     ) -> Result {
-        write_internal_symbols_plt_got_entries::<S, A>(
-            &self.internal_symbols,
-            table_writer,
-            layout,
-        )?;
+        write_internal_symbols_plt_got_entries::<S>(&self.internal_symbols, table_writer, layout)?;
 
         if !layout.args().strip_all {
             write_internal_symbols(
@@ -2328,19 +3288,173 @@ impl EpilogueLayout<'_> {
         }
         if layout.args().needs_dynamic() {
             write_epilogue_dynamic_entries(layout, table_writer)?;
+            write_version_definitions(
+                buffers.get_mut(part_id::GNU_VERSION_D),
+                table_writer,
+                layout,
+            )?;
         }
-        write_gnu_hash_tables(self, buffers)?;
-
-        write_dynamic_symbol_definitions(self, table_writer, layout)?;
+        // The hash tables and the dynamic symbols are written to separate buffers, so for shared
+        // objects with lots of exported symbols, we can save some time by writing them in
+        // parallel.
+        let (hash_result, dynsym_result) = crate::threading::join(
+            || write_gnu_hash_tables(self, buffers),
+            || write_dynamic_symbol_definitions(self, table_writer, layout),
+        );
+        hash_result?;
+        dynsym_result?;
 
-        if !&self.gnu_property_notes.is_empty() {
+        if !self.gnu_property_notes.is_empty() || self.pauth_core_info.is_some() || self.memory_seal
+        {
             write_gnu_property_notes(self, buffers)?;
         }
 
+        if !self.build_attributes.is_empty() {
+            crate::build_attributes::write(
+                &self.build_attributes,
+                buffers.get_mut(part_id::GNU_BUILD_ATTRIBUTES),
+                |location| {
+                    let FileLayout::Object(object) = layout.file_layout(location.file_id) else {
+                        bail!("Build attribute note refers to a non-object");
+                    };
+                    let section_address = object.section_resolutions[location.section_index.0]
+                        .address()
+                        .context("Build attribute note refers to an unloaded section")?;
+                    Ok(section_address + location.offset)
+                },
+            )?;
+        }
+
+        if !self.arch_attributes.is_empty() {
+            buffers
+                .get_mut(part_id::RISCV_ATTRIBUTES)
+                .copy_from_slice(&self.arch_attributes);
+        }
+
+        crate::separate_debug::write_debuglink_name(
+            buffers.get_mut(part_id::GNU_DEBUGLINK),
+            layout.args(),
+        )?;
+
         Ok(())
     }
 }
 
+/// Returns a copy of a global symbol that we've downgraded to a local, with its binding changed to
+/// match. It's also made hidden, so that it's clear that it wasn't local in its input.
+fn downgraded_to_local(sym: &crate::elf::Symbol) -> crate::elf::Symbol {
+    let mut sym = *sym;
+    sym.st_info = (object::elf::STB_LOCAL << 4) | sym.st_type();
+    sym.st_other = (sym.st_other & !0x3) | object::elf::STV_HIDDEN;
+    sym
+}
+
+fn with_size(sym: &crate::elf::Symbol, size: u64) -> crate::elf::Symbol {
+    let mut sym = *sym;
+    sym.st_size.set(LittleEndian, size);
+    sym
+}
+
+/// Writes .gnu.version_d. The first definition is the base definition, which names the output. With
+/// `--default-symver`, it's followed by a definition with the same name. Each of the versions from
+/// our version script comes next, with an auxiliary entry for its name and then one for each
+/// version that it depends on.
+fn write_version_definitions<S: StorageModel>(
+    mut out: &mut [u8],
+    table_writer: &mut TableWriter,
+    layout: &Layout<S>,
+) -> Result {
+    if out.is_empty() {
+        return Ok(());
+    }
+    let e = LittleEndian;
+    let versions = layout.symbol_db.version_script.named_versions();
+    let strtab_writer = &mut table_writer.dynsym_writer.strtab_writer;
+    let base_name = crate::layout::base_version_name(layout.args());
+    let base_name_offset = strtab_writer.write_str(base_name);
+    let default_version = layout
+        .args()
+        .default_symver
+        .then(|| (base_name, strtab_writer.write_str(base_name), Vec::new()));
+    let name_offsets = versions
+        .iter()
+        .map(|version| strtab_writer.write_str(version.name.unwrap_or_default().as_bytes()))
+        .collect::<Vec<_>>();
+
+    let definitions = std::iter::once((base_name, base_name_offset, Vec::new()))
+        .chain(default_version)
+        .chain(
+            versions
+                .iter()
+                .zip(&name_offsets)
+                .map(|(version, name_offset)| {
+                    let parent_offsets = version
+                        .parents
+                        .iter()
+                        .filter_map(|parent| {
+                            let index = versions.iter().position(|v| v.name == Some(*parent))?;
+                            Some(name_offsets[index])
+                        })
+                        .collect::<Vec<_>>();
+                    (
+                        version.name.unwrap_or_default().as_bytes(),
+                        *name_offset,
+                        parent_offsets,
+                    )
+                }),
+        )
+        .collect::<Vec<_>>();
+    let num_definitions = definitions.len();
+    for (index, (name, name_offset, parent_offsets)) in definitions.into_iter().enumerate() {
+        let num_auxes = 1 + parent_offsets.len();
+        let size = size_of::<elf::Verdef>() + num_auxes * size_of::<elf::Verdaux>();
+        let is_last = index + 1 == num_definitions;
+        let bytes = crate::slice::try_slice_take_prefix_mut(&mut out, size)
+            .ok_or_else(|| insufficient_allocation(".gnu.version_d"))?;
+        let (verdef, aux_bytes) = from_bytes_mut::<elf::Verdef>(bytes)
+            .map_err(|_| anyhow!("Incorrect .gnu.version_d alignment"))?;
+        verdef.vd_version.set(e, object::elf::VER_DEF_CURRENT);
+        verdef.vd_flags.set(
+            e,
+            if index == 0 {
+                object::elf::VER_FLG_BASE
+            } else {
+                0
+            },
+        );
+        verdef
+            .vd_ndx
+            .set(e, object::elf::VER_NDX_GLOBAL + index as u16);
+        verdef.vd_cnt.set(e, num_auxes as u16);
+        verdef.vd_hash.set(e, object::elf::hash(name));
+        verdef.vd_aux.set(e, size_of::<elf::Verdef>() as u32);
+        verdef.vd_next.set(e, if is_last { 0 } else { size as u32 });
+
+        let auxes = object::slice_from_all_bytes_mut::<elf::Verdaux>(aux_bytes)
+            .map_err(|_| anyhow!("Invalid .gnu.version_d allocation"))?;
+        for (aux_index, (aux, offset)) in auxes
+            .iter_mut()
+            .zip(std::iter::once(name_offset).chain(parent_offsets))
+            .enumerate()
+        {
+            aux.vda_name.set(e, offset);
+            let vda_next = if aux_index + 1 == num_auxes {
+                0
+            } else {
+                size_of::<elf::Verdaux>() as u32
+            };
+            aux.vda_next.set(e, vda_next);
+        }
+    }
+    if !out.is_empty() {
+        bail!(
+            "Allocated too much space in .gnu.version_d. {} bytes remain",
+            out.len()
+        );
+    }
+    Ok(())
+}
+
 fn write_gnu_property_notes(
     epilogue: &EpilogueLayout,
     buffers: &mut OutputSectionPartMap<&mut [u8]>,
@@ -2352,13 +3466,26 @@ fn write_gnu_property_notes(
     note_header.n_namesz.set(e, GNU_NOTE_NAME.len() as u32);
     note_header.n_descsz.set(
         e,
-        (epilogue.gnu_property_notes.len() * GNU_NOTE_PROPERTY_ENTRY_SIZE) as u32,
+        gnu_property_notes_descriptor_size(
+            &epilogue.gnu_property_notes,
+            epilogue.pauth_core_info.is_some(),
+            epilogue.memory_seal,
+        ) as u32,
     );
     note_header.n_type.set(e, NT_GNU_PROPERTY_TYPE_0);
 
     let name_out = crate::slice::slice_take_prefix_mut(&mut rest, GNU_NOTE_NAME.len());
     name_out.copy_from_slice(GNU_NOTE_NAME);
 
+    // Properties must be sorted by type. The memory seal property has a lower type than any of the
+    // processor-specific properties, so it goes first.
+    if epilogue.memory_seal {
+        let entry_bytes =
+            crate::slice::slice_take_prefix_mut(&mut rest, GNU_NOTE_PROPERTY_HEADER_SIZE);
+        entry_bytes[..4].copy_from_slice(&GNU_PROPERTY_MEMORY_SEAL.to_le_bytes());
+        entry_bytes[4..].copy_from_slice(&0_u32.to_le_bytes());
+    }
+
     for note in &epilogue.gnu_property_notes {
         let entry_bytes = crate::slice::slice_take_prefix_mut(&mut rest, size_of::<NoteProperty>());
         let property: &mut NoteProperty = bytemuck::from_bytes_mut(entry_bytes);
@@ -2368,6 +3495,17 @@ fn write_gnu_property_notes(
         property.pr_padding = 0;
     }
 
+    // GNU_PROPERTY_AARCH64_FEATURE_PAUTH comes after GNU_PROPERTY_AARCH64_FEATURE_1_AND, which is
+    // the only other AArch64 property that we emit.
+    if let Some(info) = epilogue.pauth_core_info {
+        let entry_bytes =
+            crate::slice::slice_take_prefix_mut(&mut rest, elf::GNU_NOTE_PAUTH_CORE_INFO_SIZE);
+        entry_bytes[..4].copy_from_slice(&GNU_PROPERTY_AARCH64_FEATURE_PAUTH.to_le_bytes());
+        entry_bytes[4..8].copy_from_slice(&16_u32.to_le_bytes());
+        entry_bytes[8..16].copy_from_slice(&info.platform.to_le_bytes());
+        entry_bytes[16..].copy_from_slice(&info.version.to_le_bytes());
+    }
+
     Ok(())
 }
 
@@ -2398,38 +3536,65 @@ fn write_gnu_hash_tables(
         object::slice_from_bytes_mut::<u32>(rest, epilogue.dynamic_symbol_definitions.len())
             .map_err(|_| anyhow!("Insufficient bytes for .gnu.hash chains"))?;
 
-    bloom.fill(0);
+    let sym_defs = &epilogue.dynamic_symbol_definitions;
+
+    // Symbol definitions were sorted by bucket during layout, so each part of the table can be
+    // computed independently of the others.
+
+    // Chain values are the hashes for the corresponding symbols (shifted by symbol_base). Bit 0 is
+    // set to indicate the end of the chain.
+    chains
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(i, chain_out)| {
+            let hash = sym_defs[i].hash;
+            let bucket = gnu_hash_layout.bucket_for_hash(hash);
+            let last_in_chain = sym_defs
+                .get(i + 1)
+                .is_none_or(|next| gnu_hash_layout.bucket_for_hash(next.hash) != bucket);
+            *chain_out = (hash & !1) | u32::from(last_in_chain);
+        });
 
-    let mut sym_defs = epilogue.dynamic_symbol_definitions.iter().peekable();
+    // Each bucket points to the first symbol in its chain, or is zero if the bucket is empty.
+    buckets
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(bucket, bucket_out)| {
+            let bucket = bucket as u32;
+            let start =
+                sym_defs.partition_point(|d| gnu_hash_layout.bucket_for_hash(d.hash) < bucket);
+            *bucket_out = sym_defs
+                .get(start)
+                .filter(|d| gnu_hash_layout.bucket_for_hash(d.hash) == bucket)
+                .map_or(0, |_| start as u32 + gnu_hash_layout.symbol_base);
+        });
 
+    // For each symbol, we set two bits in the bloom filter. This speeds up dynamic loading, since
+    // most symbols not defined by the shared object can be rejected just by the bloom filter. Any
+    // symbol can affect any word of the filter, so we build partial filters for chunks of symbols,
+    // then combine them.
+    const BLOOM_CHUNK_SIZE: usize = 16 * 1024;
     let elf_class_bits = size_of::<u64>() as u32 * 8;
-
-    let mut start_of_chain = true;
-    for (i, chain_out) in chains.iter_mut().enumerate() {
-        let sym_def = sym_defs.next().unwrap();
-
-        // For each symbol, we set two bits in the bloom filter. This speeds up dynamic loading,
-        // since most symbols not defined by the shared object can be rejected just by the bloom
-        // filter.
-        let bloom_index = ((sym_def.hash / elf_class_bits) % gnu_hash_layout.bloom_count) as usize;
-        let bit1 = 1 << (sym_def.hash % elf_class_bits);
-        let bit2 = 1 << ((sym_def.hash >> gnu_hash_layout.bloom_shift) % elf_class_bits);
-        bloom[bloom_index] |= bit1 | bit2;
-
-        // Chain values are the hashes for the corresponding symbols (shifted by symbol_base). Bit 0
-        // is cleared and then later set to 1 to indicate the end of the chain.
-        *chain_out = sym_def.hash & !1;
-        let bucket = gnu_hash_layout.bucket_for_hash(sym_def.hash);
-        if start_of_chain {
-            buckets[bucket as usize] = (i as u32) + gnu_hash_layout.symbol_base;
-            start_of_chain = false;
-        }
-        let last_in_chain = sym_defs
-            .peek()
-            .is_none_or(|next| gnu_hash_layout.bucket_for_hash(next.hash) != bucket);
-        if last_in_chain {
-            *chain_out |= 1;
-            start_of_chain = true;
+    let bloom_count = gnu_hash_layout.bloom_count;
+    let partial_blooms = (0..sym_defs.len().div_ceil(BLOOM_CHUNK_SIZE))
+        .into_par_iter()
+        .map(|chunk_index| {
+            let mut partial = vec![0_u64; bloom_count as usize];
+            let start = chunk_index * BLOOM_CHUNK_SIZE;
+            let end = (start + BLOOM_CHUNK_SIZE).min(sym_defs.len());
+            for sym_def in &sym_defs[start..end] {
+                let bloom_index = ((sym_def.hash / elf_class_bits) % bloom_count) as usize;
+                let bit1 = 1 << (sym_def.hash % elf_class_bits);
+                let bit2 = 1 << ((sym_def.hash >> gnu_hash_layout.bloom_shift) % elf_class_bits);
+                partial[bloom_index] |= bit1 | bit2;
+            }
+            partial
+        })
+        .collect::<Vec<_>>();
+    bloom.fill(0);
+    for partial in partial_blooms {
+        for (word, partial_word) in bloom.iter_mut().zip(partial) {
+            *word |= partial_word;
         }
     }
     Ok(())
@@ -2452,12 +3617,10 @@ fn write_dynamic_symbol_definitions<S: StorageModel>(
                     &mut table_writer.dynsym_writer,
                 )?;
 
-                // We don't yet support setting symbol versions for symbols that we export, so right
-                // now we just set them all to the global version.
                 if let Some(version_out) =
                     crate::slice::take_first_mut(&mut table_writer.version_writer.versym)
                 {
-                    version_out.0.set(LittleEndian, object::elf::VER_NDX_GLOBAL);
+                    version_out.0.set(LittleEndian, sym_def.version);
                 }
             }
             FileLayout::Dynamic(object) => {
@@ -2468,12 +3631,15 @@ fn write_dynamic_symbol_definitions<S: StorageModel>(
                     &mut table_writer.dynsym_writer,
                 )?;
 
-                write_symbol_version(
-                    object.input_symbol_versions,
-                    object.symbol_id_range.id_to_offset(sym_def.symbol_id),
-                    &object.version_mapping,
-                    &mut table_writer.version_writer.versym,
-                )?;
+                if layout.gnu_version_enabled() {
+                    write_symbol_version(
+                        object.input_symbol_versions,
+                        object.symbol_id_range.id_to_offset(sym_def.symbol_id),
+                        &object.version_mapping,
+                        object.default_version,
+                        &mut table_writer.version_writer.versym,
+                    )?;
+                }
             }
             _ => bail!(
                 "Internal error: Unexpected dynamic symbol definition from {:?}. {}",
@@ -2486,25 +3652,32 @@ fn write_dynamic_symbol_definitions<S: StorageModel>(
     Ok(())
 }
 
+/// Writes a dynamic symbol that's defined by a shared object, but that we provide the address of,
+/// either because of a copy relocation or a canonical PLT entry.
 fn write_copy_relocation_dynamic_symbol_definition<S: StorageModel>(
     sym_def: &crate::layout::DynamicSymbolDefinition,
     object: &DynamicLayout,
     layout: &Layout<S>,
     dynamic_symbol_writer: &mut SymbolTableWriter,
 ) -> Result {
-    debug_assert_bail!(
-        layout
-            .resolution_flags_for_symbol(sym_def.symbol_id)
-            .contains(ResolutionFlags::COPY_RELOCATION),
-        "Tried to write copy relocation for symbol without COPY_RELOCATION flag"
-    );
+    let resolution_flags = layout.resolution_flags_for_symbol(sym_def.symbol_id);
     let sym_index = sym_def.symbol_id.to_input(object.symbol_id_range);
     let sym = object.object.symbol(sym_index)?;
     let name = sym_def.name;
-    let shndx = layout
-        .output_sections
-        .output_index_of_section(output_section_id::BSS)
-        .context("Copy relocation with no BSS section")?;
+    let shndx = if resolution_flags.contains(ResolutionFlags::CANONICAL_PLT) {
+        // The symbol remains undefined, but its non-zero value tells the dynamic loader to
+        // resolve references from other modules to our PLT entry.
+        0
+    } else {
+        debug_assert_bail!(
+            resolution_flags.contains(ResolutionFlags::COPY_RELOCATION),
+            "Tried to write copy relocation for symbol without COPY_RELOCATION flag"
+        );
+        layout
+            .output_sections
+            .output_index_of_section(output_section_id::BSS)
+            .context("Copy relocation with no BSS section")?
+    };
     let res = layout
         .local_symbol_resolution(sym_def.symbol_id)
         .context("Copy relocation for unresolved symbol")?;
@@ -2527,6 +3700,13 @@ fn write_regular_object_dynamic_symbol_definition<S: StorageModel>(
 ) -> Result {
     let sym_index = sym_def.symbol_id.to_input(object.symbol_id_range);
     let sym = object.object.symbol(sym_index)?;
+    let resized;
+    let sym = if let Some(size) = object.relaxed_symbol_size(sym_index)? {
+        resized = with_size(sym, size);
+        &resized
+    } else {
+        sym
+    };
     let name = sym_def.name;
     if let Some(section_index) = object.object.symbol_section(sym, sym_index)? {
         let SectionSlot::Loaded(section) = &object.sections[section_index.0] else {
@@ -2553,6 +3733,15 @@ fn write_regular_object_dynamic_symbol_definition<S: StorageModel>(
             .with_context(|| {
                 format!("Failed to copy dynamic {}", layout.symbol_debug(symbol_id))
             })?;
+    } else if sym.is_absolute(LittleEndian) || object.object.just_symbols {
+        dynamic_symbol_writer
+            .copy_symbol_shndx(sym, name, object::elf::SHN_ABS, sym.st_value(LittleEndian))
+            .with_context(|| {
+                format!(
+                    "Failed to copy dynamic {}",
+                    layout.symbol_debug(sym_def.symbol_id)
+                )
+            })?;
     } else {
         dynamic_symbol_writer
             .copy_symbol_shndx(sym, name, 0, 0)
@@ -2579,34 +3768,66 @@ fn write_internal_symbols<S: StorageModel>(
         let Some(resolution) = layout.local_symbol_resolution(symbol_id) else {
             continue;
         };
-        let Some(section_id) = def_info.section_id() else {
-            // The null symbol is currently handled elsewhere. TODO: See if the code would be
-            // simpler if we just handled it here.
-            continue;
-        };
-
         let symbol_name = layout.symbol_db.symbol_name(symbol_id)?;
-        let mut shndx = layout
-            .output_sections
-            .output_index_of_section(section_id)
-            .with_context(|| {
-                format!(
-                    "symbol `{}` in section `{}` that we're not going to output {resolution:?}",
-                    symbol_name,
-                    layout.output_sections.display_name(section_id)
-                )
-            })?;
+        let mut st_other = object::elf::STV_DEFAULT;
 
-        // Move symbols that are in our header (section 0) into the first section, otherwise they'll
-        // show up as undefined.
-        if shndx == 0 {
-            shndx = 1;
-        }
+        let (shndx, section_id) = if let InternalSymDefInfo::ScriptAssignment(_) = def_info {
+            if let ParsedInput::Prelude(prelude) = layout.symbol_db.file(PRELUDE_FILE_ID) {
+                if prelude
+                    .script_assignment(symbol_id)
+                    .is_some_and(|assignment| assignment.hidden)
+                {
+                    st_other = object::elf::STV_HIDDEN;
+                }
+            }
+            if resolution.value_flags.contains(ValueFlags::ABSOLUTE) {
+                (object::elf::SHN_ABS, None)
+            } else {
+                script_symbol_section(layout, symbol_id, resolution.raw_value)
+                    .map_or((object::elf::SHN_ABS, None), |(section_id, shndx)| {
+                        (shndx, Some(section_id))
+                    })
+            }
+        } else {
+            let Some(section_id) = def_info.section_id() else {
+                // The null symbol is currently handled elsewhere. TODO: See if the code would be
+                // simpler if we just handled it here.
+                continue;
+            };
 
-        let address = resolution.address()?;
+            let mut shndx = layout
+                .output_sections
+                .output_index_of_section(section_id)
+                .with_context(|| {
+                    format!(
+                        "symbol `{}` in section `{}` that we're not going to output {resolution:?}",
+                        symbol_name,
+                        layout.output_sections.display_name(section_id)
+                    )
+                })?;
+
+            // Move symbols that are in our header (section 0) into the first section, otherwise
+            // they'll show up as undefined.
+            if shndx == 0 {
+                shndx = 1;
+            }
+            (shndx, Some(section_id))
+        };
+
+        let mut address = if shndx == object::elf::SHN_ABS {
+            resolution.raw_value
+        } else {
+            resolution.address()?
+        };
+        if let Some(section_id) = section_id {
+            if layout.args().output_kind() == OutputKind::RelocatableObject {
+                address = crate::relocatable_object::symbol_value(layout, section_id, address);
+            }
+        }
         let entry = symbol_writer
             .define_symbol(false, shndx, address, 0, symbol_name.bytes())
             .with_context(|| format!("Failed to write {}", layout.symbol_debug(symbol_id)))?;
+        entry.st_other = st_other;
 
         let st_type = if symbol_name.bytes() == TLS_MODULE_BASE_SYMBOL_NAME.as_bytes() {
             object::elf::STT_TLS
@@ -2618,6 +3839,60 @@ fn write_internal_symbols<S: StorageModel>(
     Ok(())
 }
 
+/// Returns the ID and index of the section that contains `address`, which is the value of a symbol
+/// assigned by a linker script. A symbol at the end of a section, such as `_etext`, is treated as
+/// being in that section, provided no other section starts there. Returns `None` for symbols
+/// outside of any section, which are made absolute.
+/// Returns the section for a symbol that a linker script assigns to. As with GNU ld, that's the
+/// output section that the assignment is within, provided that the symbol is inside it or at its
+/// end, otherwise the section containing the symbol's address.
+fn script_symbol_section<S: StorageModel>(
+    layout: &Layout<S>,
+    symbol_id: SymbolId,
+    address: u64,
+) -> Option<(OutputSectionId, u16)> {
+    let containing_section = match layout.symbol_db.file(PRELUDE_FILE_ID) {
+        ParsedInput::Prelude(prelude) => prelude.script_output_section(symbol_id),
+        _ => None,
+    }
+    .and_then(|name| layout.output_sections.section_id_by_name(name.as_bytes()))
+    .and_then(|section_id| {
+        let index = layout.output_sections.output_index_of_section(section_id)?;
+        let section = layout.section_layouts.get(section_id);
+        (section.mem_offset..=section.mem_offset + section.mem_size)
+            .contains(&address)
+            .then_some((section_id, index))
+    });
+    containing_section.or_else(|| section_for_address(layout, address))
+}
+
+fn section_for_address<S: StorageModel>(
+    layout: &Layout<S>,
+    address: u64,
+) -> Option<(OutputSectionId, u16)> {
+    let mut ending_at_address = None;
+    for (section_id, info) in layout.output_sections.ids_with_info() {
+        if output_section_id::is_header_section(section_id)
+            || !info.section_flags.contains(shf::ALLOC)
+            || info.section_flags.contains(shf::TLS)
+        {
+            continue;
+        }
+        let Some(index) = layout.output_sections.output_index_of_section(section_id) else {
+            continue;
+        };
+        let section = layout.section_layouts.get(section_id);
+        let end = section.mem_offset + section.mem_size;
+        if (section.mem_offset..end).contains(&address) {
+            return Some((section_id, index));
+        }
+        if end == address {
+            ending_at_address = Some((section_id, index));
+        }
+    }
+    ending_at_address
+}
+
 fn write_eh_frame_hdr<S: StorageModel>(
     table_writer: &mut TableWriter,
     layout: &Layout<S>,
@@ -2706,6 +3981,16 @@ const EPILOGUE_DYNAMIC_ENTRY_WRITERS: &[DynamicEntryWriter] = &[
     DynamicEntryWriter::new(object::elf::DT_SYMENT, |_inputs| {
         size_of::<elf::SymtabEntry>() as u64
     }),
+    DynamicEntryWriter::optional(
+        object::elf::DT_VERDEF,
+        |inputs| inputs.non_addressable_counts.verdef_count > 0,
+        |inputs| inputs.vma_of_section(output_section_id::GNU_VERSION_D),
+    ),
+    DynamicEntryWriter::optional(
+        object::elf::DT_VERDEFNUM,
+        |inputs| inputs.non_addressable_counts.verdef_count > 0,
+        |inputs| inputs.non_addressable_counts.verdef_count,
+    ),
     DynamicEntryWriter::optional(
         object::elf::DT_VERNEED,
         |inputs| {
@@ -2768,6 +4053,41 @@ const EPILOGUE_DYNAMIC_ENTRY_WRITERS: &[DynamicEntryWriter] = &[
         |inputs| inputs.section_part_layouts.get(part_id::RELA_PLT).mem_size > 0,
         |inputs| inputs.section_part_layouts.get(part_id::RELA_PLT).mem_size,
     ),
+    DynamicEntryWriter::optional(
+        elf::DT_AARCH64_BTI_PLT,
+        |inputs| inputs.plt_features.bti,
+        |_| 0,
+    ),
+    DynamicEntryWriter::optional(
+        elf::DT_AARCH64_PAC_PLT,
+        |inputs| inputs.plt_features.pac,
+        |_| 0,
+    ),
+    DynamicEntryWriter::optional(
+        elf::DT_AARCH64_MEMTAG_MODE,
+        |inputs| crate::memtag::is_enabled(inputs.args),
+        |inputs| crate::memtag::dynamic_mode(inputs.args),
+    ),
+    DynamicEntryWriter::optional(
+        elf::DT_AARCH64_MEMTAG_HEAP,
+        |inputs| crate::memtag::is_enabled(inputs.args),
+        |inputs| u64::from(inputs.args.memtag_heap),
+    ),
+    DynamicEntryWriter::optional(
+        elf::DT_AARCH64_MEMTAG_STACK,
+        |inputs| crate::memtag::is_enabled(inputs.args),
+        |inputs| u64::from(inputs.args.memtag_stack),
+    ),
+    DynamicEntryWriter::optional(
+        elf::DT_AARCH64_MEMTAG_GLOBALS,
+        |inputs| inputs.memtag_globals_size > 0,
+        |inputs| inputs.vma_of_section(output_section_id::MEMTAG_GLOBALS_DYNAMIC),
+    ),
+    DynamicEntryWriter::optional(
+        elf::DT_AARCH64_MEMTAG_GLOBALSSZ,
+        |inputs| inputs.memtag_globals_size > 0,
+        |inputs| inputs.memtag_globals_size,
+    ),
     DynamicEntryWriter::new(object::elf::DT_RELA, |inputs| {
         inputs.vma_of_section(output_section_id::RELA_DYN)
     }),
@@ -2809,15 +4129,20 @@ struct DynamicEntryWriter {
 struct DynamicEntryInputs<'layout> {
     args: &'layout Args,
     has_static_tls: bool,
+    has_unique_symbols: bool,
+    plt_features: PltFeatures,
     section_layouts: &'layout OutputSectionMap<OutputRecordLayout>,
     section_part_layouts: &'layout OutputSectionPartMap<OutputRecordLayout>,
     non_addressable_counts: NonAddressableCounts,
+    memtag_globals_size: u64,
 }
 
 impl DynamicEntryInputs<'_> {
     fn dt_flags(&self) -> u64 {
         let mut flags = 0;
-        flags |= object::elf::DF_BIND_NOW;
+        if !self.args.lazy_binding() {
+            flags |= object::elf::DF_BIND_NOW;
+        }
         if !self.args.output_kind().is_executable() && self.has_static_tls {
             flags |= object::elf::DF_STATIC_TLS;
         }
@@ -2826,10 +4151,18 @@ impl DynamicEntryInputs<'_> {
 
     fn dt_flags_1(&self) -> u64 {
         let mut flags = 0;
-        flags |= object::elf::DF_1_NOW;
+        if !self.args.lazy_binding() {
+            flags |= object::elf::DF_1_NOW;
+        }
         if self.args.output_kind().is_executable() && self.args.is_relocatable() {
             flags |= object::elf::DF_1_PIE;
         }
+        // Other objects may bind to our unique definitions, so we mustn't be unloaded. glibc's
+        // loader infers this for itself, but we mark our output as FreeBSD, so we say so
+        // explicitly.
+        if self.has_unique_symbols {
+            flags |= object::elf::DF_1_NODELETE;
+        }
         u64::from(flags)
     }
 
@@ -2907,6 +4240,7 @@ fn write_section_headers<S: StorageModel>(out: &mut [u8], layout: &Layout<S>) {
     let mut entries = entries.iter_mut();
     let mut name_offset = 0;
     let info_inputs = layout.info_inputs();
+    let is_relocatable_output = layout.args().output_kind() == OutputKind::RelocatableObject;
 
     for event in output_sections.sections_and_segments_events() {
         let OrderEvent::Section(section_id) = event else {
@@ -2920,7 +4254,14 @@ fn write_section_headers<S: StorageModel>(out: &mut [u8], layout: &Layout<S>) {
         {
             continue;
         }
-        let entsize = section_id.element_size();
+        let mut entsize = if section_id == output_section_id::PLT_GOT {
+            // PLT entries vary in size depending on which features they need to support.
+            layout.plt_layout.entry_size
+        } else if section_type == sht::RELA {
+            elf::RELA_ENTRY_SIZE
+        } else {
+            section_id.element_size()
+        };
         let size;
         let alignment;
         if section_type == sht::NULL {
@@ -2930,27 +4271,56 @@ fn write_section_headers<S: StorageModel>(out: &mut [u8], layout: &Layout<S>) {
             size = section_layout.mem_size;
             alignment = section_layout.alignment.value();
         };
-        let link = output_section_id::link_ids(section_id)
+        let mut link = output_section_id::link_ids(section_id)
             .iter()
             .find_map(|link_id| output_sections.output_index_of_section(*link_id))
             .unwrap_or(0);
+        let mut info = section_id.info(&info_inputs);
+        let mut flags = output_sections
+            .section_flags(section_id)
+            .without(shf::COMPRESSED);
+        let mut address = section_layout.mem_offset;
+        if is_relocatable_output {
+            // Sections in a relocatable object don't have addresses yet. We keep `SHF_GROUP`,
+            // since we copy section groups and each output section that has it holds only the
+            // members of one group.
+            address = 0;
+            if section_type == sht::NULL {
+                flags = SectionFlags::empty();
+            }
+        }
+        if layout.args().magic == Magic::Omagic && flags.contains(shf::EXECINSTR) {
+            // With `-N`, the text is writable, so we say so.
+            flags |= shf::WRITE;
+        }
+        if crate::relocatable_object::is_copied_relocation_section(layout, section_id) {
+            link = output_sections
+                .output_index_of_section(output_section_id::SYMTAB)
+                .unwrap_or(0);
+            info = crate::relocatable_object::relocation_target_index(
+                layout,
+                output_sections.name(section_id).bytes(),
+            )
+            .map_or(0, u32::from);
+        }
+        if let Some(signature_index) = layout.group_signature_indexes.get(&section_id) {
+            link = output_sections
+                .output_index_of_section(output_section_id::SYMTAB)
+                .unwrap_or(0);
+            info = *signature_index;
+            entsize = size_of::<u32>() as u64;
+        }
         let entry = entries.next().unwrap();
         let e = LittleEndian;
         entry.sh_name.set(e, name_offset);
         entry.sh_type.set(e, section_type.raw());
         // TODO: Section are always uncompressed and the output compression is not supported yet.
-        entry.sh_flags.set(
-            e,
-            output_sections
-                .section_flags(section_id)
-                .without(shf::COMPRESSED)
-                .raw(),
-        );
-        entry.sh_addr.set(e, section_layout.mem_offset);
+        entry.sh_flags.set(e, flags.raw());
+        entry.sh_addr.set(e, address);
         entry.sh_offset.set(e, section_layout.file_offset as u64);
         entry.sh_size.set(e, size);
         entry.sh_link.set(e, link.into());
-        entry.sh_info.set(e, section_id.info(&info_inputs));
+        entry.sh_info.set(e, info);
         entry.sh_addralign.set(e, alignment);
         entry.sh_entsize.set(e, entsize);
         name_offset += layout.output_sections.name(section_id).len() as u32 + 1;
@@ -2991,7 +4361,7 @@ impl<'out> ProgramHeaderWriter<'out> {
     }
 }
 
-fn write_internal_symbols_plt_got_entries<S: StorageModel, A: Arch>(
+fn write_internal_symbols_plt_got_entries<S: StorageModel>(
     internal_symbols: &InternalSymbols,
     table_writer: &mut TableWriter,
     layout: &Layout<S>,
@@ -3002,7 +4372,7 @@ fn write_internal_symbols_plt_got_entries<S: StorageModel, A: Arch>(
             continue;
         }
         if let Some(res) = layout.local_symbol_resolution(symbol_id) {
-            table_writer.process_resolution::<A>(res).with_context(|| {
+            table_writer.process_resolution(res).with_context(|| {
                 format!("Failed to process `{}`", layout.symbol_debug(symbol_id))
             })?;
         }
@@ -3011,7 +4381,7 @@ fn write_internal_symbols_plt_got_entries<S: StorageModel, A: Arch>(
 }
 
 impl<'data> DynamicLayout<'data> {
-    fn write_file<'symbol_db, S: StorageModel, A: Arch>(
+    fn write_file<'symbol_db, S: StorageModel>(
         &self,
         table_writer: &mut TableWriter,
         layout: &Layout<'data, 'symbol_db, S>,
@@ -3038,20 +4408,34 @@ impl<'data> DynamicLayout<'data> {
                         output_section_id::BSS,
                         res.value(),
                     )?;
-                } else {
+                } else if layout.args().emit_relocs {
+                    // The relocations that we copy might refer to the symbol.
+                    table_writer
+                        .debug_symbol_writer
+                        .copy_symbol_shndx(symbol, name, 0, 0)?;
+                }
+                if !res
+                    .resolution_flags
+                    .intersects(ResolutionFlags::COPY_RELOCATION | ResolutionFlags::CANONICAL_PLT)
+                {
+                    // Symbols with canonical PLT entries are written by the epilogue, since they
+                    // need to be in .gnu.hash.
                     table_writer
                         .dynsym_writer
                         .copy_symbol_shndx(symbol, name, 0, 0)?;
 
-                    write_symbol_version(
-                        self.input_symbol_versions,
-                        self.symbol_id_range.id_to_offset(symbol_id),
-                        &self.version_mapping,
-                        &mut table_writer.version_writer.versym,
-                    )?;
+                    if layout.gnu_version_enabled() {
+                        write_symbol_version(
+                            self.input_symbol_versions,
+                            self.symbol_id_range.id_to_offset(symbol_id),
+                            &self.version_mapping,
+                            self.default_version,
+                            &mut table_writer.version_writer.versym,
+                        )?;
+                    }
                 }
 
-                table_writer.process_resolution::<A>(res).with_context(|| {
+                table_writer.process_resolution(res).with_context(|| {
                     format!(
                         "Failed to write {}",
                         layout.symbol_db.symbol_debug(symbol_id)
@@ -3125,11 +4509,44 @@ impl<'data> DynamicLayout<'data> {
                     aux_index += 1;
                 }
             }
+        } else if let Some(version) = self.default_version {
+            self.write_default_verneed(version, table_writer)?;
         }
 
         Ok(())
     }
 
+    /// Writes a record to .gnu.version_r for the version named after our soname that we give to
+    /// the symbols that we import from this object with `--default-imported-symver`.
+    fn write_default_verneed(&self, version: u16, table_writer: &mut TableWriter) -> Result {
+        let e = LittleEndian;
+        let strtab_writer = &mut table_writer.dynsym_writer.strtab_writer;
+        let file_offset = strtab_writer.write_str(self.lib_name);
+        let name_offset = strtab_writer.write_str(self.lib_name);
+        let ver_need = table_writer.version_writer.take_verneed()?;
+        let next_verneed_offset = if self.is_last_verneed {
+            0
+        } else {
+            (size_of::<Verneed>() + size_of::<Vernaux>()) as u32
+        };
+        ver_need.vn_version.set(e, 1);
+        ver_need.vn_cnt.set(e, 1);
+        ver_need.vn_file.set(e, file_offset);
+        ver_need.vn_aux.set(e, size_of::<Verneed>() as u32);
+        ver_need.vn_next.set(e, next_verneed_offset);
+
+        let aux_out = table_writer
+            .version_writer
+            .take_auxes(1)?
+            .first_mut()
+            .context("Insufficient vernaux allocation")?;
+        aux_out.vna_next.set(e, 0);
+        aux_out.vna_other.set(e, version);
+        aux_out.vna_name.set(e, name_offset);
+        aux_out.vna_hash.set(e, object::elf::hash(self.lib_name));
+        Ok(())
+    }
+
     /// Write dynamic entry to indicate name of shared object to load.
     fn write_so_name(&self, table_writer: &mut TableWriter) -> Result {
         let needed_offset = table_writer
@@ -3143,25 +4560,27 @@ impl<'data> DynamicLayout<'data> {
     }
 }
 
+/// Writes the output version of an imported symbol. Symbols that don't have a version in their
+/// input get `default_version` if there is one.
 fn write_symbol_version(
     versym_in: &[Versym],
     local_symbol_index: usize,
     version_mapping: &[u16],
+    default_version: Option<u16>,
     versym_out: &mut &mut [Versym],
 ) -> Result {
     let version_out =
         crate::slice::take_first_mut(versym_out).context("Insufficient .gnu.version allocation")?;
-    let output_version =
-        versym_in
-            .get(local_symbol_index)
-            .map_or(object::elf::VER_NDX_GLOBAL, |versym| {
-                let input_version = versym.0.get(LittleEndian) & object::elf::VERSYM_VERSION;
-                if input_version <= object::elf::VER_NDX_GLOBAL {
-                    input_version
-                } else {
-                    version_mapping[usize::from(input_version) - 1]
-                }
-            });
+    let input_version = versym_in
+        .get(local_symbol_index)
+        .map_or(object::elf::VER_NDX_GLOBAL, |versym| {
+            versym.0.get(LittleEndian) & object::elf::VERSYM_VERSION
+        });
+    let output_version = if input_version <= object::elf::VER_NDX_GLOBAL {
+        default_version.unwrap_or(input_version)
+    } else {
+        version_mapping[usize::from(input_version) - 1]
+    };
     version_out.0.set(LittleEndian, output_version);
     Ok(())
 }
@@ -3197,6 +4616,38 @@ fn write_layout_to<S: StorageModel>(layout: &Layout<S>, path: &Path) -> Result {
     Ok(())
 }
 
+/// Prints the sections that we would have written had this not been a dry run.
+fn print_dry_run_report<S: StorageModel>(layout: &Layout<S>, file_size: usize) -> Result {
+    let output_sections = &layout.output_sections;
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "{:<24} {:>18} {:>12}", "Section", "Address", "Size")?;
+    for event in output_sections.sections_and_segments_events() {
+        let OrderEvent::Section(section_id) = event else {
+            continue;
+        };
+        if output_sections
+            .output_index_of_section(section_id)
+            .is_none()
+        {
+            continue;
+        }
+        let section_layout = layout.section_layouts.get(section_id);
+        writeln!(
+            out,
+            "{:<24} {:>#18x} {:>#12x}",
+            output_sections.name(section_id).to_string(),
+            section_layout.mem_offset,
+            section_layout.mem_size
+        )?;
+    }
+    writeln!(
+        out,
+        "Dry run: {file_size} bytes were not written to `{}`",
+        layout.args().output.display()
+    )?;
+    Ok(())
+}
+
 struct ResFlagsDisplay<'a>(&'a Resolution);
 
 impl Display for ResFlagsDisplay<'_> {
@@ -3212,6 +4663,7 @@ impl Display for ResFlagsDisplay<'_> {
 pub(crate) fn verify_resolution_allocation(
     output_sections: &OutputSections,
     output_kind: OutputKind,
+    plt_layout: PltLayout,
     mem_sizes: &OutputSectionPartMap<u64>,
     resolution: &Resolution,
 ) -> Result {
@@ -3236,12 +4688,67 @@ pub(crate) fn verify_resolution_allocation(
     let debug_symbol_writer = SymbolTableWriter::new(0, &mut buffers, output_sections);
     let mut table_writer = TableWriter::new(
         output_kind,
+        plt_layout,
+        0,
         0..100,
+        100,
+        Architecture::X86_64,
         &mut buffers,
         dynsym_writer,
         debug_symbol_writer,
         0,
+        plt_layout.lazy_binding.then_some(0),
+        0,
+        0,
     );
-    table_writer.process_resolution::<crate::x86_64::X86_64>(resolution)?;
+    table_writer.process_resolution(resolution)?;
     table_writer.validate_empty(mem_sizes)
 }
+
+#[test]
+fn test_aarch64_plt_dynamic_entries() {
+    let crate::args::Action::Link(args) =
+        crate::args::parse(["-m", "aarch64linux", "-shared", "a.o"].iter()).unwrap()
+    else {
+        panic!("Unexpected action");
+    };
+    let section_layouts = OutputSectionMap::with_size(0);
+    let section_part_layouts = OutputSectionPartMap::with_size(0);
+    let plt_entries = |bti, pac| {
+        let inputs = DynamicEntryInputs {
+            args: &args,
+            has_static_tls: false,
+            has_unique_symbols: false,
+            plt_features: PltFeatures {
+                bti,
+                pac,
+                ..PltFeatures::default()
+            },
+            section_layouts: &section_layouts,
+            section_part_layouts: &section_part_layouts,
+            non_addressable_counts: NonAddressableCounts {
+                verneed_count: 0,
+                verdef_count: 0,
+            },
+            memtag_globals_size: 0,
+        };
+        EPILOGUE_DYNAMIC_ENTRY_WRITERS
+            .iter()
+            .filter(|writer| {
+                matches!(
+                    writer.tag,
+                    elf::DT_AARCH64_BTI_PLT | elf::DT_AARCH64_PAC_PLT
+                ) && writer.is_present(&inputs)
+            })
+            .map(|writer| (writer.tag, (writer.cb)(&inputs)))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(plt_entries(false, false), []);
+    assert_eq!(plt_entries(true, false), [(elf::DT_AARCH64_BTI_PLT, 0)]);
+    assert_eq!(plt_entries(false, true), [(elf::DT_AARCH64_PAC_PLT, 0)]);
+    assert_eq!(
+        plt_entries(true, true),
+        [(elf::DT_AARCH64_BTI_PLT, 0), (elf::DT_AARCH64_PAC_PLT, 0)]
+    );
+}
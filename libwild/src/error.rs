@@ -21,3 +21,27 @@ macro_rules! debug_assert_bail {
 pub(crate) fn warning(message: &str) {
     println!("WARNING: wild: {message}");
 }
+
+/// Combines `errors` into a single error, reporting at most `limit` of them. A lone error is
+/// returned unchanged so that its context chain is preserved. Errors are sorted so that the output
+/// doesn't depend on the order in which our threads happened to report them.
+pub(crate) fn combine(mut errors: Vec<Error>, limit: Option<usize>) -> Result {
+    if errors.len() <= 1 {
+        return errors.pop().map_or(Ok(()), Err);
+    }
+    let mut messages = errors.iter().map(|e| format!("{e:#}")).collect::<Vec<_>>();
+    messages.sort();
+    messages.dedup();
+    let total = messages.len();
+    if let Some(limit) = limit {
+        messages.truncate(limit);
+    }
+    let mut message = messages.join("\n");
+    if messages.len() < total {
+        message.push_str(&format!(
+            "\n... and {} more errors (use --error-limit=0 to see all)",
+            total - messages.len()
+        ));
+    }
+    Err(anyhow::anyhow!("{message}"))
+}
@@ -0,0 +1,440 @@
+//! Implements `wild explain-reloc`, which prints the relocations in an object file along with what
+//! the linker would do with each of them. This is a debugging aid that avoids needing to run a full
+//! link and then inspect the output.
+//!
+//! ```text
+//! wild explain-reloc foo.o [--symbol S] [--offset O] [--shared|--pie|--no-pie|--static|--static-pie]
+//! ```
+//!
+//! `--symbol` restricts output to relocations that reference the named symbol and `--offset` to
+//! relocations that patch the byte at the specified offset in their section. The remaining flags
+//! select the kind of output to assume, which defaults to a position-independent executable.
+//!
+//! Since we only look at a single object, we don't know where undefined symbols will end up being
+//! defined. When writing a dynamic output, we assume that they might come from a shared object,
+//! which is the worst case.
+
+use crate::arch::Architecture;
+use crate::args::OutputKind;
+use crate::args::RelocationModel;
+use crate::elf::File;
+use crate::error::Result;
+use crate::layout::PltLayout;
+use crate::layout::ResolutionFlags;
+use crate::output_section_part_map::OutputSectionPartMap;
+use crate::part_id;
+use crate::resolution::ValueFlags;
+use anyhow::bail;
+use anyhow::Context as _;
+use linker_utils::elf::shf;
+use linker_utils::elf::RelocationKind;
+use linker_utils::elf::RelocationSize;
+use linker_utils::elf::SectionFlags;
+use object::read::elf::FileHeader as _;
+use object::read::elf::Rela as _;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+pub(crate) const COMMAND_NAME: &str = "explain-reloc";
+
+pub(crate) struct ExplainRelocArgs {
+    path: PathBuf,
+    symbol: Option<String>,
+    offset: Option<u64>,
+    output_kind: OutputKind,
+}
+
+pub(crate) fn parse_args<S: AsRef<str>>(
+    input: impl Iterator<Item = S>,
+) -> Result<ExplainRelocArgs> {
+    let mut path = None;
+    let mut symbol = None;
+    let mut offset = None;
+    let mut output_kind = OutputKind::DynamicExecutable(RelocationModel::Relocatable);
+    let mut input = input.map(|arg| arg.as_ref().to_owned());
+    while let Some(arg) = input.next() {
+        let option = arg.strip_prefix("--").or(arg.strip_prefix('-'));
+        match option {
+            Some("symbol") => symbol = Some(input.next().context("Missing argument to --symbol")?),
+            Some("offset") => {
+                let value = input.next().context("Missing argument to --offset")?;
+                offset = Some(parse_offset(&value)?);
+            }
+            Some("shared") => output_kind = OutputKind::SharedObject,
+            Some("pie") => {
+                output_kind = OutputKind::DynamicExecutable(RelocationModel::Relocatable);
+            }
+            Some("no-pie") => {
+                output_kind = OutputKind::DynamicExecutable(RelocationModel::NonRelocatable);
+            }
+            Some("static") => {
+                output_kind = OutputKind::StaticExecutable(RelocationModel::NonRelocatable);
+            }
+            Some("static-pie") => {
+                output_kind = OutputKind::StaticExecutable(RelocationModel::Relocatable);
+            }
+            Some(other) => bail!("Unrecognised argument to {COMMAND_NAME}: `-{other}`"),
+            None if path.is_none() => path = Some(PathBuf::from(arg)),
+            None => bail!("{COMMAND_NAME} only accepts a single object, got `{arg}` too"),
+        }
+    }
+    Ok(ExplainRelocArgs {
+        path: path.with_context(|| format!("Usage: wild {COMMAND_NAME} <object> [options]"))?,
+        symbol,
+        offset,
+        output_kind,
+    })
+}
+
+fn parse_offset(value: &str) -> Result<u64> {
+    let parsed = if let Some(hex) = value.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
+    } else {
+        value.parse()
+    };
+    parsed.with_context(|| format!("Invalid offset `{value}`"))
+}
+
+pub(crate) fn run(args: &ExplainRelocArgs) -> Result {
+    let data = std::fs::read(&args.path)
+        .with_context(|| format!("Failed to read `{}`", args.path.display()))?;
+    let header = crate::elf::FileHeader::parse(data.as_slice())?;
+    if header.e_type(LittleEndian) != object::elf::ET_REL {
+        bail!("`{}` isn't a relocatable object", args.path.display());
+    }
+    let object = File::parse(&data, false)?;
+    let mut out = String::new();
+    let e_machine = header.e_machine(LittleEndian);
+    let Some(arch) = Architecture::from_e_machine(e_machine) else {
+        bail!("Unsupported architecture {e_machine}");
+    };
+    explain(arch, &object, args, &mut out)?;
+    print!("{out}");
+    Ok(())
+}
+
+fn explain(arch: Architecture, object: &File, args: &ExplainRelocArgs, out: &mut String) -> Result {
+    let e = LittleEndian;
+    for (section_index, section) in object.sections.enumerate() {
+        let relocations = object.relocations(section_index)?;
+        if relocations.is_empty() {
+            continue;
+        }
+        let section_name = object.section_display_name(section_index);
+        let section_flags = SectionFlags::from_header(section);
+        let section_bytes = object.raw_section_data(section)?;
+        for rel in relocations {
+            let offset = rel.r_offset(e);
+            let r_type = rel.r_type(e, false);
+            let rel_info = arch.relocation_from_raw(r_type);
+            let size = match rel_info.as_ref().map(|info| &info.size) {
+                Ok(RelocationSize::ByteSize(size)) => *size as u64,
+                // Instructions that take bit-masked relocations are 4 bytes on all architectures
+                // that have them.
+                _ => 4,
+            };
+            if args
+                .offset
+                .is_some_and(|o| !(offset..offset + size).contains(&o))
+            {
+                continue;
+            }
+            let symbol = match rel.symbol(e, false) {
+                Some(index) => Some((index, object.symbol(index)?)),
+                None => None,
+            };
+            let symbol_name = match symbol {
+                Some((index, sym)) if sym.st_type() == object::elf::STT_SECTION => object
+                    .symbol_section(sym, index)?
+                    .map_or_else(|| "<section>".into(), |i| object.section_display_name(i)),
+                Some((_, sym)) => String::from_utf8_lossy(object.symbol_name(sym)?),
+                None => "<none>".into(),
+            };
+            if args.symbol.as_ref().is_some_and(|s| *s != symbol_name) {
+                continue;
+            }
+
+            writeln!(
+                out,
+                "{section_name}+0x{offset:x}: {} against `{symbol_name}` with addend {}",
+                arch.rel_type_to_string(r_type),
+                rel.r_addend(e)
+            )?;
+            let rel_info = match rel_info {
+                Ok(info) => info,
+                Err(error) => {
+                    writeln!(out, "  {error}\n")?;
+                    continue;
+                }
+            };
+
+            let value_flags = symbol.map_or(
+                ValueFlags::ABSOLUTE | ValueFlags::CAN_BYPASS_GOT,
+                |(_, sym)| crate::symbol_db::value_flags_from_elf_symbol(sym, args.output_kind),
+            );
+            writeln!(
+                out,
+                "  symbol: {}",
+                describe_value(value_flags, symbol.map(|(_, s)| s))
+            )?;
+
+            let relaxation = arch.new_relaxation(
+                r_type,
+                section_bytes,
+                offset,
+                value_flags,
+                args.output_kind,
+                section_flags,
+            );
+
+            if section_flags.contains(shf::EXECINSTR) {
+                let function_start = enclosing_function_start(object, section_index, offset)?;
+                writeln!(
+                    out,
+                    "  instruction: {}",
+                    describe_instruction(arch, section_bytes, function_start, offset)
+                )?;
+                if let Some(relaxation) = &relaxation {
+                    let mut relaxed_bytes = section_bytes.to_vec();
+                    let mut relaxed_offset = offset;
+                    let mut addend = rel.r_addend(e);
+                    relaxation.apply(&mut relaxed_bytes, &mut relaxed_offset, &mut addend);
+                    writeln!(
+                        out,
+                        "  relaxed to: {}",
+                        describe_instruction(arch, &relaxed_bytes, function_start, relaxed_offset)
+                    )?;
+                }
+            }
+
+            let kind = if let Some(relaxation) = &relaxation {
+                writeln!(out, "  relaxation: {:?}", relaxation.debug_kind())?;
+                relaxation.rel_info().kind
+            } else {
+                writeln!(out, "  relaxation: none")?;
+                rel_info.kind
+            };
+            match outcomes(kind, value_flags, section_flags, args.output_kind) {
+                Ok(outcomes) => {
+                    for outcome in outcomes {
+                        writeln!(out, "  {outcome}")?;
+                    }
+                }
+                Err(error) => writeln!(out, "  error: {error}")?,
+            }
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+fn describe_value(value_flags: ValueFlags, sym: Option<&crate::elf::Symbol>) -> &'static str {
+    let is_undefined = sym.is_some_and(|s| s.is_undefined(LittleEndian));
+    if value_flags.contains(ValueFlags::IFUNC) {
+        "an ifunc, so its address is only known at runtime"
+    } else if value_flags.contains(ValueFlags::DYNAMIC) {
+        "undefined, so it might be defined by a shared object"
+    } else if is_undefined {
+        "undefined, so it must be defined by another object in the link"
+    } else if value_flags.contains(ValueFlags::ABSOLUTE) {
+        "absolute"
+    } else if value_flags.contains(ValueFlags::CAN_BYPASS_GOT) {
+        "defined locally and can't be interposed"
+    } else {
+        "defined locally, but could be interposed at runtime"
+    }
+}
+
+/// Returns what we'll need to create in the output in order to resolve a relocation of the
+/// specified kind. This runs the same code that layout uses to decide what to allocate, then
+/// reports what got allocated.
+fn outcomes(
+    kind: RelocationKind,
+    value_flags: ValueFlags,
+    section_flags: SectionFlags,
+    output_kind: OutputKind,
+) -> Result<Vec<String>> {
+    let mut mem_sizes = OutputSectionPartMap::<u64>::with_size(part_id::NUM_GENERATED_PARTS);
+    let flags = crate::layout::reference_resolution_flags(
+        kind,
+        value_flags,
+        section_flags.contains(shf::WRITE),
+        output_kind,
+        &mut mem_sizes,
+    )?;
+    // We don't know what the rest of the link will contain, so assume that we're not binding
+    // lazily and that no control-flow protection features are enabled.
+    let plt_layout = PltLayout {
+        entry_size: crate::elf::PLT_ENTRY_SIZE,
+        lazy_binding: false,
+        features: Default::default(),
+    };
+    crate::layout::allocate_resolution(value_flags, flags, &mut mem_sizes, output_kind, plt_layout);
+
+    let mut outcomes = Vec::new();
+    let got_entries = mem_sizes.get(part_id::GOT) / crate::elf::GOT_ENTRY_SIZE;
+    if got_entries > 0 {
+        outcomes.push(format!("GOT entries: {got_entries}"));
+    }
+    if *mem_sizes.get(part_id::PLT_GOT) > 0 {
+        outcomes.push(if flags.contains(ResolutionFlags::CANONICAL_PLT) {
+            "PLT entry: needed, and used as the function's address".to_owned()
+        } else {
+            "PLT entry: needed".to_owned()
+        });
+    }
+    if flags.contains(ResolutionFlags::COPY_RELOCATION) {
+        outcomes.push("copy relocation: needed".to_owned());
+    }
+    for (part, description) in [
+        (part_id::RELA_PLT, ".rela.plt"),
+        (part_id::RELA_DYN_GENERAL, ".rela.dyn"),
+        (part_id::RELA_DYN_RELATIVE, ".rela.dyn, relative"),
+    ] {
+        let count = mem_sizes.get(part) / crate::elf::RELA_ENTRY_SIZE;
+        if count > 0 {
+            outcomes.push(format!("dynamic relocations: {count} in {description}"));
+        }
+    }
+    if outcomes.is_empty() {
+        outcomes.push("resolved at link time, nothing else needed".to_owned());
+    }
+    Ok(outcomes)
+}
+
+/// Returns the offset in the section of the function containing `offset`, or the start of the
+/// section if there's no such function. Instructions are variable length on some architectures,
+/// so we need to start decoding from a known instruction boundary.
+fn enclosing_function_start(
+    object: &File,
+    section_index: object::SectionIndex,
+    offset: u64,
+) -> Result<u64> {
+    let e = LittleEndian;
+    let mut start = 0;
+    for (symbol_index, sym) in object.symbols.enumerate() {
+        if sym.st_type() != object::elf::STT_FUNC
+            || object.symbol_section(sym, symbol_index)? != Some(section_index)
+        {
+            continue;
+        }
+        let value = sym.st_value(e);
+        if (value..value + sym.st_size(e).max(1)).contains(&offset) {
+            start = value;
+        }
+    }
+    Ok(start)
+}
+
+fn describe_instruction(arch: Architecture, bytes: &[u8], start: u64, offset: u64) -> String {
+    if arch == Architecture::X86_64 {
+        return describe_x86_64_instruction(bytes, start, offset);
+    }
+    // We don't have a disassembler for other architectures, so show the raw instruction.
+    let word_start = (offset & !3) as usize;
+    bytes
+        .get(word_start..word_start + 4)
+        .map_or_else(String::new, |word| {
+            format!(
+                "0x{:08x}",
+                u32::from_le_bytes(*word.first_chunk::<4>().unwrap())
+            )
+        })
+}
+
+#[cfg(feature = "disassembler")]
+fn describe_x86_64_instruction(bytes: &[u8], start: u64, offset: u64) -> String {
+    use iced_x86::Formatter as _;
+
+    let Some(code) = bytes.get(start as usize..) else {
+        return String::new();
+    };
+    let mut decoder = iced_x86::Decoder::with_ip(64, code, start, iced_x86::DecoderOptions::NONE);
+    let mut formatter = iced_x86::GasFormatter::new();
+    formatter.options_mut().set_rip_relative_addresses(true);
+    let mut instruction = iced_x86::Instruction::default();
+    while decoder.can_decode() {
+        decoder.decode_out(&mut instruction);
+        if (instruction.ip()..instruction.next_ip()).contains(&offset) {
+            let mut text = String::new();
+            formatter.format(&instruction, &mut text);
+            return format!("0x{:x}: {text}", instruction.ip());
+        }
+    }
+    "<unknown>".to_owned()
+}
+
+/// Without a disassembler, we can't find where the instruction starts, so show the bytes leading
+/// up to and including the relocation's offset.
+#[cfg(not(feature = "disassembler"))]
+fn describe_x86_64_instruction(bytes: &[u8], start: u64, offset: u64) -> String {
+    let from = offset.saturating_sub(8).max(start) as usize;
+    let to = (offset as usize + 4).min(bytes.len());
+    let Some(window) = bytes.get(from..to) else {
+        return String::new();
+    };
+    let hex = window
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("0x{from:x}: {hex} (build with the `disassembler` feature to decode)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse_args(["foo.o", "--offset", "0x10", "-shared"].iter()).unwrap();
+        assert_eq!(args.path, PathBuf::from("foo.o"));
+        assert_eq!(args.offset, Some(0x10));
+        assert_eq!(args.output_kind, OutputKind::SharedObject);
+        assert!(parse_args(["foo.o", "bar.o"].iter()).is_err());
+        assert!(parse_args(["--symbol"].iter()).is_err());
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let pie = OutputKind::DynamicExecutable(RelocationModel::Relocatable);
+        let local = ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT;
+        assert_eq!(
+            outcomes(RelocationKind::Absolute, local, shf::WRITE, pie).unwrap(),
+            ["dynamic relocations: 1 in .rela.dyn, relative"]
+        );
+        assert_eq!(
+            outcomes(
+                RelocationKind::GotRelative,
+                ValueFlags::DYNAMIC | ValueFlags::ABSOLUTE,
+                shf::EXECINSTR,
+                pie
+            )
+            .unwrap(),
+            ["GOT entries: 1", "dynamic relocations: 1 in .rela.dyn"]
+        );
+        assert_eq!(
+            outcomes(RelocationKind::Relative, local, shf::EXECINSTR, pie).unwrap(),
+            ["resolved at link time, nothing else needed"]
+        );
+        assert!(outcomes(
+            RelocationKind::SymbolSize,
+            ValueFlags::DYNAMIC,
+            shf::EXECINSTR,
+            pie
+        )
+        .is_err());
+        assert_eq!(
+            outcomes(
+                RelocationKind::SymbolSize,
+                ValueFlags::ADDRESS,
+                shf::WRITE,
+                OutputKind::SharedObject
+            )
+            .unwrap(),
+            ["dynamic relocations: 1 in .rela.dyn"]
+        );
+    }
+}
@@ -11,6 +11,7 @@ pub(crate) enum FileKind {
     Prelude,
     ElfObject,
     ElfDynamic,
+    ElfExecutable,
     Archive,
     Text,
     Epilogue,
@@ -26,11 +27,29 @@ impl FileKind {
                 bail!("Invalid ELF file");
             }
             let header: &elf::FileHeader = object::from_bytes(&bytes[..HEADER_LEN]).unwrap().0;
+            // The 32 bit header is smaller than the 64 bit one, which we just read, but the machine
+            // is at the same offset, so we can report what sort of object this is without
+            // reparsing.
+            let big_endian = header.e_ident.data != object::elf::ELFDATA2LSB;
+            let e_machine = if big_endian {
+                header.e_machine.get(LittleEndian).swap_bytes()
+            } else {
+                header.e_machine.get(LittleEndian)
+            };
+            if header.e_ident.class == object::elf::ELFCLASS32 {
+                bail!(
+                    "{} objects are 32 bit ELF, which is not yet supported",
+                    machine_name(e_machine, header.e_ident.class)
+                );
+            }
             if header.e_ident.class != object::elf::ELFCLASS64 {
                 bail!("Only 64 bit ELF is currently supported");
             }
-            if header.e_ident.data != object::elf::ELFDATA2LSB {
-                bail!("Only little endian is currently supported");
+            if big_endian {
+                bail!(
+                    "{} objects are big-endian, which is not yet supported",
+                    machine_name(e_machine, header.e_ident.class)
+                );
             }
 
             let sections = header.sections(LittleEndian, bytes)?;
@@ -46,6 +65,7 @@ impl FileKind {
             match header.e_type.get(LittleEndian) {
                 object::elf::ET_REL => Ok(FileKind::ElfObject),
                 object::elf::ET_DYN => Ok(FileKind::ElfDynamic),
+                object::elf::ET_EXEC => Ok(FileKind::ElfExecutable),
                 t => bail!("Unsupported ELF kind {t}"),
             }
         } else if bytes.is_ascii() {
@@ -59,3 +79,20 @@ impl FileKind {
         }
     }
 }
+
+/// Returns a name for the machine of an ELF file. x32 objects share EM_X86_64 with x86-64 and are
+/// told apart by being ELFCLASS32.
+pub(crate) fn machine_name(e_machine: u16, class: u8) -> String {
+    match e_machine {
+        object::elf::EM_386 => "i386".to_owned(),
+        object::elf::EM_ARM => "ARM".to_owned(),
+        object::elf::EM_X86_64 if class == object::elf::ELFCLASS32 => "x32".to_owned(),
+        object::elf::EM_X86_64 => "x86-64".to_owned(),
+        object::elf::EM_AARCH64 => "AArch64".to_owned(),
+        object::elf::EM_MIPS => "MIPS".to_owned(),
+        object::elf::EM_PPC64 => "PowerPC64".to_owned(),
+        object::elf::EM_S390 => "s390x".to_owned(),
+        object::elf::EM_SPARCV9 => "SPARC".to_owned(),
+        other => format!("e_machine {other}"),
+    }
+}
@@ -12,3 +12,38 @@ pub(crate) fn make_executable(file: &File) -> Result {
     file.set_permissions(permissions)?;
     Ok(())
 }
+
+/// Asks the kernel to start reading `file` into the page cache in the background. This is only a
+/// hint, so failure is ignored.
+pub(crate) fn advise_will_need(file: &File) {
+    use std::os::fd::AsRawFd;
+
+    // Safety: We pass a valid file descriptor. A length of 0 means the whole file.
+    unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_WILLNEED) };
+}
+
+/// Returns whether `file` is on a filesystem where writing via mmap is known to be unreliable or
+/// slow, in which case we'd rather build the output in memory and write it in one go.
+pub(crate) fn is_mmap_unfriendly(file: &File) -> bool {
+    use std::os::fd::AsRawFd;
+
+    const NFS_SUPER_MAGIC: u64 = 0x6969;
+    const SMB2_MAGIC_NUMBER: u64 = 0xfe53_4d42;
+    const CIFS_MAGIC_NUMBER: u64 = 0xff53_4d42;
+    const FUSE_SUPER_MAGIC: u64 = 0x6573_5546;
+    const OVERLAYFS_SUPER_MAGIC: u64 = 0x794c_7630;
+
+    let mut stats: libc::statfs = unsafe { std::mem::zeroed() };
+    // Safety: We pass a valid file descriptor and a pointer to a statfs struct that we own.
+    if unsafe { libc::fstatfs(file.as_raw_fd(), &mut stats) } != 0 {
+        return false;
+    }
+    matches!(
+        stats.f_type as u64,
+        NFS_SUPER_MAGIC
+            | SMB2_MAGIC_NUMBER
+            | CIFS_MAGIC_NUMBER
+            | FUSE_SUPER_MAGIC
+            | OVERLAYFS_SUPER_MAGIC
+    )
+}
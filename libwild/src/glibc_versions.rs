@@ -0,0 +1,139 @@
+//! Checks that the output doesn't depend on versions of glibc newer than those specified with
+//! `--max-glibc-version=2.17` (an error) or `--warn-max-glibc-version=2.17` (a warning). This is
+//! useful when building binaries on a recent distribution that need to run on older ones, since
+//! otherwise the problem only shows up when the binary fails to load on the older system.
+//!
+//! We look at every undefined symbol in every loaded regular object and if it's defined by a shared object
+//! with a `GLIBC_x.y` version that's newer than the maximum, we report the symbol, the version and
+//! the object that referenced it.
+
+use crate::args::Args;
+use crate::args::MaxGlibcVersion;
+use crate::error::Result;
+use crate::input_data::FileId;
+use crate::parsing::ParsedInput;
+use crate::resolution::ResolvedFile;
+use crate::resolution::ResolvedGroup;
+use crate::storage::StorageModel;
+use crate::symbol_db::SymbolDb;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+use std::collections::HashMap;
+
+pub(crate) fn maybe_check_glibc_versions<S: StorageModel>(
+    groups: &[ResolvedGroup],
+    symbol_db: &SymbolDb<S>,
+    args: &Args,
+) -> Result {
+    let Some(max) = args.max_glibc_version.as_ref() else {
+        return Ok(());
+    };
+
+    // For each shared object that we've looked at, the name of each of its versions, indexed by
+    // version index.
+    let mut version_names: HashMap<FileId, Vec<Option<&[u8]>>> = HashMap::new();
+    let mut problems = Vec::new();
+
+    for group in groups {
+        for file in &group.files {
+            // References from shared objects are resolved by the dynamic loader rather than by us,
+            // so only the references from our regular objects are our responsibility.
+            let ResolvedFile::Object(obj) = file else {
+                continue;
+            };
+            if obj.non_dynamic.is_none() {
+                continue;
+            }
+            for (sym_index, sym) in obj.object.symbols.enumerate() {
+                if sym_index.0 == 0 || !sym.is_undefined(LittleEndian) {
+                    continue;
+                }
+                let symbol_id = obj.symbol_id_range.input_to_id(sym_index);
+                let definition = symbol_db.definition(symbol_id);
+                let provider_id = symbol_db.file_id_for_symbol(definition);
+                let ParsedInput::Object(provider) = symbol_db.file(provider_id) else {
+                    continue;
+                };
+                if !provider.is_dynamic() {
+                    continue;
+                }
+                let local_index = definition.to_input(provider.symbol_id_range).0;
+                let Some(versym) = provider.object.versym.get(local_index) else {
+                    continue;
+                };
+                let version_index =
+                    usize::from(versym.0.get(LittleEndian) & object::elf::VERSYM_VERSION);
+                let names = match version_names.entry(provider_id) {
+                    std::collections::hash_map::Entry::Occupied(e) => e.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(e) => {
+                        e.insert(provider.object.version_names()?)
+                    }
+                };
+                let Some(version_name) = names.get(version_index).copied().flatten() else {
+                    continue;
+                };
+                let Some(version) = parse_glibc_version(version_name) else {
+                    continue;
+                };
+                if version > max.version {
+                    problems.push(format!(
+                        "`{}@{}` referenced by {obj}",
+                        symbol_db.symbol_name_for_display(symbol_id),
+                        String::from_utf8_lossy(version_name),
+                    ));
+                }
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    problems.sort();
+    let message = format!(
+        "{} symbol reference(s) require a glibc newer than {}:\n    {}",
+        problems.len(),
+        max.display_version(),
+        problems.join("\n    ")
+    );
+    if max.warn_only {
+        crate::error::warning(&message);
+        return Ok(());
+    }
+    anyhow::bail!(message)
+}
+
+/// Parses a version name like `GLIBC_2.34`. Returns None for other versions like `GLIBC_PRIVATE`.
+fn parse_glibc_version(name: &[u8]) -> Option<Vec<u32>> {
+    let version = std::str::from_utf8(name).ok()?.strip_prefix("GLIBC_")?;
+    parse_version_number(version)
+}
+
+/// Parses a dotted version number like `2.17`.
+pub(crate) fn parse_version_number(version: &str) -> Option<Vec<u32>> {
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+impl MaxGlibcVersion {
+    fn display_version(&self) -> String {
+        self.version
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_glibc_version() {
+        assert_eq!(parse_glibc_version(b"GLIBC_2.34"), Some(vec![2, 34]));
+        assert_eq!(parse_glibc_version(b"GLIBC_2.2.5"), Some(vec![2, 2, 5]));
+        assert_eq!(parse_glibc_version(b"GLIBC_PRIVATE"), None);
+        assert_eq!(parse_glibc_version(b"GCC_3.0"), None);
+        assert!(parse_glibc_version(b"GLIBC_2.2.5") < Some(vec![2, 17]));
+    }
+}
@@ -0,0 +1,104 @@
+//! Matching of shell-style glob patterns, as used by options like `--remap-inputs`. Supports `*`,
+//! `?` and bracketed character classes such as `[a-z]` or `[!0-9]`.
+
+/// Returns whether `text` matches `pattern` in its entirety.
+pub(crate) fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    // Where to resume if the current attempt fails: the pattern position just after the most
+    // recent `*` and the text position that the `*` will next try to extend to.
+    let mut backtrack = None;
+    let mut p = 0;
+    let mut t = 0;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, t + 1));
+                continue;
+            }
+            Some(b'?') => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some(b'[') => {
+                if let Some((matched, len)) = match_class(&pattern[p..], text[t]) {
+                    if matched {
+                        p += len;
+                        t += 1;
+                        continue;
+                    }
+                } else if text[t] == b'[' {
+                    // An unterminated class is treated as a literal `[`.
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+            }
+            Some(&c) if c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let Some((next_p, next_t)) = backtrack else {
+            return false;
+        };
+        p = next_p;
+        t = next_t;
+        backtrack = Some((next_p, next_t + 1));
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Matches `c` against the character class at the start of `pattern`. Returns whether it matched
+/// and the length of the class, or None if the class isn't terminated.
+fn match_class(pattern: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some(b'!' | b'^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let start = *pattern.get(i)?;
+        // A `]` immediately after the opening bracket is part of the class.
+        if start == b']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        if pattern.get(i + 1) == Some(&b'-') && pattern.get(i + 2).is_some_and(|&e| e != b']') {
+            let end = pattern[i + 2];
+            matched |= (start..=end).contains(&c);
+            i += 3;
+        } else {
+            matched |= start == c;
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_matches;
+
+    #[test]
+    fn test_glob_matches() {
+        let m = |p: &str, t: &str| glob_matches(p.as_bytes(), t.as_bytes());
+        assert!(m("foo", "foo"));
+        assert!(!m("foo", "foobar"));
+        assert!(m("*", ""));
+        assert!(m("*.a", "lib/libfoo.a"));
+        assert!(!m("*.a", "libfoo.so"));
+        assert!(m("*/libfoo.*", "/usr/lib/libfoo.so"));
+        assert!(m("a*b*c", "aXbYbZc"));
+        assert!(!m("a*b*c", "aXbYbZ"));
+        assert!(m("lib?.a", "libx.a"));
+        assert!(!m("lib?.a", "lib.a"));
+        assert!(m("lib[a-c].a", "libb.a"));
+        assert!(!m("lib[!a-c].a", "libb.a"));
+        assert!(m("lib[]x].a", "lib].a"));
+        assert!(m("lib[.a", "lib[.a"));
+    }
+}
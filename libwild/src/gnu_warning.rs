@@ -0,0 +1,122 @@
+//! Support for `.gnu.warning.SYMBOL` sections. An input can contain such a section to ask that the
+//! contents of the section be printed as a warning whenever SYMBOL is referenced. glibc uses this
+//! to warn about dangerous functions such as `gets`. A section named just `.gnu.warning` produces a
+//! warning if the object containing it is linked at all. In both cases, the section itself isn't
+//! copied to the output.
+
+use crate::error::Result;
+use crate::input_data::FileId;
+use crate::resolution::ResolvedFile;
+use crate::resolution::ResolvedGroup;
+use crate::storage::StorageModel;
+use crate::storage::SymbolNameMap as _;
+use crate::symbol::SymbolName;
+use crate::symbol_db::SymbolDb;
+use crate::symbol_db::SymbolId;
+use itertools::Itertools;
+use std::collections::HashMap;
+
+const PREFIX: &[u8] = b".gnu.warning";
+
+/// Returns whether the supplied input section holds a warning rather than data for the output.
+pub(crate) fn is_warning_section(section_name: &[u8]) -> bool {
+    section_name
+        .strip_prefix(PREFIX)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(b"."))
+}
+
+#[derive(Default)]
+pub(crate) struct SymbolWarnings<'data> {
+    /// Warnings to print if the symbol is referenced, keyed by the symbol's definition.
+    by_symbol: HashMap<SymbolId, &'data [u8]>,
+
+    /// Warnings to print because the file that contains them was loaded.
+    unconditional: Vec<(FileId, &'data [u8])>,
+}
+
+/// A reference from a file to a symbol that has a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct WarningReference {
+    pub(crate) referenced_by: FileId,
+    pub(crate) symbol_id: SymbolId,
+}
+
+impl<'data> SymbolWarnings<'data> {
+    pub(crate) fn collect<S: StorageModel>(
+        groups: &[ResolvedGroup<'data>],
+        symbol_db: &SymbolDb<'data, S>,
+    ) -> Result<Self> {
+        let mut warnings = SymbolWarnings::default();
+        for file in groups.iter().flat_map(|group| &group.files) {
+            let ResolvedFile::Object(obj) = file else {
+                continue;
+            };
+            for section in obj.object.sections.iter() {
+                let name = obj.object.section_name(section)?;
+                if !is_warning_section(name) {
+                    continue;
+                }
+                let data = obj.object.raw_section_data(section)?;
+                let text = data.split(|b| *b == 0).next().unwrap_or_default();
+                match name[PREFIX.len()..].strip_prefix(b".") {
+                    None => warnings.unconditional.push((obj.file_id, text)),
+                    Some(symbol_name) => {
+                        if let Some(symbol_id) = symbol_db
+                            .global_names
+                            .get(&SymbolName::prehashed(symbol_name))
+                        {
+                            warnings
+                                .by_symbol
+                                .entry(symbol_db.definition(symbol_id))
+                                .or_insert(text);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(warnings)
+    }
+
+    pub(crate) fn has_warning(&self, symbol_id: SymbolId) -> bool {
+        self.by_symbol.contains_key(&symbol_id)
+    }
+
+    /// Prints our unconditional warnings, then a warning for each distinct file that referenced a
+    /// symbol with a warning.
+    pub(crate) fn report<'a, S: StorageModel>(
+        &self,
+        references: impl Iterator<Item = &'a WarningReference>,
+        symbol_db: &SymbolDb<'data, S>,
+    ) {
+        for (file_id, text) in &self.unconditional {
+            crate::error::warning(&format!(
+                "{}: warning: {}",
+                symbol_db.file(*file_id),
+                String::from_utf8_lossy(text)
+            ));
+        }
+        for reference in references.sorted().dedup() {
+            let Some(text) = self.by_symbol.get(&reference.symbol_id) else {
+                continue;
+            };
+            crate::error::warning(&format!(
+                "{}: warning: {}",
+                symbol_db.file(reference.referenced_by),
+                String::from_utf8_lossy(text)
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_warning_section() {
+        assert!(is_warning_section(b".gnu.warning"));
+        assert!(is_warning_section(b".gnu.warning.gets"));
+        assert!(!is_warning_section(b".gnu.warnings"));
+        assert!(!is_warning_section(b".gnu.attributes"));
+    }
+}
@@ -0,0 +1,149 @@
+//! Optionally prints a report of which symbols needed a GOT and/or PLT entry and why. To use this,
+//! pass `--print-got-usage`.
+//!
+//! For each symbol, we list the relocations that caused the GOT or PLT entry to be allocated,
+//! together with the file containing the relocation and the reason that we couldn't bypass the GOT.
+//! This is intended to help find unnecessary indirection, e.g. code compiled without
+//! `-fvisibility=hidden` or without `-fno-plt`, or relocations that we don't know how to relax.
+
+use crate::input_data::FileId;
+use crate::layout::ResolutionFlags;
+use crate::resolution::ValueFlags;
+use crate::sharding::ShardKey as _;
+use crate::storage::StorageModel;
+use crate::symbol_db::SymbolDb;
+use crate::symbol_db::SymbolId;
+use itertools::Itertools;
+use linker_utils::elf::RelocationKind;
+use std::fmt::Display;
+
+/// A single relocation that caused a GOT or PLT entry to be needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct GotUsage {
+    pub(crate) symbol_id: SymbolId,
+    pub(crate) referenced_by: FileId,
+    pub(crate) r_type: u32,
+    pub(crate) reason: GotUsageReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum GotUsageReason {
+    /// The symbol is an ifunc, so its address isn't known until runtime.
+    Ifunc,
+
+    /// The symbol is undefined (e.g. weak), so may be provided at runtime.
+    Undefined,
+
+    /// The symbol is defined by a shared object.
+    Dynamic,
+
+    /// The symbol has default visibility in a shared object, so may be interposed at runtime.
+    Interposable,
+
+    /// The relocation refers to the GOT, but we don't know how to relax the instruction to which
+    /// it's applied.
+    NotRelaxable,
+
+    /// The relocation inherently needs a GOT entry, e.g. a TLS relocation.
+    Required,
+}
+
+impl GotUsageReason {
+    /// Returns why a relocation of kind `rel_kind`, after any relaxation, needs a GOT or PLT entry
+    /// for a symbol with `value_flags`.
+    pub(crate) fn new(value_flags: ValueFlags, rel_kind: RelocationKind) -> Self {
+        if value_flags.contains(ValueFlags::IFUNC) {
+            GotUsageReason::Ifunc
+        } else if value_flags.contains(ValueFlags::DYNAMIC | ValueFlags::ABSOLUTE) {
+            GotUsageReason::Undefined
+        } else if value_flags.contains(ValueFlags::DYNAMIC) {
+            GotUsageReason::Dynamic
+        } else if crate::layout::resolution_flags(rel_kind).intersects(
+            ResolutionFlags::GOT_TLS_OFFSET
+                | ResolutionFlags::GOT_TLS_MODULE
+                | ResolutionFlags::GOT_TLS_DESCRIPTOR,
+        ) {
+            GotUsageReason::Required
+        } else if !value_flags.contains(ValueFlags::CAN_BYPASS_GOT) {
+            GotUsageReason::Interposable
+        } else {
+            GotUsageReason::NotRelaxable
+        }
+    }
+}
+
+impl Display for GotUsageReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            GotUsageReason::Ifunc => "symbol is an ifunc",
+            GotUsageReason::Undefined => "symbol is undefined",
+            GotUsageReason::Dynamic => "symbol is defined by a shared object",
+            GotUsageReason::Interposable => "symbol can be interposed at runtime",
+            GotUsageReason::NotRelaxable => "relocation couldn't be relaxed",
+            GotUsageReason::Required => "relocation requires a GOT entry",
+        };
+        Display::fmt(reason, f)
+    }
+}
+
+/// The resolution flags that indicate that a symbol needs some kind of GOT or PLT entry.
+pub(crate) fn got_or_plt_flags() -> ResolutionFlags {
+    ResolutionFlags::GOT
+        | ResolutionFlags::PLT
+        | ResolutionFlags::GOT_TLS_OFFSET
+        | ResolutionFlags::GOT_TLS_MODULE
+        | ResolutionFlags::GOT_TLS_DESCRIPTOR
+}
+
+pub(crate) fn print_got_usage<'a, S: StorageModel>(
+    symbol_db: &SymbolDb<S>,
+    usages: impl Iterator<Item = &'a GotUsage>,
+    symbol_resolution_flags: &[ResolutionFlags],
+) {
+    let by_symbol = usages
+        .counts()
+        .into_iter()
+        .map(|(usage, count)| (usage.symbol_id, (usage, count)))
+        .into_group_map();
+
+    let mut num_got = 0;
+    let mut num_plt = 0;
+    let mut num_relocations = 0;
+
+    println!("GOT/PLT usage:");
+    for (symbol_id, mut usages) in by_symbol.into_iter().sorted_by_key(|(id, _)| *id) {
+        let flags = symbol_resolution_flags[symbol_id.as_usize()];
+        let name = symbol_db.symbol_name(symbol_id).map_or_else(
+            |_| "??".to_owned(),
+            |name| String::from_utf8_lossy(name.bytes()).into_owned(),
+        );
+        if flags.intersects(got_or_plt_flags() - ResolutionFlags::PLT) {
+            num_got += 1;
+        }
+        if flags.contains(ResolutionFlags::PLT) {
+            num_plt += 1;
+        }
+        if symbol_db.is_symbol_undefined(symbol_id).unwrap_or(false) {
+            println!("  `{name}` [{}] undefined", flags & got_or_plt_flags());
+        } else {
+            println!(
+                "  `{name}` [{}] defined in {}",
+                flags & got_or_plt_flags(),
+                symbol_db.file(symbol_db.file_id_for_symbol(symbol_id))
+            );
+        }
+        usages.sort();
+        for (usage, count) in usages {
+            num_relocations += count;
+            println!(
+                "    {count}x {} in {}: {}",
+                symbol_db.args.arch().rel_type_to_string(usage.r_type),
+                symbol_db.file(usage.referenced_by),
+                usage.reason
+            );
+        }
+    }
+    println!("Symbols with GOT entries: {num_got}");
+    println!("Symbols with PLT entries: {num_plt}");
+    println!("Relocations requiring a GOT or PLT entry: {num_relocations}");
+}
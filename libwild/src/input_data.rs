@@ -1,15 +1,20 @@
 //! Code for figuring out what input files we need to read then mapping them into memory.
 
+use crate::arch::Architecture;
 use crate::archive;
 use crate::args::Args;
 use crate::args::Input;
 use crate::args::InputSpec;
 use crate::args::Modifiers;
+use crate::elf;
 use crate::error::Result;
 use crate::file_kind::FileKind;
+use crate::linker_script::ScriptLayout;
+use crate::profile::Profile;
 use anyhow::bail;
 use anyhow::Context;
 use memmap2::Mmap;
+use object::LittleEndian;
 use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
@@ -19,6 +24,10 @@ pub(crate) struct InputData<'config> {
     pub filenames: HashSet<PathBuf>,
     pub(crate) files: Vec<InputFile>,
     pub(crate) version_script_data: Option<VersionScriptData>,
+    pub(crate) profile: Option<Profile>,
+
+    /// Fill patterns, program headers and the like, from linker scripts.
+    pub(crate) script_layout: ScriptLayout,
 }
 
 pub(crate) struct VersionScriptData {
@@ -26,7 +35,7 @@ pub(crate) struct VersionScriptData {
 }
 
 /// Identifies an input file. IDs start from 0 which is reserved for our prelude file.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) struct FileId(u32);
 
 pub(crate) const PRELUDE_FILE_ID: FileId = FileId::new(0, 0);
@@ -91,16 +100,38 @@ impl<'config> InputData<'config> {
             .map(|path| read_version_script(path))
             .transpose()?;
 
+        let profile = config
+            .profile_path
+            .as_ref()
+            .map(|path| Profile::from_file(path))
+            .transpose()?;
+
         let mut input_data = Self {
             config,
             filenames: Default::default(),
             files,
             version_script_data,
+            profile,
+            script_layout: ScriptLayout::default(),
         };
 
+        input_data.start_readahead();
+
         for input in &config.inputs {
             input_data.register_input(input)?;
         }
+        input_data
+            .script_layout
+            .symbol_assignments
+            .extend(config.defsyms.iter().cloned());
+        // Symbols named by `-u` are loaded in the same way as those named by `EXTERN`.
+        input_data.script_layout.extern_symbols.extend(
+            config
+                .undefined
+                .iter()
+                .chain(&config.require_defined)
+                .cloned(),
+        );
 
         // Our last "file", similar to the prelude is responsible for internal stuff, but this time
         // at the end.
@@ -111,9 +142,44 @@ impl<'config> InputData<'config> {
             modifiers: Default::default(),
             bytes: None,
         });
+
+        config.arch.get_or_init(|| {
+            input_data
+                .detect_architecture()
+                .unwrap_or(crate::args::default_target_arch())
+        });
+
         Ok(input_data)
     }
 
+    /// Works out which architecture we're linking for from the first input that is an ELF file, or
+    /// an archive whose first member is one. This is used when `-m` wasn't passed so that we can
+    /// link objects for architectures other than the one we're running on. If our first ELF input
+    /// is for an architecture that we don't support, we leave it to parsing to report the
+    /// mismatch.
+    fn detect_architecture(&self) -> Option<Architecture> {
+        let e_machine = self
+            .files
+            .iter()
+            .find_map(|file| first_e_machine(file.data()))?;
+        Architecture::from_e_machine(e_machine)
+    }
+
+    /// Requests asynchronous readahead of all our inputs in command-line order. Opening and
+    /// identifying each input touches its first page straight away, so without this, on a cold
+    /// cache we'd wait for each file in turn. This matters most on spinning disks and network
+    /// filesystems. Any errors will be reported when we open the files for real.
+    fn start_readahead(&self) {
+        for input in &self.config.inputs {
+            let Ok(paths) = input.path(self.config) else {
+                continue;
+            };
+            if let Ok(file) = std::fs::File::open(&paths.absolute) {
+                crate::fs::advise_will_need(&file);
+            }
+        }
+    }
+
     fn register_input(&mut self, input: &Input) -> Result {
         let paths = input.path(self.config)?;
         let absolute_path = &paths.absolute;
@@ -149,14 +215,33 @@ impl<'config> InputData<'config> {
             .with_context(|| format!("Failed to mmap input file `{}`", absolute_path.display()))?;
 
         let kind = FileKind::identify_bytes(&bytes)?;
+        if input.modifiers.just_symbols {
+            if !matches!(
+                kind,
+                FileKind::ElfObject | FileKind::ElfDynamic | FileKind::ElfExecutable
+            ) {
+                bail!(
+                    "--just-symbols requires an ELF file, but `{}` isn't one",
+                    absolute_path.display()
+                );
+            }
+        } else if matches!(kind, FileKind::ElfExecutable) {
+            bail!(
+                "`{}` is an executable, which can only be linked with --just-symbols",
+                absolute_path.display()
+            );
+        }
         if matches!(kind, FileKind::Text) {
-            for input in crate::linker_script::linker_script_to_inputs(
+            let script = crate::linker_script::read_linker_script(
                 &bytes,
                 absolute_path,
                 input.modifiers,
-            )? {
+                &self.config.lib_search_path,
+            )?;
+            for input in script.inputs {
                 self.register_input(&input)?;
             }
+            self.script_layout.extend(script.layout);
             return Ok(());
         }
 
@@ -180,6 +265,19 @@ fn read_version_script(path: &Path) -> Result<VersionScriptData> {
 
 impl Input {
     fn path(&self, args: &Args) -> Result<InputPath> {
+        let mut path = self.search_path(args)?;
+        let path_bytes = path.absolute.as_os_str().as_encoded_bytes();
+        if let Some(remap) = args
+            .remap_inputs
+            .iter()
+            .find(|remap| crate::glob::glob_matches(remap.pattern.as_bytes(), path_bytes))
+        {
+            path.absolute.clone_from(&remap.replacement);
+        }
+        Ok(path)
+    }
+
+    fn search_path(&self, args: &Args) -> Result<InputPath> {
         match &self.spec {
             InputSpec::File(p) => {
                 if self.search_first.is_some() {
@@ -228,7 +326,7 @@ impl Input {
     }
 }
 
-fn search_for_file(
+pub(crate) fn search_for_file(
     lib_search_path: &[Box<Path>],
     search_first: Option<&PathBuf>,
     filename: impl AsRef<Path>,
@@ -249,6 +347,30 @@ fn search_for_file(
     None
 }
 
+fn first_e_machine(bytes: &[u8]) -> Option<u16> {
+    if bytes.starts_with(&object::archive::MAGIC) {
+        return archive::ArchiveIterator::from_archive_bytes(bytes)
+            .ok()?
+            .map_while(Result::ok)
+            .find_map(|entry| match entry {
+                archive::ArchiveEntry::Regular(content) => first_e_machine(content.entry_data),
+                _ => None,
+            });
+    }
+    if !bytes.starts_with(&object::elf::ELFMAG) {
+        return None;
+    }
+    let header: &elf::FileHeader = object::from_bytes(bytes.get(..size_of::<elf::FileHeader>())?)
+        .ok()?
+        .0;
+    let e_machine = header.e_machine.get(LittleEndian);
+    if header.e_ident.data == object::elf::ELFDATA2LSB {
+        Some(e_machine)
+    } else {
+        Some(e_machine.swap_bytes())
+    }
+}
+
 const FILE_INDEX_BITS: u32 = 8;
 pub(crate) const MAX_FILES_PER_GROUP: u32 = 1 << FILE_INDEX_BITS;
 
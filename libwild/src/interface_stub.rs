@@ -0,0 +1,672 @@
+//! Optionally writes interface stubs for the shared object that we're linking.
+//! `--interface-stub=/path/to/libfoo.ifs` writes the text format read by `llvm-ifs`.
+//! `--interface-stub-so=/path/to/libfoo.so` writes a stub shared object, which contains just the
+//! dynamic symbols and can be linked against in place of the real shared object.
+//!
+//! A stub only describes what the shared object exports, so it only changes when the ABI does. We
+//! leave the file untouched if its contents wouldn't change, so that build systems that look at
+//! modification times can skip relinking dependents when only the implementation has changed.
+//!
+//! Symbol versions aren't included in either kind of stub, so references from dependents linked
+//! against a stub will bind to the default version of each symbol.
+//!
+//! ```text
+//! --- !ifs-v1
+//! IfsVersion: 3.0
+//! SoName: libfoo.so
+//! Target: { ObjectFormat: ELF, Arch: x86_64, Endianness: little, BitWidth: 64 }
+//! NeededLibs:
+//!   - libc.so.6
+//! Symbols:
+//!   - { Name: foo, Type: Func }
+//!   - { Name: foo_count, Type: Object, Size: 4 }
+//! ...
+//! ```
+
+use crate::arch::Architecture;
+use crate::error::Result;
+use crate::layout::FileLayout;
+use crate::layout::Layout;
+use crate::storage::StorageModel;
+use anyhow::Context as _;
+use object::elf::Dyn64;
+use object::elf::FileHeader64;
+use object::elf::Ident;
+use object::elf::ProgramHeader64;
+use object::elf::SectionHeader64;
+use object::elf::Sym64;
+use object::elf::DT_NEEDED;
+use object::elf::DT_NULL;
+use object::elf::DT_SONAME;
+use object::elf::DT_STRSZ;
+use object::elf::DT_STRTAB;
+use object::elf::DT_SYMENT;
+use object::elf::DT_SYMTAB;
+use object::elf::ELFCLASS64;
+use object::elf::ELFDATA2LSB;
+use object::elf::ELFMAG;
+use object::elf::ELFOSABI_NONE;
+use object::elf::ET_DYN;
+use object::elf::EV_CURRENT;
+use object::elf::PF_R;
+use object::elf::PF_W;
+use object::elf::PF_X;
+use object::elf::PT_DYNAMIC;
+use object::elf::PT_LOAD;
+use object::elf::PT_TLS;
+use object::elf::SHF_ALLOC;
+use object::elf::SHF_EXECINSTR;
+use object::elf::SHF_TLS;
+use object::elf::SHF_WRITE;
+use object::elf::SHT_DYNAMIC;
+use object::elf::SHT_DYNSYM;
+use object::elf::SHT_NOBITS;
+use object::elf::SHT_NULL;
+use object::elf::SHT_STRTAB;
+use object::elf::STB_GLOBAL;
+use object::elf::STB_WEAK;
+use object::elf::STV_DEFAULT;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+use object::U16;
+use object::U32;
+use object::U64;
+use std::io::Write as _;
+use std::path::Path;
+
+pub(crate) fn maybe_write_interface_stubs<S: StorageModel>(layout: &Layout<S>) -> Result {
+    let args = layout.args();
+    // The stubs are outputs of the link, so like the main output, they aren't written by a dry run.
+    if args.dry_run || (args.interface_stub.is_none() && args.interface_stub_so.is_none()) {
+        return Ok(());
+    }
+    let stub = Stub::new::<S>(layout)?;
+    if let Some(stub_path) = args.interface_stub.as_ref() {
+        let mut contents = Vec::new();
+        stub.write_ifs(&mut contents)?;
+        write_if_changed(stub_path, &contents)
+            .with_context(|| format!("Failed to write interface stub `{}`", stub_path.display()))?;
+    }
+    if let Some(stub_path) = args.interface_stub_so.as_ref() {
+        let mut contents = Vec::new();
+        stub.write_elf(&mut contents);
+        write_if_changed(stub_path, &contents).with_context(|| {
+            format!(
+                "Failed to write stub shared object `{}`",
+                stub_path.display()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+fn write_if_changed(path: &Path, contents: &[u8]) -> Result {
+    if std::fs::read(path).is_ok_and(|existing| existing == contents) {
+        return Ok(());
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StubSymbolKind {
+    Func,
+    Object,
+    Tls,
+    NoType,
+}
+
+struct StubSymbol<'data> {
+    name: &'data [u8],
+    kind: StubSymbolKind,
+    size: u64,
+    weak: bool,
+}
+
+struct Stub<'data> {
+    soname: Option<&'data str>,
+    arch: &'static str,
+    e_machine: u16,
+    e_flags: u32,
+    needed_libs: Vec<&'data [u8]>,
+    symbols: Vec<StubSymbol<'data>>,
+}
+
+impl<'data> Stub<'data> {
+    fn new<S: StorageModel>(layout: &Layout<'data, '_, S>) -> Result<Self> {
+        let args = layout.args();
+        let mut stub = Stub {
+            soname: args.soname.as_deref(),
+            arch: match args.arch() {
+                Architecture::X86_64 => "x86_64",
+                Architecture::AArch64 => "AArch64",
+                Architecture::RiscV64 => "riscv64",
+                Architecture::PowerPc64 => "ppc64le",
+            },
+            e_machine: args.arch().elf_header_arch_magic(),
+            e_flags: crate::elf_writer::output_e_flags::<S>(layout)?,
+            needed_libs: Vec::new(),
+            symbols: Vec::new(),
+        };
+        for file in layout.group_layouts.iter().flat_map(|group| &group.files) {
+            match file {
+                // Shared objects that were linked with --as-needed but weren't needed aren't loaded,
+                // so they don't get a layout and, like in our DT_NEEDED entries, aren't listed.
+                FileLayout::Dynamic(dynamic) => stub.needed_libs.push(dynamic.lib_name),
+                FileLayout::Epilogue(epilogue) => {
+                    for sym_def in &epilogue.dynamic_symbol_definitions {
+                        let file_id = layout.symbol_db.file_id_for_symbol(sym_def.symbol_id);
+                        let FileLayout::Object(object) = layout.file_layout(file_id) else {
+                            continue;
+                        };
+                        let sym = object
+                            .object
+                            .symbol(sym_def.symbol_id.to_input(object.symbol_id_range))?;
+                        stub.symbols.push(StubSymbol::new(sym_def.name, sym));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Sort so that the output doesn't depend on the order of our inputs.
+        stub.symbols.sort_by_key(|sym| sym.name);
+        Ok(stub)
+    }
+}
+
+impl<'data> StubSymbol<'data> {
+    fn new(name: &'data [u8], sym: &object::elf::Sym64<LittleEndian>) -> Self {
+        let kind = match sym.st_type() {
+            object::elf::STT_FUNC | object::elf::STT_GNU_IFUNC => StubSymbolKind::Func,
+            object::elf::STT_OBJECT | object::elf::STT_COMMON => StubSymbolKind::Object,
+            object::elf::STT_TLS => StubSymbolKind::Tls,
+            _ => StubSymbolKind::NoType,
+        };
+        StubSymbol {
+            name,
+            kind,
+            size: sym.st_size(LittleEndian),
+            weak: sym.st_bind() == object::elf::STB_WEAK,
+        }
+    }
+}
+
+impl StubSymbolKind {
+    fn ifs_name(self) -> &'static str {
+        match self {
+            StubSymbolKind::Func => "Func",
+            StubSymbolKind::Object => "Object",
+            StubSymbolKind::Tls => "TLS",
+            StubSymbolKind::NoType => "NoType",
+        }
+    }
+
+    fn st_type(self) -> u8 {
+        match self {
+            StubSymbolKind::Func => object::elf::STT_FUNC,
+            StubSymbolKind::Object => object::elf::STT_OBJECT,
+            StubSymbolKind::Tls => object::elf::STT_TLS,
+            StubSymbolKind::NoType => object::elf::STT_NOTYPE,
+        }
+    }
+
+    /// Returns the index in the stub shared object of the section that symbols of this kind are
+    /// defined in.
+    fn section_index(self) -> u16 {
+        match self {
+            StubSymbolKind::Func | StubSymbolKind::NoType => TEXT_INDEX,
+            StubSymbolKind::Object => BSS_INDEX,
+            StubSymbolKind::Tls => TBSS_INDEX,
+        }
+    }
+
+    /// Returns whether the size of symbols of this kind matters to the objects that reference them.
+    /// For data, it does, since it determines the size of copy relocations.
+    fn has_size(self) -> bool {
+        matches!(self, StubSymbolKind::Object | StubSymbolKind::Tls)
+    }
+}
+
+// The sections of our stub shared object. Symbols are defined in sections that occupy no space in
+// the file, since the stub only needs to say where they are and how big they are.
+const DYNSYM_INDEX: u16 = 1;
+const DYNSTR_INDEX: u16 = 2;
+const DYNAMIC_INDEX: u16 = 3;
+const TEXT_INDEX: u16 = 4;
+const BSS_INDEX: u16 = 5;
+const TBSS_INDEX: u16 = 6;
+const SHSTRTAB_INDEX: u16 = 7;
+const NUM_SECTIONS: u16 = 8;
+
+const SECTION_NAMES: [&[u8]; NUM_SECTIONS as usize] = [
+    b"",
+    b".dynsym",
+    b".dynstr",
+    b".dynamic",
+    b".text",
+    b".bss",
+    b".tbss",
+    b".shstrtab",
+];
+
+const STUB_PAGE_SIZE: u64 = 0x1000;
+const STUB_SYMBOL_ALIGNMENT: u64 = 16;
+
+impl Stub<'_> {
+    fn write_ifs(&self, out: &mut Vec<u8>) -> Result {
+        writeln!(out, "--- !ifs-v1")?;
+        writeln!(out, "IfsVersion: 3.0")?;
+        if let Some(soname) = self.soname {
+            writeln!(out, "SoName: {}", yaml_string(soname.as_bytes()))?;
+        }
+        writeln!(
+            out,
+            "Target: {{ ObjectFormat: ELF, Arch: {}, Endianness: little, BitWidth: 64 }}",
+            self.arch
+        )?;
+        if !self.needed_libs.is_empty() {
+            writeln!(out, "NeededLibs:")?;
+            for lib in &self.needed_libs {
+                writeln!(out, "  - {}", yaml_string(lib))?;
+            }
+        }
+        writeln!(out, "Symbols:")?;
+        for sym in &self.symbols {
+            write!(
+                out,
+                "  - {{ Name: {}, Type: {}",
+                yaml_string(sym.name),
+                sym.kind.ifs_name()
+            )?;
+            if sym.kind.has_size() {
+                write!(out, ", Size: {}", sym.size)?;
+            }
+            if sym.weak {
+                write!(out, ", Weak: true")?;
+            }
+            writeln!(out, " }}")?;
+        }
+        writeln!(out, "...")?;
+        Ok(())
+    }
+
+    /// Writes a shared object that defines our dynamic symbols and has the same DT_SONAME and
+    /// DT_NEEDED entries as our output.
+    fn write_elf(&self, out: &mut Vec<u8>) {
+        let e = LittleEndian;
+
+        let mut dynstr = vec![0];
+        let mut add_string = |s: &[u8]| {
+            let offset = dynstr.len() as u64;
+            dynstr.extend_from_slice(s);
+            dynstr.push(0);
+            offset
+        };
+
+        let mut dynamic_entries = Vec::new();
+        for lib in &self.needed_libs {
+            dynamic_entries.push((DT_NEEDED, add_string(lib)));
+        }
+        if let Some(soname) = self.soname {
+            dynamic_entries.push((DT_SONAME, add_string(soname.as_bytes())));
+        }
+
+        // Allocate addresses for our symbols within the sections that they're defined in.
+        let mut section_sizes = [0_u64; NUM_SECTIONS as usize];
+        let symbols = self
+            .symbols
+            .iter()
+            .map(|sym| {
+                let section_size = &mut section_sizes[usize::from(sym.kind.section_index())];
+                let offset = section_size.next_multiple_of(STUB_SYMBOL_ALIGNMENT);
+                *section_size = offset + sym.size;
+                (sym, add_string(sym.name), offset)
+            })
+            .collect::<Vec<_>>();
+
+        let num_segments = if section_sizes[usize::from(TBSS_INDEX)] > 0 {
+            5
+        } else {
+            4
+        };
+        let mut section_offsets = [0_u64; NUM_SECTIONS as usize];
+        let mut offset = (size_of::<FileHeader64<LittleEndian>>()
+            + num_segments * size_of::<ProgramHeader64<LittleEndian>>())
+            as u64;
+        section_offsets[usize::from(DYNSYM_INDEX)] = offset;
+        section_sizes[usize::from(DYNSYM_INDEX)] =
+            ((symbols.len() + 1) * size_of::<Sym64<LittleEndian>>()) as u64;
+        offset += section_sizes[usize::from(DYNSYM_INDEX)];
+        dynamic_entries.extend([
+            (DT_SYMTAB, section_offsets[usize::from(DYNSYM_INDEX)]),
+            (DT_SYMENT, size_of::<Sym64<LittleEndian>>() as u64),
+        ]);
+
+        section_offsets[usize::from(DYNSTR_INDEX)] = offset;
+        section_sizes[usize::from(DYNSTR_INDEX)] = dynstr.len() as u64;
+        offset = (offset + dynstr.len() as u64).next_multiple_of(8);
+        dynamic_entries.extend([
+            (DT_STRTAB, section_offsets[usize::from(DYNSTR_INDEX)]),
+            (DT_STRSZ, dynstr.len() as u64),
+            (DT_NULL, 0),
+        ]);
+
+        section_offsets[usize::from(DYNAMIC_INDEX)] = offset;
+        section_sizes[usize::from(DYNAMIC_INDEX)] =
+            (dynamic_entries.len() * size_of::<Dyn64<LittleEndian>>()) as u64;
+        offset += section_sizes[usize::from(DYNAMIC_INDEX)];
+        let loaded_size = offset;
+
+        let mut shstrtab = Vec::new();
+        let mut section_names = [0_u32; NUM_SECTIONS as usize];
+        for (name_offset, name) in section_names.iter_mut().zip(SECTION_NAMES) {
+            *name_offset = shstrtab.len() as u32;
+            shstrtab.extend_from_slice(name);
+            shstrtab.push(0);
+        }
+        section_offsets[usize::from(SHSTRTAB_INDEX)] = offset;
+        section_sizes[usize::from(SHSTRTAB_INDEX)] = shstrtab.len() as u64;
+        let section_headers_offset = (offset + shstrtab.len() as u64).next_multiple_of(8);
+
+        // Our sections that occupy no space in the file each go in their own segment, after the
+        // part of the file that we load.
+        let mut section_addresses = section_offsets;
+        let text_address = loaded_size.next_multiple_of(STUB_PAGE_SIZE);
+        section_addresses[usize::from(TEXT_INDEX)] = text_address;
+        let bss_address = (text_address + section_sizes[usize::from(TEXT_INDEX)])
+            .next_multiple_of(STUB_PAGE_SIZE);
+        section_addresses[usize::from(BSS_INDEX)] = bss_address;
+        section_addresses[usize::from(TBSS_INDEX)] = bss_address;
+        for index in [TEXT_INDEX, BSS_INDEX, TBSS_INDEX] {
+            section_offsets[usize::from(index)] = loaded_size;
+        }
+
+        let header = FileHeader64::<LittleEndian> {
+            e_ident: Ident {
+                magic: ELFMAG,
+                class: ELFCLASS64,
+                data: ELFDATA2LSB,
+                version: EV_CURRENT,
+                os_abi: ELFOSABI_NONE,
+                abi_version: 0,
+                padding: [0; 7],
+            },
+            e_type: U16::new(e, ET_DYN),
+            e_machine: U16::new(e, self.e_machine),
+            e_version: U32::new(e, u32::from(EV_CURRENT)),
+            e_entry: U64::new(e, 0),
+            e_phoff: U64::new(e, size_of::<FileHeader64<LittleEndian>>() as u64),
+            e_shoff: U64::new(e, section_headers_offset),
+            e_flags: U32::new(e, self.e_flags),
+            e_ehsize: U16::new(e, size_of::<FileHeader64<LittleEndian>>() as u16),
+            e_phentsize: U16::new(e, size_of::<ProgramHeader64<LittleEndian>>() as u16),
+            e_phnum: U16::new(e, num_segments as u16),
+            e_shentsize: U16::new(e, size_of::<SectionHeader64<LittleEndian>>() as u16),
+            e_shnum: U16::new(e, NUM_SECTIONS),
+            e_shstrndx: U16::new(e, SHSTRTAB_INDEX),
+        };
+        out.extend_from_slice(object::bytes_of(&header));
+
+        let segment = |p_type, p_flags, index: u16, file_size, mem_size| ProgramHeader64 {
+            p_type: U32::new(e, p_type),
+            p_flags: U32::new(e, p_flags),
+            p_offset: U64::new(e, section_offsets[usize::from(index)]),
+            p_vaddr: U64::new(e, section_addresses[usize::from(index)]),
+            p_paddr: U64::new(e, section_addresses[usize::from(index)]),
+            p_filesz: U64::new(e, file_size),
+            p_memsz: U64::new(e, mem_size),
+            p_align: U64::new(e, if p_type == PT_LOAD { STUB_PAGE_SIZE } else { 8 }),
+        };
+        let mut segments = vec![
+            ProgramHeader64 {
+                p_offset: U64::new(e, 0),
+                p_vaddr: U64::new(e, 0),
+                p_paddr: U64::new(e, 0),
+                ..segment(PT_LOAD, PF_R, 0, loaded_size, loaded_size)
+            },
+            segment(
+                PT_LOAD,
+                PF_R | PF_X,
+                TEXT_INDEX,
+                0,
+                section_sizes[usize::from(TEXT_INDEX)],
+            ),
+            segment(
+                PT_LOAD,
+                PF_R | PF_W,
+                BSS_INDEX,
+                0,
+                section_sizes[usize::from(BSS_INDEX)],
+            ),
+            segment(
+                PT_DYNAMIC,
+                PF_R,
+                DYNAMIC_INDEX,
+                section_sizes[usize::from(DYNAMIC_INDEX)],
+                section_sizes[usize::from(DYNAMIC_INDEX)],
+            ),
+        ];
+        if num_segments == 5 {
+            segments.push(segment(
+                PT_TLS,
+                PF_R,
+                TBSS_INDEX,
+                0,
+                section_sizes[usize::from(TBSS_INDEX)],
+            ));
+        }
+        for segment in &segments {
+            out.extend_from_slice(object::bytes_of(segment));
+        }
+
+        out.extend_from_slice(object::bytes_of(&Sym64::<LittleEndian>::default()));
+        for (sym, name_offset, offset) in &symbols {
+            let section_index = sym.kind.section_index();
+            let value = if sym.kind == StubSymbolKind::Tls {
+                *offset
+            } else {
+                section_addresses[usize::from(section_index)] + offset
+            };
+            let binding = if sym.weak { STB_WEAK } else { STB_GLOBAL };
+            let out_sym = Sym64::<LittleEndian> {
+                st_name: U32::new(e, *name_offset as u32),
+                st_info: (binding << 4) | sym.kind.st_type(),
+                st_other: STV_DEFAULT,
+                st_shndx: U16::new(e, section_index),
+                st_value: U64::new(e, value),
+                st_size: U64::new(e, sym.size),
+            };
+            out.extend_from_slice(object::bytes_of(&out_sym));
+        }
+
+        out.extend_from_slice(&dynstr);
+        out.resize(section_offsets[usize::from(DYNAMIC_INDEX)] as usize, 0);
+        for (tag, value) in dynamic_entries {
+            let entry = Dyn64::<LittleEndian> {
+                d_tag: U64::new(e, u64::from(tag)),
+                d_val: U64::new(e, value),
+            };
+            out.extend_from_slice(object::bytes_of(&entry));
+        }
+
+        out.extend_from_slice(&shstrtab);
+        out.resize(section_headers_offset as usize, 0);
+        let section_types = [
+            SHT_NULL,
+            SHT_DYNSYM,
+            SHT_STRTAB,
+            SHT_DYNAMIC,
+            SHT_NOBITS,
+            SHT_NOBITS,
+            SHT_NOBITS,
+            SHT_STRTAB,
+        ];
+        for index in 0..NUM_SECTIONS {
+            let i = usize::from(index);
+            let (flags, link, info, entsize) = match index {
+                DYNSYM_INDEX => (SHF_ALLOC, DYNSTR_INDEX, 1, size_of::<Sym64<LittleEndian>>()),
+                DYNSTR_INDEX => (SHF_ALLOC, 0, 0, 0),
+                DYNAMIC_INDEX => (
+                    SHF_ALLOC | SHF_WRITE,
+                    DYNSTR_INDEX,
+                    0,
+                    size_of::<Dyn64<LittleEndian>>(),
+                ),
+                TEXT_INDEX => (SHF_ALLOC | SHF_EXECINSTR, 0, 0, 0),
+                BSS_INDEX => (SHF_ALLOC | SHF_WRITE, 0, 0, 0),
+                TBSS_INDEX => (SHF_ALLOC | SHF_WRITE | SHF_TLS, 0, 0, 0),
+                _ => (0, 0, 0, 0),
+            };
+            let is_null = index == 0;
+            let is_allocated = flags & SHF_ALLOC != 0;
+            let section_header = SectionHeader64::<LittleEndian> {
+                sh_name: U32::new(e, section_names[i]),
+                sh_type: U32::new(e, section_types[i]),
+                sh_flags: U64::new(e, u64::from(flags)),
+                sh_addr: U64::new(
+                    e,
+                    if is_allocated {
+                        section_addresses[i]
+                    } else {
+                        0
+                    },
+                ),
+                sh_offset: U64::new(e, if is_null { 0 } else { section_offsets[i] }),
+                sh_size: U64::new(e, section_sizes[i]),
+                sh_link: U32::new(e, u32::from(link)),
+                sh_info: U32::new(e, info),
+                sh_addralign: U64::new(
+                    e,
+                    match index {
+                        0 => 0,
+                        DYNSTR_INDEX | SHSTRTAB_INDEX => 1,
+                        TEXT_INDEX | BSS_INDEX | TBSS_INDEX => STUB_SYMBOL_ALIGNMENT,
+                        _ => 8,
+                    },
+                ),
+                sh_entsize: U64::new(e, entsize as u64),
+            };
+            out.extend_from_slice(object::bytes_of(&section_header));
+        }
+    }
+}
+
+/// Returns `s` as a YAML scalar, quoting it if it contains anything other than characters that we
+/// know to be safe.
+fn yaml_string(s: &[u8]) -> String {
+    let s = String::from_utf8_lossy(s);
+    if !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'$' | b'-' | b'+'))
+    {
+        return s.into_owned();
+    }
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_stub() -> Stub<'static> {
+        Stub {
+            soname: Some("libfoo.so.1"),
+            arch: "x86_64",
+            e_machine: object::elf::EM_X86_64,
+            e_flags: 0,
+            needed_libs: vec![b"libc.so.6"],
+            symbols: vec![
+                StubSymbol {
+                    name: b"foo@@V1",
+                    kind: StubSymbolKind::Func,
+                    size: 10,
+                    weak: true,
+                },
+                StubSymbol {
+                    name: b"foo_count",
+                    kind: StubSymbolKind::Object,
+                    size: 4,
+                    weak: false,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_write_stub() {
+        let stub = test_stub();
+        let mut out = Vec::new();
+        stub.write_ifs(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+--- !ifs-v1
+IfsVersion: 3.0
+SoName: libfoo.so.1
+Target: { ObjectFormat: ELF, Arch: x86_64, Endianness: little, BitWidth: 64 }
+NeededLibs:
+  - libc.so.6
+Symbols:
+  - { Name: 'foo@@V1', Type: Func, Weak: true }
+  - { Name: foo_count, Type: Object, Size: 4 }
+...
+"
+        );
+    }
+
+    #[test]
+    fn test_write_elf_stub() {
+        use object::read::elf::Dyn as _;
+        use object::read::elf::ElfFile64;
+        use object::Object as _;
+        use object::ObjectSymbol as _;
+
+        let mut out = Vec::new();
+        test_stub().write_elf(&mut out);
+        let file = ElfFile64::<LittleEndian>::parse(out.as_slice()).unwrap();
+        assert_eq!(file.kind(), object::ObjectKind::Dynamic);
+        let symbols = file
+            .dynamic_symbols()
+            .map(|sym| {
+                (
+                    sym.name().unwrap().to_owned(),
+                    sym.kind(),
+                    sym.size(),
+                    sym.is_weak(),
+                    sym.is_undefined(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            symbols,
+            [
+                (
+                    "foo@@V1".to_owned(),
+                    object::SymbolKind::Text,
+                    10,
+                    true,
+                    false
+                ),
+                (
+                    "foo_count".to_owned(),
+                    object::SymbolKind::Data,
+                    4,
+                    false,
+                    false
+                ),
+            ]
+        );
+        let dynamic = file
+            .elf_section_table()
+            .dynamic(LittleEndian, file.data())
+            .unwrap()
+            .unwrap()
+            .0;
+        let tags = dynamic
+            .iter()
+            .map(|entry| entry.d_tag(LittleEndian) as u32)
+            .collect::<Vec<_>>();
+        assert_eq!(&tags[..2], [object::elf::DT_NEEDED, object::elf::DT_SONAME]);
+    }
+}
@@ -6,40 +6,72 @@ use self::elf::NoteHeader;
 use self::elf::Symbol;
 use self::elf::GNU_NOTE_NAME;
 use self::elf::GNU_NOTE_PROPERTY_ENTRY_SIZE;
+use self::elf::GNU_NOTE_PROPERTY_HEADER_SIZE;
 use self::output_section_id::InfoInputs;
+use crate::aarch64_errata;
+use crate::aarch64_errata::ErrataFixes;
 use crate::alignment;
 use crate::alignment::Alignment;
-use crate::arch::Arch;
-use crate::arch::Relaxation as _;
+use crate::arch::Architecture;
+use crate::arch::PltFeatures;
 use crate::args::Args;
 use crate::args::BuildIdOption;
+use crate::args::CetReport;
+use crate::args::DiscardLocals;
+use crate::args::Magic;
 use crate::args::OutputKind;
+use crate::args::SeparateSegments;
+use crate::build_attributes;
+use crate::build_attributes::NoteGroup;
 use crate::debug_assert_bail;
 use crate::elf;
 use crate::elf::EhFrameHdrEntry;
 use crate::elf::File;
 use crate::elf::FileHeader;
 use crate::elf::Versym;
+use crate::elf::GLOBAL_POINTER_SYMBOL_NAME;
+use crate::elf::GNU_PROPERTY_X86_FEATURE_1_LAM_U48;
+use crate::elf::GNU_PROPERTY_X86_FEATURE_1_LAM_U57;
 use crate::elf::GOT_ENTRY_SIZE;
 use crate::elf_writer;
 use crate::error::Error;
 use crate::error::Result;
+use crate::gnu_warning::SymbolWarnings;
+use crate::gnu_warning::WarningReference;
+use crate::got_usage;
+use crate::got_usage::GotUsage;
+use crate::got_usage::GotUsageReason;
 use crate::input_data::FileId;
 use crate::input_data::InputRef;
 use crate::input_data::PRELUDE_FILE_ID;
+use crate::linker_script::AssignmentPosition;
+use crate::linker_script::EvaluationContext;
+use crate::linker_script::Expression;
+use crate::linker_script::LayoutLookup;
+use crate::linker_script::MemoryRegion;
+use crate::linker_script::ScriptLayout;
+use crate::linker_script::SectionsCommand;
 use crate::output_section_id;
+use crate::output_section_id::LoadSegment;
 use crate::output_section_id::OutputSectionId;
 use crate::output_section_id::OutputSections;
 use crate::output_section_id::FILE_HEADER;
 use crate::output_section_map::OutputSectionMap;
+use crate::output_section_part_map;
 use crate::output_section_part_map::OutputSectionPartMap;
 use crate::parsing::InternalSymDefInfo;
+use crate::parsing::ParsedInput;
 use crate::part_id;
 use crate::part_id::PartId;
 use crate::part_id::NUM_GENERATED_PARTS;
 use crate::program_segments;
 use crate::program_segments::ProgramSegmentId;
+use crate::program_segments::GNU_RELRO;
+use crate::program_segments::LOAD_EXEC;
+use crate::program_segments::LOAD_RO;
+use crate::program_segments::LOAD_RW;
 use crate::program_segments::MAX_SEGMENTS;
+use crate::program_segments::PHDR;
 use crate::program_segments::STACK;
 use crate::resolution;
 use crate::resolution::FrameIndex;
@@ -49,19 +81,34 @@ use crate::resolution::ResolvedEpilogue;
 use crate::resolution::SectionSlot;
 use crate::resolution::UnloadedSection;
 use crate::resolution::ValueFlags;
+use crate::riscv64_relaxation;
+use crate::riscv64_relaxation::Phase;
+use crate::riscv64_relaxation::PlanOptions;
+use crate::riscv64_relaxation::Relaxations;
+use crate::riscv64_relaxation::RelocationTargets;
+use crate::riscv64_relaxation::SectionRelaxations;
+use crate::separate_debug::DebugSplit;
 use crate::sharding::ShardKey;
+use crate::split_stack::SplitStack;
 use crate::storage::StorageModel;
 use crate::storage::SymbolNameMap as _;
 use crate::string_merging::get_merged_string_output_address;
 use crate::string_merging::MergedStringStartAddresses;
 use crate::string_merging::MergedStringsSection;
 use crate::symbol::SymbolName;
+use crate::symbol::SymbolVersion;
 use crate::symbol_db::is_mapping_symbol_name;
 use crate::symbol_db::SymbolDb;
 use crate::symbol_db::SymbolDebug;
 use crate::symbol_db::SymbolId;
 use crate::symbol_db::SymbolIdRange;
 use crate::threading::prelude::*;
+use crate::thunks::is_in_range;
+use crate::thunks::NewThunk;
+use crate::thunks::ThunkTarget;
+use crate::thunks::Thunks;
+use crate::thunks::THUNK_ALIGNMENT;
+use ahash::AHashMap;
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::ensure;
@@ -70,12 +117,22 @@ use bitflags::bitflags;
 use crossbeam_queue::ArrayQueue;
 use itertools::Itertools;
 use linker_utils::elf::shf;
+use linker_utils::elf::sht;
 use linker_utils::elf::RelocationKind;
 use linker_utils::elf::SectionFlags;
+use linker_utils::elf::SectionType;
 use linker_utils::relaxation::RelocationModifier;
 use object::elf::gnu_hash;
 use object::elf::Rela64;
 use object::elf::GNU_PROPERTY_AARCH64_FEATURE_1_AND;
+use object::elf::GNU_PROPERTY_AARCH64_FEATURE_1_BTI;
+use object::elf::GNU_PROPERTY_AARCH64_FEATURE_PAUTH;
+use object::elf::GNU_PROPERTY_UINT32_AND_HI;
+use object::elf::GNU_PROPERTY_UINT32_AND_LO;
+use object::elf::GNU_PROPERTY_UINT32_OR_HI;
+use object::elf::GNU_PROPERTY_UINT32_OR_LO;
+use object::elf::GNU_PROPERTY_X86_FEATURE_1_AND;
+use object::elf::GNU_PROPERTY_X86_ISA_1_NEEDED;
 use object::elf::GNU_PROPERTY_X86_UINT32_AND_HI;
 use object::elf::GNU_PROPERTY_X86_UINT32_AND_LO;
 use object::elf::GNU_PROPERTY_X86_UINT32_OR_AND_HI;
@@ -83,6 +140,7 @@ use object::elf::GNU_PROPERTY_X86_UINT32_OR_AND_LO;
 use object::elf::GNU_PROPERTY_X86_UINT32_OR_HI;
 use object::elf::GNU_PROPERTY_X86_UINT32_OR_LO;
 use object::read::elf::Dyn as _;
+use object::read::elf::FileHeader as _;
 use object::read::elf::Rela as _;
 use object::read::elf::SectionHeader;
 use object::read::elf::Sym as _;
@@ -90,7 +148,9 @@ use object::read::elf::VerdefIterator;
 use object::LittleEndian;
 use object::SectionIndex;
 use smallvec::SmallVec;
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::ffi::CString;
 use std::fmt::Display;
 use std::mem::replace;
@@ -101,12 +161,12 @@ use std::num::NonZeroU32;
 use std::num::NonZeroU64;
 use std::sync::atomic;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU16;
 use std::sync::atomic::AtomicU64;
-use std::sync::atomic::AtomicU8;
 use std::sync::Mutex;
 
 #[tracing::instrument(skip_all, name = "Layout")]
-pub fn compute<'data, 'symbol_db, S: StorageModel, A: Arch>(
+pub fn compute<'data, 'symbol_db, S: StorageModel>(
     symbol_db: &'symbol_db SymbolDb<'data, S>,
     resolved: ResolutionOutputs<'data>,
     output: &mut elf_writer::Output,
@@ -116,18 +176,28 @@ pub fn compute<'data, 'symbol_db, S: StorageModel, A: Arch>(
         mut output_sections,
         merged_strings,
         custom_start_stop_defs,
+        script_layout,
     } = resolved;
 
     let symbol_resolution_flags = vec![AtomicResolutionFlags::empty(); symbol_db.num_symbols()];
+    let symbol_warnings = SymbolWarnings::collect(&groups, symbol_db)?;
 
-    let gc_outputs = find_required_sections::<S, A>(
+    let gc_outputs = find_required_sections::<S>(
         groups,
         symbol_db,
         &output_sections,
         &symbol_resolution_flags,
         &merged_strings,
         custom_start_stop_defs,
+        &symbol_warnings,
     )?;
+    symbol_warnings.report(
+        gc_outputs
+            .group_states
+            .iter()
+            .flat_map(|g| &g.common.warning_references),
+        symbol_db,
+    );
 
     if let Some(sym_info) = symbol_db.args.sym_info.as_deref() {
         print_symbol_info(
@@ -141,27 +211,61 @@ pub fn compute<'data, 'symbol_db, S: StorageModel, A: Arch>(
     let mut group_states = gc_outputs.group_states;
 
     merge_dynamic_symbol_definitions(&mut group_states)?;
-    merge_gnu_property_notes(&mut group_states)?;
+    merge_gnu_property_notes(&mut group_states, symbol_db.args)?;
+    merge_build_attributes(&mut group_states);
+    merge_arch_attributes(&mut group_states)?;
+    let plt_layout = PltLayout::new(
+        symbol_db.args,
+        &get_epilogue_mut(&mut group_states).gnu_property_notes,
+    );
     finalise_all_sizes(
         symbol_db,
         &output_sections,
         &mut group_states,
         &symbol_resolution_flags,
+        plt_layout,
     )?;
-    let symbol_resolution_flags: Vec<ResolutionFlags> = symbol_resolution_flags
-        .into_iter()
-        .map(|f| f.into_non_atomic())
-        .collect();
-    let non_addressable_counts = apply_non_addressable_indexes(&mut group_states, symbol_db.args)?;
-    let section_part_sizes = compute_total_section_part_sizes(
+    let symbol_resolution_flags = snapshot_resolution_flags(symbol_resolution_flags);
+    if symbol_db.args.print_got_usage {
+        got_usage::print_got_usage::<S>(
+            symbol_db,
+            group_states.iter().flat_map(|g| &g.common.got_usage),
+            &symbol_resolution_flags,
+        );
+    }
+    let non_addressable_counts = apply_non_addressable_indexes(&mut group_states, symbol_db)?;
+    let mut section_part_sizes = compute_total_section_part_sizes(
         &mut group_states,
         &mut output_sections,
         &symbol_resolution_flags,
         gc_outputs.sections_with_content,
+        symbol_db.args,
     );
-    let section_part_layouts = layout_section_parts(&section_part_sizes, &output_sections);
+    relax_sections(
+        &mut group_states,
+        &mut section_part_sizes,
+        &output_sections,
+        symbol_db,
+        &symbol_resolution_flags,
+    )?;
+    add_thunks::<S>(
+        &mut group_states,
+        &mut section_part_sizes,
+        &output_sections,
+        symbol_db,
+        &symbol_resolution_flags,
+    )?;
+    let (section_part_layouts, section_locations) =
+        layout_section_parts(&section_part_sizes, &output_sections)?;
     let section_layouts = layout_sections(&section_part_layouts);
-    output.set_size(compute_total_file_size(&section_layouts));
+    let file_size = compute_total_file_size(&section_layouts);
+    let debug_split = DebugSplit::new(
+        symbol_db.args,
+        &output_sections,
+        &section_layouts,
+        file_size,
+    )?;
+    output.set_size(file_size, debug_split);
 
     let Some(FileLayoutState::Prelude(internal)) =
         &group_states.first().and_then(|g| g.files.first())
@@ -169,7 +273,33 @@ pub fn compute<'data, 'symbol_db, S: StorageModel, A: Arch>(
         unreachable!();
     };
     let header_info = internal.header_info.as_ref().unwrap();
-    let segment_layouts = compute_segment_layout(&section_layouts, &output_sections, header_info)?;
+    let num_output_sections = u32::from(header_info.num_output_sections_with_content);
+    let section_load_addresses = compute_load_addresses(&section_layouts, &output_sections)?;
+    let is_loaded = |file_id: FileId| {
+        matches!(
+            group_states[file_id.group()].files[file_id.file()],
+            FileLayoutState::Object(_) | FileLayoutState::Dynamic(_)
+        )
+    };
+    let script_sections = ScriptSections {
+        output_sections: &output_sections,
+        section_layouts: &section_layouts,
+        load_addresses: &section_load_addresses,
+    };
+    let script_symbol_values = evaluate_script_symbols(
+        script_layout,
+        symbol_db,
+        &is_loaded,
+        None,
+        script_sections,
+        &section_locations,
+    )?;
+    let segment_layouts = compute_segment_layout(
+        &section_layouts,
+        &section_load_addresses,
+        &output_sections,
+        header_info,
+    )?;
 
     let mem_offsets: OutputSectionPartMap<u64> = starting_memory_offsets(&section_part_layouts);
     let starting_mem_offsets_by_group = compute_start_offsets_by_group(&group_states, mem_offsets);
@@ -196,6 +326,8 @@ pub fn compute<'data, 'symbol_db, S: StorageModel, A: Arch>(
         section_layouts: &section_layouts,
         merged_string_start_addresses: &merged_string_start_addresses,
         merged_strings: &merged_strings,
+        plt_layout,
+        script_symbol_values: &script_symbol_values.values,
     };
     let group_layouts = compute_symbols_and_layouts(
         group_states,
@@ -209,7 +341,41 @@ pub fn compute<'data, 'symbol_db, S: StorageModel, A: Arch>(
             .context("Group resolutions not filled")?;
     }
     update_dynamic_symbol_resolutions(&group_layouts, &mut symbol_resolutions.resolutions);
+    finalise_script_symbols(
+        script_layout,
+        symbol_db,
+        &group_layouts,
+        ScriptSections {
+            output_sections: &output_sections,
+            section_layouts: &section_layouts,
+            load_addresses: &section_load_addresses,
+        },
+        &section_locations,
+        &script_symbol_values,
+        &mut symbol_resolutions.resolutions,
+    )?;
     crate::gc_stats::maybe_write_gc_stats(&group_layouts, symbol_db.args)?;
+    let memtag_globals = crate::memtag::build_descriptors(&group_layouts, symbol_db)?;
+    let symtab_indexes = if symbol_db.args.keeps_input_relocations() {
+        crate::relocatable_object::symtab_indexes(
+            &group_layouts,
+            symbol_db,
+            &symbol_resolution_flags,
+            num_output_sections,
+        )
+    } else {
+        Vec::new()
+    };
+    let group_signature_indexes = if symbol_db.args.output_kind() == OutputKind::RelocatableObject {
+        crate::relocatable_object::group_signature_indexes(
+            &group_layouts,
+            symbol_db,
+            &symtab_indexes,
+            &output_sections,
+        )?
+    } else {
+        AHashMap::new()
+    };
 
     let relocation_statistics = OutputSectionMap::with_size(section_layouts.len());
 
@@ -219,6 +385,7 @@ pub fn compute<'data, 'symbol_db, S: StorageModel, A: Arch>(
         segment_layouts,
         section_part_layouts,
         section_layouts,
+        section_load_addresses,
         group_layouts,
         output_sections,
         non_addressable_counts,
@@ -226,10 +393,49 @@ pub fn compute<'data, 'symbol_db, S: StorageModel, A: Arch>(
         merged_strings,
         merged_string_start_addresses,
         has_static_tls: gc_outputs.has_static_tls,
+        has_unique_symbols: gc_outputs.has_unique_symbols,
+        plt_layout,
         relocation_statistics,
+        memtag_globals,
+        symtab_indexes,
+        group_signature_indexes,
     })
 }
 
+/// Marks symbols that need to be defined even if nothing references them, such as those named by
+/// `EXTERN`, as referenced. This keeps their sections when garbage collecting. Symbols that
+/// nothing defines are ignored.
+fn load_extern_symbols<S: StorageModel>(resources: &GraphResources<S>, queue: &mut LocalWorkQueue) {
+    let ParsedInput::Prelude(prelude) = resources.symbol_db.file(PRELUDE_FILE_ID) else {
+        return;
+    };
+    for name in prelude.extern_symbols {
+        if let Some(symbol_id) = resources
+            .symbol_db
+            .global_names
+            .get(&SymbolName::prehashed(name.as_bytes()))
+        {
+            load_referenced_symbol(symbol_id, resources, queue);
+        }
+    }
+}
+
+/// Marks the supplied symbol as referenced and, if it wasn't already, loads whatever defines it.
+/// The supplied symbol needn't be the selected definition, e.g. if it came from looking up a name.
+fn load_referenced_symbol<S: StorageModel>(
+    symbol_id: SymbolId,
+    resources: &GraphResources<S>,
+    queue: &mut LocalWorkQueue,
+) {
+    let symbol_id = resources.symbol_db.definition(symbol_id);
+    let file_id = resources.symbol_db.file_id_for_symbol(symbol_id);
+    let old_flags =
+        resources.symbol_resolution_flags[symbol_id.as_usize()].fetch_or(ResolutionFlags::DIRECT);
+    if old_flags.is_empty() {
+        queue.send_work(resources, file_id, WorkItem::LoadGlobalSymbol(symbol_id));
+    }
+}
+
 /// Update resolutions for all dynamic symbols that our output file defines.
 #[tracing::instrument(skip_all, name = "Update dynamic symbol resolutions")]
 fn update_dynamic_symbol_resolutions(
@@ -248,18 +454,39 @@ fn update_dynamic_symbol_resolutions(
     }
 }
 
+/// Works out how much space each group needs, including for GOT and PLT entries and dynamic
+/// relocations. Groups are processed in parallel without any shared counters. Each group only
+/// accumulates sizes, then once all groups are done, `compute_start_offsets_by_group` assigns each
+/// group a starting offset in command-line order. This means that the slots a symbol gets are
+/// deterministic regardless of how the work was scheduled.
 #[tracing::instrument(skip_all, name = "Finalise per-object sizes")]
 fn finalise_all_sizes<'data, S: StorageModel>(
     symbol_db: &SymbolDb<'data, S>,
     output_sections: &OutputSections,
     group_states: &mut [GroupState<'data>],
     symbol_resolution_flags: &[AtomicResolutionFlags],
+    plt_layout: PltLayout,
 ) -> Result {
     group_states.par_iter_mut().try_for_each(|state| {
-        state.finalise_sizes(symbol_db, output_sections, symbol_resolution_flags)
+        state.finalise_sizes(
+            symbol_db,
+            output_sections,
+            symbol_resolution_flags,
+            plt_layout,
+        )
     })
 }
 
+/// Converts our per-symbol resolution flags, which are no longer being updated, to their
+/// non-atomic form. This touches every symbol, so is done in parallel.
+#[tracing::instrument(skip_all, name = "Snapshot resolution flags")]
+fn snapshot_resolution_flags(flags: Vec<AtomicResolutionFlags>) -> Vec<ResolutionFlags> {
+    flags
+        .into_par_iter()
+        .map(AtomicResolutionFlags::into_non_atomic)
+        .collect()
+}
+
 fn get_epilogue_mut<'a, 'data>(
     group_states: &'a mut [GroupState<'data>],
 ) -> &'a mut EpilogueLayoutState<'data> {
@@ -299,6 +526,8 @@ enum PropertyClass {
 
 fn get_property_class(property_type: u32) -> Option<PropertyClass> {
     match property_type {
+        GNU_PROPERTY_UINT32_AND_LO..=GNU_PROPERTY_UINT32_AND_HI => Some(PropertyClass::And),
+        GNU_PROPERTY_UINT32_OR_LO..=GNU_PROPERTY_UINT32_OR_HI => Some(PropertyClass::Or),
         GNU_PROPERTY_X86_UINT32_AND_LO..=GNU_PROPERTY_X86_UINT32_AND_HI => Some(PropertyClass::And),
         GNU_PROPERTY_AARCH64_FEATURE_1_AND => Some(PropertyClass::And),
         GNU_PROPERTY_X86_UINT32_OR_LO..=GNU_PROPERTY_X86_UINT32_OR_HI => Some(PropertyClass::Or),
@@ -310,19 +539,23 @@ fn get_property_class(property_type: u32) -> Option<PropertyClass> {
 }
 
 #[tracing::instrument(skip_all, name = "Merge GNU property notes")]
-fn merge_gnu_property_notes(group_states: &mut [GroupState]) -> Result {
-    let properties_per_file = group_states
+fn merge_gnu_property_notes(group_states: &mut [GroupState], args: &Args) -> Result {
+    let objects = group_states
         .iter()
         .flat_map(|group| {
             group.files.iter().filter_map(|file| {
                 if let FileLayoutState::Object(object) = file {
-                    Some(&object.gnu_property_notes)
+                    Some(object)
                 } else {
                     None
                 }
             })
         })
         .collect_vec();
+    let properties_per_file = objects
+        .iter()
+        .map(|object| &object.gnu_property_notes)
+        .collect_vec();
 
     // Merge bits of each property type based on type: OR or AND operation. When a property type
     // is newly added to the map, we start either with zero or all bits-set (PropertyClass::And).
@@ -346,7 +579,7 @@ fn merge_gnu_property_notes(group_states: &mut [GroupState]) -> Result {
     }
 
     // Iterate the properties sorted by property_type so that we have a stable output!
-    let output_properties = property_map
+    let mut output_properties = property_map
         .into_iter()
         .sorted_by_key(|x| x.0)
         .filter_map(|(property_type, property_value)| {
@@ -371,11 +604,310 @@ fn merge_gnu_property_notes(group_states: &mut [GroupState]) -> Result {
         })
         .collect_vec();
 
+    let mut pauth_core_info: Option<(PauthCoreInfo, &&ObjectLayoutState)> = None;
+    for object in &objects {
+        let Some(info) = object.pauth_core_info else {
+            continue;
+        };
+        match pauth_core_info {
+            None => pauth_core_info = Some((info, object)),
+            Some((first, first_object)) if first != info => bail!(
+                "Incompatible AArch64 PAuth core info: {object} has {info}, but {first_object} \
+                 has {first}"
+            ),
+            Some(_) => {}
+        }
+    }
+    // The PAuth ABI is all or nothing. Code compiled without it won't sign or authenticate
+    // pointers, so can't be mixed with code that does.
+    if let Some((info, with_info)) = pauth_core_info {
+        if let Some(without_info) = objects.iter().find(|o| o.pauth_core_info.is_none()) {
+            bail!(
+                "{without_info} has no AArch64 PAuth core info, but {with_info} has {info}. \
+                 All inputs must be compiled for the same PAuth ABI"
+            );
+        }
+    }
+
+    if args.force_bti && matches!(args.arch(), Architecture::AArch64) {
+        for object in &objects {
+            if !has_aarch64_feature(
+                &object.gnu_property_notes,
+                GNU_PROPERTY_AARCH64_FEATURE_1_BTI,
+            ) {
+                crate::error::warning(&format!(
+                    "{object}: BTI turned on by -z force-bti when all inputs do not have BTI in \
+                     NOTE section"
+                ));
+            }
+        }
+        set_property_bits(
+            &mut output_properties,
+            GNU_PROPERTY_AARCH64_FEATURE_1_AND,
+            GNU_PROPERTY_AARCH64_FEATURE_1_BTI,
+        );
+    }
+
+    if matches!(args.arch(), Architecture::X86_64) {
+        report_missing_cet_features(&objects, args)?;
+        let mut forced_bits = 0;
+        if args.force_ibt {
+            forced_bits |= object::elf::GNU_PROPERTY_X86_FEATURE_1_IBT;
+        }
+        if args.force_shstk {
+            forced_bits |= object::elf::GNU_PROPERTY_X86_FEATURE_1_SHSTK;
+        }
+        if args.lam_u48 {
+            forced_bits |= GNU_PROPERTY_X86_FEATURE_1_LAM_U48;
+        }
+        if args.lam_u57 {
+            forced_bits |= GNU_PROPERTY_X86_FEATURE_1_LAM_U57;
+        }
+        if forced_bits != 0 {
+            set_property_bits(
+                &mut output_properties,
+                GNU_PROPERTY_X86_FEATURE_1_AND,
+                forced_bits,
+            );
+        }
+        if args.ibt_plt == Some(false) {
+            // Our PLT entries won't have landing pads, so the output isn't IBT-compatible.
+            for property in &mut output_properties {
+                if property.ptype == GNU_PROPERTY_X86_FEATURE_1_AND {
+                    property.data &= !object::elf::GNU_PROPERTY_X86_FEATURE_1_IBT;
+                }
+            }
+            output_properties.retain(|p| p.ptype != GNU_PROPERTY_X86_FEATURE_1_AND || p.data != 0);
+        }
+        if let Some(isa_needed) = args.x86_isa_needed {
+            output_properties.retain(|p| p.ptype != GNU_PROPERTY_X86_ISA_1_NEEDED);
+            set_property_bits(
+                &mut output_properties,
+                GNU_PROPERTY_X86_ISA_1_NEEDED,
+                isa_needed,
+            );
+        }
+    }
+
+    let pauth_core_info = pauth_core_info.map(|(info, _)| info);
     let epilogue = get_epilogue_mut(group_states);
     epilogue.gnu_property_notes = output_properties;
+    epilogue.pauth_core_info = pauth_core_info;
+    Ok(())
+}
+
+/// Collects the build attribute notes from all objects, dropping those for sections that we didn't
+/// load, then merges them into the epilogue.
+#[tracing::instrument(skip_all, name = "Merge build attribute notes")]
+fn merge_build_attributes(group_states: &mut [GroupState]) {
+    let mut groups = Vec::new();
+    let mut referenced_sections = HashSet::new();
+    for group in group_states.iter_mut() {
+        for file in &mut group.files {
+            let FileLayoutState::Object(object) = file else {
+                continue;
+            };
+            let note_groups = std::mem::take(&mut object.build_attributes);
+            let is_loaded = |location: &build_attributes::Location| {
+                matches!(
+                    object.sections[location.section_index.0],
+                    SectionSlot::Loaded(..)
+                )
+            };
+            for note_group in note_groups {
+                if let Some(range) = &note_group.range {
+                    if !is_loaded(&range.start) || !is_loaded(&range.end) {
+                        continue;
+                    }
+                    referenced_sections.insert((object.file_id, range.start.section_index));
+                    referenced_sections.insert((object.file_id, range.end.section_index));
+                }
+                groups.push(note_group);
+            }
+        }
+    }
+    if groups.is_empty() {
+        return;
+    }
+
+    // Work out where each referenced section will go, so that we can tell which ranges are
+    // adjacent. Sections are placed in their parts in the order in which we visit them here.
+    let mut positions = HashMap::new();
+    let mut num_in_part: HashMap<PartId, u64> = HashMap::new();
+    for group in group_states.iter() {
+        for file in &group.files {
+            let FileLayoutState::Object(object) = file else {
+                continue;
+            };
+            for (index, slot) in object.sections.iter().enumerate() {
+                let SectionSlot::Loaded(section) = slot else {
+                    continue;
+                };
+                if section.size == 0 {
+                    continue;
+                }
+                let index_in_part = num_in_part.entry(section.part_id).or_default();
+                let key = (object.file_id, object::SectionIndex(index));
+                if referenced_sections.contains(&key) {
+                    positions.insert(
+                        key,
+                        build_attributes::SectionPosition {
+                            part_id: section.part_id,
+                            index_in_part: *index_in_part,
+                            size: section.size,
+                        },
+                    );
+                }
+                *index_in_part += 1;
+            }
+        }
+    }
+
+    // Groups for empty sections don't cover anything.
+    groups.retain(|group| {
+        group.range.as_ref().is_none_or(|range| {
+            positions.contains_key(&(range.start.file_id, range.start.section_index))
+                && positions.contains_key(&(range.end.file_id, range.end.section_index))
+        })
+    });
+
+    get_epilogue_mut(group_states).build_attributes = build_attributes::merge(groups, &positions);
+}
+
+/// Merges the architecture attributes of the objects that we load into the epilogue.
+#[tracing::instrument(skip_all, name = "Merge architecture attributes")]
+fn merge_arch_attributes(group_states: &mut [GroupState]) -> Result {
+    let merged = crate::arch_attributes::merge(group_states.iter().flat_map(|group| {
+        group.files.iter().filter_map(|file| match file {
+            FileLayoutState::Object(object) => Some((&object.input, object.arch_attributes)),
+            _ => None,
+        })
+    }))?;
+    get_epilogue_mut(group_states).arch_attributes = merged;
     Ok(())
 }
 
+/// Reports inputs that aren't marked as IBT or shadow stack compatible, as requested by
+/// `-z cet-report`. Without that, we still warn about inputs lacking IBT if `-z force-ibt` marks
+/// the output as IBT-compatible regardless.
+fn report_missing_cet_features(objects: &[&ObjectLayoutState], args: &Args) -> Result {
+    for object in objects {
+        let features = object
+            .gnu_property_notes
+            .iter()
+            .find(|p| p.ptype == GNU_PROPERTY_X86_FEATURE_1_AND)
+            .map_or(0, |p| p.data);
+        for (bit, name) in [
+            (
+                object::elf::GNU_PROPERTY_X86_FEATURE_1_IBT,
+                "GNU_PROPERTY_X86_FEATURE_1_IBT",
+            ),
+            (
+                object::elf::GNU_PROPERTY_X86_FEATURE_1_SHSTK,
+                "GNU_PROPERTY_X86_FEATURE_1_SHSTK",
+            ),
+        ] {
+            if features & bit != 0 {
+                continue;
+            }
+            let message = format!("{object}: -z cet-report: file does not have {name} property");
+            match args.cet_report {
+                CetReport::None => {
+                    if bit == object::elf::GNU_PROPERTY_X86_FEATURE_1_IBT && args.force_ibt {
+                        crate::error::warning(&format!(
+                            "{object}: -z force-ibt: file does not have {name} property"
+                        ));
+                    }
+                }
+                CetReport::Warning => crate::error::warning(&message),
+                CetReport::Error => bail!(message),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sets `bits` in the output property of type `ptype`, adding the property if it isn't present.
+fn set_property_bits(output_properties: &mut Vec<GnuProperty>, ptype: u32, bits: u32) {
+    if let Some(property) = output_properties.iter_mut().find(|p| p.ptype == ptype) {
+        property.data |= bits;
+    } else {
+        output_properties.push(GnuProperty { ptype, data: bits });
+        output_properties.sort_by_key(|p| p.ptype);
+    }
+}
+
+fn has_aarch64_feature(properties: &[GnuProperty], feature: u32) -> bool {
+    properties
+        .iter()
+        .any(|p| p.ptype == GNU_PROPERTY_AARCH64_FEATURE_1_AND && p.data & feature != 0)
+}
+
+/// Determines what our PLT entries need to support. BTI and IBT landing pads are needed if the
+/// output is marked as BTI or IBT-compatible, since then indirect branches to the PLT must land on
+/// one. PAC is only used if explicitly requested.
+fn plt_features(args: &Args, output_properties: &[GnuProperty]) -> PltFeatures {
+    match args.arch() {
+        Architecture::AArch64 => PltFeatures {
+            bti: has_aarch64_feature(output_properties, GNU_PROPERTY_AARCH64_FEATURE_1_BTI),
+            pac: args.pac_plt,
+            ..PltFeatures::default()
+        },
+        Architecture::X86_64 => PltFeatures {
+            ibt: args.ibt_plt.unwrap_or_else(|| {
+                output_properties.iter().any(|p| {
+                    p.ptype == GNU_PROPERTY_X86_FEATURE_1_AND
+                        && p.data & object::elf::GNU_PROPERTY_X86_FEATURE_1_IBT != 0
+                })
+            }),
+            ..PltFeatures::default()
+        },
+        _ => PltFeatures::default(),
+    }
+}
+
+/// How we lay out the PLT. Unlike most of our layout decisions, this depends on the merged GNU
+/// property notes, so can only be determined once we've loaded all of our inputs.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PltLayout {
+    /// The size of each entry in .plt.got.
+    pub(crate) entry_size: u64,
+
+    /// Whether calls to functions in shared objects are bound when they're first made. See
+    /// `Args::lazy_binding`.
+    pub(crate) lazy_binding: bool,
+
+    pub(crate) features: PltFeatures,
+}
+
+impl PltLayout {
+    fn new(args: &Args, output_properties: &[GnuProperty]) -> PltLayout {
+        let features = plt_features(args, output_properties);
+        let lazy_binding = args.lazy_binding();
+        // On x86-64, a PLT entry that doesn't need a landing pad or to call into the dynamic
+        // loader is just an indirect jump, which fits in a smaller entry.
+        let entry_size =
+            if matches!(args.arch(), Architecture::X86_64) && !features.ibt && !lazy_binding {
+                elf::X86_64_COMPACT_PLT_ENTRY_SIZE
+            } else {
+                args.plt_entry_size()
+            };
+        PltLayout {
+            entry_size,
+            lazy_binding,
+            features,
+        }
+    }
+
+    /// Returns whether lazily bound functions get a second PLT entry in .plt.sec. With IBT, both
+    /// the entry that gets called and the entry that the GOT initially points to need to start with
+    /// an endbr64 and there isn't room for that in a single entry. The entries that get called go
+    /// in .plt.sec, while the entries that call into the dynamic loader stay in .plt.got.
+    pub(crate) fn uses_plt_sec(&self) -> bool {
+        self.lazy_binding && self.features.ibt
+    }
+}
+
 fn compute_total_file_size(section_layouts: &OutputSectionMap<OutputRecordLayout>) -> u64 {
     let mut file_size = 0;
     section_layouts.for_each(|_, s| file_size = file_size.max(s.file_offset + s.file_size));
@@ -389,6 +921,11 @@ pub struct Layout<'data, 'symbol_db, S: StorageModel> {
     pub(crate) symbol_resolutions: SymbolResolutions,
     pub(crate) section_part_layouts: OutputSectionPartMap<OutputRecordLayout>,
     pub(crate) section_layouts: OutputSectionMap<OutputRecordLayout>,
+
+    /// The address that each section is loaded from. This is the same as its address, unless a
+    /// linker script says otherwise.
+    pub(crate) section_load_addresses: OutputSectionMap<u64>,
+
     pub(crate) group_layouts: Vec<GroupLayout<'data>>,
     pub(crate) segment_layouts: SegmentLayouts,
     pub(crate) output_sections: OutputSections<'data>,
@@ -398,6 +935,21 @@ pub struct Layout<'data, 'symbol_db, S: StorageModel> {
     pub(crate) merged_string_start_addresses: MergedStringStartAddresses,
     pub(crate) relocation_statistics: OutputSectionMap<AtomicU64>,
     pub(crate) has_static_tls: bool,
+
+    /// Whether we export any `STB_GNU_UNIQUE` definitions from a shared object.
+    pub(crate) has_unique_symbols: bool,
+    pub(crate) plt_layout: PltLayout,
+
+    /// The contents of `.memtag.globals.dynamic`, which are written once everything else is.
+    pub(crate) memtag_globals: Vec<u8>,
+
+    /// When producing a relocatable object, the index of each symbol in our symbol table, so that
+    /// relocations can be rewritten to refer to it. Empty otherwise.
+    pub(crate) symtab_indexes: Vec<u32>,
+
+    /// When producing a relocatable object, the index in our symbol table of the signature of each
+    /// section group that we copied, keyed by the output section that holds the group.
+    pub(crate) group_signature_indexes: AHashMap<OutputSectionId, u32>,
 }
 
 pub(crate) struct SegmentLayouts {
@@ -409,7 +961,12 @@ pub(crate) struct SegmentLayouts {
 
 #[derive(Default, Clone)]
 pub(crate) struct SegmentLayout {
-    pub(crate) id: ProgramSegmentId,
+    pub(crate) segment_type: u32,
+    pub(crate) segment_flags: u32,
+
+    /// The physical address of the segment. This is the same as its virtual address, unless a
+    /// linker script says otherwise.
+    pub(crate) physical_address: u64,
     pub(crate) sizes: OutputRecordLayout,
 }
 
@@ -517,7 +1074,13 @@ pub(crate) struct EpilogueLayoutState<'data> {
     dynamic_symbol_definitions: Vec<DynamicSymbolDefinition<'data>>,
     gnu_hash_layout: Option<GnuHashLayout>,
     gnu_property_notes: Vec<GnuProperty>,
+    pauth_core_info: Option<PauthCoreInfo>,
+    memory_seal: bool,
     build_id_size: Option<usize>,
+    build_attributes: Vec<NoteGroup<'data>>,
+    arch_attributes: Vec<u8>,
+    debuglink_size: u64,
+    version_definitions_size: u64,
 }
 
 #[derive(Default, Debug)]
@@ -534,6 +1097,10 @@ pub(crate) struct EpilogueLayout<'data> {
     pub(crate) dynamic_symbol_definitions: Vec<DynamicSymbolDefinition<'data>>,
     dynsym_start_index: u32,
     pub(crate) gnu_property_notes: Vec<GnuProperty>,
+    pub(crate) pauth_core_info: Option<PauthCoreInfo>,
+    pub(crate) memory_seal: bool,
+    pub(crate) build_attributes: Vec<NoteGroup<'data>>,
+    pub(crate) arch_attributes: Vec<u8>,
 }
 
 pub(crate) struct ObjectLayout<'data> {
@@ -543,17 +1110,28 @@ pub(crate) struct ObjectLayout<'data> {
     pub(crate) sections: Vec<SectionSlot>,
     pub(crate) section_resolutions: Vec<SectionResolution>,
     pub(crate) symbol_id_range: SymbolIdRange,
+    pub(crate) split_stack: SplitStack,
+    pub(crate) thunks: Thunks,
+    pub(crate) relaxations: Relaxations,
 }
 
 pub(crate) struct PreludeLayout {
     pub(crate) entry_symbol_id: Option<SymbolId>,
     pub(crate) tlsld_got_entry: Option<NonZeroU64>,
+    pub(crate) lazy_plt_header: Option<LazyPltHeader>,
     pub(crate) identity: String,
     pub(crate) header_info: HeaderInfo,
     pub(crate) internal_symbols: InternalSymbols,
     pub(crate) dynamic_linker: Option<CString>,
 }
 
+/// The PLT header and the GOT entries that it uses, which are present when we're doing lazy binding.
+#[derive(Clone, Copy)]
+pub(crate) struct LazyPltHeader {
+    pub(crate) got_address: u64,
+    pub(crate) plt_address: u64,
+}
+
 pub(crate) struct InternalSymbols {
     pub(crate) symbol_definitions: Vec<InternalSymDefInfo>,
     pub(crate) start_symbol_id: SymbolId,
@@ -578,6 +1156,10 @@ pub(crate) struct DynamicLayout<'data> {
 
     pub(crate) verdef_info: Option<VerdefInfo<'data>>,
 
+    /// The output version that we give to the symbols that we import from this object, if it
+    /// doesn't define any versions and we're using `--default-imported-symver`.
+    pub(crate) default_version: Option<u16>,
+
     /// Whether this is the last DynamicLayout that puts content into .gnu.version_r.
     pub(crate) is_last_verneed: bool,
 }
@@ -594,6 +1176,7 @@ trait SymbolRequestHandler<'data, S: StorageModel>: std::fmt::Display + HandlerD
         common: &mut CommonGroupState,
         symbol_db: &SymbolDb<'data, S>,
         symbol_resolution_flags: &[AtomicResolutionFlags],
+        plt_layout: PltLayout,
     ) -> Result {
         let _file_span = symbol_db.args.trace_span_for_file(self.file_id());
         let symbol_id_range = self.symbol_id_range();
@@ -621,7 +1204,11 @@ trait SymbolRequestHandler<'data, S: StorageModel>: std::fmt::Display + HandlerD
                                 symbol_db.symbol_name_for_display(symbol_id)
                             )
                         })?;
+                } else if current_res_flags.contains(ResolutionFlags::CANONICAL_PLT) {
+                    self.check_canonical_plt(symbol_db, symbol_id)?;
                 } else {
+                    // Symbols with canonical PLT entries get written by the epilogue, which
+                    // allocates space for them.
                     let name = symbol_db.symbol_name(symbol_id)?;
                     common.allocate(part_id::DYNSTR, name.len() as u64 + 1);
                     common.allocate(part_id::DYNSYM, crate::elf::SYMTAB_ENTRY_SIZE);
@@ -633,6 +1220,7 @@ trait SymbolRequestHandler<'data, S: StorageModel>: std::fmt::Display + HandlerD
                     value_flags,
                     resolution_flags.get(),
                     symbol_db.args.output_kind(),
+                    plt_layout,
                 )?;
             }
 
@@ -641,6 +1229,7 @@ trait SymbolRequestHandler<'data, S: StorageModel>: std::fmt::Display + HandlerD
                 resolution_flags,
                 &mut common.mem_sizes,
                 symbol_db.args.output_kind(),
+                plt_layout,
             );
         }
         if symbol_db.args.should_output_symbol_versions() {
@@ -657,7 +1246,7 @@ trait SymbolRequestHandler<'data, S: StorageModel>: std::fmt::Display + HandlerD
         Ok(())
     }
 
-    fn load_symbol<'scope, A: Arch>(
+    fn load_symbol<'scope>(
         &mut self,
         common: &mut CommonGroupState<'data>,
         symbol_id: SymbolId,
@@ -676,6 +1265,12 @@ trait SymbolRequestHandler<'data, S: StorageModel>: std::fmt::Display + HandlerD
             symbol_db.symbol_name(symbol_id)?
         );
     }
+
+    /// Checks that a canonical PLT entry, which becomes the address of `symbol_id` for the whole
+    /// process, can be used for the symbol.
+    fn check_canonical_plt(&self, _symbol_db: &SymbolDb<'data, S>, _symbol_id: SymbolId) -> Result {
+        Ok(())
+    }
 }
 
 fn export_dynamic<'data, S: StorageModel>(
@@ -683,10 +1278,18 @@ fn export_dynamic<'data, S: StorageModel>(
     symbol_id: SymbolId,
     graph_resources: &GraphResources<'data, '_, S>,
 ) -> Result {
-    let name = graph_resources.symbol_db.symbol_name(symbol_id)?;
+    let symbol_db = graph_resources.symbol_db;
+    let mut name = symbol_db.symbol_name(symbol_id)?.bytes();
+    let version = match SymbolVersion::parse(name) {
+        Some(symbol_version) => {
+            name = symbol_version.name;
+            symver_version_index(symbol_db, symbol_version)?
+        }
+        None => output_version_index(symbol_db, name),
+    };
     common
         .dynamic_symbol_definitions
-        .push(DynamicSymbolDefinition::new(symbol_id, name.bytes()));
+        .push(DynamicSymbolDefinition::new(symbol_id, name, version));
     Ok(())
 }
 
@@ -695,6 +1298,7 @@ fn allocate_symbol_resolution(
     resolution_flags: &AtomicResolutionFlags,
     mem_sizes: &mut OutputSectionPartMap<u64>,
     output_kind: OutputKind,
+    plt_layout: PltLayout,
 ) {
     let mut r = resolution_flags.get();
     if !r.is_empty() && value_flags.contains(ValueFlags::IFUNC) {
@@ -702,7 +1306,7 @@ fn allocate_symbol_resolution(
         r |= ResolutionFlags::GOT | ResolutionFlags::PLT;
     }
 
-    allocate_resolution(value_flags, r, mem_sizes, output_kind);
+    allocate_resolution(value_flags, r, mem_sizes, output_kind, plt_layout);
 }
 
 /// Computes how much to allocation for a particular resolution. This is intended for debug
@@ -710,6 +1314,7 @@ fn allocate_symbol_resolution(
 pub(crate) fn compute_allocations(
     resolution: &Resolution,
     output_kind: OutputKind,
+    plt_layout: PltLayout,
 ) -> OutputSectionPartMap<u64> {
     let mut sizes = OutputSectionPartMap::with_size(NUM_GENERATED_PARTS);
     allocate_resolution(
@@ -717,15 +1322,33 @@ pub(crate) fn compute_allocations(
         resolution.resolution_flags,
         &mut sizes,
         output_kind,
+        plt_layout,
     );
     sizes
 }
 
-fn allocate_resolution(
+/// Returns whether the GOT entry for a resolution gets filled in by the dynamic loader the first
+/// time that the function is called via the PLT, rather than when the output is loaded. If so, it
+/// needs a JUMP_SLOT relocation in .rela.plt rather than a GLOB_DAT in .rela.dyn.
+pub(crate) fn is_lazily_bound(
+    value_flags: ValueFlags,
+    resolution_flags: ResolutionFlags,
+    plt_layout: PltLayout,
+) -> bool {
+    plt_layout.lazy_binding
+        && resolution_flags.contains(ResolutionFlags::PLT)
+        && value_flags.contains(ValueFlags::DYNAMIC)
+        && !value_flags.contains(ValueFlags::IFUNC)
+        && !resolution_flags
+            .intersects(ResolutionFlags::COPY_RELOCATION | ResolutionFlags::GOT_REFERENCE)
+}
+
+pub(crate) fn allocate_resolution(
     value_flags: ValueFlags,
     resolution_flags: ResolutionFlags,
     mem_sizes: &mut OutputSectionPartMap<u64>,
     output_kind: OutputKind,
+    plt_layout: PltLayout,
 ) {
     let has_dynamic_symbol = value_flags.contains(ValueFlags::DYNAMIC)
         || resolution_flags.contains(ResolutionFlags::EXPORT_DYNAMIC);
@@ -735,10 +1358,17 @@ fn allocate_resolution(
     }
     if resolution_flags.contains(ResolutionFlags::GOT) {
         mem_sizes.increment(part_id::GOT, elf::GOT_ENTRY_SIZE);
+        let is_lazily_bound = is_lazily_bound(value_flags, resolution_flags, plt_layout);
         if resolution_flags.contains(ResolutionFlags::PLT) {
-            mem_sizes.increment(part_id::PLT_GOT, elf::PLT_ENTRY_SIZE);
+            mem_sizes.increment(part_id::PLT_GOT, plt_layout.entry_size);
+            if is_lazily_bound && plt_layout.uses_plt_sec() {
+                mem_sizes.increment(part_id::PLT_SEC, elf::PLT_ENTRY_SIZE);
+            }
         }
-        if value_flags.contains(ValueFlags::IFUNC) {
+        if value_flags.contains(ValueFlags::IFUNC)
+            || is_lazily_bound
+            || resolution_flags.contains(ResolutionFlags::CANONICAL_PLT)
+        {
             mem_sizes.increment(part_id::RELA_PLT, elf::RELA_ENTRY_SIZE);
         } else if resolution_flags.contains(ResolutionFlags::COPY_RELOCATION) {
             // Copy relocation means that we know the relative address.
@@ -785,7 +1415,7 @@ impl HandlerData for ObjectLayoutState<'_> {
 }
 
 impl<'data, S: StorageModel> SymbolRequestHandler<'data, S> for ObjectLayoutState<'data> {
-    fn load_symbol<'scope, A: Arch>(
+    fn load_symbol<'scope>(
         &mut self,
         common: &mut CommonGroupState<'data>,
         symbol_id: SymbolId,
@@ -804,9 +1434,11 @@ impl<'data, S: StorageModel> SymbolRequestHandler<'data, S> for ObjectLayoutStat
             .symbol_section(local_symbol, object_symbol_index)?
         {
             self.sections_required.push(SectionRequest::new(section_id));
-            self.load_sections::<S, A>(common, resources, queue)?;
-        } else if local_symbol.is_common(LittleEndian) {
-            let common_symbol = CommonSymbol::new(local_symbol)?;
+            self.load_sections::<S>(common, resources, queue)?;
+        } else if local_symbol.is_common(LittleEndian)
+            && resources.symbol_db.args.output_kind() != OutputKind::RelocatableObject
+        {
+            let common_symbol = CommonSymbol::new(local_symbol, symbol_id, resources.symbol_db)?;
             common.allocate(
                 output_section_id::BSS.part_id_with_alignment(common_symbol.alignment),
                 common_symbol.size,
@@ -827,7 +1459,7 @@ impl HandlerData for DynamicLayoutState<'_> {
 }
 
 impl<'data, S: StorageModel> SymbolRequestHandler<'data, S> for DynamicLayoutState<'data> {
-    fn load_symbol<'scope, A: Arch>(
+    fn load_symbol<'scope>(
         &mut self,
         _common: &mut CommonGroupState,
         symbol_id: SymbolId,
@@ -871,6 +1503,23 @@ impl<'data, S: StorageModel> SymbolRequestHandler<'data, S> for DynamicLayoutSta
             bail!("Cannot apply copy relocation for symbol");
         }
 
+        // The shared object binds references to a protected symbol to its own definition, so it
+        // would never see writes to our copy.
+        if symbol.st_visibility() == object::elf::STV_PROTECTED {
+            bail!(
+                "Copy relocation against non-copyable protected symbol in {}. Recompile with -fPIC",
+                self.input
+            );
+        }
+        if self.needs_indirect_extern_access {
+            bail!(
+                "Copy relocation against `{}` in {}, which needs indirect extern access. Recompile \
+                 with -fPIC",
+                symbol_db.symbol_name_for_display(symbol_id),
+                self.input
+            );
+        }
+
         let section = self
             .object
             .section(SectionIndex(usize::from(section_index)))?;
@@ -893,6 +1542,32 @@ impl<'data, S: StorageModel> SymbolRequestHandler<'data, S> for DynamicLayoutSta
 
         Ok(())
     }
+
+    fn check_canonical_plt(&self, symbol_db: &SymbolDb<'data, S>, symbol_id: SymbolId) -> Result {
+        let symbol = self
+            .object
+            .symbol(self.symbol_id_range().id_to_input(symbol_id))?;
+
+        // The shared object would keep using the real address of a protected function, which
+        // breaks pointer equality with our PLT entry.
+        if symbol.st_visibility() == object::elf::STV_PROTECTED {
+            bail!(
+                "Non-canonical reference to canonical protected function `{}` in {}. Recompile \
+                 with -fPIC",
+                symbol_db.symbol_name_for_display(symbol_id),
+                self.input
+            );
+        }
+        if self.needs_indirect_extern_access {
+            bail!(
+                "Non-canonical reference to canonical function `{}` in {}, which needs indirect \
+                 extern access. Recompile with -fPIC",
+                symbol_db.symbol_name_for_display(symbol_id),
+                self.input
+            );
+        }
+        Ok(())
+    }
 }
 
 impl HandlerData for PreludeLayoutState {
@@ -906,7 +1581,7 @@ impl HandlerData for PreludeLayoutState {
 }
 
 impl<'data, S: StorageModel> SymbolRequestHandler<'data, S> for PreludeLayoutState {
-    fn load_symbol<'scope, A: Arch>(
+    fn load_symbol<'scope>(
         &mut self,
         _common: &mut CommonGroupState,
         _symbol_id: SymbolId,
@@ -928,7 +1603,7 @@ impl HandlerData for EpilogueLayoutState<'_> {
 }
 
 impl<'data, S: StorageModel> SymbolRequestHandler<'data, S> for EpilogueLayoutState<'data> {
-    fn load_symbol<'scope, A: Arch>(
+    fn load_symbol<'scope>(
         &mut self,
         _common: &mut CommonGroupState,
         _symbol_id: SymbolId,
@@ -950,6 +1625,13 @@ struct CommonGroupState<'data> {
 
     /// Indexed by `FrameIndex`.
     exception_frames: Vec<ExceptionFrame<'data>>,
+
+    /// Relocations that caused GOT or PLT entries to be needed. Only populated when we've been
+    /// asked to report GOT usage.
+    got_usage: Vec<GotUsage>,
+
+    /// References to symbols that have a `.gnu.warning` section.
+    warning_references: Vec<WarningReference>,
 }
 
 impl CommonGroupState<'_> {
@@ -958,6 +1640,8 @@ impl CommonGroupState<'_> {
             mem_sizes: output_sections.new_part_map(),
             dynamic_symbol_definitions: Default::default(),
             exception_frames: Default::default(),
+            got_usage: Default::default(),
+            warning_references: Default::default(),
         }
     }
 
@@ -1035,6 +1719,27 @@ struct ObjectLayoutState<'data> {
     eh_frame_size: u64,
 
     gnu_property_notes: Vec<GnuProperty>,
+
+    /// The AArch64 PAuth ABI platform and version that this object was compiled for, if any.
+    pauth_core_info: Option<PauthCoreInfo>,
+
+    split_stack: SplitStack,
+
+    /// Range-extension thunks for branches in our sections that can't reach their targets.
+    thunks: Thunks,
+
+    /// The bytes that RISC-V linker relaxation deletes from our sections.
+    relaxations: Relaxations,
+
+    /// Space in `.memtag.globals.dynamic` for the descriptors of the globals that we tag.
+    memtag_globals_size: u64,
+
+    /// The contents of our `.gnu.build.attributes`, which are merged with those of other objects.
+    build_attributes: Vec<NoteGroup<'data>>,
+
+    /// The contents of our architecture attributes section, e.g. `.riscv.attributes`. Empty if we
+    /// don't have one.
+    arch_attributes: &'data [u8],
 }
 
 #[derive(Default)]
@@ -1055,6 +1760,24 @@ pub(crate) struct GnuProperty {
     pub(crate) data: u32,
 }
 
+/// The contents of an AArch64 PAuth ABI core info property, which says how signed pointers are
+/// encoded. Unlike other properties, this isn't merged bitwise. All inputs that have it must agree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PauthCoreInfo {
+    pub(crate) platform: u64,
+    pub(crate) version: u64,
+}
+
+impl Display for PauthCoreInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "platform 0x{:x}, version 0x{:x}",
+            self.platform, self.version
+        )
+    }
+}
+
 #[derive(Default)]
 struct LocalWorkQueue {
     /// The index of the worker that owns this queue.
@@ -1067,7 +1790,7 @@ struct LocalWorkQueue {
 bitflags! {
     /// What kind of resolution we want for a symbol or section.
     #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
-    pub(crate) struct ResolutionFlags: u8 {
+    pub(crate) struct ResolutionFlags: u16 {
         /// The direct value is needed. e.g. via a relative or absolute relocation that doesn't use the
         /// PLT or GOT.
         const DIRECT = 1 << 0;
@@ -1098,11 +1821,22 @@ bitflags! {
         /// We encountered a direct reference to a symbol from a non-writable section and so we're
         /// going to need to do a copy relocation.
         const COPY_RELOCATION = 1 << 7;
+
+        /// An executable referenced the address of a function in a shared object directly, so
+        /// instead uses the address of our PLT entry. The PLT entry then becomes the function's
+        /// canonical address, which we export so that pointers to the function compare equal
+        /// everywhere.
+        const CANONICAL_PLT = 1 << 8;
+
+        /// The GOT entry is referenced directly rather than only via our PLT entry, e.g. to load
+        /// the address of a function. Such an entry can't be bound lazily, since it needs to hold
+        /// the function's real address from the start.
+        const GOT_REFERENCE = 1 << 9;
     }
 }
 
 struct AtomicResolutionFlags {
-    value: AtomicU8,
+    value: AtomicU16,
 }
 
 impl AtomicResolutionFlags {
@@ -1112,7 +1846,7 @@ impl AtomicResolutionFlags {
 
     fn new(flags: ResolutionFlags) -> Self {
         Self {
-            value: AtomicU8::new(flags.bits()),
+            value: AtomicU16::new(flags.bits()),
         }
     }
 
@@ -1140,7 +1874,7 @@ impl AtomicResolutionFlags {
 impl Clone for AtomicResolutionFlags {
     fn clone(&self) -> Self {
         Self {
-            value: AtomicU8::new(self.value.load(atomic::Ordering::Relaxed)),
+            value: AtomicU16::new(self.value.load(atomic::Ordering::Relaxed)),
         }
     }
 }
@@ -1161,6 +1895,14 @@ struct DynamicLayoutState<'data> {
 
     verdef_info: Option<VerdefInfo<'data>>,
 
+    /// Whether the object doesn't define any versions, but we're going to need one named after its
+    /// soname for the symbols that we import from it because of `--default-imported-symver`.
+    has_default_version: bool,
+
+    /// Whether the object was marked with `GNU_PROPERTY_1_NEEDED_INDIRECT_EXTERN_ACCESS`, meaning
+    /// that it expects all references to its symbols to go via the GOT.
+    needs_indirect_extern_access: bool,
+
     non_addressable_indexes: NonAddressableIndexes,
 }
 
@@ -1178,6 +1920,10 @@ pub(crate) struct DynamicSymbolDefinition<'data> {
     pub(crate) symbol_id: SymbolId,
     pub(crate) name: &'data [u8],
     pub(crate) hash: u32,
+
+    /// The version that the version script gives the symbol. Only used for symbols defined by
+    /// regular objects.
+    pub(crate) version: u16,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -1186,6 +1932,8 @@ pub(crate) struct Section {
     pub(crate) part_id: PartId,
     /// Size in memory.
     pub(crate) size: u64,
+    /// Space for range-extension thunks that we place after the section's own contents.
+    pub(crate) thunks_size: u64,
     pub(crate) resolution_kind: ResolutionFlags,
     pub(crate) is_writable: bool,
 }
@@ -1201,6 +1949,12 @@ pub(crate) struct GroupLayout<'data> {
 
     pub(crate) eh_frame_start_address: u64,
 
+    /// The address at which this group's entries in .rela.plt start.
+    pub(crate) rela_plt_start_address: u64,
+
+    /// The address at which this group's entries in .plt.got start.
+    pub(crate) plt_got_start_address: u64,
+
     pub(crate) mem_sizes: OutputSectionPartMap<u64>,
     pub(crate) file_sizes: OutputSectionPartMap<usize>,
 }
@@ -1255,6 +2009,10 @@ struct GraphResources<'data, 'scope, S: StorageModel> {
     merged_strings: &'scope OutputSectionMap<MergedStringsSection<'data>>,
 
     has_static_tls: AtomicBool,
+
+    has_unique_symbols: AtomicBool,
+
+    symbol_warnings: &'scope SymbolWarnings<'data>,
 }
 
 struct FinaliseLayoutResources<'scope, 'data, S: StorageModel> {
@@ -1264,6 +2022,11 @@ struct FinaliseLayoutResources<'scope, 'data, S: StorageModel> {
     section_layouts: &'scope OutputSectionMap<OutputRecordLayout>,
     merged_string_start_addresses: &'scope MergedStringStartAddresses,
     merged_strings: &'scope OutputSectionMap<MergedStringsSection<'data>>,
+    plt_layout: PltLayout,
+
+    /// The value of each linker script assignment to a symbol, indexed like
+    /// `ScriptLayout::symbol_assignments`.
+    script_symbol_values: &'scope [u64],
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -1273,9 +2036,9 @@ enum WorkItem {
     /// symbol and process any relocations for that section.
     LoadGlobalSymbol(SymbolId),
 
-    /// A direct reference to a dynamic symbol has been encountered. The symbol should be defined in
-    /// BSS with a copy relocation.
-    ExportCopyRelocation(SymbolId),
+    /// A direct reference to a dynamic symbol has been encountered. The symbol should be defined by
+    /// our output, either in BSS with a copy relocation or, for functions, at a canonical PLT entry.
+    ExportDefinition(SymbolId),
 }
 
 impl WorkItem {
@@ -1286,7 +2049,7 @@ impl WorkItem {
     fn symbol_id(self) -> SymbolId {
         match self {
             WorkItem::LoadGlobalSymbol(s) => s,
-            WorkItem::ExportCopyRelocation(s) => s,
+            WorkItem::ExportDefinition(s) => s,
         }
     }
 }
@@ -1333,8 +2096,8 @@ impl<'data, S: StorageModel> Layout<'data, '_, S> {
     }
 
     pub(crate) fn entry_symbol_address(&self) -> Result<u64> {
-        if self.args().output_kind() == OutputKind::SharedObject {
-            // Shared objects don't have an entry point.
+        if !self.args().output_kind().is_executable() {
+            // Shared objects and relocatable objects don't have an entry point.
             return Ok(0);
         }
         let symbol_id = self
@@ -1356,6 +2119,14 @@ impl<'data, S: StorageModel> Layout<'data, '_, S> {
         Ok(resolution.value())
     }
 
+    /// Returns the value of `__global_pointer$`, if defined.
+    pub(crate) fn global_pointer(&self) -> Option<u64> {
+        let symbol_id = self.symbol_db.global_names.get(&SymbolName::prehashed(
+            GLOBAL_POINTER_SYMBOL_NAME.as_bytes(),
+        ))?;
+        Some(self.merged_symbol_resolution(symbol_id)?.value())
+    }
+
     pub(crate) fn tls_start_address(&self) -> u64 {
         let tdata = self.section_layouts.get(output_section_id::TDATA);
         tdata.mem_offset
@@ -1371,6 +2142,18 @@ impl<'data, S: StorageModel> Layout<'data, '_, S> {
         alignment.align_up(tls_end)
     }
 
+    /// Returns the address that the thread pointer points to in an executable. Offsets of TLS
+    /// variables from the thread pointer are relative to this.
+    pub(crate) fn thread_pointer_address(&self) -> u64 {
+        match self.args().arch() {
+            Architecture::X86_64 => self.tls_end_address(),
+            Architecture::AArch64 => self.tls_start_address_aarch64(),
+            Architecture::RiscV64 => self.tls_start_address(),
+            // The thread pointer is biased so that signed 16 bit offsets can reach 64KiB of TLS.
+            Architecture::PowerPc64 => self.tls_start_address() + 0x7000,
+        }
+    }
+
     /// Returns the memory address of the start of the TLS segment used by the AArch64.
     pub(crate) fn tls_start_address_aarch64(&self) -> u64 {
         let tdata = self.section_layouts.get(output_section_id::TDATA);
@@ -1432,7 +2215,7 @@ impl<'data, S: StorageModel> Layout<'data, '_, S> {
     /// symbol `_GLOBAL_OFFSET_TABLE_`.
     pub(crate) fn got_base(&self) -> u64 {
         let got_layout = self.section_layouts.get(output_section_id::GOT);
-        got_layout.mem_offset
+        got_layout.mem_offset + self.args().arch().got_base_offset()
     }
 
     /// Returns whether we're going to output the .gnu.version section.
@@ -1495,26 +2278,441 @@ fn compute_start_offsets_by_group(
         .collect_vec()
 }
 
-#[tracing::instrument(skip_all, name = "Assign symbol addresses")]
-fn compute_symbols_and_layouts<'data, S: StorageModel>(
-    group_states: Vec<GroupState<'data>>,
-    starting_mem_offsets_by_group: Vec<OutputSectionPartMap<u64>>,
-    per_group_res_writers: &mut [sharded_vec_writer::Shard<Option<Resolution>>],
-    resources: &FinaliseLayoutResources<'_, 'data, S>,
-) -> Result<Vec<GroupLayout<'data>>> {
+/// Returns the address of each section of each file of each group, if it's loaded, as they'd be
+/// assigned by `finalise_layout` with the supplied layout of section parts.
+fn simulate_all_section_addresses(
+    group_states: &[GroupState<'_>],
+    part_layouts: &OutputSectionPartMap<OutputRecordLayout>,
+) -> Vec<Vec<Vec<Option<u64>>>> {
+    let starting_mem_offsets_by_group =
+        compute_start_offsets_by_group(group_states, starting_memory_offsets(part_layouts));
     group_states
-        .into_par_iter()
+        .par_iter()
         .zip(starting_mem_offsets_by_group)
-        .zip(per_group_res_writers)
-        .map(|((state, mut memory_offsets), symbols_out)| {
-            if cfg!(debug_assertions) {
-                let offset_verifier = crate::verification::OffsetVerifier::new(
-                    &memory_offsets,
-                    &state.common.mem_sizes,
-                );
-
-                // Make sure that ignored offsets really aren't used by `finalise_layout` by setting
-                // them to an arbitrary value. If they are used, we'll quickly notice.
+        .map(|(group, mut memory_offsets)| {
+            group
+                .files
+                .iter()
+                .map(|file| match file {
+                    FileLayoutState::Object(object) => {
+                        object.simulate_section_addresses(&mut memory_offsets)
+                    }
+                    _ => Vec::new(),
+                })
+                .collect_vec()
+        })
+        .collect()
+}
+
+/// Deletes bytes from RISC-V code where the compiler has told us that we may, e.g. by turning calls
+/// into jumps that don't need an auipc, then deletes the alignment padding that's no longer needed.
+/// See `riscv64_relaxation`. This changes the sizes of sections, so needs to happen before we add
+/// thunks. As when adding thunks, branch targets that aren't in an input section aren't considered.
+#[tracing::instrument(skip_all, name = "Relax sections")]
+fn relax_sections<'data, S: StorageModel>(
+    group_states: &mut [GroupState<'data>],
+    section_part_sizes: &mut OutputSectionPartMap<u64>,
+    output_sections: &OutputSections,
+    symbol_db: &SymbolDb<'data, S>,
+    symbol_resolution_flags: &[ResolutionFlags],
+) -> Result {
+    let args = symbol_db.args;
+    // Relocatable output keeps its relocations, including R_RISCV_ALIGN, for the final link to act
+    // on.
+    if args.arch() != Architecture::RiscV64 || args.output_kind() == OutputKind::RelocatableObject {
+        return Ok(());
+    }
+    // Alignment padding needs deleting even if we're not allowed to relax anything else.
+    let mut phase = if args.relax.code {
+        Phase::Shrink
+    } else {
+        Phase::Align
+    };
+    let relax_gp = args.relax.gp && is_global_pointer_ours(symbol_db, symbol_resolution_flags);
+    loop {
+        let (part_layouts, _) = layout_section_parts(section_part_sizes, output_sections)?;
+        let mut margin = 0;
+        part_layouts.map(|part_id, rec| {
+            if rec.mem_size > 0 {
+                margin = margin.max(part_id.alignment().value());
+            }
+        });
+        let section_addresses = simulate_all_section_addresses(group_states, &part_layouts);
+        let planner = RelaxationPlanner {
+            group_states,
+            section_addresses: &section_addresses,
+            symbol_db,
+            options: PlanOptions {
+                phase,
+                absolute_addresses: !args.output_kind().is_relocatable(),
+                compressed: false,
+                margin,
+                global_pointer: relax_gp.then(|| {
+                    layout_sections(&part_layouts)
+                        .get(output_section_id::DATA)
+                        .mem_offset
+                        + linker_utils::riscv64::GLOBAL_POINTER_OFFSET
+                }),
+            },
+        };
+        let new_plans = group_states
+            .par_iter()
+            .map(|group| {
+                let mut found = Vec::new();
+                for (file_index, file) in group.files.iter().enumerate() {
+                    if let FileLayoutState::Object(object) = file {
+                        object.plan_relaxations(file_index, &planner, &mut found)?;
+                    }
+                }
+                Ok(found)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut changed = false;
+        for (group, found) in group_states.iter_mut().zip(new_plans) {
+            for (file_index, section_index, plan) in found {
+                let FileLayoutState::Object(object) = &mut group.files[file_index] else {
+                    unreachable!();
+                };
+                let input_size = object
+                    .object
+                    .section_size(object.object.section(section_index)?)?;
+                let SectionSlot::Loaded(section) = &mut object.sections[section_index.0] else {
+                    unreachable!();
+                };
+                let old_capacity = section.capacity();
+                section.size = input_size - plan.total_deleted();
+                let new_capacity = section.capacity();
+                for sizes in [&mut group.common.mem_sizes, &mut *section_part_sizes] {
+                    let size = sizes.get_mut(section.part_id);
+                    *size = *size - old_capacity + new_capacity;
+                }
+                object.relaxations.insert(section_index, plan);
+                changed = true;
+            }
+        }
+        // Padding is deleted in a single pass, since deleting padding doesn't affect how much of
+        // the padding that follows it can be deleted.
+        if phase == Phase::Align {
+            return Ok(());
+        }
+        if !changed {
+            phase = Phase::Align;
+        }
+    }
+}
+
+/// What we need in order to estimate where code and the things it refers to will end up while we're
+/// deciding what to relax.
+/// Returns whether `__global_pointer$` is the one that we define at the start of the data, rather
+/// than, say, one from a linker script, and whether anything references it. The startup code sets
+/// gp if it references the symbol, so if nothing does, then gp can't be relied on.
+fn is_global_pointer_ours<S: StorageModel>(
+    symbol_db: &SymbolDb<S>,
+    symbol_resolution_flags: &[ResolutionFlags],
+) -> bool {
+    symbol_db
+        .global_names
+        .get(&SymbolName::prehashed(
+            GLOBAL_POINTER_SYMBOL_NAME.as_bytes(),
+        ))
+        .map(|symbol_id| symbol_db.definition(symbol_id))
+        .is_some_and(|symbol_id| {
+            symbol_db.file_id_for_symbol(symbol_id) == PRELUDE_FILE_ID
+                && !symbol_resolution_flags[symbol_id.as_usize()].is_empty()
+        })
+}
+
+struct RelaxationPlanner<'a, 'data, S: StorageModel> {
+    group_states: &'a [GroupState<'data>],
+
+    /// The address of each section of each file of each group, if it's loaded.
+    section_addresses: &'a [Vec<Vec<Option<u64>>>],
+
+    symbol_db: &'a SymbolDb<'data, S>,
+    options: PlanOptions,
+}
+
+impl<S: StorageModel> RelaxationPlanner<'_, '_, S> {
+    /// Returns the address that `rel`, which is in `object`, refers to. Returns None if the symbol
+    /// isn't defined in an input section or if it mightn't be referenced directly, e.g. because
+    /// it's dynamic.
+    fn target_address(
+        &self,
+        object: &ObjectLayoutState,
+        rel: &Rela64<LittleEndian>,
+    ) -> Result<Option<u64>> {
+        let e = LittleEndian;
+        let Some(symbol_index) = rel.symbol(e, false) else {
+            return Ok(None);
+        };
+        let symbol_id = self
+            .symbol_db
+            .definition(object.symbol_id_range.input_to_id(symbol_index));
+        let value_flags = self.symbol_db.local_symbol_value_flags(symbol_id);
+        if !value_flags.contains(ValueFlags::CAN_BYPASS_GOT)
+            || value_flags.intersects(ValueFlags::DYNAMIC | ValueFlags::IFUNC)
+        {
+            return Ok(None);
+        }
+        let file_id = self.symbol_db.file_id_for_symbol(symbol_id);
+        let FileLayoutState::Object(target_object) =
+            &self.group_states[file_id.group()].files[file_id.file()]
+        else {
+            return Ok(None);
+        };
+        let symbol_index = symbol_id.to_input(target_object.symbol_id_range);
+        let symbol = target_object.object.symbol(symbol_index)?;
+        let offset = symbol.st_value(e).wrapping_add(rel.r_addend.get(e) as u64);
+        if symbol.is_absolute(e) {
+            return Ok(Some(offset));
+        }
+        let Some(section_index) = target_object.object.symbol_section(symbol, symbol_index)? else {
+            return Ok(None);
+        };
+        let Some(section_address) =
+            self.section_addresses[file_id.group()][file_id.file()][section_index.0]
+        else {
+            return Ok(None);
+        };
+        Ok(Some(
+            section_address
+                + target_object
+                    .relaxations
+                    .output_offset(section_index, offset),
+        ))
+    }
+}
+
+/// The targets of the relocations of a section that we're planning relaxations for.
+struct SectionTargets<'a, 'p, 'data, S: StorageModel> {
+    planner: &'a RelaxationPlanner<'p, 'data, S>,
+    object: &'a ObjectLayoutState<'data>,
+    section_index: object::SectionIndex,
+}
+
+impl<S: StorageModel> RelocationTargets for SectionTargets<'_, '_, '_, S> {
+    fn address(&self, rel: &Rela64<LittleEndian>) -> Result<Option<u64>> {
+        self.planner.target_address(self.object, rel)
+    }
+
+    fn label_offset(&self, rel: &Rela64<LittleEndian>) -> Result<Option<u64>> {
+        let e = LittleEndian;
+        let Some(symbol_index) = rel.symbol(e, false) else {
+            return Ok(None);
+        };
+        let symbol = self.object.object.symbol(symbol_index)?;
+        let section_index = self.object.object.symbol_section(symbol, symbol_index)?;
+        Ok((section_index == Some(self.section_index)).then(|| symbol.st_value(e)))
+    }
+}
+
+/// Adds thunks after sections: range-extension thunks for branches that won't be able to reach their
+/// targets and patches that work around CPU errata. Both make sections bigger, which moves code
+/// around, so we repeat this until no more thunks are needed. The addresses that we use here are
+/// computed the same way as the final addresses, but before symbol resolutions exist, so branch
+/// targets that aren't in an input section, e.g. symbols defined by linker scripts, aren't
+/// considered.
+#[tracing::instrument(skip_all, name = "Add thunks")]
+fn add_thunks<'data, S: StorageModel>(
+    group_states: &mut [GroupState<'data>],
+    section_part_sizes: &mut OutputSectionPartMap<u64>,
+    output_sections: &OutputSections,
+    symbol_db: &SymbolDb<'data, S>,
+    symbol_resolution_flags: &[ResolutionFlags],
+) -> Result {
+    // Branches in relocatable output aren't resolved until the final link, which is also where any
+    // thunks will be added.
+    if symbol_db.args.output_kind() == OutputKind::RelocatableObject {
+        return Ok(());
+    }
+    let thunk_info = symbol_db.args.arch().thunk_info();
+    let errata_fixes = ErrataFixes::from_args(symbol_db.args);
+    if thunk_info.is_none() && errata_fixes.is_none() {
+        return Ok(());
+    }
+    loop {
+        let (part_layouts, _) = layout_section_parts(section_part_sizes, output_sections)?;
+
+        let mut code_range = (u64::MAX, 0);
+        let mut plt_range = (u64::MAX, 0);
+        part_layouts.map(|part_id, rec| {
+            if rec.mem_size == 0 {
+                return;
+            }
+            let section_id = part_id.output_section_id();
+            let range = if section_id == part_id::PLT_GOT.output_section_id()
+                || section_id == part_id::PLT_SEC.output_section_id()
+            {
+                &mut plt_range
+            } else if output_sections
+                .section_flags(section_id)
+                .contains(shf::EXECINSTR)
+            {
+                &mut code_range
+            } else {
+                return;
+            };
+            range.0 = range.0.min(rec.mem_offset);
+            range.1 = range.1.max(rec.mem_offset + rec.mem_size);
+        });
+        let all_code_start = code_range.0.min(plt_range.0);
+        let all_code_end = code_range.1.max(plt_range.1);
+        let check_branches = thunk_info.is_some_and(|info| {
+            all_code_end.saturating_sub(all_code_start) >= info.min_branch_range
+        });
+        if !check_branches && errata_fixes.is_none() {
+            return Ok(());
+        }
+
+        let section_addresses = simulate_all_section_addresses(group_states, &part_layouts);
+        let planner = ThunkPlanner {
+            group_states,
+            section_addresses: &section_addresses,
+            plt_range: plt_range.0..plt_range.1,
+            symbol_db,
+            symbol_resolution_flags,
+            check_branches,
+            errata_fixes,
+        };
+        let new_thunks = group_states
+            .par_iter()
+            .map(|group| {
+                let mut found = Vec::new();
+                for (file_index, file) in group.files.iter().enumerate() {
+                    if let FileLayoutState::Object(object) = file {
+                        object.find_new_thunks::<S>(file_index, &planner, &mut found)?;
+                    }
+                }
+                Ok(found)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut added_thunks = false;
+        for (group, found) in group_states.iter_mut().zip(new_thunks) {
+            for (file_index, thunk) in found {
+                let FileLayoutState::Object(object) = &mut group.files[file_index] else {
+                    unreachable!();
+                };
+                let section_index = match thunk {
+                    NewThunk::Branch(target) => target.section_index,
+                    NewThunk::MovedInstruction { section_index, .. } => section_index,
+                };
+                let SectionSlot::Loaded(section) = &mut object.sections[section_index.0] else {
+                    unreachable!();
+                };
+                let old_capacity = section.capacity();
+                let offset = section.thunks_offset() + section.thunks_size;
+                match thunk {
+                    NewThunk::Branch(target) => {
+                        object.thunks.insert(target, offset);
+                        section.thunks_size += thunk_info.map_or(0, |info| info.size);
+                    }
+                    NewThunk::MovedInstruction {
+                        instruction_offset, ..
+                    } => {
+                        object.thunks.insert_moved_instruction(
+                            section_index,
+                            instruction_offset,
+                            offset,
+                        );
+                        section.thunks_size += aarch64_errata::PATCH_SIZE;
+                    }
+                }
+                let growth = section.capacity() - old_capacity;
+                group.common.mem_sizes.increment(section.part_id, growth);
+                section_part_sizes.increment(section.part_id, growth);
+                added_thunks = true;
+            }
+        }
+        if !added_thunks {
+            return Ok(());
+        }
+    }
+}
+
+/// What we need in order to estimate where branches and their targets will end up while we're
+/// deciding which thunks to add.
+struct ThunkPlanner<'a, 'data, S: StorageModel> {
+    group_states: &'a [GroupState<'data>],
+
+    /// The address of each section of each file of each group, if it's loaded.
+    section_addresses: &'a [Vec<Vec<Option<u64>>>],
+
+    /// The range of addresses that PLT entries might be at.
+    plt_range: std::ops::Range<u64>,
+
+    symbol_db: &'a SymbolDb<'data, S>,
+    symbol_resolution_flags: &'a [ResolutionFlags],
+
+    /// Whether code is spread out enough that some branches might not reach their targets.
+    check_branches: bool,
+
+    errata_fixes: Option<ErrataFixes>,
+}
+
+impl<S: StorageModel> ThunkPlanner<'_, '_, S> {
+    /// Returns whether a branch at `place` to the supplied symbol might not be able to reach it. If
+    /// the symbol has a PLT entry, then we don't know yet which entry it'll be given, so we assume
+    /// that the branch might reach any of them.
+    fn might_be_out_of_range(
+        &self,
+        place: u64,
+        symbol_id: SymbolId,
+        addend: i64,
+        range: u64,
+    ) -> Result<bool> {
+        if self.symbol_resolution_flags[symbol_id.as_usize()].contains(ResolutionFlags::PLT)
+            && !self.plt_range.is_empty()
+            && (!is_in_range(place, self.plt_range.start, range)
+                || !is_in_range(place, self.plt_range.end, range))
+        {
+            return Ok(true);
+        }
+
+        let file_id = self.symbol_db.file_id_for_symbol(symbol_id);
+        let FileLayoutState::Object(object) =
+            &self.group_states[file_id.group()].files[file_id.file()]
+        else {
+            return Ok(false);
+        };
+        let symbol_index = symbol_id.to_input(object.symbol_id_range);
+        let symbol = object.object.symbol(symbol_index)?;
+        let Some(section_index) = object.object.symbol_section(symbol, symbol_index)? else {
+            return Ok(false);
+        };
+        let Some(section_address) =
+            self.section_addresses[file_id.group()][file_id.file()][section_index.0]
+        else {
+            return Ok(false);
+        };
+        let target = section_address
+            .wrapping_add(symbol.st_value(LittleEndian))
+            .wrapping_add(addend as u64);
+        Ok(!is_in_range(place, target, range))
+    }
+}
+
+#[tracing::instrument(skip_all, name = "Assign symbol addresses")]
+fn compute_symbols_and_layouts<'data, S: StorageModel>(
+    group_states: Vec<GroupState<'data>>,
+    starting_mem_offsets_by_group: Vec<OutputSectionPartMap<u64>>,
+    per_group_res_writers: &mut [sharded_vec_writer::Shard<Option<Resolution>>],
+    resources: &FinaliseLayoutResources<'_, 'data, S>,
+) -> Result<Vec<GroupLayout<'data>>> {
+    group_states
+        .into_par_iter()
+        .zip(starting_mem_offsets_by_group)
+        .zip(per_group_res_writers)
+        .map(|((state, mut memory_offsets), symbols_out)| {
+            if cfg!(debug_assertions) {
+                let offset_verifier = crate::verification::OffsetVerifier::new(
+                    &memory_offsets,
+                    &state.common.mem_sizes,
+                );
+
+                // Make sure that ignored offsets really aren't used by `finalise_layout` by setting
+                // them to an arbitrary value. If they are used, we'll quickly notice.
                 crate::verification::clear_ignored(&mut memory_offsets);
 
                 let layout = state.finalise_layout(&mut memory_offsets, symbols_out, resources)?;
@@ -1535,6 +2733,7 @@ fn compute_symbols_and_layouts<'data, S: StorageModel>(
 #[tracing::instrument(skip_all, name = "Compute segment layouts")]
 fn compute_segment_layout(
     section_layouts: &OutputSectionMap<OutputRecordLayout>,
+    section_load_addresses: &OutputSectionMap<u64>,
     output_sections: &OutputSections,
     header_info: &HeaderInfo,
 ) -> Result<SegmentLayouts> {
@@ -1617,8 +2816,11 @@ fn compute_segment_layout(
                     );
                 } else {
                     // All segments should only cover sections that are allocated and have a non-zero address.
+                    // A linker script can place sections at address zero.
                     ensure!(
-                        part.mem_offset != 0 || section_id == FILE_HEADER,
+                        part.mem_offset != 0
+                            || section_id == FILE_HEADER
+                            || output_sections.script_layout,
                         "Missing memory offset for section `{}` present in a program segment.",
                         output_sections.name(section_id)
                     );
@@ -1628,6 +2830,14 @@ fn compute_segment_layout(
                          segment.",
                         output_sections.name(section_id)
                     );
+                    // With `-n` and `-N`, the headers aren't loaded.
+                    if output_sections.magic != Magic::Demand
+                        && (section_id == FILE_HEADER
+                            || section_id == output_section_id::PROGRAM_HEADERS
+                            || section_id == output_section_id::SECTION_HEADERS)
+                    {
+                        continue;
+                    }
                     for (_, rec) in &mut active_records {
                         rec.file_start = rec.file_start.min(part.file_offset);
                         rec.mem_start = rec.mem_start.min(part.mem_offset);
@@ -1640,33 +2850,755 @@ fn compute_segment_layout(
         }
     }
 
-    complete.sort_by_key(|r| r.segment_id);
-    assert_eq!(complete.len(), MAX_SEGMENTS);
-    let mut tls_start_address = None;
-    let segments = header_info
-        .active_segment_ids
-        .iter()
-        .map(|&id| {
-            let r = &complete[id.as_usize()];
-            if id == program_segments::TLS {
-                tls_start_address = Some(r.mem_start);
-            }
-            SegmentLayout {
-                id,
-                sizes: OutputRecordLayout {
-                    file_size: r.file_end - r.file_start,
-                    mem_size: r.mem_end - r.mem_start,
-                    alignment: r.alignment,
-                    file_offset: r.file_start,
-                    mem_offset: r.mem_start,
-                },
-            }
-        })
-        .collect();
-    Ok(SegmentLayouts {
-        segments,
-        tls_start_address,
-    })
+    complete.sort_by_key(|r| r.segment_id);
+    assert_eq!(complete.len(), MAX_SEGMENTS);
+    if output_sections.magic != Magic::Demand {
+        // Our single loadable segment covers what would otherwise be in each of them.
+        for id in [LOAD_EXEC, LOAD_RW] {
+            let (file_start, file_end, mem_start, mem_end, alignment) = {
+                let r = &complete[id.as_usize()];
+                (
+                    r.file_start,
+                    r.file_end,
+                    r.mem_start,
+                    r.mem_end,
+                    r.alignment,
+                )
+            };
+            let load = &mut complete[LOAD_RO.as_usize()];
+            load.file_start = load.file_start.min(file_start);
+            load.file_end = load.file_end.max(file_end);
+            load.mem_start = load.mem_start.min(mem_start);
+            load.mem_end = load.mem_end.max(mem_end);
+            load.alignment = load.alignment.max(alignment);
+        }
+    }
+    if output_sections.relro {
+        // The loader rounds the end of RELRO to a page boundary, so we cover the padding that we
+        // left after it.
+        let relro = &mut complete[GNU_RELRO.as_usize()];
+        let padded = output_sections.common_page_size.align_up(relro.mem_end);
+        relro.file_end += (padded - relro.mem_end) as usize;
+        relro.mem_end = padded;
+    }
+    let mut tls_start_address = None;
+    let segments: Vec<SegmentLayout> = header_info
+        .active_segment_ids
+        .iter()
+        .map(|&id| {
+            let r = &complete[id.as_usize()];
+            if id == program_segments::TLS {
+                tls_start_address = Some(r.mem_start);
+            }
+            SegmentLayout {
+                segment_type: id.segment_type(),
+                segment_flags: id.segment_flags(),
+                physical_address: r.mem_start,
+                sizes: OutputRecordLayout {
+                    file_size: r.file_end - r.file_start,
+                    mem_size: r.mem_end - r.mem_start,
+                    alignment: r.alignment,
+                    file_offset: r.file_start,
+                    mem_offset: r.mem_start,
+                },
+            }
+        })
+        .collect();
+    let segments = if output_sections.script_program_headers.is_empty() {
+        segments
+    } else {
+        compute_script_segment_layouts(
+            section_layouts,
+            section_load_addresses,
+            output_sections,
+            &segments,
+        )?
+    };
+    Ok(SegmentLayouts {
+        segments,
+        tls_start_address,
+    })
+}
+
+/// Computes the layout of the program headers that a linker script defined with `PHDRS`. Each
+/// covers the sections that were assigned to it. Those without any sections, but which are of a
+/// type that we'd normally write, e.g. `PT_INTERP`, cover whatever ours would have covered.
+fn compute_script_segment_layouts(
+    section_layouts: &OutputSectionMap<OutputRecordLayout>,
+    section_load_addresses: &OutputSectionMap<u64>,
+    output_sections: &OutputSections,
+    built_in_segments: &[SegmentLayout],
+) -> Result<Vec<SegmentLayout>> {
+    use output_section_id::OrderEvent;
+
+    let headers = &output_sections.script_program_headers;
+    let mut layouts: Vec<Option<OutputRecordLayout>> = vec![None; headers.len()];
+    // The offset from the addresses of the sections in each header to their load addresses.
+    let mut load_offsets = vec![0; headers.len()];
+    let mut flags = vec![object::elf::PF_R; headers.len()];
+    for event in output_sections.sections_and_segments_events() {
+        let OrderEvent::Section(section_id) = event else {
+            continue;
+        };
+        if output_sections
+            .output_index_of_section(section_id)
+            .is_none()
+        {
+            continue;
+        }
+        let info = output_sections.output_info(section_id);
+        let part = section_layouts.get(section_id);
+        let is_header = section_id == FILE_HEADER
+            || section_id == output_section_id::PROGRAM_HEADERS
+            || section_id == output_section_id::SECTION_HEADERS;
+        if output_sections.load_segment_for(section_id).is_none()
+            && info.section_flags.contains(shf::ALLOC)
+            && part.mem_size > 0
+            && !is_header
+        {
+            bail!(
+                "Section `{}` isn't in any PT_LOAD program header from PHDRS",
+                output_sections.display_name(section_id)
+            );
+        }
+        let load_offset = section_load_addresses
+            .get(section_id)
+            .wrapping_sub(part.mem_offset);
+        for &index in &info.script_program_headers {
+            if info.section_flags.contains(shf::WRITE) {
+                flags[index] |= object::elf::PF_W;
+            }
+            if info.section_flags.contains(shf::EXECINSTR) {
+                flags[index] |= object::elf::PF_X;
+            }
+            let Some(layout) = &mut layouts[index] else {
+                layouts[index] = Some(*part);
+                load_offsets[index] = load_offset;
+                continue;
+            };
+            if headers[index].segment_type == object::elf::PT_LOAD
+                && part.file_size > 0
+                && load_offset != load_offsets[index]
+            {
+                bail!(
+                    "Section `{}` can't be put in program header `{}`, since its load address \
+                     isn't contiguous with those of the other sections in it",
+                    output_sections.display_name(section_id),
+                    headers[index].name
+                );
+            }
+            // A loadable segment is mapped as a single range, so its sections need to be at the
+            // same offsets from each other in memory as in the file.
+            if headers[index].segment_type == object::elf::PT_LOAD
+                && output_sections.has_data_in_file(section_id)
+                && part.mem_offset.wrapping_sub(part.file_offset as u64)
+                    != layout.mem_offset.wrapping_sub(layout.file_offset as u64)
+            {
+                bail!(
+                    "Section `{}` can't be put in program header `{}`, since it isn't contiguous \
+                     with the other sections in it",
+                    output_sections.display_name(section_id),
+                    headers[index].name
+                );
+            }
+            let file_end =
+                (layout.file_offset + layout.file_size).max(part.file_offset + part.file_size);
+            let mem_end =
+                (layout.mem_offset + layout.mem_size).max(part.mem_offset + part.mem_size);
+            layout.file_offset = layout.file_offset.min(part.file_offset);
+            layout.mem_offset = layout.mem_offset.min(part.mem_offset);
+            layout.file_size = file_end - layout.file_offset;
+            layout.mem_size = mem_end - layout.mem_offset;
+            layout.alignment = layout.alignment.max(part.alignment);
+        }
+    }
+
+    Ok(headers
+        .iter()
+        .zip(layouts)
+        .zip(flags)
+        .zip(load_offsets)
+        .map(|(((header, layout), flags), load_offset)| {
+            let built_in = built_in_segments.iter().find(|segment| {
+                segment.segment_type == header.segment_type
+                    && header.segment_type != object::elf::PT_LOAD
+            });
+            let (sizes, segment_flags) = match (layout, built_in) {
+                (Some(sizes), _) => (sizes, flags),
+                (None, Some(built_in)) => (built_in.sizes, built_in.segment_flags),
+                (None, None) => (OutputRecordLayout::default(), flags),
+            };
+            SegmentLayout {
+                segment_type: header.segment_type,
+                segment_flags: header.flags.unwrap_or(segment_flags),
+                physical_address: header
+                    .physical_address
+                    .unwrap_or(sizes.mem_offset.wrapping_add(load_offset)),
+                sizes,
+            }
+        })
+        .collect())
+}
+
+/// Works out the address that each section is loaded from. This is the same as its address, unless
+/// a linker script puts it in a different memory region with `AT>`. As with GNU ld, a section
+/// without `AT>` that follows another in the same memory region keeps the same offset between its
+/// address and its load address, so that e.g. `.bss` stays in the same segment as `.data`. Members
+/// of an overlay other than the first are loaded straight after the previous member. Also checks
+/// that the sections put in each memory region fit in it.
+fn compute_load_addresses(
+    section_layouts: &OutputSectionMap<OutputRecordLayout>,
+    output_sections: &OutputSections,
+) -> Result<OutputSectionMap<u64>> {
+    use output_section_id::OrderEvent;
+
+    let mut load_addresses = OutputSectionMap::with_size(section_layouts.len());
+    section_layouts.for_each(|section_id, layout| {
+        *load_addresses.get_mut(section_id) = layout.mem_offset;
+    });
+    let regions = output_sections.memory_regions;
+    if regions.is_empty() && !output_sections.script_layout {
+        return Ok(load_addresses);
+    }
+
+    // The end of what's been placed or loaded in each region.
+    let mut region_ends = regions.iter().map(|region| region.origin).collect_vec();
+    // The first section that doesn't fit in each region.
+    let mut overflowing_sections = vec![None; regions.len()];
+    // The offset from address to load address of the last section in each region, together with
+    // the region that the section is loaded from. The last entry is for outside any region.
+    let mut load_offsets = vec![None; regions.len() + 1];
+    // Where the previous section's load image ends and the region that it's loaded from, which is
+    // where the next member of an overlay is loaded.
+    let mut previous_load_end: Option<(u64, Option<usize>)> = None;
+    let mut current_region = None;
+    for event in output_sections.sections_and_segments_events() {
+        let OrderEvent::Section(section_id) = event else {
+            continue;
+        };
+        if output_section_id::is_header_section(section_id)
+            || !output_sections
+                .section_flags(section_id)
+                .contains(shf::ALLOC)
+            || output_sections
+                .output_index_of_section(section_id)
+                .is_none()
+        {
+            continue;
+        }
+        let layout = section_layouts.get(section_id);
+        let placement = output_sections
+            .output_info(section_id)
+            .script_placement
+            .as_ref();
+        if let Some(placement) = placement {
+            current_region = placement.region;
+        }
+        let address = layout.mem_offset;
+        let explicit_load_address = placement
+            .and_then(|p| p.load_address)
+            .map(|expression| {
+                let sections = ScriptSections {
+                    output_sections,
+                    section_layouts,
+                    load_addresses: &load_addresses,
+                };
+                expression
+                    .evaluate(&EvaluationContext {
+                        location: address,
+                        layout: Some(&sections),
+                        ..sections.base_context()
+                    })
+                    .with_context(|| {
+                        format!(
+                            "Failed to evaluate load address of `{}`",
+                            output_sections.display_name(section_id)
+                        )
+                    })
+            })
+            .transpose()?;
+        let offsets_index = current_region.unwrap_or(regions.len());
+        let (load_address, load_region) =
+            match (explicit_load_address, placement.and_then(|p| p.load_region)) {
+                (Some(load_address), _) => (load_address, None),
+                (None, Some(load_region)) => (
+                    layout.alignment.align_up(region_ends[load_region]),
+                    Some(load_region),
+                ),
+                (None, None) if placement.is_some_and(|p| p.shares_address) => {
+                    previous_load_end.unwrap_or((address, None))
+                }
+                (None, None) if placement.is_some_and(|p| p.is_fixed()) => (address, None),
+                (None, None) => load_offsets[offsets_index]
+                    .map_or((address, None), |(offset, region)| {
+                        (address.wrapping_add(offset), region)
+                    }),
+            };
+        load_offsets[offsets_index] = Some((load_address.wrapping_sub(address), load_region));
+        previous_load_end = Some((load_address + layout.mem_size, load_region));
+        *load_addresses.get_mut(section_id) = load_address;
+
+        let mut use_region = |region: usize, start: u64, size: u64| -> Result {
+            let region_info = &regions[region];
+            if start < region_info.origin {
+                bail!(
+                    "Section `{}` at 0x{start:x} is before the start of memory region `{}` at \
+                     0x{:x}",
+                    output_sections.display_name(section_id),
+                    region_info.name,
+                    region_info.origin
+                );
+            }
+            let end = start + size;
+            region_ends[region] = region_ends[region].max(end);
+            if end - region_info.origin > region_info.length {
+                overflowing_sections[region].get_or_insert(section_id);
+            }
+            Ok(())
+        };
+        if let Some(region) = current_region {
+            use_region(region, address, layout.mem_size)?;
+        }
+        if let Some(region) = load_region.filter(|_| layout.file_size > 0) {
+            use_region(region, load_address, layout.file_size as u64)?;
+        }
+    }
+
+    let overflows = regions
+        .iter()
+        .zip(&region_ends)
+        .zip(overflowing_sections)
+        .filter_map(|((region, end), section_id)| {
+            Some(format!(
+                "Section `{}` doesn't fit in memory region `{}`, which is overflowed by {} bytes",
+                output_sections.display_name(section_id?),
+                region.name,
+                end - region.origin - region.length
+            ))
+        })
+        .collect_vec();
+    if !overflows.is_empty() {
+        bail!(
+            "{}\n\n{}",
+            overflows.join("\n"),
+            memory_usage_summary(regions, &region_ends)
+        );
+    }
+    Ok(load_addresses)
+}
+
+/// Returns a table of how much of each memory region is used, in the same format as GNU ld's
+/// `--print-memory-usage`.
+fn memory_usage_summary(regions: &[MemoryRegion], region_ends: &[u64]) -> String {
+    let mut out = "Memory region         Used Size  Region Size  %age Used".to_owned();
+    for (region, end) in regions.iter().zip(region_ends) {
+        let used = end - region.origin;
+        let percentage = if region.length == 0 {
+            0.0
+        } else {
+            used as f64 * 100.0 / region.length as f64
+        };
+        out.push_str(&format!(
+            "\n{:>16}: {:>12} B {:>10} B {percentage:>9.2}%",
+            region.name, used, region.length
+        ));
+    }
+    out
+}
+
+/// The values of the symbols assigned to by linker scripts and `--defsym`, indexed like
+/// `ScriptLayout::symbol_assignments`.
+#[derive(Default)]
+struct ScriptSymbolValues {
+    values: Vec<u64>,
+
+    /// Whether each value depends on the address of a symbol defined by an input file. We don't
+    /// know those addresses until symbol resolutions have been computed, so such values are
+    /// placeholders until `finalise_script_symbols` evaluates them again.
+    uses_input_symbols: Vec<bool>,
+}
+
+/// Evaluates the assignments to symbols in linker scripts, in the order that they appear, and
+/// returns the value of each. This happens once we know where sections go, since that's what `.`,
+/// `ADDR(...)` and `SIZEOF(...)` refer to. `is_loaded` says whether we're loading the file with
+/// the supplied ID. Symbols from input files can only be used once `resolutions` are available,
+/// which is also when we check `ASSERT`s. `section_locations` gives the values of `.` within
+/// output sections.
+#[tracing::instrument(skip_all, name = "Evaluate linker script symbols")]
+fn evaluate_script_symbols<'data, S: StorageModel>(
+    script_layout: &ScriptLayout,
+    symbol_db: &SymbolDb<'data, S>,
+    is_loaded: &dyn Fn(FileId) -> bool,
+    resolutions: Option<&[Option<Resolution>]>,
+    sections: ScriptSections<'_, 'data>,
+    section_locations: &OutputSectionMap<SectionLocations>,
+) -> Result<ScriptSymbolValues> {
+    let assignments = &script_layout.symbol_assignments;
+    if assignments.is_empty() && script_layout.assertions.is_empty() {
+        return Ok(ScriptSymbolValues::default());
+    }
+    let mut lookup = ScriptLayoutLookup {
+        symbol_db,
+        is_loaded,
+        resolutions,
+        sections,
+        values: HashMap::new(),
+        uses_input_symbols: Cell::new(false),
+    };
+    let base_context = sections.base_context();
+
+    // The value of `.` before each command in `SECTIONS`, then after the last one.
+    let mut locations = Vec::with_capacity(script_layout.sections.len() + 1);
+    let mut location = 0;
+    let replaces_default_layout = script_layout.replaces_default_layout();
+    for (index, command) in script_layout.sections.iter().enumerate() {
+        // Commands that are moved by `INSERT` start wherever their first section ended up, or
+        // next to the section that they were inserted relative to if none of theirs are output.
+        if let Some(insertion) = script_layout
+            .insertion_for(index)
+            .filter(|insertion| insertion.commands.start == index)
+        {
+            location = script_layout.sections[insertion.commands.clone()]
+                .iter()
+                .find_map(|command| match command {
+                    SectionsCommand::OutputSection(output) => {
+                        lookup.section(&output.name).map(|(address, _)| address)
+                    }
+                    SectionsCommand::SetLocation(_) => None,
+                })
+                .or_else(|| {
+                    lookup.section(&insertion.anchor).map(|(address, size)| {
+                        if insertion.after {
+                            address + size
+                        } else {
+                            address
+                        }
+                    })
+                })
+                .unwrap_or(location);
+        }
+        locations.push(location);
+        match command {
+            // Without a layout of its own, a script's assignments to `.` are ignored.
+            SectionsCommand::SetLocation(_) if !replaces_default_layout => {}
+            SectionsCommand::SetLocation(value) => {
+                location = value.evaluate(&EvaluationContext {
+                    location,
+                    ..base_context
+                })?;
+            }
+            SectionsCommand::OutputSection(output) => {
+                if let Some((address, size)) = lookup.section(&output.name) {
+                    // After an overlay, `.` is the end of its largest member.
+                    location = if output.shares_address {
+                        location.max(address + size)
+                    } else {
+                        address + size
+                    };
+                }
+            }
+        }
+    }
+    locations.push(location);
+
+    let mut values = ScriptSymbolValues {
+        values: Vec::with_capacity(assignments.len()),
+        uses_input_symbols: Vec::with_capacity(assignments.len()),
+    };
+    for assignment in assignments {
+        let location = script_location(
+            assignment.position,
+            script_layout,
+            &locations,
+            &sections,
+            section_locations,
+        );
+        lookup.uses_input_symbols.set(false);
+        let result = assignment.value.evaluate(&EvaluationContext {
+            location,
+            layout: Some(&lookup),
+            ..base_context
+        });
+        let uses_input_symbols = lookup.uses_input_symbols.get();
+        let value = match result {
+            Ok(value) => value,
+            // Input symbols are zero until we know their addresses, which might have made the
+            // expression invalid, e.g. by dividing by zero. We'll find out when we try again.
+            Err(_) if uses_input_symbols && resolutions.is_none() => 0,
+            Err(error) => {
+                return Err(error.context(format!(
+                    "Failed to evaluate linker script assignment to `{}`",
+                    assignment.name
+                )))
+            }
+        };
+        lookup
+            .values
+            .insert(&assignment.name, (value, uses_input_symbols));
+        values.values.push(value);
+        values.uses_input_symbols.push(uses_input_symbols);
+    }
+
+    if resolutions.is_some() {
+        for assertion in &script_layout.assertions {
+            let location = script_location(
+                assertion.position,
+                script_layout,
+                &locations,
+                &sections,
+                section_locations,
+            );
+            let condition = assertion
+                .condition
+                .evaluate(&EvaluationContext {
+                    location,
+                    layout: Some(&lookup),
+                    ..base_context
+                })
+                .with_context(|| {
+                    format!(
+                        "Failed to evaluate linker script ASSERT with message `{}`",
+                        assertion.message
+                    )
+                })?;
+            if condition == 0 {
+                bail!("Linker script assertion failed: {}", assertion.message);
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Returns the value of `.` at the supplied position in a linker script. `locations` is its value
+/// before each command in `SECTIONS`.
+fn script_location(
+    position: AssignmentPosition,
+    script_layout: &ScriptLayout,
+    locations: &[u64],
+    sections: &ScriptSections,
+    section_locations: &OutputSectionMap<SectionLocations>,
+) -> u64 {
+    match position {
+        AssignmentPosition::TopLevel => 0,
+        AssignmentPosition::BeforeCommand(index) => locations[index],
+        AssignmentPosition::InOutputSection {
+            index,
+            at_end,
+            location_index,
+        } => {
+            let name = match &script_layout.sections[index] {
+                SectionsCommand::OutputSection(output) => Some(output.name.as_str()),
+                SectionsCommand::SetLocation(_) => None,
+            };
+            let Some(section_id) = name.and_then(|name| sections.output_section_id(name)) else {
+                // If the section is empty, so isn't being output, symbols in it get the location
+                // that it would have started at.
+                return locations[index];
+            };
+            let layout = sections.section_layouts.get(section_id);
+            let section_locations = section_locations.get(section_id);
+            if at_end {
+                section_locations
+                    .end
+                    .get(location_index)
+                    .copied()
+                    .unwrap_or(layout.mem_offset + layout.mem_size)
+            } else {
+                section_locations
+                    .start
+                    .get(location_index)
+                    .copied()
+                    .unwrap_or(layout.mem_offset)
+            }
+        }
+    }
+}
+
+/// Evaluates again any linker script assignments that depend on symbols from input files, now
+/// that those symbols have addresses, and updates the resolutions of the symbols assigned to.
+/// Also checks any `ASSERT`s.
+fn finalise_script_symbols<'data, S: StorageModel>(
+    script_layout: &ScriptLayout,
+    symbol_db: &SymbolDb<'data, S>,
+    group_layouts: &[GroupLayout<'data>],
+    sections: ScriptSections<'_, 'data>,
+    section_locations: &OutputSectionMap<SectionLocations>,
+    initial_values: &ScriptSymbolValues,
+    resolutions: &mut [Option<Resolution>],
+) -> Result {
+    if !initial_values.uses_input_symbols.contains(&true) && script_layout.assertions.is_empty() {
+        return Ok(());
+    }
+    let is_loaded = |file_id: FileId| {
+        matches!(
+            group_layouts[file_id.group()].files[file_id.file()],
+            FileLayout::Object(_) | FileLayout::Dynamic(_)
+        )
+    };
+    let values = evaluate_script_symbols(
+        script_layout,
+        symbol_db,
+        &is_loaded,
+        Some(resolutions),
+        sections,
+        section_locations,
+    )?;
+    let ParsedInput::Prelude(prelude) = symbol_db.file(PRELUDE_FILE_ID) else {
+        unreachable!();
+    };
+    for (index, def_info) in prelude.symbol_definitions.iter().enumerate() {
+        let InternalSymDefInfo::ScriptAssignment(assignment_index) = def_info else {
+            continue;
+        };
+        let assignment_index = *assignment_index as usize;
+        let symbol_id = SymbolId::from_usize(index);
+        if !values.uses_input_symbols[assignment_index] || !symbol_db.is_canonical(symbol_id) {
+            continue;
+        }
+        if let Some(resolution) = &mut resolutions[symbol_id.as_usize()] {
+            resolution.raw_value = values.values[assignment_index];
+        }
+    }
+    Ok(())
+}
+
+/// Where each output section goes, both in memory and when loaded. This is all that `AT(...)` can
+/// refer to, since symbols don't yet have values when load addresses are decided.
+#[derive(Clone, Copy)]
+struct ScriptSections<'a, 'data> {
+    output_sections: &'a OutputSections<'data>,
+    section_layouts: &'a OutputSectionMap<OutputRecordLayout>,
+    load_addresses: &'a OutputSectionMap<u64>,
+}
+
+impl ScriptSections<'_, '_> {
+    /// Returns the context for evaluating linker script expressions, without `.` or a way to
+    /// look up sections and symbols.
+    fn base_context(&self) -> EvaluationContext<'_> {
+        EvaluationContext {
+            location: 0,
+            sizeof_headers: [
+                FILE_HEADER,
+                output_section_id::PROGRAM_HEADERS,
+                output_section_id::SECTION_HEADERS,
+            ]
+            .iter()
+            .map(|id| self.section_layouts.get(*id).file_size as u64)
+            .sum(),
+            max_page_size: self.output_sections.max_page_size.value(),
+            common_page_size: self.output_sections.common_page_size.value(),
+            memory_regions: self.output_sections.memory_regions,
+            layout: None,
+        }
+    }
+
+    fn output_section_id(&self, name: &str) -> Option<OutputSectionId> {
+        let section_id = self.output_sections.section_id_by_name(name.as_bytes())?;
+        self.output_sections.output_index_of_section(section_id)?;
+        Some(section_id)
+    }
+}
+
+impl LayoutLookup for ScriptSections<'_, '_> {
+    fn section(&self, name: &str) -> Option<(u64, u64)> {
+        let layout = self.section_layouts.get(self.output_section_id(name)?);
+        Some((layout.mem_offset, layout.mem_size))
+    }
+
+    fn load_address(&self, name: &str) -> Option<u64> {
+        Some(*self.load_addresses.get(self.output_section_id(name)?))
+    }
+
+    fn symbol_value(&self, name: &str) -> Result<u64> {
+        bail!("Symbol `{name}` can't be used in a load address")
+    }
+
+    fn is_defined(&self, _name: &str) -> bool {
+        false
+    }
+}
+
+/// What the expressions in linker script assignments to symbols can refer to.
+struct ScriptLayoutLookup<'a, 'data, S: StorageModel> {
+    symbol_db: &'a SymbolDb<'data, S>,
+    is_loaded: &'a dyn Fn(FileId) -> bool,
+    resolutions: Option<&'a [Option<Resolution>]>,
+    sections: ScriptSections<'a, 'data>,
+
+    /// The latest value of each symbol that's been assigned so far and whether it depends on
+    /// symbols from input files.
+    values: HashMap<&'a str, (u64, bool)>,
+
+    /// Set when the expression being evaluated uses a symbol from an input file.
+    uses_input_symbols: Cell<bool>,
+}
+
+impl<S: StorageModel> ScriptLayoutLookup<'_, '_, S> {
+    fn definition(&self, name: &str) -> Option<SymbolId> {
+        self.symbol_db
+            .global_names
+            .get(&SymbolName::prehashed(name.as_bytes()))
+            .map(|symbol_id| self.symbol_db.definition(symbol_id))
+    }
+
+    /// Returns whether the symbol is defined by an input file that we're loading.
+    fn is_defined_by_input(&self, name: &str) -> bool {
+        let Some(symbol_id) = self.definition(name) else {
+            return false;
+        };
+        let file_id = self.symbol_db.file_id_for_symbol(symbol_id);
+        let ParsedInput::Object(object) = self.symbol_db.file(file_id) else {
+            return false;
+        };
+        (self.is_loaded)(file_id)
+            && object
+                .object
+                .symbol(symbol_id.to_input(object.symbol_id_range))
+                .is_ok_and(|symbol| !symbol.is_undefined(LittleEndian))
+    }
+}
+
+impl<S: StorageModel> LayoutLookup for ScriptLayoutLookup<'_, '_, S> {
+    fn section(&self, name: &str) -> Option<(u64, u64)> {
+        self.sections.section(name)
+    }
+
+    fn load_address(&self, name: &str) -> Option<u64> {
+        self.sections.load_address(name)
+    }
+
+    fn symbol_value(&self, name: &str) -> Result<u64> {
+        if let Some(&(value, uses_input_symbols)) = self.values.get(name) {
+            if uses_input_symbols {
+                self.uses_input_symbols.set(true);
+            }
+            return Ok(value);
+        }
+        if self.is_defined_by_input(name) {
+            self.uses_input_symbols.set(true);
+            let Some(resolutions) = self.resolutions else {
+                return Ok(0);
+            };
+            let symbol_id = self.definition(name).context("Missing definition")?;
+            return resolutions[symbol_id.as_usize()]
+                .map(|resolution| resolution.raw_value)
+                .with_context(|| format!("Symbol `{name}` doesn't have an address"));
+        }
+        let is_assigned_later = self.definition(name).is_some_and(|symbol_id| {
+            matches!(
+                self.symbol_db.file(self.symbol_db.file_id_for_symbol(symbol_id)),
+                ParsedInput::Prelude(prelude) if prelude.script_assignment(symbol_id).is_some()
+            )
+        });
+        if is_assigned_later {
+            bail!("Symbol `{name}` is used in a linker script before it's assigned");
+        }
+        bail!("Undefined symbol `{name}` in linker script expression")
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.values.contains_key(name) || self.is_defined_by_input(name)
+    }
 }
 
 #[tracing::instrument(skip_all, name = "Compute total section sizes")]
@@ -1675,6 +3607,7 @@ fn compute_total_section_part_sizes(
     output_sections: &mut OutputSections,
     symbol_resolution_flags: &[ResolutionFlags],
     sections_with_content: OutputSectionMap<bool>,
+    args: &Args,
 ) -> OutputSectionPartMap<u64> {
     let mut total_sizes: OutputSectionPartMap<u64> = output_sections.new_part_map();
     for group_state in group_states.iter() {
@@ -1690,6 +3623,7 @@ fn compute_total_section_part_sizes(
         sections_with_content,
         output_sections,
         symbol_resolution_flags,
+        args,
     );
     total_sizes
 }
@@ -1698,15 +3632,23 @@ fn compute_total_section_part_sizes(
 /// but which need to be unique. It's non parallel. It could potentially be run in parallel with
 /// some of the stages that run after it, that don't need access to the file states.
 #[tracing::instrument(skip_all, name = "Apply non-addressable indexes")]
-fn apply_non_addressable_indexes(
+fn apply_non_addressable_indexes<S: StorageModel>(
     group_states: &mut [GroupState],
-    args: &Args,
+    symbol_db: &SymbolDb<S>,
 ) -> Result<NonAddressableCounts> {
+    let args = symbol_db.args;
+    let verdef_count = num_version_definitions(symbol_db);
     let mut indexes = NonAddressableIndexes {
-        // Allocate version indexes starting from after the local and global indexes.
-        gnu_version_r_index: object::elf::VER_NDX_GLOBAL + 1,
+        // Allocate version indexes starting from after the local and global indexes and any
+        // versions that we define. The base definition uses the global index.
+        gnu_version_r_index: (object::elf::VER_NDX_GLOBAL as usize + verdef_count.max(1))
+            .try_into()
+            .context("Too many symbol versions")?,
+    };
+    let mut counts = NonAddressableCounts {
+        verneed_count: 0,
+        verdef_count: verdef_count as u64,
     };
-    let mut counts = NonAddressableCounts { verneed_count: 0 };
     for g in group_states.iter_mut() {
         for s in &mut g.files {
             match s {
@@ -1721,7 +3663,8 @@ fn apply_non_addressable_indexes(
     // If we were going to output symbol versions, but we didn't actually use any, then we drop all
     // versym allocations. This is partly to avoid wasting unnecessary space in the output file, but
     // mostly in order match what GNU ld does.
-    if counts.verneed_count == 0 && args.should_output_symbol_versions() {
+    if counts.verneed_count == 0 && counts.verdef_count == 0 && args.should_output_symbol_versions()
+    {
         for g in group_states {
             *g.common.mem_sizes.get_mut(part_id::GNU_VERSION) = 0;
         }
@@ -1738,6 +3681,74 @@ struct NonAddressableIndexes {
 pub(crate) struct NonAddressableCounts {
     /// The number of shared objects that want to emit a verneed record.
     pub(crate) verneed_count: u64,
+
+    /// The number of versions that we define, including the base definition.
+    pub(crate) verdef_count: u64,
+}
+
+/// Returns the number of versions that we'll write to .gnu.version_d, including the base
+/// definition, or zero if we're not defining any. With `--default-symver`, there's a version named
+/// after our soname that comes after the base definition and before the versions from our version
+/// script.
+fn num_version_definitions<S: StorageModel>(symbol_db: &SymbolDb<S>) -> usize {
+    let num_named = symbol_db.version_script.named_versions().len()
+        + usize::from(symbol_db.args.default_symver);
+    if num_named == 0 || !symbol_db.args.should_output_symbol_versions() {
+        return 0;
+    }
+    num_named + 1
+}
+
+/// Returns the index of the version that we give to the exported symbol with the supplied name.
+fn output_version_index<S: StorageModel>(symbol_db: &SymbolDb<S>, name: &[u8]) -> u16 {
+    let index = symbol_db
+        .version_script
+        .version_index(&SymbolName::prehashed(name));
+    if !symbol_db.args.default_symver {
+        return index;
+    }
+    // Symbols without a version from the version script get the default version. That comes first,
+    // so the other versions move up one.
+    index + 1
+}
+
+/// Returns the version index for a symbol that `.symver` gave a version. Non-default versions, the
+/// ones written with a single `@`, are hidden, so that they can't be used by new links.
+fn symver_version_index<S: StorageModel>(
+    symbol_db: &SymbolDb<S>,
+    symbol_version: SymbolVersion,
+) -> Result<u16> {
+    let mut index = symbol_db
+        .version_script
+        .named_version_index(symbol_version.version)
+        .with_context(|| {
+            format!(
+                "Version node not found for symbol `{}@{}`",
+                String::from_utf8_lossy(symbol_version.name),
+                String::from_utf8_lossy(symbol_version.version)
+            )
+        })?;
+    if symbol_db.args.default_symver {
+        index += 1;
+    }
+    if !symbol_version.is_default {
+        index |= object::elf::VERSYM_HIDDEN;
+    }
+    Ok(index)
+}
+
+/// Returns the name of our base version definition. As with GNU ld, this is our soname if we have
+/// one, otherwise the name of the output file.
+pub(crate) fn base_version_name(args: &Args) -> &[u8] {
+    args.soname.as_ref().map_or_else(
+        || {
+            args.output
+                .file_name()
+                .unwrap_or(args.output.as_os_str())
+                .as_encoded_bytes()
+        },
+        |soname| soname.as_bytes(),
+    )
 }
 
 /// Returns the starting memory address for each alignment within each segment.
@@ -1758,16 +3769,18 @@ struct GcOutputs<'data> {
     group_states: Vec<GroupState<'data>>,
     sections_with_content: OutputSectionMap<bool>,
     has_static_tls: bool,
+    has_unique_symbols: bool,
 }
 
 #[tracing::instrument(skip_all, name = "Find required sections")]
-fn find_required_sections<'data, S: StorageModel, A: Arch>(
+fn find_required_sections<'data, S: StorageModel>(
     groups_in: Vec<resolution::ResolvedGroup<'data>>,
     symbol_db: &SymbolDb<'data, S>,
     output_sections: &OutputSections<'data>,
     symbol_resolution_flags: &[AtomicResolutionFlags],
     merged_strings: &OutputSectionMap<MergedStringsSection<'data>>,
     custom_start_stop_defs: Vec<InternalSymDefInfo>,
+    symbol_warnings: &SymbolWarnings<'data>,
 ) -> Result<GcOutputs<'data>> {
     let num_workers = groups_in.len();
     let (worker_slots, groups) = create_worker_slots(
@@ -1793,6 +3806,8 @@ fn find_required_sections<'data, S: StorageModel, A: Arch>(
         sections_with_content: output_sections.new_section_map(),
         merged_strings,
         has_static_tls: AtomicBool::new(false),
+        has_unique_symbols: AtomicBool::new(false),
+        symbol_warnings,
     };
     let resources_ref = &resources;
 
@@ -1802,7 +3817,7 @@ fn find_required_sections<'data, S: StorageModel, A: Arch>(
         .try_for_each(|(i, mut group)| -> Result {
             let _span = tracing::debug_span!("find_required_sections", gid = i).entered();
             for file in &mut group.files {
-                activate::<S, A>(&mut group.common, file, &mut group.queue, resources_ref)
+                activate::<S>(&mut group.common, file, &mut group.queue, resources_ref)
                     .with_context(|| format!("Failed to activate {file}"))?;
             }
             let _ = resources_ref.waiting_workers.push(group);
@@ -1816,7 +3831,7 @@ fn find_required_sections<'data, S: StorageModel, A: Arch>(
                     let mut idle = false;
                     while !resources.done.load(atomic::Ordering::SeqCst) {
                         while let Some(worker) = resources.waiting_workers.pop() {
-                            worker.do_pending_work::<S, A>(resources_ref);
+                            worker.do_pending_work::<S>(resources_ref);
                         }
                         if idle {
                             // Wait until there's more work to do or until we shut down.
@@ -1848,17 +3863,15 @@ fn find_required_sections<'data, S: StorageModel, A: Arch>(
             });
         }
     });
-    let mut errors: Vec<Error> = take(resources.errors.lock().unwrap().as_mut());
-    // TODO: Figure out good way to report more than one error.
-    if let Some(error) = errors.pop() {
-        return Err(error);
-    }
+    let errors: Vec<Error> = take(resources.errors.lock().unwrap().as_mut());
+    crate::error::combine(errors, symbol_db.args.error_limit)?;
     let group_states = unwrap_worker_states(&resources.worker_slots);
     let sections_with_content = resources.sections_with_content.into_map(|v| v.into_inner());
     Ok(GcOutputs {
         group_states,
         sections_with_content,
         has_static_tls: resources.has_static_tls.load(atomic::Ordering::Relaxed),
+        has_unique_symbols: resources.has_unique_symbols.load(atomic::Ordering::Relaxed),
     })
 }
 
@@ -1906,7 +3919,7 @@ fn unwrap_worker_states<'data>(
 impl<'data> GroupState<'data> {
     /// Does work until there's nothing left in the queue, then returns our worker to its slot and
     /// shuts down.
-    fn do_pending_work<'scope, S: StorageModel, A: Arch>(
+    fn do_pending_work<'scope, S: StorageModel>(
         mut self,
         resources: &GraphResources<'data, 'scope, S>,
     ) {
@@ -1915,7 +3928,7 @@ impl<'data> GroupState<'data> {
                 let file_id = work_item.file_id(resources.symbol_db);
                 let file = &mut self.files[file_id.file()];
                 if let Err(error) =
-                    file.do_work::<S, A>(&mut self.common, work_item, resources, &mut self.queue)
+                    file.do_work::<S>(&mut self.common, work_item, resources, &mut self.queue)
                 {
                     resources.report_error(error);
                     return;
@@ -1937,6 +3950,7 @@ impl<'data> GroupState<'data> {
         symbol_db: &SymbolDb<'data, S>,
         output_sections: &OutputSections,
         symbol_resolution_flags: &[AtomicResolutionFlags],
+        plt_layout: PltLayout,
     ) -> Result {
         for file_state in &mut self.files {
             file_state.finalise_sizes(
@@ -1944,6 +3958,7 @@ impl<'data> GroupState<'data> {
                 symbol_db,
                 output_sections,
                 symbol_resolution_flags,
+                plt_layout,
             )?;
         }
         self.common.validate_sizes()?;
@@ -1957,6 +3972,8 @@ impl<'data> GroupState<'data> {
         resources: &FinaliseLayoutResources<'_, 'data, S>,
     ) -> Result<GroupLayout<'data>> {
         let eh_frame_start_address = *memory_offsets.get(part_id::EH_FRAME);
+        let rela_plt_start_address = *memory_offsets.get(part_id::RELA_PLT);
+        let plt_got_start_address = *memory_offsets.get(part_id::PLT_GOT);
         let mut files = self
             .files
             .into_iter()
@@ -1971,6 +3988,10 @@ impl<'data> GroupState<'data> {
                 .get(output_section_id::DYNSTR)
                 .mem_offset) as u32;
         memory_offsets.increment(part_id::DYNSTR, *self.common.mem_sizes.get(part_id::DYNSTR));
+        memory_offsets.increment(
+            part_id::RELA_PLT,
+            *self.common.mem_sizes.get(part_id::RELA_PLT),
+        );
 
         set_last_verneed(&self.common, resources, memory_offsets, &mut files);
 
@@ -1981,6 +4002,8 @@ impl<'data> GroupState<'data> {
             file_sizes: compute_file_sizes(&self.common.mem_sizes, resources.output_sections),
             mem_sizes: self.common.mem_sizes,
             eh_frame_start_address,
+            rela_plt_start_address,
+            plt_got_start_address,
         })
     }
 }
@@ -2004,7 +4027,7 @@ fn set_last_verneed<S: StorageModel>(
     if is_last_verneed {
         for file in files.iter_mut().rev() {
             if let FileLayout::Dynamic(d) = file {
-                if d.verdef_info.is_some() {
+                if d.verdef_info.is_some() || d.default_version.is_some() {
                     d.is_last_verneed = true;
                     break;
                 }
@@ -2013,14 +4036,14 @@ fn set_last_verneed<S: StorageModel>(
     }
 }
 
-fn activate<'data, S: StorageModel, A: Arch>(
+fn activate<'data, S: StorageModel>(
     common: &mut CommonGroupState<'data>,
     file: &mut FileLayoutState<'data>,
     queue: &mut LocalWorkQueue,
     resources: &GraphResources<'data, '_, S>,
 ) -> Result {
     match file {
-        FileLayoutState::Object(s) => s.activate::<S, A>(common, resources, queue)?,
+        FileLayoutState::Object(s) => s.activate::<S>(common, resources, queue)?,
         FileLayoutState::Prelude(s) => s.activate(common, resources, queue)?,
         FileLayoutState::Dynamic(s) => s.activate(common, resources, queue)?,
         FileLayoutState::NotLoaded(_) => {}
@@ -2064,7 +4087,7 @@ impl LocalWorkQueue {
         );
     }
 
-    fn send_copy_relocation_request<S: StorageModel>(
+    fn send_export_definition_request<S: StorageModel>(
         &mut self,
         symbol_id: SymbolId,
         resources: &GraphResources<S>,
@@ -2074,7 +4097,7 @@ impl LocalWorkQueue {
         self.send_work(
             resources,
             symbol_file_id,
-            WorkItem::ExportCopyRelocation(symbol_id),
+            WorkItem::ExportDefinition(symbol_id),
         );
     }
 }
@@ -2126,30 +4149,37 @@ impl<'data> FileLayoutState<'data> {
         symbol_db: &SymbolDb<'data, S>,
         output_sections: &OutputSections,
         symbol_resolution_flags: &[AtomicResolutionFlags],
+        plt_layout: PltLayout,
     ) -> Result {
         match self {
             FileLayoutState::Object(s) => {
-                s.finalise_sizes(common, symbol_db, output_sections, symbol_resolution_flags);
-                s.finalise_symbol_sizes(common, symbol_db, symbol_resolution_flags)?;
+                s.finalise_sizes(
+                    common,
+                    symbol_db,
+                    output_sections,
+                    symbol_resolution_flags,
+                    plt_layout,
+                );
+                s.finalise_symbol_sizes(common, symbol_db, symbol_resolution_flags, plt_layout)?;
             }
             FileLayoutState::Dynamic(s) => {
-                s.finalise_sizes(common)?;
-                s.finalise_symbol_sizes(common, symbol_db, symbol_resolution_flags)?;
+                s.finalise_sizes(common, symbol_db, symbol_resolution_flags)?;
+                s.finalise_symbol_sizes(common, symbol_db, symbol_resolution_flags, plt_layout)?;
             }
             FileLayoutState::Prelude(s) => {
                 s.finalise_sizes(common, symbol_db, symbol_resolution_flags)?;
-                s.finalise_symbol_sizes(common, symbol_db, symbol_resolution_flags)?;
+                s.finalise_symbol_sizes(common, symbol_db, symbol_resolution_flags, plt_layout)?;
             }
             FileLayoutState::Epilogue(s) => {
                 s.finalise_sizes(common, symbol_db, symbol_resolution_flags)?;
-                s.finalise_symbol_sizes(common, symbol_db, symbol_resolution_flags)?;
+                s.finalise_symbol_sizes(common, symbol_db, symbol_resolution_flags, plt_layout)?;
             }
             FileLayoutState::NotLoaded(_) => {}
         }
         Ok(())
     }
 
-    fn do_work<'scope, S: StorageModel, A: Arch>(
+    fn do_work<'scope, S: StorageModel>(
         &mut self,
         common: &mut CommonGroupState<'data>,
         work_item: WorkItem,
@@ -2158,18 +4188,18 @@ impl<'data> FileLayoutState<'data> {
     ) -> Result {
         match work_item {
             WorkItem::LoadGlobalSymbol(symbol_id) => self
-                .handle_symbol_request::<S, A>(common, symbol_id, resources, queue)
+                .handle_symbol_request::<S>(common, symbol_id, resources, queue)
                 .with_context(|| {
                     format!(
                         "Failed to load {} from {self}",
                         resources.symbol_db.symbol_debug(symbol_id),
                     )
                 }),
-            WorkItem::ExportCopyRelocation(symbol_id) => match self {
+            WorkItem::ExportDefinition(symbol_id) => match self {
                 FileLayoutState::Dynamic(_) => export_dynamic(common, symbol_id, resources),
                 _ => {
                     bail!(
-                        "Internal error: ExportCopyRelocation sent to non-dynamic object for: {}",
+                        "Internal error: ExportDefinition sent to non-dynamic object for: {}",
                         resources.symbol_db.symbol_debug(symbol_id)
                     )
                 }
@@ -2177,7 +4207,7 @@ impl<'data> FileLayoutState<'data> {
         }
     }
 
-    fn handle_symbol_request<'scope, S: StorageModel, A: Arch>(
+    fn handle_symbol_request<'scope, S: StorageModel>(
         &mut self,
         common: &mut CommonGroupState<'data>,
         symbol_id: SymbolId,
@@ -2186,17 +4216,17 @@ impl<'data> FileLayoutState<'data> {
     ) -> Result {
         match self {
             FileLayoutState::Object(state) => {
-                state.load_symbol::<A>(common, symbol_id, resources, queue)?;
+                state.load_symbol(common, symbol_id, resources, queue)?;
             }
             FileLayoutState::Prelude(state) => {
-                state.load_symbol::<A>(common, symbol_id, resources, queue)?;
+                state.load_symbol(common, symbol_id, resources, queue)?;
             }
             FileLayoutState::Dynamic(state) => {
-                state.load_symbol::<A>(common, symbol_id, resources, queue)?;
+                state.load_symbol(common, symbol_id, resources, queue)?;
             }
             FileLayoutState::NotLoaded(_) => {}
             FileLayoutState::Epilogue(state) => {
-                state.load_symbol::<A>(common, symbol_id, resources, queue)?;
+                state.load_symbol(common, symbol_id, resources, queue)?;
             }
         }
         Ok(())
@@ -2365,6 +4395,58 @@ impl std::fmt::Display for ObjectLayout<'_> {
     }
 }
 
+impl<'data> ObjectLayout<'data> {
+    /// Returns `addend` adjusted for any bytes that relaxation deleted between the symbol and the
+    /// location that the symbol plus the addend refers to.
+    pub(crate) fn relaxed_addend(
+        &self,
+        symbol_index: object::SymbolIndex,
+        addend: i64,
+    ) -> Result<i64> {
+        let Some((relaxations, symbol)) = self.symbol_relaxations(symbol_index)? else {
+            return Ok(addend);
+        };
+        let start = symbol.st_value(LittleEndian);
+        let Some(end) = start.checked_add_signed(addend) else {
+            return Ok(addend);
+        };
+        Ok(relaxations.output_offset(end) as i64 - relaxations.output_offset(start) as i64)
+    }
+
+    /// Returns the size of the symbol if relaxation deleted any bytes within it.
+    pub(crate) fn relaxed_symbol_size(
+        &self,
+        symbol_index: object::SymbolIndex,
+    ) -> Result<Option<u64>> {
+        let Some((relaxations, symbol)) = self.symbol_relaxations(symbol_index)? else {
+            return Ok(None);
+        };
+        let e = LittleEndian;
+        let start = symbol.st_value(e);
+        let size = symbol.st_size(e);
+        let relaxed_size =
+            relaxations.output_offset(start + size) - relaxations.output_offset(start);
+        Ok((relaxed_size != size).then_some(relaxed_size))
+    }
+
+    fn symbol_relaxations(
+        &self,
+        symbol_index: object::SymbolIndex,
+    ) -> Result<Option<(&SectionRelaxations, &'data Symbol)>> {
+        if self.relaxations.is_empty() {
+            return Ok(None);
+        }
+        let symbol = self.object.symbol(symbol_index)?;
+        let Some(section_index) = self.object.symbol_section(symbol, symbol_index)? else {
+            return Ok(None);
+        };
+        Ok(self
+            .relaxations
+            .get(section_index)
+            .map(|relaxations| (relaxations, symbol)))
+    }
+}
+
 struct SectionRequest {
     id: object::SectionIndex,
 }
@@ -2382,24 +4464,48 @@ impl Section {
         part_id: PartId,
     ) -> Result<Section> {
         let object_section = object_state.object.section(section_index)?;
-        let size = object_state.object.section_size(object_section)?;
+        // We only copy section groups when producing a relocatable object, in which case we leave
+        // out any members that we discarded.
+        let size = if SectionType::from_header(object_section) == sht::GROUP {
+            crate::relocatable_object::group_section_size(
+                object_state.object,
+                object_section,
+                &object_state.sections,
+            )?
+        } else {
+            object_state.object.section_size(object_section)?
+        };
         let section = Section {
             index: section_index,
             part_id,
             size,
+            thunks_size: 0,
             resolution_kind: ResolutionFlags::empty(),
             is_writable: SectionFlags::from_header(object_section).contains(shf::WRITE),
         };
         Ok(section)
     }
 
-    // How much space we take up. This is our size rounded up to the next multiple of our alignment,
-    // unless we're in a packed section, in which case it's just our size.
+    // How much space we take up. This is our size, including any thunks, rounded up to the next
+    // multiple of our alignment, unless we're in a packed section, in which case it's just our size.
     pub(crate) fn capacity(&self) -> u64 {
         if self.part_id.should_pack() {
+            self.size_with_thunks()
+        } else {
+            self.alignment().align_up(self.size_with_thunks())
+        }
+    }
+
+    /// Returns the offset within the section at which our range-extension thunks start.
+    pub(crate) fn thunks_offset(&self) -> u64 {
+        self.size.next_multiple_of(THUNK_ALIGNMENT)
+    }
+
+    pub(crate) fn size_with_thunks(&self) -> u64 {
+        if self.thunks_size == 0 {
             self.size
         } else {
-            self.alignment().align_up(self.size)
+            self.thunks_offset() + self.thunks_size
         }
     }
 
@@ -2417,7 +4523,7 @@ impl Section {
     }
 }
 
-fn process_relocation<S: StorageModel, A: Arch>(
+fn process_relocation<S: StorageModel>(
     object: &mut ObjectLayoutState,
     common: &mut CommonGroupState,
     rel: &Rela64<LittleEndian>,
@@ -2431,51 +4537,92 @@ fn process_relocation<S: StorageModel, A: Arch>(
         let symbol_db = resources.symbol_db;
         let symbol_id = symbol_db.definition(object.symbol_id_range.input_to_id(local_sym_index));
         let symbol_value_flags = symbol_db.local_symbol_value_flags(symbol_id);
+        if resources.symbol_warnings.has_warning(symbol_id)
+            && symbol_db.file_id_for_symbol(symbol_id) != object.file_id
+        {
+            common.warning_references.push(WarningReference {
+                referenced_by: object.file_id,
+                symbol_id,
+            });
+        }
+        if args.output_kind() == OutputKind::RelocatableObject {
+            // The relocation is copied to the output rather than being applied, so all we need is
+            // for the symbol to be in our symbol table. Undefined symbols are fine, since they may
+            // be defined by something that our output is later linked with.
+            let previous_flags = resources.symbol_resolution_flags[symbol_id.as_usize()]
+                .fetch_or(ResolutionFlags::DIRECT);
+            if previous_flags.is_empty() {
+                queue.send_symbol_request(symbol_id, resources);
+            }
+            return Ok(next_modifier);
+        }
         let rel_offset = rel.r_offset.get(LittleEndian);
         let r_type = rel.r_type(LittleEndian, false);
-
-        let rel_info = if let Some(relaxation) = A::Relaxation::new(
-            r_type,
-            object.object.raw_section_data(section)?,
-            rel_offset,
-            symbol_value_flags,
-            args.output_kind(),
-            SectionFlags::from_header(section),
-        ) {
+        let arch = args.arch();
+
+        let original_kind = arch.relocation_from_raw(r_type)?.kind;
+        let relaxation = if args.relax.allows(original_kind) {
+            arch.new_relaxation(
+                r_type,
+                object.object.raw_section_data(section)?,
+                rel_offset,
+                symbol_value_flags,
+                args.output_kind(),
+                SectionFlags::from_header(section),
+            )
+        } else {
+            None
+        };
+        let rel_info = if let Some(relaxation) = relaxation {
             next_modifier = relaxation.next_modifier();
             relaxation.rel_info()
         } else {
-            A::relocation_from_raw(r_type)?
+            arch.relocation_from_raw(r_type)?
         };
-        if does_relocation_require_static_tls(r_type) {
+        if does_relocation_require_static_tls(original_kind) {
             resources
                 .has_static_tls
                 .store(true, atomic::Ordering::Relaxed);
         }
 
-        let section_is_writable = SectionFlags::from_header(section).contains(shf::WRITE);
-        let mut resolution_kind = resolution_flags(rel_info.kind);
-        if resolution_kind.contains(ResolutionFlags::DIRECT)
-            && symbol_value_flags.contains(ValueFlags::DYNAMIC)
-        {
-            if section_is_writable {
-                common.allocate(part_id::RELA_DYN_GENERAL, elf::RELA_ENTRY_SIZE);
-            } else if symbol_value_flags.contains(ValueFlags::FUNCTION) {
-                resolution_kind.remove(ResolutionFlags::DIRECT);
-                resolution_kind |= ResolutionFlags::PLT | ResolutionFlags::GOT;
-            } else if !symbol_value_flags.contains(ValueFlags::ABSOLUTE) {
-                resolution_kind |= ResolutionFlags::COPY_RELOCATION;
-            }
+        if rel_info.kind == RelocationKind::AuthAbsolute && args.pack_relative_relocs {
+            // We don't write RELR, so rather than ignoring a request to pack signed pointers into
+            // .relr.auth.dyn, we report it.
+            bail!(
+                "{}: -z pack-relative-relocs isn't supported with signed pointers, since we \
+                 don't write .relr.auth.dyn",
+                object.input
+            );
         }
 
+        let section_is_writable = SectionFlags::from_header(section).contains(shf::WRITE);
+        let resolution_kind = reference_resolution_flags(
+            rel_info.kind,
+            symbol_value_flags,
+            section_is_writable,
+            args.output_kind(),
+            &mut common.mem_sizes,
+        )
+        .with_context(|| {
+            format!(
+                "{}: {} against `{}` in section `{}`",
+                object.input,
+                arch.rel_type_to_string(r_type),
+                symbol_db.symbol_name_for_display(symbol_id),
+                String::from_utf8_lossy(object.object.section_name(section).unwrap_or_default())
+            )
+        })?;
+
         let previous_flags =
             resources.symbol_resolution_flags[symbol_id.as_usize()].fetch_or(resolution_kind);
 
-        if args.is_relocatable()
-            && rel_info.kind == RelocationKind::Absolute
-            && symbol_value_flags.contains(ValueFlags::ADDRESS)
-        {
-            common.allocate(part_id::RELA_DYN_RELATIVE, elf::RELA_ENTRY_SIZE);
+        if args.print_got_usage && resolution_kind.intersects(got_usage::got_or_plt_flags()) {
+            common.got_usage.push(GotUsage {
+                symbol_id,
+                referenced_by: object.file_id,
+                r_type,
+                reason: GotUsageReason::new(symbol_value_flags, rel_info.kind),
+            });
         }
 
         if previous_flags.is_empty() {
@@ -2488,36 +4635,117 @@ fn process_relocation<S: StorageModel, A: Arch>(
                 args,
             ) {
                 let symbol_name = symbol_db.symbol_name(symbol_id)?;
-                resources.report_error(anyhow::anyhow!(
-                    "Undefined symbol {}, referenced by {}",
-                    String::from_utf8_lossy(symbol_name.bytes()),
-                    object.input
-                ));
+                if !args
+                    .ignore_unresolved_symbols
+                    .iter()
+                    .any(|ignored| ignored.as_bytes() == symbol_name.bytes())
+                {
+                    resources.report_error(anyhow::anyhow!(
+                        "Undefined symbol {}, referenced by {}",
+                        String::from_utf8_lossy(symbol_name.bytes()),
+                        object.input
+                    ));
+                }
             }
         }
 
-        if resolution_kind.contains(ResolutionFlags::COPY_RELOCATION)
-            && !previous_flags.contains(ResolutionFlags::COPY_RELOCATION)
+        let exported_definition = ResolutionFlags::COPY_RELOCATION | ResolutionFlags::CANONICAL_PLT;
+        if resolution_kind.intersects(exported_definition)
+            && !previous_flags.intersects(exported_definition)
         {
-            queue.send_copy_relocation_request(symbol_id, resources);
+            queue.send_export_definition_request(symbol_id, resources);
         }
     }
     Ok(next_modifier)
 }
 
+/// Works out what a reference to a symbol with `value_flags` via a relocation of kind `rel_kind`
+/// requires. Returns the resolution flags that the symbol needs and allocates in `mem_sizes` any
+/// dynamic relocation that's needed for the reference itself. `explain-reloc` uses this too, so
+/// that what it reports matches what we do.
+pub(crate) fn reference_resolution_flags(
+    rel_kind: RelocationKind,
+    value_flags: ValueFlags,
+    section_is_writable: bool,
+    output_kind: OutputKind,
+    mem_sizes: &mut OutputSectionPartMap<u64>,
+) -> Result<ResolutionFlags> {
+    if rel_kind == RelocationKind::AuthAbsolute {
+        // Signed pointers can only be produced by the dynamic loader, so we always need a dynamic
+        // relocation. If the symbol is dynamic, then space for that is allocated below.
+        if !output_kind.is_relocatable() {
+            bail!("Signed pointers require position-independent output");
+        }
+        if !section_is_writable {
+            bail!("Signed pointers can't be written to a read-only section");
+        }
+        if !value_flags.contains(ValueFlags::DYNAMIC) {
+            mem_sizes.increment(part_id::RELA_DYN_GENERAL, elf::RELA_ENTRY_SIZE);
+        }
+    }
+    let mut resolution_kind = resolution_flags(rel_kind);
+    if rel_kind == RelocationKind::SymbolSize {
+        if is_symbol_size_dynamic(value_flags, output_kind) {
+            if !section_is_writable {
+                bail!("The size of a dynamic symbol can't be written to a read-only section");
+            }
+            mem_sizes.increment(part_id::RELA_DYN_GENERAL, elf::RELA_ENTRY_SIZE);
+        }
+    } else if resolution_kind.contains(ResolutionFlags::DIRECT)
+        && value_flags.contains(ValueFlags::DYNAMIC)
+        // A relocation of kind `None`, e.g. a TLSDESC call that we've relaxed away, doesn't write
+        // anything, so it needs neither a dynamic relocation nor a copy of the symbol.
+        && rel_kind != RelocationKind::None
+    {
+        if section_is_writable {
+            mem_sizes.increment(part_id::RELA_DYN_GENERAL, elf::RELA_ENTRY_SIZE);
+        } else if value_flags.contains(ValueFlags::FUNCTION) {
+            resolution_kind.remove(ResolutionFlags::DIRECT);
+            resolution_kind |= ResolutionFlags::PLT | ResolutionFlags::GOT;
+            if output_kind.is_executable() {
+                resolution_kind |= ResolutionFlags::CANONICAL_PLT;
+            }
+        } else if !value_flags.contains(ValueFlags::ABSOLUTE) {
+            resolution_kind |= ResolutionFlags::COPY_RELOCATION;
+        }
+    }
+    if output_kind.is_relocatable()
+        && rel_kind == RelocationKind::Absolute
+        && value_flags.contains(ValueFlags::ADDRESS)
+    {
+        mem_sizes.increment(part_id::RELA_DYN_RELATIVE, elf::RELA_ENTRY_SIZE);
+    }
+    Ok(resolution_kind)
+}
+
+/// Returns whether the size of a symbol with `value_flags` is only known at runtime. That's the case
+/// if it's defined by a shared object, since a different version of the shared object might be
+/// loaded, or if we're writing a shared object and the symbol can be interposed. `value_flags`
+/// should be those of the symbol's definition, so that we and the writer agree.
+pub(crate) fn is_symbol_size_dynamic(value_flags: ValueFlags, output_kind: OutputKind) -> bool {
+    value_flags.contains(ValueFlags::DYNAMIC)
+        || (output_kind == OutputKind::SharedObject
+            && !value_flags.intersects(ValueFlags::CAN_BYPASS_GOT | ValueFlags::DOWNGRADE_TO_LOCAL))
+}
+
 /// Returns whether the supplied relocation type requires static TLS. If true and we're writing a
 /// shared object, then the STATIC_TLS will be set in the shared object which is a signal to the
 /// runtime loader that the shared object cannot be loaded at runtime (e.g. with dlopen).
-fn does_relocation_require_static_tls(r_type: u32) -> bool {
-    r_type == object::elf::R_X86_64_GOTTPOFF
+fn does_relocation_require_static_tls(rel_kind: RelocationKind) -> bool {
+    matches!(
+        rel_kind,
+        RelocationKind::GotTpOff | RelocationKind::GotTpOffGot | RelocationKind::GotTpOffGotBase
+    )
 }
 
-fn resolution_flags(rel_kind: RelocationKind) -> ResolutionFlags {
+pub(crate) fn resolution_flags(rel_kind: RelocationKind) -> ResolutionFlags {
     match rel_kind {
         RelocationKind::PltRelative | RelocationKind::PltRelGotBase => {
             ResolutionFlags::PLT | ResolutionFlags::GOT
         }
-        RelocationKind::GotRelGotBase | RelocationKind::GotRelative => ResolutionFlags::GOT,
+        RelocationKind::GotRelGotBase | RelocationKind::GotRelative => {
+            ResolutionFlags::GOT | ResolutionFlags::GOT_REFERENCE
+        }
         RelocationKind::GotTpOff
         | RelocationKind::GotTpOffGot
         | RelocationKind::GotTpOffGotBase => ResolutionFlags::GOT_TLS_OFFSET,
@@ -2532,12 +4760,20 @@ fn resolution_flags(rel_kind: RelocationKind) -> ResolutionFlags {
             ResolutionFlags::empty()
         }
         RelocationKind::Absolute
+        | RelocationKind::AuthAbsolute
         | RelocationKind::Relative
+        | RelocationKind::RelativeLocalEntry
         | RelocationKind::DtpOff
         | RelocationKind::TpOff
         | RelocationKind::TpOffAArch64
         | RelocationKind::SymRelGotBase
+        | RelocationKind::SymRelGlobalPointer
         | RelocationKind::Got
+        | RelocationKind::AbsoluteSet
+        | RelocationKind::AbsoluteAddition
+        | RelocationKind::AbsoluteSubtraction
+        | RelocationKind::PairedLow
+        | RelocationKind::SymbolSize
         | RelocationKind::None => ResolutionFlags::DIRECT,
         RelocationKind::AbsoluteAArch64 => ResolutionFlags::empty(),
     }
@@ -2554,7 +4790,8 @@ impl PreludeLayoutState {
             },
             entry_symbol_id: None,
             needs_tlsld_got_entry: false,
-            identity: crate::identity::linker_identity(),
+            // This is filled in when we activate.
+            identity: String::new(),
             header_info: None,
             dynamic_linker: None,
             shstrtab_size: 0,
@@ -2577,6 +4814,10 @@ impl PreludeLayoutState {
         });
 
         // Allocate space to store the identify of the linker in the .comment section.
+        let args = resources.symbol_db.args;
+        if args.enable_linker_version && !args.strip_section_headers {
+            self.identity = crate::identity::linker_identity();
+        }
         common.allocate(
             output_section_id::COMMENT.part_id_with_alignment(alignment::MIN),
             self.identity.len() as u64,
@@ -2592,6 +4833,15 @@ impl PreludeLayoutState {
         if resources.symbol_db.args.output_kind().is_executable() {
             self.load_entry_point(resources, queue)?;
         }
+        load_extern_symbols(resources, queue);
+        self.define_script_symbols(resources);
+        if resources.symbol_db.args.lazy_binding() {
+            common.allocate(
+                part_id::GOT,
+                elf::GOT_ENTRY_SIZE * elf::NUM_LAZY_GOT_RESERVED_ENTRIES,
+            );
+            common.allocate(part_id::PLT_GOT, elf::PLT_HEADER_SIZE);
+        }
         if resources.symbol_db.args.tls_mode() == TlsMode::Preserve {
             // Allocate space for a TLS module number and offset for use with TLSLD relocations.
             common.allocate(part_id::GOT, elf::GOT_ENTRY_SIZE * 2);
@@ -2631,21 +4881,44 @@ impl PreludeLayoutState {
         resources: &GraphResources<S>,
         queue: &mut LocalWorkQueue,
     ) -> Result {
+        let ParsedInput::Prelude(prelude) = resources.symbol_db.file(PRELUDE_FILE_ID) else {
+            unreachable!();
+        };
+        let name = prelude.entry_symbol_name;
         let symbol_id = resources
             .symbol_db
             .global_names
-            .get(&SymbolName::prehashed(b"_start"))
-            .context("Missing _start symbol")?;
+            .get(&SymbolName::prehashed(name))
+            .with_context(|| {
+                format!(
+                    "Missing entry point symbol `{}`",
+                    String::from_utf8_lossy(name)
+                )
+            })?;
         self.entry_symbol_id = Some(symbol_id);
-        let file_id = resources.symbol_db.file_id_for_symbol(symbol_id);
-        let old_flags = resources.symbol_resolution_flags[symbol_id.as_usize()]
-            .fetch_or(ResolutionFlags::DIRECT);
-        if old_flags.is_empty() {
-            queue.send_work(resources, file_id, WorkItem::LoadGlobalSymbol(symbol_id));
-        }
+        load_referenced_symbol(symbol_id, resources, queue);
         Ok(())
     }
 
+    /// Marks symbols that linker scripts assign to as referenced, so that they get defined even if
+    /// nothing refers to them. This doesn't apply to `PROVIDE`, which only defines symbols that
+    /// are referenced.
+    fn define_script_symbols<S: StorageModel>(&self, resources: &GraphResources<S>) {
+        let ParsedInput::Prelude(prelude) = resources.symbol_db.file(PRELUDE_FILE_ID) else {
+            return;
+        };
+        for symbol_id in self.symbol_id_range {
+            if resources.symbol_db.is_canonical(symbol_id)
+                && prelude
+                    .script_assignment(symbol_id)
+                    .is_some_and(|assignment| !assignment.provide)
+            {
+                resources.symbol_resolution_flags[symbol_id.as_usize()]
+                    .fetch_or(ResolutionFlags::DIRECT);
+            }
+        }
+    }
+
     fn finalise_sizes<S: StorageModel>(
         &self,
         common: &mut CommonGroupState,
@@ -2677,6 +4950,7 @@ impl PreludeLayoutState {
         sections_with_content: OutputSectionMap<bool>,
         output_sections: &mut OutputSections,
         symbol_resolution_flags: &[ResolutionFlags],
+        args: &Args,
     ) {
         use output_section_id::OrderEvent;
 
@@ -2694,8 +4968,13 @@ impl PreludeLayoutState {
         });
 
         // Keep any sections that we've said we want to keep regardless.
+        let is_relocatable_output = args.output_kind() == OutputKind::RelocatableObject;
         for section_id in output_section_id::built_in_section_ids() {
-            if section_id.built_in_details().keep_if_empty {
+            if section_id.built_in_details().keep_if_empty
+                && !(is_relocatable_output
+                    && (section_id == output_section_id::PROGRAM_HEADERS
+                        || section_id == output_section_id::SECTION_HEADERS))
+            {
                 keep_sections[section_id.as_usize()] = true;
             }
         }
@@ -2711,6 +4990,17 @@ impl PreludeLayoutState {
                     }
                 }
             });
+
+        // Keep any sections that a linker script assigns within, even if they're empty.
+        for (section_id, info) in output_sections.ids_with_info() {
+            if info
+                .script_placement
+                .as_ref()
+                .is_some_and(|placement| placement.has_assignments)
+            {
+                keep_sections[section_id.as_usize()] = true;
+            }
+        }
         let num_sections = keep_sections.iter().filter(|p| **p).count();
 
         // Compute output indexes of each of section.
@@ -2743,10 +5033,48 @@ impl PreludeLayoutState {
                 }
             }
         }
-        let active_segment_ids = (0..crate::program_segments::MAX_SEGMENTS)
-            .filter(|i| keep_segments[*i] || i == &STACK.as_usize())
-            .map(ProgramSegmentId::new)
-            .collect();
+        if args.magic != Magic::Demand {
+            // Everything that we load goes in a single segment and since the headers aren't
+            // loaded, there's nothing for PT_PHDR to describe.
+            keep_segments[LOAD_RO.as_usize()] |=
+                keep_segments[LOAD_EXEC.as_usize()] || keep_segments[LOAD_RW.as_usize()];
+            keep_segments[LOAD_EXEC.as_usize()] = false;
+            keep_segments[LOAD_RW.as_usize()] = false;
+            keep_segments[PHDR.as_usize()] = false;
+        }
+        keep_segments[GNU_RELRO.as_usize()] &= output_sections.relro;
+        output_sections.relro = keep_segments[GNU_RELRO.as_usize()];
+        // Relocatable objects don't have program headers.
+        let active_segment_ids = if is_relocatable_output {
+            Vec::new()
+        } else {
+            (0..crate::program_segments::MAX_SEGMENTS)
+                .filter(|i| keep_segments[*i] || i == &STACK.as_usize())
+                .map(ProgramSegmentId::new)
+                .collect()
+        };
+        if output_sections.script_layout && output_sections.script_program_headers.is_empty() {
+            let section_headers_size = if args.strip_section_headers {
+                0
+            } else {
+                u64::from(elf::SECTION_HEADER_SIZE) * num_sections as u64
+            };
+            output_sections.synthesize_program_headers(
+                &keep_sections,
+                &active_segment_ids,
+                |num_program_headers| {
+                    u64::from(elf::FILE_HEADER_SIZE)
+                        + u64::from(elf::PROGRAM_HEADER_SIZE) * num_program_headers as u64
+                        + section_headers_size
+                },
+            );
+        }
+
+        let num_program_headers = if output_sections.script_program_headers.is_empty() {
+            active_segment_ids.len()
+        } else {
+            output_sections.script_program_headers.len()
+        };
 
         let header_info = HeaderInfo {
             num_output_sections_with_content: num_sections
@@ -2754,6 +5082,7 @@ impl PreludeLayoutState {
                 .expect("output section count must fit in a u16"),
 
             active_segment_ids,
+            num_program_headers,
         };
 
         // Allocate space for headers based on segment and section counts.
@@ -2761,13 +5090,23 @@ impl PreludeLayoutState {
 
         extra_sizes.increment(part_id::FILE_HEADER, u64::from(elf::FILE_HEADER_SIZE));
         extra_sizes.increment(part_id::PROGRAM_HEADERS, header_info.program_headers_size());
-        extra_sizes.increment(part_id::SECTION_HEADERS, header_info.section_headers_size());
-        self.shstrtab_size = output_sections
-            .ids_with_info()
-            .filter(|(id, _info)| output_sections.output_index_of_section(*id).is_some())
-            .map(|(_id, info)| info.name.len() as u64 + 1)
-            .sum::<u64>();
+        if !args.strip_section_headers {
+            extra_sizes.increment(part_id::SECTION_HEADERS, header_info.section_headers_size());
+            self.shstrtab_size = output_sections
+                .ids_with_info()
+                .filter(|(id, _info)| output_sections.output_index_of_section(*id).is_some())
+                .map(|(_id, info)| info.name.len() as u64 + 1)
+                .sum::<u64>();
+        }
         extra_sizes.increment(part_id::SHSTRTAB, self.shstrtab_size);
+        if args.keeps_input_relocations() {
+            // Each output section other than the null section gets a section symbol whose index
+            // in the symbol table is the same as the index of the section.
+            extra_sizes.increment(
+                part_id::SYMTAB_LOCAL,
+                (num_sections as u64 - 1) * size_of::<elf::SymtabEntry>() as u64,
+            );
+        }
 
         // We need to allocate both our own size record and the file totals, since they've already
         // been computed.
@@ -2788,6 +5127,22 @@ impl PreludeLayoutState {
             .get(output_section_id::FILE_HEADER);
         assert_eq!(header_layout.file_offset, 0);
 
+        // The prelude comes first, so the reserved GOT entries end up at the start of .got, which
+        // is where DT_PLTGOT points.
+        let lazy_plt_header = resources.symbol_db.args.lazy_binding().then(|| {
+            let got_address = *memory_offsets.get(part_id::GOT);
+            memory_offsets.increment(
+                part_id::GOT,
+                elf::GOT_ENTRY_SIZE * elf::NUM_LAZY_GOT_RESERVED_ENTRIES,
+            );
+            let plt_address = *memory_offsets.get(part_id::PLT_GOT);
+            memory_offsets.increment(part_id::PLT_GOT, elf::PLT_HEADER_SIZE);
+            LazyPltHeader {
+                got_address,
+                plt_address,
+            }
+        });
+
         let tlsld_got_entry = self.needs_tlsld_got_entry.then(|| {
             let address = NonZeroU64::new(*memory_offsets.get(part_id::GOT))
                 .expect("GOT address must never be zero");
@@ -2820,6 +5175,7 @@ impl PreludeLayoutState {
             internal_symbols: self.internal_symbols,
             entry_symbol_id: self.entry_symbol_id,
             tlsld_got_entry,
+            lazy_plt_header,
             identity: self.identity,
             dynamic_linker: self.dynamic_linker,
             header_info: self
@@ -2907,6 +5263,26 @@ fn create_start_end_symbol_resolution<S: StorageModel>(
             let sec = resources.section_layouts.get(section_id);
             (sec.mem_offset + sec.mem_size, ValueFlags::ADDRESS)
         }
+        InternalSymDefInfo::GotBase => (
+            resources
+                .section_layouts
+                .get(output_section_id::GOT)
+                .mem_offset
+                + resources.symbol_db.args.arch().got_base_offset(),
+            ValueFlags::ADDRESS,
+        ),
+        InternalSymDefInfo::GlobalPointer => (
+            resources
+                .section_layouts
+                .get(output_section_id::DATA)
+                .mem_offset
+                + linker_utils::riscv64::GLOBAL_POINTER_OFFSET,
+            ValueFlags::ADDRESS,
+        ),
+        InternalSymDefInfo::ScriptAssignment(index) => (
+            resources.script_symbol_values[index as usize],
+            resources.symbol_db.local_symbol_value_flags(symbol_id),
+        ),
     };
 
     Some(create_resolution(
@@ -2915,6 +5291,7 @@ fn create_start_end_symbol_resolution<S: StorageModel>(
         None,
         value_flags,
         memory_offsets,
+        resources.plt_layout,
     ))
 }
 
@@ -2947,6 +5324,8 @@ impl<'data> EpilogueLayoutState<'data> {
             BuildIdOption::Hex(hex) => Some(hex.len()),
             BuildIdOption::Uuid => Some(size_of::<uuid::Uuid>()),
         };
+        self.memory_seal = resources.symbol_db.args.memory_seal;
+        self.debuglink_size = crate::separate_debug::debuglink_size(resources.symbol_db.args);
     }
 
     fn new(
@@ -2966,17 +5345,26 @@ impl<'data> EpilogueLayoutState<'data> {
             dynamic_symbol_definitions: Default::default(),
             gnu_hash_layout: None,
             gnu_property_notes: Default::default(),
+            pauth_core_info: None,
+            memory_seal: false,
             build_id_size: Default::default(),
+            build_attributes: Vec::new(),
+            arch_attributes: Vec::new(),
+            debuglink_size: 0,
+            version_definitions_size: 0,
         }
     }
 
     fn gnu_property_notes_section_size(&self) -> u64 {
-        if self.gnu_property_notes.is_empty() {
+        let descriptor_size = gnu_property_notes_descriptor_size(
+            &self.gnu_property_notes,
+            self.pauth_core_info.is_some(),
+            self.memory_seal,
+        );
+        if descriptor_size == 0 {
             0
         } else {
-            (size_of::<NoteHeader>()
-                + GNU_NOTE_NAME.len()
-                + self.gnu_property_notes.len() * GNU_NOTE_PROPERTY_ENTRY_SIZE) as u64
+            (size_of::<NoteHeader>() + GNU_NOTE_NAME.len() + descriptor_size) as u64
         }
     }
 
@@ -3026,6 +5414,8 @@ impl<'data> EpilogueLayoutState<'data> {
                 part_id::DYNSYM,
                 (self.dynamic_symbol_definitions.len() * size_of::<elf::SymtabEntry>()) as u64,
             );
+
+            self.allocate_version_definitions(common, symbol_db);
         }
 
         common.allocate(
@@ -3037,9 +5427,51 @@ impl<'data> EpilogueLayoutState<'data> {
             common.allocate(part_id::NOTE_GNU_BUILD_ID, build_id_sec_size);
         }
 
+        common.allocate(
+            part_id::GNU_BUILD_ATTRIBUTES,
+            build_attributes::encoded_size(&self.build_attributes),
+        );
+
+        common.allocate(part_id::RISCV_ATTRIBUTES, self.arch_attributes.len() as u64);
+        common.allocate(part_id::GNU_DEBUGLINK, self.debuglink_size);
+
         Ok(())
     }
 
+    /// Allocates space for .gnu.version_d and for the names of the versions that it defines. Each
+    /// definition has an auxiliary entry for its own name, followed by one for each version that it
+    /// depends on.
+    fn allocate_version_definitions<S: StorageModel>(
+        &mut self,
+        common: &mut CommonGroupState,
+        symbol_db: &SymbolDb<'data, S>,
+    ) {
+        let num_definitions = num_version_definitions(symbol_db);
+        if num_definitions == 0 {
+            return;
+        }
+        let versions = symbol_db.version_script.named_versions();
+        // Unlike the versions from our version script, the base definition and any default version
+        // don't have parents.
+        let num_auxes = num_definitions
+            + versions
+                .iter()
+                .map(|version| version.parents.len())
+                .sum::<usize>();
+        self.version_definitions_size = (num_definitions * size_of::<elf::Verdef>()
+            + num_auxes * size_of::<elf::Verdaux>()) as u64;
+        common.allocate(part_id::GNU_VERSION_D, self.version_definitions_size);
+        let base_name_size = base_version_name(symbol_db.args).len() + 1;
+        common.allocate(
+            part_id::DYNSTR,
+            (base_name_size * (1 + usize::from(symbol_db.args.default_symver))
+                + versions
+                    .iter()
+                    .map(|version| version.name.unwrap_or_default().len() + 1)
+                    .sum::<usize>()) as u64,
+        );
+    }
+
     /// Allocates space required for .gnu.hash. Also sorts dynamic symbol definitions by their hash
     /// bucket as required by .gnu.hash.
     fn allocate_gnu_hash(&mut self, common: &mut CommonGroupState) {
@@ -3105,24 +5537,64 @@ impl<'data> EpilogueLayoutState<'data> {
             memory_offsets.increment(part_id::NOTE_GNU_BUILD_ID, build_id_sec_size);
         }
 
+        memory_offsets.increment(
+            part_id::GNU_BUILD_ATTRIBUTES,
+            build_attributes::encoded_size(&self.build_attributes),
+        );
+
+        memory_offsets.increment(part_id::RISCV_ATTRIBUTES, self.arch_attributes.len() as u64);
+        memory_offsets.increment(part_id::GNU_DEBUGLINK, self.debuglink_size);
+        memory_offsets.increment(part_id::GNU_VERSION_D, self.version_definitions_size);
+
         Ok(EpilogueLayout {
             internal_symbols: self.internal_symbols,
             gnu_hash_layout: self.gnu_hash_layout,
             dynamic_symbol_definitions: self.dynamic_symbol_definitions,
             dynsym_start_index,
             gnu_property_notes: self.gnu_property_notes,
+            pauth_core_info: self.pauth_core_info,
+            memory_seal: self.memory_seal,
+            build_attributes: self.build_attributes,
+            arch_attributes: self.arch_attributes,
         })
     }
 }
 
+/// Returns the size of the descriptor of the .note.gnu.property note that we'll write. The memory
+/// seal property, if present, has no data, so is smaller than the other properties, while the PAuth
+/// core info is larger.
+pub(crate) fn gnu_property_notes_descriptor_size(
+    gnu_property_notes: &[GnuProperty],
+    has_pauth_core_info: bool,
+    memory_seal: bool,
+) -> usize {
+    let memory_seal_size = if memory_seal {
+        GNU_NOTE_PROPERTY_HEADER_SIZE
+    } else {
+        0
+    };
+    let pauth_core_info_size = if has_pauth_core_info {
+        elf::GNU_NOTE_PAUTH_CORE_INFO_SIZE
+    } else {
+        0
+    };
+    gnu_property_notes.len() * GNU_NOTE_PROPERTY_ENTRY_SIZE
+        + memory_seal_size
+        + pauth_core_info_size
+}
+
 pub(crate) struct HeaderInfo {
     pub(crate) num_output_sections_with_content: u16,
     pub(crate) active_segment_ids: Vec<ProgramSegmentId>,
+
+    /// The number of program headers that we'll write. This is the number of active segments,
+    /// unless a linker script defined our program headers.
+    pub(crate) num_program_headers: usize,
 }
 
 impl HeaderInfo {
     pub(crate) fn program_headers_size(&self) -> u64 {
-        u64::from(elf::PROGRAM_HEADER_SIZE) * self.active_segment_ids.len() as u64
+        u64::from(elf::PROGRAM_HEADER_SIZE) * self.num_program_headers as u64
     }
 
     pub(crate) fn section_headers_size(&self) -> u64 {
@@ -3149,6 +5621,13 @@ fn new_object_layout_state(input_state: resolution::ResolvedObject) -> FileLayou
             sections_required: Default::default(),
             cies: Default::default(),
             gnu_property_notes: Default::default(),
+            pauth_core_info: None,
+            split_stack: Default::default(),
+            thunks: Default::default(),
+            relaxations: Default::default(),
+            memtag_globals_size: 0,
+            build_attributes: Vec::new(),
+            arch_attributes: &[],
         })
     } else {
         FileLayoutState::Dynamic(DynamicLayoutState {
@@ -3161,16 +5640,18 @@ fn new_object_layout_state(input_state: resolution::ResolvedObject) -> FileLayou
 
             // These fields are filled in properly when we activate.
             symbol_versions_needed: Default::default(),
+            needs_indirect_extern_access: false,
 
             // These fields are filled in when we finalise sizes.
             verdef_info: None,
+            has_default_version: false,
             non_addressable_indexes: Default::default(),
         })
     }
 }
 
 impl<'data> ObjectLayoutState<'data> {
-    fn activate<'scope, S: StorageModel, A: Arch>(
+    fn activate<'scope, S: StorageModel>(
         &mut self,
         common: &mut CommonGroupState<'data>,
         resources: &GraphResources<'data, 'scope, S>,
@@ -3200,7 +5681,7 @@ impl<'data> ObjectLayoutState<'data> {
             }
         }
         if let Some(eh_frame_section_index) = eh_frame_section {
-            process_eh_frame_data::<S, A>(
+            process_eh_frame_data::<S>(
                 self,
                 common,
                 self.symbol_id_range(),
@@ -3215,14 +5696,46 @@ impl<'data> ObjectLayoutState<'data> {
             process_gnu_property_note(self, note_gnu_property_index)?;
         }
 
-        if resources.symbol_db.args.output_kind() == OutputKind::SharedObject {
-            self.load_non_hidden_symbols::<S, A>(common, resources, queue)?;
+        self.build_attributes = crate::build_attributes::parse_object(self.object, self.file_id)?;
+        self.arch_attributes =
+            crate::arch_attributes::section_data(self.object, resources.symbol_db.args.arch())?;
+
+        if crate::memtag::is_enabled(resources.symbol_db.args) {
+            let num_tagged = crate::memtag::tagged_symbols(self.object)?.len() as u64;
+            self.memtag_globals_size = num_tagged * crate::memtag::MAX_DESCRIPTOR_SIZE;
+            common.allocate(part_id::MEMTAG_GLOBALS_DYNAMIC, self.memtag_globals_size);
+        }
+
+        self.split_stack = SplitStack::from_object(self.object)?;
+        if self.split_stack.uses_split_stack
+            && matches!(resources.symbol_db.args.arch(), Architecture::X86_64)
+        {
+            // We might need to redirect calls from __morestack to __morestack_non_split when we
+            // write, so make sure that the latter has a resolution.
+            if let Some(symbol_id) = resources.symbol_db.global_names.get(&SymbolName::prehashed(
+                crate::split_stack::MORESTACK_NON_SPLIT_SYMBOL_NAME,
+            )) {
+                let old_flags = resources.symbol_resolution_flags[symbol_id.as_usize()]
+                    .fetch_or(ResolutionFlags::DIRECT);
+                if old_flags.is_empty() {
+                    let file_id = resources.symbol_db.file_id_for_symbol(symbol_id);
+                    queue.send_work(resources, file_id, WorkItem::LoadGlobalSymbol(symbol_id));
+                }
+            }
+        }
+
+        // Symbols from images named by `--just-symbols` aren't ours to export, although they may
+        // still need to be dynamic if we reference them.
+        if resources.symbol_db.args.output_kind() == OutputKind::SharedObject
+            && !self.object.just_symbols
+        {
+            self.load_non_hidden_symbols::<S>(common, resources, queue)?;
         }
-        self.load_sections::<S, A>(common, resources, queue)
+        self.load_sections::<S>(common, resources, queue)
     }
 
     /// Loads sections in `sections_required` (which may be empty).
-    fn load_sections<'scope, S: StorageModel, A: Arch>(
+    fn load_sections<'scope, S: StorageModel>(
         &mut self,
         common: &mut CommonGroupState<'data>,
         resources: &GraphResources<'data, 'scope, S>,
@@ -3234,11 +5747,17 @@ impl<'data> ObjectLayoutState<'data> {
             let section_id = section_request.id;
             match &self.sections[section_id.0] {
                 SectionSlot::Unloaded(unloaded) | SectionSlot::MustLoad(unloaded) => {
-                    self.load_section::<S, A>(common, queue, *unloaded, section_id, resources)?;
+                    self.load_section::<S>(common, queue, *unloaded, section_id, resources)?;
                 }
                 SectionSlot::UnloadedDebugInfo(part_id) => {
                     self.load_debug_section(common, *part_id, section_id)?;
                 }
+                SectionSlot::Discard
+                    if resources.symbol_db.args.output_kind() == OutputKind::RelocatableObject =>
+                {
+                    // The section was a member of a duplicate COMDAT group. References to it, e.g.
+                    // from `.eh_frame`, get dropped when we copy the relocations.
+                }
                 SectionSlot::Discard => {
                     bail!(
                         "{self}: Don't know what segment to put `{}` in, but it's referenced",
@@ -3258,7 +5777,7 @@ impl<'data> ObjectLayoutState<'data> {
         Ok(())
     }
 
-    fn load_section<'scope, S: StorageModel, A: Arch>(
+    fn load_section<'scope, S: StorageModel>(
         &mut self,
         common: &mut CommonGroupState<'data>,
         queue: &mut LocalWorkQueue,
@@ -3274,7 +5793,7 @@ impl<'data> ObjectLayoutState<'data> {
                 modifier = RelocationModifier::Normal;
                 continue;
             }
-            modifier = process_relocation::<S, A>(
+            modifier = process_relocation::<S>(
                 self,
                 common,
                 rel,
@@ -3291,7 +5810,7 @@ impl<'data> ObjectLayoutState<'data> {
             .get(part_id.output_section_id())
             .fetch_or(true, atomic::Ordering::Relaxed);
 
-        self.process_section_exception_frames::<S, A>(
+        self.process_section_exception_frames::<S>(
             unloaded.last_frame_index,
             common,
             resources,
@@ -3300,11 +5819,19 @@ impl<'data> ObjectLayoutState<'data> {
 
         self.sections[section_id.0] = SectionSlot::Loaded(section);
 
+        if resources.symbol_db.args.emit_relocs {
+            if let Some(rela_index) = self.object.relocations.get(section_id) {
+                if let SectionSlot::Unloaded(rela) = self.sections[rela_index.0] {
+                    self.load_section::<S>(common, queue, rela, rela_index, resources)?;
+                }
+            }
+        }
+
         Ok(())
     }
 
     /// Processes the exception frames for a section that we're loading.
-    fn process_section_exception_frames<S: StorageModel, A: Arch>(
+    fn process_section_exception_frames<S: StorageModel>(
         &mut self,
         frame_index: Option<FrameIndex>,
         common: &mut CommonGroupState<'data>,
@@ -3327,14 +5854,7 @@ impl<'data> ObjectLayoutState<'data> {
             // section.
             if let Some(eh_frame_section) = self.eh_frame_section {
                 for rel in frame_data_relocations {
-                    process_relocation::<S, A>(
-                        self,
-                        common,
-                        rel,
-                        eh_frame_section,
-                        resources,
-                        queue,
-                    )?;
+                    process_relocation::<S>(self, common, rel, eh_frame_section, resources, queue)?;
                 }
             }
         }
@@ -3369,6 +5889,7 @@ impl<'data> ObjectLayoutState<'data> {
         symbol_db: &SymbolDb<'data, S>,
         output_sections: &OutputSections,
         symbol_resolution_flags: &[AtomicResolutionFlags],
+        plt_layout: PltLayout,
     ) {
         common.mem_sizes.resize(output_sections.num_parts());
         if !symbol_db.args.strip_all {
@@ -3382,6 +5903,7 @@ impl<'data> ObjectLayoutState<'data> {
                     section.resolution_kind,
                     &mut common.mem_sizes,
                     output_kind,
+                    plt_layout,
                 );
             }
         }
@@ -3423,7 +5945,7 @@ impl<'data> ObjectLayoutState<'data> {
                 // If we've decided to emit the symbol even though it's not referenced (because it's
                 // in a section we're emitting), then make sure we have a resolution for it.
                 sym_state.fetch_or(ResolutionFlags::DIRECT);
-                if sym.is_local() {
+                if info.is_local {
                     num_locals += 1;
                 } else {
                     num_globals += 1;
@@ -3437,6 +5959,158 @@ impl<'data> ObjectLayoutState<'data> {
         common.allocate(part_id::STRTAB, strings_size as u64);
     }
 
+    /// Returns the addresses that our sections will be given by `finalise_layout` if sizes don't
+    /// change in the meantime.
+    fn simulate_section_addresses(
+        &self,
+        memory_offsets: &mut OutputSectionPartMap<u64>,
+    ) -> Vec<Option<u64>> {
+        self.sections
+            .iter()
+            .map(|slot| match slot {
+                SectionSlot::Loaded(sec) | SectionSlot::LoadedDebugInfo(sec) => {
+                    let address = *memory_offsets.get(sec.part_id);
+                    memory_offsets.increment(sec.part_id, sec.capacity());
+                    Some(address)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Decides which bytes to delete from our executable sections. Sections for which the decision
+    /// differs from what was previously decided are added to `found`.
+    fn plan_relaxations<S: StorageModel>(
+        &self,
+        file_index: usize,
+        planner: &RelaxationPlanner<'_, 'data, S>,
+        found: &mut Vec<(usize, object::SectionIndex, SectionRelaxations)>,
+    ) -> Result {
+        let e = LittleEndian;
+        let section_addresses =
+            &planner.section_addresses[self.file_id.group()][self.file_id.file()];
+        // c.j is only used in code that could already contain compressed instructions.
+        let options = PlanOptions {
+            compressed: FileHeader::parse(self.object.data)?.e_flags(e) & object::elf::EF_RISCV_RVC
+                != 0,
+            ..planner.options
+        };
+        for (slot, section_address) in self.sections.iter().zip(section_addresses) {
+            let (SectionSlot::Loaded(section), Some(section_address)) = (slot, section_address)
+            else {
+                continue;
+            };
+            let object_section = self.object.section(section.index)?;
+            if !SectionFlags::from_header(object_section).contains(shf::EXECINSTR) {
+                continue;
+            }
+            let previous = self.relaxations.get(section.index);
+            let plan = riscv64_relaxation::plan_section(
+                self.object.relocations(section.index)?,
+                self.object.raw_section_data(object_section)?,
+                *section_address,
+                section.alignment().value(),
+                previous,
+                options,
+                &SectionTargets {
+                    planner,
+                    object: self,
+                    section_index: section.index,
+                },
+            )
+            .with_context(|| {
+                format!(
+                    "Failed to relax section `{}` of {}",
+                    self.object.section_display_name(section.index),
+                    self.input
+                )
+            })?;
+            if previous.map_or(!plan.is_empty(), |previous| *previous != plan) {
+                found.push((file_index, section.index, plan));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds branches in our executable sections that might not be able to reach their targets
+    /// and that don't already go via a thunk, as well as instructions that need to be moved to
+    /// work around CPU errata.
+    fn find_new_thunks<S: StorageModel>(
+        &self,
+        file_index: usize,
+        planner: &ThunkPlanner<S>,
+        found: &mut Vec<(usize, NewThunk)>,
+    ) -> Result {
+        let e = LittleEndian;
+        let arch = planner.symbol_db.args.arch();
+        let section_addresses =
+            &planner.section_addresses[self.file_id.group()][self.file_id.file()];
+        let code_ranges = planner
+            .errata_fixes
+            .map(|_| aarch64_errata::code_ranges(self.object))
+            .transpose()?;
+        let mut targets = HashSet::new();
+        for (slot, section_address) in self.sections.iter().zip(section_addresses) {
+            let (SectionSlot::Loaded(section), Some(section_address)) = (slot, section_address)
+            else {
+                continue;
+            };
+            let object_section = self.object.section(section.index)?;
+            if !SectionFlags::from_header(object_section).contains(shf::EXECINSTR) {
+                continue;
+            }
+            if let (Some(fixes), Some(code_ranges)) = (planner.errata_fixes, &code_ranges) {
+                aarch64_errata::find_instructions_to_move(
+                    fixes,
+                    self.object.raw_section_data(object_section)?,
+                    &code_ranges[section.index.0],
+                    *section_address,
+                    |instruction_offset| {
+                        if !self
+                            .thunks
+                            .is_instruction_moved(section.index, instruction_offset)
+                        {
+                            found.push((
+                                file_index,
+                                NewThunk::MovedInstruction {
+                                    section_index: section.index,
+                                    instruction_offset,
+                                },
+                            ));
+                        }
+                    },
+                );
+            }
+            if !planner.check_branches {
+                continue;
+            }
+            for rel in self.object.relocations(section.index)? {
+                let Some(range) = arch.branch_range(rel.r_type(e, false)) else {
+                    continue;
+                };
+                let Some(symbol_index) = rel.symbol(e, false) else {
+                    continue;
+                };
+                let target = ThunkTarget {
+                    section_index: section.index,
+                    symbol_id: planner
+                        .symbol_db
+                        .definition(self.symbol_id_range.input_to_id(symbol_index)),
+                    addend: rel.r_addend.get(e),
+                };
+                if self.thunks.get(&target).is_some() || targets.contains(&target) {
+                    continue;
+                }
+                let place = section_address + rel.r_offset.get(e);
+                if planner.might_be_out_of_range(place, target.symbol_id, target.addend, range)? {
+                    targets.insert(target);
+                    found.push((file_index, NewThunk::Branch(target)));
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn finalise_layout<S: StorageModel>(
         mut self,
         memory_offsets: &mut OutputSectionPartMap<u64>,
@@ -3495,6 +6169,7 @@ impl<'data> ObjectLayoutState<'data> {
         }
 
         memory_offsets.increment(part_id::EH_FRAME, self.eh_frame_size);
+        memory_offsets.increment(part_id::MEMTAG_GLOBALS_DYNAMIC, self.memtag_globals_size);
 
         Ok(ObjectLayout {
             input: self.input,
@@ -3503,6 +6178,9 @@ impl<'data> ObjectLayoutState<'data> {
             sections: self.sections,
             section_resolutions,
             symbol_id_range,
+            split_stack: self.split_stack,
+            thunks: self.thunks,
+            relaxations: self.relaxations,
         })
     }
 
@@ -3555,7 +6233,9 @@ impl<'data> ObjectLayoutState<'data> {
             .symbol_section(local_symbol, local_symbol_index)?
         {
             if let Some(section_address) = section_resolutions[section_index.0].address() {
-                local_symbol.st_value(e) + section_address
+                self.relaxations
+                    .output_offset(section_index, local_symbol.st_value(e))
+                    + section_address
             } else {
                 match get_merged_string_output_address(
                     local_symbol_index,
@@ -3573,6 +6253,13 @@ impl<'data> ObjectLayoutState<'data> {
                         if resources.symbol_db.is_mapping_symbol(symbol_id) {
                             return Ok(None);
                         }
+                        // Nor for symbols in the duplicate COMDAT groups that we discard when
+                        // producing a relocatable object. References to them get dropped.
+                        if resources.symbol_db.args.output_kind() == OutputKind::RelocatableObject
+                            && matches!(self.sections[section_index.0], SectionSlot::Discard)
+                        {
+                            return Ok(None);
+                        }
                         bail!(
                             "Symbol is in a section that we didn't load. \
                              Symbol: {} Section: {} Res: {resolution_flags}",
@@ -3582,8 +6269,12 @@ impl<'data> ObjectLayoutState<'data> {
                     }
                 }
             }
-        } else if local_symbol.is_common(e) {
-            let common = CommonSymbol::new(local_symbol)?;
+        } else if local_symbol.is_common(e)
+            && resources.symbol_db.args.output_kind() != OutputKind::RelocatableObject
+        {
+            // Relocatable outputs keep common symbols as they are, leaving allocation to the final
+            // link.
+            let common = CommonSymbol::new(local_symbol, symbol_id, resources.symbol_db)?;
             let offset = memory_offsets
                 .get_mut(output_section_id::BSS.part_id_with_alignment(common.alignment));
             let address = *offset;
@@ -3610,10 +6301,11 @@ impl<'data> ObjectLayoutState<'data> {
             dynamic_symbol_index,
             value_flags,
             memory_offsets,
+            resources.plt_layout,
         )))
     }
 
-    fn load_non_hidden_symbols<'scope, S: StorageModel, A: Arch>(
+    fn load_non_hidden_symbols<'scope, S: StorageModel>(
         &mut self,
         common: &mut CommonGroupState<'data>,
         resources: &GraphResources<'data, 'scope, S>,
@@ -3622,14 +6314,24 @@ impl<'data> ObjectLayoutState<'data> {
         for (sym_index, sym) in self.object.symbols.enumerate() {
             if can_export_symbol(sym) {
                 let symbol_id = self.symbol_id_range().input_to_id(sym_index);
+                // Another object's definition was selected, e.g. of a weak or unique symbol that
+                // both of us define. That object will export it.
+                if !resources.symbol_db.is_canonical(symbol_id) {
+                    continue;
+                }
                 let value_flags = resources.symbol_db.local_symbol_value_flags(symbol_id);
                 if value_flags.contains(ValueFlags::DOWNGRADE_TO_LOCAL) {
                     continue;
                 }
+                if sym.st_bind() == object::elf::STB_GNU_UNIQUE {
+                    resources
+                        .has_unique_symbols
+                        .store(true, atomic::Ordering::Relaxed);
+                }
                 let old_flags = resources.symbol_resolution_flags[symbol_id.as_usize()]
                     .fetch_or(ResolutionFlags::EXPORT_DYNAMIC);
                 if old_flags.is_empty() {
-                    self.load_symbol::<A>(common, symbol_id, resources, queue)?;
+                    self.load_symbol(common, symbol_id, resources, queue)?;
                 }
                 if !old_flags.contains(ResolutionFlags::EXPORT_DYNAMIC) {
                     export_dynamic(common, symbol_id, resources)?;
@@ -3642,12 +6344,16 @@ impl<'data> ObjectLayoutState<'data> {
 
 pub(crate) struct SymbolCopyInfo<'data> {
     pub(crate) name: &'data [u8],
+
+    /// Whether the symbol goes in the local part of the symbol table. This includes globals that
+    /// we've downgraded to locals, e.g. because of a version script or `--exclude-libs`.
+    pub(crate) is_local: bool,
 }
 
 impl<'data> SymbolCopyInfo<'data> {
     /// The primary purpose of this function is to determine whether a symbol should be copied into
     /// the symtab. In the process, we also return the name of the symbol, to avoid needing to read
-    /// it again.
+    /// it again, and whether it's local.
     pub(crate) fn new<S: StorageModel>(
         object: &crate::elf::File<'data>,
         sym_index: object::SymbolIndex,
@@ -3658,7 +6364,19 @@ impl<'data> SymbolCopyInfo<'data> {
         sections: &[SectionSlot],
     ) -> Option<SymbolCopyInfo<'data>> {
         let e = LittleEndian;
-        if !symbol_db.is_canonical(symbol_id) || sym.is_undefined(e) {
+        let is_relocatable_output = symbol_db.args.output_kind() == OutputKind::RelocatableObject;
+        let keeps_input_relocations = symbol_db.args.keeps_input_relocations();
+        if !symbol_db.is_canonical(symbol_id) {
+            return None;
+        }
+        // Relocatable outputs need to list the symbols that they reference, but don't define, so
+        // that the final link can resolve them. Relocations that we copy with `--emit-relocs` may
+        // refer to undefined weak symbols, so they need listing too.
+        if sym.is_undefined(e) && (!keeps_input_relocations || sym.is_local()) {
+            return None;
+        }
+        // As with other linkers, we only import the globals of images named by `--just-symbols`.
+        if object.just_symbols && (sym.is_local() || sym.is_undefined(e)) {
             return None;
         }
 
@@ -3669,7 +6387,7 @@ impl<'data> SymbolCopyInfo<'data> {
             }
         }
 
-        if sym.is_common(e) && symbol_state.is_empty() {
+        if sym.is_common(e) && symbol_state.is_empty() && !is_relocatable_output {
             return None;
         }
 
@@ -3677,14 +6395,32 @@ impl<'data> SymbolCopyInfo<'data> {
         // checks. That's also the reason why we return the symbol name, so that the caller, if it
         // needs the name, doesn't have a go and read it again.
         let name = object.symbol_name(sym).ok()?;
-        if name.is_empty()
-            || (sym.is_local() && name.starts_with(b".L"))
-            || is_mapping_symbol_name(name)
-        {
+        if name.is_empty() {
+            return None;
+        }
+        let is_discarded_local = sym.is_local()
+            && match symbol_db.args.discard_locals {
+                DiscardLocals::None => false,
+                DiscardLocals::Temporary => name.starts_with(b".L"),
+                DiscardLocals::All => true,
+            };
+        if is_relocatable_output {
+            // Mapping symbols are needed by the final link. Discarded locals normally aren't, but
+            // some relocations, e.g. RISC-V's PCREL_LO12, need to refer to the label on the
+            // instruction that they're paired with.
+            if is_discarded_local && symbol_state.is_empty() {
+                return None;
+            }
+        } else if is_discarded_local || is_mapping_symbol_name(name) {
             return None;
         }
 
-        Some(SymbolCopyInfo { name })
+        let is_local = sym.is_local()
+            || (!is_relocatable_output
+                && symbol_db
+                    .local_symbol_value_flags(symbol_id)
+                    .contains(ValueFlags::DOWNGRADE_TO_LOCAL));
+        Some(SymbolCopyInfo { name, is_local })
     }
 }
 
@@ -3696,7 +6432,7 @@ pub(crate) fn can_export_symbol(sym: &crate::elf::SymtabEntry) -> bool {
         && (visibility == object::elf::STV_DEFAULT || visibility == object::elf::STV_PROTECTED)
 }
 
-fn process_eh_frame_data<'data, S: StorageModel, A: Arch>(
+fn process_eh_frame_data<'data, S: StorageModel>(
     object: &mut ObjectLayoutState<'data>,
     common: &mut CommonGroupState<'data>,
     file_symbol_id_range: SymbolIdRange,
@@ -3739,14 +6475,7 @@ fn process_eh_frame_data<'data, S: StorageModel, A: Arch>(
                 }
                 // We currently always load all CIEs, so any relocations found in CIEs always need
                 // to be processed.
-                process_relocation::<S, A>(
-                    object,
-                    common,
-                    rel,
-                    eh_frame_section,
-                    resources,
-                    queue,
-                )?;
+                process_relocation::<S>(object, common, rel, eh_frame_section, resources, queue)?;
                 if let Some(local_sym_index) = rel.symbol(e, false) {
                     let local_symbol_id = file_symbol_id_range.input_to_id(local_sym_index);
                     let definition = resources.symbol_db.definition(local_symbol_id);
@@ -3832,6 +6561,21 @@ fn process_gnu_property_note(
         {
             let gnu_property = gnu_property?;
 
+            if gnu_property.pr_type() == GNU_PROPERTY_AARCH64_FEATURE_PAUTH {
+                let data = gnu_property.pr_data();
+                if data.len() != 16 {
+                    bail!(
+                        "Invalid AArch64 PAuth core info property of size {}",
+                        data.len()
+                    );
+                }
+                object.pauth_core_info = Some(PauthCoreInfo {
+                    platform: u64::from_le_bytes(data[..8].try_into().unwrap()),
+                    version: u64::from_le_bytes(data[8..].try_into().unwrap()),
+                });
+                continue;
+            }
+
             // Right now, skip all properties other than the with size equal to 4.
             // There are existing properties, but unused right now:
             // GNU_PROPERTY_STACK_SIZE, GNU_PROPERTY_NO_COPY_ON_PROTECTED
@@ -3849,6 +6593,38 @@ fn process_gnu_property_note(
     Ok(())
 }
 
+/// Returns whether a shared object's .note.gnu.property has `GNU_PROPERTY_1_NEEDED` with the
+/// `GNU_PROPERTY_1_NEEDED_INDIRECT_EXTERN_ACCESS` bit set.
+fn needs_indirect_extern_access(object: &File) -> Result<bool> {
+    let e = LittleEndian;
+    for section in object.sections.iter() {
+        if section.sh_type(e) != object::elf::SHT_NOTE
+            || object.section_name(section)? != b".note.gnu.property"
+        {
+            continue;
+        }
+        let Some(notes) = section.notes(e, object.data)? else {
+            continue;
+        };
+        for note in notes {
+            let Some(gnu_properties) = note?.gnu_properties(e) else {
+                continue;
+            };
+            for gnu_property in gnu_properties {
+                let gnu_property = gnu_property?;
+                if gnu_property.pr_type() == object::elf::GNU_PROPERTY_1_NEEDED
+                    && gnu_property.data_u32(e)?
+                        & object::elf::GNU_PROPERTY_1_NEEDED_INDIRECT_EXTERN_ACCESS
+                        != 0
+                {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
 /// A "common information entry". This is part of the .eh_frame data in ELF.
 #[derive(PartialEq, Eq, Hash)]
 struct Cie<'data> {
@@ -3872,12 +6648,22 @@ struct CommonSymbol {
 }
 
 impl CommonSymbol {
-    fn new(local_symbol: &crate::elf::SymtabEntry) -> Result<CommonSymbol> {
+    fn new<S: StorageModel>(
+        local_symbol: &crate::elf::SymtabEntry,
+        symbol_id: SymbolId,
+        symbol_db: &SymbolDb<S>,
+    ) -> Result<CommonSymbol> {
         let e = LittleEndian;
         debug_assert!(local_symbol.is_common(e));
         // Common symbols misuse the value field (which we access via `address()`) to store the
-        // alignment.
-        let alignment = Alignment::new(local_symbol.st_value(e))?;
+        // alignment. Other common symbols with the same name may have required more.
+        let alignment = Alignment::new(
+            symbol_db
+                .common_alignments
+                .get(&symbol_id)
+                .copied()
+                .unwrap_or(local_symbol.st_value(e)),
+        )?;
         let size = alignment.align_up(local_symbol.st_size(e));
         Ok(CommonSymbol { size, alignment })
     }
@@ -3904,6 +6690,7 @@ fn create_resolution(
     dynamic_symbol_index: Option<NonZeroU32>,
     value_flags: ValueFlags,
     memory_offsets: &mut OutputSectionPartMap<u64>,
+    plt_layout: PltLayout,
 ) -> Resolution {
     let mut resolution = Resolution {
         raw_value,
@@ -3914,7 +6701,13 @@ fn create_resolution(
         value_flags,
     };
     if res_kind.contains(ResolutionFlags::PLT) {
-        let plt_address = allocate_plt(memory_offsets);
+        let mut plt_address = allocate_plt(memory_offsets, plt_layout.entry_size);
+        if plt_layout.uses_plt_sec() && is_lazily_bound(value_flags, res_kind, plt_layout) {
+            // The entry that we just allocated in .plt.got is only used until the function has
+            // been bound. Everything else should call the entry in .plt.sec.
+            plt_address = NonZeroU64::new(*memory_offsets.get(part_id::PLT_SEC)).unwrap();
+            memory_offsets.increment(part_id::PLT_SEC, elf::PLT_ENTRY_SIZE);
+        }
         resolution.plt_address = Some(plt_address);
         if value_flags.contains(ValueFlags::DYNAMIC) {
             resolution.raw_value = plt_address.get();
@@ -3942,9 +6735,9 @@ fn allocate_got(num_entries: u64, memory_offsets: &mut OutputSectionPartMap<u64>
     got_address
 }
 
-fn allocate_plt(memory_offsets: &mut OutputSectionPartMap<u64>) -> NonZeroU64 {
+fn allocate_plt(memory_offsets: &mut OutputSectionPartMap<u64>, plt_entry_size: u64) -> NonZeroU64 {
     let plt_address = NonZeroU64::new(*memory_offsets.get(part_id::PLT_GOT)).unwrap();
-    memory_offsets.increment(part_id::PLT_GOT, elf::PLT_ENTRY_SIZE);
+    memory_offsets.increment(part_id::PLT_GOT, plt_entry_size);
     plt_address
 }
 
@@ -4067,6 +6860,7 @@ impl Resolution {
                 return Ok(r);
             }
         }
+        let addend = object_layout.relaxed_addend(symbol_index, addend)?;
         Ok(self.raw_value.wrapping_add(addend as u64))
     }
 }
@@ -4074,28 +6868,404 @@ impl Resolution {
 fn layout_section_parts(
     sizes: &OutputSectionPartMap<u64>,
     output_sections: &OutputSections,
-) -> OutputSectionPartMap<OutputRecordLayout> {
+) -> Result<(
+    OutputSectionPartMap<OutputRecordLayout>,
+    OutputSectionMap<SectionLocations>,
+)> {
+    let mut start_addresses = output_sections.new_section_map::<Option<u64>>();
+    for (section_id, info) in output_sections.ids_with_info() {
+        *start_addresses.get_mut(section_id) = info.start_address;
+    }
+    loop {
+        let (layouts, section_locations, section_to_move) =
+            layout_section_parts_with_start_addresses(sizes, output_sections, &start_addresses)?;
+        let Some(section_move) = section_to_move else {
+            return Ok((layouts, section_locations));
+        };
+        *start_addresses.get_mut(section_move.section_id) = Some(section_move.address);
+    }
+}
+
+/// A section that needs to be placed at a different address, after which layout of section parts
+/// needs to be redone.
+struct SectionMove {
+    section_id: OutputSectionId,
+    address: u64,
+}
+
+/// The values of `.` before and after each assignment to it within an output section of a linker
+/// script. These are empty for sections without such assignments.
+#[derive(Default, Debug)]
+struct SectionLocations {
+    /// For assignments before the section's input sections. The section starts at the last value,
+    /// aligned to the section's alignment.
+    start: Vec<u64>,
+
+    /// For assignments after the input sections, starting from the end of them. The section ends
+    /// at the last value.
+    end: Vec<u64>,
+}
+
+/// Evaluates assignments to `.` within an output section, starting with `.` at `location`, and
+/// returns the value before and after each of them.
+fn evaluate_section_locations(
+    assignments: &[Expression],
+    location: u64,
+    base_context: &EvaluationContext,
+) -> Result<Vec<u64>> {
+    let mut locations = Vec::with_capacity(assignments.len() + 1);
+    locations.push(location);
+    for assignment in assignments {
+        let previous = *locations.last().unwrap();
+        let location = assignment.evaluate(&EvaluationContext {
+            location: previous,
+            ..*base_context
+        })?;
+        if location < previous {
+            bail!("Assignment to `.` moves it backwards from 0x{previous:x} to 0x{location:x}");
+        }
+        locations.push(location);
+    }
+    Ok(locations)
+}
+
+/// Lays out section parts, placing sections at the supplied start addresses. If a section with a
+/// start address isn't at the start of its segment, then rather than leaving a large gap in the
+/// file, we move the start of the segment along so that the section ends up close to where it
+/// should be. In that case, we return the section at the start of the segment and its new address
+/// so that the caller can try again. We also return the values of `.` within linker script output
+/// sections, which can move the start of a section or pad its end.
+fn layout_section_parts_with_start_addresses(
+    sizes: &OutputSectionPartMap<u64>,
+    output_sections: &OutputSections,
+    start_addresses: &OutputSectionMap<Option<u64>>,
+) -> Result<(
+    OutputSectionPartMap<OutputRecordLayout>,
+    OutputSectionMap<SectionLocations>,
+    Option<SectionMove>,
+)> {
+    use output_section_id::OrderEvent;
     let mut file_offset = 0;
     let mut mem_offset = output_sections.base_address;
     let mut current_seg_id = None;
+    let mut segment_alignment = alignment::MIN;
+    // The first section in the current segment that has content and where it starts.
+    let mut segment_start: Option<(OutputSectionId, u64)> = None;
+    // The end of the last section with content. Starting a new segment can move our location
+    // along, but a section at the start of a segment only needs to come after this.
+    let mut content_end = 0;
+    let mut current_section = None;
+    let mut section_to_move = None;
+    let mut error = None;
     let mut nonalloc_mem_offsets: OutputSectionMap<u64> =
         OutputSectionMap::with_size(output_sections.num_sections());
+    let sizeof_headers = [
+        part_id::FILE_HEADER,
+        part_id::PROGRAM_HEADERS,
+        part_id::SECTION_HEADERS,
+    ]
+    .iter()
+    .map(|id| *sizes.get(*id))
+    .sum();
+    let base_context = EvaluationContext {
+        location: 0,
+        sizeof_headers,
+        max_page_size: output_sections.max_page_size.value(),
+        common_page_size: output_sections.common_page_size.value(),
+        memory_regions: output_sections.memory_regions,
+        layout: None,
+    };
+    let mut section_locations = output_sections.new_section_map::<SectionLocations>();
+    let headers_loaded = output_sections.load_segment_for(FILE_HEADER).is_some();
+    let mut header_address = None;
+    // Whether we've placed a section other than our headers, which a linker script's location
+    // counter starts at zero for.
+    let mut placed_script_section = false;
+    // Whether we've placed a section, other than our headers, at a fixed address. Once we have,
+    // moving our headers down won't make room for anything that comes later.
+    let mut placed_fixed_section = false;
+    // Where the next section in each memory region goes if the script doesn't say otherwise, and
+    // the region of the section that we're currently in, which orphans stay in.
+    let mut region_locations = output_sections
+        .memory_regions
+        .iter()
+        .map(|region| region.origin)
+        .collect::<Vec<_>>();
+    let mut current_region = None;
+    // The end of the largest member of the overlay that we're in, if any.
+    let mut overlay_end: Option<u64> = None;
+    // The section that follows `PT_GNU_RELRO`, which needs to start on a new page.
+    let section_after_relro = output_sections
+        .sections_and_segments_events()
+        .skip_while(|event| !matches!(event, OrderEvent::SegmentEnd(GNU_RELRO)))
+        .find_map(|event| match event {
+            OrderEvent::Section(section_id) => Some(section_id),
+            _ => None,
+        })
+        .filter(|_| output_sections.relro);
 
-    sizes.output_order_map(output_sections, |part_id, section_alignment, part_size| {
+    let parts = sizes.output_order_map(output_sections, |part_id, section_alignment, part_size| {
         let section_id = part_id.output_section_id();
         let section_flags = output_sections.section_flags(section_id);
-        let mem_size = *part_size;
+        let mut mem_size = *part_size;
         // Note, we align up even if our size is zero, otherwise our section will start at an
         // unaligned address.
         file_offset = section_alignment.align_up_usize(file_offset);
 
         if section_flags.contains(shf::ALLOC) {
+            let is_first_part = current_section.is_none_or(|(id, _)| id != section_id);
+            let shares_address = output_sections.script_layout
+                && is_first_part
+                && output_sections
+                    .output_info(section_id)
+                    .script_placement
+                    .as_ref()
+                    .is_some_and(|placement| placement.shares_address);
+            // The members of an overlay all start at the same address. Whatever follows the
+            // overlay goes after the largest of them.
+            if shares_address {
+                overlay_end = Some(overlay_end.map_or(mem_offset, |end| end.max(mem_offset)));
+            } else if is_first_part {
+                if let Some(end) = overlay_end.take() {
+                    mem_offset = mem_offset.max(end);
+                }
+            }
+            let location = mem_offset;
             mem_offset = section_alignment.align_up(mem_offset);
-            let seg_id = output_sections.loadable_segment_id_for(section_id);
+            if is_first_part && section_after_relro == Some(section_id) {
+                let padded = output_sections.common_page_size.align_up(mem_offset);
+                file_offset += (padded - mem_offset) as usize;
+                mem_offset = padded;
+            }
+            let seg_id = output_sections.load_segment_for(section_id);
+            let preceding_end = mem_offset;
             if current_seg_id != seg_id {
+                let previous_seg_id = current_seg_id;
                 current_seg_id = seg_id;
-                let segment_alignment = seg_id.map_or(alignment::MIN, |s| s.alignment());
-                mem_offset = segment_alignment.align_modulo(file_offset as u64, mem_offset);
+                segment_alignment = seg_id
+                    .filter(|_| output_sections.magic == Magic::Demand)
+                    .map_or(alignment::MIN, |s| {
+                        s.alignment(output_sections.max_page_size)
+                    });
+                // Without paging, everything is in a single segment.
+                if output_sections.magic == Magic::Demand {
+                    segment_start = None;
+                }
+                // Where a linker script defines our program headers, it's up to the script to
+                // separate segments.
+                let exec = Some(LoadSegment::BuiltIn(LOAD_EXEC));
+                let starts_new_page = match output_sections.separate_segments {
+                    SeparateSegments::None => false,
+                    SeparateSegments::Code => seg_id == exec || previous_seg_id == exec,
+                    SeparateSegments::Loadable => {
+                        matches!(seg_id, Some(LoadSegment::BuiltIn(_)))
+                    }
+                };
+                if starts_new_page {
+                    file_offset = segment_alignment.align_up_usize(file_offset);
+                }
+                if output_sections.script_layout {
+                    // The linker script decides where sections go in memory, so we move along in
+                    // the file instead.
+                    file_offset = segment_alignment
+                        .align_file_offset_modulo(mem_offset, file_offset as u64)
+                        as usize;
+                } else {
+                    mem_offset = segment_alignment.align_modulo(file_offset as u64, mem_offset);
+                }
+            }
+            // Nothing precedes our headers, so a linker script can move them below our usual base
+            // address.
+            let mut preceding_end = if section_id == FILE_HEADER {
+                0
+            } else {
+                preceding_end
+            };
+            let mut address = start_addresses.get(section_id).filter(|_| is_first_part);
+            let is_first_section = !placed_script_section;
+            // If the script doesn't say where its first section goes, then we leave it, and our
+            // headers, at our usual base address.
+            let script_placement = output_sections
+                .output_info(section_id)
+                .script_placement
+                .as_ref()
+                .filter(|placement| {
+                    !is_first_section || placement.is_fixed() || placement.region.is_some()
+                });
+            if output_sections.script_layout
+                && is_first_part
+                && !output_section_id::is_header_section(section_id)
+                && (script_placement.is_some() || !is_first_section)
+            {
+                placed_script_section = true;
+                if let Some(region) = current_region {
+                    region_locations[region] = location;
+                }
+                if let Some(placement) = script_placement {
+                    current_region = placement.region;
+                }
+                let location = match script_placement {
+                    Some(placement) if !placement.is_fixed() => {
+                        placement.region.map(|region| region_locations[region])
+                    }
+                    _ => None,
+                }
+                .unwrap_or(if is_first_section { 0 } else { location });
+                let context = EvaluationContext {
+                    location,
+                    ..base_context
+                };
+                let script_address = match script_placement
+                    .map(|placement| placement.address(section_alignment, &context))
+                    .transpose()
+                {
+                    Ok(script_address) => script_address,
+                    Err(script_error) => {
+                        error.get_or_insert(script_error.context(format!(
+                            "Failed to evaluate linker script address of `{}`",
+                            output_sections.display_name(section_id)
+                        )));
+                        None
+                    }
+                };
+                if is_first_section && headers_loaded {
+                    // Our headers need to come immediately before the first section, so move them
+                    // if they aren't already there. They need to be at the start of a page.
+                    let target = address
+                        .or(script_address)
+                        .map(|address| address.saturating_sub(sizeof_headers));
+                    if let Some(target) = target.filter(|target| {
+                        header_address != Some(*target)
+                            && output_sections.max_page_size.align_down(*target) == *target
+                    }) {
+                        section_to_move.get_or_insert(SectionMove {
+                            section_id: FILE_HEADER,
+                            address: target,
+                        });
+                    } else {
+                        address = address.or(script_address);
+                    }
+                } else {
+                    if is_first_section {
+                        // Our headers aren't loaded, so nothing precedes this section in memory.
+                        preceding_end = 0;
+                    }
+                    address = address.or(script_address);
+                }
+            }
+            if shares_address {
+                address = current_section.map(|(_, start)| start);
+                segment_start = None;
+            }
+            if segment_start.is_none() {
+                preceding_end = preceding_end.min(content_end);
+            }
+            if let Some(address) = address {
+                if address < preceding_end && !shares_address {
+                    // Unless we were told where to start, we can move our headers, and everything
+                    // that follows them, down far enough that this section fits.
+                    let can_move_headers = !output_sections.script_layout
+                        && !output_sections.fixed_base_address
+                        && !placed_fixed_section;
+                    let header_target = header_address
+                        .filter(|_| can_move_headers)
+                        .and_then(|header_address| {
+                            header_address.checked_sub(preceding_end - address)
+                        })
+                        .map(|target| output_sections.max_page_size.align_down(target));
+                    if let Some(target) = header_target {
+                        section_to_move.get_or_insert(SectionMove {
+                            section_id: FILE_HEADER,
+                            address: target,
+                        });
+                    } else {
+                        error.get_or_insert_with(|| {
+                            anyhow!(
+                                "Can't place `{}` at 0x{address:x}, since that's before the end \
+                                 of the preceding section at 0x{preceding_end:x}",
+                                output_sections.display_name(section_id)
+                            )
+                        });
+                    }
+                } else if let Some((first_id, first_address)) = segment_start {
+                    // We're part way through a segment, so any gap also needs to be in the
+                    // file. Moving the whole segment by a multiple of the page size keeps this
+                    // gap small, provided that the start of the segment isn't also fixed.
+                    let shift = output_sections
+                        .max_page_size
+                        .align_down(address - preceding_end);
+                    if shift > 0
+                        && start_addresses.get(first_id).is_none()
+                        && output_sections
+                            .output_info(first_id)
+                            .script_placement
+                            .is_none()
+                    {
+                        section_to_move.get_or_insert(SectionMove {
+                            section_id: first_id,
+                            address: first_address + shift,
+                        });
+                    }
+                    if output_sections.has_data_in_file(section_id) {
+                        file_offset += (address - preceding_end) as usize;
+                    }
+                    mem_offset = address;
+                } else {
+                    mem_offset = address;
+                    file_offset = if output_sections.script_layout {
+                        segment_alignment.align_file_offset_modulo(address, file_offset as u64)
+                    } else {
+                        segment_alignment.align_modulo(address, file_offset as u64)
+                    } as usize;
+                }
+                placed_fixed_section |= section_id != FILE_HEADER;
+            }
+            let placement = output_sections
+                .output_info(section_id)
+                .script_placement
+                .as_ref()
+                .filter(|_| output_sections.script_layout);
+            let mut evaluate_locations = |assignments: &[Expression], location: u64| {
+                evaluate_section_locations(assignments, location, &base_context)
+                    .map_err(|location_error| {
+                        error.get_or_insert(location_error.context(format!(
+                            "Failed to evaluate linker script assignment to `.` in `{}`",
+                            output_sections.display_name(section_id)
+                        )));
+                    })
+                    .ok()
+            };
+            if let Some(placement) =
+                placement.filter(|p| is_first_part && !p.start_locations.is_empty())
+            {
+                if let Some(locations) = evaluate_locations(placement.start_locations, mem_offset) {
+                    let start = section_alignment.align_up(*locations.last().unwrap());
+                    if output_sections.has_data_in_file(section_id) {
+                        file_offset += (start - mem_offset) as usize;
+                    }
+                    mem_offset = start;
+                    section_locations.get_mut(section_id).start = locations;
+                }
+            }
+            if let Some(placement) = placement.filter(|p| {
+                !p.end_locations.is_empty()
+                    && part_id
+                        == output_section_part_map::last_output_part(section_id, output_sections)
+            }) {
+                let content_end = mem_offset + mem_size;
+                if let Some(locations) = evaluate_locations(placement.end_locations, content_end) {
+                    mem_size += *locations.last().unwrap() - content_end;
+                    section_locations.get_mut(section_id).end = locations;
+                }
+            }
+            if is_first_part {
+                current_section = Some((section_id, mem_offset));
+                if section_id == FILE_HEADER {
+                    header_address = Some(mem_offset);
+                }
+            }
+            if mem_size > 0 && segment_start.is_none() {
+                segment_start = current_section;
             }
             let file_size = if output_sections.has_data_in_file(section_id) {
                 mem_size as usize
@@ -4112,6 +7282,9 @@ fn layout_section_parts(
             };
             file_offset += file_size;
             mem_offset += mem_size;
+            if mem_size > 0 {
+                content_end = mem_offset;
+            }
             section_layout
         } else {
             let section_id = part_id.output_section_id();
@@ -4129,7 +7302,11 @@ fn layout_section_parts(
             file_offset += mem_size as usize;
             section_layout
         }
-    })
+    });
+    if let Some(error) = error {
+        return Err(error);
+    }
+    Ok((parts, section_locations, section_to_move))
 }
 
 impl<'data> DynamicLayoutState<'data> {
@@ -4144,6 +7321,7 @@ impl<'data> DynamicLayoutState<'data> {
         if let Some(soname) = dt_info.soname {
             self.lib_name = soname;
         }
+        self.needs_indirect_extern_access = needs_indirect_extern_access(self.object)?;
         common.allocate(
             part_id::DYNAMIC,
             size_of::<crate::elf::DynamicEntry>() as u64,
@@ -4181,10 +7359,19 @@ impl<'data> DynamicLayoutState<'data> {
         Ok(())
     }
 
-    fn finalise_sizes(&mut self, common: &mut CommonGroupState<'data>) -> Result {
+    fn finalise_sizes<S: StorageModel>(
+        &mut self,
+        common: &mut CommonGroupState<'data>,
+        symbol_db: &SymbolDb<'data, S>,
+        symbol_resolution_flags: &[AtomicResolutionFlags],
+    ) -> Result {
         let e = LittleEndian;
         let mut version_count = 0;
 
+        if symbol_db.args.emit_relocs {
+            self.allocate_imported_symbols(common, symbol_db, symbol_resolution_flags)?;
+        }
+
         if let Some((mut verdef_iterator, link)) = self.object.verdef.clone() {
             let defs = verdef_iterator.clone();
 
@@ -4234,25 +7421,65 @@ impl<'data> DynamicLayoutState<'data> {
                     version_count,
                 });
             }
+        } else if symbol_db.args.default_imported_symver
+            && symbol_db.args.should_output_symbol_versions()
+            && self.symbol_id_range().into_iter().any(|symbol_id| {
+                !symbol_resolution_flags[symbol_id.as_usize()]
+                    .get()
+                    .is_empty()
+            })
+        {
+            // Both the file and the version are named after the soname.
+            self.has_default_version = true;
+            common.allocate(part_id::DYNSTR, 2 * (self.lib_name.len() as u64 + 1));
+            common.allocate(part_id::GNU_VERSION_R, default_verneed_size());
         }
 
         Ok(())
     }
 
+    /// Allocates symbol table entries for the symbols that we import from this object. These are
+    /// only needed with `--emit-relocs`, since the relocations that we copy can refer to them.
+    /// Symbols that need copy relocations already have entries, since we define them.
+    fn allocate_imported_symbols<S: StorageModel>(
+        &self,
+        common: &mut CommonGroupState<'data>,
+        symbol_db: &SymbolDb<'data, S>,
+        symbol_resolution_flags: &[AtomicResolutionFlags],
+    ) -> Result {
+        for symbol_id in self.symbol_id_range() {
+            let flags = symbol_resolution_flags[symbol_id.as_usize()].get();
+            if flags.is_empty() || flags.contains(ResolutionFlags::COPY_RELOCATION) {
+                continue;
+            }
+            common.allocate(part_id::SYMTAB_GLOBAL, size_of::<elf::SymtabEntry>() as u64);
+            common.allocate(
+                part_id::STRTAB,
+                symbol_db.symbol_name(symbol_id)?.len() as u64 + 1,
+            );
+        }
+        Ok(())
+    }
+
     fn apply_non_addressable_indexes(
         &mut self,
         indexes: &mut NonAddressableIndexes,
         counts: &mut NonAddressableCounts,
     ) -> Result {
         self.non_addressable_indexes = *indexes;
-        if let Some(info) = self.verdef_info.as_ref() {
-            if info.version_count > 0 {
-                counts.verneed_count += 1;
-                indexes.gnu_version_r_index = indexes
-                    .gnu_version_r_index
-                    .checked_add(info.version_count)
-                    .context("Symbol versions overflowed 2**16")?;
-            }
+        let version_count = if self.has_default_version {
+            1
+        } else {
+            self.verdef_info
+                .as_ref()
+                .map_or(0, |info| info.version_count)
+        };
+        if version_count > 0 {
+            counts.verneed_count += 1;
+            indexes.gnu_version_r_index = indexes
+                .gnu_version_r_index
+                .checked_add(version_count)
+                .context("Symbol versions overflowed 2**16")?;
         }
         Ok(())
     }
@@ -4287,6 +7514,10 @@ impl<'data> DynamicLayoutState<'data> {
                 // Since this is a definition, the dynamic symbol index will be determined by the
                 // epilogue and set by `update_dynamic_symbol_resolutions`.
                 dynamic_symbol_index = None;
+            } else if resolution_flags.contains(ResolutionFlags::CANONICAL_PLT) {
+                // Like copy relocations, the epilogue writes the dynamic symbol.
+                address = 0;
+                dynamic_symbol_index = None;
             } else {
                 address = 0;
                 let symbol_index = take_dynsym_index(memory_offsets, resources.section_layouts)?;
@@ -4303,6 +7534,7 @@ impl<'data> DynamicLayoutState<'data> {
                 dynamic_symbol_index,
                 ValueFlags::DYNAMIC,
                 memory_offsets,
+                resources.plt_layout,
             );
 
             resolutions_out.write(Some(resolution))?;
@@ -4315,6 +7547,12 @@ impl<'data> DynamicLayoutState<'data> {
                     + u64::from(v.version_count) * size_of::<crate::elf::Vernaux>() as u64,
             );
         }
+        let default_version = self
+            .has_default_version
+            .then_some(self.non_addressable_indexes.gnu_version_r_index);
+        if default_version.is_some() {
+            memory_offsets.increment(part_id::GNU_VERSION_R, default_verneed_size());
+        }
 
         Ok(DynamicLayout {
             file_id: self.file_id(),
@@ -4325,6 +7563,7 @@ impl<'data> DynamicLayoutState<'data> {
             input_symbol_versions: self.symbol_versions,
             version_mapping,
             verdef_info: self.verdef_info,
+            default_version,
             // We set this to true later for one object.
             is_last_verneed: false,
         })
@@ -4344,6 +7583,11 @@ impl<'data> DynamicLayoutState<'data> {
     }
 }
 
+/// Returns the size of the record in .gnu.version_r for an object that we give a default version.
+fn default_verneed_size() -> u64 {
+    (size_of::<crate::elf::Verneed>() + size_of::<crate::elf::Vernaux>()) as u64
+}
+
 /// Assigns the address in BSS for the copy relocation of a symbol.
 fn assign_copy_relocation_address(
     file: &File,
@@ -4508,11 +7752,12 @@ impl GnuHashLayout {
 }
 
 impl<'data> DynamicSymbolDefinition<'data> {
-    fn new(symbol_id: SymbolId, name: &'data [u8]) -> Self {
+    fn new(symbol_id: SymbolId, name: &'data [u8], version: u16) -> Self {
         Self {
             symbol_id,
             name,
             hash: gnu_hash(name),
+            version,
         }
     }
 }
@@ -4528,7 +7773,8 @@ fn test_no_disallowed_overlaps() {
             .build()
             .unwrap();
     let section_part_sizes = output_sections.new_part_map::<u64>().map(|_, _| 7);
-    let section_part_layouts = layout_section_parts(&section_part_sizes, &output_sections);
+    let (section_part_layouts, _) =
+        layout_section_parts(&section_part_sizes, &output_sections).unwrap();
     let section_layouts = layout_sections(&section_part_layouts);
 
     // Make sure no alloc sections overlap
@@ -4570,6 +7816,7 @@ fn test_no_disallowed_overlaps() {
     let header_info = HeaderInfo {
         num_output_sections_with_content: 0,
         active_segment_ids: (0..MAX_SEGMENTS).map(ProgramSegmentId::new).collect(),
+        num_program_headers: MAX_SEGMENTS,
     };
 
     let mut section_index = 0;
@@ -4584,15 +7831,20 @@ fn test_no_disallowed_overlaps() {
         }
     }
 
-    let segment_layouts =
-        compute_segment_layout(&section_layouts, &output_sections, &header_info).unwrap();
+    let load_addresses = compute_load_addresses(&section_layouts, &output_sections).unwrap();
+    let segment_layouts = compute_segment_layout(
+        &section_layouts,
+        &load_addresses,
+        &output_sections,
+        &header_info,
+    )
+    .unwrap();
 
     // Make sure loadable segments don't overlap in memory or in the file.
     let mut last_file = 0;
     let mut last_mem = 0;
     for seg_layout in &segment_layouts.segments {
-        let seg_id = seg_layout.id;
-        if seg_id.segment_type() != object::elf::PT_LOAD {
+        if seg_layout.segment_type != object::elf::PT_LOAD {
             continue;
         }
         assert!(
@@ -4612,6 +7864,93 @@ fn test_no_disallowed_overlaps() {
     }
 }
 
+/// Makes sure that sections with a start address get placed there and that for loadable segments,
+/// file offsets remain congruent with addresses.
+#[test]
+fn test_section_start_address() {
+    let mut output_sections =
+        crate::output_section_id::OutputSectionsBuilder::with_base_address(0x1000)
+            .build()
+            .unwrap();
+    output_sections.section_infos[output_section_id::TEXT.as_usize()].start_address =
+        Some(0x80_0123);
+    let section_part_sizes = output_sections.new_part_map::<u64>().map(|_, _| 7);
+    let (section_part_layouts, _) =
+        layout_section_parts(&section_part_sizes, &output_sections).unwrap();
+    let section_layouts = layout_sections(&section_part_layouts);
+    let text = section_layouts.get(output_section_id::TEXT);
+    assert_eq!(text.mem_offset, 0x80_0123);
+    assert_eq!(
+        text.file_offset as u64 % alignment::PAGE.value(),
+        text.mem_offset % alignment::PAGE.value()
+    );
+
+    output_sections.section_infos[output_section_id::TEXT.as_usize()].start_address = Some(0x1000);
+    assert!(layout_section_parts(&section_part_sizes, &output_sections).is_err());
+}
+
+/// Makes sure that a section placed below our default base address brings our headers down with
+/// it, but only if we weren't told where to put them.
+#[test]
+fn test_section_below_base_address() {
+    let mut output_sections =
+        crate::output_section_id::OutputSectionsBuilder::with_base_address(0x40_0000)
+            .build()
+            .unwrap();
+    output_sections.section_infos[output_section_id::TEXT.as_usize()].start_address = Some(0x7c00);
+    // Only our headers and .text have content, since whatever precedes .text needs to fit below
+    // it.
+    let section_part_sizes = output_sections.new_part_map::<u64>().map(|part_id, _| {
+        let section_id = part_id.output_section_id();
+        if section_id == output_section_id::FILE_HEADER || section_id == output_section_id::TEXT {
+            7
+        } else {
+            0
+        }
+    });
+    let (section_part_layouts, _) =
+        layout_section_parts(&section_part_sizes, &output_sections).unwrap();
+    let section_layouts = layout_sections(&section_part_layouts);
+    let text = section_layouts.get(output_section_id::TEXT);
+    assert_eq!(text.mem_offset, 0x7c00);
+    let header = section_layouts.get(output_section_id::FILE_HEADER);
+    assert_eq!(header.mem_offset, 0x7000);
+    assert_eq!(header.file_offset, 0);
+
+    output_sections.fixed_base_address = true;
+    assert!(layout_section_parts(&section_part_sizes, &output_sections).is_err());
+}
+
+/// AArch64 PLT entries get a BTI landing pad if the output is marked as BTI-compatible and are
+/// larger if they need to authenticate the GOT entry.
+#[test]
+fn test_aarch64_plt_layout() {
+    let plt_layout = |extra_args: &[&str], properties: &[GnuProperty]| {
+        let mut all_args = vec!["-m", "aarch64linux", "a.o"];
+        all_args.extend_from_slice(extra_args);
+        let crate::args::Action::Link(args) = crate::args::parse(all_args.iter()).unwrap() else {
+            panic!("Unexpected action");
+        };
+        PltLayout::new(&args, properties)
+    };
+    let bti = [GnuProperty {
+        ptype: GNU_PROPERTY_AARCH64_FEATURE_1_AND,
+        data: GNU_PROPERTY_AARCH64_FEATURE_1_BTI,
+    }];
+
+    let default = plt_layout(&[], &[]);
+    assert!(!default.features.bti && !default.features.pac);
+    assert_eq!(default.entry_size, elf::PLT_ENTRY_SIZE);
+
+    let with_bti = plt_layout(&[], &bti);
+    assert!(with_bti.features.bti && !with_bti.features.pac);
+    assert_eq!(with_bti.entry_size, elf::PLT_ENTRY_SIZE);
+
+    let with_pac = plt_layout(&["-z", "pac-plt"], &bti);
+    assert!(with_pac.features.bti && with_pac.features.pac);
+    assert_eq!(with_pac.entry_size, elf::AARCH64_PAC_PLT_ENTRY_SIZE);
+}
+
 impl Display for ResolutionFlags {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         bitflags::parser::to_writer(self, f)
@@ -4626,17 +7965,31 @@ fn verify_consistent_allocation_handling(
     value_flags: ValueFlags,
     resolution_flags: ResolutionFlags,
     output_kind: OutputKind,
+    plt_layout: PltLayout,
 ) -> Result {
     let output_sections = output_section_id::OutputSectionsBuilder::with_base_address(0)
         .build()
         .unwrap();
     let mut mem_sizes = output_sections.new_part_map();
     let resolution_flags = AtomicResolutionFlags::new(resolution_flags);
-    allocate_symbol_resolution(value_flags, &resolution_flags, &mut mem_sizes, output_kind);
+    // The writer that we verify against is x86-64. We verify with the default PLT entry size, since
+    // that's what gets used when there's IBT or lazy binding.
+    let plt_layout = PltLayout {
+        entry_size: elf::PLT_ENTRY_SIZE,
+        ..plt_layout
+    };
+    allocate_symbol_resolution(
+        value_flags,
+        &resolution_flags,
+        &mut mem_sizes,
+        output_kind,
+        plt_layout,
+    );
     let resolution_flags = resolution_flags.get();
     let mut memory_offsets = output_sections.new_part_map();
     *memory_offsets.get_mut(part_id::GOT) = 0x10;
     *memory_offsets.get_mut(part_id::PLT_GOT) = 0x10;
+    *memory_offsets.get_mut(part_id::PLT_SEC) = 0x10;
     let has_dynamic_symbol = value_flags.contains(ValueFlags::DYNAMIC)
         || (resolution_flags.contains(ResolutionFlags::EXPORT_DYNAMIC)
             && !value_flags.contains(ValueFlags::CAN_BYPASS_GOT));
@@ -4647,10 +8000,12 @@ fn verify_consistent_allocation_handling(
         dynamic_symbol_index,
         value_flags,
         &mut memory_offsets,
+        plt_layout,
     );
     elf_writer::verify_resolution_allocation(
         &output_sections,
         output_kind,
+        plt_layout,
         &mem_sizes,
         &resolution,
     )
@@ -4660,6 +8015,7 @@ fn verify_consistent_allocation_handling(
              output_kind={output_kind:?} \
              value_flags={value_flags} \
              resolution_flags={resolution_flags} \
+             plt_layout={plt_layout:?} \
              has_dynamic_symbol={has_dynamic_symbol:?}"
         )
     })?;
@@ -1,4 +1,4 @@
-use crate::args::parse;
+use crate::args::parse_command_line;
 use args::Args;
 use tracing_subscriber::fmt;
 use tracing_subscriber::layer::SubscriberExt;
@@ -6,37 +6,61 @@ use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
 pub(crate) mod aarch64;
+pub(crate) mod aarch64_errata;
 pub(crate) mod alignment;
 pub(crate) mod arch;
+pub(crate) mod arch_attributes;
 pub(crate) mod archive;
 pub(crate) mod archive_splitter;
+pub(crate) mod archive_stats;
 pub mod args;
+pub(crate) mod binary_output;
+pub(crate) mod build_attributes;
 pub(crate) mod debug_trace;
+pub(crate) mod dependencies;
 pub(crate) mod diff;
 pub(crate) mod elf;
 pub(crate) mod elf_writer;
 pub mod error;
+pub(crate) mod explain_reloc;
 pub(crate) mod file_kind;
 pub(crate) mod fs;
 pub(crate) mod gc_stats;
+pub(crate) mod glibc_versions;
+pub(crate) mod glob;
+pub(crate) mod gnu_warning;
+pub(crate) mod got_usage;
 pub(crate) mod grouping;
 pub(crate) mod hash;
 pub(crate) mod identity;
 pub(crate) mod input_data;
+pub(crate) mod interface_stub;
 pub(crate) mod layout;
 pub(crate) mod linker_script;
+pub(crate) mod memory_budget;
+pub(crate) mod memtag;
 pub(crate) mod output_section_id;
 pub(crate) mod output_section_map;
 pub(crate) mod output_section_part_map;
 pub(crate) mod output_trace;
 pub(crate) mod parsing;
 pub(crate) mod part_id;
+pub(crate) mod ppc64;
+pub(crate) mod profile;
 pub(crate) mod program_segments;
+pub(crate) mod relaxation_stats;
+pub(crate) mod relocatable_object;
 pub(crate) mod resolution;
+pub(crate) mod riscv64;
+pub(crate) mod riscv64_relaxation;
 pub(crate) mod save_dir;
+pub(crate) mod separate_debug;
 pub(crate) mod sharding;
 pub(crate) mod shutdown;
+pub(crate) mod size_report;
 pub(crate) mod slice;
+pub(crate) mod split_stack;
+pub(crate) mod stack_report;
 pub(crate) mod storage;
 pub(crate) mod string_merging;
 #[cfg(feature = "fork")]
@@ -52,6 +76,7 @@ pub(crate) mod threading;
 #[cfg(feature = "single-threaded")]
 #[path = "threading_none.rs"]
 pub(crate) mod threading;
+pub(crate) mod thunks;
 pub(crate) mod timing;
 pub(crate) mod validation;
 pub(crate) mod verification;
@@ -66,7 +91,7 @@ pub struct Linker {
 impl Linker {
     pub fn from_args<S: AsRef<str>, I: Iterator<Item = S>>(args: I) -> error::Result<Self> {
         Ok(Linker {
-            action: parse(args)?,
+            action: parse_command_line(args)?,
         })
     }
 
@@ -94,15 +119,9 @@ impl Linker {
                         .with(EnvFilter::from_default_env())
                         .init();
                 }
-                match args.arch {
-                    arch::Architecture::X86_64 => {
-                        link::<storage::InMemory, x86_64::X86_64>(args, done_closure)
-                    }
-                    arch::Architecture::AArch64 => {
-                        link::<storage::InMemory, aarch64::AArch64>(args, done_closure)
-                    }
-                }
+                link::<storage::InMemory>(args, done_closure)
             }
+            args::Action::ExplainReloc(args) => explain_reloc::run(args),
             args::Action::Version => {
                 println!(
                     "Wild version {} (compatible with GNU linkers)",
@@ -116,13 +135,13 @@ impl Linker {
     pub fn should_fork(&self) -> bool {
         match &self.action {
             args::Action::Link(args) => args.should_fork(),
-            args::Action::Version => false,
+            args::Action::ExplainReloc(_) | args::Action::Version => false,
         }
     }
 }
 
 #[tracing::instrument(skip_all, name = "Link")]
-fn link<S: storage::StorageModel, A: arch::Arch>(
+fn link<S: storage::StorageModel>(
     args: &Args,
     done_closure: Option<Box<dyn FnOnce()>>,
 ) -> error::Result {
@@ -130,15 +149,32 @@ fn link<S: storage::StorageModel, A: arch::Arch>(
     let mut output = elf_writer::Output::new(args);
     let input_data = input_data::InputData::from_args(args)?;
     let inputs = archive_splitter::split_archives(&input_data)?;
-    let files = parsing::parse_input_files(&inputs, args)?;
+    let files = parsing::parse_input_files(&inputs, args, &input_data.script_layout)?;
     let groups = grouping::group_files(files, args);
     let herd = bumpalo_herd::Herd::new();
-    let mut symbol_db =
-        symbol_db::SymbolDb::<S>::build(&groups, input_data.version_script_data.as_ref(), args)?;
-    let resolved = resolution::resolve_symbols_and_sections(&groups, &mut symbol_db, &herd)?;
-    let layout = layout::compute::<S, A>(&symbol_db, resolved, &mut output)?;
-    let output_file = output.write::<S, A>(&layout)?;
+    let mut symbol_db = symbol_db::SymbolDb::<S>::build(
+        &groups,
+        input_data.version_script_data.as_ref(),
+        args,
+        &herd,
+    )?;
+    let resolved = resolution::resolve_symbols_and_sections(
+        &groups,
+        &mut symbol_db,
+        input_data.profile.as_ref(),
+        &input_data.script_layout,
+        &herd,
+    )?;
+    archive_stats::maybe_write_archive_stats(&groups, &resolved.groups, args)?;
+    dependencies::maybe_print_dependencies(&resolved.groups, &symbol_db, args)?;
+    glibc_versions::maybe_check_glibc_versions(&resolved.groups, &symbol_db, args)?;
+    let layout = layout::compute::<S>(&symbol_db, resolved, &mut output)?;
+    let output_file = output.write::<S>(&layout)?;
+    interface_stub::maybe_write_interface_stubs::<S>(&layout)?;
+    stack_report::maybe_write_stack_report::<S>(&layout)?;
+    size_report::maybe_write_size_report(&layout)?;
     diff::maybe_diff()?;
+    memory_budget::check_peak_usage(args);
 
     let scope = tracing::info_span!("Shutdown");
     let _scope = scope.enter();
@@ -1,12 +1,62 @@
-//! This module is responsible for parsing very basic linker scripts. These are not the kind of
-//! linker script you might write to specify the layout of your program on an embedded platform, we
-//! don't currently support those. It's just for supporting small linker scripts that are put in
-//! place of .so files to tell the linker to load some other input file(s).
+//! This module is responsible for parsing linker scripts. Most commonly, these are small scripts
+//! that are put in place of .so files to tell the linker to load some other input file(s),
+//! possibly split across several files with `INCLUDE`.
+//!
+//! A `SECTIONS` command takes over the layout of the output. Input sections go in the first output
+//! section with a pattern that matches them and output sections are placed in the order listed,
+//! at the addresses given by assignments to the location counter, `.`. Sections that the script
+//! doesn't mention, which GNU ld calls orphans, are placed after the last listed section with
+//! similar flags. Within an output section, input sections stay in the order of the input files,
+//! rather than the order of the patterns that matched them. Input sections matched by a pattern
+//! in `KEEP` are roots for `--gc-sections`, as are those marked `SHF_GNU_RETAIN`. Assignments to
+//! `.` inside an output section, e.g. `. = ALIGN(8);`, move the start of the section if they come
+//! before its input section descriptions, otherwise they pad its end. Unless program headers are
+//! given with `PHDRS`, we create a loadable segment for each run of sections with the same
+//! permissions. Our file header
+//! and program headers are loaded at the start of the first of those if the script leaves room for
+//! them, e.g. with `. = 0x10000 + SIZEOF_HEADERS;`.
+//!
+//! Program headers defined with `PHDRS` replace the ones that we'd normally write. Sections are
+//! assigned to them with `:phdr` after their output section descriptions.
+//!
+//! `MEMORY` defines regions that output sections can be put in with `>region`, in which case they
+//! follow the previous section in the same region, and loaded from with `AT>region`. We report an
+//! error, along with how much of each region is used, if the sections don't fit. A load address
+//! can also be given directly with `AT(address)`, which can refer to earlier sections, e.g. via
+//! `LOADADDR(...)`. Other sections keep the same offset between their address and load address as
+//! the previous section in the same region, unless they're given an address.
+//!
+//! `OVERLAY` is expanded into an output section for each of its members. These all start at the
+//! address of the overlay, but each is loaded after the previous one, and whatever follows the
+//! overlay goes after the largest member. `__load_start_*` and `__load_stop_*` are provided for
+//! each member.
+//!
+//! Assignments to symbols, including those wrapped in `PROVIDE`, `PROVIDE_HIDDEN` or `HIDDEN`,
+//! are evaluated once sections have been placed, so they can use `ADDR`, `SIZEOF` and `DEFINED`.
+//! Within an output section, `.` is the start of the section if the assignment comes before any
+//! input section descriptions, otherwise its end, in either case moved along by any preceding
+//! assignments to `.`. `PROVIDE` only defines a symbol if something
+//! references it and no input file defines it.
+//!
+//! `--defsym` assignments are treated as if they came at the top level of a script, after any
+//! scripts. Expressions that refer to symbols from input files are evaluated again once those
+//! symbols have addresses.
+//!
+//! `ENTRY` sets the symbol used as the entry point instead of `_start`. It and the symbols named
+//! by `EXTERN` cause the archive members that define them to be loaded. `ASSERT`s are checked
+//! after all symbols have been assigned values, with `.` determined in the same way as for
+//! assignments.
+//!
+//! `INSERT AFTER section` or `INSERT BEFORE section` after a `SECTIONS` command moves the output
+//! sections that it lists next to the named section rather than taking over the layout. If that's
+//! true of all `SECTIONS` that we were given, then the rest of our usual layout is kept and the
+//! addresses in the script are ignored.
 
 use crate::args::Input;
 use crate::args::InputSpec;
 use crate::args::Modifiers;
 use crate::error::Result;
+use crate::glob::glob_matches;
 use crate::hash::PassThroughHasher;
 use crate::hash::PreHashed;
 use crate::input_data::VersionScriptData;
@@ -15,37 +65,747 @@ use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Context;
 use std::collections::HashSet;
+use std::ops::Range;
 use std::path::Path;
+use std::path::PathBuf;
+
+/// How deeply `INCLUDE` commands can be nested. This is the same limit as GNU ld and also stops us
+/// from recursing forever if a script includes itself.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// What we got from reading a linker script.
+#[derive(Default)]
+pub(crate) struct LinkerScript {
+    pub(crate) inputs: Vec<Input>,
+    pub(crate) layout: ScriptLayout,
+}
+
+/// The parts of linker scripts that affect how we lay out the output.
+#[derive(Default)]
+pub(crate) struct ScriptLayout {
+    pub(crate) section_fills: Vec<SectionFill>,
+
+    /// Program headers from `PHDRS`, in the order that they should be written.
+    pub(crate) program_headers: Vec<ProgramHeader>,
+
+    pub(crate) section_program_headers: Vec<SectionProgramHeaders>,
+
+    /// The contents of `SECTIONS`. If this isn't empty, it determines the order and addresses of
+    /// our output sections.
+    pub(crate) sections: Vec<SectionsCommand>,
+
+    /// Regions of memory from `MEMORY`, in the order that they were defined.
+    pub(crate) memory_regions: Vec<MemoryRegion>,
+
+    /// Assignments to symbols, both inside and outside of `SECTIONS`, in the order that they
+    /// appear.
+    pub(crate) symbol_assignments: Vec<SymbolAssignment>,
+
+    /// Conditions from `ASSERT`, which are checked once the layout is complete.
+    pub(crate) assertions: Vec<Assertion>,
+
+    /// The entry point from the last `ENTRY` command, if any.
+    pub(crate) entry: Option<String>,
+
+    /// Symbols from `EXTERN`, which are treated as referenced, so that the archive members that
+    /// define them get loaded.
+    pub(crate) extern_symbols: Vec<String>,
+
+    /// Runs of commands in `sections` that are followed by `INSERT`, so are placed relative to
+    /// another output section rather than in the order given.
+    pub(crate) insertions: Vec<Insertion>,
+}
+
+/// `INSERT AFTER section` or `INSERT BEFORE section`, which applies to the commands from the
+/// preceding `SECTIONS` of the same script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Insertion {
+    /// The indexes in `ScriptLayout::sections` of the commands being inserted.
+    pub(crate) commands: Range<usize>,
+
+    /// The name of the output section that the commands go next to.
+    pub(crate) anchor: String,
+
+    /// Whether the commands go after `anchor`, as opposed to before it.
+    pub(crate) after: bool,
+}
+
+impl std::fmt::Display for Insertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = if self.after { "AFTER" } else { "BEFORE" };
+        write!(f, "INSERT {direction} {}", self.anchor)
+    }
+}
+
+/// A region of memory defined with `MEMORY`, e.g. `FLASH (rx) : ORIGIN = 0x8000000, LENGTH = 512K`.
+/// As with GNU ld, the origin and length need to be constant, since they're evaluated when the
+/// script is read. The attributes, which GNU ld uses to pick regions for sections that the script
+/// doesn't say where to put, are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MemoryRegion {
+    pub(crate) name: String,
+    pub(crate) origin: u64,
+    pub(crate) length: u64,
+}
+
+/// An assignment to a symbol, e.g. `_etext = .;` or `PROVIDE(end = .);`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SymbolAssignment {
+    pub(crate) name: String,
+    pub(crate) value: Expression,
+
+    /// Whether the assignment is wrapped in `PROVIDE` or `PROVIDE_HIDDEN`, in which case it only
+    /// defines the symbol if it's referenced and not defined by any input file.
+    pub(crate) provide: bool,
+
+    /// Whether the assignment is wrapped in `HIDDEN` or `PROVIDE_HIDDEN`, in which case the symbol
+    /// isn't visible outside of the output.
+    pub(crate) hidden: bool,
+
+    pub(crate) position: AssignmentPosition,
+}
+
+/// An `ASSERT(condition, "message")`. If the condition evaluates to zero, the link fails with the
+/// message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Assertion {
+    pub(crate) condition: Expression,
+    pub(crate) message: String,
+    pub(crate) position: AssignmentPosition,
+}
+
+/// Where in a script a symbol assignment is, which determines the value of `.` in its expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AssignmentPosition {
+    /// Outside of `SECTIONS`, where `.` is zero.
+    TopLevel,
+
+    /// In `SECTIONS`, before the command with the supplied index in `ScriptLayout::sections`.
+    BeforeCommand(usize),
+
+    /// Inside the output section with the supplied index in `ScriptLayout::sections`. `.` is the
+    /// start of the section, if the assignment comes before any input section descriptions, or
+    /// otherwise its end, after the `location_index` assignments to `.` that precede the assignment
+    /// in the same part of the section.
+    InOutputSection {
+        index: usize,
+        at_end: bool,
+        location_index: usize,
+    },
+}
+
+impl AssignmentPosition {
+    /// Returns the position with any command index increased by `num_commands`.
+    fn offset(self, num_commands: usize) -> Self {
+        match self {
+            AssignmentPosition::TopLevel => AssignmentPosition::TopLevel,
+            AssignmentPosition::BeforeCommand(index) => {
+                AssignmentPosition::BeforeCommand(num_commands + index)
+            }
+            AssignmentPosition::InOutputSection {
+                index,
+                at_end,
+                location_index,
+            } => AssignmentPosition::InOutputSection {
+                index: num_commands + index,
+                at_end,
+                location_index,
+            },
+        }
+    }
+}
+
+/// A command from `SECTIONS` that affects the layout of the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SectionsCommand {
+    /// An assignment to the location counter, e.g. `. = ALIGN(0x1000);`. Compound assignments such
+    /// as `. += 0x100;` are expanded, so this is the new value.
+    SetLocation(Expression),
+    OutputSection(Box<OutputSectionCommand>),
+}
+
+/// An output section description, e.g. `.text 0x1000 : { *(.text .text.*) }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OutputSectionCommand {
+    pub(crate) name: String,
+
+    /// The address of the section, if given before the colon.
+    pub(crate) address: Option<Expression>,
+
+    /// The alignment from `ALIGN(...)` after the colon, if any.
+    pub(crate) alignment: Option<Expression>,
+
+    /// The memory region given with `>region`, which the section is placed in.
+    pub(crate) region: Option<String>,
+
+    /// The memory region given with `AT>region`, which the section is loaded from.
+    pub(crate) load_region: Option<String>,
+
+    /// The load address given with `AT(address)` after the colon, if any.
+    pub(crate) load_address: Option<Expression>,
+
+    /// Whether the section has type `NOLOAD`, so takes up memory, but not space in the file.
+    pub(crate) no_load: bool,
+
+    /// Whether the section is a member of an `OVERLAY` other than the first, so goes at the same
+    /// address as the previous section rather than after it, and is loaded after it.
+    pub(crate) shares_address: bool,
+
+    pub(crate) inputs: Vec<InputSectionDescription>,
+
+    /// Assignments to `.` that come before the input section descriptions, which move the start of
+    /// the section, e.g. `. = ALIGN(8);`.
+    pub(crate) start_locations: Vec<Expression>,
+
+    /// Assignments to `.` that come after the input section descriptions, or in a section without
+    /// any, which pad the end of the section, e.g. `. = . + 0x400;`.
+    pub(crate) end_locations: Vec<Expression>,
+}
+
+/// Which input sections go in an output section, e.g. `*crt0.o(.text .text.*)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InputSectionDescription {
+    pub(crate) file_pattern: String,
+
+    /// Patterns from `EXCLUDE_FILE(...)` for files whose sections don't match.
+    pub(crate) excluded_files: Vec<String>,
+
+    pub(crate) section_patterns: Vec<String>,
+
+    /// Whether the description is wrapped in `KEEP`, so the sections that it matches are kept
+    /// even if nothing refers to them.
+    pub(crate) keep: bool,
+}
+
+/// The name used in `SECTIONS` for sections that should be discarded.
+pub(crate) const DISCARD_SECTION_NAME: &str = "/DISCARD/";
+
+/// An expression in a linker script, e.g. `ALIGN(. + 0x100, 16)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Expression {
+    Number(u64),
+
+    /// The location counter, `.`.
+    Location,
+
+    /// A symbol or, as an argument to a function such as `SIZEOF`, a section name.
+    Name(String),
+
+    String(String),
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+    Call(String, Vec<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnaryOperator {
+    Negate,
+    Not,
+    Complement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOperator {
+    Multiply,
+    Divide,
+    Remainder,
+    Add,
+    Subtract,
+    ShiftLeft,
+    ShiftRight,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Xor,
+    Or,
+    LogicalAnd,
+    LogicalOr,
+}
+
+/// What we need in order to evaluate an expression.
+pub(crate) struct EvaluationContext<'a> {
+    /// The value of the location counter, `.`.
+    pub(crate) location: u64,
+
+    /// The value of `SIZEOF_HEADERS`.
+    pub(crate) sizeof_headers: u64,
+
+    /// The value of `CONSTANT(MAXPAGESIZE)`.
+    pub(crate) max_page_size: u64,
+
+    /// The value of `CONSTANT(COMMONPAGESIZE)`.
+    pub(crate) common_page_size: u64,
+
+    /// The regions that `ORIGIN(...)` and `LENGTH(...)` refer to.
+    pub(crate) memory_regions: &'a [MemoryRegion],
+
+    /// The layout that symbols and functions such as `ADDR(...)` refer to. This is None when we're
+    /// evaluating expressions in order to decide where sections go.
+    pub(crate) layout: Option<&'a dyn LayoutLookup>,
+}
+
+/// Lets expressions that define symbols refer to things that are only known once we've laid out
+/// the output.
+pub(crate) trait LayoutLookup {
+    /// Returns the address and size of the output section with the supplied name, or None if we're
+    /// not outputting it.
+    fn section(&self, name: &str) -> Option<(u64, u64)>;
+
+    /// Returns the load address of the output section with the supplied name, or None if we're not
+    /// outputting it. This is the value of `LOADADDR(name)`.
+    fn load_address(&self, name: &str) -> Option<u64>;
+
+    fn symbol_value(&self, name: &str) -> Result<u64>;
+
+    /// Returns whether the symbol is defined, either by an input file or by an assignment that
+    /// we've already evaluated. This is the value of `DEFINED(name)`.
+    fn is_defined(&self, name: &str) -> bool;
+}
+
+/// A program header defined in `PHDRS`, e.g. `text PT_LOAD FILEHDR PHDRS FLAGS(5);`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ProgramHeader {
+    pub(crate) name: String,
+    pub(crate) segment_type: u32,
+
+    /// Whether the segment includes the file header (`FILEHDR`).
+    pub(crate) includes_file_header: bool,
+
+    /// Whether the segment includes the program headers (`PHDRS`).
+    pub(crate) includes_program_headers: bool,
+
+    /// The physical address of the segment, from `AT(address)`.
+    pub(crate) physical_address: Option<u64>,
+
+    /// The segment flags, from `FLAGS(flags)`. If not given, we derive them from the sections in
+    /// the segment.
+    pub(crate) flags: Option<u32>,
+}
+
+/// The program headers that an output section is assigned to, from `:phdr` after its description.
+/// An empty list, from `:NONE`, means that the section isn't in any segment.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct SectionProgramHeaders {
+    pub(crate) section_name: String,
+    pub(crate) program_headers: Vec<String>,
+}
+
+impl ScriptLayout {
+    pub(crate) fn extend(&mut self, other: ScriptLayout) {
+        self.section_fills.extend(other.section_fills);
+        self.program_headers.extend(other.program_headers);
+        self.section_program_headers
+            .extend(other.section_program_headers);
+        self.memory_regions.extend(other.memory_regions);
+        // Positions in `other` refer to its own section commands, which now come after ours.
+        let num_commands = self.sections.len();
+        self.symbol_assignments
+            .extend(other.symbol_assignments.into_iter().map(|mut assignment| {
+                assignment.position = assignment.position.offset(num_commands);
+                assignment
+            }));
+        self.assertions
+            .extend(other.assertions.into_iter().map(|mut assertion| {
+                assertion.position = assertion.position.offset(num_commands);
+                assertion
+            }));
+        self.insertions
+            .extend(other.insertions.into_iter().map(|mut insertion| {
+                insertion.commands =
+                    num_commands + insertion.commands.start..num_commands + insertion.commands.end;
+                insertion
+            }));
+        self.sections.extend(other.sections);
+        if other.entry.is_some() {
+            self.entry = other.entry;
+        }
+        self.extern_symbols.extend(other.extern_symbols);
+    }
+
+    /// Returns whether `SECTIONS` takes over the layout of the output, which is the case unless all
+    /// of its commands are followed by `INSERT`.
+    pub(crate) fn replaces_default_layout(&self) -> bool {
+        (0..self.sections.len()).any(|index| self.insertion_for(index).is_none())
+    }
+
+    /// Returns whether the output section described by the command with the supplied index contains
+    /// assignments, either to `.` or to symbols. As with GNU ld, we output such sections even if
+    /// nothing goes in them, so that they can be referred to with `ADDR(...)` and the like.
+    pub(crate) fn section_has_assignments(&self, index: usize) -> bool {
+        let SectionsCommand::OutputSection(output) = &self.sections[index] else {
+            return false;
+        };
+        !output.start_locations.is_empty()
+            || !output.end_locations.is_empty()
+            || self.symbol_assignments.iter().any(|assignment| {
+                matches!(
+                    assignment.position,
+                    AssignmentPosition::InOutputSection { index: i, .. } if i == index
+                )
+            })
+    }
+
+    /// Returns the `INSERT` that applies to the command with the supplied index, if any.
+    pub(crate) fn insertion_for(&self, index: usize) -> Option<&Insertion> {
+        self.insertions
+            .iter()
+            .find(|insertion| insertion.commands.contains(&index))
+    }
+
+    /// Returns the name of the output section that `SECTIONS` puts the supplied input section in,
+    /// together with whether the pattern that matched it is in `KEEP`, or None if no pattern
+    /// matches it. `file_name` is the name of the input file, or of the archive member if the file
+    /// is from an archive, in which case `archive_name` is also set.
+    pub(crate) fn output_section_for(
+        &self,
+        archive_name: Option<&[u8]>,
+        file_name: &[u8],
+        section_name: &[u8],
+    ) -> Option<(&str, bool)> {
+        self.sections.iter().find_map(|command| {
+            let SectionsCommand::OutputSection(output) = command else {
+                return None;
+            };
+            output
+                .inputs
+                .iter()
+                .find(|input| input.matches(archive_name, file_name, section_name))
+                .map(|input| (output.name.as_str(), input.keep))
+        })
+    }
+}
+
+impl InputSectionDescription {
+    fn matches(&self, archive_name: Option<&[u8]>, file_name: &[u8], section_name: &[u8]) -> bool {
+        let matches_file = |pattern: &str| {
+            // As with GNU ld, `archive:member` matches a member of a particular archive.
+            match (pattern.split_once(':'), archive_name) {
+                (Some((archive_pattern, member_pattern)), Some(archive_name)) => {
+                    (archive_pattern.is_empty()
+                        || glob_matches(archive_pattern.as_bytes(), archive_name))
+                        && (member_pattern.is_empty()
+                            || glob_matches(member_pattern.as_bytes(), file_name))
+                }
+                (Some(_), None) => false,
+                (None, _) => glob_matches(pattern.as_bytes(), file_name),
+            }
+        };
+        matches_file(&self.file_pattern)
+            && !self
+                .excluded_files
+                .iter()
+                .any(|pattern| matches_file(pattern))
+            && self
+                .section_patterns
+                .iter()
+                .any(|pattern| glob_matches(pattern.as_bytes(), section_name))
+    }
+}
+
+impl Expression {
+    pub(crate) fn evaluate(&self, context: &EvaluationContext) -> Result<u64> {
+        Ok(match self {
+            Expression::Number(value) => *value,
+            Expression::Location => context.location,
+            Expression::Name(name) if name == "SIZEOF_HEADERS" => context.sizeof_headers,
+            Expression::Name(name) => match context.layout {
+                Some(layout) => layout.symbol_value(name)?,
+                None => bail!("Unsupported symbol `{name}` in linker script expression"),
+            },
+            Expression::String(string) => {
+                bail!("Unexpected string \"{string}\" in linker script expression")
+            }
+            Expression::Unary(operator, operand) => {
+                let value = operand.evaluate(context)?;
+                match operator {
+                    UnaryOperator::Negate => value.wrapping_neg(),
+                    UnaryOperator::Not => u64::from(value == 0),
+                    UnaryOperator::Complement => !value,
+                }
+            }
+            Expression::Binary(operator, left, right) => {
+                let left = left.evaluate(context)?;
+                let right = right.evaluate(context)?;
+                match operator {
+                    BinaryOperator::Multiply => left.wrapping_mul(right),
+                    BinaryOperator::Divide | BinaryOperator::Remainder if right == 0 => {
+                        bail!("Division by zero in linker script expression")
+                    }
+                    BinaryOperator::Divide => left / right,
+                    BinaryOperator::Remainder => left % right,
+                    BinaryOperator::Add => left.wrapping_add(right),
+                    BinaryOperator::Subtract => left.wrapping_sub(right),
+                    BinaryOperator::ShiftLeft => left.checked_shl(right as u32).unwrap_or(0),
+                    BinaryOperator::ShiftRight => left.checked_shr(right as u32).unwrap_or(0),
+                    BinaryOperator::Less => u64::from(left < right),
+                    BinaryOperator::LessOrEqual => u64::from(left <= right),
+                    BinaryOperator::Greater => u64::from(left > right),
+                    BinaryOperator::GreaterOrEqual => u64::from(left >= right),
+                    BinaryOperator::Equal => u64::from(left == right),
+                    BinaryOperator::NotEqual => u64::from(left != right),
+                    BinaryOperator::And => left & right,
+                    BinaryOperator::Xor => left ^ right,
+                    BinaryOperator::Or => left | right,
+                    BinaryOperator::LogicalAnd => u64::from(left != 0 && right != 0),
+                    BinaryOperator::LogicalOr => u64::from(left != 0 || right != 0),
+                }
+            }
+            Expression::Conditional(condition, if_true, if_false) => {
+                if condition.evaluate(context)? != 0 {
+                    if_true.evaluate(context)?
+                } else {
+                    if_false.evaluate(context)?
+                }
+            }
+            Expression::Call(function, args) => evaluate_call(function, args, context)?,
+        })
+    }
+
+    /// Returns whether the value of the expression doesn't depend on where anything is placed, so
+    /// that a symbol with this value should be absolute rather than an address.
+    pub(crate) fn is_absolute(&self) -> bool {
+        match self {
+            Expression::Number(_) | Expression::String(_) => true,
+            Expression::Location => false,
+            Expression::Name(name) => name == "SIZEOF_HEADERS",
+            Expression::Unary(_, operand) => operand.is_absolute(),
+            Expression::Binary(_, left, right) => left.is_absolute() && right.is_absolute(),
+            Expression::Conditional(condition, if_true, if_false) => {
+                condition.is_absolute() && if_true.is_absolute() && if_false.is_absolute()
+            }
+            Expression::Call(function, args) => match function.as_str() {
+                "ABSOLUTE" | "SIZEOF" | "DEFINED" | "ORIGIN" | "org" | "LENGTH" | "len"
+                | "CONSTANT" | "LOADADDR" => true,
+                "ADDR" => false,
+                "ALIGN" if args.len() == 1 => false,
+                _ => args.iter().all(Expression::is_absolute),
+            },
+        }
+    }
+
+    /// Returns whether this is `ALIGN(n)` or `ALIGN(., n)`, which only move the location counter
+    /// on by less than the alignment.
+    pub(crate) fn is_location_alignment(&self) -> bool {
+        match self {
+            Expression::Call(function, args) if function == "ALIGN" => {
+                args.len() == 1 || args.first() == Some(&Expression::Location)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn evaluate_call(function: &str, args: &[Expression], context: &EvaluationContext) -> Result<u64> {
+    let arg = |index: usize| -> Result<u64> {
+        args.get(index)
+            .with_context(|| format!("Too few arguments to {function}"))?
+            .evaluate(context)
+    };
+    Ok(match (function, args.len()) {
+        ("ALIGN", 1) => align_up(context.location, arg(0)?),
+        ("ALIGN", 2) => align_up(arg(0)?, arg(1)?),
+        ("ABSOLUTE", 1) => arg(0)?,
+        ("MAX", 2) => arg(0)?.max(arg(1)?),
+        ("MIN", 2) => arg(0)?.min(arg(1)?),
+        // We don't support setting segment addresses with options like `-Ttext-segment` in
+        // combination with linker scripts, so this is always the default.
+        ("SEGMENT_START", 2) => arg(1)?,
+        ("ADDR", 1) => {
+            let (name, section) = output_section(function, &args[0], context)?;
+            section
+                .with_context(|| {
+                    format!("ADDR({name}) refers to a section that isn't being output")
+                })?
+                .0
+        }
+        ("LOADADDR", 1) => {
+            let Expression::Name(name) = &args[0] else {
+                bail!("Expected section name in LOADADDR, got {:?}", args[0]);
+            };
+            context
+                .layout
+                .with_context(|| format!("LOADADDR({name}) can only be used in assignments"))?
+                .load_address(name)
+                .with_context(|| {
+                    format!("LOADADDR({name}) refers to a section that isn't being output")
+                })?
+        }
+        ("SIZEOF", 1) => output_section(function, &args[0], context)?
+            .1
+            .map_or(0, |(_, size)| size),
+        ("DEFINED", 1) => {
+            let Expression::Name(name) = &args[0] else {
+                bail!("Expected symbol name in DEFINED, got {:?}", args[0]);
+            };
+            let layout = context.layout.with_context(|| {
+                format!("DEFINED({name}) can only be used in assignments to symbols")
+            })?;
+            u64::from(layout.is_defined(name))
+        }
+        ("ORIGIN" | "org", 1) => memory_region(&args[0], context)?.origin,
+        ("LENGTH" | "len", 1) => memory_region(&args[0], context)?.length,
+        ("CONSTANT", 1) => match &args[0] {
+            Expression::Name(name) if name == "MAXPAGESIZE" => context.max_page_size,
+            Expression::Name(name) if name == "COMMONPAGESIZE" => context.common_page_size,
+            other => bail!("Unsupported constant {other:?} in linker script expression"),
+        },
+        // As an expression, e.g. `_x = ASSERT(...)`, this has the value of the condition.
+        ("ASSERT", 2) => {
+            let value = arg(0)?;
+            if value == 0 {
+                let (Expression::String(message) | Expression::Name(message)) = &args[1] else {
+                    bail!("Expected message in ASSERT, got {:?}", args[1]);
+                };
+                bail!("Linker script assertion failed: {message}");
+            }
+            value
+        }
+        _ => bail!("Unsupported function `{function}` in linker script expression"),
+    })
+}
+
+/// Returns the name of the output section that `name` refers to, together with its address and
+/// size, or None if it isn't being output.
+fn output_section<'a>(
+    function: &str,
+    name: &'a Expression,
+    context: &EvaluationContext,
+) -> Result<(&'a str, Option<(u64, u64)>)> {
+    let Expression::Name(name) = name else {
+        bail!("Expected section name in {function}, got {name:?}");
+    };
+    let layout = context.layout.with_context(|| {
+        format!("{function}({name}) can only be used in assignments to symbols")
+    })?;
+    Ok((name, layout.section(name)))
+}
+
+fn memory_region<'a>(
+    name: &Expression,
+    context: &EvaluationContext<'a>,
+) -> Result<&'a MemoryRegion> {
+    let Expression::Name(name) = name else {
+        bail!("Expected memory region name, got {name:?}");
+    };
+    context
+        .memory_regions
+        .iter()
+        .find(|region| region.name == *name)
+        .with_context(|| format!("Unknown memory region `{name}`"))
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    if alignment == 0 {
+        return value;
+    }
+    value.div_ceil(alignment).wrapping_mul(alignment)
+}
+
+/// A pattern with which to fill the gaps in an output section, from `=fillexp` or `FILL(expr)` in
+/// the section's description.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct SectionFill {
+    pub(crate) section_name: String,
+    pub(crate) pattern: Vec<u8>,
+}
 
 /// Parse the kind of linker script that's put in place of a shared object to specify that the
 /// linker should load several files.
-pub(crate) fn linker_script_to_inputs(
+pub(crate) fn read_linker_script(
+    bytes: &[u8],
+    path: &Path,
+    modifiers: Modifiers,
+    lib_search_path: &[Box<Path>],
+) -> Result<LinkerScript> {
+    read_script(bytes, path, modifiers, lib_search_path, 0)
+}
+
+/// Parses the argument to `--defsym`, e.g. `end=origin+0x1000`. The result is evaluated like an
+/// assignment at the top level of a linker script, after those from any scripts.
+pub(crate) fn parse_defsym(text: &str) -> Result<SymbolAssignment> {
+    let mut tokens = Tokeniser::new(text);
+    let (name, value) = match take_assignment(&mut tokens)? {
+        Some((name, value)) if name != "." => (name, value),
+        _ => bail!("--defsym requires an argument of the form SYMBOL=EXPRESSION"),
+    };
+    if let Some(token) = tokens.next_expression_token() {
+        bail!("Unexpected `{token}` after --defsym expression for `{name}`");
+    }
+    Ok(SymbolAssignment::new(name, value, false, false))
+}
+
+fn read_script(
     bytes: &[u8],
     path: &Path,
     modifiers: Modifiers,
-) -> Result<Vec<Input>> {
+    lib_search_path: &[Box<Path>],
+    depth: usize,
+) -> Result<LinkerScript> {
     let text = std::str::from_utf8(bytes)?;
     let directory = path
         .parent()
         .ok_or_else(|| anyhow!("Need directory for path `{}`", path.display()))?;
-    Ok(inputs_from_script(text, modifiers)
-        .with_context(|| format!("Failed to parse linker script `{}`", path.display()))?
-        .into_iter()
-        .map(|mut input| {
-            input.search_first = Some(directory.to_owned());
-            input
-        })
-        .collect())
+    let mut include = |name: &str, modifiers: Modifiers| {
+        if depth >= MAX_INCLUDE_DEPTH {
+            bail!("Linker script `INCLUDE`s are nested more than {MAX_INCLUDE_DEPTH} deep");
+        }
+        let included = find_include(name, lib_search_path)?;
+        let bytes = std::fs::read(&included)
+            .with_context(|| format!("Failed to read `{}`", included.display()))?;
+        read_script(&bytes, &included, modifiers, lib_search_path, depth + 1)
+    };
+    let mut script = parse_script(text, modifiers, &mut include)
+        .with_context(|| format!("Failed to parse linker script `{}`", path.display()))?;
+    for input in &mut script.inputs {
+        // Inputs from included scripts are searched for relative to the script that named them.
+        input
+            .search_first
+            .get_or_insert_with(|| directory.to_owned());
+    }
+    Ok(script)
+}
+
+/// Finds a file named by an `INCLUDE` command. Like GNU ld, we look in the current directory, then
+/// on the library search path.
+fn find_include(name: &str, lib_search_path: &[Box<Path>]) -> Result<PathBuf> {
+    let path = Path::new(name);
+    if path.exists() {
+        return Ok(path.to_owned());
+    }
+    crate::input_data::search_for_file(lib_search_path, None, path)
+        .with_context(|| format!("Couldn't find included linker script `{name}`"))
 }
 
 /// A version script. See https://sourceware.org/binutils/docs/ld/VERSION.html
+///
+/// A script either contains a single anonymous version, which just says what should be global and
+/// what should be local, or any number of named versions. Named versions are given indexes in the
+/// order that they're defined, starting after `VER_NDX_GLOBAL`, which is used for the base
+/// definition.
+///
+/// Patterns inside `extern "C++" { ... }` are matched against demangled names. We demangle both
+/// Itanium C++ and Rust v0 names. As with GNU ld, a name that doesn't demangle is matched as-is.
 #[derive(Default)]
 pub(crate) struct VersionScript<'data> {
-    // For now, we only support a single version.
-    version: Option<Version<'data>>,
+    versions: Vec<Version<'data>>,
+
+    /// Whether any version has `extern "C++"` patterns, in which case we need to demangle names in
+    /// order to match them.
+    matches_demangled: bool,
 }
 
-struct Version<'data> {
+pub(crate) struct Version<'data> {
+    /// The name of the version, or `None` if it's anonymous.
+    pub(crate) name: Option<&'data str>,
+
+    /// The versions that this one depends on, from after its closing brace.
+    pub(crate) parents: Vec<&'data str>,
+
     globals: MatchRules<'data>,
     locals: MatchRules<'data>,
 }
@@ -55,6 +815,19 @@ struct MatchRules<'data> {
     matches_all: bool,
     exact: HashSet<PreHashed<SymbolName<'data>>, PassThroughHasher>,
     prefixes: Vec<&'data [u8]>,
+
+    /// Patterns from `extern "C++"` blocks, which are matched against demangled names.
+    demangled_exact: HashSet<PreHashed<SymbolName<'data>>, PassThroughHasher>,
+    demangled_globs: Vec<&'data str>,
+}
+
+/// How specific a pattern in a version script is. Where a symbol matches patterns in more than one
+/// place, the most specific one decides its version and whether it's local.
+#[derive(Clone, Copy)]
+enum MatchKind {
+    Exact,
+    Glob,
+    All,
 }
 
 impl<'data> MatchRules<'data> {
@@ -68,13 +841,51 @@ impl<'data> MatchRules<'data> {
         }
     }
 
-    fn matches(&self, name: &PreHashed<SymbolName>) -> bool {
-        self.matches_all
-            || self.exact.contains(name)
-            || self
-                .prefixes
-                .iter()
-                .any(|prefix| name.bytes().starts_with(prefix))
+    /// Adds a pattern from an `extern "C++"` block. Quoted patterns are matched literally.
+    fn push_demangled(&mut self, pattern: &'data str) {
+        if let Some(literal) = pattern.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+            self.demangled_exact
+                .insert(SymbolName::prehashed(literal.as_bytes()));
+        } else if pattern.contains(['*', '?', '[']) {
+            self.demangled_globs.push(pattern);
+        } else {
+            self.demangled_exact
+                .insert(SymbolName::prehashed(pattern.as_bytes()));
+        }
+    }
+
+    /// Returns whether `name` matches one of our patterns of the specified kind. `demangled` is
+    /// what `extern "C++"` patterns are matched against, if we have any.
+    fn matches(
+        &self,
+        name: &PreHashed<SymbolName>,
+        demangled: Option<&str>,
+        kind: MatchKind,
+    ) -> bool {
+        match kind {
+            MatchKind::Exact => {
+                self.exact.contains(name)
+                    || demangled.is_some_and(|d| {
+                        self.demangled_exact
+                            .contains(&SymbolName::prehashed(d.as_bytes()))
+                    })
+            }
+            MatchKind::Glob => {
+                self.prefixes
+                    .iter()
+                    .any(|prefix| name.bytes().starts_with(prefix))
+                    || demangled.is_some_and(|d| {
+                        self.demangled_globs
+                            .iter()
+                            .any(|pattern| glob_matches(pattern.as_bytes(), d.as_bytes()))
+                    })
+            }
+            MatchKind::All => self.matches_all,
+        }
+    }
+
+    fn has_demangled_patterns(&self) -> bool {
+        !self.demangled_exact.is_empty() || !self.demangled_globs.is_empty()
     }
 }
 
@@ -89,18 +900,170 @@ impl<'data> VersionScript<'data> {
     #[tracing::instrument(skip_all, name = "Parse version script")]
     pub(crate) fn parse(data: &'data VersionScriptData) -> Result<VersionScript<'data>> {
         let mut tokens = Tokeniser::new(&data.raw);
-        // For now, we only support anonymous versions - i.e. a single version that just says what
-        // should be global and what should be local.
-        tokens.expect("{")?;
-        let version = Version::parse(&mut tokens)?;
+        let mut versions: Vec<Version> = Vec::new();
+        while let Some(token) = tokens.next() {
+            let name = match token {
+                ";" => continue,
+                "{" => None,
+                name => {
+                    tokens
+                        .expect("{")
+                        .with_context(|| format!("Failed to parse version `{name}`"))?;
+                    Some(name)
+                }
+            };
+            if versions.iter().any(|version| version.name == name) {
+                bail!("Version `{}` is defined more than once", name.unwrap_or(""));
+            }
+            let version = Version::parse(&mut tokens, name)?;
+            if let Some(parent) = version
+                .parents
+                .iter()
+                .find(|parent| !versions.iter().any(|v| v.name == Some(**parent)))
+            {
+                bail!(
+                    "Version `{}` depends on `{parent}`, which isn't defined before it",
+                    name.unwrap_or("")
+                );
+            }
+            versions.push(version);
+        }
+        if versions.len() > 1 && versions.iter().any(|version| version.name.is_none()) {
+            bail!("An anonymous version can't be combined with other versions");
+        }
+        let matches_demangled = versions.iter().any(|version| {
+            version.globals.has_demangled_patterns() || version.locals.has_demangled_patterns()
+        });
         Ok(VersionScript {
-            version: Some(version),
+            versions,
+            matches_demangled,
         })
     }
 
     pub(crate) fn is_local(&self, name: &PreHashed<SymbolName>) -> bool {
-        self.version.as_ref().is_some_and(|ver| ver.is_local(name))
+        self.rule_for(name).is_some_and(|(_, is_local)| is_local)
     }
+
+    /// Returns the index of the version that a global symbol with the supplied name should be
+    /// given. This is `VER_NDX_GLOBAL` if the symbol isn't given a named version.
+    pub(crate) fn version_index(&self, name: &PreHashed<SymbolName>) -> u16 {
+        match self.rule_for(name) {
+            Some((index, false)) if self.versions[index].name.is_some() => {
+                object::elf::VER_NDX_GLOBAL + 1 + index as u16
+            }
+            _ => object::elf::VER_NDX_GLOBAL,
+        }
+    }
+
+    /// Returns the index of the version with the most specific pattern that matches `name` and
+    /// whether that pattern says that the symbol is local. Global patterns win over local ones
+    /// that are just as specific.
+    fn rule_for(&self, name: &PreHashed<SymbolName>) -> Option<(usize, bool)> {
+        let demangled = self.demangled(name);
+        [MatchKind::Exact, MatchKind::Glob, MatchKind::All]
+            .into_iter()
+            .find_map(|kind| {
+                let matching = |is_local: bool| {
+                    self.versions.iter().position(|version| {
+                        let rules = if is_local {
+                            &version.locals
+                        } else {
+                            &version.globals
+                        };
+                        rules.matches(name, demangled.as_deref(), kind)
+                    })
+                };
+                matching(false)
+                    .map(|index| (index, false))
+                    .or_else(|| matching(true).map(|index| (index, true)))
+            })
+    }
+
+    /// Returns the index of the version called `version_name`, or `None` if we don't define it.
+    /// This is how symbols that `.symver` puts in a particular version find their index.
+    pub(crate) fn named_version_index(&self, version_name: &[u8]) -> Option<u16> {
+        self.versions
+            .iter()
+            .position(|version| version.name.is_some_and(|n| n.as_bytes() == version_name))
+            .map(|index| object::elf::VER_NDX_GLOBAL + 1 + index as u16)
+    }
+
+    /// Returns whether a symbol that `.symver` puts in the version called `version_name` is made
+    /// local by that version's patterns. Patterns in other versions don't apply to it.
+    pub(crate) fn is_local_in_version(
+        &self,
+        name: &PreHashed<SymbolName>,
+        version_name: &[u8],
+    ) -> bool {
+        let Some(version) = self
+            .versions
+            .iter()
+            .find(|version| version.name.is_some_and(|n| n.as_bytes() == version_name))
+        else {
+            return false;
+        };
+        let demangled = self.demangled(name);
+        [MatchKind::Exact, MatchKind::Glob, MatchKind::All]
+            .into_iter()
+            .find_map(|kind| {
+                if version.globals.matches(name, demangled.as_deref(), kind) {
+                    Some(false)
+                } else if version.locals.matches(name, demangled.as_deref(), kind) {
+                    Some(true)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns what `extern "C++"` patterns should be matched against, if we have any.
+    fn demangled(&self, name: &PreHashed<SymbolName>) -> Option<String> {
+        if !self.matches_demangled {
+            return None;
+        }
+        demangle(name.bytes()).or_else(|| std::str::from_utf8(name.bytes()).ok().map(Into::into))
+    }
+
+    /// Returns the names that versions explicitly make global, together with the name of the
+    /// version, if any. Patterns with wildcards and `extern "C++"` patterns aren't included.
+    pub(crate) fn exact_globals(
+        &self,
+    ) -> impl Iterator<Item = (Option<&'data str>, &PreHashed<SymbolName<'data>>)> {
+        self.versions.iter().flat_map(|version| {
+            version
+                .globals
+                .exact
+                .iter()
+                .map(|name| (version.name, name))
+        })
+    }
+
+    /// Returns the versions that we define, in order of their indexes. This is empty if the script
+    /// only has an anonymous version.
+    pub(crate) fn named_versions(&self) -> &[Version<'data>] {
+        if self.versions.iter().any(|version| version.name.is_some()) {
+            &self.versions
+        } else {
+            &[]
+        }
+    }
+}
+
+/// Returns the demangled form of `name` if it's an Itanium C++ or Rust v0 symbol name. Rust names
+/// are demangled without their hashes, since those aren't something that a script can know.
+fn demangle(name: &[u8]) -> Option<String> {
+    if name.starts_with(b"_R") {
+        let name = std::str::from_utf8(name).ok()?;
+        return Some(format!("{:#}", rustc_demangle::try_demangle(name).ok()?));
+    }
+    if name.starts_with(b"_Z") {
+        return cpp_demangle::BorrowedSymbol::new(name)
+            .ok()?
+            .demangle(&cpp_demangle::DemangleOptions::default())
+            .ok();
+    }
+    None
 }
 
 enum VersionRuleSection {
@@ -109,60 +1072,126 @@ enum VersionRuleSection {
 }
 
 impl<'data> Version<'data> {
-    fn parse(tokens: &mut Tokeniser<'data>) -> Result<Version<'data>> {
+    /// Parses the contents of a version, the opening brace having already been consumed. This
+    /// needs to be fast, since e.g. rustc can emit a version script that's more than 300k lines, so
+    /// rather than tokenising, we just look for the end of each pattern.
+    fn parse(tokens: &mut Tokeniser<'data>, name: Option<&'data str>) -> Result<Version<'data>> {
         let mut version = Version {
+            name,
+            parents: Vec::new(),
             globals: Default::default(),
             locals: Default::default(),
         };
-        let mut section = None;
-        // We read line-by-line rather than token-by-token because it's much faster. This is
-        // important when for example rustc emits a version script that's more than 300k lines.
-        while let Some(line) = tokens.next_line() {
-            let mut line = line.trim();
-            if line.starts_with('}') {
+        // Patterns that come before `global:` or `local:` are global.
+        let mut section = VersionRuleSection::Global;
+        loop {
+            tokens.skip_whitespace_and_comments();
+            let text = tokens.text;
+            if text.is_empty() {
+                bail!("Missing close '}}' in version script");
+            }
+            if let Some(rest) = text.strip_prefix('}') {
+                tokens.text = rest;
+                version.parse_parents(tokens);
                 return Ok(version);
             }
-            // Note, we don't currently support comments that have content after them on the same
-            // line. Doing so would require us to search every line for embedded comments, which
-            // would hurt performance.
-            if line.ends_with("*/") {
-                if let Some(start_index) = line.find("/*") {
-                    line = line[..start_index].trim();
-                }
+            if let Some(rest) = text.strip_prefix("global:") {
+                section = VersionRuleSection::Global;
+                tokens.text = rest;
+                continue;
             }
-
-            if line.starts_with("/*") {
-                while let Some(line) = tokens.next_line() {
-                    if line.ends_with("*/") {
-                        break;
-                    }
-                }
-            } else if line == "global:" {
-                section = Some(VersionRuleSection::Global);
-            } else if line == "local:" {
-                section = Some(VersionRuleSection::Local);
-            } else if let Some(pattern) = line.strip_suffix(';') {
-                match section {
-                    Some(VersionRuleSection::Global) => {
-                        version.globals.push(SymbolMatcher::from_pattern(pattern)?);
-                    }
-                    Some(VersionRuleSection::Local) => {
-                        version.locals.push(SymbolMatcher::from_pattern(pattern)?);
-                    }
-                    None => bail!("Expected global/local, found `{line}`"),
-                }
-            } else if !line.is_empty() {
+            if let Some(rest) = text.strip_prefix("local:") {
+                section = VersionRuleSection::Local;
+                tokens.text = rest;
+                continue;
+            }
+            if let Some(rest) = text
+                .strip_prefix("extern")
+                .filter(|rest| rest.starts_with(|c: char| c.is_whitespace() || c == '"'))
+            {
+                tokens.text = rest;
+                let rules = match section {
+                    VersionRuleSection::Global => &mut version.globals,
+                    VersionRuleSection::Local => &mut version.locals,
+                };
+                parse_extern_block(tokens, rules)?;
+                continue;
+            }
+            let end = memchr::memchr(b';', text.as_bytes()).unwrap_or(text.len());
+            let pattern = text[..end].trim_end();
+            if pattern.contains(['\n', '{', '}']) || end == text.len() {
+                let line = text.lines().next().unwrap_or_default();
                 bail!("Unsupported version script line `{line}`");
             }
+            tokens.text = &text[end + 1..];
+            let pattern = SymbolMatcher::from_pattern(pattern)?;
+            match section {
+                VersionRuleSection::Global => version.globals.push(pattern),
+                VersionRuleSection::Local => version.locals.push(pattern),
+            }
         }
-        bail!("Missing close '}}' in version script");
     }
 
-    fn is_local(&self, name: &PreHashed<SymbolName>) -> bool {
-        if self.globals.matches(name) {
-            return false;
+    /// Parses the names of the versions that this one depends on, which follow its closing brace.
+    fn parse_parents(&mut self, tokens: &mut Tokeniser<'data>) {
+        while let Some(token) = tokens.peek() {
+            if token == ";" {
+                tokens.next();
+                return;
+            }
+            let mut lookahead = *tokens;
+            lookahead.next();
+            // Without a semicolon, the next thing might be the start of another version.
+            if token == "{" || token == "}" || lookahead.peek() == Some("{") {
+                return;
+            }
+            self.parents.push(token);
+            *tokens = lookahead;
+        }
+    }
+}
+
+/// Parses `"C++" { ... }` after `extern` in a version script, adding its patterns to `rules`.
+fn parse_extern_block<'data>(
+    tokens: &mut Tokeniser<'data>,
+    rules: &mut MatchRules<'data>,
+) -> Result {
+    match tokens.next() {
+        Some("\"C++\"") => {}
+        Some(language) => bail!("Unsupported language {language} in version script"),
+        None => bail!("Expected language after `extern` in version script"),
+    }
+    tokens.expect("{")?;
+    loop {
+        tokens.skip_whitespace_and_comments();
+        let text = tokens.text;
+        if let Some(rest) = text.strip_prefix('}') {
+            tokens.text = rest;
+            tokens.skip_whitespace_and_comments();
+            if let Some(rest) = tokens.text.strip_prefix(';') {
+                tokens.text = rest;
+            }
+            return Ok(());
+        }
+        // Quoted patterns may contain characters that would otherwise end the pattern.
+        let search_from = if text.starts_with('"') {
+            memchr::memchr(b'"', &text.as_bytes()[1..])
+                .map(|end| end + 2)
+                .context("Unterminated string in version script")?
+        } else {
+            0
+        };
+        let Some(end) = memchr::memchr2(b';', b'}', &text.as_bytes()[search_from..])
+            .map(|end| end + search_from)
+        else {
+            bail!("Missing close '}}' in version script");
+        };
+        let pattern = text[..end].trim_end();
+        if !pattern.is_empty() {
+            rules.push_demangled(pattern);
         }
-        self.locals.matches(name)
+        // Leave a closing brace to be consumed on the next iteration.
+        tokens.text = text[end..].strip_prefix(';').unwrap_or(&text[end..]);
     }
 }
 
@@ -183,13 +1212,19 @@ impl<'data> SymbolMatcher<'data> {
         Ok(SymbolMatcher::Exact(token))
     }
 }
-
+#[derive(Clone, Copy)]
 struct Tokeniser<'a> {
     text: &'a str,
 }
 
+/// Operators in expressions that are longer than a single character, longest first.
+const MULTI_CHAR_OPERATORS: &[&str] = &[
+    "<<=", ">>=", "<<", ">>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "&=",
+    "|=",
+];
+
 impl<'a> Tokeniser<'a> {
-    fn next(&mut self) -> Option<&'a str> {
+    fn skip_whitespace_and_comments(&mut self) {
         loop {
             self.text = self.text.trim_start();
             if try_take(&mut self.text, "/*") {
@@ -204,38 +1239,70 @@ impl<'a> Tokeniser<'a> {
                 }
                 continue;
             }
-            if self.text.is_empty() {
-                return None;
-            }
-            let bytes = self.text.as_bytes();
-            let mut len = 0;
-            for byte in bytes {
-                if b" \n\t(){};".contains(byte) {
-                    break;
-                }
-                len += 1;
-            }
-            if len == 0 {
-                len = 1;
-            }
-            let token = &self.text[..len];
-            self.text = &self.text[len..];
-            return Some(token);
+            return;
         }
     }
 
-    fn next_line(&mut self) -> Option<&'a str> {
-        while let Some(rest) = self.text.strip_prefix('\n') {
-            self.text = rest;
-        }
+    fn next(&mut self) -> Option<&'a str> {
+        self.skip_whitespace_and_comments();
         if self.text.is_empty() {
             return None;
         }
         let bytes = self.text.as_bytes();
-        let end_pos = memchr::memchr(b'\n', bytes).unwrap_or(bytes.len());
-        let (line, rest) = self.text.split_at(end_pos);
+        let mut len = 0;
+        for byte in bytes {
+            if b" \n\t(){};".contains(byte) {
+                break;
+            }
+            len += 1;
+        }
+        if len == 0 {
+            len = 1;
+        }
+        Some(self.take(len))
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        { *self }.next()
+    }
+
+    /// Returns the next token of an expression. Unlike `next`, this splits on operators, so
+    /// `.+4` is three tokens, but section names can't contain characters like `*`.
+    fn next_expression_token(&mut self) -> Option<&'a str> {
+        self.skip_whitespace_and_comments();
+        let first = *self.text.as_bytes().first()?;
+        let len = if is_name_byte(first) {
+            self.text.bytes().take_while(|b| is_name_byte(*b)).count()
+        } else if first == b'"' {
+            memchr::memchr(b'"', &self.text.as_bytes()[1..]).map_or(self.text.len(), |end| end + 2)
+        } else {
+            MULTI_CHAR_OPERATORS
+                .iter()
+                .find(|operator| self.text.starts_with(*operator))
+                .map_or_else(
+                    || self.text.chars().next().map_or(1, char::len_utf8),
+                    |operator| operator.len(),
+                )
+        };
+        Some(self.take(len))
+    }
+
+    fn peek_expression_token(&self) -> Option<&'a str> {
+        { *self }.next_expression_token()
+    }
+
+    fn expect_expression_token(&mut self, expected: &str) -> Result {
+        match self.next_expression_token() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => bail!("Expected token '{expected}', got '{token}'"),
+            None => bail!("Expected token '{expected}', got end of input"),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> &'a str {
+        let (token, rest) = self.text.split_at(len);
         self.text = rest;
-        Some(line)
+        token
     }
 
     fn new(text: &'a str) -> Self {
@@ -249,66 +1316,1132 @@ impl<'a> Tokeniser<'a> {
         if token != expected {
             bail!("Expected token '{expected}', got '{token}'");
         }
-        Ok(())
+        Ok(())
+    }
+}
+
+enum Command<'a> {
+    Arg(&'a str),
+    Include(&'a str),
+    Group(Vec<Command<'a>>),
+    AsNeeded(Vec<Command<'a>>),
+    Sections(Vec<SectionsItem<'a>>),
+    Memory(Vec<MemoryRegion>),
+    Phdrs(Vec<ProgramHeader>),
+    Assignment(SymbolAssignment),
+    Assertion(Assertion),
+    Entry(&'a str),
+    Extern(Vec<&'a str>),
+    Insert { after: bool, anchor: &'a str },
+    Ignored,
+}
+
+enum SectionsItem<'a> {
+    SetLocation(Expression),
+    Assignment(SymbolAssignment),
+    Assertion(Assertion),
+    OutputSection(Box<OutputSectionDescription<'a>>),
+}
+
+struct OutputSectionDescription<'a> {
+    command: OutputSectionCommand,
+    fill: Option<Vec<u8>>,
+
+    /// Assignments to symbols within the section, each with where in the section it is.
+    symbol_assignments: Vec<(SymbolAssignment, InSectionPosition)>,
+
+    /// `ASSERT`s within the section, each with where in the section it is.
+    assertions: Vec<(Assertion, InSectionPosition)>,
+
+    /// The names given with `:phdr`, if any.
+    program_headers: Option<Vec<&'a str>>,
+}
+
+/// Where within an output section an assignment to a symbol or an `ASSERT` is. See
+/// `AssignmentPosition::InOutputSection`.
+#[derive(Clone, Copy)]
+struct InSectionPosition {
+    at_end: bool,
+    location_index: usize,
+}
+
+fn parse_commands_up_to<'a>(
+    tokens: &mut Tokeniser<'a>,
+    end: Option<&str>,
+) -> Result<Vec<Command<'a>>> {
+    let mut out = Vec::new();
+    loop {
+        if end.is_none() {
+            match take_assignment_statement(tokens)? {
+                Some(Assignment::Symbol(assignment)) => {
+                    out.push(Command::Assignment(assignment));
+                    continue;
+                }
+                Some(Assignment::Location(_)) => {
+                    bail!("Assignments to `.` are only supported within SECTIONS")
+                }
+                Some(Assignment::Assertion(assertion)) => {
+                    out.push(Command::Assertion(assertion));
+                    continue;
+                }
+                None => {}
+            }
+        }
+        let Some(token) = tokens.next() else {
+            break;
+        };
+        if end == Some(token) {
+            return Ok(out);
+        }
+        if token.chars().all(|ch| ch.is_ascii_uppercase() || ch == '_') {
+            out.push(parse_command(tokens, token)?);
+        } else {
+            out.push(Command::Arg(token));
+        }
+    }
+    if let Some(expected) = end {
+        bail!("Got end of script, expected '{expected}'");
+    }
+    Ok(out)
+}
+
+fn parse_command<'a>(tokens: &mut Tokeniser<'a>, token: &str) -> Result<Command<'a>> {
+    match token {
+        "GROUP" | "INPUT" => {
+            tokens.expect("(")?;
+            Ok(Command::Group(parse_commands_up_to(tokens, Some(")"))?))
+        }
+        "OUTPUT_FORMAT" => {
+            tokens.expect("(")?;
+            parse_commands_up_to(tokens, Some(")"))?;
+            Ok(Command::Ignored)
+        }
+        "AS_NEEDED" => {
+            tokens.expect("(")?;
+            Ok(Command::AsNeeded(parse_commands_up_to(tokens, Some(")"))?))
+        }
+        "SECTIONS" => {
+            tokens.expect("{")?;
+            Ok(Command::Sections(parse_sections(tokens)?))
+        }
+        "PHDRS" => {
+            tokens.expect("{")?;
+            Ok(Command::Phdrs(parse_phdrs(tokens)?))
+        }
+        "MEMORY" => {
+            tokens.expect("{")?;
+            Ok(Command::Memory(parse_memory(tokens)?))
+        }
+        "INCLUDE" => Ok(Command::Include(
+            tokens
+                .next()
+                .context("Expected a filename after `INCLUDE`")?,
+        )),
+        "ENTRY" => {
+            tokens.expect("(")?;
+            let symbol = tokens.next().context("Expected a symbol name in `ENTRY`")?;
+            tokens.expect(")")?;
+            Ok(Command::Entry(symbol))
+        }
+        "EXTERN" => {
+            tokens.expect("(")?;
+            let mut symbols = Vec::new();
+            loop {
+                let token = tokens.next().context("Got end of script, expected ')'")?;
+                if token == ")" {
+                    break;
+                }
+                symbols.extend(token.split(',').filter(|name| !name.is_empty()));
+            }
+            Ok(Command::Extern(symbols))
+        }
+        "ASSERT" => Ok(Command::Assertion(parse_assertion(tokens)?)),
+        "INSERT" => {
+            let after = match tokens.next() {
+                Some("AFTER") => true,
+                Some("BEFORE") => false,
+                other => bail!("Expected AFTER or BEFORE after INSERT, got {other:?}"),
+            };
+            let anchor = tokens
+                .next()
+                .context("Expected a section name after INSERT")?;
+            if tokens.peek() == Some(";") {
+                tokens.next();
+            }
+            Ok(Command::Insert { after, anchor })
+        }
+        _ => bail!("Unsupported linker script command `{token}`"),
+    }
+}
+
+/// Parses the contents of `SECTIONS { ... }`, up to and including the closing brace. Commands
+/// other than `ASSERT(...)`, e.g. `ENTRY(...)`, are skipped.
+fn parse_sections<'a>(tokens: &mut Tokeniser<'a>) -> Result<Vec<SectionsItem<'a>>> {
+    let mut items = Vec::new();
+    loop {
+        if let Some(assignment) = take_assignment_statement(tokens)? {
+            items.push(match assignment {
+                Assignment::Location(value) => SectionsItem::SetLocation(value),
+                Assignment::Symbol(assignment) => SectionsItem::Assignment(assignment),
+                Assignment::Assertion(assertion) => SectionsItem::Assertion(assertion),
+            });
+            continue;
+        }
+        let token = tokens.next().context("Got end of script, expected '}'")?;
+        if token == "}" {
+            return Ok(items);
+        }
+        if token == ";" {
+            continue;
+        }
+        if token == "ASSERT" && tokens.peek() == Some("(") {
+            items.push(SectionsItem::Assertion(parse_assertion(tokens)?));
+        } else if token == "OVERLAY" {
+            parse_overlay(tokens, &mut items)?;
+        } else if is_command(token) && tokens.peek() == Some("(") {
+            tokens.next();
+            skip_until_closing(tokens, "(", ")")?;
+        } else {
+            items.push(SectionsItem::OutputSection(Box::new(
+                parse_output_section_description(tokens, token)?,
+            )));
+        }
+    }
+}
+
+enum Assignment {
+    Location(Expression),
+    Symbol(SymbolAssignment),
+
+    /// `. = ASSERT(...);`, which is a way of writing `ASSERT` that doesn't change `.`.
+    Assertion(Assertion),
+}
+
+/// Like `take_assignment`, but also accepts assignments wrapped in `PROVIDE(...)`,
+/// `PROVIDE_HIDDEN(...)` or `HIDDEN(...)`. The position of symbol assignments is left for the
+/// caller to fill in.
+fn take_assignment_statement(tokens: &mut Tokeniser) -> Result<Option<Assignment>> {
+    let mut lookahead = *tokens;
+    let wrapper = lookahead.next_expression_token();
+    let (provide, hidden) = match wrapper {
+        Some("PROVIDE") => (true, false),
+        Some("PROVIDE_HIDDEN") => (true, true),
+        Some("HIDDEN") => (false, true),
+        _ => {
+            return Ok(take_assignment(tokens)?.map(|(target, value)| {
+                if target == "." {
+                    location_assignment(value)
+                } else {
+                    Assignment::Symbol(SymbolAssignment::new(target, value, false, false))
+                }
+            }));
+        }
+    };
+    let wrapper = wrapper.unwrap_or_default();
+    if lookahead.next_expression_token() != Some("(") {
+        return Ok(None);
+    }
+    let (target, value) = take_assignment(&mut lookahead)?
+        .with_context(|| format!("Expected an assignment to a symbol in {wrapper}(...)"))?;
+    if target == "." {
+        bail!("Can't assign to `.` in {wrapper}(...)");
+    }
+    lookahead.expect_expression_token(")")?;
+    if lookahead.peek_expression_token() == Some(";") {
+        lookahead.next_expression_token();
+    }
+    *tokens = lookahead;
+    Ok(Some(Assignment::Symbol(SymbolAssignment::new(
+        target, value, provide, hidden,
+    ))))
+}
+
+/// Returns the assignment `. = value;`, which is an assertion if the value is `ASSERT(...)`.
+fn location_assignment(value: Expression) -> Assignment {
+    match value {
+        Expression::Call(function, mut args) if function == "ASSERT" && args.len() == 2 => {
+            let message = match args.pop() {
+                Some(Expression::String(message) | Expression::Name(message)) => message,
+                _ => String::new(),
+            };
+            Assignment::Assertion(Assertion {
+                condition: args.pop().unwrap(),
+                message,
+                position: AssignmentPosition::TopLevel,
+            })
+        }
+        value => Assignment::Location(value),
+    }
+}
+
+impl OutputSectionCommand {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            address: None,
+            alignment: None,
+            region: None,
+            load_region: None,
+            load_address: None,
+            no_load: false,
+            shares_address: false,
+            inputs: Vec::new(),
+            start_locations: Vec::new(),
+            end_locations: Vec::new(),
+        }
+    }
+}
+
+impl SymbolAssignment {
+    fn new(name: &str, value: Expression, provide: bool, hidden: bool) -> Self {
+        Self {
+            name: name.to_owned(),
+            value,
+            provide,
+            hidden,
+            position: AssignmentPosition::TopLevel,
+        }
+    }
+}
+
+/// If the next statement is an assignment such as `. = ALIGN(8);` or `foo += 4;`, consumes it,
+/// including any terminating semicolon, and returns what was assigned to and the new value.
+/// Compound assignments are expanded, so for `. += 4`, the value is `. + 4`.
+fn take_assignment<'a>(tokens: &mut Tokeniser<'a>) -> Result<Option<(&'a str, Expression)>> {
+    let mut lookahead = *tokens;
+    let Some(target) = lookahead
+        .next_expression_token()
+        .filter(|token| token.bytes().all(is_name_byte))
+    else {
+        return Ok(None);
+    };
+    let operator = match lookahead.next_expression_token() {
+        Some("=") => None,
+        Some("+=") => Some(BinaryOperator::Add),
+        Some("-=") => Some(BinaryOperator::Subtract),
+        Some("*=") => Some(BinaryOperator::Multiply),
+        Some("/=") => Some(BinaryOperator::Divide),
+        Some("&=") => Some(BinaryOperator::And),
+        Some("|=") => Some(BinaryOperator::Or),
+        Some("<<=") => Some(BinaryOperator::ShiftLeft),
+        Some(">>=") => Some(BinaryOperator::ShiftRight),
+        _ => return Ok(None),
+    };
+    *tokens = lookahead;
+    let mut value = parse_expression(tokens)
+        .with_context(|| format!("Failed to parse assignment to `{target}`"))?;
+    if let Some(operator) = operator {
+        let current = if target == "." {
+            Expression::Location
+        } else {
+            Expression::Name(target.to_owned())
+        };
+        value = Expression::Binary(operator, Box::new(current), Box::new(value));
+    }
+    if tokens.peek_expression_token() == Some(";") {
+        tokens.next_expression_token();
+    }
+    Ok(Some((target, value)))
+}
+
+fn parse_expression(tokens: &mut Tokeniser) -> Result<Expression> {
+    let condition = parse_binary_expression(tokens, 0)?;
+    if tokens.peek_expression_token() != Some("?") {
+        return Ok(condition);
+    }
+    tokens.next_expression_token();
+    let if_true = parse_expression(tokens)?;
+    tokens.expect_expression_token(":")?;
+    let if_false = parse_expression(tokens)?;
+    Ok(Expression::Conditional(
+        Box::new(condition),
+        Box::new(if_true),
+        Box::new(if_false),
+    ))
+}
+
+/// Parses a sequence of binary operations, all with at least the supplied precedence.
+fn parse_binary_expression(tokens: &mut Tokeniser, min_precedence: u8) -> Result<Expression> {
+    let mut left = parse_unary_expression(tokens)?;
+    while let Some((operator, precedence)) =
+        tokens.peek_expression_token().and_then(binary_operator)
+    {
+        if precedence < min_precedence {
+            break;
+        }
+        tokens.next_expression_token();
+        let right = parse_binary_expression(tokens, precedence + 1)?;
+        left = Expression::Binary(operator, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+}
+
+/// Returns the operator for the supplied token, together with its precedence, which is the same
+/// as in C.
+fn binary_operator(token: &str) -> Option<(BinaryOperator, u8)> {
+    Some(match token {
+        "||" => (BinaryOperator::LogicalOr, 1),
+        "&&" => (BinaryOperator::LogicalAnd, 2),
+        "|" => (BinaryOperator::Or, 3),
+        "^" => (BinaryOperator::Xor, 4),
+        "&" => (BinaryOperator::And, 5),
+        "==" => (BinaryOperator::Equal, 6),
+        "!=" => (BinaryOperator::NotEqual, 6),
+        "<" => (BinaryOperator::Less, 7),
+        "<=" => (BinaryOperator::LessOrEqual, 7),
+        ">" => (BinaryOperator::Greater, 7),
+        ">=" => (BinaryOperator::GreaterOrEqual, 7),
+        "<<" => (BinaryOperator::ShiftLeft, 8),
+        ">>" => (BinaryOperator::ShiftRight, 8),
+        "+" => (BinaryOperator::Add, 9),
+        "-" => (BinaryOperator::Subtract, 9),
+        "*" => (BinaryOperator::Multiply, 10),
+        "/" => (BinaryOperator::Divide, 10),
+        "%" => (BinaryOperator::Remainder, 10),
+        _ => return None,
+    })
+}
+
+fn parse_unary_expression(tokens: &mut Tokeniser) -> Result<Expression> {
+    let token = tokens
+        .next_expression_token()
+        .context("Got end of script, expected expression")?;
+    let operator = match token {
+        "-" => UnaryOperator::Negate,
+        "!" => UnaryOperator::Not,
+        "~" => UnaryOperator::Complement,
+        "+" => return parse_unary_expression(tokens),
+        "(" => {
+            let inner = parse_expression(tokens)?;
+            tokens.expect_expression_token(")")?;
+            return Ok(inner);
+        }
+        "." => return Ok(Expression::Location),
+        _ if token.starts_with('"') => {
+            return Ok(Expression::String(token.trim_matches('"').to_owned()));
+        }
+        _ if token.as_bytes()[0].is_ascii_digit() => {
+            return Ok(Expression::Number(parse_integer(token)?));
+        }
+        _ if token.bytes().all(is_name_byte) => {
+            if tokens.peek_expression_token() != Some("(") {
+                return Ok(Expression::Name(token.to_owned()));
+            }
+            tokens.next_expression_token();
+            let mut args = Vec::new();
+            if tokens.peek_expression_token() == Some(")") {
+                tokens.next_expression_token();
+            } else {
+                loop {
+                    args.push(parse_expression(tokens)?);
+                    match tokens.next_expression_token() {
+                        Some(")") => break,
+                        Some(",") => {}
+                        other => bail!(
+                            "Expected ',' or ')' in arguments to {token}, got {}",
+                            other.unwrap_or("end of input")
+                        ),
+                    }
+                }
+            }
+            return Ok(Expression::Call(token.to_owned(), args));
+        }
+        _ => bail!("Unexpected '{token}' in expression"),
+    };
+    Ok(Expression::Unary(
+        operator,
+        Box::new(parse_unary_expression(tokens)?),
+    ))
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"_.$".contains(&byte)
+}
+
+/// Parses the contents of `PHDRS { ... }`, up to and including the closing brace.
+fn parse_phdrs(tokens: &mut Tokeniser) -> Result<Vec<ProgramHeader>> {
+    let mut headers = Vec::new();
+    loop {
+        let name = tokens.next().context("Got end of script, expected '}'")?;
+        if name == "}" {
+            return Ok(headers);
+        }
+        let type_name = tokens
+            .next()
+            .with_context(|| format!("Expected a type for program header `{name}`"))?;
+        let mut header = ProgramHeader {
+            name: name.to_owned(),
+            segment_type: parse_segment_type(type_name)?,
+            includes_file_header: false,
+            includes_program_headers: false,
+            physical_address: None,
+            flags: None,
+        };
+        loop {
+            let token = tokens.next().context("Got end of script, expected ';'")?;
+            match token {
+                ";" => break,
+                "FILEHDR" => header.includes_file_header = true,
+                "PHDRS" => header.includes_program_headers = true,
+                "AT" | "FLAGS" => {
+                    tokens.expect("(")?;
+                    let expression = tokens
+                        .next()
+                        .with_context(|| format!("Expected expression in {token}"))?;
+                    tokens.expect(")")?;
+                    let value = parse_integer(expression)?;
+                    if token == "AT" {
+                        header.physical_address = Some(value);
+                    } else {
+                        header.flags = Some(
+                            u32::try_from(value)
+                                .with_context(|| format!("Invalid FLAGS({expression})"))?,
+                        );
+                    }
+                }
+                other => bail!("Unexpected `{other}` in program header `{name}`"),
+            }
+        }
+        headers.push(header);
+    }
+}
+
+/// Parses the contents of `MEMORY { ... }`, up to and including the closing brace.
+fn parse_memory(tokens: &mut Tokeniser) -> Result<Vec<MemoryRegion>> {
+    let mut regions: Vec<MemoryRegion> = Vec::new();
+    loop {
+        let name = tokens
+            .next_expression_token()
+            .context("Got end of script, expected '}'")?;
+        if name == "}" {
+            return Ok(regions);
+        }
+        if regions.iter().any(|region| region.name == name) {
+            bail!("Memory region `{name}` is defined more than once");
+        }
+        if tokens.peek_expression_token() == Some("(") {
+            // Attributes, e.g. `(rx)` or `(!w)`.
+            tokens.next_expression_token();
+            while tokens
+                .next_expression_token()
+                .with_context(|| format!("Unterminated attributes of memory region `{name}`"))?
+                != ")"
+            {}
+        }
+        tokens.expect_expression_token(":")?;
+        let value = |tokens: &mut Tokeniser, attribute: &[&str]| -> Result<u64> {
+            let token = tokens.next_expression_token();
+            if !token.is_some_and(|token| attribute.contains(&token)) {
+                bail!(
+                    "Expected `{}` in memory region `{name}`, got {token:?}",
+                    attribute[0]
+                );
+            }
+            tokens.expect_expression_token("=")?;
+            parse_expression(tokens)?.evaluate(&EvaluationContext {
+                location: 0,
+                sizeof_headers: 0,
+                max_page_size: crate::alignment::PAGE.value(),
+                common_page_size: crate::alignment::PAGE.value(),
+                memory_regions: &regions,
+                layout: None,
+            })
+        };
+        let origin = value(tokens, &["ORIGIN", "org", "o"])
+            .with_context(|| format!("Invalid origin for memory region `{name}`"))?;
+        tokens.expect_expression_token(",")?;
+        let length = value(tokens, &["LENGTH", "len", "l"])
+            .with_context(|| format!("Invalid length for memory region `{name}`"))?;
+        regions.push(MemoryRegion {
+            name: name.to_owned(),
+            origin,
+            length,
+        });
+    }
+}
+
+fn parse_segment_type(name: &str) -> Result<u32> {
+    Ok(match name {
+        "PT_NULL" => object::elf::PT_NULL,
+        "PT_LOAD" => object::elf::PT_LOAD,
+        "PT_DYNAMIC" => object::elf::PT_DYNAMIC,
+        "PT_INTERP" => object::elf::PT_INTERP,
+        "PT_NOTE" => object::elf::PT_NOTE,
+        "PT_SHLIB" => object::elf::PT_SHLIB,
+        "PT_PHDR" => object::elf::PT_PHDR,
+        "PT_TLS" => object::elf::PT_TLS,
+        "PT_GNU_EH_FRAME" => object::elf::PT_GNU_EH_FRAME,
+        "PT_GNU_STACK" => object::elf::PT_GNU_STACK,
+        "PT_GNU_RELRO" => object::elf::PT_GNU_RELRO,
+        "PT_GNU_PROPERTY" => object::elf::PT_GNU_PROPERTY,
+        _ => u32::try_from(parse_integer(name)?)
+            .with_context(|| format!("Invalid program header type `{name}`"))?,
+    })
+}
+
+/// Parses a number, which may be hexadecimal with a `0x` prefix. Decimal numbers may have a `K`
+/// or `M` suffix, which multiplies them by 1024 or 1024*1024.
+fn parse_integer(expression: &str) -> Result<u64> {
+    let value = if let Some(digits) = expression
+        .strip_prefix("0x")
+        .or_else(|| expression.strip_prefix("0X"))
+    {
+        u64::from_str_radix(digits, 16).ok()
+    } else if let Some(digits) = expression.strip_suffix(['K', 'k']) {
+        digits.parse::<u64>().ok().and_then(|v| v.checked_mul(1024))
+    } else if let Some(digits) = expression.strip_suffix(['M', 'm']) {
+        digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|v| v.checked_mul(1024 * 1024))
+    } else {
+        expression.parse().ok()
+    };
+    value.with_context(|| format!("Unsupported expression `{expression}`"))
+}
+
+/// Parses the arguments of `ASSERT(condition, "message")`, the next token being the opening
+/// parenthesis.
+fn parse_assertion(tokens: &mut Tokeniser) -> Result<Assertion> {
+    tokens.expect("(")?;
+    let condition = parse_expression(tokens).context("Failed to parse `ASSERT` condition")?;
+    tokens.expect_expression_token(",")?;
+    let message = tokens
+        .next_expression_token()
+        .context("Expected a message in `ASSERT`")?
+        .trim_matches('"')
+        .to_owned();
+    tokens.expect_expression_token(")")?;
+    if tokens.peek_expression_token() == Some(";") {
+        tokens.next_expression_token();
+    }
+    Ok(Assertion {
+        condition,
+        message,
+        position: AssignmentPosition::TopLevel,
+    })
+}
+
+fn is_command(token: &str) -> bool {
+    token.chars().all(|ch| ch.is_ascii_uppercase() || ch == '_')
+}
+
+/// Parses an output section description such as `.text 0x1000 : AT(0x2000) { *(.text*) } =0x90`.
+fn parse_output_section_description<'a>(
+    tokens: &mut Tokeniser<'a>,
+    name_token: &'a str,
+) -> Result<OutputSectionDescription<'a>> {
+    let name = name_token.strip_suffix(':').unwrap_or(name_token);
+    let mut command = OutputSectionCommand::new(name);
+    if name.len() == name_token.len() {
+        loop {
+            match tokens
+                .peek_expression_token()
+                .context("Expected ':' after output section name")?
+            {
+                ":" => {
+                    tokens.next_expression_token();
+                    break;
+                }
+                "(" if is_output_section_type(tokens) => {
+                    // Other types, e.g. `(COPY)`, don't currently affect anything.
+                    tokens.next_expression_token();
+                    command.no_load |= tokens.next_expression_token() == Some("NOLOAD");
+                    tokens.next_expression_token();
+                }
+                _ if command.address.is_none() => {
+                    command.address = Some(parse_expression(tokens).with_context(|| {
+                        format!("Failed to parse address of output section `{name}`")
+                    })?);
+                }
+                other => bail!("Unexpected `{other}` in output section `{name}`"),
+            }
+        }
+    }
+    loop {
+        match tokens
+            .peek_expression_token()
+            .context("Expected '{' in output section description")?
+        {
+            "{" => {
+                tokens.next_expression_token();
+                break;
+            }
+            "ONLY_IF_RO" | "ONLY_IF_RW" | "NOCROSSREFS" | "ALIGN_WITH_INPUT" => {
+                tokens.next_expression_token();
+            }
+            _ => match parse_expression(tokens)? {
+                Expression::Call(function, mut args) if function == "ALIGN" && args.len() == 1 => {
+                    command.alignment = args.pop();
+                }
+                Expression::Call(function, mut args) if function == "AT" && args.len() == 1 => {
+                    command.load_address = args.pop();
+                }
+                // We don't yet support aligning input sections.
+                Expression::Call(function, args) if function == "SUBALIGN" && args.len() == 1 => {}
+                other => bail!("Unexpected {other:?} in output section `{name}`"),
+            },
+        }
+    }
+    let mut description = parse_output_section_contents(tokens, command)?;
+    parse_output_section_trailer(tokens, &mut description)?;
+    Ok(description)
+}
+
+/// Parses the contents of an output section, up to and including the closing brace, the opening
+/// brace having already been consumed.
+fn parse_output_section_contents<'a>(
+    tokens: &mut Tokeniser<'a>,
+    mut command: OutputSectionCommand,
+) -> Result<OutputSectionDescription<'a>> {
+    let name = command.name.clone();
+    let mut contents_fill = None;
+    // Each of these is recorded with the number of input section descriptions and assignments to
+    // `.` that precede it.
+    let mut location_assignments = Vec::new();
+    let mut symbol_assignments = Vec::new();
+    let mut assertions = Vec::new();
+    loop {
+        let preceding = (command.inputs.len(), location_assignments.len());
+        match take_assignment_statement(tokens)? {
+            Some(Assignment::Location(value)) => {
+                location_assignments.push((value, preceding.0));
+                continue;
+            }
+            Some(Assignment::Symbol(assignment)) => {
+                symbol_assignments.push((assignment, preceding));
+                continue;
+            }
+            Some(Assignment::Assertion(assertion)) => {
+                assertions.push((assertion, preceding));
+                continue;
+            }
+            None => {}
+        }
+        let token = tokens.next().context("Got end of script, expected '}'")?;
+        match token {
+            "}" => break,
+            ";" | "CREATE_OBJECT_SYMBOLS" | "CONSTRUCTORS" => {}
+            "FILL" if tokens.peek() == Some("(") => {
+                tokens.next();
+                let expression = tokens.next().context("Expected expression in FILL")?;
+                tokens.expect(")")?;
+                contents_fill = Some(parse_fill_expression(expression)?);
+            }
+            "KEEP" if tokens.peek() == Some("(") => {
+                tokens.next();
+                let file_token = tokens.next().context("Expected input section in KEEP")?;
+                command.inputs.push(InputSectionDescription {
+                    keep: true,
+                    ..parse_input_section_description(tokens, file_token)?
+                });
+                tokens.expect(")")?;
+            }
+            "BYTE" | "SHORT" | "LONG" | "QUAD" | "SQUAD" if tokens.peek() == Some("(") => {
+                crate::error::warning(&format!(
+                    "{token}(...) in output section `{name}` isn't supported and will be ignored"
+                ));
+                tokens.next();
+                skip_until_closing(tokens, "(", ")")?;
+            }
+            "ASSERT" if tokens.peek() == Some("(") => {
+                let preceding = (command.inputs.len(), location_assignments.len());
+                assertions.push((parse_assertion(tokens)?, preceding));
+            }
+            _ if is_command(token)
+                && tokens.peek() == Some("(")
+                && !is_input_section_wrapper(token) =>
+            {
+                tokens.next();
+                skip_until_closing(tokens, "(", ")")?;
+            }
+            _ => command
+                .inputs
+                .push(parse_input_section_description(tokens, token)?),
+        }
+    }
+    let num_inputs = command.inputs.len();
+    for (assignment, (num_preceding_inputs, _)) in &symbol_assignments {
+        if *num_preceding_inputs > 0 && *num_preceding_inputs < num_inputs {
+            crate::error::warning(&format!(
+                "Assignment to `{}` between input sections in output section `{name}` isn't \
+                 supported, so it will be given the address of the end of the section",
+                assignment.name
+            ));
+        }
+    }
+    // In a section without any input sections, assignments to `.` make the section bigger rather
+    // than moving it, so everything is at the end.
+    let has_no_inputs = num_inputs == 0 && !location_assignments.is_empty();
+    let is_at_end = |num_preceding_inputs: usize| num_preceding_inputs > 0 || has_no_inputs;
+    for (value, num_preceding_inputs) in location_assignments {
+        if num_preceding_inputs > 0 && num_preceding_inputs < num_inputs {
+            crate::error::warning(&format!(
+                "Assignment to `.` between input sections in output section `{name}` isn't \
+                 supported, so it will apply at the end of the section"
+            ));
+        }
+        if is_at_end(num_preceding_inputs) {
+            command.end_locations.push(value);
+        } else {
+            command.start_locations.push(value);
+        }
+    }
+    let num_start_locations = command.start_locations.len();
+    let position = |(num_preceding_inputs, num_preceding_locations): (usize, usize)| {
+        let at_end = is_at_end(num_preceding_inputs);
+        InSectionPosition {
+            at_end,
+            location_index: if at_end {
+                num_preceding_locations - num_start_locations
+            } else {
+                num_preceding_locations
+            },
+        }
+    };
+    Ok(OutputSectionDescription {
+        command,
+        fill: contents_fill,
+        symbol_assignments: symbol_assignments
+            .into_iter()
+            .map(|(assignment, preceding)| (assignment, position(preceding)))
+            .collect(),
+        assertions: assertions
+            .into_iter()
+            .map(|(assertion, preceding)| (assertion, position(preceding)))
+            .collect(),
+        program_headers: None,
+    })
+}
+
+/// Parses the things that can follow the closing brace of an output section, e.g. `>region
+/// AT>lma_region :phdr =0x90`.
+fn parse_output_section_trailer<'a>(
+    tokens: &mut Tokeniser<'a>,
+    description: &mut OutputSectionDescription<'a>,
+) -> Result {
+    let name = description.command.name.clone();
+    while let Some(next) = tokens.peek() {
+        let is_load_region = next == "AT" || next.starts_with("AT>");
+        if is_load_region || next.starts_with('>') {
+            tokens.next();
+            let mut rest = next.strip_prefix("AT").unwrap_or(next);
+            if rest.is_empty() {
+                rest = tokens.next().context("Expected '>' after AT")?;
+            }
+            let rest = rest
+                .strip_prefix('>')
+                .with_context(|| format!("Expected '>' after AT, got `{rest}`"))?;
+            let region_name = if rest.is_empty() {
+                tokens
+                    .next()
+                    .context("Expected memory region name after '>'")?
+            } else {
+                rest
+            };
+            let region = if is_load_region {
+                &mut description.command.load_region
+            } else {
+                &mut description.command.region
+            };
+            *region = Some(region_name.to_owned());
+            if is_load_region && description.command.load_address.is_some() {
+                bail!("Output section `{name}` has both AT(...) and AT>{region_name}");
+            }
+        } else if let Some(rest) = next.strip_prefix(':') {
+            tokens.next();
+            let program_header = if rest.is_empty() {
+                tokens
+                    .next()
+                    .context("Expected program header name after ':'")?
+            } else {
+                rest
+            };
+            let program_headers = description.program_headers.get_or_insert_with(Vec::new);
+            if program_header != "NONE" {
+                program_headers.push(program_header);
+            }
+        } else if let Some(expression) = next.strip_prefix('=') {
+            tokens.next();
+            let expression = if expression.is_empty() {
+                tokens
+                    .next()
+                    .context("Expected fill expression after '='")?
+            } else {
+                expression
+            };
+            // We apply the fill to the whole section, so a FILL in the contents, which would
+            // apply to everything after it, takes precedence.
+            let fill = parse_fill_expression(expression)?;
+            description.fill.get_or_insert(fill);
+        } else if next == "," {
+            tokens.next();
+        } else {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Parses an overlay such as `OVERLAY 0x1000 : AT(0x4000) { .a { *(.a) } .b { *(.b) } } >RAM`,
+/// the `OVERLAY` keyword having already been consumed. Each member becomes an output section
+/// that starts at the same address, but is loaded after the previous member. For each member, we
+/// also provide `__load_start_<name>` and `__load_stop_<name>`, where the name has any characters
+/// that aren't valid in a C identifier removed.
+fn parse_overlay<'a>(tokens: &mut Tokeniser<'a>, items: &mut Vec<SectionsItem<'a>>) -> Result {
+    let address = if tokens.peek_expression_token() == Some(":") {
+        None
+    } else {
+        Some(parse_expression(tokens).context("Failed to parse address of OVERLAY")?)
+    };
+    tokens.expect_expression_token(":")?;
+    let mut overlay = OutputSectionDescription {
+        command: OutputSectionCommand::new("OVERLAY"),
+        fill: None,
+        symbol_assignments: Vec::new(),
+        assertions: Vec::new(),
+        program_headers: None,
+    };
+    loop {
+        match tokens
+            .peek_expression_token()
+            .context("Expected '{' in OVERLAY")?
+        {
+            "{" => {
+                tokens.next_expression_token();
+                break;
+            }
+            "NOCROSSREFS" => {
+                tokens.next_expression_token();
+            }
+            _ => match parse_expression(tokens)? {
+                Expression::Call(function, mut args) if function == "AT" && args.len() == 1 => {
+                    overlay.command.load_address = args.pop();
+                }
+                other => bail!("Unexpected {other:?} in OVERLAY"),
+            },
+        }
+    }
+
+    let mut members = Vec::new();
+    loop {
+        let token = tokens.next().context("Got end of script, expected '}'")?;
+        if token == "}" {
+            break;
+        }
+        let name = token.strip_suffix(':').unwrap_or(token);
+        tokens
+            .expect("{")
+            .with_context(|| format!("Failed to parse `{name}` in OVERLAY"))?;
+        let mut member = parse_output_section_contents(tokens, OutputSectionCommand::new(name))?;
+        parse_output_section_trailer(tokens, &mut member)?;
+        members.push(member);
+    }
+    parse_output_section_trailer(tokens, &mut overlay)?;
+
+    let mut symbol_assignments = Vec::new();
+    for (index, member) in members.iter_mut().enumerate() {
+        let command = &mut member.command;
+        if index == 0 {
+            command.address.clone_from(&address);
+            command.load_address = overlay.command.load_address.take();
+            command.load_region = overlay.command.load_region.take();
+        } else {
+            command.shares_address = true;
+        }
+        command.region.clone_from(&overlay.command.region);
+        if member.program_headers.is_none() {
+            member.program_headers.clone_from(&overlay.program_headers);
+        }
+        if member.fill.is_none() {
+            member.fill.clone_from(&overlay.fill);
+        }
+
+        let symbol_name: String = command
+            .name
+            .chars()
+            .filter(|ch| ch.is_ascii_alphanumeric() || *ch == '_')
+            .collect();
+        let section = || vec![Expression::Name(command.name.clone())];
+        let start = Expression::Call("LOADADDR".to_owned(), section());
+        let stop = Expression::Binary(
+            BinaryOperator::Add,
+            Box::new(start.clone()),
+            Box::new(Expression::Call("SIZEOF".to_owned(), section())),
+        );
+        symbol_assignments.push(SymbolAssignment::new(
+            &format!("__load_start_{symbol_name}"),
+            start,
+            true,
+            false,
+        ));
+        symbol_assignments.push(SymbolAssignment::new(
+            &format!("__load_stop_{symbol_name}"),
+            stop,
+            true,
+            false,
+        ));
+    }
+    items.extend(
+        members
+            .into_iter()
+            .map(|member| SectionsItem::OutputSection(Box::new(member))),
+    );
+    items.extend(symbol_assignments.into_iter().map(SectionsItem::Assignment));
+    Ok(())
+}
+
+/// Returns whether the tokens start with an output section type such as `(NOLOAD)`.
+fn is_output_section_type(tokens: &Tokeniser) -> bool {
+    let mut lookahead = *tokens;
+    lookahead.next_expression_token() == Some("(")
+        && lookahead.next_expression_token().is_some_and(|token| {
+            matches!(
+                token,
+                "NOLOAD" | "COPY" | "INFO" | "DSECT" | "OVERLAY" | "READONLY"
+            )
+        })
+        && lookahead.next_expression_token() == Some(")")
+}
+
+/// Returns whether `token` is something like `SORT` that can be wrapped around the patterns in an
+/// input section description. These don't affect what matches.
+fn is_input_section_wrapper(token: &str) -> bool {
+    token.starts_with("SORT") || token == "REVERSE" || token == "EXCLUDE_FILE"
+}
+
+/// Parses an input section description such as `*(.text .text.*)`, where `file_token` is the
+/// file pattern, which has already been consumed. Sorting isn't supported, so `SORT(...)` and
+/// similar are just treated as though their contents appeared directly.
+fn parse_input_section_description(
+    tokens: &mut Tokeniser,
+    file_token: &str,
+) -> Result<InputSectionDescription> {
+    let mut description = InputSectionDescription {
+        file_pattern: String::new(),
+        excluded_files: Vec::new(),
+        section_patterns: Vec::new(),
+        keep: false,
+    };
+    let mut file_token = file_token;
+    if file_token == "EXCLUDE_FILE" {
+        tokens.expect("(")?;
+        parse_excluded_files(tokens, &mut description.excluded_files)?;
+        file_token = tokens.next().context("Expected file pattern")?;
+    }
+    let mut file_depth = 0;
+    while is_input_section_wrapper(file_token) && tokens.peek() == Some("(") {
+        tokens.next();
+        file_depth += 1;
+        file_token = tokens.next().context("Expected file pattern")?;
+    }
+    for _ in 0..file_depth {
+        tokens.expect(")")?;
+    }
+    description.file_pattern = file_token.to_owned();
+
+    if tokens.peek() != Some("(") {
+        // A file name on its own includes all of the file's sections.
+        description.section_patterns.push("*".to_owned());
+        return Ok(description);
+    }
+    tokens.next();
+    let mut depth = 1;
+    loop {
+        let token = tokens.next().context("Got end of script, expected ')'")?;
+        match token {
+            ")" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(description);
+                }
+            }
+            "EXCLUDE_FILE" => {
+                tokens.expect("(")?;
+                parse_excluded_files(tokens, &mut description.excluded_files)?;
+            }
+            _ if is_input_section_wrapper(token) && tokens.peek() == Some("(") => {
+                tokens.next();
+                depth += 1;
+            }
+            _ => description.section_patterns.push(token.to_owned()),
+        }
     }
 }
 
-enum Command<'a> {
-    Arg(&'a str),
-    Group(Vec<Command<'a>>),
-    AsNeeded(Vec<Command<'a>>),
-    Ignored,
+/// Parses the contents of `EXCLUDE_FILE(...)`, up to and including the closing parenthesis.
+fn parse_excluded_files(tokens: &mut Tokeniser, out: &mut Vec<String>) -> Result {
+    loop {
+        let token = tokens.next().context("Got end of script, expected ')'")?;
+        if token == ")" {
+            return Ok(());
+        }
+        out.push(token.to_owned());
+    }
 }
 
-fn parse_commands_up_to<'a>(
-    tokens: &mut Tokeniser<'a>,
-    end: Option<&str>,
-) -> Result<Vec<Command<'a>>> {
-    let mut out = Vec::new();
-    while let Some(token) = tokens.next() {
-        if end == Some(token) {
-            return Ok(out);
-        }
-        if token.chars().all(|ch| ch.is_ascii_uppercase() || ch == '_') {
-            out.push(parse_command(tokens, token)?);
+/// Returns the bytes to repeat for the supplied fill expression. As with GNU ld, a hex number
+/// gives a pattern with a byte for each pair of digits, including leading zeros, so `0x90` is a
+/// single byte. Other numbers give a 4 byte pattern. Either way, the pattern is big-endian.
+fn parse_fill_expression(expression: &str) -> Result<Vec<u8>> {
+    if let Some(digits) = expression
+        .strip_prefix("0x")
+        .or_else(|| expression.strip_prefix("0X"))
+        .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_hexdigit()))
+    {
+        let padded = if digits.len() % 2 == 1 {
+            format!("0{digits}")
         } else {
-            out.push(Command::Arg(token));
-        }
-    }
-    if let Some(expected) = end {
-        bail!("Got end of script, expected '{expected}'");
+            digits.to_owned()
+        };
+        return (0..padded.len())
+            .step_by(2)
+            .map(|i| Ok(u8::from_str_radix(&padded[i..i + 2], 16)?))
+            .collect();
     }
-    Ok(out)
+    let value: u64 = expression
+        .parse()
+        .map_err(|_| anyhow!("Unsupported fill expression `{expression}`"))?;
+    Ok((value as u32).to_be_bytes().to_vec())
 }
 
-fn parse_command<'a>(tokens: &mut Tokeniser<'a>, token: &str) -> Result<Command<'a>> {
-    match token {
-        "GROUP" | "INPUT" => {
-            tokens.expect("(")?;
-            Ok(Command::Group(parse_commands_up_to(tokens, Some(")"))?))
-        }
-        "OUTPUT_FORMAT" => {
-            tokens.expect("(")?;
-            parse_commands_up_to(tokens, Some(")"))?;
-            Ok(Command::Ignored)
-        }
-        "AS_NEEDED" => {
-            tokens.expect("(")?;
-            Ok(Command::AsNeeded(parse_commands_up_to(tokens, Some(")"))?))
+/// Skips tokens up to and including the `close` that matches an `open` that was just consumed.
+fn skip_until_closing(tokens: &mut Tokeniser, open: &str, close: &str) -> Result {
+    let mut depth = 1;
+    while depth > 0 {
+        let token = tokens
+            .next()
+            .with_context(|| format!("Got end of script, expected '{close}'"))?;
+        if token == open {
+            depth += 1;
+        } else if token == close {
+            depth -= 1;
         }
-        _ => bail!("Unsupported linker script command `{token}`"),
     }
+    Ok(())
 }
 
-fn inputs_from_script(text: &str, starting_modifiers: Modifiers) -> Result<Vec<Input>> {
+/// A callback that reads the script named by an `INCLUDE` command and returns its contents.
+type IncludeFn<'a> = dyn FnMut(&str, Modifiers) -> Result<LinkerScript> + 'a;
+
+fn parse_script(
+    text: &str,
+    starting_modifiers: Modifiers,
+    include: &mut IncludeFn,
+) -> Result<LinkerScript> {
     let mut tokens = Tokeniser::new(text);
     let commands = parse_commands_up_to(&mut tokens, None)?;
-    let mut inputs = Vec::new();
-    collect_inputs(&commands, &mut inputs, starting_modifiers);
-    Ok(inputs)
+    let mut script = LinkerScript::default();
+    collect_commands(&commands, &mut script, starting_modifiers, include)?;
+    Ok(script)
 }
 
-fn collect_inputs(commands: &[Command], inputs: &mut Vec<Input>, modifiers: Modifiers) {
+fn collect_commands(
+    commands: &[Command],
+    script: &mut LinkerScript,
+    modifiers: Modifiers,
+    include: &mut IncludeFn,
+) -> Result {
     for command in commands {
         match command {
             Command::Arg(arg) => {
@@ -317,23 +2450,133 @@ fn collect_inputs(commands: &[Command], inputs: &mut Vec<Input>, modifiers: Modi
                 } else {
                     InputSpec::File(Box::from(Path::new(arg)))
                 };
-                inputs.push(Input {
+                script.inputs.push(Input {
                     spec,
                     search_first: None,
                     modifiers,
                 });
             }
-            Command::Group(subs) => collect_inputs(subs, inputs, modifiers),
+            Command::Include(name) => {
+                let included = include(name, modifiers)?;
+                script.inputs.extend(included.inputs);
+                script.layout.extend(included.layout);
+            }
+            Command::Group(subs) => collect_commands(subs, script, modifiers, include)?,
             Command::AsNeeded(subs) => {
                 let sub_modifiers = Modifiers {
                     as_needed: true,
                     ..modifiers
                 };
-                collect_inputs(subs, inputs, sub_modifiers);
+                collect_commands(subs, script, sub_modifiers, include)?;
+            }
+            Command::Sections(items) => {
+                for item in items {
+                    let index = script.layout.sections.len();
+                    let description = match item {
+                        SectionsItem::SetLocation(value) => {
+                            script
+                                .layout
+                                .sections
+                                .push(SectionsCommand::SetLocation(value.clone()));
+                            continue;
+                        }
+                        SectionsItem::Assignment(assignment) => {
+                            script.layout.symbol_assignments.push(SymbolAssignment {
+                                position: AssignmentPosition::BeforeCommand(index),
+                                ..assignment.clone()
+                            });
+                            continue;
+                        }
+                        SectionsItem::Assertion(assertion) => {
+                            script.layout.assertions.push(Assertion {
+                                position: AssignmentPosition::BeforeCommand(index),
+                                ..assertion.clone()
+                            });
+                            continue;
+                        }
+                        SectionsItem::OutputSection(description) => description,
+                    };
+                    for (assignment, position) in &description.symbol_assignments {
+                        script.layout.symbol_assignments.push(SymbolAssignment {
+                            position: AssignmentPosition::InOutputSection {
+                                index,
+                                at_end: position.at_end,
+                                location_index: position.location_index,
+                            },
+                            ..assignment.clone()
+                        });
+                    }
+                    for (assertion, position) in &description.assertions {
+                        script.layout.assertions.push(Assertion {
+                            position: AssignmentPosition::InOutputSection {
+                                index,
+                                at_end: position.at_end,
+                                location_index: position.location_index,
+                            },
+                            ..assertion.clone()
+                        });
+                    }
+                    let section_name = &description.command.name;
+                    if let Some(pattern) = &description.fill {
+                        script.layout.section_fills.push(SectionFill {
+                            section_name: section_name.clone(),
+                            pattern: pattern.clone(),
+                        });
+                    }
+                    if let Some(program_headers) = &description.program_headers {
+                        script
+                            .layout
+                            .section_program_headers
+                            .push(SectionProgramHeaders {
+                                section_name: section_name.clone(),
+                                program_headers: program_headers
+                                    .iter()
+                                    .map(|name| (*name).to_owned())
+                                    .collect(),
+                            });
+                    }
+                    script
+                        .layout
+                        .sections
+                        .push(SectionsCommand::OutputSection(Box::new(
+                            description.command.clone(),
+                        )));
+                }
+            }
+            Command::Memory(regions) => {
+                script.layout.memory_regions.extend(regions.iter().cloned());
+            }
+            Command::Phdrs(headers) => {
+                script
+                    .layout
+                    .program_headers
+                    .extend(headers.iter().cloned());
+            }
+            Command::Assignment(assignment) => {
+                script.layout.symbol_assignments.push(assignment.clone());
+            }
+            Command::Assertion(assertion) => script.layout.assertions.push(assertion.clone()),
+            Command::Entry(symbol) => script.layout.entry = Some((*symbol).to_owned()),
+            Command::Extern(symbols) => script
+                .layout
+                .extern_symbols
+                .extend(symbols.iter().map(|symbol| (*symbol).to_owned())),
+            Command::Insert { after, anchor } => {
+                let layout = &mut script.layout;
+                let start = layout
+                    .insertions
+                    .last()
+                    .map_or(0, |insertion| insertion.commands.end);
+                layout.insertions.push(Insertion {
+                    commands: start..layout.sections.len(),
+                    anchor: (*anchor).to_owned(),
+                    after: *after,
+                });
             }
             Command::Ignored => {}
         }
     }
+    Ok(())
 }
 
 fn try_take(input: &mut &str, pattern: &str) -> bool {
@@ -359,6 +2602,11 @@ mod tests {
     use super::*;
     use crate::args::InputSpec;
     use itertools::assert_equal;
+    use itertools::Itertools as _;
+
+    fn no_include(name: &str, _modifiers: Modifiers) -> Result<LinkerScript> {
+        panic!("Unexpected INCLUDE {name}");
+    }
 
     #[test]
     fn test_tokenisation() {
@@ -382,13 +2630,15 @@ mod tests {
 
     #[test]
     fn test_inputs_from_script() {
-        let inputs = inputs_from_script(
+        let inputs = parse_script(
             r#"/* GNU ld script */
             GROUP ( libgcc_s.so.1 -lgcc )
         "#,
             Modifiers::default(),
+            &mut no_include,
         )
-        .unwrap();
+        .unwrap()
+        .inputs;
         assert_equal(
             inputs.into_iter().map(|i| i.spec),
             [
@@ -397,22 +2647,57 @@ mod tests {
             ],
         );
 
-        let inputs = inputs_from_script("INPUT(libfoo.so)", Modifiers::default()).unwrap();
+        let inputs = parse_script("INPUT(libfoo.so)", Modifiers::default(), &mut no_include)
+            .unwrap()
+            .inputs;
         assert_equal(
             inputs.into_iter().map(|i| i.spec),
             [InputSpec::File(Box::from(Path::new("libfoo.so")))],
         );
     }
 
+    #[test]
+    fn test_include() {
+        let mut included = Vec::new();
+        let inputs = parse_script(
+            "INPUT(a.o) INCLUDE memory.ld GROUP(c.o)",
+            Modifiers::default(),
+            &mut |name, modifiers| {
+                included.push(name.to_owned());
+                Ok(LinkerScript {
+                    inputs: vec![Input {
+                        spec: InputSpec::File(Box::from(Path::new("b.o"))),
+                        search_first: Some(PathBuf::from("/inc")),
+                        modifiers,
+                    }],
+                    layout: ScriptLayout::default(),
+                })
+            },
+        )
+        .unwrap()
+        .inputs;
+        assert_eq!(included, ["memory.ld"]);
+        assert_equal(
+            inputs.into_iter().map(|i| i.spec),
+            [
+                InputSpec::File(Box::from(Path::new("a.o"))),
+                InputSpec::File(Box::from(Path::new("b.o"))),
+                InputSpec::File(Box::from(Path::new("c.o"))),
+            ],
+        );
+    }
+
     #[test]
     fn test_test_inputs_from_script() {
-        let inputs = inputs_from_script(
+        let inputs = parse_script(
             r#"OUTPUT_FORMAT(elf64-x86-64)
             GROUP ( /lib/x86_64-linux-gnu/libc.so.6 /usr/lib/x86_64-linux-gnu/libc_nonshared.a  AS_NEEDED ( /lib64/ld-linux-x86-64.so.2 ) )
         "#,
         Modifiers::default(),
+        &mut no_include,
         )
-        .unwrap();
+        .unwrap()
+        .inputs;
         assert_equal(
             inputs.into_iter().map(|i| i.spec),
             [
@@ -425,6 +2710,651 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_section_fills() {
+        let script = parse_script(
+            r#"SECTIONS {
+                . = 0x10000;
+                .text : { *(.text .text.*) } =0x90
+                PROVIDE(etext = .);
+                .rodata ALIGN(16) : { FILL(0xDEADBEEF); *(.rodata*) } >rom
+                .data : AT(0x2000) { *(.data) } = 0x0012
+                .bss (NOLOAD) : { *(.bss) }
+            }"#,
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap();
+        assert_eq!(
+            script.layout.section_fills,
+            [
+                SectionFill {
+                    section_name: ".text".to_owned(),
+                    pattern: vec![0x90]
+                },
+                SectionFill {
+                    section_name: ".rodata".to_owned(),
+                    pattern: vec![0xde, 0xad, 0xbe, 0xef]
+                },
+                SectionFill {
+                    section_name: ".data".to_owned(),
+                    pattern: vec![0x00, 0x12]
+                },
+            ]
+        );
+        let SectionsCommand::OutputSection(data) = &script.layout.sections[3] else {
+            panic!("Expected .data");
+        };
+        assert_eq!(data.load_address, Some(Expression::Number(0x2000)));
+        assert_eq!(parse_fill_expression("0x123").unwrap(), [0x01, 0x23]);
+        assert_eq!(parse_fill_expression("144").unwrap(), [0, 0, 0, 0x90]);
+    }
+
+    #[test]
+    fn test_program_headers() {
+        let script = parse_script(
+            r#"PHDRS {
+                headers PT_PHDR PHDRS;
+                text PT_LOAD FILEHDR PHDRS FLAGS(5);
+                data PT_LOAD AT(0x2000);
+                custom 0x60000001;
+            }
+            SECTIONS {
+                .text : { *(.text) } :text
+                .data : { *(.data) } : data :custom =0x00
+                .comment 0 : { *(.comment) } :NONE
+            }"#,
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap();
+        let header = |name: &str, segment_type| ProgramHeader {
+            name: name.to_owned(),
+            segment_type,
+            includes_file_header: false,
+            includes_program_headers: false,
+            physical_address: None,
+            flags: None,
+        };
+        assert_eq!(
+            script.layout.program_headers,
+            [
+                ProgramHeader {
+                    includes_program_headers: true,
+                    ..header("headers", object::elf::PT_PHDR)
+                },
+                ProgramHeader {
+                    includes_file_header: true,
+                    includes_program_headers: true,
+                    flags: Some(5),
+                    ..header("text", object::elf::PT_LOAD)
+                },
+                ProgramHeader {
+                    physical_address: Some(0x2000),
+                    ..header("data", object::elf::PT_LOAD)
+                },
+                header("custom", 0x6000_0001),
+            ]
+        );
+        assert_eq!(
+            script.layout.section_program_headers,
+            [
+                SectionProgramHeaders {
+                    section_name: ".text".to_owned(),
+                    program_headers: vec!["text".to_owned()],
+                },
+                SectionProgramHeaders {
+                    section_name: ".data".to_owned(),
+                    program_headers: vec!["data".to_owned(), "custom".to_owned()],
+                },
+                SectionProgramHeaders {
+                    section_name: ".comment".to_owned(),
+                    program_headers: Vec::new(),
+                },
+            ]
+        );
+        assert_eq!(script.layout.section_fills.len(), 1);
+        assert!(parse_script(
+            "PHDRS { text PT_LOAD BOGUS; }",
+            Modifiers::default(),
+            &mut no_include
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_sections() {
+        let script = parse_script(
+            r#"SECTIONS {
+                . = 0x10000 + SIZEOF_HEADERS;
+                .text : { KEEP(*(.init)) *(EXCLUDE_FILE(*crtend.o) .text SORT(.text.*)) }
+                . = ALIGN(0x1000);
+                .data ALIGN(16) : ALIGN(32) { libfoo.a:bar.o(.data) *(.data .data.*) }
+                .bss : { *(.bss) *(COMMON) }
+                /DISCARD/ : { *(.comment) }
+            }"#,
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap();
+        let layout = &script.layout;
+        assert_eq!(layout.sections.len(), 6);
+        let SectionsCommand::SetLocation(start) = &layout.sections[0] else {
+            panic!("Expected assignment to `.`");
+        };
+        let context = EvaluationContext {
+            location: 0,
+            sizeof_headers: 0x100,
+            max_page_size: 0x1000,
+            common_page_size: 0x1000,
+            memory_regions: &[],
+            layout: None,
+        };
+        assert_eq!(start.evaluate(&context).unwrap(), 0x10100);
+        let SectionsCommand::OutputSection(data) = &layout.sections[3] else {
+            panic!("Expected output section");
+        };
+        assert_eq!(data.name, ".data");
+        assert!(data.address.as_ref().unwrap().is_location_alignment());
+        assert_eq!(data.alignment, Some(Expression::Number(32)));
+
+        let output_section = |archive: Option<&str>, file: &str, section: &str| {
+            layout
+                .output_section_for(
+                    archive.map(str::as_bytes),
+                    file.as_bytes(),
+                    section.as_bytes(),
+                )
+                .map(|(name, _)| name)
+        };
+        assert_eq!(output_section(None, "a.o", ".init"), Some(".text"));
+        assert_eq!(output_section(None, "a.o", ".text.foo"), Some(".text"));
+        assert_eq!(output_section(None, "/lib/crtend.o", ".text"), None);
+        assert_eq!(
+            output_section(Some("libfoo.a"), "bar.o", ".data"),
+            Some(".data")
+        );
+        assert_eq!(output_section(None, "a.o", "COMMON"), Some(".bss"));
+        assert_eq!(
+            output_section(None, "a.o", ".comment"),
+            Some(DISCARD_SECTION_NAME)
+        );
+        assert_eq!(output_section(None, "a.o", ".rodata"), None);
+        let is_kept = |section: &str| {
+            layout
+                .output_section_for(None, b"a.o", section.as_bytes())
+                .is_some_and(|(_, keep)| keep)
+        };
+        assert!(is_kept(".init"));
+        assert!(!is_kept(".text.foo"));
+    }
+
+    #[test]
+    fn test_memory_regions() {
+        let script = parse_script(
+            r#"MEMORY {
+                FLASH (rx) : ORIGIN = 0x08000000, LENGTH = 512K
+                RAM (!rx) : org = 0x20000000, l = LENGTH(FLASH) / 4
+            }
+            SECTIONS {
+                .text : { *(.text*) } > FLASH
+                .data : { *(.data*) } >RAM AT> FLASH
+                .noinit (NOLOAD) : { *(.noinit*) } > RAM AT >FLASH
+            }"#,
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap();
+        assert_eq!(
+            script.layout.memory_regions,
+            [
+                MemoryRegion {
+                    name: "FLASH".to_owned(),
+                    origin: 0x0800_0000,
+                    length: 0x8_0000,
+                },
+                MemoryRegion {
+                    name: "RAM".to_owned(),
+                    origin: 0x2000_0000,
+                    length: 0x2_0000,
+                },
+            ]
+        );
+        let regions = script
+            .layout
+            .sections
+            .iter()
+            .map(|command| {
+                let SectionsCommand::OutputSection(output) = command else {
+                    panic!("Expected output section");
+                };
+                (
+                    output.region.as_deref(),
+                    output.load_region.as_deref(),
+                    output.no_load,
+                )
+            })
+            .collect_vec();
+        assert_eq!(
+            regions,
+            [
+                (Some("FLASH"), None, false),
+                (Some("RAM"), Some("FLASH"), false),
+                (Some("RAM"), Some("FLASH"), true),
+            ]
+        );
+        assert!(parse_script(
+            "MEMORY { RAM : ORIGIN = 0, LENGTH = 1K RAM : ORIGIN = 0, LENGTH = 1K }",
+            Modifiers::default(),
+            &mut no_include
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_expressions() {
+        fn evaluate(text: &str) -> Result<u64> {
+            let expression = parse_expression(&mut Tokeniser::new(text))?;
+            expression.evaluate(&EvaluationContext {
+                location: 0x1234,
+                sizeof_headers: 0x40,
+                max_page_size: 0x1000,
+                common_page_size: 0x1000,
+                memory_regions: &[],
+                layout: None,
+            })
+        }
+
+        assert_eq!(evaluate("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(evaluate("(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(evaluate("4K + 1M").unwrap(), 0x101000);
+        assert_eq!(evaluate("1 << 4 | 1").unwrap(), 17);
+        assert_eq!(evaluate("2 > 1 ? 10 : 20").unwrap(), 10);
+        assert_eq!(evaluate("-1").unwrap(), u64::MAX);
+        assert_eq!(evaluate("ALIGN(0x100)").unwrap(), 0x1300);
+        assert_eq!(evaluate("ALIGN(. + 1, 8)").unwrap(), 0x1238);
+        assert_eq!(evaluate("MAX(SIZEOF_HEADERS, 3)").unwrap(), 0x40);
+        assert_eq!(evaluate("CONSTANT(MAXPAGESIZE)").unwrap(), 0x1000);
+        assert!(evaluate("foo + 1").is_err());
+        assert!(evaluate("BOGUS(1)").is_err());
+    }
+
+    #[test]
+    fn test_symbol_assignments() {
+        let script = parse_script(
+            r#"MEMORY { RAM : ORIGIN = 0x20000000, LENGTH = 64K }
+            _estack = ORIGIN(RAM) + LENGTH(RAM);
+            SECTIONS {
+                . = 0x10000;
+                PROVIDE(__text_start = .);
+                .text : { _stext = .; *(.text*) PROVIDE_HIDDEN(_etext = .); }
+                HIDDEN(__text_size = SIZEOF(.text));
+            }"#,
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap();
+        let assignments = script
+            .layout
+            .symbol_assignments
+            .iter()
+            .map(|a| (a.name.as_str(), a.provide, a.hidden, a.position))
+            .collect_vec();
+        assert_eq!(
+            assignments,
+            [
+                ("_estack", false, false, AssignmentPosition::TopLevel),
+                (
+                    "__text_start",
+                    true,
+                    false,
+                    AssignmentPosition::BeforeCommand(1)
+                ),
+                (
+                    "_stext",
+                    false,
+                    false,
+                    AssignmentPosition::InOutputSection {
+                        index: 1,
+                        at_end: false,
+                        location_index: 0
+                    }
+                ),
+                (
+                    "_etext",
+                    true,
+                    true,
+                    AssignmentPosition::InOutputSection {
+                        index: 1,
+                        at_end: true,
+                        location_index: 0
+                    }
+                ),
+                (
+                    "__text_size",
+                    false,
+                    true,
+                    AssignmentPosition::BeforeCommand(2)
+                ),
+            ]
+        );
+        let absolute = script
+            .layout
+            .symbol_assignments
+            .iter()
+            .map(|a| a.value.is_absolute())
+            .collect_vec();
+        assert_eq!(absolute, [true, false, false, false, true]);
+
+        struct TestLayout;
+
+        impl LayoutLookup for TestLayout {
+            fn section(&self, name: &str) -> Option<(u64, u64)> {
+                (name == ".text").then_some((0x10000, 0x80))
+            }
+
+            fn load_address(&self, name: &str) -> Option<u64> {
+                (name == ".text").then_some(0x800_0000)
+            }
+
+            fn symbol_value(&self, name: &str) -> Result<u64> {
+                match name {
+                    "_stext" => Ok(0x10000),
+                    _ => bail!("Undefined symbol `{name}`"),
+                }
+            }
+
+            fn is_defined(&self, name: &str) -> bool {
+                name == "_stext"
+            }
+        }
+
+        let evaluate = |text: &str| {
+            parse_expression(&mut Tokeniser::new(text))?.evaluate(&EvaluationContext {
+                location: 0x1234,
+                sizeof_headers: 0x40,
+                max_page_size: 0x1000,
+                common_page_size: 0x1000,
+                memory_regions: &script.layout.memory_regions,
+                layout: Some(&TestLayout),
+            })
+        };
+        assert_eq!(evaluate("ADDR(.text) + SIZEOF(.text)").unwrap(), 0x10080);
+        assert_eq!(evaluate("SIZEOF(.data)").unwrap(), 0);
+        assert_eq!(
+            evaluate("LOADADDR(.text) + SIZEOF(.text)").unwrap(),
+            0x800_0080
+        );
+        assert!(evaluate("LOADADDR(.data)").is_err());
+        assert!(parse_expression(&mut Tokeniser::new("LOADADDR(.text)"))
+            .unwrap()
+            .is_absolute());
+        assert_eq!(evaluate("_stext + 4").unwrap(), 0x10004);
+        assert_eq!(evaluate("DEFINED(_stext) ? 1 : 2").unwrap(), 1);
+        assert_eq!(evaluate("DEFINED(foo) ? 1 : 2").unwrap(), 2);
+        assert_eq!(evaluate("ORIGIN(RAM) + LENGTH(RAM)").unwrap(), 0x2001_0000);
+        assert!(evaluate("ADDR(.data)").is_err());
+        assert!(evaluate("foo").is_err());
+
+        assert!(parse_script(". = 0x1000;", Modifiers::default(), &mut no_include).is_err());
+    }
+
+    #[test]
+    fn test_locations_in_output_sections() {
+        let script = parse_script(
+            "SECTIONS {
+                .data : { . = ALIGN(8); _sdata = .; *(.data) _mid = .; . += 4; _edata = .; }
+                .stack (NOLOAD) : { _sstack = .; . = . + 0x200; _estack = .; }
+            }",
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap();
+        let layout = &script.layout;
+        let [SectionsCommand::OutputSection(data), SectionsCommand::OutputSection(stack)] =
+            layout.sections.as_slice()
+        else {
+            panic!("Unexpected commands {:?}", layout.sections);
+        };
+        let context = EvaluationContext {
+            location: 0x1001,
+            sizeof_headers: 0,
+            max_page_size: 0x1000,
+            common_page_size: 0x1000,
+            memory_regions: &[],
+            layout: None,
+        };
+        let evaluate = |locations: &[Expression]| {
+            locations
+                .iter()
+                .map(|location| location.evaluate(&context).unwrap())
+                .collect_vec()
+        };
+        assert_eq!(evaluate(&data.start_locations), [0x1008]);
+        assert_eq!(evaluate(&data.end_locations), [0x1005]);
+        assert!(stack.start_locations.is_empty());
+        assert_eq!(evaluate(&stack.end_locations), [0x1201]);
+        assert!(layout.section_has_assignments(0));
+        assert!(layout.section_has_assignments(1));
+
+        let without_assignments = parse_script(
+            "SECTIONS { .text : { *(.text) } .marker : { _marker = .; } }",
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap()
+        .layout;
+        assert!(!without_assignments.section_has_assignments(0));
+        assert!(without_assignments.section_has_assignments(1));
+
+        let position = |index, at_end, location_index| AssignmentPosition::InOutputSection {
+            index,
+            at_end,
+            location_index,
+        };
+        assert_eq!(
+            layout
+                .symbol_assignments
+                .iter()
+                .map(|a| (a.name.as_str(), a.position))
+                .collect_vec(),
+            [
+                ("_sdata", position(0, false, 1)),
+                ("_mid", position(0, true, 0)),
+                ("_edata", position(0, true, 1)),
+                ("_sstack", position(1, true, 0)),
+                ("_estack", position(1, true, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entry_assert_and_extern() {
+        let script = parse_script(
+            r#"ENTRY(_start)
+            EXTERN(foo bar, baz)
+            ENTRY(reset_handler)
+            SECTIONS {
+                .text : { *(.text) ASSERT(SIZEOF(.text) > 0, "No code") }
+                ASSERT(ADDR(.text) == 0x1000, "Wrong address");
+                . = ASSERT(. < 0x2000, "Too big");
+            }
+            ASSERT(DEFINED(foo), "foo missing")"#,
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap();
+        let layout = &script.layout;
+        assert_eq!(layout.entry.as_deref(), Some("reset_handler"));
+        assert_eq!(layout.extern_symbols, ["foo", "bar", "baz"]);
+        let assertions = layout
+            .assertions
+            .iter()
+            .map(|a| (a.message.as_str(), a.position))
+            .collect_vec();
+        assert_eq!(
+            assertions,
+            [
+                (
+                    "No code",
+                    AssignmentPosition::InOutputSection {
+                        index: 0,
+                        at_end: true,
+                        location_index: 0
+                    }
+                ),
+                ("Wrong address", AssignmentPosition::BeforeCommand(1)),
+                ("Too big", AssignmentPosition::BeforeCommand(1)),
+                ("foo missing", AssignmentPosition::TopLevel),
+            ]
+        );
+        // `. = ASSERT(...)` doesn't move the location counter.
+        assert_eq!(layout.sections.len(), 1);
+
+        let mut combined = ScriptLayout::default();
+        combined.extend(
+            parse_script(
+                "SECTIONS { .data : { *(.data) } }",
+                Modifiers::default(),
+                &mut no_include,
+            )
+            .unwrap()
+            .layout,
+        );
+        combined.extend(script.layout);
+        assert_eq!(combined.entry.as_deref(), Some("reset_handler"));
+        assert_eq!(
+            combined.assertions[1].position,
+            AssignmentPosition::BeforeCommand(2)
+        );
+    }
+
+    #[test]
+    fn test_insert() {
+        let script = parse_script(
+            "SECTIONS { .a : { *(.a) } .b : { *(.b) } } INSERT AFTER .data;
+            SECTIONS { .c : { *(.c) } } INSERT BEFORE .text",
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap();
+        let layout = &script.layout;
+        assert_eq!(
+            layout.insertions,
+            [
+                Insertion {
+                    commands: 0..2,
+                    anchor: ".data".to_owned(),
+                    after: true,
+                },
+                Insertion {
+                    commands: 2..3,
+                    anchor: ".text".to_owned(),
+                    after: false,
+                },
+            ]
+        );
+        assert!(!layout.replaces_default_layout());
+        assert_eq!(layout.insertion_for(1).unwrap().anchor, ".data");
+
+        let mut combined = parse_script(
+            "SECTIONS { .text : { *(.text) } }",
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap()
+        .layout;
+        combined.extend(script.layout);
+        assert!(combined.replaces_default_layout());
+        assert!(combined.insertion_for(0).is_none());
+        assert_eq!(combined.insertion_for(3).unwrap().commands, 3..4);
+
+        assert!(parse_script(
+            "SECTIONS { .a : { *(.a) } } INSERT .data",
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_overlay() {
+        let script = parse_script(
+            "SECTIONS {
+                OVERLAY 0x1000 : NOCROSSREFS AT(0x4000) {
+                    .ov.a { *(.a) }
+                    .ov.b { *(.b) } =0x90
+                } >RAM :code
+                .data : { *(.data) }
+            }",
+            Modifiers::default(),
+            &mut no_include,
+        )
+        .unwrap();
+        let layout = &script.layout;
+        let outputs = layout
+            .sections
+            .iter()
+            .filter_map(|command| match command {
+                SectionsCommand::OutputSection(output) => Some(output),
+                SectionsCommand::SetLocation(_) => None,
+            })
+            .collect_vec();
+        assert_eq!(
+            outputs.iter().map(|output| &output.name).collect_vec(),
+            [".ov.a", ".ov.b", ".data"]
+        );
+        let (a, b) = (outputs[0], outputs[1]);
+        assert_eq!(a.address, Some(Expression::Number(0x1000)));
+        assert_eq!(a.load_address, Some(Expression::Number(0x4000)));
+        assert!(!a.shares_address);
+        assert_eq!(b.address, None);
+        assert_eq!(b.load_address, None);
+        assert!(b.shares_address);
+        assert!(!outputs[2].shares_address);
+        assert_eq!(a.region.as_deref(), Some("RAM"));
+        assert_eq!(b.region.as_deref(), Some("RAM"));
+        assert_eq!(
+            layout.section_fills,
+            [SectionFill {
+                section_name: ".ov.b".to_owned(),
+                pattern: vec![0x90]
+            }]
+        );
+        assert_eq!(
+            layout
+                .section_program_headers
+                .iter()
+                .map(|headers| (
+                    headers.section_name.as_str(),
+                    headers.program_headers.clone()
+                ))
+                .collect_vec(),
+            [
+                (".ov.a", vec!["code".to_owned()]),
+                (".ov.b", vec!["code".to_owned()])
+            ]
+        );
+
+        let names = layout
+            .symbol_assignments
+            .iter()
+            .map(|assignment| {
+                assert!(assignment.provide);
+                assignment.name.as_str()
+            })
+            .collect_vec();
+        assert_eq!(
+            names,
+            [
+                "__load_start_ova",
+                "__load_stop_ova",
+                "__load_start_ovb",
+                "__load_stop_ovb"
+            ]
+        );
+    }
+
     #[test]
     fn test_parse_version_script() {
         let data = VersionScriptData {
@@ -442,7 +3372,8 @@ mod tests {
             .into(),
         };
         let script = VersionScript::parse(&data).unwrap();
-        let version = script.version.unwrap();
+        assert_eq!(script.versions.len(), 1);
+        let version = &script.versions[0];
         assert_equal(
             version
                 .globals
@@ -461,4 +3392,95 @@ mod tests {
         );
         assert!(version.locals.matches_all);
     }
+
+    #[test]
+    fn test_parse_named_versions() {
+        let data = VersionScriptData {
+            raw: r#"
+                    VERS_1 {
+                        global: foo; foo_*;
+                        local: *;
+                    };
+                    VERS_2 {
+                        foo_bar;
+                        baz*;
+                    } VERS_1;"#
+                .into(),
+        };
+        let script = VersionScript::parse(&data).unwrap();
+        assert_equal(
+            script.named_versions().iter().map(|v| v.name.unwrap()),
+            ["VERS_1", "VERS_2"],
+        );
+        assert_equal(script.versions[1].parents.iter().copied(), ["VERS_1"]);
+
+        let version_of = |name: &str| script.version_index(&SymbolName::prehashed(name.as_bytes()));
+        assert_eq!(version_of("foo"), 2);
+        assert_eq!(version_of("foo_x"), 2);
+        // An exact match takes precedence over a glob in an earlier version.
+        assert_eq!(version_of("foo_bar"), 3);
+        assert_eq!(version_of("baz1"), 3);
+        assert!(script.is_local(&SymbolName::prehashed(b"other")));
+        assert!(!script.is_local(&SymbolName::prehashed(b"baz1")));
+        assert_equal(
+            script
+                .exact_globals()
+                .map(|(version, name)| (version.unwrap(), name.bytes())),
+            [("VERS_1", b"foo".as_slice()), ("VERS_2", b"foo_bar")],
+        );
+
+        // Symbols versioned with `.symver` only look at the patterns of their own version.
+        assert_eq!(script.named_version_index(b"VERS_1"), Some(2));
+        assert_eq!(script.named_version_index(b"VERS_2"), Some(3));
+        assert_eq!(script.named_version_index(b"VERS_3"), None);
+        assert!(script.is_local_in_version(&SymbolName::prehashed(b"other"), b"VERS_1"));
+        assert!(!script.is_local_in_version(&SymbolName::prehashed(b"other"), b"VERS_2"));
+        assert!(!script.is_local_in_version(&SymbolName::prehashed(b"foo_x"), b"VERS_1"));
+    }
+
+    #[test]
+    fn test_extern_cxx_version_script() {
+        let data = VersionScriptData {
+            raw: r#"
+                    VERS_1 {
+                        global:
+                            extern "C++" {
+                                ns::Widget::*;
+                                "int twice<int>(int)";
+                                mycrate::inner::exported
+                            };
+                            c_fn;
+                        local:
+                            extern "C++" { ns::Hidden::*; };
+                    };"#
+            .into(),
+        };
+        let script = VersionScript::parse(&data).unwrap();
+        let version_of = |name: &str| script.version_index(&SymbolName::prehashed(name.as_bytes()));
+        let is_local = |name: &str| script.is_local(&SymbolName::prehashed(name.as_bytes()));
+        // ns::Widget::f(int)
+        assert_eq!(version_of("_ZN2ns6Widget1fEi"), 2);
+        // int twice<int>(int)
+        assert_eq!(version_of("_Z5twiceIiET_S0_"), 2);
+        // long twice<long>(long)
+        assert_eq!(version_of("_Z5twiceIlET_S0_"), 1);
+        // Rust v0 mangling of mycrate::inner::exported
+        assert_eq!(version_of("_RNvNtCs1234_7mycrate5inner8exported"), 2);
+        assert_eq!(version_of("c_fn"), 2);
+        // ns::Hidden::g()
+        assert!(is_local("_ZN2ns6Hidden1gEv"));
+        assert!(!is_local("_ZN2ns6Widget1fEi"));
+    }
+
+    #[test]
+    fn test_invalid_version_scripts() {
+        for raw in [
+            "VERS_2 { foo; } VERS_1;",
+            "VERS_1 { foo; }; VERS_1 { bar; };",
+            "{ foo; }; VERS_1 { bar; };",
+        ] {
+            let data = VersionScriptData { raw: raw.into() };
+            assert!(VersionScript::parse(&data).is_err(), "{raw}");
+        }
+    }
 }
@@ -0,0 +1,59 @@
+//! Support for `--max-memory=SIZE`, which asks us to try to keep our peak memory usage within a
+//! budget, e.g. on small CI runners where exceeding it would get us OOM-killed. We have no way to
+//! bound our memory usage precisely, so instead we use the budget to pick settings that trade link
+//! speed for lower peak usage:
+//!
+//! - Each thread has its own allocator arenas and scratch buffers, so we reduce the number of
+//!   threads until their combined overhead fits within the budget.
+//! - Unless the user explicitly asked otherwise, we write the output via mmap. Dirty pages of a
+//!   file-backed mapping can be written back and reclaimed by the kernel, whereas building the
+//!   output in memory requires all of it to be resident at once.
+//!
+//! After linking, we warn if our peak usage exceeded the budget anyway.
+
+use crate::args::Args;
+use crate::args::MmapOutput;
+use std::num::NonZeroUsize;
+
+/// A rough estimate of the memory used by each thread beyond what is shared between threads.
+const MEMORY_PER_THREAD: u64 = 128 * 1024 * 1024;
+
+/// Adjusts the settings in `args` to suit the memory budget, if one was specified.
+pub(crate) fn apply(args: &mut Args) {
+    let Some(budget) = args.max_memory else {
+        return;
+    };
+    let max_threads = usize::try_from(budget / MEMORY_PER_THREAD)
+        .ok()
+        .and_then(NonZeroUsize::new)
+        .unwrap_or(NonZeroUsize::MIN);
+    args.num_threads = args.num_threads.min(max_threads);
+    if args.mmap_output == MmapOutput::Auto {
+        args.mmap_output = MmapOutput::Always;
+    }
+}
+
+/// Warns if our peak memory usage so far exceeded the budget.
+pub(crate) fn check_peak_usage(args: &Args) {
+    let Some(budget) = args.max_memory else {
+        return;
+    };
+    let Some(peak) = peak_resident_bytes() else {
+        return;
+    };
+    if peak > budget {
+        crate::error::warning(&format!(
+            "Peak memory usage of {peak} bytes exceeded --max-memory={budget}"
+        ));
+    }
+}
+
+fn peak_resident_bytes() -> Option<u64> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // Safety: We pass a pointer to a rusage struct that we own.
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    // ru_maxrss is in kilobytes.
+    u64::try_from(usage.ru_maxrss).ok().map(|kb| kb * 1024)
+}
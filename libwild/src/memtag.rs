@@ -0,0 +1,180 @@
+//! Support for AArch64 memory tagging (MTE). `-z memtag-mode`, `-z memtag-heap` and
+//! `-z memtag-stack` are recorded in dynamic tags for the dynamic loader, which is what enables
+//! tagging when the program starts.
+//!
+//! Compilers that tag globals emit a `.memtag.globals.static` section containing a relocation
+//! against each global that should be tagged. We don't copy that section to the output. Instead, we
+//! write a `.memtag.globals.dynamic` section describing where each tagged global is, which the
+//! dynamic loader reads in order to tag them. See the memtag ABI supplement for details of the
+//! format: https://github.com/ARM-software/abi-aa/blob/main/memtagabielf64/memtagabielf64.rst
+
+use crate::arch::Architecture;
+use crate::args::Args;
+use crate::args::MemtagMode;
+use crate::elf::File;
+use crate::error::Result;
+use crate::layout::FileLayout;
+use crate::layout::GroupLayout;
+use anyhow::bail;
+use linker_utils::elf::sht;
+use linker_utils::elf::SectionType;
+use object::read::elf::Rela as _;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+
+/// Tagging works in units of this many bytes, so tagged globals must be aligned to and sized as a
+/// multiple of this.
+const GRANULE_SIZE: u64 = 16;
+
+/// The number of low bits of the first value of each descriptor that hold the size of the global
+/// rather than the distance from the previous global.
+const STEP_SIZE_BITS: u32 = 3;
+
+/// The most space that a single descriptor can take up, which is two 64 bit ULEB128 values. We
+/// allocate this much per tagged global, since we don't know how big each descriptor will be
+/// until we know the addresses of the globals.
+pub(crate) const MAX_DESCRIPTOR_SIZE: u64 = 20;
+
+/// Returns whether we should tell the dynamic loader about memory tagging.
+pub(crate) fn is_enabled(args: &Args) -> bool {
+    matches!(args.arch(), Architecture::AArch64)
+        && args.memtag_mode != MemtagMode::None
+        && args.needs_dynamic()
+}
+
+/// Returns the value of the DT_AARCH64_MEMTAG_MODE dynamic tag.
+pub(crate) fn dynamic_mode(args: &Args) -> u64 {
+    u64::from(args.memtag_mode == MemtagMode::Async)
+}
+
+/// Returns the indexes of the symbols that `object` asks to have tagged.
+pub(crate) fn tagged_symbols(object: &File) -> Result<Vec<object::SymbolIndex>> {
+    let e = LittleEndian;
+    let mut symbols = Vec::new();
+    for (index, section) in object.sections.enumerate() {
+        if SectionType::from_header(section) != sht::AARCH64_MEMTAG_GLOBALS_STATIC {
+            continue;
+        }
+        for rel in object.relocations(index)? {
+            if let Some(symbol_index) = rel.symbol(e, false) {
+                symbols.push(symbol_index);
+            }
+        }
+    }
+    Ok(symbols)
+}
+
+/// A global that's been allocated an address.
+struct TaggedGlobal {
+    address: u64,
+    size: u64,
+    name: String,
+}
+
+/// Builds the contents of `.memtag.globals.dynamic`. Globals in sections that we didn't load are
+/// left out, as are globals that were overridden by a definition in another object.
+pub(crate) fn build_descriptors<S: crate::storage::StorageModel>(
+    group_layouts: &[GroupLayout],
+    symbol_db: &crate::symbol_db::SymbolDb<S>,
+) -> Result<Vec<u8>> {
+    if !is_enabled(symbol_db.args) {
+        return Ok(Vec::new());
+    }
+    let e = LittleEndian;
+    let mut globals = Vec::new();
+    for file in group_layouts.iter().flat_map(|group| &group.files) {
+        let FileLayout::Object(obj) = file else {
+            continue;
+        };
+        for symbol_index in tagged_symbols(obj.object)? {
+            let symbol_id = obj.symbol_id_range.input_to_id(symbol_index);
+            if !symbol_db.is_canonical(symbol_id) {
+                continue;
+            }
+            let sym = obj.object.symbol(symbol_index)?;
+            let Some(section_index) = obj.object.symbol_section(sym, symbol_index)? else {
+                continue;
+            };
+            let Some(section_address) = obj.section_resolutions[section_index.0].address() else {
+                continue;
+            };
+            globals.push(TaggedGlobal {
+                address: section_address + sym.st_value(e),
+                size: sym.st_size(e),
+                name: symbol_db.symbol_name_for_display(symbol_id).to_string(),
+            });
+        }
+    }
+    encode_descriptors(globals)
+}
+
+fn encode_descriptors(mut globals: Vec<TaggedGlobal>) -> Result<Vec<u8>> {
+    globals.sort_by_key(|g| g.address);
+    globals.dedup_by_key(|g| g.address);
+    let mut out = Vec::new();
+    let mut previous_end = 0;
+    for global in &globals {
+        if global.address % GRANULE_SIZE != 0 {
+            bail!(
+                "Tagged global `{}` at 0x{:x} isn't aligned to {GRANULE_SIZE} bytes",
+                global.name,
+                global.address
+            );
+        }
+        if global.size == 0 || global.size % GRANULE_SIZE != 0 {
+            bail!(
+                "Tagged global `{}` has size {}, which isn't a non-zero multiple of \
+                 {GRANULE_SIZE}",
+                global.name,
+                global.size
+            );
+        }
+        let Some(step) = global.address.checked_sub(previous_end) else {
+            bail!("Tagged global `{}` overlaps the previous one", global.name);
+        };
+        let step = (step / GRANULE_SIZE) << STEP_SIZE_BITS;
+        let granules = global.size / GRANULE_SIZE;
+        if granules < 1 << STEP_SIZE_BITS {
+            write_uleb128(&mut out, step | granules);
+        } else {
+            // A size of zero in the first value means that the size follows.
+            write_uleb128(&mut out, step);
+            write_uleb128(&mut out, granules - 1);
+        }
+        previous_end = global.address + global.size;
+    }
+    Ok(out)
+}
+
+fn write_uleb128(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_descriptors() {
+        let global = |address, size| TaggedGlobal {
+            address,
+            size,
+            name: String::new(),
+        };
+        let out = encode_descriptors(vec![global(0x1100, 0x100), global(0x1000, 0x10)]).unwrap();
+        // 0x1000 is 0x100 granules in and one granule long. 0x1100 is 15 granules after that and is
+        // 16 granules long, which doesn't fit in the first value.
+        assert_eq!(out, [0x81, 0x10, 0x78, 0x0f]);
+
+        assert!(encode_descriptors(vec![global(0x1008, 0x10)]).is_err());
+        assert!(encode_descriptors(vec![global(0x1000, 0x18)]).is_err());
+    }
+}
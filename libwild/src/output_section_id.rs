@@ -19,13 +19,24 @@ use self::elf::TLS_MODULE_BASE_SYMBOL_NAME;
 use crate::alignment;
 use crate::alignment::Alignment;
 use crate::alignment::NUM_ALIGNMENTS;
+use crate::args::Magic;
 use crate::args::OutputKind;
+use crate::args::SeparateSegments;
+use crate::args::SortCommon;
 use crate::elf;
 use crate::elf::DynamicEntry;
 use crate::elf::Versym;
 use crate::error::Result;
 use crate::layout::NonAddressableCounts;
 use crate::layout::OutputRecordLayout;
+use crate::linker_script::EvaluationContext;
+use crate::linker_script::Expression;
+use crate::linker_script::MemoryRegion;
+use crate::linker_script::ProgramHeader;
+use crate::linker_script::ScriptLayout;
+use crate::linker_script::SectionProgramHeaders;
+use crate::linker_script::SectionsCommand;
+use crate::linker_script::DISCARD_SECTION_NAME;
 use crate::output_section_map::OutputSectionMap;
 use crate::output_section_part_map::OutputSectionPartMap;
 use crate::part_id;
@@ -37,6 +48,8 @@ use crate::program_segments::ProgramSegmentId;
 use crate::resolution::SectionSlot;
 use ahash::AHashMap;
 use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
 #[allow(clippy::wildcard_imports)]
 use linker_utils::elf::secnames::*;
 use linker_utils::elf::shf;
@@ -67,6 +80,10 @@ pub(crate) struct CustomSectionDetails<'data> {
     pub(crate) alignment: Alignment,
     pub(crate) section_flags: SectionFlags,
     pub(crate) ty: SectionType,
+
+    /// Whether this input section should be placed in an output section of its own. See
+    /// `--unique`.
+    pub(crate) unique: bool,
 }
 
 // Single-part sections that we generate ourselves rather than copying directly from input objects.
@@ -91,6 +108,14 @@ pub(crate) const NOTE_GNU_PROPERTY: OutputSectionId =
     part_id::NOTE_GNU_PROPERTY.output_section_id();
 pub(crate) const NOTE_GNU_BUILD_ID: OutputSectionId =
     part_id::NOTE_GNU_BUILD_ID.output_section_id();
+pub(crate) const MEMTAG_GLOBALS_DYNAMIC: OutputSectionId =
+    part_id::MEMTAG_GLOBALS_DYNAMIC.output_section_id();
+pub(crate) const GNU_BUILD_ATTRIBUTES: OutputSectionId =
+    part_id::GNU_BUILD_ATTRIBUTES.output_section_id();
+pub(crate) const PLT_SEC: OutputSectionId = part_id::PLT_SEC.output_section_id();
+pub(crate) const RISCV_ATTRIBUTES: OutputSectionId = part_id::RISCV_ATTRIBUTES.output_section_id();
+pub(crate) const GNU_DEBUGLINK: OutputSectionId = part_id::GNU_DEBUGLINK.output_section_id();
+pub(crate) const GNU_VERSION_D: OutputSectionId = part_id::GNU_VERSION_D.output_section_id();
 
 // These two are multi-part sections, but we can pick any part we wish in order to get the section
 // ID.
@@ -117,8 +142,42 @@ pub(crate) const NUM_BUILT_IN_REGULAR_SECTIONS: usize = 14;
 pub(crate) struct OutputSections<'data> {
     /// The base address for our output binary.
     pub(crate) base_address: u64,
+
+    /// Whether `base_address` was given explicitly with `--image-base` or `-Ttext-segment`. If it
+    /// wasn't, then a section with a start address below it moves our headers down too.
+    pub(crate) fixed_base_address: bool,
     pub(crate) section_infos: Vec<SectionOutputInfo<'data>>,
 
+    /// The order of the alignment-based parts of `.bss`, which is where common symbols go.
+    pub(crate) sort_common: SortCommon,
+
+    /// Whether segments are page aligned, or packed together as for `-n` and `-N`.
+    pub(crate) magic: Magic,
+
+    /// The alignment of loadable segments.
+    pub(crate) max_page_size: Alignment,
+
+    /// Which loadable segments start on a page boundary in the file.
+    pub(crate) separate_segments: SeparateSegments,
+
+    /// Whether we're emitting a `PT_GNU_RELRO` segment. Cleared during layout if nothing would be
+    /// in it.
+    pub(crate) relro: bool,
+
+    /// The page size that `PT_GNU_RELRO` is padded to, so that making it read-only doesn't affect
+    /// whatever follows it.
+    pub(crate) common_page_size: Alignment,
+
+    /// Program headers defined by a linker script. If there are any, we write these instead of our
+    /// own.
+    pub(crate) script_program_headers: Vec<ProgramHeader>,
+
+    /// Whether a linker script's `SECTIONS` determines the order and addresses of our sections.
+    pub(crate) script_layout: bool,
+
+    /// Memory regions defined by a linker script's `MEMORY` command.
+    pub(crate) memory_regions: &'data [MemoryRegion],
+
     // TODO: Consider moving this to Layout. We can't populate this until we know which output
     // sections have content, which we don't know until half way through the layout phase.
     /// Mapping from internal section IDs to output section indexes. None, if the section isn't
@@ -136,6 +195,10 @@ struct CustomSectionIds {
     data: Vec<OutputSectionId>,
     bss: Vec<OutputSectionId>,
     nonalloc: Vec<OutputSectionId>,
+
+    /// Debug info sections, if we're putting them after everything else. Otherwise they're in
+    /// `nonalloc`.
+    debug: Vec<OutputSectionId>,
 }
 
 impl OutputSections<'_> {
@@ -223,6 +286,105 @@ pub(crate) struct SectionOutputInfo<'data> {
     pub(crate) name: SectionName<'data>,
     pub(crate) section_flags: SectionFlags,
     pub(crate) ty: SectionType,
+
+    /// A pattern to fill gaps in the section with, set by a linker script.
+    pub(crate) fill: Option<&'data [u8]>,
+
+    /// The address at which to place the section, if set by `--section-start` or similar.
+    pub(crate) start_address: Option<u64>,
+
+    /// Indexes into `OutputSections::script_program_headers` of the program headers that contain
+    /// this section.
+    pub(crate) script_program_headers: Vec<usize>,
+
+    /// Where a linker script's `SECTIONS` places this section, if it mentions it.
+    pub(crate) script_placement: Option<SectionPlacement<'data>>,
+}
+
+/// Where the `SECTIONS` command of a linker script places an output section.
+#[derive(Debug, Default)]
+pub(crate) struct SectionPlacement<'data> {
+    /// Assignments to the location counter that come before the section, in order.
+    pub(crate) location_assignments: Vec<&'data Expression>,
+
+    /// The address given in the output section description, if any.
+    pub(crate) address: Option<&'data Expression>,
+
+    /// The alignment given with `ALIGN(...)` after the colon, if any.
+    pub(crate) alignment: Option<&'data Expression>,
+
+    /// The index in `OutputSections::memory_regions` of the region given with `>region`, if any.
+    pub(crate) region: Option<usize>,
+
+    /// The index of the region given with `AT>region`, if any.
+    pub(crate) load_region: Option<usize>,
+
+    /// The load address given with `AT(address)`, if any.
+    pub(crate) load_address: Option<&'data Expression>,
+
+    /// Whether the section is a member of an `OVERLAY` other than the first, so starts at the same
+    /// address as the previous section.
+    pub(crate) shares_address: bool,
+
+    /// Assignments to `.` within the section, before its input sections, which move its start.
+    pub(crate) start_locations: &'data [Expression],
+
+    /// Assignments to `.` within the section, after its input sections, which pad its end.
+    pub(crate) end_locations: &'data [Expression],
+
+    /// Whether the section contains assignments, in which case we output it even if it's empty.
+    pub(crate) has_assignments: bool,
+}
+
+impl SectionPlacement<'_> {
+    /// Returns the address of the section. `context` supplies the value of the location counter
+    /// after the previous section.
+    pub(crate) fn address(
+        &self,
+        section_alignment: Alignment,
+        context: &EvaluationContext,
+    ) -> Result<u64> {
+        let mut context = EvaluationContext { ..*context };
+        for assignment in &self.location_assignments {
+            context.location = assignment.evaluate(&context)?;
+        }
+        let address = match self.address {
+            Some(address) => address.evaluate(&context)?,
+            None => section_alignment.align_up(context.location),
+        };
+        let Some(alignment) = self.alignment else {
+            return Ok(address);
+        };
+        Ok(Alignment::new(alignment.evaluate(&context)?)?.align_up(address))
+    }
+
+    /// Returns whether the script decides where the section goes, rather than it just following
+    /// the previous section, possibly with some alignment.
+    pub(crate) fn is_fixed(&self) -> bool {
+        self.address.is_some()
+            || self
+                .location_assignments
+                .iter()
+                .any(|assignment| !assignment.is_location_alignment())
+    }
+}
+
+/// A loadable segment that sections can be placed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LoadSegment {
+    BuiltIn(ProgramSegmentId),
+
+    /// An index into `OutputSections::script_program_headers`.
+    Script(usize),
+}
+
+impl LoadSegment {
+    pub(crate) fn alignment(self, page_alignment: Alignment) -> Alignment {
+        match self {
+            LoadSegment::BuiltIn(segment_id) => segment_id.alignment(page_alignment),
+            LoadSegment::Script(_) => page_alignment,
+        }
+    }
 }
 
 pub(crate) struct BuiltInSectionDetails {
@@ -423,6 +585,46 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
         min_alignment: alignment::NOTE_GNU_BUILD_ID,
         ..DEFAULT_DEFS
     },
+    BuiltInSectionDetails {
+        name: SectionName(MEMTAG_GLOBALS_DYNAMIC_SECTION_NAME),
+        ty: sht::AARCH64_MEMTAG_GLOBALS_DYNAMIC,
+        section_flags: shf::ALLOC,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        name: SectionName(GNU_BUILD_ATTRIBUTES_SECTION_NAME),
+        ty: sht::NOTE,
+        min_alignment: alignment::GNU_BUILD_ATTRIBUTES,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        name: SectionName(PLT_SEC_SECTION_NAME),
+        ty: sht::PROGBITS,
+        section_flags: shf::ALLOC.with(shf::EXECINSTR),
+        element_size: crate::elf::PLT_ENTRY_SIZE,
+        min_alignment: alignment::PLT,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        name: SectionName(RISCV_ATTRIBUTES_SECTION_NAME),
+        ty: sht::RISCV_ATTRIBUTES,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        name: SectionName(GNU_DEBUGLINK_SECTION_NAME),
+        ty: sht::PROGBITS,
+        min_alignment: alignment::GNU_DEBUGLINK,
+        ..DEFAULT_DEFS
+    },
+    BuiltInSectionDetails {
+        name: SectionName(GNU_VERSION_D_SECTION_NAME),
+        ty: sht::GNU_VERDEF,
+        section_flags: shf::ALLOC,
+        info_fn: Some(version_d_info),
+        min_alignment: alignment::VERSION_D,
+        link: &[DYNSTR],
+        ..DEFAULT_DEFS
+    },
     // Multi-part generated sections
     BuiltInSectionDetails {
         name: SectionName(SYMTAB_SECTION_NAME),
@@ -674,6 +876,10 @@ pub(crate) struct OutputSectionsBuilder<'data> {
     custom_by_name: AHashMap<SectionName<'data>, OutputSectionId>,
     // TODO: Change this to be an OutputSectionMap.
     section_infos: Vec<SectionOutputInfo<'data>>,
+
+    /// Whether to place debug info sections after all other sections, both in the file and in the
+    /// section header table. This lets us split them off into a separate file by truncating.
+    debug_info_last: bool,
 }
 
 impl<'data> OutputSectionsBuilder<'data> {
@@ -690,6 +896,8 @@ impl<'data> OutputSectionsBuilder<'data> {
             } else if !info.section_flags.contains(shf::WRITE) {
                 if info.section_flags.contains(shf::ALLOC) {
                     custom.ro.push(id);
+                } else if self.debug_info_last && info.name.bytes().starts_with(b".debug_") {
+                    custom.debug.push(id);
                 } else {
                     custom.nonalloc.push(id);
                 }
@@ -702,8 +910,18 @@ impl<'data> OutputSectionsBuilder<'data> {
 
         let mut output_sections = OutputSections {
             base_address: self.base_address,
+            fixed_base_address: false,
             section_infos: self.section_infos,
             custom_by_name: self.custom_by_name,
+            sort_common: SortCommon::default(),
+            magic: Magic::Demand,
+            max_page_size: alignment::PAGE,
+            separate_segments: SeparateSegments::None,
+            relro: false,
+            common_page_size: alignment::PAGE,
+            script_program_headers: Vec::new(),
+            script_layout: false,
+            memory_regions: &[],
             output_section_indexes: Default::default(),
             sections_and_segments_events: custom.sections_and_segments_events(),
         };
@@ -719,7 +937,11 @@ impl<'data> OutputSectionsBuilder<'data> {
         sections: &mut [SectionSlot],
     ) {
         for custom in custom_sections {
-            let section_id = self.add_section(custom.name, custom.section_flags, custom.ty);
+            let section_id = if custom.unique {
+                self.add_unique_section(custom.name, custom.section_flags, custom.ty)
+            } else {
+                self.add_section(custom.name, custom.section_flags, custom.ty)
+            };
             // Section flags are sometimes different, take the union of everything we're
             // given.
             self.section_infos[section_id.as_usize()].section_flags |= custom.section_flags;
@@ -736,17 +958,36 @@ impl<'data> OutputSectionsBuilder<'data> {
         section_flags: SectionFlags,
         section_type: SectionType,
     ) -> OutputSectionId {
-        *self.custom_by_name.entry(name).or_insert_with(|| {
-            let id = OutputSectionId::from_usize(self.section_infos.len());
-            self.section_infos.push(SectionOutputInfo {
-                section_flags,
-                name,
-                // We'll fill this in properly in `determine_loadable_segment_ids`.
-                loadable_segment_id: None,
-                ty: section_type,
-            });
-            id
-        })
+        if let Some(id) = self.custom_by_name.get(&name) {
+            return *id;
+        }
+        let id = self.add_unique_section(name, section_flags, section_type);
+        self.custom_by_name.insert(name, id);
+        id
+    }
+
+    /// Adds an output section that isn't shared with any other input section, even those with the
+    /// same name. Since the section can't be looked up by name, it doesn't get `__start_` or
+    /// `__stop_` symbols.
+    fn add_unique_section(
+        &mut self,
+        name: SectionName<'data>,
+        section_flags: SectionFlags,
+        section_type: SectionType,
+    ) -> OutputSectionId {
+        let id = OutputSectionId::from_usize(self.section_infos.len());
+        self.section_infos.push(SectionOutputInfo {
+            section_flags,
+            name,
+            // We'll fill this in properly in `determine_loadable_segment_ids`.
+            loadable_segment_id: None,
+            ty: section_type,
+            fill: None,
+            start_address: None,
+            script_program_headers: Vec::new(),
+            script_placement: None,
+        });
+        id
     }
 
     pub(crate) fn with_base_address(base_address: u64) -> Self {
@@ -757,14 +998,45 @@ impl<'data> OutputSectionsBuilder<'data> {
                 name: d.name,
                 loadable_segment_id: Some(crate::program_segments::LOAD_RO),
                 ty: d.ty,
+                fill: None,
+                start_address: None,
+                script_program_headers: Vec::new(),
+                script_placement: None,
             })
             .collect();
         Self {
             section_infos,
             base_address,
             custom_by_name: AHashMap::new(),
+            debug_info_last: false,
         }
     }
+
+    /// Adds the output sections that a linker script's `SECTIONS` describes and assigns within, but
+    /// that no input section goes in, e.g. `.stack (NOLOAD) : { . = . + 0x400; }`. As with GNU
+    /// ld, these are writable and have no data in the file.
+    pub(crate) fn add_script_sections(&mut self, script_layout: &'data ScriptLayout) {
+        if !script_layout.replaces_default_layout() {
+            return;
+        }
+        for (index, command) in script_layout.sections.iter().enumerate() {
+            let SectionsCommand::OutputSection(output) = command else {
+                continue;
+            };
+            let name = output.name.as_bytes();
+            if output.name == DISCARD_SECTION_NAME
+                || built_in_section_ids().any(|id| id.built_in_details().name.bytes() == name)
+                || !script_layout.section_has_assignments(index)
+            {
+                continue;
+            }
+            self.add_section(SectionName(name), shf::ALLOC.with(shf::WRITE), sht::NOBITS);
+        }
+    }
+
+    pub(crate) fn place_debug_info_last(&mut self) {
+        self.debug_info_last = true;
+    }
 }
 
 impl CustomSectionIds {
@@ -789,7 +1061,13 @@ impl CustomSectionIds {
         events.push(INTERP.event());
         events.push(OrderEvent::SegmentEnd(crate::program_segments::INTERP));
         events.push(OrderEvent::SegmentStart(crate::program_segments::NOTE));
+        events.push(OrderEvent::SegmentStart(
+            crate::program_segments::GNU_PROPERTY,
+        ));
         events.push(NOTE_GNU_PROPERTY.event());
+        events.push(OrderEvent::SegmentEnd(
+            crate::program_segments::GNU_PROPERTY,
+        ));
         events.push(NOTE_GNU_BUILD_ID.event());
         events.push(NOTE_ABI_TAG.event());
         events.push(OrderEvent::SegmentEnd(crate::program_segments::NOTE));
@@ -797,7 +1075,9 @@ impl CustomSectionIds {
         events.push(DYNSYM.event());
         events.push(DYNSTR.event());
         events.push(GNU_VERSION.event());
+        events.push(GNU_VERSION_D.event());
         events.push(GNU_VERSION_R.event());
+        events.push(MEMTAG_GLOBALS_DYNAMIC.event());
         events.push(RELA_DYN.event());
         events.push(RODATA.event());
         events.push(OrderEvent::SegmentStart(crate::program_segments::EH_FRAME));
@@ -811,6 +1091,7 @@ impl CustomSectionIds {
 
         events.push(OrderEvent::SegmentStart(crate::program_segments::LOAD_EXEC));
         events.push(PLT_GOT.event());
+        events.push(PLT_SEC.event());
         events.push(TEXT.event());
         events.push(INIT.event());
         events.push(FINI.event());
@@ -818,14 +1099,16 @@ impl CustomSectionIds {
         events.push(OrderEvent::SegmentEnd(crate::program_segments::LOAD_EXEC));
 
         events.push(OrderEvent::SegmentStart(crate::program_segments::LOAD_RW));
+        events.push(OrderEvent::SegmentStart(crate::program_segments::GNU_RELRO));
         events.push(GOT.event());
         events.push(RELA_PLT.event());
         events.push(INIT_ARRAY.event());
         events.push(FINI_ARRAY.event());
-        events.push(DATA.event());
         events.push(OrderEvent::SegmentStart(crate::program_segments::DYNAMIC));
         events.push(DYNAMIC.event());
         events.push(OrderEvent::SegmentEnd(crate::program_segments::DYNAMIC));
+        events.push(OrderEvent::SegmentEnd(crate::program_segments::GNU_RELRO));
+        events.push(DATA.event());
         events.extend(build_section_events(&self.data));
         events.push(OrderEvent::SegmentStart(crate::program_segments::TLS));
         events.push(TDATA.event());
@@ -840,9 +1123,13 @@ impl CustomSectionIds {
 
         events.extend(build_section_events(&self.nonalloc));
         events.push(COMMENT.event());
+        events.push(GNU_BUILD_ATTRIBUTES.event());
+        events.push(RISCV_ATTRIBUTES.event());
+        events.push(GNU_DEBUGLINK.event());
         events.push(SHSTRTAB.event());
         events.push(SYMTAB.event());
         events.push(STRTAB.event());
+        events.extend(build_section_events(&self.debug));
 
         events
     }
@@ -887,6 +1174,543 @@ impl<'data> OutputSections<'data> {
         self.output_info(id).loadable_segment_id
     }
 
+    /// Returns the loadable segment that a section goes in. If a linker script defined our program
+    /// headers, this is the first `PT_LOAD` that the section was assigned to.
+    pub(crate) fn load_segment_for(&self, id: OutputSectionId) -> Option<LoadSegment> {
+        if self.script_program_headers.is_empty() {
+            return self.loadable_segment_id_for(id).map(LoadSegment::BuiltIn);
+        }
+        self.output_info(id)
+            .script_program_headers
+            .iter()
+            .copied()
+            .find(|index| self.script_program_headers[*index].segment_type == object::elf::PT_LOAD)
+            .map(LoadSegment::Script)
+    }
+
+    /// Records which of the program headers from a linker script each section goes in. Allocated
+    /// sections that weren't assigned to any go in the same ones as the allocated section before
+    /// them, or in the first `PT_LOAD` if there's no such section. Our headers go in the program
+    /// headers that include them with `FILEHDR` or `PHDRS`.
+    pub(crate) fn assign_script_program_headers(
+        &mut self,
+        headers: &[ProgramHeader],
+        assignments: &[SectionProgramHeaders],
+    ) -> Result {
+        let mut assigned = vec![None; self.section_infos.len()];
+        for assignment in assignments {
+            let indexes = assignment
+                .program_headers
+                .iter()
+                .map(|name| {
+                    headers
+                        .iter()
+                        .position(|header| header.name == *name)
+                        .with_context(|| {
+                            format!(
+                                "Section `{}` is assigned to program header `{name}`, which isn't \
+                                 defined in PHDRS",
+                                assignment.section_name
+                            )
+                        })
+                })
+                .collect::<Result<Vec<usize>>>()?;
+            for (info, assigned) in self.section_infos.iter().zip(&mut assigned) {
+                if info.name.bytes() == assignment.section_name.as_bytes() {
+                    *assigned = Some(indexes.clone());
+                }
+            }
+        }
+        if headers.is_empty() {
+            return Ok(());
+        }
+        let indexes_where = |predicate: fn(&ProgramHeader) -> bool| -> Vec<usize> {
+            (0..headers.len())
+                .filter(|index| predicate(&headers[*index]))
+                .collect()
+        };
+        let file_header_indexes = indexes_where(|header| header.includes_file_header);
+        let program_header_indexes = indexes_where(|header| {
+            header.includes_program_headers || header.segment_type == object::elf::PT_PHDR
+        });
+        let mut current = headers
+            .iter()
+            .position(|header| header.segment_type == object::elf::PT_LOAD)
+            .into_iter()
+            .collect::<Vec<usize>>();
+
+        let section_ids = self
+            .sections_and_segments_events()
+            .filter_map(|event| match event {
+                OrderEvent::Section(section_id) => Some(section_id),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for section_id in section_ids {
+            let info = &mut self.section_infos[section_id.as_usize()];
+            info.script_program_headers =
+                if section_id == FILE_HEADER || section_id == SECTION_HEADERS {
+                    file_header_indexes.clone()
+                } else if section_id == PROGRAM_HEADERS {
+                    program_header_indexes.clone()
+                } else if info.section_flags.contains(shf::ALLOC) {
+                    if let Some(indexes) = assigned[section_id.as_usize()].take() {
+                        current = indexes;
+                    }
+                    current.clone()
+                } else {
+                    Vec::new()
+                };
+        }
+        self.script_program_headers = headers.to_vec();
+        Ok(())
+    }
+
+    /// Places our sections in the order given by the `SECTIONS` command of a linker script and
+    /// records where the script places each of them. Allocated sections that the script doesn't
+    /// mention go after one that it does with similar flags, or in the same place relative to the
+    /// mentioned sections as they would normally. Sections from `SECTIONS` that are followed by
+    /// `INSERT` go before or after the section that it names. Our file header and program headers
+    /// stay at the start.
+    pub(crate) fn apply_script_sections(&mut self, script_layout: &'data ScriptLayout) -> Result {
+        self.memory_regions = &script_layout.memory_regions;
+        if !script_layout.replaces_default_layout() {
+            return self.apply_script_insertions(script_layout);
+        }
+        let placed = self.place_script_sections(
+            script_layout,
+            (0..script_layout.sections.len())
+                .filter(|index| script_layout.insertion_for(*index).is_none()),
+        )?;
+        let mut inserted = Vec::new();
+        for insertion in &script_layout.insertions {
+            let sections = self.place_script_sections(script_layout, insertion.commands.clone())?;
+            inserted.push((insertion, sections));
+        }
+
+        // The non-loadable segments that each section is in, outermost first. We don't keep RELRO,
+        // since the script decides what follows the sections that it would cover.
+        let mut segments_by_section = vec![Vec::new(); self.section_infos.len()];
+        let mut default_order = Vec::new();
+        let mut active = Vec::new();
+        for event in self.sections_and_segments_events() {
+            match event {
+                OrderEvent::SegmentStart(segment_id) => active.push(segment_id),
+                OrderEvent::SegmentEnd(segment_id) => active.retain(|id| *id != segment_id),
+                OrderEvent::Section(section_id) => {
+                    segments_by_section[section_id.as_usize()] = active
+                        .iter()
+                        .copied()
+                        .filter(|id| {
+                            id.segment_type() != object::elf::PT_LOAD
+                                && *id != crate::program_segments::GNU_RELRO
+                        })
+                        .collect();
+                    default_order.push(section_id);
+                }
+            }
+        }
+
+        let is_placed = |id: OutputSectionId| self.output_info(id).script_placement.is_some();
+        let orphan_class = |id: OutputSectionId| {
+            let info = self.output_info(id);
+            (
+                info.section_flags.contains(shf::WRITE),
+                info.section_flags.contains(shf::EXECINSTR),
+                info.section_flags.contains(shf::TLS),
+                info.ty == sht::NOBITS,
+            )
+        };
+        let mut order = placed;
+        for (default_index, &section_id) in default_order.iter().enumerate() {
+            if is_header_section(section_id)
+                || is_placed(section_id)
+                || !self.section_flags(section_id).contains(shf::ALLOC)
+            {
+                continue;
+            }
+            let segment = segments_by_section[section_id.as_usize()].last();
+            let anchor = order
+                .iter()
+                .rposition(|id| {
+                    segment.is_some() && segments_by_section[id.as_usize()].last() == segment
+                })
+                .or_else(|| {
+                    order.iter().rposition(|id| {
+                        is_placed(*id) && orphan_class(*id) == orphan_class(section_id)
+                    })
+                })
+                .or_else(|| {
+                    default_order[..default_index]
+                        .iter()
+                        .rev()
+                        .find_map(|previous| order.iter().position(|id| id == previous))
+                });
+            let mut position = anchor.map_or(0, |anchor| anchor + 1);
+            // Keep orphans that follow the same section in their usual order.
+            while order.get(position).is_some_and(|id| !is_placed(*id)) {
+                position += 1;
+            }
+            order.insert(position, section_id);
+        }
+        for (insertion, sections) in inserted {
+            let anchor = self.section_id_by_name(insertion.anchor.as_bytes());
+            let Some(position) = order.iter().position(|id| Some(*id) == anchor) else {
+                bail!("`{insertion}` refers to a section that isn't in the output");
+            };
+            let position = position + usize::from(insertion.after);
+            order.splice(position..position, sections);
+        }
+
+        let mut events = vec![
+            OrderEvent::SegmentStart(crate::program_segments::LOAD_RO),
+            FILE_HEADER.event(),
+            OrderEvent::SegmentStart(crate::program_segments::PHDR),
+            PROGRAM_HEADERS.event(),
+            OrderEvent::SegmentEnd(crate::program_segments::PHDR),
+            SECTION_HEADERS.event(),
+        ];
+        let mut emitted = [false; crate::program_segments::MAX_SEGMENTS];
+        emitted[crate::program_segments::LOAD_RO.as_usize()] = true;
+        emitted[crate::program_segments::PHDR.as_usize()] = true;
+        let mut open: Vec<ProgramSegmentId> = Vec::new();
+        for section_id in order {
+            let wanted = &segments_by_section[section_id.as_usize()];
+            let common = open.iter().zip(wanted).take_while(|(a, b)| a == b).count();
+            while open.len() > common {
+                events.extend(open.pop().map(OrderEvent::SegmentEnd));
+            }
+            for &segment_id in &wanted[common..] {
+                if emitted[segment_id.as_usize()] {
+                    bail!(
+                        "Linker script SECTIONS separates `{}` from other sections that need to be \
+                         in the same program header as it",
+                        self.display_name(section_id)
+                    );
+                }
+                emitted[segment_id.as_usize()] = true;
+                events.push(OrderEvent::SegmentStart(segment_id));
+                open.push(segment_id);
+            }
+            events.push(section_id.event());
+        }
+        events.extend(open.into_iter().rev().map(OrderEvent::SegmentEnd));
+        events.push(OrderEvent::SegmentEnd(crate::program_segments::LOAD_RO));
+        // Segments without any sections, such as the stack, still need to be present.
+        for segment_id in (0..crate::program_segments::MAX_SEGMENTS).map(ProgramSegmentId::new) {
+            if !emitted[segment_id.as_usize()] {
+                events.push(OrderEvent::SegmentStart(segment_id));
+                events.push(OrderEvent::SegmentEnd(segment_id));
+            }
+        }
+        events.extend(
+            default_order
+                .into_iter()
+                .filter(|id| !self.section_flags(*id).contains(shf::ALLOC))
+                .map(OrderEvent::Section),
+        );
+
+        self.sections_and_segments_events = events;
+        self.script_layout = true;
+        self.determine_loadable_segment_ids()
+    }
+
+    /// Records where the supplied commands from `SECTIONS` place each section, returning the
+    /// sections in the order given.
+    fn place_script_sections(
+        &mut self,
+        script_layout: &'data ScriptLayout,
+        commands: impl IntoIterator<Item = usize>,
+    ) -> Result<Vec<OutputSectionId>> {
+        let region_index = |name: &Option<String>, section_name: &str| {
+            name.as_ref()
+                .map(|name| {
+                    script_layout
+                        .memory_regions
+                        .iter()
+                        .position(|region| region.name == *name)
+                        .with_context(|| {
+                            format!(
+                                "Output section `{section_name}` refers to unknown memory region \
+                                 `{name}`"
+                            )
+                        })
+                })
+                .transpose()
+        };
+        let mut placed = Vec::new();
+        let mut pending_assignments = Vec::new();
+        for (index, command) in commands
+            .into_iter()
+            .map(|index| (index, &script_layout.sections[index]))
+        {
+            let output = match command {
+                SectionsCommand::SetLocation(value) => {
+                    pending_assignments.push(value);
+                    continue;
+                }
+                SectionsCommand::OutputSection(output) => output,
+            };
+            let section_id = self.section_id_by_name(output.name.as_bytes());
+            let Some(info) = section_id
+                .filter(|id| !is_header_section(*id))
+                .map(|id| &mut self.section_infos[id.as_usize()])
+                .filter(|info| {
+                    info.section_flags.contains(shf::ALLOC) && info.script_placement.is_none()
+                })
+            else {
+                // The assignments apply to whatever comes next, which includes an address for
+                // this section if it had one.
+                pending_assignments.extend(&output.address);
+                continue;
+            };
+            info.script_placement = Some(SectionPlacement {
+                location_assignments: std::mem::take(&mut pending_assignments),
+                address: output.address.as_ref(),
+                alignment: output.alignment.as_ref(),
+                region: region_index(&output.region, &output.name)?,
+                load_region: region_index(&output.load_region, &output.name)?,
+                load_address: output.load_address.as_ref(),
+                shares_address: output.shares_address,
+                start_locations: &output.start_locations,
+                end_locations: &output.end_locations,
+                has_assignments: script_layout.section_has_assignments(index),
+            });
+            if output.no_load {
+                info.ty = sht::NOBITS;
+            }
+            placed.extend(section_id);
+        }
+        Ok(placed)
+    }
+
+    /// Moves the sections named by a linker script whose `SECTIONS` are all followed by `INSERT`,
+    /// but otherwise keeps our usual layout. Since the script doesn't take over the layout,
+    /// addresses, memory regions and assignments to `.` in it are ignored.
+    fn apply_script_insertions(&mut self, script_layout: &'data ScriptLayout) -> Result {
+        let mut events = std::mem::take(&mut self.sections_and_segments_events);
+        for insertion in &script_layout.insertions {
+            let mut sections = Vec::new();
+            for command in &script_layout.sections[insertion.commands.clone()] {
+                let output = match command {
+                    SectionsCommand::SetLocation(_) => {
+                        crate::error::warning(&format!(
+                            "Ignoring assignment to `.` in SECTIONS followed by `{insertion}`"
+                        ));
+                        continue;
+                    }
+                    SectionsCommand::OutputSection(output) => output,
+                };
+                if output.address.is_some()
+                    || output.alignment.is_some()
+                    || output.region.is_some()
+                    || output.load_region.is_some()
+                    || output.load_address.is_some()
+                    || output.shares_address
+                    || !output.start_locations.is_empty()
+                    || !output.end_locations.is_empty()
+                {
+                    crate::error::warning(&format!(
+                        "Ignoring placement of `{}` in SECTIONS followed by `{insertion}`",
+                        output.name
+                    ));
+                }
+                let Some(section_id) =
+                    self.section_id_by_name(output.name.as_bytes())
+                        .filter(|id| {
+                            !is_header_section(*id)
+                                && self.section_flags(*id).contains(shf::ALLOC)
+                                && !sections.contains(id)
+                        })
+                else {
+                    continue;
+                };
+                if output.no_load {
+                    self.section_infos[section_id.as_usize()].ty = sht::NOBITS;
+                }
+                sections.push(section_id);
+            }
+            events
+                .retain(|event| !matches!(event, OrderEvent::Section(id) if sections.contains(id)));
+            let anchor = self.section_id_by_name(insertion.anchor.as_bytes());
+            let Some(position) = events
+                .iter()
+                .position(|event| matches!(event, OrderEvent::Section(id) if Some(*id) == anchor))
+            else {
+                bail!("`{insertion}` refers to a section that isn't in the output");
+            };
+            let position = position + usize::from(insertion.after);
+            events.splice(
+                position..position,
+                sections.into_iter().map(OrderEvent::Section),
+            );
+        }
+        self.sections_and_segments_events = events;
+        self.determine_loadable_segment_ids()
+    }
+
+    /// Creates program headers for a linker script that has `SECTIONS`, but not `PHDRS`. Each run
+    /// of sections with the same permissions goes in a loadable segment, with a new one started
+    /// wherever the script sets the address or memory region of a section, or where a section with
+    /// data in the file follows one without. Our other segments, e.g. `PT_TLS`, are
+    /// written as usual. As with GNU ld, our file header and program headers are only loaded if the
+    /// script leaves room for them before the first section, e.g. with `. = 0x10000 +
+    /// SIZEOF_HEADERS`. `keep_sections` says which sections we're outputting and `headers_size`
+    /// returns the size of our headers when there are the supplied number of program headers.
+    pub(crate) fn synthesize_program_headers(
+        &mut self,
+        keep_sections: &[bool],
+        active_segment_ids: &[ProgramSegmentId],
+        headers_size: impl Fn(usize) -> u64,
+    ) {
+        // The index of the loadable segment, amongst those that we're creating, that each
+        // allocated section goes in.
+        let mut assigned = vec![None; self.section_infos.len()];
+        let mut num_loads: usize = 0;
+        let mut current_permissions = None;
+        let mut waiting = Vec::new();
+        let mut starts_segment = false;
+        let mut first_section = None;
+        let mut current_regions = (None, None);
+        let mut follows_nobits = false;
+        for event in &self.sections_and_segments_events {
+            let OrderEvent::Section(section_id) = *event else {
+                continue;
+            };
+            let info = &self.section_infos[section_id.as_usize()];
+            if is_header_section(section_id) || !info.section_flags.contains(shf::ALLOC) {
+                continue;
+            }
+            first_section.get_or_insert(info);
+            starts_segment |= info.start_address.is_some()
+                || info
+                    .script_placement
+                    .as_ref()
+                    .is_some_and(SectionPlacement::is_fixed);
+            if let Some(placement) = &info.script_placement {
+                let regions = (placement.region, placement.load_region);
+                starts_segment |= regions.0 != current_regions.0
+                    || (regions.1.is_some() && regions != current_regions)
+                    || placement.load_address.is_some()
+                    || placement.shares_address;
+                current_regions = regions;
+            }
+            // Sections that we're not outputting go in the same segment as the next one that we
+            // are, so that they don't break up the segment that they're in.
+            waiting.push(section_id);
+            if !keep_sections[section_id.as_usize()] {
+                continue;
+            }
+            let permissions = (
+                info.section_flags.contains(shf::WRITE),
+                info.section_flags.contains(shf::EXECINSTR),
+            );
+            let is_nobits = info.ty == sht::NOBITS;
+            starts_segment |= follows_nobits && !is_nobits;
+            follows_nobits = is_nobits;
+            if starts_segment || current_permissions != Some(permissions) {
+                num_loads += 1;
+                current_permissions = Some(permissions);
+                starts_segment = false;
+            }
+            for id in waiting.drain(..) {
+                assigned[id.as_usize()] = Some(num_loads - 1);
+            }
+        }
+        for id in waiting {
+            assigned[id.as_usize()] = num_loads.checked_sub(1);
+        }
+
+        let has_interp = active_segment_ids.contains(&crate::program_segments::INTERP);
+        let other_segment_types = active_segment_ids
+            .iter()
+            .filter(|id| {
+                id.segment_type() != object::elf::PT_LOAD
+                    && **id != crate::program_segments::PHDR
+                    && **id != crate::program_segments::INTERP
+            })
+            .map(|id| id.segment_type())
+            .collect::<Vec<_>>();
+        let sizeof_headers =
+            headers_size(1 + usize::from(has_interp) + num_loads + other_segment_types.len());
+        let headers_fit = num_loads > 0
+            && first_section
+                .and_then(|info| info.script_placement.as_ref())
+                .and_then(|placement| {
+                    let location = placement
+                        .region
+                        .map_or(0, |region| self.memory_regions[region].origin);
+                    placement
+                        .address(
+                            alignment::MIN,
+                            &EvaluationContext {
+                                location,
+                                sizeof_headers,
+                                max_page_size: self.max_page_size.value(),
+                                common_page_size: self.common_page_size.value(),
+                                memory_regions: self.memory_regions,
+                                layout: None,
+                            },
+                        )
+                        .ok()
+                })
+                .and_then(|address| address.checked_sub(sizeof_headers))
+                .is_some_and(|start| self.max_page_size.align_down(start) == start);
+
+        let header = |segment_type| ProgramHeader {
+            name: String::new(),
+            segment_type,
+            includes_file_header: false,
+            includes_program_headers: false,
+            physical_address: None,
+            flags: None,
+        };
+        let mut headers = Vec::new();
+        if headers_fit {
+            headers.push(ProgramHeader {
+                includes_program_headers: true,
+                ..header(object::elf::PT_PHDR)
+            });
+        }
+        if has_interp {
+            headers.push(header(object::elf::PT_INTERP));
+        }
+        let first_load = headers.len();
+        headers.extend((0..num_loads).map(|_| header(object::elf::PT_LOAD)));
+        headers.extend(other_segment_types.into_iter().map(header));
+        for (index, header) in headers.iter_mut().enumerate() {
+            header.name = format!("#{index}");
+        }
+        if headers_fit {
+            headers[first_load].includes_file_header = true;
+            headers[first_load].includes_program_headers = true;
+        }
+
+        for (raw, (info, index)) in self.section_infos.iter_mut().zip(assigned).enumerate() {
+            let section_id = OutputSectionId::from_usize(raw);
+            info.script_program_headers = if !is_header_section(section_id) {
+                index.map(|index| first_load + index).into_iter().collect()
+            } else if !headers_fit {
+                Vec::new()
+            } else if section_id == PROGRAM_HEADERS {
+                vec![0, first_load]
+            } else {
+                vec![first_load]
+            };
+        }
+        self.script_program_headers = headers;
+    }
+
+    /// Returns the ID of the output section with the supplied name, if there is one.
+    pub(crate) fn section_id_by_name(&self, name: &[u8]) -> Option<OutputSectionId> {
+        self.custom_by_name
+            .get(&SectionName(name))
+            .copied()
+            .or_else(|| {
+                built_in_section_ids().find(|id| id.built_in_details().name.bytes() == name)
+            })
+    }
+
     pub(crate) fn name(&self, section_id: OutputSectionId) -> SectionName<'data> {
         self.output_info(section_id).name
     }
@@ -910,6 +1734,19 @@ impl<'data> OutputSections<'data> {
     }
 }
 
+/// Returns whether the supplied section is one of the headers that we write at the start of the
+/// file.
+pub(crate) fn is_header_section(section_id: OutputSectionId) -> bool {
+    section_id == FILE_HEADER || section_id == PROGRAM_HEADERS || section_id == SECTION_HEADERS
+}
+
+/// Returns the ID of the regular built-in section with the supplied name, if there is one.
+pub(crate) fn regular_built_in_by_name(name: &[u8]) -> Option<OutputSectionId> {
+    (0..NUM_BUILT_IN_REGULAR_SECTIONS as u32)
+        .map(OutputSectionId::regular)
+        .find(|id| id.built_in_details().name.bytes() == name)
+}
+
 pub(crate) fn link_ids(section_id: OutputSectionId) -> &'static [OutputSectionId] {
     SECTION_DEFINITIONS
         .get(section_id.as_usize())
@@ -936,6 +1773,10 @@ fn version_r_info(info: &InfoInputs) -> u32 {
     info.non_addressable_counts.verneed_count as u32
 }
 
+fn version_d_info(info: &InfoInputs) -> u32 {
+    info.non_addressable_counts.verdef_count as u32
+}
+
 fn dynsym_info(_info: &InfoInputs) -> u32 {
     // The only local we ever write to .dynsym is the null symbol, so this is unconditionally 1.
     1
@@ -992,6 +1833,12 @@ fn test_constant_ids() {
         (NOTE_ABI_TAG, NOTE_ABI_TAG_SECTION_NAME),
         (NOTE_GNU_PROPERTY, NOTE_GNU_PROPERTY_SECTION_NAME),
         (NOTE_GNU_BUILD_ID, NOTE_GNU_BUILD_ID_SECTION_NAME),
+        (MEMTAG_GLOBALS_DYNAMIC, MEMTAG_GLOBALS_DYNAMIC_SECTION_NAME),
+        (GNU_BUILD_ATTRIBUTES, GNU_BUILD_ATTRIBUTES_SECTION_NAME),
+        (PLT_SEC, PLT_SEC_SECTION_NAME),
+        (RISCV_ATTRIBUTES, RISCV_ATTRIBUTES_SECTION_NAME),
+        (GNU_DEBUGLINK, GNU_DEBUGLINK_SECTION_NAME),
+        (GNU_VERSION_D, GNU_VERSION_D_SECTION_NAME),
     ];
     for (id, name) in check {
         assert_eq!(id.built_in_details().name.bytes(), *name);
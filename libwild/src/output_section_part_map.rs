@@ -1,5 +1,6 @@
 use crate::alignment;
 use crate::alignment::Alignment;
+use crate::args::SortCommon;
 use crate::output_section_id::OrderEvent;
 use crate::output_section_id::OutputSectionId;
 use crate::output_section_id::OutputSections;
@@ -91,14 +92,28 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             let count = section_id.num_parts();
             let base_part_id = section_id.base_part_id();
             let max_alignment = self.max_alignment(base_part_id, count);
-            parts_out[base_part_id.as_usize()..base_part_id.as_usize() + count]
+            let parts = parts_out[base_part_id.as_usize()..base_part_id.as_usize() + count]
                 .iter_mut()
-                .enumerate()
-                .for_each(|(offset, out)| {
+                .enumerate();
+            if parts_are_reversed(section_id, output_sections) {
+                // Parts are ordered by descending alignment, so we visit them in reverse. The first
+                // part we visit needs the alignment of the whole section.
+                parts.rev().for_each(|(offset, out)| {
+                    let part_id = base_part_id.offset(offset);
+                    let alignment = if offset == count - 1 {
+                        max_alignment
+                    } else {
+                        part_id.alignment().min(max_alignment)
+                    };
+                    *out = cb(part_id, alignment, self.get(part_id));
+                });
+            } else {
+                parts.for_each(|(offset, out)| {
                     let part_id = base_part_id.offset(offset);
                     let alignment = part_id.alignment().min(max_alignment);
                     *out = cb(part_id, alignment, self.get(part_id));
                 });
+            }
         }
 
         OutputSectionPartMap { parts: parts_out }
@@ -338,6 +353,24 @@ fn test_output_order_map() {
     );
 }
 
+/// Returns whether the parts of the supplied section are output in reverse order.
+fn parts_are_reversed(section_id: OutputSectionId, output_sections: &OutputSections) -> bool {
+    section_id == crate::output_section_id::BSS
+        && output_sections.sort_common == SortCommon::Ascending
+}
+
+/// Returns the part of the supplied section that comes last in the output.
+pub(crate) fn last_output_part(
+    section_id: OutputSectionId,
+    output_sections: &OutputSections,
+) -> PartId {
+    if parts_are_reversed(section_id, output_sections) {
+        section_id.base_part_id()
+    } else {
+        section_id.base_part_id().offset(section_id.num_parts() - 1)
+    }
+}
+
 #[test]
 fn test_max_alignment() {
     use crate::output_section_id;
@@ -1,15 +1,22 @@
+use crate::arch::Architecture;
 use crate::archive_splitter::InputBytes;
 use crate::args::Args;
 use crate::args::Modifiers;
 use crate::args::OutputKind;
 use crate::args::RelocationModel;
 use crate::elf::File;
+use crate::elf::GLOBAL_POINTER_SYMBOL_NAME;
+use crate::elf::TOC_SYMBOL_NAME;
 use crate::error::Result;
 use crate::file_kind::FileKind;
 use crate::input_data::FileId;
 use crate::input_data::InputRef;
 use crate::input_data::PRELUDE_FILE_ID;
 use crate::input_data::UNINITIALISED_FILE_ID;
+use crate::linker_script::AssignmentPosition;
+use crate::linker_script::ScriptLayout;
+use crate::linker_script::SectionsCommand;
+use crate::linker_script::SymbolAssignment;
 use crate::output_section_id;
 use crate::output_section_id::OutputSectionId;
 use crate::sharding::ShardKey;
@@ -17,17 +24,22 @@ use crate::symbol::SymbolName;
 use crate::symbol_db::SymbolId;
 use crate::symbol_db::SymbolIdRange;
 use crate::threading::prelude::*;
+use anyhow::bail;
 use anyhow::Context;
+use itertools::Itertools as _;
+use object::read::elf::Sym as _;
+use std::collections::HashSet;
 use std::path::Path;
 
 #[tracing::instrument(skip_all, name = "Parse input files")]
 pub(crate) fn parse_input_files<'data>(
     inputs: &'data [InputBytes],
     args: &'data Args,
+    script_layout: &'data ScriptLayout,
 ) -> Result<Vec<ParsedInput<'data>>> {
     let mut objects = inputs
         .par_iter()
-        .map(|f| ParsedInput::new(f, args))
+        .map(|f| ParsedInput::new(f, args, script_layout))
         .collect::<Result<Vec<ParsedInput>>>()?;
 
     set_start_symbol_ids(&mut objects);
@@ -59,13 +71,25 @@ fn set_start_symbol_ids(objects: &mut [ParsedInput]) {
 // the two smaller variants, so it doesn't matter.
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum ParsedInput<'data> {
-    Prelude(Prelude),
+    Prelude(Prelude<'data>),
     Object(ParsedInputObject<'data>),
     Epilogue(Epilogue),
 }
 
-pub(crate) struct Prelude {
+pub(crate) struct Prelude<'data> {
     pub(crate) symbol_definitions: Vec<InternalSymDefInfo>,
+
+    /// The assignments that `InternalSymDefInfo::ScriptAssignment` refers to.
+    pub(crate) script_assignments: &'data [SymbolAssignment],
+
+    /// The commands from `SECTIONS` that the positions of `script_assignments` refer to.
+    script_sections: &'data [SectionsCommand],
+
+    /// The name of the symbol whose address is the entry point of an executable.
+    pub(crate) entry_symbol_name: &'data [u8],
+
+    /// Symbols that need to be loaded even if nothing references them, e.g. from `EXTERN`.
+    pub(crate) extern_symbols: &'data [String],
 }
 
 pub(crate) struct ParsedInputObject<'data> {
@@ -103,12 +127,37 @@ pub(crate) enum InternalSymDefInfo {
     /// Defines a symbol that points at the non-inclusive end of the section. i.e. 1 byte past the
     /// last byte of the section.
     SectionEnd(OutputSectionId),
+
+    /// Defines a symbol that points at the base address used by GOT-relative relocations. This is
+    /// `.TOC.` on PowerPC64, which is biased from the start of the GOT.
+    GotBase,
+
+    /// Defines `__global_pointer$` on RISC-V, which points a little way into .data so that the
+    /// start of .data can be accessed relative to gp.
+    GlobalPointer,
+
+    /// Defines a symbol that's assigned by a linker script. The index is into
+    /// `ScriptLayout::symbol_assignments` and is of the last assignment to the symbol, since that's
+    /// what determines its final value.
+    ScriptAssignment(u32),
 }
 
 impl<'data> ParsedInputObject<'data> {
-    fn new(input: &'data InputBytes, is_dynamic: bool) -> Result<Self> {
-        let object = File::parse(input.data, is_dynamic)
+    fn new(input: &'data InputBytes, is_dynamic: bool, arch: Architecture) -> Result<Self> {
+        let mut object = File::parse(input.data, is_dynamic)
             .with_context(|| format!("Failed to parse object file `{input}`"))?;
+        if input.modifiers.just_symbols {
+            object.keep_just_symbols();
+        }
+        let e_machine = object.e_machine();
+        let input_arch = Architecture::from_e_machine(e_machine);
+        if input_arch != Some(arch) {
+            let machine = input_arch.map_or_else(
+                || crate::file_kind::machine_name(e_machine, object::elf::ELFCLASS64),
+                |input_arch| input_arch.to_string(),
+            );
+            bail!("`{input}` is for {machine}, but we're linking for {arch}");
+        }
         let num_symbols = object.symbols.len();
         Ok(Self {
             input: input.input.clone(),
@@ -139,6 +188,22 @@ impl<'data> ParsedInputObject<'data> {
         &self.input.file.filename
     }
 
+    /// Returns the name that other shared objects would use to refer to this one in `DT_NEEDED`.
+    /// This is its `DT_SONAME` if it has one, otherwise the name of the file.
+    pub(crate) fn soname(&self) -> Result<&'data [u8]> {
+        Ok(self
+            .object
+            .dynamic_tag_strings(object::elf::DT_SONAME)?
+            .first()
+            .copied()
+            .unwrap_or_else(|| {
+                self.filename()
+                    .file_name()
+                    .unwrap_or_default()
+                    .as_encoded_bytes()
+            }))
+    }
+
     pub(crate) fn symbol_name(
         &self,
         symbol_id: crate::symbol_db::SymbolId,
@@ -147,16 +212,47 @@ impl<'data> ParsedInputObject<'data> {
         let symbol = self.object.symbol(index)?;
         Ok(SymbolName::new(self.object.symbol_name(symbol)?))
     }
+
+    pub(crate) fn symbol_st_other(&self, symbol_id: crate::symbol_db::SymbolId) -> Result<u8> {
+        let index = symbol_id.to_input(self.symbol_id_range);
+        Ok(self.object.symbol(index)?.st_other)
+    }
+
+    pub(crate) fn symbol_st_size(&self, symbol_id: crate::symbol_db::SymbolId) -> Result<u64> {
+        let index = symbol_id.to_input(self.symbol_id_range);
+        Ok(self.object.symbol(index)?.st_size(object::LittleEndian))
+    }
+
+    pub(crate) fn is_symbol_undefined(
+        &self,
+        symbol_id: crate::symbol_db::SymbolId,
+    ) -> Result<bool> {
+        let index = symbol_id.to_input(self.symbol_id_range);
+        Ok(self
+            .object
+            .symbol(index)?
+            .is_undefined(object::LittleEndian))
+    }
 }
 
 impl<'data> ParsedInput<'data> {
-    fn new(input: &'data InputBytes, args: &'data Args) -> Result<Self> {
+    fn new(
+        input: &'data InputBytes,
+        args: &'data Args,
+        script_layout: &'data ScriptLayout,
+    ) -> Result<Self> {
         Ok(match input.kind {
-            FileKind::ElfObject | FileKind::Archive => {
-                Self::Object(ParsedInputObject::new(input, false)?)
+            FileKind::ElfObject | FileKind::Archive | FileKind::ElfExecutable => {
+                Self::Object(ParsedInputObject::new(input, false, args.arch())?)
             }
-            FileKind::Prelude => Self::Prelude(Prelude::new(args)),
-            FileKind::ElfDynamic => Self::Object(ParsedInputObject::new(input, true)?),
+            FileKind::Prelude => Self::Prelude(Prelude::new(args, script_layout)),
+            // For shared objects named by `--just-symbols`, we want the full symbol table, not just
+            // the dynamic symbols.
+            FileKind::ElfDynamic => Self::Object(ParsedInputObject::new(
+                input,
+                !input.modifiers.just_symbols,
+                args.arch(),
+            )?),
             FileKind::Text => unreachable!("Should have been handled earlier"),
             FileKind::Epilogue => Self::Epilogue(Epilogue::new()),
         })
@@ -223,40 +319,70 @@ impl<'data> ParsedInput<'data> {
     }
 }
 
-impl Prelude {
-    fn new(args: &Args) -> Self {
+impl<'data> Prelude<'data> {
+    fn new(args: &Args, script_layout: &'data ScriptLayout) -> Self {
         // The undefined symbol must always be symbol 0.
         let mut symbol_definitions = vec![InternalSymDefInfo::Undefined];
-        for section_id in output_section_id::built_in_section_ids() {
-            // If we're not producing a relocatable output, then don't define any symbols for the
-            // .dynamic section.
-            if section_id == output_section_id::DYNAMIC && !args.is_relocatable() {
-                continue;
-            }
-            let def = section_id.built_in_details();
-            // .rela.plt start/stop symbols are only emitted for non-relocatable executables.
-            // Emitting them for relocatable binaries causes glibc to try to call the resolver
-            // functions without taking into account that the binary has been relocated.
-            if args.output_kind() != OutputKind::StaticExecutable(RelocationModel::NonRelocatable)
-                && section_id == output_section_id::RELA_PLT
-            {
-                continue;
-            }
-            if def.start_symbol_name(args.output_kind()).is_some() {
-                symbol_definitions.push(InternalSymDefInfo::SectionStart(section_id));
-            }
-            if def.end_symbol_name(args.output_kind()).is_some() {
-                symbol_definitions.push(InternalSymDefInfo::SectionEnd(section_id));
+        let script_assignments = script_layout.symbol_assignments.as_slice();
+        let entry_symbol_name = script_layout
+            .entry
+            .as_deref()
+            .unwrap_or("_start")
+            .as_bytes();
+        let extern_symbols = script_layout.extern_symbols.as_slice();
+        // Symbols like `_end` and `__bss_start` get defined by the final link, not by us.
+        if args.output_kind() != OutputKind::RelocatableObject {
+            add_built_in_symbol_definitions(args, &mut symbol_definitions);
+        }
+        // We define one symbol for each name that the script assigns to.
+        let mut seen = HashSet::new();
+        let mut last_assignments = script_assignments
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, assignment)| seen.insert(assignment.name.as_str()))
+            .map(|(index, _)| InternalSymDefInfo::ScriptAssignment(index as u32))
+            .collect_vec();
+        last_assignments.reverse();
+        symbol_definitions.extend(last_assignments);
+        Self {
+            symbol_definitions,
+            script_assignments,
+            script_sections: &script_layout.sections,
+            entry_symbol_name,
+            extern_symbols,
+        }
+    }
+
+    /// Returns the assignment that defines the supplied symbol, if it's defined by a linker script.
+    pub(crate) fn script_assignment(&self, symbol_id: SymbolId) -> Option<&'data SymbolAssignment> {
+        match self.symbol_definitions[symbol_id.as_usize()] {
+            InternalSymDefInfo::ScriptAssignment(index) => {
+                Some(&self.script_assignments[index as usize])
             }
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the output section that a linker script assigns to the supplied symbol
+    /// within, if any.
+    pub(crate) fn script_output_section(&self, symbol_id: SymbolId) -> Option<&'data str> {
+        let AssignmentPosition::InOutputSection { index, .. } =
+            self.script_assignment(symbol_id)?.position
+        else {
+            return None;
+        };
+        match &self.script_sections[index] {
+            SectionsCommand::OutputSection(output) => Some(&output.name),
+            SectionsCommand::SetLocation(_) => None,
         }
-        Self { symbol_definitions }
     }
 
     pub(crate) fn symbol_name(
         &self,
         symbol_id: SymbolId,
         output_kind: OutputKind,
-    ) -> SymbolName<'static> {
+    ) -> SymbolName<'data> {
         let def = &self.symbol_definitions[symbol_id.as_usize()];
         let name = match def {
             InternalSymDefInfo::Undefined => Some(""),
@@ -266,12 +392,53 @@ impl Prelude {
             InternalSymDefInfo::SectionEnd(section_id) => {
                 section_id.built_in_details().end_symbol_name(output_kind)
             }
+            InternalSymDefInfo::GotBase => Some(TOC_SYMBOL_NAME),
+            InternalSymDefInfo::GlobalPointer => Some(GLOBAL_POINTER_SYMBOL_NAME),
+            InternalSymDefInfo::ScriptAssignment(index) => {
+                Some(self.script_assignments[*index as usize].name.as_str())
+            }
         }
         .unwrap();
         SymbolName::new(name.as_bytes())
     }
 }
 
+/// Adds definitions for the symbols that we define ourselves, such as the start and end of built-in
+/// sections.
+fn add_built_in_symbol_definitions(args: &Args, symbol_definitions: &mut Vec<InternalSymDefInfo>) {
+    for section_id in output_section_id::built_in_section_ids() {
+        // If we're not producing a relocatable output, then don't define any symbols for the
+        // .dynamic section.
+        if section_id == output_section_id::DYNAMIC && !args.is_relocatable() {
+            continue;
+        }
+        let def = section_id.built_in_details();
+        // .rela.plt start/stop symbols are only emitted for non-relocatable executables.
+        // Emitting them for relocatable binaries causes glibc to try to call the resolver
+        // functions without taking into account that the binary has been relocated.
+        if args.output_kind() != OutputKind::StaticExecutable(RelocationModel::NonRelocatable)
+            && section_id == output_section_id::RELA_PLT
+        {
+            continue;
+        }
+        if def.start_symbol_name(args.output_kind()).is_some() {
+            symbol_definitions.push(InternalSymDefInfo::SectionStart(section_id));
+        }
+        if def.end_symbol_name(args.output_kind()).is_some() {
+            symbol_definitions.push(InternalSymDefInfo::SectionEnd(section_id));
+        }
+    }
+    if matches!(args.arch(), Architecture::PowerPc64) {
+        symbol_definitions.push(InternalSymDefInfo::GotBase);
+    }
+    // Shared objects use whatever gp was set to by the executable, so they don't get their own.
+    if matches!(args.arch(), Architecture::RiscV64)
+        && args.output_kind() != OutputKind::SharedObject
+    {
+        symbol_definitions.push(InternalSymDefInfo::GlobalPointer);
+    }
+}
+
 impl std::fmt::Display for ParsedInputObject<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::Display::fmt(&self.input, f)
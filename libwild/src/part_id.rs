@@ -1,6 +1,7 @@
 use crate::alignment::Alignment;
 use crate::alignment::NUM_ALIGNMENTS;
 use crate::args::Args;
+use crate::args::OutputKind;
 use crate::elf::SectionHeader;
 use crate::error::Result;
 use crate::output_section_id;
@@ -9,12 +10,15 @@ use crate::output_section_id::OutputSectionId;
 use crate::output_section_id::SectionName;
 use crate::output_section_id::FINI;
 use crate::output_section_id::INIT;
+use anyhow::bail;
 #[allow(clippy::wildcard_imports)]
 use linker_utils::elf::secnames::*;
 use linker_utils::elf::shf;
 use linker_utils::elf::sht;
 use linker_utils::elf::SectionFlags;
 use linker_utils::elf::SectionType;
+use object::read::elf::SectionHeader as _;
+use object::LittleEndian;
 use std::fmt::Debug;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -62,8 +66,14 @@ pub(crate) const GNU_VERSION: PartId = PartId(15);
 pub(crate) const GNU_VERSION_R: PartId = PartId(16);
 pub(crate) const NOTE_GNU_PROPERTY: PartId = PartId(17);
 pub(crate) const NOTE_GNU_BUILD_ID: PartId = PartId(18);
+pub(crate) const MEMTAG_GLOBALS_DYNAMIC: PartId = PartId(19);
+pub(crate) const GNU_BUILD_ATTRIBUTES: PartId = PartId(20);
+pub(crate) const PLT_SEC: PartId = PartId(21);
+pub(crate) const RISCV_ATTRIBUTES: PartId = PartId(22);
+pub(crate) const GNU_DEBUGLINK: PartId = PartId(23);
+pub(crate) const GNU_VERSION_D: PartId = PartId(24);
 
-pub(crate) const NUM_SINGLE_PART_SECTIONS: u32 = 19;
+pub(crate) const NUM_SINGLE_PART_SECTIONS: u32 = 25;
 
 // Generated sections that have more than one part. Fortunately they all have exactly 2 parts.
 pub(crate) const SYMTAB_LOCAL: PartId = PartId::multi(0);
@@ -94,11 +104,112 @@ impl<'data> UnresolvedSection<'data> {
         object: &crate::elf::File<'data>,
         section: &SectionHeader,
         args: &Args,
+    ) -> Result<Option<Self>> {
+        if args.output_kind() == OutputKind::RelocatableObject {
+            Self::for_relocatable_output(object, section, args)
+        } else {
+            Self::for_linked_output(object, section, args)
+        }
+    }
+
+    /// When producing a relocatable object, input sections keep their names so that the final link
+    /// can decide where to put them. Relocation sections are copied too, so that the relocations
+    /// can be applied by the final link.
+    fn for_relocatable_output(
+        object: &crate::elf::File<'data>,
+        section: &SectionHeader,
+        args: &Args,
+    ) -> Result<Option<Self>> {
+        let section_name = object.section_name(section)?;
+        let alignment = Alignment::new(object.section_alignment(section)?.max(1))?;
+        match SectionType::from_header(section) {
+            sht::RELA => {
+                let target =
+                    object.section(object::SectionIndex(section.sh_info(LittleEndian) as usize))?;
+                if Self::for_relocatable_output(object, target, args)?.is_none() {
+                    return Ok(None);
+                }
+                let target_name = object.section_name(target)?;
+                if section_name.strip_prefix(b".rela") != Some(target_name) {
+                    bail!(
+                        "Relocation section `{}` applies to `{}`, which isn't supported with -r",
+                        String::from_utf8_lossy(section_name),
+                        String::from_utf8_lossy(target_name)
+                    );
+                }
+                return Ok(Some(UnresolvedSection {
+                    part_id: TemporaryPartId::Custom(
+                        CustomSectionId {
+                            name: SectionName(section_name),
+                        },
+                        alignment,
+                    ),
+                    is_string_merge: false,
+                }));
+            }
+            // Section groups are copied so that the final link can discard duplicate COMDAT
+            // groups. Each one gets an output section of its own. See `relocatable_object`.
+            sht::GROUP => {
+                return Ok(Some(UnresolvedSection {
+                    part_id: TemporaryPartId::Custom(
+                        CustomSectionId {
+                            name: SectionName(section_name),
+                        },
+                        alignment,
+                    ),
+                    is_string_merge: false,
+                }));
+            }
+            // The address-significance table refers to symbols by index, which would change. It's
+            // only an optimisation hint, so we drop it.
+            sht::LLVM_ADDRSIG => return Ok(None),
+            _ => {}
+        }
+        let Some(mut unresolved) = Self::for_linked_output(object, section, args)? else {
+            return Ok(None);
+        };
+        // Sections that we build by combining the inputs keep their built-in section.
+        let is_combined = match unresolved.part_id {
+            TemporaryPartId::BuiltIn(id) => {
+                id == NOTE_GNU_PROPERTY
+                    || id == NOTE_GNU_BUILD_ID
+                    || id.output_section_id() == output_section_id::COMMENT
+            }
+            TemporaryPartId::Custom(..) | TemporaryPartId::EhFrameData => false,
+        };
+        if !is_combined && !section_name.is_empty() {
+            unresolved.part_id = TemporaryPartId::Custom(
+                CustomSectionId {
+                    name: SectionName(section_name),
+                },
+                alignment,
+            );
+        }
+        Ok(Some(unresolved))
+    }
+
+    fn for_linked_output(
+        object: &crate::elf::File<'data>,
+        section: &SectionHeader,
+        args: &Args,
     ) -> Result<Option<Self>> {
         // Ideally we support reading an actual linker script to make these decisions, but for now
         // we just hard code stuff.
         let section_name = object.section_name(section).unwrap_or_default();
+        if SectionType::from_header(section) == sht::LLVM_SYMPART {
+            // Partitions split a shared object into a main partition and separately loadable
+            // partitions. We don't support that, so rather than silently producing a single
+            // partition that doesn't match what the user asked for, we report an error.
+            bail!(
+                "Input contains partition section `{}`, but ELF partitions are not supported",
+                String::from_utf8_lossy(section_name)
+            );
+        }
         let section_flags = SectionFlags::from_header(section);
+        if args.strip_section_headers && !section_flags.contains(shf::ALLOC) {
+            // Without section headers, nothing would be able to find this section.
+            return Ok(None);
+        }
         let alignment = Alignment::new(object.section_alignment(section)?.max(1))?;
         let built_in_section_id = if section_name.starts_with(RODATA_SECTION_NAME) {
             Some(output_section_id::RODATA)
@@ -127,7 +238,12 @@ impl<'data> UnresolvedSection<'data> {
         } else if section_name.starts_with(TBSS_SECTION_NAME) {
             Some(output_section_id::TBSS)
         } else if section_name == COMMENT_SECTION_NAME {
-            Some(output_section_id::COMMENT)
+            return Ok(Some(UnresolvedSection {
+                part_id: TemporaryPartId::BuiltIn(
+                    output_section_id::COMMENT.part_id_with_alignment(alignment),
+                ),
+                is_string_merge: should_merge_comment(object, section)?,
+            }));
         } else if section_name == EH_FRAME_SECTION_NAME {
             return Ok(Some(UnresolvedSection {
                 part_id: TemporaryPartId::EhFrameData,
@@ -140,19 +256,23 @@ impl<'data> UnresolvedSection<'data> {
         } else if section_name == NOTE_GNU_BUILD_ID_SECTION_NAME {
             Some(output_section_id::NOTE_GNU_BUILD_ID)
         } else if section_name.starts_with(b".rela")
+            || SectionType::from_header(section) == sht::REL
             || STRTAB_SECTION_NAME == section_name
             || SYMTAB_SECTION_NAME == section_name
             || SHSTRTAB_SECTION_NAME == section_name
             || GROUP_SECTION_NAME == section_name
+            || SectionType::from_header(section) == sht::AARCH64_MEMTAG_GLOBALS_STATIC
+            || section_name == GNU_BUILD_ATTRIBUTES_SECTION_NAME
+            || section_name == RISCV_ATTRIBUTES_SECTION_NAME
+            || section_name == GNU_DEBUGLINK_SECTION_NAME
+            || crate::gnu_warning::is_warning_section(section_name)
         {
             // We don't currently allow references to these sections, discard them so that we avoid
             // allocating output section IDs.
             None
-        } else if args.strip_debug
-            && section_name.starts_with(b".debug_")
-            && !section_flags.contains(shf::ALLOC)
-        {
-            // Drop soon string merge debug info section.
+        } else if args.strip_debug && is_debug_section(section_name, section_flags) {
+            // Dropping debug info here means that we never read it, which for large outputs is
+            // most of the work that stripping would otherwise save.
             None
         } else if section_name == NOTE_GNU_PROPERTY_SECTION_NAME {
             return Ok(Some(UnresolvedSection {
@@ -215,6 +335,25 @@ impl<'data> UnresolvedSection<'data> {
     }
 }
 
+/// Returns whether we should merge the strings in a .comment section. Each input object generally
+/// has the same compiler version string, so not merging them makes the section grow with the number
+/// of inputs. Nothing refers to the contents of .comment, so unlike other sections, we merge it
+/// even without the merge flags, or when string merging was disabled, provided it actually
+/// contains null-terminated strings.
+fn should_merge_comment(object: &crate::elf::File, section: &SectionHeader) -> Result<bool> {
+    let section_flags = SectionFlags::from_header(section);
+    if section_flags.contains(shf::ALLOC)
+        || object.section_alignment(section)? > 1
+        || section.compression(LittleEndian, object.data)?.is_some()
+    {
+        return Ok(false);
+    }
+    Ok(object
+        .raw_section_data(section)?
+        .last()
+        .is_some_and(|&b| b == 0))
+}
+
 /// Returns whether the supplied section meets our criteria for string merging. String merging is
 /// optional, so there are cases where we might be able to merge, but don't currently. For example
 /// if alignment is > 1.
@@ -303,6 +442,18 @@ impl<'data> TemporaryPartId<'data> {
     }
 }
 
+/// Returns whether the section with the supplied name and flags contains debug info and should
+/// therefore be dropped by `--strip-debug`. As well as DWARF, this covers compressed DWARF from older
+/// toolchains and stabs.
+fn is_debug_section(section_name: &[u8], section_flags: SectionFlags) -> bool {
+    !section_flags.contains(shf::ALLOC)
+        && (section_name.starts_with(b".debug_")
+            || section_name.starts_with(b".zdebug_")
+            || section_name.starts_with(b".stab")
+            || section_name == b".debug"
+            || section_name == b".line")
+}
+
 #[cfg(test)]
 pub(crate) fn built_in_part_ids(
 ) -> impl ExactSizeIterator<Item = PartId> + DoubleEndedIterator<Item = PartId> {
@@ -348,4 +499,15 @@ mod tests {
             assert_eq!(part_id, part_id2);
         }
     }
+    #[test]
+    fn test_is_debug_section() {
+        let non_alloc = SectionFlags::empty();
+        assert!(is_debug_section(b".debug_info", non_alloc));
+        assert!(is_debug_section(b".zdebug_line", non_alloc));
+        assert!(is_debug_section(b".stabstr", non_alloc));
+        assert!(is_debug_section(b".line", non_alloc));
+        assert!(!is_debug_section(b".debug_info", shf::ALLOC));
+        assert!(!is_debug_section(b".debugger_data", non_alloc));
+        assert!(!is_debug_section(b".comment", non_alloc));
+    }
 }
@@ -0,0 +1,281 @@
+use crate::arch::PltFeatures;
+use crate::elf::PPC64_PLT_ENTRY_SIZE;
+use crate::resolution::ValueFlags;
+use crate::thunks::ThunkInfo;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+use linker_utils::elf::ppc64_rel_type_to_string;
+use linker_utils::elf::shf;
+use linker_utils::elf::DynamicRelocationKind;
+use linker_utils::elf::RelocationKindInfo;
+use linker_utils::ppc64::direct_call_relocation;
+use linker_utils::ppc64::relocation_type_from_raw;
+use linker_utils::ppc64::PowerPcInstruction;
+use linker_utils::ppc64::RelaxationKind;
+use linker_utils::relaxation::RelocationModifier;
+
+pub(crate) struct PowerPc64;
+
+/// A PLT call stub. Callers expect r2 to be restored when we return, so the stub saves it in the
+/// caller's stack frame and the nop after the call gets replaced with a load of the saved value.
+/// The callee gets entered via its global entry point with its address in r12, so that it can
+/// compute its own TOC pointer.
+const PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0x18, 0x00, 0x41, 0xf8, // std r2, 24(r1)
+    0x00, 0x00, 0x82, 0x3d, // addis r12, r2, ha(&(.got[n]) - .TOC.)
+    0x00, 0x00, 0x8c, 0xe9, // ld r12, lo(&(.got[n]) - .TOC.)(r12)
+    0xa6, 0x03, 0x89, 0x7d, // mtctr r12
+    0x20, 0x04, 0x80, 0x4e, // bctr
+    0x00, 0x00, 0x00, 0x60, // nop
+    0x00, 0x00, 0x00, 0x60, // nop
+    0x00, 0x00, 0x00, 0x60, // nop
+];
+
+/// A long-branch stub, used when a `bl` or `b` can't reach its target. The target is computed
+/// relative to the stub's own address, so unlike a PLT stub, this doesn't need the TOC pointer and
+/// leaves r2 alone. r11 and r12 are volatile across calls, so are free for us to use.
+const THUNK_TEMPLATE: &[u8] = &[
+    0xa6, 0x02, 0x88, 0x7d, // mflr r12
+    0x05, 0x00, 0x9f, 0x42, // bcl 20, 31, 1f
+    0xa6, 0x02, 0x68, 0x7d, // 1: mflr r11
+    0xa6, 0x03, 0x88, 0x7d, // mtlr r12
+    0x00, 0x00, 0x8b, 0x3d, // addis r12, r11, ha(target - 1b)
+    0x00, 0x00, 0x8c, 0x39, // addi r12, r12, lo(target - 1b)
+    0xa6, 0x03, 0x89, 0x7d, // mtctr r12
+    0x20, 0x04, 0x80, 0x4e, // bctr
+];
+
+/// The offset within the thunk of the instruction whose address ends up in r11.
+const THUNK_BASE_OFFSET: u64 = 8;
+
+/// How far `b` and `bl` can reach in either direction, less a little to allow for branches to a
+/// function's local entry point, which can be up to 64 bytes after the symbol that layout checks.
+const BRANCH_RANGE: u64 = (1 << 25) - 64;
+
+/// How far `bc` can reach in either direction. We don't redirect these via thunks.
+const CONDITIONAL_BRANCH_RANGE: u64 = 1 << 15;
+
+const _ASSERTS: () = {
+    assert!(PLT_ENTRY_TEMPLATE.len() as u64 == PPC64_PLT_ENTRY_SIZE);
+};
+
+/// Version 2 of the ABI, which is the only one used on little-endian.
+const EF_PPC64_ABI_V2: u32 = 2;
+
+impl crate::arch::Arch for PowerPc64 {
+    type Relaxation = Relaxation;
+
+    fn elf_header_arch_magic() -> u16 {
+        object::elf::EM_PPC64
+    }
+
+    // The table of the relocations is documented in the "Relocation Types Table" section of the
+    // 64-Bit ELF V2 ABI Specification.
+    fn relocation_from_raw(r_type: u32) -> Result<RelocationKindInfo> {
+        relocation_type_from_raw(r_type).ok_or_else(|| {
+            anyhow!(
+                "Unsupported relocation type {}",
+                Self::rel_type_to_string(r_type)
+            )
+        })
+    }
+
+    fn get_dynamic_relocation_type(relocation: DynamicRelocationKind) -> u32 {
+        relocation.ppc64_r_type()
+    }
+
+    fn rel_type_to_string(r_type: u32) -> std::borrow::Cow<'static, str> {
+        ppc64_rel_type_to_string(r_type)
+    }
+
+    fn merge_e_flags(input_flags: impl Iterator<Item = u32>) -> crate::error::Result<u32> {
+        for flags in input_flags {
+            // Objects that don't contain any functions may leave the ABI version unspecified.
+            let abi = flags & object::elf::EF_PPC64_ABI;
+            if abi != 0 && abi != EF_PPC64_ABI_V2 {
+                bail!("Cannot link objects using version {abi} of the PowerPC64 ELF ABI");
+            }
+        }
+        Ok(EF_PPC64_ABI_V2)
+    }
+
+    fn got_relocation_range(r_type: u32) -> Option<std::ops::Range<i64>> {
+        // The small code model accesses GOT entries with a 16 bit offset from the TOC pointer. The
+        // medium and large code models use the _HA and _LO forms, which can reach much further.
+        match r_type {
+            object::elf::R_PPC64_GOT16
+            | object::elf::R_PPC64_GOT16_DS
+            | object::elf::R_PPC64_GOT_TPREL16_DS
+            | object::elf::R_PPC64_GOT_TLSGD16
+            | object::elf::R_PPC64_GOT_TLSLD16 => Some(-0x8000..0x8000),
+            _ => None,
+        }
+    }
+
+    fn write_plt_entry(
+        plt_entry: &mut [u8],
+        got_address: u64,
+        _plt_address: u64,
+        got_base: u64,
+        _features: PltFeatures,
+    ) -> crate::error::Result {
+        plt_entry.copy_from_slice(PLT_ENTRY_TEMPLATE);
+        let offset = got_address.wrapping_sub(got_base);
+        anyhow::ensure!(
+            (offset as i64).unsigned_abs() < (1 << 31),
+            "GOT entry is more than 2GiB away from the TOC pointer"
+        );
+        PowerPcInstruction::Ha16.write_to_value(offset, &mut plt_entry[4..8]);
+        PowerPcInstruction::Lo16Ds.write_to_value(offset, &mut plt_entry[8..12]);
+        Ok(())
+    }
+
+    fn thunk_info() -> Option<ThunkInfo> {
+        Some(ThunkInfo {
+            size: THUNK_TEMPLATE.len() as u64,
+            min_branch_range: BRANCH_RANGE,
+        })
+    }
+
+    fn branch_range(r_type: u32) -> Option<u64> {
+        match r_type {
+            object::elf::R_PPC64_REL24 => Some(BRANCH_RANGE),
+            _ => None,
+        }
+    }
+
+    fn fixed_branch_range(r_type: u32) -> Option<u64> {
+        match r_type {
+            object::elf::R_PPC64_REL14
+            | object::elf::R_PPC64_REL14_BRTAKEN
+            | object::elf::R_PPC64_REL14_BRNTAKEN => Some(CONDITIONAL_BRANCH_RANGE),
+            _ => None,
+        }
+    }
+
+    fn write_thunk(
+        thunk: &mut [u8],
+        thunk_address: u64,
+        target_address: u64,
+    ) -> crate::error::Result {
+        thunk.copy_from_slice(THUNK_TEMPLATE);
+        let offset = target_address.wrapping_sub(thunk_address + THUNK_BASE_OFFSET);
+        anyhow::ensure!(
+            (offset as i64).unsigned_abs() < (1 << 31),
+            "Branch target is more than 2GiB away from its thunk"
+        );
+        PowerPcInstruction::Ha16.write_to_value(offset, &mut thunk[16..20]);
+        PowerPcInstruction::Lo16.write_to_value(offset, &mut thunk[20..24]);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Relaxation {
+    kind: RelaxationKind,
+    rel_info: RelocationKindInfo,
+}
+
+impl crate::arch::Relaxation for Relaxation {
+    fn new(
+        relocation_kind: u32,
+        _section_bytes: &[u8],
+        _offset_in_section: u64,
+        value_flags: crate::resolution::ValueFlags,
+        _output_kind: crate::args::OutputKind,
+        section_flags: linker_utils::elf::SectionFlags,
+    ) -> Option<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        // IFuncs cannot be referenced directly, they always need to go via the GOT.
+        if value_flags.contains(ValueFlags::IFUNC) {
+            return None;
+        }
+
+        // All relaxations below only apply to executable code, so we shouldn't attempt them if a
+        // relocation is in a non-executable section.
+        if !section_flags.contains(shf::EXECINSTR) {
+            return None;
+        }
+
+        // A call to a function in the same output shares our TOC, so can branch directly to the
+        // function's local entry point without going via a call stub.
+        if relocation_kind == object::elf::R_PPC64_REL24
+            && value_flags.contains(ValueFlags::CAN_BYPASS_GOT)
+        {
+            return Some(Relaxation {
+                kind: RelaxationKind::NoOp,
+                rel_info: direct_call_relocation(),
+            });
+        }
+
+        None
+    }
+
+    fn apply(&self, section_bytes: &mut [u8], offset_in_section: &mut u64, addend: &mut i64) {
+        self.kind.apply(section_bytes, offset_in_section, addend);
+    }
+
+    fn rel_info(&self) -> RelocationKindInfo {
+        self.rel_info
+    }
+
+    fn debug_kind(&self) -> &dyn std::fmt::Debug {
+        &self.kind
+    }
+
+    fn next_modifier(&self) -> RelocationModifier {
+        self.kind.next_modifier()
+    }
+}
+
+#[test]
+fn test_call_relaxation() {
+    use crate::arch::Relaxation as _;
+    use crate::args::OutputKind;
+    use crate::args::RelocationModel;
+    use linker_utils::elf::RelocationKind;
+
+    let relax = |value_flags| {
+        Relaxation::new(
+            object::elf::R_PPC64_REL24,
+            &[],
+            0,
+            value_flags,
+            OutputKind::StaticExecutable(RelocationModel::Relocatable),
+            shf::EXECINSTR,
+        )
+    };
+
+    let r = relax(ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT).unwrap();
+    assert_eq!(r.rel_info().kind, RelocationKind::RelativeLocalEntry);
+    assert!(relax(ValueFlags::ADDRESS | ValueFlags::DYNAMIC).is_none());
+    assert!(relax(ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT | ValueFlags::IFUNC).is_none());
+}
+
+#[test]
+fn test_write_thunk() {
+    use crate::arch::Arch as _;
+
+    let mut thunk = [0; 32];
+    PowerPc64::write_thunk(&mut thunk, 0x1000_0000, 0x1234_5678).unwrap();
+    // addis r12, r11, 0x234; addi r12, r12, 0x5670
+    assert_eq!(
+        &thunk[16..24],
+        [0x34, 0x02, 0x8b, 0x3d, 0x70, 0x56, 0x8c, 0x39]
+    );
+    assert_eq!(&thunk[..16], &THUNK_TEMPLATE[..16]);
+    assert_eq!(&thunk[24..], &THUNK_TEMPLATE[24..]);
+
+    // Backwards. The low half is negative when sign-extended, so the high half gets rounded up.
+    // addis r12, r11, -0x234; addi r12, r12, -0x5680
+    PowerPc64::write_thunk(&mut thunk, 0x1234_5678, 0x1000_0000).unwrap();
+    assert_eq!(
+        &thunk[16..24],
+        [0xcc, 0xfd, 0x8b, 0x3d, 0x80, 0xa9, 0x8c, 0x39]
+    );
+
+    assert!(PowerPc64::write_thunk(&mut thunk, 0, 1 << 32).is_err());
+}
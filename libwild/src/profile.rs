@@ -0,0 +1,158 @@
+//! Support for profile-guided layout from a raw profile, passed via `--profile=/path/to/profile`.
+//! This is for users who don't have compiler-emitted call-graph-profile sections, but do have a
+//! profile collected with perf.
+//!
+//! Two formats are accepted and can be mixed within a single file:
+//!
+//! - The output of `perf script`. We count the leaf frame of each sample. LBR branch records
+//!   as printed by `perf script -F brstacksym` (`from+0x1/to+0x0/P/-/-/0`) count towards both the
+//!   source and the target of each branch. Pass `--no-demangle` to perf, since we match symbols by
+//!   their mangled names.
+//! - An edge profile where each line has the form `caller callee count`.
+//!
+//! The profile gives us a weight for each function. Input sections that contain a function with a
+//! non-zero weight are placed together in a `.text.hot` output section rather than being spread
+//! throughout `.text`, which keeps the code that actually runs on as few pages as possible.
+
+use crate::elf::File;
+use crate::error::Result;
+use anyhow::Context as _;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub(crate) const HOT_TEXT_SECTION_NAME: &[u8] = b".text.hot";
+
+pub(crate) struct Profile {
+    weights: HashMap<Vec<u8>, u64>,
+}
+
+impl Profile {
+    pub(crate) fn from_file(path: &Path) -> Result<Profile> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read profile `{}`", path.display()))?;
+        Ok(Profile::parse(&text))
+    }
+
+    fn parse(text: &str) -> Profile {
+        let mut profile = Profile {
+            weights: HashMap::new(),
+        };
+        // Whether we're waiting for the first frame of a perf sample's call chain.
+        let mut expecting_leaf = false;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                expecting_leaf = false;
+                continue;
+            }
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+
+            if let [caller, callee, count] = tokens[..] {
+                if let Ok(count) = count.parse::<u64>() {
+                    profile.add(caller, count);
+                    profile.add(callee, count);
+                    continue;
+                }
+            }
+
+            let mut had_branches = false;
+            for token in &tokens {
+                // Skip DSO paths, which are parenthesised.
+                if token.starts_with('(') {
+                    continue;
+                }
+                let parts = token.split('/').collect::<Vec<_>>();
+                if let [from, to, _flags, _, ..] = parts[..] {
+                    profile.add(from, 1);
+                    profile.add(to, 1);
+                    had_branches = true;
+                }
+            }
+            if had_branches {
+                continue;
+            }
+
+            // A frame ends with `<address> <symbol>+<offset> (<dso>)`.
+            let n = tokens.len();
+            let is_frame = n >= 3
+                && tokens[n - 1].starts_with('(')
+                && u64::from_str_radix(tokens[n - 3], 16).is_ok();
+            if !is_frame {
+                // A sample header whose call chain follows on subsequent lines.
+                expecting_leaf = true;
+                continue;
+            }
+            if n > 3 {
+                // A sample without a call chain, all on one line.
+                profile.add(tokens[n - 2], 1);
+            } else if expecting_leaf {
+                profile.add(tokens[n - 2], 1);
+                expecting_leaf = false;
+            }
+        }
+        profile
+    }
+
+    fn add(&mut self, symbol: &str, weight: u64) {
+        let name = symbol.split_once("+0x").map_or(symbol, |(name, _)| name);
+        // Unsymbolised addresses start with a digit, which symbol names can't.
+        if name.is_empty() || name == "[unknown]" || name.starts_with(|c: char| c.is_ascii_digit())
+        {
+            return;
+        }
+        *self.weights.entry(name.as_bytes().to_owned()).or_default() += weight;
+    }
+
+    /// Returns, for each section in `object`, whether it contains a function that was seen in the
+    /// profile.
+    pub(crate) fn hot_sections(&self, object: &File) -> Result<Vec<bool>> {
+        let mut hot = vec![false; object.sections.len()];
+        for (sym_index, sym) in object.symbols.enumerate() {
+            if sym.st_type() != object::elf::STT_FUNC || sym.is_undefined(LittleEndian) {
+                continue;
+            }
+            let name = object.symbol_name(sym)?;
+            if self.weights.get(name).is_none_or(|w| *w == 0) {
+                continue;
+            }
+            if let Some(section_index) = object.symbol_section(sym, sym_index)? {
+                hot[section_index.0] = true;
+            }
+        }
+        Ok(hot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        let profile = Profile::parse(
+            "\
+# An edge profile
+main helper 10
+
+perf 123 [000] 1.000: 1000 cycles:u:
+\t401136 leaf+0x6 (/tmp/a.out)
+\t401200 caller+0x10 (/tmp/a.out)
+
+perf 123 [000] 1.001: 1000 cycles:u:  401300 single+0x2 (/tmp/a.out)
+ 401400 [unknown] (/tmp/a.out)
+branch+0x4/target+0x0/P/-/-/0  4011aa/4011bb/P/-/-/0
+",
+        );
+        let weight = |name: &str| profile.weights.get(name.as_bytes()).copied();
+        assert_eq!(weight("main"), Some(10));
+        assert_eq!(weight("helper"), Some(10));
+        assert_eq!(weight("leaf"), Some(1));
+        assert_eq!(weight("caller"), None);
+        assert_eq!(weight("single"), Some(1));
+        assert_eq!(weight("branch"), Some(1));
+        assert_eq!(weight("target"), Some(1));
+        assert_eq!(profile.weights.len(), 6);
+    }
+}
@@ -13,6 +13,8 @@ pub(crate) const TLS: ProgramSegmentId = ProgramSegmentId(6);
 pub(crate) const EH_FRAME: ProgramSegmentId = ProgramSegmentId(7);
 pub(crate) const DYNAMIC: ProgramSegmentId = ProgramSegmentId(8);
 pub(crate) const STACK: ProgramSegmentId = ProgramSegmentId(9);
+pub(crate) const GNU_PROPERTY: ProgramSegmentId = ProgramSegmentId(10);
+pub(crate) const GNU_RELRO: ProgramSegmentId = ProgramSegmentId(11);
 
 pub(crate) struct ProgramSegmentDef {
     pub(crate) segment_type: u32,
@@ -60,6 +62,14 @@ const PROGRAM_SEGMENT_DEFS: &[ProgramSegmentDef] = &[
         segment_type: object::elf::PT_GNU_STACK,
         segment_flags: object::elf::PF_R | object::elf::PF_W,
     },
+    ProgramSegmentDef {
+        segment_type: object::elf::PT_GNU_PROPERTY,
+        segment_flags: object::elf::PF_R,
+    },
+    ProgramSegmentDef {
+        segment_type: object::elf::PT_GNU_RELRO,
+        segment_flags: object::elf::PF_R,
+    },
 ];
 
 impl ProgramSegmentId {
@@ -83,9 +93,13 @@ impl ProgramSegmentId {
         )
     }
 
-    pub(crate) fn alignment(self) -> crate::alignment::Alignment {
+    /// Returns the alignment of this segment when pages are of size `page_alignment`.
+    pub(crate) fn alignment(
+        self,
+        page_alignment: crate::alignment::Alignment,
+    ) -> crate::alignment::Alignment {
         if self.segment_type() == object::elf::PT_LOAD {
-            crate::alignment::PAGE
+            page_alignment
         } else {
             crate::alignment::MIN
         }
@@ -163,4 +177,8 @@ fn test_constant_segment_ids() {
         PROGRAM_SEGMENT_DEFS[NOTE.as_usize()].segment_type,
         object::elf::PT_NOTE
     );
+    assert_eq!(
+        PROGRAM_SEGMENT_DEFS[GNU_PROPERTY.as_usize()].segment_type,
+        object::elf::PT_GNU_PROPERTY
+    );
 }
@@ -0,0 +1,170 @@
+//! Optionally prints how many relocations we relaxed and how many we could potentially have
+//! relaxed, but didn't. To use this, pass `--print-relaxations`.
+//!
+//! Applied relaxations are grouped by relocation type and the kind of relaxation performed.
+//! Relocations that go via the GOT or PLT, or that use a TLS access model other than local-exec,
+//! but which we left alone, are grouped by relocation type and the reason we couldn't relax them.
+//! This is intended to help check that GOT-bypass and TLS optimisations are firing as expected.
+
+use crate::arch::Architecture;
+use crate::args::Args;
+use crate::got_usage::got_or_plt_flags;
+use crate::got_usage::GotUsageReason;
+use crate::resolution::ValueFlags;
+use itertools::Itertools;
+use linker_utils::elf::RelocationKind;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+#[derive(Default)]
+pub(crate) struct RelaxationStats {
+    /// Counts keyed by relocation type and the kind of relaxation applied.
+    applied: HashMap<(u32, String), u64>,
+
+    /// Counts keyed by relocation type and the reason that we didn't relax.
+    skipped: HashMap<(u32, SkipReason), u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum SkipReason {
+    /// Relaxations of this kind were disabled, e.g. by `--no-relax-tls`.
+    Disabled,
+
+    /// TLS accesses in a shared object can't be relaxed to local-exec.
+    SharedObjectTls,
+
+    Other(GotUsageReason),
+}
+
+impl RelaxationStats {
+    pub(crate) fn record_applied(&mut self, r_type: u32, relaxation_kind: impl std::fmt::Debug) {
+        *self
+            .applied
+            .entry((r_type, format!("{relaxation_kind:?}")))
+            .or_default() += 1;
+    }
+
+    /// Records that we didn't relax a relocation of type `r_type`, provided it's one that it might
+    /// have been possible to relax.
+    pub(crate) fn record_not_applied(
+        &mut self,
+        r_type: u32,
+        value_flags: ValueFlags,
+        args: &Args,
+    ) -> crate::error::Result {
+        let kind = args.arch().relocation_from_raw(r_type)?.kind;
+        if !is_relaxation_candidate(kind) {
+            return Ok(());
+        }
+        let reason = if !args.relax.allows(kind) {
+            SkipReason::Disabled
+        } else if !args.output_kind().is_executable() && is_tls_model_with_local_exec_form(kind) {
+            SkipReason::SharedObjectTls
+        } else {
+            // We only get here for relocations that might have been relaxed, so even those that
+            // usually need a GOT entry, such as TLS relocations, count as not relaxable.
+            match GotUsageReason::new(value_flags, kind) {
+                GotUsageReason::Required => SkipReason::Other(GotUsageReason::NotRelaxable),
+                reason => SkipReason::Other(reason),
+            }
+        };
+        *self.skipped.entry((r_type, reason)).or_default() += 1;
+        Ok(())
+    }
+
+    pub(crate) fn merge(&mut self, other: RelaxationStats) {
+        for (key, count) in other.applied {
+            *self.applied.entry(key).or_default() += count;
+        }
+        for (key, count) in other.skipped {
+            *self.skipped.entry(key).or_default() += count;
+        }
+    }
+
+    pub(crate) fn print(&self, arch: Architecture) {
+        println!("Relaxations applied:");
+        for ((r_type, kind), count) in sorted_by_count(&self.applied) {
+            println!("  {count}x {} -> {kind}", arch.rel_type_to_string(*r_type));
+        }
+        println!("Relaxations skipped:");
+        for ((r_type, reason), count) in sorted_by_count(&self.skipped) {
+            println!("  {count}x {}: {reason}", arch.rel_type_to_string(*r_type));
+        }
+        println!(
+            "Total relaxations applied: {}",
+            self.applied.values().sum::<u64>()
+        );
+        println!(
+            "Total relaxations skipped: {}",
+            self.skipped.values().sum::<u64>()
+        );
+    }
+}
+
+fn sorted_by_count<K: Ord>(counts: &HashMap<K, u64>) -> impl Iterator<Item = (&K, &u64)> {
+    counts
+        .iter()
+        .sorted_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)))
+}
+
+/// Returns whether relocations of `kind` go via the GOT or PLT or use a TLS access model that we
+/// might be able to relax.
+fn is_relaxation_candidate(kind: RelocationKind) -> bool {
+    crate::layout::resolution_flags(kind).intersects(got_or_plt_flags())
+        || matches!(
+            kind,
+            RelocationKind::TlsLd | RelocationKind::TlsLdGot | RelocationKind::TlsLdGotBase
+        )
+}
+
+/// Returns whether `kind` is a TLS access that would only be relaxed if we were producing an
+/// executable.
+fn is_tls_model_with_local_exec_form(kind: RelocationKind) -> bool {
+    matches!(
+        kind,
+        RelocationKind::TlsGd
+            | RelocationKind::TlsGdGot
+            | RelocationKind::TlsGdGotBase
+            | RelocationKind::TlsLd
+            | RelocationKind::TlsLdGot
+            | RelocationKind::TlsLdGotBase
+            | RelocationKind::GotTpOff
+            | RelocationKind::GotTpOffGot
+            | RelocationKind::GotTpOffGotBase
+            | RelocationKind::TlsDesc
+            | RelocationKind::TlsDescGot
+            | RelocationKind::TlsDescGotBase
+            | RelocationKind::TlsDescCall
+    )
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::Disabled => Display::fmt("relaxation disabled by --no-relax option", f),
+            SkipReason::SharedObjectTls => {
+                Display::fmt("TLS access can't be relaxed in a shared object", f)
+            }
+            SkipReason::Other(reason) => Display::fmt(reason, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_and_sort() {
+        let mut a = RelaxationStats::default();
+        a.record_applied(1, "Foo");
+        a.record_applied(2, "Bar");
+        let mut b = RelaxationStats::default();
+        b.record_applied(2, "Bar");
+        a.merge(b);
+        let sorted = sorted_by_count(&a.applied)
+            .map(|((r_type, kind), count)| (*r_type, kind.as_str(), *count))
+            .collect_vec();
+        assert_eq!(sorted, [(2, "\"Bar\"", 2), (1, "\"Foo\"", 1)]);
+    }
+}
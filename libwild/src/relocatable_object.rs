@@ -0,0 +1,490 @@
+//! Support for producing a relocatable object (`-r`) rather than an executable or shared object.
+//! Input sections with the same name are concatenated, but instead of applying relocations, we
+//! copy them into the output so that they can be applied by a later link. Each relocation needs to
+//! be rewritten so that its offset is relative to the output section and so that it refers to the
+//! index of its symbol in our symbol table rather than in that of the input object.
+//!
+//! Where a relocation refers to a symbol that doesn't get a symbol table entry, e.g. a section
+//! symbol, we instead refer to the symbol for the output section and adjust the addend to
+//! compensate. We emit one section symbol for each output section, starting straight after the
+//! null symbol, so the symbol index of an output section is the same as its section index.
+//!
+//! With `--emit-relocs`, we do the same when linking an executable or shared object, so that
+//! post-link optimisers such as BOLT can tell which parts of the output refer to which others. In
+//! that case, we still apply the relocations, but also keep a copy of them in which the offsets are
+//! addresses. Relocations in each output section go in a single relocation section, named after
+//! it.
+//!
+//! Section groups, such as the COMDAT groups used for C++ inline functions, are copied too, since
+//! the final link needs them in order to discard all but one copy of each. Each member of a group
+//! must end up in an output section that no other input contributes to, since the group refers to
+//! whole sections. If several of our inputs have a COMDAT group with the same signature, we keep
+//! only the first.
+
+use crate::args::OutputKind;
+use crate::elf::Rela;
+use crate::error::Result;
+use crate::layout::FileLayout;
+use crate::layout::GroupLayout;
+use crate::layout::InternalSymbols;
+use crate::layout::Layout;
+use crate::layout::ObjectLayout;
+use crate::layout::ResolutionFlags;
+use crate::layout::SymbolCopyInfo;
+use crate::output_section_id::OutputSectionId;
+use crate::parsing::InternalSymDefInfo;
+use crate::resolution::SectionSlot;
+use crate::sharding::ShardKey as _;
+use crate::storage::StorageModel;
+use crate::string_merging::get_merged_string_output_address;
+use crate::symbol_db::SymbolDb;
+use crate::symbol_db::SymbolId;
+use ahash::AHashMap;
+use anyhow::bail;
+use anyhow::Context;
+use linker_utils::elf::sht;
+use object::read::elf::Rela as _;
+use object::read::elf::SectionHeader as _;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+use object::SectionIndex;
+use object::U32Bytes;
+
+/// A section group (`SHT_GROUP`) from one of our input objects.
+pub(crate) struct SectionGroup<'data> {
+    pub(crate) flags: u32,
+
+    /// The name that identifies the group. Groups with the same signature are copies of each other
+    /// if they're COMDAT groups.
+    pub(crate) signature: &'data [u8],
+
+    members: &'data [U32Bytes<LittleEndian>],
+}
+
+impl<'data> SectionGroup<'data> {
+    pub(crate) fn parse(
+        object: &crate::elf::File<'data>,
+        section: &crate::elf::SectionHeader,
+    ) -> Result<Self> {
+        let e = LittleEndian;
+        let (flags, members) = section
+            .group(e, object.data)?
+            .context("Section isn't a section group")?;
+        let signature_index = object::SymbolIndex(section.sh_info(e) as usize);
+        let symbol = object.symbol(signature_index)?;
+        // A group can be identified by a section symbol, in which case the signature is the name
+        // of the section.
+        let signature = if symbol.st_type() == object::elf::STT_SECTION {
+            let section_index = object
+                .symbol_section(symbol, signature_index)?
+                .context("Section group signature isn't in a section")?;
+            object.section_name(object.section(section_index)?)?
+        } else {
+            object.symbol_name(symbol)?
+        };
+        Ok(Self {
+            flags,
+            signature,
+            members,
+        })
+    }
+
+    pub(crate) fn is_comdat(&self) -> bool {
+        self.flags & object::elf::GRP_COMDAT != 0
+    }
+
+    pub(crate) fn members(&self) -> impl Iterator<Item = SectionIndex> + '_ {
+        self.members
+            .iter()
+            .map(|member| SectionIndex(member.get(LittleEndian) as usize))
+    }
+
+    /// Returns the members that we're copying into our output.
+    fn kept_members<'a>(
+        &'a self,
+        sections: &'a [SectionSlot],
+    ) -> impl Iterator<Item = SectionIndex> + 'a {
+        self.members()
+            .filter(|index| !matches!(sections.get(index.0), None | Some(SectionSlot::Discard)))
+    }
+}
+
+/// Returns the size of our copy of `section`, which is a section group. We leave out any members
+/// that we discarded.
+pub(crate) fn group_section_size(
+    object: &crate::elf::File,
+    section: &crate::elf::SectionHeader,
+    sections: &[SectionSlot],
+) -> Result<u64> {
+    let group = SectionGroup::parse(object, section)?;
+    let num_words = 1 + group.kept_members(sections).count();
+    Ok((num_words * size_of::<u32>()) as u64)
+}
+
+/// Writes our copy of `group_section` from `obj` to `out`. The members are replaced by the indexes
+/// of the output sections that they ended up in.
+pub(crate) fn write_group<S: StorageModel>(
+    out: &mut [u8],
+    obj: &ObjectLayout,
+    group_section: &crate::elf::SectionHeader,
+    layout: &Layout<S>,
+) -> Result {
+    let e = LittleEndian;
+    let group = SectionGroup::parse(obj.object, group_section)?;
+    let words: &mut [U32Bytes<LittleEndian>] = crate::elf::slice_from_all_bytes_mut(out);
+    let (flags_out, members_out) = words
+        .split_first_mut()
+        .context("Insufficient space allocated to section group")?;
+    flags_out.set(e, group.flags);
+    let mut members_out = members_out.iter_mut();
+    for member in group.kept_members(&obj.sections) {
+        let output_index = loaded_output_section_id(&obj.sections[member.0])
+            .and_then(|section_id| layout.output_sections.output_index_of_section(section_id))
+            .with_context(|| {
+                format!(
+                    "Member `{}` of section group `{}` isn't being output",
+                    obj.object.section_display_name(member),
+                    String::from_utf8_lossy(group.signature)
+                )
+            })?;
+        members_out
+            .next()
+            .context("Insufficient space allocated to section group")?
+            .set(e, u32::from(output_index));
+    }
+    Ok(())
+}
+
+/// Returns the index in our symbol table of the signature symbol of each section group that we're
+/// copying, keyed by the output section that holds the group.
+pub(crate) fn group_signature_indexes<S: StorageModel>(
+    group_layouts: &[GroupLayout],
+    symbol_db: &SymbolDb<S>,
+    symtab_indexes: &[u32],
+    output_sections: &crate::output_section_id::OutputSections,
+) -> Result<AHashMap<OutputSectionId, u32>> {
+    let e = LittleEndian;
+    let mut indexes = AHashMap::new();
+    for file in group_layouts.iter().flat_map(|group| &group.files) {
+        let FileLayout::Object(obj) = file else {
+            continue;
+        };
+        for (section_index, section) in obj.object.sections.enumerate() {
+            if linker_utils::elf::SectionType::from_header(section) != sht::GROUP {
+                continue;
+            }
+            let Some(section_id) = loaded_output_section_id(&obj.sections[section_index.0]) else {
+                continue;
+            };
+            let signature_index = object::SymbolIndex(section.sh_info(e) as usize);
+            let symbol = obj.object.symbol(signature_index)?;
+            let symtab_index = if symbol.st_type() == object::elf::STT_SECTION {
+                // We don't write input section symbols, but the symbol for the output section has
+                // the same index as the section.
+                obj.object
+                    .symbol_section(symbol, signature_index)?
+                    .and_then(|index| loaded_output_section_id(&obj.sections[index.0]))
+                    .and_then(|id| output_sections.output_index_of_section(id))
+                    .map_or(0, u32::from)
+            } else {
+                let symbol_id = obj.symbol_id_range.input_to_id(signature_index);
+                symtab_indexes[symbol_db.definition(symbol_id).as_usize()]
+            };
+            if symtab_index == 0 {
+                bail!(
+                    "The signature of section group `{}` in {} isn't in our symbol table",
+                    obj.object.section_display_name(section_index),
+                    obj.input
+                );
+            }
+            indexes.insert(section_id, symtab_index);
+        }
+    }
+    Ok(indexes)
+}
+
+/// Returns the index in our symbol table of each symbol, or 0 for symbols that don't have an
+/// entry. The order in which we assign indexes needs to match the order in which the symbols are
+/// written. `num_sections` is the number of section headers that we're writing.
+pub(crate) fn symtab_indexes<'data, S: StorageModel>(
+    group_layouts: &[GroupLayout<'data>],
+    symbol_db: &SymbolDb<'data, S>,
+    symbol_resolution_flags: &[ResolutionFlags],
+    num_sections: u32,
+) -> Vec<u32> {
+    let mut indexes = vec![0; symbol_db.num_symbols()];
+    // The null symbol and the section symbols come first.
+    let mut next_local_index = num_sections;
+    let mut globals = Vec::new();
+    for file in group_layouts.iter().flat_map(|group| &group.files) {
+        match file {
+            FileLayout::Object(obj) => {
+                for (sym_index, sym) in obj.object.symbols.enumerate() {
+                    let symbol_id = obj.symbol_id_range.input_to_id(sym_index);
+                    let Some(info) = SymbolCopyInfo::new(
+                        obj.object,
+                        sym_index,
+                        sym,
+                        symbol_id,
+                        symbol_db,
+                        symbol_resolution_flags[symbol_id.as_usize()],
+                        &obj.sections,
+                    ) else {
+                        continue;
+                    };
+                    if info.is_local {
+                        indexes[symbol_id.as_usize()] = next_local_index;
+                        next_local_index += 1;
+                    } else {
+                        globals.push(symbol_id);
+                    }
+                }
+            }
+            FileLayout::Prelude(prelude) => {
+                add_written_internal_symbols(
+                    &prelude.internal_symbols,
+                    symbol_db,
+                    symbol_resolution_flags,
+                    &mut globals,
+                );
+            }
+            FileLayout::Epilogue(epilogue) => {
+                add_written_internal_symbols(
+                    &epilogue.internal_symbols,
+                    symbol_db,
+                    symbol_resolution_flags,
+                    &mut globals,
+                );
+            }
+            // With `--emit-relocs`, each symbol that we reference from a shared object gets an
+            // entry, either as a definition if it needs a copy relocation, or as undefined.
+            FileLayout::Dynamic(dynamic) => globals.extend(
+                dynamic
+                    .symbol_id_range
+                    .into_iter()
+                    .filter(|symbol_id| !symbol_resolution_flags[symbol_id.as_usize()].is_empty()),
+            ),
+            FileLayout::NotLoaded => {}
+        }
+    }
+    for (global_index, symbol_id) in (next_local_index..).zip(globals) {
+        indexes[symbol_id.as_usize()] = global_index;
+    }
+    indexes
+}
+
+/// Adds the linker-defined symbols that get written to our symbol table to `symbol_ids`.
+fn add_written_internal_symbols<S: StorageModel>(
+    internal_symbols: &InternalSymbols,
+    symbol_db: &SymbolDb<S>,
+    symbol_resolution_flags: &[ResolutionFlags],
+    symbol_ids: &mut Vec<SymbolId>,
+) {
+    for (local_index, def_info) in internal_symbols.symbol_definitions.iter().enumerate() {
+        let symbol_id = internal_symbols.start_symbol_id.add_usize(local_index);
+        if symbol_db.is_canonical(symbol_id)
+            && !symbol_resolution_flags[symbol_id.as_usize()].is_empty()
+            && !matches!(def_info, InternalSymDefInfo::Undefined)
+        {
+            symbol_ids.push(symbol_id);
+        }
+    }
+}
+
+/// Rewrites the relocations in `out`, which contains a copy of `rela_section` from `obj`.
+pub(crate) fn write_relocations<S: StorageModel>(
+    out: &mut [u8],
+    obj: &ObjectLayout,
+    rela_section: &crate::elf::SectionHeader,
+    layout: &Layout<S>,
+) -> Result {
+    let e = LittleEndian;
+    let target_index = SectionIndex(rela_section.sh_info(e) as usize);
+    let (Some(target_address), Some(target_section_id)) = (
+        obj.section_resolutions[target_index.0].address(),
+        loaded_output_section_id(&obj.sections[target_index.0]),
+    ) else {
+        // The section that the relocations apply to was discarded, e.g. because it was in a
+        // duplicate COMDAT group. We can't drop the relocations since space has already been
+        // allocated for them, so we turn them into R_*_NONE.
+        out.fill(0);
+        return Ok(());
+    };
+    let relocations: &mut [Rela] = crate::elf::slice_from_all_bytes_mut(out);
+    // In a relocatable object, offsets are relative to the section. Otherwise, they're addresses.
+    let target_offset = if is_relocatable_output(layout) {
+        target_address - output_section_address(layout, target_section_id)
+    } else {
+        target_address
+    };
+
+    for rel in relocations {
+        let offset = obj
+            .relaxations
+            .output_offset(target_index, rel.r_offset.get(e));
+        rel.r_offset.set(e, offset + target_offset);
+        let Some(sym_index) = rel.symbol(e, false) else {
+            continue;
+        };
+        let r_type = rel.r_type(e, false);
+        let symbol_id = obj.symbol_id_range.input_to_id(sym_index);
+        let definition = layout.symbol_db.definition(symbol_id);
+        let symtab_index = layout.symtab_indexes[definition.as_usize()];
+        if symtab_index != 0 {
+            rel.set_r_info(e, false, symtab_index, r_type);
+            continue;
+        }
+
+        // The symbol isn't in our symbol table, so refer to it via its output section.
+        let sym = obj.object.symbol(sym_index)?;
+        let section_index = obj
+            .object
+            .symbol_section(sym, sym_index)?
+            .with_context(|| {
+                format!(
+                    "Relocation refers to {}, which isn't in a section",
+                    layout.symbol_debug(symbol_id)
+                )
+            })?;
+        let (address, section_id) =
+            if let SectionSlot::MergeStrings(merge_slot) = &obj.sections[section_index.0] {
+                // The string that the relocation refers to may have been merged with one from
+                // elsewhere, so we refer to wherever it ended up. For a section symbol, the addend
+                // selects the string, so it's used up in finding the string. For a named symbol, we
+                // keep the addend as is, since it may be a bias such as the -4 of a PC-relative
+                // reference, which could put the address before the start of the section.
+                let is_section_symbol = sym.st_name(e) == 0;
+                let addend = if is_section_symbol {
+                    rel.r_addend.get(e)
+                } else {
+                    0
+                };
+                let address = get_merged_string_output_address(
+                    sym_index,
+                    addend,
+                    obj.object,
+                    &obj.sections,
+                    &layout.merged_strings,
+                    &layout.merged_string_start_addresses,
+                    false,
+                )?
+                .context("Missing merged string")?;
+                if is_section_symbol {
+                    rel.r_addend.set(e, 0);
+                }
+                (address, merge_slot.part_id.output_section_id())
+            } else if let (Some(section_address), Some(section_id)) = (
+                obj.section_resolutions[section_index.0].address(),
+                loaded_output_section_id(&obj.sections[section_index.0]),
+            ) {
+                (section_address + sym.st_value(e), section_id)
+            } else {
+                // The symbol is in a discarded section. Debug info is the main thing that refers to
+                // such sections and it expects that those references will resolve to zero. In a
+                // relocatable object, we leave nothing for the final link to apply, as GNU ld does.
+                // That lets it recognise the frame descriptions for functions in discarded COMDAT
+                // groups.
+                let r_type = if is_relocatable_output(layout) {
+                    0
+                } else {
+                    r_type
+                };
+                rel.set_r_info(e, false, 0, r_type);
+                rel.r_addend.set(e, 0);
+                continue;
+            };
+        let Some(section_symbol_index) = layout.output_sections.output_index_of_section(section_id)
+        else {
+            bail!(
+                "Relocation refers to {}, which is in a section that isn't being output",
+                layout.symbol_debug(symbol_id)
+            );
+        };
+        let offset_in_output_section = address
+            .checked_sub(output_section_address(layout, section_id))
+            .with_context(|| {
+                format!(
+                    "Relocation refers to {} at {address:#x}, which is before the start of its \
+                     output section {}",
+                    layout.symbol_debug(symbol_id),
+                    layout.output_sections.display_name(section_id)
+                )
+            })?;
+        rel.set_r_info(e, false, u32::from(section_symbol_index), r_type);
+        rel.r_addend.set(
+            e,
+            rel.r_addend
+                .get(e)
+                .wrapping_add(offset_in_output_section as i64),
+        );
+    }
+    Ok(())
+}
+
+/// Returns the output index of the section that the output relocation section with the supplied
+/// name applies to. This is the section with the same name, less the `.rela` prefix.
+pub(crate) fn relocation_target_index<S: StorageModel>(
+    layout: &Layout<S>,
+    rela_section_name: &[u8],
+) -> Option<u16> {
+    let target_name = rela_section_name.strip_prefix(b".rela")?;
+    // With `--emit-relocs`, the target may be a built-in section, so we can't just look it up
+    // amongst the custom sections. A custom section may have the same name as a built-in section
+    // that we're not outputting.
+    layout
+        .output_sections
+        .ids_with_info()
+        .filter(|(_, info)| info.name.bytes() == target_name)
+        .find_map(|(id, _)| layout.output_sections.output_index_of_section(id))
+}
+
+/// Returns whether the supplied output section contains relocations that we copied from our
+/// inputs, as opposed to dynamic relocations that we generated.
+pub(crate) fn is_copied_relocation_section<S: StorageModel>(
+    layout: &Layout<S>,
+    section_id: OutputSectionId,
+) -> bool {
+    layout.args().keeps_input_relocations()
+        && section_id.opt_built_in_details().is_none()
+        && layout.output_sections.section_type(section_id) == sht::RELA
+}
+
+/// Returns the offset of a symbol relative to the start of its output section. This is what goes
+/// in `st_value` of symbols in a relocatable object.
+pub(crate) fn symbol_value<S: StorageModel>(
+    layout: &Layout<S>,
+    section_id: OutputSectionId,
+    address: u64,
+) -> u64 {
+    address - output_section_address(layout, section_id)
+}
+
+/// Returns the value of the symbol for the output section with the supplied ID. In a relocatable
+/// object, this is 0, since sections don't yet have addresses.
+pub(crate) fn section_symbol_value<S: StorageModel>(
+    layout: &Layout<S>,
+    section_id: OutputSectionId,
+) -> u64 {
+    if is_relocatable_output(layout) {
+        0
+    } else {
+        output_section_address(layout, section_id)
+    }
+}
+
+fn is_relocatable_output<S: StorageModel>(layout: &Layout<S>) -> bool {
+    layout.args().output_kind() == OutputKind::RelocatableObject
+}
+
+fn output_section_address<S: StorageModel>(layout: &Layout<S>, section_id: OutputSectionId) -> u64 {
+    layout.section_layouts.get(section_id).mem_offset
+}
+
+fn loaded_output_section_id(slot: &SectionSlot) -> Option<OutputSectionId> {
+    match slot {
+        SectionSlot::Loaded(section) | SectionSlot::LoadedDebugInfo(section) => {
+            Some(section.output_section_id())
+        }
+        _ => None,
+    }
+}
@@ -3,7 +3,10 @@
 //! assigned to.
 
 use self::part_id::NOTE_GNU_PROPERTY;
+use crate::alignment::Alignment;
 use crate::args::Args;
+use crate::args::Magic;
+use crate::args::OutputKind;
 use crate::debug_assert_bail;
 use crate::elf::File;
 use crate::error::Error;
@@ -15,6 +18,9 @@ use crate::input_data::FileId;
 use crate::input_data::InputRef;
 use crate::input_data::PRELUDE_FILE_ID;
 use crate::input_data::UNINITIALISED_FILE_ID;
+use crate::linker_script::ScriptLayout;
+use crate::linker_script::DISCARD_SECTION_NAME;
+use crate::output_section_id;
 use crate::output_section_id::CustomSectionDetails;
 use crate::output_section_id::OutputSections;
 use crate::output_section_id::OutputSectionsBuilder;
@@ -25,9 +31,13 @@ use crate::parsing::ParsedInput;
 use crate::parsing::ParsedInputObject;
 use crate::parsing::Prelude;
 use crate::part_id;
+use crate::part_id::CustomSectionId;
 use crate::part_id::PartId;
 use crate::part_id::TemporaryPartId;
 use crate::part_id::UnresolvedSection;
+use crate::profile::Profile;
+use crate::profile::HOT_TEXT_SECTION_NAME;
+use crate::relocatable_object::SectionGroup;
 use crate::sharding::ShardKey;
 use crate::storage::StorageModel;
 use crate::storage::SymbolNameMap as _;
@@ -38,6 +48,8 @@ use crate::symbol::SymbolName;
 use crate::symbol_db::SymbolDb;
 use crate::symbol_db::SymbolId;
 use crate::symbol_db::SymbolIdRange;
+use ahash::AHashMap;
+use ahash::AHashSet;
 use anyhow::bail;
 use anyhow::Context;
 use atomic_take::AtomicTake;
@@ -46,8 +58,10 @@ use crossbeam_queue::ArrayQueue;
 use crossbeam_queue::SegQueue;
 use itertools::Itertools;
 use linker_utils::elf::shf;
+use linker_utils::elf::sht;
 use linker_utils::elf::SectionFlags;
 use linker_utils::elf::SectionType;
+use object::read::elf::SectionHeader as _;
 use object::read::elf::Sym as _;
 use object::LittleEndian;
 use rayon::iter::IntoParallelRefMutIterator;
@@ -64,19 +78,22 @@ pub(crate) struct ResolutionOutputs<'data> {
     pub(crate) output_sections: OutputSections<'data>,
     pub(crate) merged_strings: OutputSectionMap<MergedStringsSection<'data>>,
     pub(crate) custom_start_stop_defs: Vec<InternalSymDefInfo>,
+    pub(crate) script_layout: &'data ScriptLayout,
 }
 
 #[tracing::instrument(skip_all, name = "Symbol resolution")]
 pub fn resolve_symbols_and_sections<'data, S: StorageModel>(
     groups: &'data [Group<'data>],
     symbol_db: &mut SymbolDb<'data, S>,
+    profile: Option<&Profile>,
+    script_layout: &'data ScriptLayout,
     herd: &'data bumpalo_herd::Herd,
 ) -> Result<ResolutionOutputs<'data>> {
     let (mut groups, undefined_symbols, internal) = resolve_symbols_in_files(groups, symbol_db)?;
 
-    resolve_sections(&mut groups, herd, symbol_db.args)?;
+    resolve_sections(&mut groups, herd, profile, script_layout, symbol_db.args)?;
 
-    let output_sections = assign_section_ids(&mut groups, symbol_db.args)?;
+    let output_sections = assign_section_ids(&mut groups, script_layout, symbol_db.args)?;
 
     let merged_strings =
         crate::string_merging::merge_strings(&mut groups, &output_sections, symbol_db.args)?;
@@ -86,6 +103,12 @@ pub fn resolve_symbols_and_sections<'data, S: StorageModel>(
 
     resolve_alternative_symbol_definitions(symbol_db, &groups)?;
 
+    if !symbol_db.args.undefined_version {
+        check_version_script_symbols_defined(symbol_db, &groups)?;
+    }
+
+    check_required_symbols_defined(symbol_db, &groups)?;
+
     groups[PRELUDE_FILE_ID.group()].files[PRELUDE_FILE_ID.file()] =
         ResolvedFile::Prelude(ResolvedPrelude {
             symbol_definitions: &internal.symbol_definitions,
@@ -95,6 +118,7 @@ pub fn resolve_symbols_and_sections<'data, S: StorageModel>(
         output_sections,
         merged_strings,
         custom_start_stop_defs,
+        script_layout,
     })
 }
 
@@ -105,7 +129,7 @@ pub(crate) fn resolve_symbols_in_files<'data, S: StorageModel>(
 ) -> Result<(
     Vec<ResolvedGroup<'data>>,
     SegQueue<UndefinedSymbol<'data>>,
-    &'data Prelude,
+    &'data Prelude<'data>,
 )> {
     let mut num_objects = 0;
     let mut objects = Vec::new();
@@ -192,6 +216,29 @@ pub(crate) fn resolve_symbols_in_files<'data, S: StorageModel>(
         work_queue,
     };
 
+    // The entry point and symbols named by `EXTERN` need to be defined even if no object refers
+    // to them, so we load the archive members that define them.
+    if let Some(prelude) = prelude {
+        let entry = symbol_db
+            .args
+            .output_kind()
+            .is_executable()
+            .then_some(prelude.entry_symbol_name);
+        let extern_symbols = prelude.extern_symbols.iter().map(|name| name.as_bytes());
+        for name in entry.into_iter().chain(extern_symbols) {
+            let Some(symbol_id) = symbol_db.global_names.get(&SymbolName::prehashed(name)) else {
+                continue;
+            };
+            let file_id = symbol_db.file_id_for_symbol(symbol_id);
+            if matches!(
+                groups[file_id.group()].files[file_id.file()],
+                ParsedInput::Object(_)
+            ) {
+                resources.request_file_id(file_id);
+            }
+        }
+    }
+
     let done = AtomicBool::new(false);
 
     crate::threading::scope(|s| {
@@ -255,6 +302,8 @@ pub(crate) fn resolve_symbols_in_files<'data, S: StorageModel>(
 fn resolve_sections<'data>(
     groups: &mut [ResolvedGroup<'data>],
     herd: &'data bumpalo_herd::Herd,
+    profile: Option<&Profile>,
+    script_layout: &'data ScriptLayout,
     args: &Args,
 ) -> Result {
     let loaded_metrics: LoadedMetrics = Default::default();
@@ -274,6 +323,8 @@ fn resolve_sections<'data>(
                     obj,
                     &mut non_dynamic.custom_sections,
                     &mut non_dynamic.string_merge_extras,
+                    profile,
+                    script_layout,
                     args,
                     allocator,
                     &loaded_metrics,
@@ -287,6 +338,77 @@ fn resolve_sections<'data>(
 
     loaded_metrics.log();
 
+    if args.output_kind() == OutputKind::RelocatableObject {
+        discard_duplicate_section_groups(groups)?;
+    }
+
+    Ok(())
+}
+
+/// When producing a relocatable object, we copy section groups into our output. Where more than
+/// one input has a COMDAT group with the same signature, we keep only the first, as the final link
+/// would. Since a group lists whole sections, we also check that each output section that holds a
+/// group member holds only members of that group.
+fn discard_duplicate_section_groups(groups: &mut [ResolvedGroup]) -> Result {
+    let mut comdat_signatures = AHashSet::new();
+    let mut section_group_by_name: AHashMap<&[u8], Option<&[u8]>> = AHashMap::new();
+    for file in groups.iter_mut().flat_map(|group| &mut group.files) {
+        let ResolvedFile::Object(obj) = file else {
+            continue;
+        };
+        let Some(non_dynamic) = obj.non_dynamic.as_mut() else {
+            continue;
+        };
+        let mut member_signatures = AHashMap::new();
+        for (section_index, section) in obj.object.sections.enumerate() {
+            if SectionType::from_header(section) != sht::GROUP
+                || matches!(non_dynamic.sections[section_index.0], SectionSlot::Discard)
+            {
+                continue;
+            }
+            let group = SectionGroup::parse(obj.object, section).with_context(|| {
+                format!(
+                    "Failed to read section group `{}` of {}",
+                    obj.object.section_display_name(section_index),
+                    obj.input
+                )
+            })?;
+            let is_duplicate = group.is_comdat() && !comdat_signatures.insert(group.signature);
+            for member in group.members() {
+                if is_duplicate {
+                    if let Some(slot) = non_dynamic.sections.get_mut(member.0) {
+                        *slot = SectionSlot::Discard;
+                    }
+                } else {
+                    member_signatures.insert(member, group.signature);
+                }
+            }
+            if is_duplicate {
+                non_dynamic.sections[section_index.0] = SectionSlot::Discard;
+            }
+        }
+        let sections = &non_dynamic.sections;
+        non_dynamic
+            .custom_sections
+            .retain(|custom| !matches!(sections[custom.index.0], SectionSlot::Discard));
+        for custom in &non_dynamic.custom_sections {
+            if custom.ty == sht::GROUP {
+                continue;
+            }
+            let signature = member_signatures.get(&custom.index).copied();
+            let existing = *section_group_by_name
+                .entry(custom.name.bytes())
+                .or_insert(signature);
+            if existing != signature {
+                bail!(
+                    "Section `{}` of {} can't be combined with other sections of the same name, \
+                     since they're not all members of the same section group",
+                    custom.name,
+                    obj.input
+                );
+            }
+        }
+    }
     Ok(())
 }
 
@@ -367,6 +489,12 @@ fn resolve_alternative_symbol_definitions<'data, S: StorageModel>(
             alternatives.push(symbol_id);
         }
         let selected = select_symbol(symbol_db, first, &alternatives, resolved);
+        merge_common_symbols(
+            symbol_db,
+            selected,
+            std::iter::once(first).chain(alternatives.iter().rev().copied()),
+            resolved,
+        );
         symbol_db.replace_definition(first, selected);
         for &alt in &alternatives {
             symbol_db.replace_definition(alt, selected);
@@ -375,6 +503,63 @@ fn resolve_alternative_symbol_definitions<'data, S: StorageModel>(
     Ok(())
 }
 
+/// Checks that each symbol named explicitly by the version script is defined by a regular object in
+/// the link. A version script that names symbols that no longer exist is most likely stale.
+fn check_version_script_symbols_defined<S: StorageModel>(
+    symbol_db: &SymbolDb<S>,
+    resolved: &[ResolvedGroup],
+) -> Result {
+    let is_defined = |symbol_id: SymbolId| {
+        let definition = symbol_db.definition(symbol_id);
+        symbol_db.symbol_strength(definition, resolved) != SymbolStrength::Undefined
+            && !symbol_db
+                .symbol_value_flags(definition)
+                .contains(ValueFlags::DYNAMIC)
+    };
+    let errors = symbol_db
+        .version_script
+        .exact_globals()
+        .filter(|(_, name)| !symbol_db.global_names.get(name).is_some_and(is_defined))
+        .map(|(version, name)| {
+            let name = String::from_utf8_lossy(name.bytes());
+            match version {
+                Some(version) => anyhow::anyhow!(
+                    "Version script assigns version `{version}` to symbol `{name}`, which isn't \
+                     defined"
+                ),
+                None => {
+                    anyhow::anyhow!("Version script exports symbol `{name}`, which isn't defined")
+                }
+            }
+        })
+        .collect();
+    crate::error::combine(errors, symbol_db.args.error_limit)
+}
+
+/// Checks that each symbol named by `--require-defined` is defined, either by one of our inputs or
+/// by a shared object that we link against.
+fn check_required_symbols_defined<S: StorageModel>(
+    symbol_db: &SymbolDb<S>,
+    resolved: &[ResolvedGroup],
+) -> Result {
+    let errors = symbol_db
+        .args
+        .require_defined
+        .iter()
+        .filter(|name| {
+            !symbol_db
+                .global_names
+                .get(&SymbolName::prehashed(name.as_bytes()))
+                .is_some_and(|symbol_id| {
+                    let definition = symbol_db.definition(symbol_id);
+                    symbol_db.symbol_strength(definition, resolved) != SymbolStrength::Undefined
+                })
+        })
+        .map(|name| anyhow::anyhow!("Required symbol `{name}` isn't defined"))
+        .collect();
+    crate::error::combine(errors, symbol_db.args.error_limit)
+}
+
 /// Selects which version of the symbol to use.
 fn select_symbol<S: StorageModel>(
     symbol_db: &SymbolDb<S>,
@@ -386,7 +571,10 @@ fn select_symbol<S: StorageModel>(
     if first_strength == SymbolStrength::Strong {
         return symbol_id;
     }
-    let mut max_common = None;
+    let mut max_common = match first_strength {
+        SymbolStrength::Common { size, .. } => Some((size, symbol_id)),
+        _ => None,
+    };
     for &alt in alternatives.iter().rev() {
         // Dynamic symbols, even strong ones, don't override non-dynamic weak symbols.
         if symbol_db
@@ -398,7 +586,7 @@ fn select_symbol<S: StorageModel>(
         let strength = symbol_db.symbol_strength(alt, resolved);
         match strength {
             SymbolStrength::Strong => return alt,
-            SymbolStrength::Common(size) => {
+            SymbolStrength::Common { size, .. } => {
                 if let Some((previous_size, _)) = max_common {
                     if size <= previous_size {
                         continue;
@@ -424,6 +612,64 @@ fn select_symbol<S: StorageModel>(
     symbol_id
 }
 
+/// Gives a selected common symbol the largest alignment of any of the common symbols with the same
+/// name, since code that referred to any of them may rely on that alignment. With `--warn-common`,
+/// also reports each common symbol that was merged into or overridden by the selected symbol.
+fn merge_common_symbols<S: StorageModel>(
+    symbol_db: &mut SymbolDb<S>,
+    selected: SymbolId,
+    candidates: impl Iterator<Item = SymbolId>,
+    resolved: &[ResolvedGroup],
+) {
+    let selected_strength = symbol_db.symbol_strength(selected, resolved);
+    let selected_alignment = match selected_strength {
+        SymbolStrength::Common { alignment, .. } => alignment,
+        _ => 0,
+    };
+    let mut max_alignment = selected_alignment;
+    for symbol_id in candidates.filter(|symbol_id| *symbol_id != selected) {
+        let SymbolStrength::Common { size, alignment } =
+            symbol_db.symbol_strength(symbol_id, resolved)
+        else {
+            continue;
+        };
+        max_alignment = max_alignment.max(alignment);
+        if symbol_db.args.warn_common {
+            warn_common(symbol_db, symbol_id, size, selected, selected_strength);
+        }
+    }
+    if matches!(selected_strength, SymbolStrength::Common { .. })
+        && max_alignment > selected_alignment
+    {
+        symbol_db.common_alignments.insert(selected, max_alignment);
+    }
+}
+
+fn warn_common<S: StorageModel>(
+    symbol_db: &SymbolDb<S>,
+    symbol_id: SymbolId,
+    size: u64,
+    selected: SymbolId,
+    selected_strength: SymbolStrength,
+) {
+    let name = symbol_db.symbol_name_for_display(symbol_id);
+    let file = symbol_db.file(symbol_db.file_id_for_symbol(symbol_id));
+    let selected_file = symbol_db.file(symbol_db.file_id_for_symbol(selected));
+    let message = match selected_strength {
+        SymbolStrength::Common {
+            size: selected_size,
+            ..
+        } if selected_size > size => {
+            format!("{file}: common of `{name}` overridden by larger common from {selected_file}")
+        }
+        SymbolStrength::Common { .. } => {
+            format!("{selected_file} and {file}: multiple common of `{name}`")
+        }
+        _ => format!("{file}: common of `{name}` overridden by definition from {selected_file}"),
+    };
+    crate::error::warning(&message);
+}
+
 #[derive(PartialEq, Eq, Clone, Copy)]
 enum SymbolStrength {
     /// The object containing this symbol wasn't loaded, so the definition can be ignored.
@@ -435,9 +681,9 @@ enum SymbolStrength {
     /// The object strongly defines the symbol.
     Strong,
 
-    /// The symbol is a "common" symbol with the specified size. The definition with the largest
-    /// size will be selected.
-    Common(u64),
+    /// The symbol is a "common" symbol with the specified size and alignment. The definition with
+    /// the largest size will be selected.
+    Common { size: u64, alignment: u64 },
 }
 
 pub(crate) struct ResolvedGroup<'data> {
@@ -539,9 +785,13 @@ pub(crate) struct ResolvedEpilogue {
 #[tracing::instrument(skip_all, name = "Assign section IDs")]
 fn assign_section_ids<'data>(
     resolved: &mut [ResolvedGroup<'data>],
+    script_layout: &'data ScriptLayout,
     args: &Args,
 ) -> Result<OutputSections<'data>> {
     let mut output_sections_builder = OutputSectionsBuilder::with_base_address(args.base_address());
+    if args.separate_debug_file.is_some() {
+        output_sections_builder.place_debug_info_last();
+    }
     for group in resolved {
         for file in &mut group.files {
             if let ResolvedFile::Object(s) = file {
@@ -554,7 +804,48 @@ fn assign_section_ids<'data>(
             }
         }
     }
-    output_sections_builder.build()
+    if args.output_kind() != OutputKind::RelocatableObject {
+        output_sections_builder.add_script_sections(script_layout);
+    }
+    let mut output_sections = output_sections_builder.build()?;
+    output_sections.fixed_base_address = args.image_base.is_some();
+    output_sections.sort_common = args.sort_common;
+    output_sections.magic = args.magic;
+    output_sections.max_page_size = args.max_page_size;
+    output_sections.separate_segments = args.separate_segments;
+    // With lazy binding, the GOT gets written at runtime, so it can't be made read-only.
+    output_sections.relro = args.relro
+        && args.magic == Magic::Demand
+        && !args.lazy_binding()
+        && args.output_kind() != OutputKind::RelocatableObject;
+    output_sections.common_page_size = args.common_page_size.min(args.max_page_size);
+    if !script_layout.sections.is_empty() && args.output_kind() != OutputKind::RelocatableObject {
+        output_sections.apply_script_sections(script_layout)?;
+    }
+    for fill in &script_layout.section_fills {
+        if let Some(info) = output_sections
+            .section_infos
+            .iter_mut()
+            .find(|info| info.name.bytes() == fill.section_name.as_bytes())
+        {
+            info.fill = Some(&fill.pattern);
+        }
+    }
+    for (name, address) in &args.section_starts {
+        for info in &mut output_sections.section_infos {
+            if info.name.bytes() == name.as_bytes() {
+                info.start_address = Some(*address);
+            }
+        }
+    }
+    // Relocatable objects don't have program headers.
+    if args.output_kind() != OutputKind::RelocatableObject {
+        output_sections.assign_script_program_headers(
+            &script_layout.program_headers,
+            &script_layout.section_program_headers,
+        )?;
+    }
+    Ok(output_sections)
 }
 
 struct Outputs<'data> {
@@ -658,6 +949,10 @@ fn allocate_start_stop_symbol_id<'data, S: StorageModel>(
     custom_start_stop_defs: &mut Vec<InternalSymDefInfo>,
     output_sections: &OutputSections,
 ) -> Option<SymbolId> {
+    // The final link will define these once it knows where the sections end up.
+    if symbol_db.args.output_kind() == OutputKind::RelocatableObject {
+        return None;
+    }
     let symbol_name_bytes = name.bytes();
     let (section_name, is_start) = if let Some(s) = symbol_name_bytes.strip_prefix(b"__start_") {
         (s, true)
@@ -688,11 +983,18 @@ impl<'data> ResolvedObject<'data> {
         let mut non_dynamic = None;
 
         if obj.is_dynamic() {
+            if resources.symbol_db.args.output_kind() == OutputKind::RelocatableObject {
+                bail!("Shared objects can't be used as inputs when producing a relocatable object");
+            }
             resolve_dynamic_symbols(obj, resources, undefined_symbols_out, definitions_out)
                 .with_context(|| format!("Failed to resolve symbols in {obj}"))?;
         } else {
-            resolve_symbols(obj, resources, undefined_symbols_out, definitions_out)
-                .with_context(|| format!("Failed to resolve symbols in {obj}"))?;
+            // An image named by `--just-symbols` has already been linked, so anything that it
+            // references is no concern of ours.
+            if !obj.object.just_symbols {
+                resolve_symbols(obj, resources, undefined_symbols_out, definitions_out)
+                    .with_context(|| format!("Failed to resolve symbols in {obj}"))?;
+            }
 
             // We'll fill this in during section resolution.
             non_dynamic = Some(NonDynamicResolved {
@@ -716,19 +1018,56 @@ fn resolve_sections_for_object<'data>(
     obj: &ResolvedObject<'data>,
     custom_sections: &mut Vec<CustomSectionDetails<'data>>,
     string_merge_extras: &mut Vec<StringMergeSectionExtra<'data>>,
+    profile: Option<&Profile>,
+    script_layout: &'data ScriptLayout,
     args: &Args,
     allocator: &bumpalo_herd::Member<'data>,
     loaded_metrics: &LoadedMetrics,
 ) -> Result<Vec<SectionSlot>> {
-    let sections = obj
+    let use_script_sections =
+        !script_layout.sections.is_empty() && args.output_kind() != OutputKind::RelocatableObject;
+    let hot_sections = profile
+        .map(|profile| profile.hot_sections(obj.object))
+        .transpose()?;
+    let mut sections = obj
         .object
         .sections
         .enumerate()
         .map(|(input_section_index, input_section)| {
-            if let Some(unloaded) =
+            if let Some(mut unloaded) =
                 UnresolvedSection::from_section(obj.object, input_section, args)?
             {
+                // Each section group that we copy when producing a relocatable object lists its
+                // own members, so can't be combined with other groups.
+                let unique = (!args.unique_sections.is_empty()
+                    && apply_unique_sections(obj.object, input_section, &mut unloaded, args)?)
+                    || SectionType::from_header(input_section) == sht::GROUP;
+                let mut keep = false;
+                if use_script_sections && !unique {
+                    match apply_script_sections(obj, input_section, &mut unloaded, script_layout)? {
+                        ScriptSectionAction::Discard => return Ok(SectionSlot::Discard),
+                        ScriptSectionAction::Include => {}
+                        ScriptSectionAction::Keep => keep = true,
+                    }
+                }
+                if let TemporaryPartId::BuiltIn(id) = unloaded.part_id {
+                    if id.output_section_id() == output_section_id::TEXT
+                        && hot_sections
+                            .as_ref()
+                            .is_some_and(|hot| hot[input_section_index.0])
+                    {
+                        unloaded.part_id = TemporaryPartId::Custom(
+                            CustomSectionId {
+                                name: SectionName(HOT_TEXT_SECTION_NAME),
+                            },
+                            Alignment::new(obj.object.section_alignment(input_section)?.max(1))?,
+                        );
+                    }
+                }
                 let section_flags = SectionFlags::from_header(input_section);
+                // Sections that are kept by the script or marked SHF_GNU_RETAIN are roots for
+                // garbage collection.
+                let keep = keep || section_flags.should_retain();
                 let mut part_id = part_id::CUSTOM_PLACEHOLDER;
                 let mut custom_section = None;
                 match unloaded.part_id {
@@ -739,6 +1078,7 @@ fn resolve_sections_for_object<'data>(
                             section_flags,
                             ty: SectionType::from_header(input_section),
                             index: input_section_index,
+                            unique,
                         });
                     }
                     TemporaryPartId::BuiltIn(p) => part_id = p,
@@ -759,11 +1099,12 @@ fn resolve_sections_for_object<'data>(
                             SectionSlot::NoteGnuProperty(input_section_index)
                         }
                         TemporaryPartId::BuiltIn(id)
-                            if id
-                                .output_section_id()
-                                .built_in_details()
-                                .section_flags
-                                .should_retain() =>
+                            if keep
+                                || id
+                                    .output_section_id()
+                                    .built_in_details()
+                                    .section_flags
+                                    .should_retain() =>
                         {
                             SectionSlot::MustLoad(UnloadedSection::new(id))
                         }
@@ -781,7 +1122,7 @@ fn resolve_sections_for_object<'data>(
                                 } else {
                                     SectionSlot::UnloadedDebugInfo(part_id::CUSTOM_PLACEHOLDER)
                                 }
-                            } else if section_flags.should_retain() {
+                            } else if keep {
                                 SectionSlot::MustLoad(UnloadedSection::new(
                                     part_id::CUSTOM_PLACEHOLDER,
                                 ))
@@ -803,9 +1144,180 @@ fn resolve_sections_for_object<'data>(
             }
         })
         .collect::<Result<Vec<_>>>()?;
+    if args.emit_relocs {
+        resolve_emitted_relocation_sections(obj.object, &mut sections, custom_sections, allocator)?;
+    }
     Ok(sections)
 }
 
+/// With `--emit-relocs`, we copy each relocation section into the output if we load the section
+/// that it applies to. The copies are named after the output section of their target, so for
+/// example, the relocations for `.text.foo` go in `.rela.text`. We don't keep relocations for
+/// sections that get rewritten rather than copied, such as `.eh_frame` and string-merge sections.
+/// Nor do we keep those for debug info, since post-link optimisers don't need them.
+fn resolve_emitted_relocation_sections<'data>(
+    object: &File<'data>,
+    sections: &mut [SectionSlot],
+    custom_sections: &mut Vec<CustomSectionDetails<'data>>,
+    allocator: &bumpalo_herd::Member<'data>,
+) -> Result {
+    for (input_section_index, input_section) in object.sections.enumerate() {
+        if SectionType::from_header(input_section) != sht::RELA {
+            continue;
+        }
+        let target_index = object::SectionIndex(input_section.sh_info(LittleEndian) as usize);
+        let Some(SectionSlot::Unloaded(target) | SectionSlot::MustLoad(target)) =
+            sections.get(target_index.0)
+        else {
+            continue;
+        };
+        let target_name = if target.part_id == part_id::CUSTOM_PLACEHOLDER {
+            custom_sections
+                .iter()
+                .find(|custom| custom.index == target_index)
+                .context("Missing custom section for relocation target")?
+                .name
+                .bytes()
+        } else {
+            target
+                .part_id
+                .output_section_id()
+                .built_in_details()
+                .name
+                .bytes()
+        };
+        let name = allocator.alloc_slice_copy(&[b".rela", target_name].concat());
+        custom_sections.push(CustomSectionDetails {
+            name: SectionName(name),
+            alignment: Alignment::new(object.section_alignment(input_section)?.max(1))?,
+            section_flags: SectionFlags::from_header(input_section),
+            ty: sht::RELA,
+            index: input_section_index,
+            unique: false,
+        });
+        // This gets loaded by `load_section` when it loads the target.
+        sections[input_section_index.0] =
+            SectionSlot::Unloaded(UnloadedSection::new(part_id::CUSTOM_PLACEHOLDER));
+    }
+    Ok(())
+}
+
+/// Returns whether `--unique` asks for the supplied section to be placed in an output section of
+/// its own. If it does and the section would otherwise go into a built-in output section, it's
+/// changed to be a custom section, since built-in sections are always shared.
+fn apply_unique_sections<'data>(
+    object: &File<'data>,
+    input_section: &object::elf::SectionHeader64<LittleEndian>,
+    unloaded: &mut UnresolvedSection<'data>,
+    args: &Args,
+) -> Result<bool> {
+    let section_name = object.section_name(input_section)?;
+    if args.unique_sections.matches_name(section_name) {
+        match unloaded.part_id {
+            TemporaryPartId::Custom(..) => {}
+            // Unwinding info and notes need to be combined with their counterparts from other
+            // objects in order to be of any use.
+            TemporaryPartId::EhFrameData => return Ok(false),
+            TemporaryPartId::BuiltIn(id) if id == NOTE_GNU_PROPERTY => return Ok(false),
+            TemporaryPartId::BuiltIn(_) => {
+                unloaded.part_id = TemporaryPartId::Custom(
+                    CustomSectionId {
+                        name: SectionName(section_name),
+                    },
+                    Alignment::new(object.section_alignment(input_section)?.max(1))?,
+                );
+            }
+        }
+        return Ok(true);
+    }
+    let is_debug_info = section_name.starts_with(b".debug_")
+        && !SectionFlags::from_header(input_section).contains(shf::ALLOC);
+    Ok(args.unique_sections.orphans
+        && matches!(unloaded.part_id, TemporaryPartId::Custom(..))
+        && !is_debug_info)
+}
+
+/// What the `SECTIONS` command of a linker script says to do with an input section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScriptSectionAction {
+    /// The section is in `/DISCARD/`.
+    Discard,
+
+    /// The section goes in the output if something refers to it.
+    Include,
+
+    /// The section is in `KEEP`, so goes in the output even if nothing refers to it.
+    Keep,
+}
+
+/// Moves the supplied section to the output section that the `SECTIONS` command of a linker script
+/// puts it in.
+fn apply_script_sections<'data>(
+    obj: &ResolvedObject<'data>,
+    input_section: &object::elf::SectionHeader64<LittleEndian>,
+    unloaded: &mut UnresolvedSection<'data>,
+    script_layout: &'data ScriptLayout,
+) -> Result<ScriptSectionAction> {
+    let section_name = obj.object.section_name(input_section)?;
+    let path = obj.input.file.filename.as_os_str().as_encoded_bytes();
+    let output_name = match &obj.input.entry {
+        Some(entry) => {
+            script_layout.output_section_for(Some(path), entry.identifier.as_slice(), section_name)
+        }
+        None => script_layout.output_section_for(None, path, section_name),
+    };
+    let Some((output_name, keep)) = output_name else {
+        return Ok(ScriptSectionAction::Include);
+    };
+    if output_name == DISCARD_SECTION_NAME {
+        return Ok(ScriptSectionAction::Discard);
+    }
+    let action = if keep {
+        ScriptSectionAction::Keep
+    } else {
+        ScriptSectionAction::Include
+    };
+    let current_name = match unloaded.part_id {
+        TemporaryPartId::Custom(custom_id, _) => custom_id.name.0,
+        // Sections that we build by combining the inputs, such as unwinding info and notes, stay
+        // in their usual output sections.
+        TemporaryPartId::EhFrameData => return Ok(action),
+        TemporaryPartId::BuiltIn(id)
+            if !id.output_section_id().is_regular()
+                || id.output_section_id() == output_section_id::COMMENT =>
+        {
+            return Ok(action)
+        }
+        TemporaryPartId::BuiltIn(id) => id.output_section_id().built_in_details().name.bytes(),
+    };
+    if current_name == output_name.as_bytes() {
+        return Ok(action);
+    }
+    let alignment = Alignment::new(obj.object.section_alignment(input_section)?.max(1))?;
+    unloaded.part_id = if let Some(section_id) =
+        output_section_id::regular_built_in_by_name(output_name.as_bytes())
+    {
+        let is_nobits = SectionType::from_header(input_section) == sht::NOBITS;
+        if is_nobits != (section_id.built_in_details().ty == sht::NOBITS) {
+            bail!(
+                "Linker script puts `{}` from {} in `{output_name}`, but only one of them is \
+                     NOBITS",
+                String::from_utf8_lossy(section_name),
+                obj.input
+            );
+        }
+        TemporaryPartId::BuiltIn(section_id.part_id_with_alignment(alignment))
+    } else {
+        TemporaryPartId::Custom(
+            CustomSectionId {
+                name: SectionName(output_name.as_bytes()),
+            },
+            alignment,
+        )
+    };
+    Ok(action)
+}
+
 fn resolve_symbols<'data, S: StorageModel>(
     obj: &ParsedInputObject<'data>,
     resources: &ResolutionResources<'data, '_, '_, S>,
@@ -824,6 +1336,7 @@ fn resolve_symbols<'data, S: StorageModel>(
                     definition,
                     resources,
                     obj,
+                    &[],
                     undefined_symbols_out,
                 )
             },
@@ -837,6 +1350,7 @@ fn resolve_dynamic_symbols<'data, S: StorageModel>(
     undefined_symbols_out: &SegQueue<UndefinedSymbol<'data>>,
     definitions_out: &mut [SymbolId],
 ) -> Result {
+    let needed = obj.object.dynamic_tag_strings(object::elf::DT_NEEDED)?;
     obj.object
         .symbols
         .enumerate()
@@ -849,6 +1363,7 @@ fn resolve_dynamic_symbols<'data, S: StorageModel>(
                     definition,
                     resources,
                     obj,
+                    &needed,
                     undefined_symbols_out,
                 )
             },
@@ -856,12 +1371,34 @@ fn resolve_dynamic_symbols<'data, S: StorageModel>(
     Ok(())
 }
 
+/// Returns whether the file with the supplied ID is a shared object that we only link against if
+/// something needs it and is in `needed`, the `DT_NEEDED` entries of the shared object that refers
+/// to it. As with GNU ld, such references don't count for `--as-needed`, since the dynamic loader
+/// will load the shared object regardless.
+fn is_needed_dependency<S: StorageModel>(
+    file_id: FileId,
+    needed: &[&[u8]],
+    resources: &ResolutionResources<'_, '_, '_, S>,
+) -> Result<bool> {
+    if needed.is_empty() {
+        return Ok(false);
+    }
+    let ParsedInput::Object(target) = resources.symbol_db.file(file_id) else {
+        return Ok(false);
+    };
+    if !target.is_dynamic() || !target.is_optional() {
+        return Ok(false);
+    }
+    Ok(needed.contains(&target.soname()?))
+}
+
 fn resolve_symbol<'data, S: StorageModel>(
     local_symbol_index: object::SymbolIndex,
     local_symbol: &crate::elf::SymtabEntry,
     definition_out: &mut SymbolId,
     resources: &ResolutionResources<'data, '_, '_, S>,
     obj: &ParsedInputObject<'data>,
+    needed: &[&[u8]],
     undefined_symbols_out: &SegQueue<UndefinedSymbol<'data>>,
 ) -> Result {
     // Don't try to resolve symbols that are already defined, e.g. locals and globals that we
@@ -881,7 +1418,10 @@ fn resolve_symbol<'data, S: StorageModel>(
         Some(symbol_id) => {
             *definition_out = symbol_id;
             let symbol_file_id = resources.symbol_db.file_id_for_symbol(symbol_id);
-            if symbol_file_id != obj.file_id && !local_symbol.is_weak() {
+            if symbol_file_id != obj.file_id
+                && !local_symbol.is_weak()
+                && !is_needed_dependency(symbol_file_id, needed, resources)?
+            {
                 resources.request_file_id(symbol_file_id);
             } else if symbol_file_id != PRELUDE_FILE_ID {
                 // The symbol is weak and we can't be sure that the file that defined it will end up
@@ -978,8 +1518,8 @@ bitflags! {
         const CAN_BYPASS_GOT = 1 << 4;
 
         /// We have a version script and the version script says that the symbol should be downgraded to
-        /// a local. It's still treated as a global for name lookup purposes, but after that, it becomes
-        /// local.
+        /// a local, or the symbol comes from an archive named by `--exclude-libs`. It's still treated
+        /// as a global for name lookup purposes, but after that, it becomes local.
         const DOWNGRADE_TO_LOCAL = 1 << 5;
 
         /// Set when the value is function. Currently only set for dynamic symbols, since that's all
@@ -1010,6 +1550,14 @@ impl std::fmt::Display for ValueFlags {
 impl<S: StorageModel> SymbolDb<'_, S> {
     fn symbol_strength(&self, symbol_id: SymbolId, resolved: &[ResolvedGroup]) -> SymbolStrength {
         let file_id = self.file_id_for_symbol(symbol_id);
+        if let ParsedInput::Prelude(prelude) = self.file(file_id) {
+            // Assignments in linker scripts override definitions in input files, unless they're
+            // wrapped in PROVIDE.
+            return match prelude.script_assignment(symbol_id) {
+                Some(assignment) if !assignment.provide => SymbolStrength::Strong,
+                _ => SymbolStrength::Undefined,
+            };
+        }
         if let ResolvedFile::Object(obj) = &resolved[file_id.group()].files[file_id.file()] {
             let local_index = symbol_id.to_input(obj.symbol_id_range);
             let Ok(obj_symbol) = obj.object.symbol(local_index) else {
@@ -1020,7 +1568,11 @@ impl<S: StorageModel> SymbolDb<'_, S> {
             if obj_symbol.is_weak() {
                 SymbolStrength::Weak
             } else if obj_symbol.is_common(e) {
-                SymbolStrength::Common(obj_symbol.st_size(e))
+                // Common symbols store their alignment in the value field.
+                SymbolStrength::Common {
+                    size: obj_symbol.st_size(e),
+                    alignment: obj_symbol.st_value(e),
+                }
             } else {
                 SymbolStrength::Strong
             }
@@ -0,0 +1,264 @@
+use crate::arch::Arch;
+use crate::arch::PltFeatures;
+use crate::elf::PLT_ENTRY_SIZE;
+use crate::resolution::ValueFlags;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+use linker_utils::elf::riscv64_rel_type_to_string;
+use linker_utils::elf::shf;
+use linker_utils::elf::DynamicRelocationKind;
+use linker_utils::elf::RelocationKind;
+use linker_utils::elf::RelocationKindInfo;
+use linker_utils::relaxation::RelocationModifier;
+use linker_utils::riscv64::is_auipc_ld_pair;
+use linker_utils::riscv64::relocation_type_from_raw;
+use linker_utils::riscv64::RelaxationKind;
+use linker_utils::riscv64::RiscVInstruction;
+
+pub(crate) struct RiscV64;
+
+const PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0x17, 0x0e, 0x00, 0x00, // auipc t3, hi(&(.got.plt[n]))
+    0x03, 0x3e, 0x0e, 0x00, // ld t3, lo(&(.got.plt[n]))(t3)
+    0x67, 0x03, 0x0e, 0x00, // jalr t1, t3
+    0x13, 0x00, 0x00, 0x00, // nop
+];
+
+const _ASSERTS: () = {
+    assert!(PLT_ENTRY_TEMPLATE.len() as u64 == PLT_ENTRY_SIZE);
+};
+
+impl crate::arch::Arch for RiscV64 {
+    type Relaxation = Relaxation;
+
+    fn elf_header_arch_magic() -> u16 {
+        object::elf::EM_RISCV
+    }
+
+    // The table of the relocations is documented here:
+    // https://github.com/riscv-non-isa/riscv-elf-psabi-doc/blob/master/riscv-elf.adoc.
+    fn relocation_from_raw(r_type: u32) -> Result<RelocationKindInfo> {
+        relocation_type_from_raw(r_type).ok_or_else(|| {
+            anyhow!(
+                "Unsupported relocation type {}",
+                Self::rel_type_to_string(r_type)
+            )
+        })
+    }
+
+    fn get_dynamic_relocation_type(relocation: DynamicRelocationKind) -> u32 {
+        relocation.riscv64_r_type()
+    }
+
+    fn rel_type_to_string(r_type: u32) -> std::borrow::Cow<'static, str> {
+        riscv64_rel_type_to_string(r_type)
+    }
+
+    fn merge_e_flags(input_flags: impl Iterator<Item = u32>) -> crate::error::Result<u32> {
+        // The output uses compressed instructions or the TSO memory model if any input does. The
+        // ABI flags on the other hand need to agree between all inputs.
+        const ABI_FLAGS: u32 = object::elf::EF_RISCV_FLOAT_ABI | object::elf::EF_RISCV_RVE;
+        let mut merged: Option<u32> = None;
+        for flags in input_flags {
+            let Some(merged) = merged.as_mut() else {
+                merged = Some(flags);
+                continue;
+            };
+            if (flags ^ *merged) & ABI_FLAGS != 0 {
+                bail!(
+                    "Cannot link objects with different floating-point or RVE ABIs \
+                     (e_flags 0x{flags:x} and 0x{merged:x})"
+                );
+            }
+            *merged |= flags & (object::elf::EF_RISCV_RVC | object::elf::EF_RISCV_TSO);
+        }
+        Ok(merged.unwrap_or(0))
+    }
+
+    fn fixed_branch_range(r_type: u32) -> Option<u64> {
+        // Calls use an auipc and jalr, which can reach anywhere within 2GiB, so don't need thunks.
+        // The branches that use a single instruction, on the other hand, are limited to the size
+        // of the enclosing function in practice.
+        match r_type {
+            object::elf::R_RISCV_JAL => Some(1 << 20),
+            object::elf::R_RISCV_BRANCH => Some(1 << 12),
+            object::elf::R_RISCV_RVC_JUMP => Some(1 << 11),
+            object::elf::R_RISCV_RVC_BRANCH => Some(1 << 8),
+            _ => None,
+        }
+    }
+
+    fn got_relocation_range(r_type: u32) -> Option<std::ops::Range<i64>> {
+        match r_type {
+            // The high part is rounded to account for the low part being sign-extended.
+            object::elf::R_RISCV_GOT_HI20
+            | object::elf::R_RISCV_TLS_GOT_HI20
+            | object::elf::R_RISCV_TLS_GD_HI20
+            | object::elf::R_RISCV_TLSDESC_HI20 => Some(-(1 << 31) - 0x800..(1 << 31) - 0x800),
+            _ => None,
+        }
+    }
+
+    fn write_plt_entry(
+        plt_entry: &mut [u8],
+        got_address: u64,
+        plt_address: u64,
+        _got_base: u64,
+        _features: PltFeatures,
+    ) -> crate::error::Result {
+        plt_entry.copy_from_slice(PLT_ENTRY_TEMPLATE);
+        let offset = got_address.wrapping_sub(plt_address);
+        anyhow::ensure!(
+            (offset as i64).unsigned_abs() < (1 << 31),
+            "PLT is more than 2GiB away from GOT"
+        );
+        RiscVInstruction::UType.write_to_value(offset, &mut plt_entry[0..4]);
+        RiscVInstruction::IType.write_to_value(offset, &mut plt_entry[4..8]);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Relaxation {
+    kind: RelaxationKind,
+    rel_info: RelocationKindInfo,
+}
+
+impl crate::arch::Relaxation for Relaxation {
+    fn new(
+        relocation_kind: u32,
+        section_bytes: &[u8],
+        offset_in_section: u64,
+        value_flags: crate::resolution::ValueFlags,
+        output_kind: crate::args::OutputKind,
+        section_flags: linker_utils::elf::SectionFlags,
+    ) -> Option<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        let mut relocation = RiscV64::relocation_from_raw(relocation_kind).unwrap();
+        let can_bypass_got = value_flags.contains(ValueFlags::CAN_BYPASS_GOT);
+        let is_absolute = value_flags.contains(ValueFlags::ABSOLUTE)
+            && !value_flags.contains(ValueFlags::DYNAMIC);
+
+        // IFuncs cannot be referenced directly, they always need to go via the GOT.
+        if value_flags.contains(ValueFlags::IFUNC) {
+            return match relocation_kind {
+                object::elf::R_RISCV_CALL | object::elf::R_RISCV_CALL_PLT => {
+                    relocation.kind = RelocationKind::PltRelative;
+                    Some(Relaxation {
+                        kind: RelaxationKind::NoOp,
+                        rel_info: relocation,
+                    })
+                }
+                _ => None,
+            };
+        }
+
+        // All relaxations below only apply to executable code, so we shouldn't attempt them if a
+        // relocation is in a non-executable section.
+        if !section_flags.contains(shf::EXECINSTR) {
+            return None;
+        }
+
+        // When we go from a GOT load to computing the address relative to the instruction, the
+        // value of an absolute symbol would be wrong if the output gets loaded at a different
+        // address.
+        let can_compute_address = can_bypass_got && !(is_absolute && output_kind.is_relocatable());
+
+        // The relaxations that shorten code, e.g. auipc+jalr -> jal, are decided during layout,
+        // since they change addresses. See `riscv64_relaxation`. The relaxations here keep the
+        // instruction sequence the same length.
+        match relocation_kind {
+            object::elf::R_RISCV_CALL | object::elf::R_RISCV_CALL_PLT if can_bypass_got => {
+                relocation.kind = RelocationKind::Relative;
+                return Some(Relaxation {
+                    kind: RelaxationKind::NoOp,
+                    rel_info: relocation,
+                });
+            }
+
+            // GOT load -> address computation. The auipc now gets the high part of the offset to
+            // the symbol rather than to the GOT entry and the ld becomes an addi. The paired low
+            // part relocation on the ld then picks up the value computed for the auipc.
+            object::elf::R_RISCV_GOT_HI20
+                if can_compute_address
+                    && is_auipc_ld_pair(&section_bytes[offset_in_section as usize..]) =>
+            {
+                return Some(Relaxation {
+                    kind: RelaxationKind::GotLoadToAddi,
+                    rel_info: relocation_type_from_raw(object::elf::R_RISCV_PCREL_HI20).unwrap(),
+                });
+            }
+
+            _ => (),
+        }
+
+        None
+    }
+
+    fn apply(&self, section_bytes: &mut [u8], offset_in_section: &mut u64, addend: &mut i64) {
+        self.kind.apply(section_bytes, offset_in_section, addend);
+    }
+
+    fn rel_info(&self) -> RelocationKindInfo {
+        self.rel_info
+    }
+
+    fn debug_kind(&self) -> &dyn std::fmt::Debug {
+        &self.kind
+    }
+
+    fn next_modifier(&self) -> RelocationModifier {
+        self.kind.next_modifier()
+    }
+}
+
+#[test]
+fn test_got_relaxation() {
+    use crate::arch::Relaxation as _;
+    use crate::args::OutputKind;
+    use crate::args::RelocationModel;
+
+    // auipc a0, 0; ld a0, 0(a0)
+    let code = [0x17, 0x05, 0x00, 0x00, 0x03, 0x35, 0x05, 0x00];
+    let relax = |value_flags, bytes: &[u8]| {
+        Relaxation::new(
+            object::elf::R_RISCV_GOT_HI20,
+            bytes,
+            0,
+            value_flags,
+            OutputKind::StaticExecutable(RelocationModel::Relocatable),
+            shf::EXECINSTR,
+        )
+    };
+
+    let r = relax(ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT, &code).unwrap();
+    let mut out = code;
+    r.apply(&mut out, &mut 0, &mut 0);
+    // auipc a0, 0; addi a0, a0, 0
+    assert_eq!(out, [0x17, 0x05, 0x00, 0x00, 0x13, 0x05, 0x05, 0x00]);
+    assert_eq!(r.rel_info().kind, RelocationKind::Relative);
+
+    assert!(relax(ValueFlags::ABSOLUTE | ValueFlags::CAN_BYPASS_GOT, &code).is_none());
+    assert!(relax(ValueFlags::ADDRESS, &code).is_none());
+    // The ld must use the register written by the auipc.
+    let other_base = [0x17, 0x05, 0x00, 0x00, 0x03, 0xb5, 0x05, 0x00];
+    assert!(relax(
+        ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT,
+        &other_base
+    )
+    .is_none());
+}
+
+#[test]
+fn test_relaxation_markers() {
+    // These are acted on by layout, so don't write anything themselves.
+    for r_type in [object::elf::R_RISCV_RELAX, object::elf::R_RISCV_ALIGN] {
+        assert_eq!(
+            RiscV64::relocation_from_raw(r_type).unwrap().kind,
+            RelocationKind::None
+        );
+    }
+}
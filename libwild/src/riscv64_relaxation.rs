@@ -0,0 +1,832 @@
+//! RISC-V linker relaxation. Unlike relaxation on other architectures, which rewrites instruction
+//! sequences in place, RISC-V relaxation deletes bytes from executable sections. Compilers emit
+//! sequences that can reach anywhere, e.g. auipc+jalr for a call, and mark each one that we're
+//! allowed to shorten with an `R_RISCV_RELAX` at the same offset. Because the assembler can't know
+//! how much code will be deleted, it also emits the worst-case amount of padding wherever code
+//! needs to be aligned, together with an `R_RISCV_ALIGN` that tells us to delete whatever padding
+//! isn't needed once we know where things are.
+//!
+//! We do the following:
+//!
+//! * `auipc+jalr` -> `jal` (or `c.j` for a tail call when compressed instructions are available)
+//!   when the target is within range.
+//! * `lui` + low part -> low part relative to `x0` when the value fits in a signed 12 bit
+//!   immediate, which lets us delete the `lui`.
+//! * `lui` or `auipc` + low part -> low part relative to gp when the address is within 2KiB of
+//!   `__global_pointer$`. We only do this if we defined `__global_pointer$` and something, normally
+//!   the startup code that sets gp, references it.
+//! * Deleting the unneeded part of the padding marked by `R_RISCV_ALIGN`. This isn't optional,
+//!   since the padding that the assembler emits is generally not the right size.
+//!
+//! Deleting bytes only ever brings code closer together, except where a section boundary needs
+//! more padding than before. We allow for that by requiring targets to be in range by a margin of
+//! the largest section alignment, as GNU ld does. A sequence that we relax in one pass therefore
+//! stays in range in later passes, so layout keeps the relaxations that it has already decided on
+//! and repeats until no more are found. Alignment padding is only deleted once everything else has
+//! been decided, since how much we can delete depends on the final position of the padding.
+//!
+//! We don't relax TLS accesses or use `c.lui`.
+//!
+//! Relaxations that don't delete bytes, such as going from a GOT load to an address computation,
+//! are done by the `Relaxation` implementation in `riscv64`.
+
+use crate::elf::Rela;
+use crate::error::Result;
+use crate::thunks::is_in_range;
+use anyhow::bail;
+use linker_utils::riscv64::call_link_register;
+use linker_utils::riscv64::fits_in_i12;
+use linker_utils::riscv64::has_base_register;
+use linker_utils::riscv64::is_auipc;
+use linker_utils::riscv64::is_lui;
+use linker_utils::riscv64::set_base_register;
+use linker_utils::riscv64::write_c_j;
+use linker_utils::riscv64::write_jal;
+use linker_utils::riscv64::write_nops;
+use linker_utils::riscv64::GLOBAL_POINTER_OFFSET;
+use linker_utils::riscv64::GLOBAL_POINTER_REGISTER;
+use linker_utils::riscv64::ZERO_REGISTER;
+use object::LittleEndian;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// The range of a jal.
+const JAL_RANGE: u64 = 1 << 20;
+
+/// The range of a c.j.
+const C_J_RANGE: u64 = 1 << 11;
+
+/// What we do with an instruction sequence or padding that we're relaxing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SiteKind {
+    /// An auipc+jalr that becomes a jal that writes the return address to the specified register.
+    CallToJal { link_register: u32 },
+
+    /// An auipc+jalr tail call that becomes a c.j.
+    CallToCJump,
+
+    /// A lui or auipc that we delete, since its low parts no longer need it.
+    DeletedHighPart,
+
+    /// An instruction that adds the low part of a value to the register written by a lui that we
+    /// deleted. It gets changed to use the specified base register instead.
+    LowPart { base_register: u32 },
+
+    /// An instruction that adds the low part of an address to the register written by a lui or
+    /// auipc that we deleted. It gets changed to use gp as its base. For an auipc, the relocation
+    /// references the auipc rather than the address, so `high_part` holds the symbol index and
+    /// addend of the auipc's relocation.
+    GlobalPointerLowPart { high_part: Option<(u32, i64)> },
+
+    /// `padding` bytes of alignment padding, of which we keep the first `keep`.
+    Align { padding: u64, keep: u64 },
+}
+
+impl SiteKind {
+    /// Returns the range of bytes, relative to the start of the site, that we delete.
+    fn deleted_range(self) -> std::ops::Range<u64> {
+        match self {
+            SiteKind::CallToJal { .. } => 4..8,
+            SiteKind::CallToCJump => 2..8,
+            SiteKind::DeletedHighPart => 0..4,
+            SiteKind::LowPart { .. } | SiteKind::GlobalPointerLowPart { .. } => 0..0,
+            SiteKind::Align { padding, keep } => keep..padding,
+        }
+    }
+
+    /// Returns whether this is what we do for a relocation of type `r_type`. Other relocations at
+    /// the same offset, such as the `R_RISCV_RELAX` that allowed the relaxation, are left alone.
+    fn applies_to(self, r_type: u32) -> bool {
+        match self {
+            SiteKind::CallToJal { .. } | SiteKind::CallToCJump => {
+                matches!(
+                    r_type,
+                    object::elf::R_RISCV_CALL | object::elf::R_RISCV_CALL_PLT
+                )
+            }
+            SiteKind::DeletedHighPart => matches!(
+                r_type,
+                object::elf::R_RISCV_HI20 | object::elf::R_RISCV_PCREL_HI20
+            ),
+            SiteKind::LowPart { .. } => matches!(
+                r_type,
+                object::elf::R_RISCV_LO12_I | object::elf::R_RISCV_LO12_S
+            ),
+            SiteKind::GlobalPointerLowPart { high_part: None } => matches!(
+                r_type,
+                object::elf::R_RISCV_LO12_I | object::elf::R_RISCV_LO12_S
+            ),
+            SiteKind::GlobalPointerLowPart { high_part: Some(_) } => matches!(
+                r_type,
+                object::elf::R_RISCV_PCREL_LO12_I | object::elf::R_RISCV_PCREL_LO12_S
+            ),
+            SiteKind::Align { .. } => r_type == object::elf::R_RISCV_ALIGN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Site {
+    /// The offset in the input section of the relocation that we're relaxing.
+    offset: u64,
+    kind: SiteKind,
+
+    /// The number of bytes deleted by earlier sites in the same section.
+    deleted_before: u64,
+}
+
+impl Site {
+    fn deleted_range(&self) -> std::ops::Range<u64> {
+        let range = self.kind.deleted_range();
+        self.offset + range.start..self.offset + range.end
+    }
+}
+
+/// The relaxations that we're doing in a single section, ordered by offset.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct SectionRelaxations {
+    sites: Vec<Site>,
+}
+
+impl SectionRelaxations {
+    fn push(&mut self, offset: u64, kind: SiteKind) {
+        let deleted_before = self.total_deleted();
+        self.sites.push(Site {
+            offset,
+            kind,
+            deleted_before,
+        });
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    /// Returns the number of bytes that we delete from the section.
+    pub(crate) fn total_deleted(&self) -> u64 {
+        self.sites.last().map_or(0, |site| {
+            site.deleted_before + site.deleted_range().end - site.deleted_range().start
+        })
+    }
+
+    /// Returns where the byte at `input_offset` in the input section ends up in the output
+    /// section. Offsets within bytes that we delete map to where those bytes would have been.
+    pub(crate) fn output_offset(&self, input_offset: u64) -> u64 {
+        let num_before = self
+            .sites
+            .partition_point(|site| site.deleted_range().start < input_offset);
+        let Some(site) = num_before.checked_sub(1).map(|i| &self.sites[i]) else {
+            return input_offset;
+        };
+        let range = site.deleted_range();
+        let deleted = site.deleted_before + (input_offset.min(range.end) - range.start);
+        input_offset - deleted
+    }
+
+    fn site(&self, offset: u64) -> Option<&Site> {
+        self.sites
+            .binary_search_by_key(&offset, |site| site.offset)
+            .ok()
+            .map(|i| &self.sites[i])
+    }
+
+    /// Copies the bytes of `input` that we don't delete to `out`.
+    pub(crate) fn copy_data(&self, input: &[u8], out: &mut [u8]) -> Result {
+        let expected = input.len() as u64 - self.total_deleted();
+        if out.len() as u64 != expected {
+            bail!(
+                "Relaxed section needs {expected} bytes, but {} were allocated",
+                out.len()
+            );
+        }
+        let mut input_pos = 0;
+        let mut output_pos = 0;
+        let deleted_ranges = self
+            .sites
+            .iter()
+            .map(|site| site.deleted_range())
+            .chain(std::iter::once(input.len() as u64..input.len() as u64));
+        for deleted in deleted_ranges {
+            let kept = &input[input_pos as usize..deleted.start as usize];
+            out[output_pos..output_pos + kept.len()].copy_from_slice(kept);
+            output_pos += kept.len();
+            input_pos = deleted.end;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the instructions for the relaxation, if any, that relocation `rel` is for. `out`
+    /// is the output section. Returns the output offset and the relocation to apply there, or None
+    /// if there's nothing more to do for the relocation.
+    pub(crate) fn relax_relocation(&self, rel: &Rela, out: &mut [u8]) -> Option<(u64, Rela)> {
+        let e = LittleEndian;
+        let input_offset = rel.r_offset.get(e);
+        let offset = self.output_offset(input_offset);
+        let r_type = rel.r_type(e, false);
+        let Some(site) = self
+            .site(input_offset)
+            .filter(|site| site.kind.applies_to(r_type))
+        else {
+            return Some((offset, *rel));
+        };
+        let out = &mut out[offset as usize..];
+        let mut rel = *rel;
+        let r_sym = rel.r_sym(e, false);
+        match site.kind {
+            SiteKind::CallToJal { link_register } => {
+                write_jal(out, link_register);
+                rel.set_r_info(e, false, r_sym, object::elf::R_RISCV_JAL);
+            }
+            SiteKind::CallToCJump => {
+                write_c_j(out);
+                rel.set_r_info(e, false, r_sym, object::elf::R_RISCV_RVC_JUMP);
+            }
+            SiteKind::DeletedHighPart => return None,
+            SiteKind::LowPart { base_register } => set_base_register(out, base_register),
+            SiteKind::GlobalPointerLowPart { high_part } => {
+                set_base_register(out, GLOBAL_POINTER_REGISTER);
+                let (r_sym, addend) = high_part.unwrap_or((r_sym, rel.r_addend.get(e)));
+                let r_type = if matches!(
+                    r_type,
+                    object::elf::R_RISCV_LO12_I | object::elf::R_RISCV_PCREL_LO12_I
+                ) {
+                    object::elf::R_RISCV_GPREL_I
+                } else {
+                    object::elf::R_RISCV_GPREL_S
+                };
+                rel.set_r_info(e, false, r_sym, r_type);
+                rel.r_addend.set(e, addend);
+            }
+            SiteKind::Align { keep, .. } => {
+                write_nops(&mut out[..keep as usize]);
+                return None;
+            }
+        }
+        Some((offset, rel))
+    }
+}
+
+/// The relaxations for all sections of an object, keyed by section index.
+#[derive(Debug, Default)]
+pub(crate) struct Relaxations {
+    sections: HashMap<usize, SectionRelaxations>,
+}
+
+impl Relaxations {
+    pub(crate) fn get(&self, section_index: object::SectionIndex) -> Option<&SectionRelaxations> {
+        self.sections.get(&section_index.0)
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        section_index: object::SectionIndex,
+        relaxations: SectionRelaxations,
+    ) {
+        self.sections.insert(section_index.0, relaxations);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Returns where the byte at `offset` in the supplied input section ends up in the output.
+    pub(crate) fn output_offset(&self, section_index: object::SectionIndex, offset: u64) -> u64 {
+        self.get(section_index)
+            .map_or(offset, |relaxations| relaxations.output_offset(offset))
+    }
+}
+
+/// Which relaxations layout is currently looking for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    /// Shortening instruction sequences. Alignment padding is left as is.
+    Shrink,
+
+    /// Deleting alignment padding. The sequences that we shorten are fixed by now.
+    Align,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PlanOptions {
+    pub(crate) phase: Phase,
+
+    /// Whether addresses are known at link time, i.e. the output isn't position-independent. If
+    /// they aren't, then we can't drop the high part of an absolute address.
+    pub(crate) absolute_addresses: bool,
+
+    /// Whether the output may contain compressed instructions.
+    pub(crate) compressed: bool,
+
+    /// How far inside its range a target must be to allow for later changes in padding between
+    /// sections.
+    pub(crate) margin: u64,
+
+    /// Where `__global_pointer$` currently is, if we may relax accesses to nearby data to use gp
+    /// as their base.
+    pub(crate) global_pointer: Option<u64>,
+}
+
+/// Where the symbols that relocations reference are, which `plan_section` needs to know.
+pub(crate) trait RelocationTargets {
+    /// Returns the address that the symbol and addend of `rel` refer to given the previous pass's
+    /// decisions, or None if the symbol mightn't be referenced directly, e.g. because it's dynamic.
+    fn address(&self, rel: &Rela) -> Result<Option<u64>>;
+
+    /// Returns the offset of the symbol of `rel` if it's a label in the section that we're
+    /// planning. The low part of a PC-relative address references a label on its auipc.
+    fn label_offset(&self, rel: &Rela) -> Result<Option<u64>>;
+}
+
+/// Decides which relaxations to do in a section. `section_address` is where the section is
+/// currently expected to be and `previous` is what we decided in the previous pass, which is what
+/// section addresses were computed with.
+pub(crate) fn plan_section(
+    relocations: &[Rela],
+    data: &[u8],
+    section_address: u64,
+    section_alignment: u64,
+    previous: Option<&SectionRelaxations>,
+    options: PlanOptions,
+    targets: &impl RelocationTargets,
+) -> Result<SectionRelaxations> {
+    let e = LittleEndian;
+    let mut plan = SectionRelaxations::default();
+    let mut next_free_offset = 0;
+    // The symbol index and addend of each auipc that we delete, keyed by its offset, so that its
+    // low parts can be changed to refer to the same address.
+    let mut deleted_auipcs = HashMap::new();
+    let needed_auipcs = if options.global_pointer.is_some() && options.phase == Phase::Shrink {
+        needed_auipcs(relocations, data, targets)?
+    } else {
+        HashSet::new()
+    };
+    for (i, rel) in relocations.iter().enumerate() {
+        let offset = rel.r_offset.get(e);
+        if offset < next_free_offset {
+            continue;
+        }
+        let r_type = rel.r_type(e, false);
+        let previous_kind = previous
+            .and_then(|previous| previous.site(offset))
+            .map(|site| site.kind)
+            .filter(|kind| kind.applies_to(r_type) && !matches!(kind, SiteKind::Align { .. }));
+        let kind = if let Some(kind) = previous_kind {
+            Some(kind)
+        } else if r_type == object::elf::R_RISCV_ALIGN {
+            if options.phase == Phase::Align {
+                plan_align(rel, plan.total_deleted(), section_alignment)?
+            } else {
+                None
+            }
+        } else if options.phase == Phase::Shrink && is_marked_relax(relocations, i) {
+            let place = section_address + previous.map_or(offset, |p| p.output_offset(offset));
+            let bytes = data.get(offset as usize..).unwrap_or_default();
+            match r_type {
+                object::elf::R_RISCV_PCREL_HI20 if needed_auipcs.contains(&offset) => None,
+                object::elf::R_RISCV_PCREL_LO12_I | object::elf::R_RISCV_PCREL_LO12_S => {
+                    plan_pc_relative_low_part(rel, bytes, targets, &deleted_auipcs)?
+                }
+                _ => plan_relaxable(r_type, place, bytes, options, || targets.address(rel))?,
+            }
+        } else {
+            None
+        };
+        if let Some(kind) = kind {
+            plan.push(offset, kind);
+            next_free_offset = offset + kind.deleted_range().end.max(1);
+            if r_type == object::elf::R_RISCV_PCREL_HI20 {
+                deleted_auipcs.insert(offset, (rel.r_sym(e, false), rel.r_addend.get(e)));
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// Returns whether the relocation at index `i` is followed by an `R_RISCV_RELAX` at the same offset,
+/// which is how the compiler tells us that we may relax it.
+fn is_marked_relax(relocations: &[Rela], i: usize) -> bool {
+    let e = LittleEndian;
+    relocations.get(i + 1).is_some_and(|next| {
+        next.r_type(e, false) == object::elf::R_RISCV_RELAX
+            && next.r_offset.get(e) == relocations[i].r_offset.get(e)
+    })
+}
+
+/// Returns the offsets of the auipcs that we mustn't delete because they have a low part that we
+/// wouldn't relax. We only relax low parts that come after their auipc, since we decide what to do
+/// with each auipc before we get to the low parts that come after it.
+fn needed_auipcs(
+    relocations: &[Rela],
+    data: &[u8],
+    targets: &impl RelocationTargets,
+) -> Result<HashSet<u64>> {
+    let e = LittleEndian;
+    let mut needed = HashSet::new();
+    for (i, rel) in relocations.iter().enumerate() {
+        if !matches!(
+            rel.r_type(e, false),
+            object::elf::R_RISCV_PCREL_LO12_I | object::elf::R_RISCV_PCREL_LO12_S
+        ) {
+            continue;
+        }
+        let Some(label_offset) = targets.label_offset(rel)? else {
+            continue;
+        };
+        let offset = rel.r_offset.get(e);
+        let bytes = data.get(offset as usize..).unwrap_or_default();
+        if offset < label_offset || !is_marked_relax(relocations, i) || !has_base_register(bytes) {
+            needed.insert(label_offset);
+        }
+    }
+    Ok(needed)
+}
+
+/// Decides what to do with the low part of a PC-relative address, which we can only relax if we
+/// deleted its auipc.
+fn plan_pc_relative_low_part(
+    rel: &Rela,
+    bytes: &[u8],
+    targets: &impl RelocationTargets,
+    deleted_auipcs: &HashMap<u64, (u32, i64)>,
+) -> Result<Option<SiteKind>> {
+    if !has_base_register(bytes) {
+        return Ok(None);
+    }
+    let Some(label_offset) = targets.label_offset(rel)? else {
+        return Ok(None);
+    };
+    Ok(deleted_auipcs
+        .get(&label_offset)
+        .map(|&high_part| SiteKind::GlobalPointerLowPart {
+            high_part: Some(high_part),
+        }))
+}
+
+fn plan_align(rel: &Rela, deleted_before: u64, section_alignment: u64) -> Result<Option<SiteKind>> {
+    let e = LittleEndian;
+    let offset = rel.r_offset.get(e);
+    let padding = rel.r_addend.get(e) as u64;
+    if padding == 0 {
+        return Ok(None);
+    }
+    // The assembler emits enough padding to align the code that follows regardless of how it ends
+    // up being placed, which is 2 bytes less than the alignment when compressed instructions are
+    // available and 4 bytes less otherwise.
+    let alignment = (padding + 2).next_power_of_two();
+    if section_alignment < alignment {
+        bail!(
+            "R_RISCV_ALIGN at offset 0x{offset:x} requests alignment {alignment}, but the \
+             section is only aligned to {section_alignment}"
+        );
+    }
+    let position = offset - deleted_before;
+    let keep = position.next_multiple_of(alignment) - position;
+    if keep > padding {
+        bail!(
+            "R_RISCV_ALIGN at offset 0x{offset:x} needs {keep} bytes of padding, but only \
+             {padding} are available"
+        );
+    }
+    Ok(Some(SiteKind::Align { padding, keep }))
+}
+
+/// Decides what to do with a sequence that the compiler has told us that we may relax. `place` is
+/// the sequence's current address and `bytes` are the section's bytes from the start of it.
+fn plan_relaxable(
+    r_type: u32,
+    place: u64,
+    bytes: &[u8],
+    options: PlanOptions,
+    target_address: impl FnOnce() -> Result<Option<u64>>,
+) -> Result<Option<SiteKind>> {
+    let is_in_range_with_margin = |target: u64, range: u64| {
+        range > options.margin && is_in_range(place, target, range - options.margin)
+    };
+    let fits_with_margin = |value: u64| {
+        fits_in_i12(value.wrapping_add(options.margin))
+            && fits_in_i12(value.wrapping_sub(options.margin))
+    };
+    // The global pointer is at a fixed offset from the start of the data. Data that comes after
+    // that start stays where it is relative to the global pointer as code shrinks, but we still
+    // allow for padding changing.
+    let is_near_global_pointer = |target: u64| {
+        options.global_pointer.is_some_and(|gp| {
+            target >= gp.wrapping_sub(GLOBAL_POINTER_OFFSET)
+                && fits_with_margin(target.wrapping_sub(gp))
+        })
+    };
+    let kind = match r_type {
+        object::elf::R_RISCV_CALL | object::elf::R_RISCV_CALL_PLT => {
+            let Some(link_register) = call_link_register(bytes) else {
+                return Ok(None);
+            };
+            let Some(target) = target_address()? else {
+                return Ok(None);
+            };
+            if options.compressed
+                && link_register == ZERO_REGISTER
+                && is_in_range_with_margin(target, C_J_RANGE)
+            {
+                SiteKind::CallToCJump
+            } else if is_in_range_with_margin(target, JAL_RANGE) {
+                SiteKind::CallToJal { link_register }
+            } else {
+                return Ok(None);
+            }
+        }
+        object::elf::R_RISCV_HI20 if options.absolute_addresses && is_lui(bytes) => {
+            let Some(target) = target_address()? else {
+                return Ok(None);
+            };
+            if !fits_with_margin(target) && !is_near_global_pointer(target) {
+                return Ok(None);
+            }
+            SiteKind::DeletedHighPart
+        }
+        object::elf::R_RISCV_PCREL_HI20 if options.absolute_addresses && is_auipc(bytes) => {
+            let Some(target) = target_address()? else {
+                return Ok(None);
+            };
+            if !is_near_global_pointer(target) {
+                return Ok(None);
+            }
+            SiteKind::DeletedHighPart
+        }
+        object::elf::R_RISCV_LO12_I | object::elf::R_RISCV_LO12_S
+            if options.absolute_addresses && has_base_register(bytes) =>
+        {
+            let Some(target) = target_address()? else {
+                return Ok(None);
+            };
+            if fits_with_margin(target) {
+                SiteKind::LowPart {
+                    base_register: ZERO_REGISTER,
+                }
+            } else if is_near_global_pointer(target) {
+                SiteKind::GlobalPointerLowPart { high_part: None }
+            } else {
+                return Ok(None);
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rela(offset: u64, r_type: u32, addend: i64) -> Rela {
+        let e = LittleEndian;
+        let mut rel = Rela {
+            r_offset: Default::default(),
+            r_info: Default::default(),
+            r_addend: Default::default(),
+        };
+        rel.r_offset.set(e, offset);
+        rel.set_r_info(e, false, 1, r_type);
+        rel.r_addend.set(e, addend);
+        rel
+    }
+
+    const OPTIONS: PlanOptions = PlanOptions {
+        phase: Phase::Shrink,
+        absolute_addresses: true,
+        compressed: true,
+        margin: 16,
+        global_pointer: None,
+    };
+
+    /// Returns the supplied address for each relocation. Symbols with an index of at least
+    /// `LABEL` are labels at an offset of the index minus `LABEL`.
+    struct Targets<F>(F);
+
+    const LABEL: u32 = 100;
+
+    impl<F: Fn(&Rela) -> Option<u64>> RelocationTargets for Targets<F> {
+        fn address(&self, rel: &Rela) -> Result<Option<u64>> {
+            Ok((self.0)(rel))
+        }
+
+        fn label_offset(&self, rel: &Rela) -> Result<Option<u64>> {
+            let symbol_index = rel.r_sym(LittleEndian, false);
+            Ok((symbol_index >= LABEL).then(|| u64::from(symbol_index - LABEL)))
+        }
+    }
+
+    /// call f; nop; nop; .p2align 3; lui a0, %hi(x); addi a0, a0, %lo(x)
+    fn code() -> (Vec<u8>, Vec<Rela>) {
+        let mut data = Vec::new();
+        // auipc ra, 0; jalr ra, 0(ra)
+        data.extend_from_slice(&[0x97, 0x00, 0x00, 0x00, 0xe7, 0x80, 0x00, 0x00]);
+        // Padding: nop; c.nop
+        data.extend_from_slice(&[0x13, 0x00, 0x00, 0x00, 0x01, 0x00]);
+        // lui a0, 0; addi a0, a0, 0
+        data.extend_from_slice(&[0x37, 0x05, 0x00, 0x00, 0x13, 0x05, 0x05, 0x00]);
+        let relocations = vec![
+            rela(0, object::elf::R_RISCV_CALL_PLT, 0),
+            rela(0, object::elf::R_RISCV_RELAX, 0),
+            rela(8, object::elf::R_RISCV_ALIGN, 6),
+            rela(14, object::elf::R_RISCV_HI20, 0),
+            rela(14, object::elf::R_RISCV_RELAX, 0),
+            rela(18, object::elf::R_RISCV_LO12_I, 0),
+            rela(18, object::elf::R_RISCV_RELAX, 0),
+        ];
+        (data, relocations)
+    }
+
+    #[test]
+    fn test_plan_section() {
+        let (data, relocations) = code();
+        let targets = Targets(|rel: &Rela| {
+            Some(if rel.r_offset.get(LittleEndian) == 0 {
+                0x10100
+            } else {
+                0x100
+            })
+        });
+        let shrink =
+            plan_section(&relocations, &data, 0x10000, 8, None, OPTIONS, &targets).unwrap();
+        assert_eq!(shrink.total_deleted(), 8);
+        // The call becomes a jal, so the 4 bytes after it get deleted.
+        assert_eq!(shrink.output_offset(4), 4);
+        assert_eq!(shrink.output_offset(6), 4);
+        assert_eq!(shrink.output_offset(8), 4);
+        // The lui gets deleted too.
+        assert_eq!(shrink.output_offset(14), 10);
+        assert_eq!(shrink.output_offset(18), 10);
+        assert_eq!(shrink.output_offset(22), 14);
+
+        // A later pass keeps what we've already decided on, even without a target address.
+        let again = plan_section(
+            &relocations,
+            &data,
+            0x10000,
+            8,
+            Some(&shrink),
+            OPTIONS,
+            &Targets(|_: &Rela| None),
+        )
+        .unwrap();
+        assert_eq!(again, shrink);
+
+        // Padding now starts at offset 4, so only 4 of its 6 bytes are needed.
+        let align = plan_section(
+            &relocations,
+            &data,
+            0x10000,
+            8,
+            Some(&shrink),
+            PlanOptions {
+                phase: Phase::Align,
+                ..OPTIONS
+            },
+            &Targets(|_: &Rela| None),
+        )
+        .unwrap();
+        assert_eq!(align.total_deleted(), 10);
+        assert_eq!(align.output_offset(14), 8);
+
+        let mut out = vec![0; data.len() - 10];
+        align.copy_data(&data, &mut out).unwrap();
+        let applied: Vec<_> = relocations
+            .iter()
+            .filter_map(|rel| align.relax_relocation(rel, &mut out))
+            .map(|(offset, rel)| (offset, rel.r_type(LittleEndian, false)))
+            .collect();
+        assert_eq!(
+            applied,
+            [
+                (0, object::elf::R_RISCV_JAL),
+                (0, object::elf::R_RISCV_RELAX),
+                (8, object::elf::R_RISCV_RELAX),
+                (8, object::elf::R_RISCV_LO12_I),
+                (8, object::elf::R_RISCV_RELAX),
+            ]
+        );
+        assert_eq!(
+            out,
+            [
+                0xef, 0x00, 0x00, 0x00, // jal ra, .
+                0x13, 0x00, 0x00, 0x00, // nop
+                0x13, 0x05, 0x00, 0x00, // addi a0, zero, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let (data, relocations) = code();
+        let targets = Targets(|rel: &Rela| {
+            Some(if rel.r_offset.get(LittleEndian) == 0 {
+                0x10000 + JAL_RANGE
+            } else {
+                0x800
+            })
+        });
+        let plan = plan_section(&relocations, &data, 0x10000, 8, None, OPTIONS, &targets).unwrap();
+        assert_eq!(plan, SectionRelaxations::default());
+
+        // Without an R_RISCV_RELAX, nothing is relaxed.
+        let plan = plan_section(
+            &[rela(0, object::elf::R_RISCV_CALL_PLT, 0)],
+            &data,
+            0x10000,
+            8,
+            None,
+            OPTIONS,
+            &Targets(|_: &Rela| Some(0x10000)),
+        )
+        .unwrap();
+        assert_eq!(plan, SectionRelaxations::default());
+    }
+
+    #[test]
+    fn test_align_errors() {
+        let (data, _) = code();
+        let relocations = [rela(8, object::elf::R_RISCV_ALIGN, 6)];
+        let options = PlanOptions {
+            phase: Phase::Align,
+            ..OPTIONS
+        };
+        let targets = Targets(|_: &Rela| None);
+        assert!(plan_section(&relocations, &data, 0, 4, None, options, &targets).is_err());
+        let relocations = [rela(2, object::elf::R_RISCV_ALIGN, 4)];
+        assert!(plan_section(&relocations, &data, 0, 8, None, options, &targets).is_err());
+    }
+
+    #[test]
+    fn test_global_pointer() {
+        let e = LittleEndian;
+        let mut data = Vec::new();
+        // lui a0, 0; lw a0, 0(a0)
+        data.extend_from_slice(&[0x37, 0x05, 0x00, 0x00, 0x03, 0x25, 0x05, 0x00]);
+        // auipc a1, 0; sw a2, 0(a1)
+        data.extend_from_slice(&[0x97, 0x05, 0x00, 0x00, 0x23, 0xa0, 0xc5, 0x00]);
+        // auipc a3, 0; ld a3, 0(a3)
+        data.extend_from_slice(&[0x97, 0x06, 0x00, 0x00, 0x83, 0xb6, 0x06, 0x00]);
+        let mut relocations = vec![
+            rela(0, object::elf::R_RISCV_HI20, 0),
+            rela(0, object::elf::R_RISCV_RELAX, 0),
+            rela(4, object::elf::R_RISCV_LO12_I, 0),
+            rela(4, object::elf::R_RISCV_RELAX, 0),
+            rela(8, object::elf::R_RISCV_PCREL_HI20, 4),
+            rela(8, object::elf::R_RISCV_RELAX, 0),
+            rela(12, object::elf::R_RISCV_PCREL_LO12_S, 0),
+            rela(12, object::elf::R_RISCV_RELAX, 0),
+            rela(16, object::elf::R_RISCV_PCREL_HI20, 4),
+            rela(16, object::elf::R_RISCV_RELAX, 0),
+            // Without an R_RISCV_RELAX, this stops the auipc that it's paired with being deleted.
+            rela(20, object::elf::R_RISCV_PCREL_LO12_I, 0),
+        ];
+        relocations[6].set_r_info(e, false, LABEL + 8, object::elf::R_RISCV_PCREL_LO12_S);
+        relocations[10].set_r_info(e, false, LABEL + 16, object::elf::R_RISCV_PCREL_LO12_I);
+        let options = PlanOptions {
+            global_pointer: Some(0x12800),
+            ..OPTIONS
+        };
+
+        let far = Targets(|_: &Rela| Some(0x13000));
+        let plan = plan_section(&relocations, &data, 0x10000, 8, None, options, &far).unwrap();
+        assert_eq!(plan, SectionRelaxations::default());
+
+        let near = Targets(|_: &Rela| Some(0x12900));
+        let plan = plan_section(&relocations, &data, 0x10000, 8, None, options, &near).unwrap();
+        assert_eq!(plan.total_deleted(), 8);
+
+        let mut out = vec![0; data.len() - 8];
+        plan.copy_data(&data, &mut out).unwrap();
+        let applied: Vec<_> = relocations
+            .iter()
+            .filter_map(|rel| plan.relax_relocation(rel, &mut out))
+            .map(|(offset, rel)| (offset, rel.r_type(e, false), rel.r_sym(e, false)))
+            .collect();
+        assert_eq!(
+            applied,
+            [
+                (0, object::elf::R_RISCV_RELAX, 1),
+                (0, object::elf::R_RISCV_GPREL_I, 1),
+                (0, object::elf::R_RISCV_RELAX, 1),
+                (4, object::elf::R_RISCV_RELAX, 1),
+                // The low part now refers to what the auipc did.
+                (4, object::elf::R_RISCV_GPREL_S, 1),
+                (4, object::elf::R_RISCV_RELAX, 1),
+                (8, object::elf::R_RISCV_PCREL_HI20, 1),
+                (8, object::elf::R_RISCV_RELAX, 1),
+                (12, object::elf::R_RISCV_PCREL_LO12_I, LABEL + 16),
+            ]
+        );
+        assert_eq!(
+            out,
+            [
+                0x03, 0xa5, 0x01, 0x00, // lw a0, 0(gp)
+                0x23, 0xa0, 0xc1, 0x00, // sw a2, 0(gp)
+                0x97, 0x06, 0x00, 0x00, // auipc a3, 0
+                0x83, 0xb6, 0x06, 0x00, // ld a3, 0(a3)
+            ]
+        );
+        let rel = plan.relax_relocation(&relocations[6], &mut out).unwrap().1;
+        assert_eq!(rel.r_addend.get(e), 4);
+    }
+}
@@ -0,0 +1,271 @@
+//! Splitting debug info into a separate file (`--separate-debug-file`). This does the equivalent of
+//! `objcopy --only-keep-debug`, `strip --strip-debug` and `objcopy --add-gnu-debuglink`, but
+//! without needing to read and rewrite the whole output afterwards.
+//!
+//! When splitting, debug info sections are placed after all other sections, both in the file and
+//! in the section header table. The main output then stops where the debug info would start and
+//! the debug info is written directly into the debug file, which is created alongside the main
+//! output. Once everything else has been written, we fill in the headers of the debug file, copy
+//! the few other sections that it needs from the main output and shorten the section header table
+//! of the main output so that it no longer refers to the debug info.
+//!
+//! The debug file has the same section headers as the main output, so section indexes and
+//! addresses match. Allocated sections, other than notes, are marked as NOBITS and their
+//! contents omitted. We keep the notes so that debuggers that find the debug file via its build
+//! ID can check that it has the same build ID as the main output. The main output gets a
+//! `.gnu_debuglink` section containing the name of the debug file and its CRC32, which is how
+//! debuggers find it otherwise.
+
+use crate::alignment;
+use crate::args::Args;
+use crate::elf::FileHeader;
+use crate::elf::SectionHeader;
+use crate::error::Result;
+use crate::layout::OutputRecordLayout;
+use crate::output_section_id;
+use crate::output_section_id::OutputSections;
+use crate::output_section_map::OutputSectionMap;
+use anyhow::bail;
+use anyhow::Context;
+use linker_utils::elf::shf;
+use linker_utils::elf::sht;
+use linker_utils::elf::SectionFlags;
+use linker_utils::elf::SectionType;
+use object::LittleEndian;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Returns the size of our `.gnu_debuglink` section, or 0 if we're not writing one. The section
+/// contains the null-terminated name of the debug file, padded to a multiple of 4 bytes, followed
+/// by the CRC32 of the debug file.
+pub(crate) fn debuglink_size(args: &Args) -> u64 {
+    debuglink_name(args).map_or(0, |name| crc_offset(name) + 4)
+}
+
+/// Writes the name of the debug file into our `.gnu_debuglink` section. The CRC is filled in by
+/// `finish_debug_file` once the debug file has been written.
+pub(crate) fn write_debuglink_name(out: &mut [u8], args: &Args) -> Result {
+    let Some(name) = debuglink_name(args) else {
+        return Ok(());
+    };
+    let Some(name_out) = out.get_mut(..name.len()) else {
+        bail!("Insufficient space allocated to .gnu_debuglink");
+    };
+    name_out.copy_from_slice(name);
+    Ok(())
+}
+
+/// Where things go when we split debug info into a separate file.
+pub(crate) struct DebugSplit {
+    pub(crate) path: Arc<Path>,
+
+    /// The offset in our output at which debug info starts. The main output file stops here.
+    pub(crate) debug_start: usize,
+
+    /// The offset in the debug file of the debug info. This is congruent to `debug_start` modulo
+    /// the alignment of each debug info section, so that they stay aligned.
+    pub(crate) debug_offset: usize,
+
+    /// The size of the debug file.
+    pub(crate) file_size: usize,
+
+    /// The index of the section header of the first debug info section.
+    first_debug_index: u16,
+
+    /// The index of the section header of `.gnu_debuglink`.
+    debuglink_index: u16,
+
+    /// The offset in the main output at which to write the CRC of the debug file.
+    crc_offset: usize,
+
+    /// For each section header, the offset in the debug file of its contents if, other than debug
+    /// info, it's a section whose contents we copy there.
+    copied_offsets: Vec<Option<usize>>,
+}
+
+impl DebugSplit {
+    /// Decides where things go in the debug file, if we're writing one. `file_size` is the size
+    /// that our output would be if we weren't splitting it.
+    pub(crate) fn new(
+        args: &Args,
+        output_sections: &OutputSections,
+        section_layouts: &OutputSectionMap<OutputRecordLayout>,
+        file_size: u64,
+    ) -> Result<Option<DebugSplit>> {
+        let Some(path) = args.separate_debug_file.as_deref() else {
+            return Ok(None);
+        };
+        let file_size = file_size as usize;
+        let num_headers = section_layouts
+            .get(output_section_id::SECTION_HEADERS)
+            .file_size
+            / size_of::<SectionHeader>();
+        let mut section_ids = vec![None; num_headers];
+        for (section_id, _) in output_sections.ids_with_info() {
+            if let Some(index) = output_sections.output_index_of_section(section_id) {
+                *section_ids
+                    .get_mut(usize::from(index))
+                    .context("Section index is past the end of the section headers")? =
+                    Some(section_id);
+            }
+        }
+        let debuglink_index = output_sections
+            .output_index_of_section(output_section_id::GNU_DEBUGLINK)
+            .context("Missing .gnu_debuglink")?;
+        let name = debuglink_name(args).context("Missing debug file name")?;
+        let crc_offset = section_layouts
+            .get(output_section_id::GNU_DEBUGLINK)
+            .file_offset
+            + self::crc_offset(name) as usize;
+
+        // Program headers are omitted from the debug file, since they aren't needed in order to
+        // read the debug info, so the section headers come straight after the file header.
+        let mut offset = size_of::<FileHeader>() + num_headers * size_of::<SectionHeader>();
+        let mut copied_offsets = vec![None; num_headers];
+        let mut debug_start = None;
+        let mut debug_alignment = alignment::MIN;
+        for (index, section_id) in section_ids.iter().enumerate() {
+            let Some(section_id) = *section_id else {
+                continue;
+            };
+            let flags = output_sections.section_flags(section_id);
+            let section_layout = section_layouts.get(section_id);
+            if is_debug_info(output_sections.name(section_id).bytes(), flags) {
+                let (first_index, first_offset) =
+                    debug_start.get_or_insert((index, section_layout.file_offset));
+                *first_index = (*first_index).min(index);
+                *first_offset = (*first_offset).min(section_layout.file_offset);
+                debug_alignment = debug_alignment.max(section_layout.alignment);
+            } else if index != usize::from(debuglink_index)
+                && keeps_contents(output_sections.section_type(section_id), flags)
+            {
+                offset = section_layout.alignment.align_up_usize(offset);
+                copied_offsets[index] = Some(offset);
+                offset += section_layout.file_size;
+            }
+        }
+        let (first_debug_index, debug_start) = debug_start.unwrap_or((num_headers, file_size));
+        let debug_offset =
+            debug_alignment.align_up_usize(offset) + debug_start % debug_alignment.value() as usize;
+
+        Ok(Some(DebugSplit {
+            path: Arc::from(path),
+            debug_start,
+            debug_offset,
+            file_size: debug_offset + (file_size - debug_start),
+            first_debug_index: u16::try_from(first_debug_index)?,
+            debuglink_index,
+            crc_offset,
+            copied_offsets,
+        }))
+    }
+}
+
+/// Completes the debug file `debug_image`, into which we've already written the debug info, then
+/// records its CRC in `.gnu_debuglink` in the main output, `image`. The section header table of the
+/// main output is shortened so that it no longer includes the debug info.
+#[tracing::instrument(skip_all, name = "Write separate debug file")]
+pub(crate) fn finish_debug_file(
+    split: &DebugSplit,
+    image: &mut [u8],
+    debug_image: &mut [u8],
+) -> Result {
+    let e = LittleEndian;
+    let (header, _) = object::from_bytes::<FileHeader>(image)
+        .map_err(|_| anyhow::anyhow!("Output is too small to contain an ELF header"))?;
+    let shoff = header.e_shoff.get(e) as usize;
+    let shnum = usize::from(header.e_shnum.get(e));
+    let (section_headers, _) = object::slice_from_bytes::<SectionHeader>(
+        image
+            .get(shoff..)
+            .context("Section headers are past the end of the output")?,
+        shnum,
+    )
+    .map_err(|_| anyhow::anyhow!("Section headers are past the end of the output"))?;
+
+    // If we're updating the debug file in place, whatever was there before mustn't show through.
+    debug_image[..split.debug_offset].fill(0);
+
+    let mut new_header = *header;
+    new_header.e_phoff.set(e, 0);
+    new_header.e_phnum.set(e, 0);
+    new_header.e_phentsize.set(e, 0);
+    new_header.e_shoff.set(e, size_of::<FileHeader>() as u64);
+
+    // The header of `.gnu_debuglink` is replaced with an empty one, since tools that find the debug
+    // file would otherwise try to follow the link again from there. Allocated sections, other than
+    // notes, keep their headers, but are marked as NOBITS.
+    let mut new_section_headers = Vec::with_capacity(shnum);
+    for (index, section) in section_headers.iter().enumerate() {
+        let mut new_section = *section;
+        let offset = section.sh_offset.get(e) as usize;
+        if index == usize::from(split.debuglink_index) {
+            object::bytes_of_mut(&mut new_section).fill(0);
+        } else if index >= usize::from(split.first_debug_index) {
+            new_section
+                .sh_offset
+                .set(e, (offset - split.debug_start + split.debug_offset) as u64);
+        } else if let Some(new_offset) = split.copied_offsets.get(index).copied().flatten() {
+            let size = section.sh_size.get(e) as usize;
+            debug_image
+                .get_mut(new_offset..new_offset + size)
+                .context("Insufficient space allocated in debug file")?
+                .copy_from_slice(
+                    image
+                        .get(offset..offset + size)
+                        .context("Section extends past the end of the output")?,
+                );
+            new_section.sh_offset.set(e, new_offset as u64);
+        } else if SectionType::from_header(section) != sht::NULL {
+            new_section.sh_type.set(e, sht::NOBITS.raw());
+        }
+        new_section_headers.push(new_section);
+    }
+    let headers_size = size_of::<FileHeader>() + shnum * size_of::<SectionHeader>();
+    debug_image[..size_of::<FileHeader>()].copy_from_slice(object::bytes_of(&new_header));
+    debug_image[size_of::<FileHeader>()..headers_size]
+        .copy_from_slice(object::bytes_of_slice(&new_section_headers));
+
+    let crc = crc32fast::hash(debug_image);
+    image
+        .get_mut(split.crc_offset..split.crc_offset + 4)
+        .context("Insufficient space allocated to .gnu_debuglink")?
+        .copy_from_slice(&crc.to_le_bytes());
+
+    // Clear the headers that we dropped, since they'd otherwise be left as junk in the file.
+    let (header, _) = object::from_bytes_mut::<FileHeader>(image)
+        .map_err(|_| anyhow::anyhow!("Output is too small to contain an ELF header"))?;
+    header.e_shnum.set(e, split.first_debug_index);
+    let section_header_size = size_of::<SectionHeader>();
+    image[shoff + usize::from(split.first_debug_index) * section_header_size
+        ..shoff + shnum * section_header_size]
+        .fill(0);
+
+    Ok(())
+}
+
+/// Returns whether the debug file gets the contents of a section of type `ty`. We keep notes so
+/// that debuggers that find the debug file via its build ID can check that it has the same build ID
+/// as the main output.
+fn keeps_contents(ty: SectionType, flags: SectionFlags) -> bool {
+    ty != sht::NOBITS && ty != sht::NULL && (ty == sht::NOTE || !flags.contains(shf::ALLOC))
+}
+
+fn is_debug_info(name: &[u8], flags: SectionFlags) -> bool {
+    name.starts_with(b".debug_") && !flags.contains(shf::ALLOC)
+}
+
+/// Returns the name to put in `.gnu_debuglink`. Debuggers look for a file with this name in the
+/// same directory as the output and in various other places, so it doesn't include a directory.
+fn debuglink_name(args: &Args) -> Option<&[u8]> {
+    Some(
+        args.separate_debug_file
+            .as_deref()?
+            .file_name()?
+            .as_encoded_bytes(),
+    )
+}
+
+fn crc_offset(name: &[u8]) -> u64 {
+    crate::alignment::GNU_DEBUGLINK.align_up(name.len() as u64 + 1)
+}
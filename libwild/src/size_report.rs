@@ -0,0 +1,270 @@
+//! Optionally writes a report of what contributes to the size of the output. To use this, pass
+//! `--size-report=/path/to/report.txt`. Add `--size-report-format=json` to get JSON instead of
+//! text, which is easier for tools that track size regressions to consume.
+//!
+//! Sizes are broken down three ways: by output section, by input file, with archive members listed
+//! separately, and by the Rust crate that each function or variable belongs to, which we get from
+//! the symbol's mangled name. Symbols that don't have a Rust mangled name are counted against
+//! `[non-rust]`. Input files and crates only include what was copied from the inputs, so things
+//! that we generate, such as the GOT, PLT and symbol tables, only appear in the section breakdown.
+
+use crate::args::SizeReportFormat;
+use crate::error::Result;
+use crate::layout::FileLayout;
+use crate::layout::Layout;
+use crate::output_section_id;
+use crate::resolution::SectionSlot;
+use crate::storage::StorageModel;
+use anyhow::Context as _;
+use itertools::Itertools;
+use linker_utils::elf::shf;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+use std::collections::HashMap;
+use std::io::Write as _;
+use std::path::Path;
+
+pub(crate) fn maybe_write_size_report<S: StorageModel>(layout: &Layout<S>) -> Result {
+    let Some(report_path) = layout.args().size_report.as_ref() else {
+        return Ok(());
+    };
+    write_size_report(layout, report_path)
+        .with_context(|| format!("Failed to write size report `{}`", report_path.display()))
+}
+
+struct SizeReport {
+    /// Output sections with their file size and their size in memory. Sections that don't get
+    /// loaded at runtime take up no memory.
+    sections: Vec<(String, u64, u64)>,
+    files: Vec<(String, u64)>,
+    crates: Vec<(String, u64)>,
+}
+
+const NON_RUST: &str = "[non-rust]";
+
+fn write_size_report<S: StorageModel>(layout: &Layout<S>, report_path: &Path) -> Result {
+    let mut sections = Vec::new();
+    for (section_id, info) in layout.output_sections.ids_with_info() {
+        let section_layout = layout.section_layouts.get(section_id);
+        if section_layout.file_size == 0 && section_layout.mem_size == 0 {
+            continue;
+        }
+        let name = if section_id == output_section_id::FILE_HEADER {
+            "[file header]".to_owned()
+        } else {
+            String::from_utf8_lossy(info.name.bytes()).into_owned()
+        };
+        let mem_size = if info.section_flags.contains(shf::ALLOC) {
+            section_layout.mem_size
+        } else {
+            0
+        };
+        sections.push((name, section_layout.file_size as u64, mem_size));
+    }
+
+    let mut files = Vec::new();
+    let mut crates: HashMap<String, u64> = HashMap::new();
+    for file in layout.group_layouts.iter().flat_map(|group| &group.files) {
+        let FileLayout::Object(obj) = file else {
+            continue;
+        };
+        let size: u64 = obj
+            .sections
+            .iter()
+            .map(|slot| match slot {
+                SectionSlot::Loaded(section) | SectionSlot::LoadedDebugInfo(section) => {
+                    section.size
+                }
+                _ => 0,
+            })
+            .sum();
+        if size > 0 {
+            files.push((obj.input.to_string(), size));
+        }
+
+        for (symbol_index, sym) in obj.object.symbols.enumerate() {
+            if !matches!(
+                sym.st_type(),
+                object::elf::STT_FUNC | object::elf::STT_OBJECT | object::elf::STT_TLS
+            ) || sym.st_size(LittleEndian) == 0
+            {
+                continue;
+            }
+            let Some(section_index) = obj.object.symbol_section(sym, symbol_index)? else {
+                continue;
+            };
+            if !matches!(obj.sections[section_index.0], SectionSlot::Loaded(..)) {
+                continue;
+            }
+            let symbol_id = obj.symbol_id_range.input_to_id(symbol_index);
+            if !layout.symbol_db.is_canonical(symbol_id) {
+                continue;
+            }
+            let name = obj.object.symbol_name(sym)?;
+            let crate_name = rust_crate_name(name).unwrap_or_else(|| NON_RUST.to_owned());
+            *crates.entry(crate_name).or_default() += sym.st_size(LittleEndian);
+        }
+    }
+
+    let mut report = SizeReport {
+        sections,
+        files,
+        crates: crates.into_iter().collect(),
+    };
+    report.sort();
+
+    let mut out = std::io::BufWriter::new(std::fs::File::create(report_path)?);
+    match layout.args().size_report_format {
+        SizeReportFormat::Text => report.write_text(&mut out)?,
+        SizeReportFormat::Json => report.write_json(&mut out)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+impl SizeReport {
+    /// Sorts each breakdown, biggest first.
+    fn sort(&mut self) {
+        self.sections
+            .sort_by(|a, b| b.2.max(b.1).cmp(&a.2.max(a.1)).then_with(|| a.0.cmp(&b.0)));
+        self.files
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        self.crates
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    }
+
+    fn write_text(&self, out: &mut impl std::io::Write) -> Result {
+        writeln!(out, "{:>12} {:>12}  section", "file size", "vm size")?;
+        for (name, file_size, mem_size) in &self.sections {
+            writeln!(out, "{file_size:>12} {mem_size:>12}  {name}")?;
+        }
+        writeln!(out)?;
+        writeln!(out, "{:>12}  input file", "size")?;
+        for (name, size) in &self.files {
+            writeln!(out, "{size:>12}  {name}")?;
+        }
+        writeln!(out)?;
+        writeln!(out, "{:>12}  crate", "size")?;
+        for (name, size) in &self.crates {
+            writeln!(out, "{size:>12}  {name}")?;
+        }
+        Ok(())
+    }
+
+    fn write_json(&self, out: &mut impl std::io::Write) -> Result {
+        let sections = self
+            .sections
+            .iter()
+            .map(|(name, file_size, mem_size)| {
+                format!(
+                    "{{\"name\":{},\"file_size\":{file_size},\"vm_size\":{mem_size}}}",
+                    json_string(name)
+                )
+            })
+            .join(",");
+        let entries = |entries: &[(String, u64)]| {
+            entries
+                .iter()
+                .map(|(name, size)| format!("{{\"name\":{},\"size\":{size}}}", json_string(name)))
+                .join(",")
+        };
+        writeln!(
+            out,
+            "{{\"sections\":[{sections}],\"files\":[{}],\"crates\":[{}]}}",
+            entries(&self.files),
+            entries(&self.crates)
+        )?;
+        Ok(())
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if u32::from(c) < 0x20 => out.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Returns the name of the crate that the supplied symbol belongs to if it has a Rust mangled name.
+/// Both the legacy and v0 mangling schemes are supported. Trait impls like `<foo::Bar as
+/// core::fmt::Debug>::fmt` are attributed to the crate of the type.
+fn rust_crate_name(name: &[u8]) -> Option<String> {
+    if name.starts_with(b"_ZN") {
+        // Legacy mangling uses the same scheme as C++, but always ends with a hash.
+        let hash_suffix_len = b"17h0123456789abcdefE".len();
+        if name.len() < hash_suffix_len || !name[name.len() - hash_suffix_len..].starts_with(b"17h")
+        {
+            return None;
+        }
+    } else if !name.starts_with(b"_R") {
+        return None;
+    }
+    let demangled = rustc_demangle::try_demangle(std::str::from_utf8(name).ok()?).ok()?;
+    let demangled = format!("{demangled:#}");
+    let path = demangled.trim_start_matches('<');
+    let crate_name = &path[..path.find("::")?];
+    // Anything other than an identifier means that the path didn't start with a crate, e.g.
+    // because it's an impl for a slice or a reference.
+    if crate_name.is_empty()
+        || !crate_name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_')
+    {
+        return None;
+    }
+    Some(crate_name.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_crate_name() {
+        assert_eq!(
+            rust_crate_name(b"_ZN4core3fmt5write17h0123456789abcdefE").as_deref(),
+            Some("core")
+        );
+        assert_eq!(
+            rust_crate_name(b"_RNvNtCs1234_7mycrate3foo3bar").as_deref(),
+            Some("mycrate")
+        );
+        assert_eq!(
+            rust_crate_name(b"_RNvCsfRbg5hJ4A6u_5hello4main").as_deref(),
+            Some("hello")
+        );
+        // Crate names that start with an underscore.
+        assert_eq!(
+            rust_crate_name(b"_ZN8_private3foo17h0123456789abcdefE").as_deref(),
+            Some("_private")
+        );
+        assert_eq!(
+            rust_crate_name(b"_RNvCs1234_8__private4main").as_deref(),
+            Some("_private")
+        );
+        // A trait impl, which we attribute to the crate of the type.
+        assert_eq!(
+            rust_crate_name(
+                b"_ZN49_$LT$mycrate..Foo$u20$as$u20$core..fmt..Debug$GT$3fmt17h0123456789abcdefE"
+            )
+            .as_deref(),
+            Some("mycrate")
+        );
+        // C++ names use the same prefix as the legacy Rust scheme, but don't have a hash.
+        assert_eq!(rust_crate_name(b"_ZN3foo3barEv"), None);
+        assert_eq!(rust_crate_name(b"main"), None);
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+}
@@ -0,0 +1,241 @@
+//! Support for code compiled with `-fsplit-stack`, which is used by gccgo and some other runtimes.
+//! Functions compiled this way start with a check that there's enough space left in the current
+//! stack segment and if there isn't, call `__morestack` to allocate a new segment. Segments are
+//! small, which is fine so long as all the code is split-stack, but code that isn't, e.g. libc,
+//! expects a normally sized stack.
+//!
+//! Objects containing split-stack code have a `.note.GNU-split-stack` section. Like gold, when a
+//! function in such an object calls a function that isn't split-stack, we rewrite the function's
+//! prologue so that it allocates a larger stack segment (or always allocates a new segment) and
+//! make it call `__morestack_non_split` rather than `__morestack`. We only support this on x86-64.
+
+use crate::arch::Architecture;
+use crate::elf::File;
+use crate::error::Result;
+use crate::layout::FileLayout;
+use crate::layout::Layout;
+use crate::layout::ObjectLayout;
+use crate::layout::Section;
+use crate::parsing::ParsedInput;
+use crate::storage::StorageModel;
+use crate::storage::SymbolNameMap as _;
+use crate::symbol::SymbolName;
+use anyhow::bail;
+use anyhow::Context as _;
+use object::read::elf::Rela as _;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+
+const SPLIT_STACK_SECTION_NAME: &[u8] = b".note.GNU-split-stack";
+const NO_SPLIT_STACK_SECTION_NAME: &[u8] = b".note.GNU-no-split-stack";
+
+pub(crate) const MORESTACK_SYMBOL_NAME: &[u8] = b"__morestack";
+pub(crate) const MORESTACK_NON_SPLIT_SYMBOL_NAME: &[u8] = b"__morestack_non_split";
+
+/// How much extra stack space we ask for by default when a split-stack function calls a function
+/// that isn't split-stack. This matches gold.
+pub(crate) const DEFAULT_SPLIT_STACK_ADJUST_SIZE: u32 = 0x4000;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SplitStack {
+    /// Whether the object contains split-stack code.
+    pub(crate) uses_split_stack: bool,
+
+    /// Whether the object also contains functions built with the `no_split_stack` attribute, in
+    /// which case we don't complain about functions whose prologues we don't recognise.
+    pub(crate) has_no_split_stack: bool,
+}
+
+impl SplitStack {
+    pub(crate) fn from_object(object: &File) -> Result<SplitStack> {
+        let mut split_stack = SplitStack::default();
+        for section in object.sections.iter() {
+            let name = object.section_name(section)?;
+            if name == SPLIT_STACK_SECTION_NAME {
+                split_stack.uses_split_stack = true;
+            } else if name == NO_SPLIT_STACK_SECTION_NAME {
+                split_stack.has_no_split_stack = true;
+            }
+        }
+        Ok(split_stack)
+    }
+}
+
+/// Rewrites functions in `section` that call functions that aren't split-stack. Must be called
+/// after relocations have been applied to `out`.
+pub(crate) fn adjust_calls_to_non_split<S: StorageModel>(
+    object_layout: &ObjectLayout,
+    section: &Section,
+    section_address: u64,
+    out: &mut [u8],
+    layout: &Layout<S>,
+) -> Result {
+    if !object_layout.split_stack.uses_split_stack
+        || !matches!(layout.args().arch(), Architecture::X86_64)
+    {
+        return Ok(());
+    }
+    let e = LittleEndian;
+    let object = object_layout.object;
+
+    // The offset and size of each function in the section.
+    let mut functions = object
+        .symbols
+        .enumerate()
+        .filter(|(index, sym)| {
+            sym.st_type() == object::elf::STT_FUNC
+                && object.symbol_section(sym, *index).ok().flatten() == Some(section.index)
+        })
+        .map(|(_, sym)| (sym.st_value(e), sym.st_size(e)))
+        .collect::<Vec<_>>();
+    if functions.is_empty() {
+        return Ok(());
+    }
+    functions.sort_unstable();
+    functions.dedup();
+    let function_containing = |offset: u64| {
+        functions
+            .iter()
+            .position(|&(start, size)| (start..start + size).contains(&offset))
+    };
+
+    let relocations = object.relocations(section.index)?;
+    let mut needs_adjustment = vec![false; functions.len()];
+    for rel in relocations {
+        let Some(symbol_index) = rel.symbol(e, false) else {
+            continue;
+        };
+        let Some(function_index) = function_containing(rel.r_offset.get(e)) else {
+            continue;
+        };
+        let symbol_id = object_layout.symbol_id_range.input_to_id(symbol_index);
+        if is_call_to_non_split(symbol_id, layout)? {
+            needs_adjustment[function_index] = true;
+        }
+    }
+    if !needs_adjustment.contains(&true) {
+        return Ok(());
+    }
+
+    for (&(start, size), _) in functions.iter().zip(&needs_adjustment).filter(|(_, n)| **n) {
+        let adjusted = adjust_prologue(
+            out,
+            start as usize,
+            size as usize,
+            layout.args().split_stack_adjust_size,
+        );
+        if !adjusted {
+            if object_layout.split_stack.has_no_split_stack {
+                continue;
+            }
+            bail!(
+                "Failed to match split-stack sequence at offset 0x{start:x} in section `{}`",
+                object.section_display_name(section.index)
+            );
+        }
+
+        // Our function now needs to call `__morestack_non_split`, which allocates additional stack
+        // space, instead of `__morestack`.
+        for rel in relocations {
+            let offset = rel.r_offset.get(e);
+            if !(start..start + size).contains(&offset) {
+                continue;
+            }
+            let Some(symbol_index) = rel.symbol(e, false) else {
+                continue;
+            };
+            let symbol_id = object_layout.symbol_id_range.input_to_id(symbol_index);
+            if layout.symbol_db.symbol_name(symbol_id)?.bytes() != MORESTACK_SYMBOL_NAME {
+                continue;
+            }
+            let r_type = rel.r_type(e, false);
+            if r_type != object::elf::R_X86_64_PLT32 && r_type != object::elf::R_X86_64_PC32 {
+                bail!("Unexpected relocation type {r_type} referencing __morestack");
+            }
+            let target = morestack_non_split_address(layout)?;
+            let place = section_address + offset;
+            let value = target
+                .wrapping_add(rel.r_addend.get(e) as u64)
+                .wrapping_sub(place) as i64;
+            let value = i32::try_from(value).context("__morestack_non_split is out of range")?;
+            let offset = offset as usize;
+            out.get_mut(offset..offset + 4)
+                .context("Relocation outside of section")?
+                .copy_from_slice(&value.to_le_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether `symbol_id` refers to a function that isn't compiled with split-stack.
+fn is_call_to_non_split<S: StorageModel>(
+    symbol_id: crate::symbol_db::SymbolId,
+    layout: &Layout<S>,
+) -> Result<bool> {
+    let symbol_db = layout.symbol_db;
+    let definition = symbol_db.definition(symbol_id);
+    if symbol_db
+        .symbol_name(definition)?
+        .bytes()
+        .starts_with(MORESTACK_SYMBOL_NAME)
+    {
+        return Ok(false);
+    }
+    let file_id = symbol_db.file_id_for_symbol(definition);
+    let ParsedInput::Object(definer) = symbol_db.file(file_id) else {
+        return Ok(false);
+    };
+    let sym = definer
+        .object
+        .symbol(definition.to_input(definer.symbol_id_range))?;
+    if sym.st_type() != object::elf::STT_FUNC {
+        return Ok(false);
+    }
+    Ok(match layout.file_layout(file_id) {
+        FileLayout::Object(o) => !o.split_stack.uses_split_stack,
+        FileLayout::Dynamic(_) => true,
+        _ => false,
+    })
+}
+
+/// Rewrites the split-stack prologue of the function at `start`. Returns false if the prologue
+/// wasn't recognised.
+fn adjust_prologue(out: &mut [u8], start: usize, size: usize, adjust_size: u32) -> bool {
+    let Some(function) = out.get_mut(start..start + size) else {
+        return false;
+    };
+    // cmp %fs:NN,%rsp
+    if function.starts_with(&[0x64, 0x48, 0x3b, 0x24, 0x25]) && function.len() > 9 {
+        // We'll call __morestack if the carry flag is set after the comparison, so we replace the
+        // comparison with stc followed by an 8 byte nop.
+        function[..9].copy_from_slice(&[0xf9, 0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        return true;
+    }
+    // lea NN(%rsp),%r10 or lea NN(%rsp),%r11
+    if (function.starts_with(&[0x4c, 0x8d, 0x94, 0x24])
+        || function.starts_with(&[0x4c, 0x8d, 0x9c, 0x24]))
+        && function.len() > 8
+    {
+        // This computes the stack pointer that the function will need, which is then compared
+        // against the limit. The offset is negative, so we reduce it by the extra space we want.
+        // If there's already plenty of space on the stack, we won't need to call __morestack.
+        let offset = u32::from_le_bytes(*function[4..8].first_chunk().unwrap());
+        function[4..8].copy_from_slice(&offset.wrapping_sub(adjust_size).to_le_bytes());
+        return true;
+    }
+    false
+}
+
+fn morestack_non_split_address<S: StorageModel>(layout: &Layout<S>) -> Result<u64> {
+    let symbol_id = layout
+        .symbol_db
+        .global_names
+        .get(&SymbolName::prehashed(MORESTACK_NON_SPLIT_SYMBOL_NAME))
+        .context(
+            "Split-stack code calls non-split-stack code, but __morestack_non_split isn't defined",
+        )?;
+    layout
+        .merged_symbol_resolution(symbol_id)
+        .context("Missing resolution for __morestack_non_split")?
+        .address()
+}
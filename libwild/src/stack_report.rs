@@ -0,0 +1,365 @@
+//! Optionally writes a report of how much stack each function might use. To use this, pass
+//! `--stack-report=/path/to/file.txt` and compile with `-fstack-size-section`, which makes the
+//! compiler emit a `.stack_sizes` section recording the size of each function's stack frame.
+//!
+//! We combine the frame sizes with the call graph, which we get from the call relocations in each
+//! function, to find the deepest call path starting at each function. Each line contains
+//! tab-separated fields:
+//!
+//! ```text
+//! function  frame  worst-case  call path
+//! main      32     80          main -> parse -> read_token
+//! ```
+//!
+//! A `+` after the worst case means that the path calls a function whose frame size we don't
+//! know, e.g. because it's in a shared object or wasn't compiled with `-fstack-size-section`, so
+//! the real worst case is larger. Functions that can reach a recursive call report `unbounded`.
+//!
+//! Only calls that have a relocation are visible to us. Calls between functions in the same input
+//! section are usually resolved by the assembler, so it's best to also use `-ffunction-sections`.
+//! Indirect calls aren't included, although code that takes the address of a function counts as
+//! calling it.
+
+use crate::arch::Architecture;
+use crate::error::Result;
+use crate::layout::FileLayout;
+use crate::layout::Layout;
+use crate::layout::ObjectLayout;
+use crate::resolution::SectionSlot;
+use crate::storage::StorageModel;
+use crate::symbol_db::SymbolId;
+use anyhow::bail;
+use anyhow::Context as _;
+use linker_utils::elf::RelocationKind;
+use object::read::elf::Rela as _;
+use object::read::elf::Sym as _;
+use object::LittleEndian;
+use std::collections::HashMap;
+use std::io::Write as _;
+use std::path::Path;
+
+pub(crate) const STACK_SIZES_SECTION_NAME: &[u8] = b".stack_sizes";
+
+pub(crate) fn maybe_write_stack_report<S: StorageModel>(layout: &Layout<S>) -> Result {
+    let Some(report_path) = layout.args().stack_report.as_ref() else {
+        return Ok(());
+    };
+    write_stack_report::<S>(layout, report_path)
+        .with_context(|| format!("Failed to write stack report `{}`", report_path.display()))
+}
+
+#[derive(Default)]
+struct Function {
+    frame_size: Option<u64>,
+    callees: Vec<SymbolId>,
+}
+
+#[derive(Clone, Copy)]
+enum Depth {
+    /// We're part way through computing the depth of this function.
+    InProgress,
+    Bounded {
+        total: u64,
+        /// Whether the path includes a function whose frame size we don't know.
+        incomplete: bool,
+        /// The callee that's next on the deepest path, if any.
+        next: Option<SymbolId>,
+    },
+    Unbounded,
+}
+
+struct CallGraph {
+    functions: HashMap<SymbolId, Function>,
+    depths: HashMap<SymbolId, Depth>,
+}
+
+fn write_stack_report<S: StorageModel>(layout: &Layout<S>, report_path: &Path) -> Result {
+    let mut graph = CallGraph {
+        functions: HashMap::new(),
+        depths: HashMap::new(),
+    };
+    for file in layout.group_layouts.iter().flat_map(|group| &group.files) {
+        if let FileLayout::Object(obj) = file {
+            graph.add_object::<S>(obj, layout)?;
+        }
+    }
+
+    let mut roots = graph
+        .functions
+        .iter()
+        .filter(|(_, f)| f.frame_size.is_some())
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    for &id in &roots {
+        graph.depth(id);
+    }
+    let sort_key = |id: &SymbolId| match graph.depths[id] {
+        Depth::Unbounded => u64::MAX,
+        Depth::Bounded { total, .. } => total,
+        Depth::InProgress => 0,
+    };
+    roots.sort_by_cached_key(|id| {
+        (
+            std::cmp::Reverse(sort_key(id)),
+            layout.symbol_db.symbol_name_for_display(*id).to_string(),
+        )
+    });
+
+    let mut out = std::io::BufWriter::new(std::fs::File::create(report_path)?);
+    writeln!(out, "function\tframe\tworst-case\tcall path")?;
+    for id in roots {
+        let name = |id| layout.symbol_db.symbol_name_for_display(id);
+        let frame_size = graph.functions[&id].frame_size.unwrap_or(0);
+        match graph.depths[&id] {
+            Depth::Bounded {
+                total, incomplete, ..
+            } => {
+                let plus = if incomplete { "+" } else { "" };
+                write!(out, "{}\t{frame_size}\t{total}{plus}\t", name(id))?;
+                let mut current = Some(id);
+                let mut separator = "";
+                while let Some(id) = current {
+                    write!(out, "{separator}{}", name(id))?;
+                    separator = " -> ";
+                    current = match graph.depths.get(&id) {
+                        Some(Depth::Bounded { next, .. }) => *next,
+                        _ => None,
+                    };
+                }
+                writeln!(out)?;
+            }
+            Depth::Unbounded | Depth::InProgress => {
+                writeln!(out, "{}\t{frame_size}\tunbounded\t(recursive)", name(id))?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+impl CallGraph {
+    fn add_object<S: StorageModel>(&mut self, obj: &ObjectLayout, layout: &Layout<S>) -> Result {
+        let e = LittleEndian;
+        let object = obj.object;
+        let symbol_db = layout.symbol_db;
+        let is_loaded = |section_index: object::SectionIndex| {
+            matches!(obj.sections[section_index.0], SectionSlot::Loaded(..))
+        };
+
+        // Functions in this object, keyed by their section and their offset within that section.
+        let mut functions_by_location = HashMap::new();
+        for (symbol_index, sym) in object.symbols.enumerate() {
+            if sym.st_type() != object::elf::STT_FUNC {
+                continue;
+            }
+            let Some(section_index) = object.symbol_section(sym, symbol_index)? else {
+                continue;
+            };
+            if !is_loaded(section_index) {
+                continue;
+            }
+            let symbol_id = symbol_db.definition(obj.symbol_id_range.input_to_id(symbol_index));
+            if symbol_db.file_id_for_symbol(symbol_id) != obj.file_id {
+                // The definition that we used came from a different file.
+                continue;
+            }
+            functions_by_location.insert((section_index, sym.st_value(e)), (symbol_id, sym));
+        }
+
+        let call_bias = match layout.args().arch() {
+            // For calls, the addend includes the distance from the relocation to the end of the
+            // instruction.
+            Architecture::X86_64 => 4,
+            Architecture::AArch64 | Architecture::RiscV64 | Architecture::PowerPc64 => 0,
+        };
+        for (&(section_index, start), &(symbol_id, sym)) in &functions_by_location {
+            let mut callees = Vec::new();
+            let end = start + sym.st_size(e);
+            for rel in object.relocations(section_index)? {
+                if !(start..end).contains(&rel.r_offset(e))
+                    || !matches!(
+                        layout
+                            .args()
+                            .arch()
+                            .relocation_from_raw(rel.r_type(e, false))?
+                            .kind,
+                        RelocationKind::PltRelative | RelocationKind::Relative
+                    )
+                {
+                    continue;
+                }
+                let Some(target_index) = rel.symbol(e, false) else {
+                    continue;
+                };
+                let target = object.symbol(target_index)?;
+                if target.st_type() != object::elf::STT_SECTION {
+                    callees
+                        .push(symbol_db.definition(obj.symbol_id_range.input_to_id(target_index)));
+                    continue;
+                }
+                // A call to a local function via its section symbol.
+                let Some(target_section) = object.symbol_section(target, target_index)? else {
+                    continue;
+                };
+                let offset = rel.r_addend(e).wrapping_add(call_bias) as u64;
+                if let Some(&(target_id, _)) = functions_by_location.get(&(target_section, offset))
+                {
+                    callees.push(target_id);
+                }
+            }
+            self.functions.entry(symbol_id).or_default().callees = callees;
+        }
+
+        for (section_index, section) in object.sections.enumerate() {
+            if object.section_name(section)? != STACK_SIZES_SECTION_NAME {
+                continue;
+            }
+            let section_data = object.raw_section_data(section)?;
+            let relocations = object.relocations(section_index)?;
+            let mut data = section_data;
+            while !data.is_empty() {
+                // Each entry is the address of a function, which has a relocation, followed by the
+                // size of its stack frame.
+                let offset = (section_data.len() - data.len()) as u64;
+                let Some((address, rest)) = data.split_first_chunk::<8>() else {
+                    bail!("Truncated entry at offset 0x{offset:x}");
+                };
+                let address = u64::from_le_bytes(*address);
+                data = rest;
+                let frame_size = take_uleb128(&mut data)?;
+                let Some(rel) = relocations.iter().find(|rel| rel.r_offset(e) == offset) else {
+                    bail!("Missing relocation for entry at offset 0x{offset:x}");
+                };
+                let Some(target_index) = rel.symbol(e, false) else {
+                    continue;
+                };
+                let target = object.symbol(target_index)?;
+                let Some(target_section) = object.symbol_section(target, target_index)? else {
+                    continue;
+                };
+                let target_offset = target
+                    .st_value(e)
+                    .wrapping_add(address)
+                    .wrapping_add(rel.r_addend(e) as u64);
+                if let Some(function) = functions_by_location
+                    .get(&(target_section, target_offset))
+                    .and_then(|(id, _)| self.functions.get_mut(id))
+                {
+                    function.frame_size = Some(frame_size);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn depth(&mut self, id: SymbolId) -> Depth {
+        if let Some(depth) = self.depths.get(&id) {
+            return match depth {
+                Depth::InProgress => Depth::Unbounded,
+                other => *other,
+            };
+        }
+        let Some(function) = self.functions.get(&id) else {
+            // A function in a shared object, or one that we didn't load.
+            return Depth::Bounded {
+                total: 0,
+                incomplete: true,
+                next: None,
+            };
+        };
+        let frame_size = function.frame_size;
+        let callees = function.callees.clone();
+        self.depths.insert(id, Depth::InProgress);
+
+        let mut deepest = 0;
+        let mut next = None;
+        let mut incomplete = frame_size.is_none();
+        let mut unbounded = false;
+        for callee in callees {
+            match self.depth(callee) {
+                Depth::Bounded {
+                    total,
+                    incomplete: callee_incomplete,
+                    ..
+                } => {
+                    incomplete |= callee_incomplete;
+                    if next.is_none() || total > deepest {
+                        deepest = total;
+                        next = Some(callee);
+                    }
+                }
+                Depth::Unbounded | Depth::InProgress => unbounded = true,
+            }
+        }
+        let depth = if unbounded {
+            Depth::Unbounded
+        } else {
+            Depth::Bounded {
+                total: frame_size.unwrap_or(0) + deepest,
+                incomplete,
+                next,
+            }
+        };
+        self.depths.insert(id, depth);
+        depth
+    }
+}
+
+fn take_uleb128(data: &mut &[u8]) -> Result<u64> {
+    let mut value = 0_u64;
+    let mut shift = 0;
+    loop {
+        let Some((&byte, rest)) = data.split_first() else {
+            bail!("Truncated ULEB128");
+        };
+        *data = rest;
+        if shift >= 64 {
+            bail!("ULEB128 is too large");
+        }
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth() {
+        let id = SymbolId::from_usize;
+        let function = |frame_size, callees: &[usize]| Function {
+            frame_size,
+            callees: callees.iter().copied().map(id).collect(),
+        };
+        let mut graph = CallGraph {
+            functions: [
+                (id(1), function(Some(48), &[2, 3])),
+                (id(2), function(Some(32), &[3])),
+                (id(3), function(Some(16), &[])),
+                (id(4), function(Some(8), &[5])),
+                (id(5), function(Some(8), &[4])),
+                (id(6), function(Some(8), &[7])),
+            ]
+            .into_iter()
+            .collect(),
+            depths: HashMap::new(),
+        };
+        let bounded = |graph: &mut CallGraph, f| match graph.depth(id(f)) {
+            Depth::Bounded {
+                total,
+                incomplete,
+                next,
+            } => Some((total, incomplete, next)),
+            _ => None,
+        };
+        assert_eq!(bounded(&mut graph, 1), Some((96, false, Some(id(2)))));
+        assert_eq!(bounded(&mut graph, 3), Some((16, false, None)));
+        assert_eq!(bounded(&mut graph, 4), None);
+        // 7 isn't one of our functions, so we don't know how much stack it uses.
+        assert_eq!(bounded(&mut graph, 6), Some((8, true, Some(id(7)))));
+    }
+}
@@ -26,6 +26,38 @@ impl<'data> SymbolName<'data> {
     }
 }
 
+/// The parts of a symbol name that has a version specifier, like the ones `.symver` produces. e.g.
+/// `foo@VERS_1` or `foo@@VERS_2`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SymbolVersion<'data> {
+    /// The name without the version specifier.
+    pub(crate) name: &'data [u8],
+
+    pub(crate) version: &'data [u8],
+
+    /// Whether this is the default version of the symbol, which is written with `@@`.
+    pub(crate) is_default: bool,
+}
+
+impl<'data> SymbolVersion<'data> {
+    /// Splits `name` into its name and version, or returns `None` if it doesn't have a version.
+    pub(crate) fn parse(name: &'data [u8]) -> Option<SymbolVersion<'data>> {
+        let at_offset = memchr::memchr(b'@', name)?;
+        let (version, is_default) = match name[at_offset + 1..].strip_prefix(b"@") {
+            Some(version) => (version, true),
+            None => (&name[at_offset + 1..], false),
+        };
+        if version.is_empty() {
+            return None;
+        }
+        Some(SymbolVersion {
+            name: &name[..at_offset],
+            version,
+            is_default,
+        })
+    }
+}
+
 impl Display for SymbolName<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         String::from_utf8_lossy(self.bytes).fmt(f)
@@ -63,3 +95,20 @@ impl Display for SymDebug<'_> {
         write!(f, "{vis} {kind}")
     }
 }
+
+#[test]
+fn test_parse_symbol_version() {
+    let parse = |name: &'static str| {
+        SymbolVersion::parse(name.as_bytes()).map(|v| {
+            (
+                std::str::from_utf8(v.name).unwrap(),
+                std::str::from_utf8(v.version).unwrap(),
+                v.is_default,
+            )
+        })
+    };
+    assert_eq!(parse("foo"), None);
+    assert_eq!(parse("foo@"), None);
+    assert_eq!(parse("foo@VERS_1"), Some(("foo", "VERS_1", false)));
+    assert_eq!(parse("foo@@VERS_2"), Some(("foo", "VERS_2", true)));
+}
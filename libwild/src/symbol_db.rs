@@ -3,6 +3,8 @@
 
 use crate::args::Args;
 use crate::args::OutputKind;
+use crate::elf::GLOBAL_POINTER_SYMBOL_NAME;
+use crate::elf::TOC_SYMBOL_NAME;
 use crate::error::Result;
 use crate::grouping::Group;
 use crate::hash::PreHashed;
@@ -11,21 +13,25 @@ use crate::input_data::VersionScriptData;
 use crate::input_data::PRELUDE_FILE_ID;
 use crate::input_data::UNINITIALISED_FILE_ID;
 use crate::linker_script::VersionScript;
+use crate::output_section_id;
 use crate::output_section_id::OutputSectionId;
 use crate::parsing::InternalSymDefInfo;
 use crate::parsing::ParsedInput;
+use crate::parsing::ParsedInputObject;
 use crate::parsing::Prelude;
 use crate::resolution::ValueFlags;
 use crate::sharding::ShardKey;
 use crate::storage::StorageModel;
 use crate::storage::SymbolNameMap;
 use crate::symbol::SymbolName;
+use crate::symbol::SymbolVersion;
 use crate::threading::prelude::*;
 use anyhow::Context;
 use itertools::Itertools;
 use object::read::elf::Sym as _;
 use object::LittleEndian;
 use std::collections::hash_map;
+use std::collections::HashMap;
 use std::mem::replace;
 use std::mem::take;
 
@@ -69,9 +75,17 @@ pub struct SymbolDb<'data, S: StorageModel> {
 
     epilogue_file_id: FileId,
 
+    /// The version script, if any. This decides which symbols are local and which versions we give
+    /// to the symbols that we export.
+    pub(crate) version_script: VersionScript<'data>,
+
     /// The names of symbols that mark the start / stop of sections. These are indexed by the offset
     /// into the epilogue's symbol IDs.
     start_stop_symbol_names: Vec<SymbolName<'data>>,
+
+    /// Alignments for selected common symbols that are more strictly aligned than their own, taken
+    /// from other common symbols with the same name. Keyed by the selected symbol.
+    pub(crate) common_alignments: HashMap<SymbolId, u64>,
 }
 
 /// A global symbol that hasn't been put into our database yet.
@@ -203,14 +217,20 @@ impl Iterator for SymbolIdRangeIterator {
 
 struct SymbolLoadOutputs<'data> {
     pending_symbols: Vec<PendingSymbol<'data>>,
+
+    /// Names like `foo@VERS_1` for symbols defined by shared objects. These are added after all
+    /// other names and never become alternative definitions, since the same symbol IDs are
+    /// generally also present in `pending_symbols` under their unversioned names.
+    versioned_names: Vec<PendingSymbol<'data>>,
 }
 
 impl<'data, S: StorageModel> SymbolDb<'data, S> {
     #[tracing::instrument(skip_all, name = "Build symbol DB")]
     pub fn build(
         groups: &'data [Group],
-        version_script_data: Option<&VersionScriptData>,
+        version_script_data: Option<&'data VersionScriptData>,
         args: &'data Args,
+        herd: &'data bumpalo_herd::Herd,
     ) -> Result<Self> {
         let version_script = version_script_data
             .map(VersionScript::parse)
@@ -247,7 +267,8 @@ impl<'data, S: StorageModel> SymbolDb<'data, S> {
             })
             .collect_vec();
 
-        let symbol_per_file = read_symbols(groups, &version_script, &mut per_group_writers, args)?;
+        let symbol_per_file =
+            read_symbols(groups, &version_script, &mut per_group_writers, args, herd)?;
 
         for writer in per_group_writers {
             symbol_definitions_writer.return_shard(writer.resolutions);
@@ -268,6 +289,8 @@ impl<'data, S: StorageModel> SymbolDb<'data, S> {
             num_symbols_per_group,
             start_stop_symbol_names: Default::default(),
             symbol_value_flags,
+            version_script,
+            common_alignments: HashMap::new(),
         };
         index.populate_symbol_db(symbol_per_file)?;
         Ok(index)
@@ -283,8 +306,17 @@ impl<'data, S: StorageModel> SymbolDb<'data, S> {
             .map(|s| s.pending_symbols.len())
             .sum();
         self.global_names.reserve(approx_num_symbols);
+        let mut versioned_names = Vec::new();
         for pending in symbol_per_file {
             self.add_symbols(pending.pending_symbols);
+            versioned_names.push(pending.versioned_names);
+        }
+        // If something else already defines a versioned name, e.g. a regular object via `.symver`
+        // or an earlier shared object, then that definition takes precedence.
+        for pending in versioned_names.into_iter().flatten() {
+            self.global_names
+                .entry(pending.name)
+                .or_insert(pending.symbol_id);
         }
         Ok(())
     }
@@ -362,6 +394,33 @@ impl<'data, S: StorageModel> SymbolDb<'data, S> {
         }
     }
 
+    /// Returns the `st_other` field of the input symbol with the supplied ID. Symbols that we
+    /// define ourselves don't have one, so get 0.
+    pub(crate) fn symbol_st_other(&self, symbol_id: SymbolId) -> Result<u8> {
+        match self.file(self.file_id_for_symbol(symbol_id)) {
+            ParsedInput::Object(o) => o.symbol_st_other(symbol_id),
+            ParsedInput::Prelude(_) | ParsedInput::Epilogue(_) => Ok(0),
+        }
+    }
+
+    /// Returns the `st_size` field of the input symbol with the supplied ID. Symbols that we define
+    /// ourselves have a size of 0.
+    pub(crate) fn symbol_st_size(&self, symbol_id: SymbolId) -> Result<u64> {
+        match self.file(self.file_id_for_symbol(symbol_id)) {
+            ParsedInput::Object(o) => o.symbol_st_size(symbol_id),
+            ParsedInput::Prelude(_) | ParsedInput::Epilogue(_) => Ok(0),
+        }
+    }
+
+    /// Returns whether the input symbol with the supplied ID is undefined. Symbols that we define
+    /// ourselves never are.
+    pub(crate) fn is_symbol_undefined(&self, symbol_id: SymbolId) -> Result<bool> {
+        match self.file(self.file_id_for_symbol(symbol_id)) {
+            ParsedInput::Object(o) => o.is_symbol_undefined(symbol_id),
+            ParsedInput::Prelude(_) | ParsedInput::Epilogue(_) => Ok(false),
+        }
+    }
+
     /// Returns the value flags for the specified symbol without taking into consideration what
     /// symbol is the definition.
     pub(crate) fn local_symbol_value_flags(&self, symbol_id: SymbolId) -> ValueFlags {
@@ -443,23 +502,35 @@ fn read_symbols<'data, 'out>(
     version_script: &VersionScript,
     symbols_out_by_file: &mut [SymbolInfoWriter],
     args: &Args,
+    herd: &'data bumpalo_herd::Herd,
 ) -> Result<Vec<SymbolLoadOutputs<'data>>> {
     groups
         .par_iter()
         .zip(symbols_out_by_file)
-        .map(|(group, symbols_out)| {
-            let mut outputs = SymbolLoadOutputs {
-                pending_symbols: Vec::new(),
-            };
-            for file in &group.files {
-                let filename = file.filename();
-                load_symbols_from_file(file, version_script, symbols_out, &mut outputs, args)
+        .map_init(
+            || herd.get(),
+            |allocator, (group, symbols_out)| {
+                let mut outputs = SymbolLoadOutputs {
+                    pending_symbols: Vec::new(),
+                    versioned_names: Vec::new(),
+                };
+                for file in &group.files {
+                    let filename = file.filename();
+                    load_symbols_from_file(
+                        file,
+                        version_script,
+                        symbols_out,
+                        &mut outputs,
+                        args,
+                        allocator,
+                    )
                     .with_context(|| {
                         format!("Failed to load symbols from `{}`", filename.display())
                     })?;
-            }
-            Ok(outputs)
-        })
+                }
+                Ok(outputs)
+            },
+        )
         .collect::<Result<Vec<SymbolLoadOutputs>>>()
 }
 
@@ -469,6 +540,7 @@ fn load_symbols_from_file<'data>(
     symbols_out: &mut SymbolInfoWriter,
     outputs: &mut SymbolLoadOutputs<'data>,
     args: &Args,
+    allocator: &bumpalo_herd::Member<'data>,
 ) -> Result {
     match reader {
         ParsedInput::Object(s) => {
@@ -479,10 +551,20 @@ fn load_symbols_from_file<'data>(
                     symbols_out,
                     outputs,
                 )?;
+                add_versioned_names(s, allocator, outputs)?;
+            } else if s.object.just_symbols {
+                JustSymbolsSymbolLoader { args }.load_symbols(
+                    s.file_id,
+                    &s.object,
+                    symbols_out,
+                    outputs,
+                )?;
             } else {
                 RegularObjectSymbolLoader {
                     args,
                     version_script,
+                    is_excluded_lib: s.input.entry.is_some()
+                        && args.exclude_libs.excludes(&s.input.file.filename),
                 }
                 .load_symbols(s.file_id, &s.object, symbols_out, outputs)?;
             }
@@ -495,17 +577,62 @@ fn load_symbols_from_file<'data>(
     Ok(())
 }
 
-fn value_flags_from_elf_symbol(sym: &crate::elf::Symbol, args: &Args) -> ValueFlags {
+/// Adds names like `foo@VERS_1` for the symbols that a shared object defines with versions, so that
+/// references that ask for a particular version, e.g. via `.symver`, resolve to that version.
+/// Symbols with hidden versions, i.e. those that aren't the default, can only be found this way.
+fn add_versioned_names<'data>(
+    object: &ParsedInputObject<'data>,
+    allocator: &bumpalo_herd::Member<'data>,
+    outputs: &mut SymbolLoadOutputs<'data>,
+) -> Result {
+    let e = LittleEndian;
+    if object.object.versym.is_empty() {
+        return Ok(());
+    }
+    let version_names = object.object.version_names()?;
+    for ((sym_index, symbol), versym) in object.object.symbols.enumerate().zip(object.object.versym)
+    {
+        if symbol.is_undefined(e) || symbol.is_local() {
+            continue;
+        }
+        let version_index = versym.0.get(e) & object::elf::VERSYM_VERSION;
+        // The base version names the object rather than being a version that symbols have.
+        if version_index <= object::elf::VER_NDX_GLOBAL {
+            continue;
+        }
+        let Some(version_name) = version_names
+            .get(usize::from(version_index))
+            .copied()
+            .flatten()
+        else {
+            continue;
+        };
+        let name = object.object.symbol_name(symbol)?;
+        let versioned_name = allocator.alloc_slice_copy(&[name, b"@", version_name].concat());
+        outputs.versioned_names.push(PendingSymbol::from_prehashed(
+            object.symbol_id_range.input_to_id(sym_index),
+            SymbolName::prehashed(versioned_name),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn value_flags_from_elf_symbol(
+    sym: &crate::elf::Symbol,
+    output_kind: OutputKind,
+) -> ValueFlags {
     let is_undefined = sym.is_undefined(LittleEndian);
     let mut can_bypass_got = sym.st_visibility() != object::elf::STV_DEFAULT
         || sym.is_local()
-        || args.output_kind().is_static_executable()
+        || output_kind.is_static_executable()
+        // Nothing in a relocatable object is resolved at runtime until it's been linked again.
+        || output_kind == OutputKind::RelocatableObject
         // Symbols defined in an executable cannot be interposed since the executable is always the
         // first place checked for a symbol by the dynamic loader.
-        || (args.output_kind().is_executable() && !is_undefined);
+        || (output_kind.is_executable() && !is_undefined);
     // When writing a shared object, TLS variables should never bypass the GOT, even if they're
     // local variables.
-    if args.output_kind() == OutputKind::SharedObject && sym.st_type() == object::elf::STT_TLS {
+    if output_kind == OutputKind::SharedObject && sym.st_type() == object::elf::STT_TLS {
         can_bypass_got = false;
     }
     let mut flags: ValueFlags = if sym.is_absolute(LittleEndian) {
@@ -593,15 +720,14 @@ trait SymbolLoader {
             // with double-at specifies that it's the default version. We don't currently support
             // matching arbitrary versions, however if we see a default version, we strip it so that
             // it can be used via a regular symbol reference.
-            if let Some(at_offset) = memchr::memchr(b'@', name_bytes) {
-                if name_bytes[at_offset..].starts_with(b"@@") {
-                    name_bytes = &name_bytes[..at_offset];
-                }
+            let symbol_version = SymbolVersion::parse(name_bytes);
+            if let Some(version) = symbol_version.filter(|v| v.is_default) {
+                name_bytes = version.name;
             }
 
             let name = SymbolName::prehashed(name_bytes);
 
-            if self.should_downgrade_to_local(&name) {
+            if self.should_downgrade_to_local(&name, symbol_version) {
                 value_flags |= ValueFlags::DOWNGRADE_TO_LOCAL;
                 // If we're downgrading to a local, then we're writing a shared object. Shared
                 // objects should never bypass the GOT for TLS variables.
@@ -620,8 +746,13 @@ trait SymbolLoader {
 
     fn compute_value_flags(&self, symbol: &crate::elf::Symbol) -> ValueFlags;
 
-    /// Returns whether we should downgrade a symbol with the specified name to be a local.
-    fn should_downgrade_to_local(&self, _name: &PreHashed<SymbolName>) -> bool {
+    /// Returns whether we should downgrade a symbol with the specified name to be a local. `version`
+    /// is set if the name has a version specifier.
+    fn should_downgrade_to_local(
+        &self,
+        _name: &PreHashed<SymbolName>,
+        _version: Option<SymbolVersion>,
+    ) -> bool {
         false
     }
 
@@ -633,17 +764,52 @@ trait SymbolLoader {
 struct RegularObjectSymbolLoader<'a> {
     args: &'a Args,
     version_script: &'a VersionScript<'a>,
+
+    /// Whether the object is a member of an archive named by `--exclude-libs`, in which case none of
+    /// its symbols get exported.
+    is_excluded_lib: bool,
 }
 
 struct DynamicObjectSymbolLoader;
 
+/// Loads the symbols of an input named by `--just-symbols`. Their values are addresses in an image
+/// that's loaded separately, so they're absolute.
+struct JustSymbolsSymbolLoader<'a> {
+    args: &'a Args,
+}
+
 impl SymbolLoader for RegularObjectSymbolLoader<'_> {
     fn compute_value_flags(&self, symbol: &crate::elf::Symbol) -> ValueFlags {
-        value_flags_from_elf_symbol(symbol, self.args)
+        value_flags_from_elf_symbol(symbol, self.args.output_kind())
     }
 
-    fn should_downgrade_to_local(&self, name: &PreHashed<SymbolName>) -> bool {
-        self.version_script.is_local(name)
+    fn should_downgrade_to_local(
+        &self,
+        name: &PreHashed<SymbolName>,
+        version: Option<SymbolVersion>,
+    ) -> bool {
+        if self.is_excluded_lib {
+            return true;
+        }
+        // A symbol that `.symver` puts in a version is only subject to that version's patterns,
+        // which match against the name without the version.
+        match version {
+            Some(version) => self
+                .version_script
+                .is_local_in_version(&SymbolName::prehashed(version.name), version.version),
+            None => self.version_script.is_local(name),
+        }
+    }
+}
+
+impl SymbolLoader for JustSymbolsSymbolLoader<'_> {
+    fn compute_value_flags(&self, symbol: &crate::elf::Symbol) -> ValueFlags {
+        let mut flags = value_flags_from_elf_symbol(symbol, self.args.output_kind());
+        if !symbol.is_undefined(LittleEndian) {
+            flags.remove(ValueFlags::ADDRESS | ValueFlags::IFUNC);
+            flags |= ValueFlags::ABSOLUTE;
+        }
+        flags
     }
 }
 
@@ -771,11 +937,11 @@ impl TryFrom<usize> for SymbolId {
     }
 }
 
-impl Prelude {
+impl<'data> Prelude<'data> {
     fn load_symbols(
         &self,
         symbols_out: &mut SymbolInfoWriter,
-        outputs: &mut SymbolLoadOutputs,
+        outputs: &mut SymbolLoadOutputs<'data>,
         output_kind: OutputKind,
     ) {
         outputs
@@ -801,6 +967,30 @@ impl Prelude {
                         .push(PendingSymbol::new(symbol_id, name));
                     ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT
                 }
+                InternalSymDefInfo::GotBase => {
+                    outputs
+                        .pending_symbols
+                        .push(PendingSymbol::new(symbol_id, TOC_SYMBOL_NAME.as_bytes()));
+                    ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT
+                }
+                InternalSymDefInfo::GlobalPointer => {
+                    outputs.pending_symbols.push(PendingSymbol::new(
+                        symbol_id,
+                        GLOBAL_POINTER_SYMBOL_NAME.as_bytes(),
+                    ));
+                    ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT
+                }
+                InternalSymDefInfo::ScriptAssignment(index) => {
+                    let assignment = &self.script_assignments[*index as usize];
+                    outputs
+                        .pending_symbols
+                        .push(PendingSymbol::new(symbol_id, assignment.name.as_bytes()));
+                    if assignment.value.is_absolute() {
+                        ValueFlags::ABSOLUTE
+                    } else {
+                        ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT
+                    }
+                }
             };
             symbols_out.set_next(value_flags, symbol_id, PRELUDE_FILE_ID);
         }
@@ -819,6 +1009,10 @@ impl InternalSymDefInfo {
             InternalSymDefInfo::Undefined => None,
             InternalSymDefInfo::SectionStart(i) => Some(i),
             InternalSymDefInfo::SectionEnd(i) => Some(i),
+            InternalSymDefInfo::GotBase => Some(output_section_id::GOT),
+            InternalSymDefInfo::GlobalPointer => Some(output_section_id::DATA),
+            // Which section these are in isn't known until we've evaluated them.
+            InternalSymDefInfo::ScriptAssignment(_) => None,
         }
     }
 }
@@ -76,6 +76,16 @@ where
     func();
 }
 
+pub(crate) fn join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    (oper_a(), oper_b())
+}
+
 pub(crate) struct Scope<'scope> {
     _scope: PhantomData<&'scope ()>,
 }
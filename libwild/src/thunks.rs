@@ -0,0 +1,134 @@
+//! Range-extension thunks, also known as veneers. Branch instructions on some architectures can
+//! only reach a limited distance, e.g. ±128MiB for `b` and `bl` on AArch64 or ±32MiB on PowerPC64.
+//! When a branch's target ends up further away than that, we redirect the branch to a thunk that
+//! does the jump using an instruction sequence that can reach further. Branches that we can't
+//! redirect, e.g. conditional branches, are reported as errors if their targets are out of range.
+//!
+//! Each thunk is placed immediately after the input section containing the branches that use it.
+//! Adding thunks makes sections bigger, which can push other branches out of range, so layout
+//! keeps adding thunks until no more are needed. Thunks are only ever added, never removed, so this
+//! always terminates.
+//!
+//! The same space is used for patches that work around CPU errata by moving an instruction out of
+//! the way. See `aarch64_errata`.
+
+use crate::symbol_db::SymbolId;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+/// Thunks are written at offsets within the section that are a multiple of this.
+pub(crate) const THUNK_ALIGNMENT: u64 = 4;
+
+/// Information about the range-extension thunks used by an architecture.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ThunkInfo {
+    /// The size in bytes of each thunk.
+    pub(crate) size: u64,
+
+    /// The shortest distance that any branch needing a thunk can reach. If all executable code fits
+    /// within this distance, then we don't need to look for branches that are out of range.
+    pub(crate) min_branch_range: u64,
+}
+
+/// What a thunk jumps to. Branches from the same section to the same symbol and addend share a
+/// thunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ThunkTarget {
+    pub(crate) section_index: object::SectionIndex,
+    pub(crate) symbol_id: SymbolId,
+    pub(crate) addend: i64,
+}
+
+/// A thunk that layout has decided is needed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum NewThunk {
+    /// A range-extension thunk for branches to the target.
+    Branch(ThunkTarget),
+
+    /// A patch that the instruction at the specified offset gets moved into.
+    MovedInstruction {
+        section_index: object::SectionIndex,
+        instruction_offset: u64,
+    },
+}
+
+/// The thunks for an object. Most objects don't have any, so we only allocate once they do.
+#[derive(Debug, Default)]
+pub(crate) struct Thunks {
+    maps: Option<Box<ThunkMaps>>,
+}
+
+#[derive(Debug, Default)]
+struct ThunkMaps {
+    /// Keyed by target, with values being the offset of the thunk within the section that contains
+    /// it.
+    offsets: HashMap<ThunkTarget, u64>,
+
+    /// Keyed by section index and the offset of an instruction that we've moved, with values being
+    /// the offset of the patch that we moved it to.
+    moved_instructions: BTreeMap<(usize, u64), u64>,
+}
+
+impl Thunks {
+    pub(crate) fn get(&self, target: &ThunkTarget) -> Option<u64> {
+        self.maps.as_ref()?.offsets.get(target).copied()
+    }
+
+    pub(crate) fn insert(&mut self, target: ThunkTarget, offset_in_section: u64) {
+        self.maps_mut().offsets.insert(target, offset_in_section);
+    }
+
+    pub(crate) fn is_instruction_moved(
+        &self,
+        section_index: object::SectionIndex,
+        instruction_offset: u64,
+    ) -> bool {
+        self.maps.as_ref().is_some_and(|maps| {
+            maps.moved_instructions
+                .contains_key(&(section_index.0, instruction_offset))
+        })
+    }
+
+    pub(crate) fn insert_moved_instruction(
+        &mut self,
+        section_index: object::SectionIndex,
+        instruction_offset: u64,
+        patch_offset: u64,
+    ) {
+        self.maps_mut()
+            .moved_instructions
+            .insert((section_index.0, instruction_offset), patch_offset);
+    }
+
+    /// Returns the instructions that were moved out of the supplied section and the offsets of the
+    /// patches that they were moved to.
+    pub(crate) fn moved_instructions(
+        &self,
+        section_index: object::SectionIndex,
+    ) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.maps.iter().flat_map(move |maps| {
+            maps.moved_instructions
+                .range((section_index.0, 0)..=(section_index.0, u64::MAX))
+                .map(|((_, from), to)| (*from, *to))
+        })
+    }
+
+    fn maps_mut(&mut self) -> &mut ThunkMaps {
+        self.maps.get_or_insert_with(Default::default)
+    }
+}
+
+/// Returns whether a branch at `place` can reach `target` if it's limited to `range` in either
+/// direction.
+pub(crate) fn is_in_range(place: u64, target: u64, range: u64) -> bool {
+    let delta = target.wrapping_sub(place) as i64;
+    delta >= -(range as i64) && delta < range as i64
+}
+
+#[test]
+fn test_is_in_range() {
+    assert!(is_in_range(0x1000, 0x1000 + (1 << 27) - 4, 1 << 27));
+    assert!(!is_in_range(0x1000, 0x1000 + (1 << 27), 1 << 27));
+    assert!(is_in_range(0x1000 + (1 << 27), 0x1000, 1 << 27));
+    assert!(!is_in_range(0x1000 + (1 << 27) + 4, 0x1000, 1 << 27));
+}
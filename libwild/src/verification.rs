@@ -71,7 +71,6 @@ pub(crate) fn clear_ignored(expected: &mut OutputSectionPartMap<u64>) {
     const IGNORED_OFFSET: u64 = 0x98760000;
 
     const IGNORED: &[PartId] = &[
-        part_id::RELA_PLT,
         part_id::EH_FRAME_HDR,
         part_id::RELA_DYN_GENERAL,
         part_id::RELA_DYN_RELATIVE,
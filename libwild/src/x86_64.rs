@@ -4,10 +4,14 @@
 //! static-PIE binary because dynamic relocations haven't yet been applied to the GOT yet.
 
 use crate::arch::Arch;
+use crate::arch::PltFeatures;
 use crate::args::OutputKind;
 use crate::elf::PLT_ENTRY_SIZE;
+use crate::elf::PLT_HEADER_SIZE;
+use crate::elf::X86_64_COMPACT_PLT_ENTRY_SIZE;
 use crate::resolution::ValueFlags;
 use anyhow::anyhow;
+use anyhow::Context as _;
 use anyhow::Result;
 use linker_utils::elf::shf;
 use linker_utils::elf::x86_64_rel_type_to_string;
@@ -26,10 +30,54 @@ const PLT_ENTRY_TEMPLATE: &[u8] = &[
     0x0f, 0x1f, 0x44, 0x0, 0x0, // nopl   0x0(%rax,%rax,1)
 ];
 
+/// A PLT entry for when there's no need for a landing pad. Lazy binding needs all of .plt.got to use
+/// the size of the lazy entries, so this is only used when none of the entries will be lazily bound.
+const COMPACT_PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0xff, 0x25, 0x0, 0x0, 0x0, 0x0, // jmp *{relative GOT address}(%rip)
+    0x66, 0x90, // xchg %ax,%ax
+];
+
+/// A PLT entry for use with `-z lazy`. The GOT entry initially points at the push, so the first
+/// call goes via the PLT header with the index of the entry's JUMP_SLOT relocation on the stack.
+const LAZY_PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0xff, 0x25, 0x0, 0x0, 0x0, 0x0, // jmp *{relative GOT address}(%rip)
+    0x68, 0x0, 0x0, 0x0, 0x0, // push {index in .rela.plt}
+    0xe9, 0x0, 0x0, 0x0, 0x0, // jmp {relative PLT header address}
+];
+
+/// The lazy-binding PLT entry used when the output has IBT enabled. The GOT entry is reached via an
+/// indirect branch, so needs a landing pad. Calls go via the corresponding entry in .plt.sec, which
+/// is a regular PLT entry.
+const IBT_LAZY_PLT_ENTRY_TEMPLATE: &[u8] = &[
+    0xf3, 0x0f, 0x1e, 0xfa, // endbr64
+    0x68, 0x0, 0x0, 0x0, 0x0, // push {index in .rela.plt}
+    0xe9, 0x0, 0x0, 0x0, 0x0, // jmp {relative PLT header address}
+    0x66, 0x90, // xchg %ax,%ax
+];
+
+/// The PLT header, which passes the reserved GOT entries to the dynamic loader's resolver. The
+/// first of these identifies the output and the second is the address of the resolver.
+const PLT_HEADER_TEMPLATE: &[u8] = &[
+    0xff, 0x35, 0x0, 0x0, 0x0, 0x0, // push {relative GOT[1] address}(%rip)
+    0xff, 0x25, 0x0, 0x0, 0x0, 0x0, // jmp *{relative GOT[2] address}(%rip)
+    0x0f, 0x1f, 0x40, 0x0, // nopl 0x0(%rax)
+];
+
 const _ASSERTS: () = {
     assert!(PLT_ENTRY_TEMPLATE.len() as u64 == PLT_ENTRY_SIZE);
+    assert!(COMPACT_PLT_ENTRY_TEMPLATE.len() as u64 == X86_64_COMPACT_PLT_ENTRY_SIZE);
+    assert!(LAZY_PLT_ENTRY_TEMPLATE.len() as u64 == PLT_ENTRY_SIZE);
+    assert!(IBT_LAZY_PLT_ENTRY_TEMPLATE.len() as u64 == PLT_ENTRY_SIZE);
+    assert!(PLT_HEADER_TEMPLATE.len() as u64 == PLT_HEADER_SIZE);
 };
 
+/// Returns the offset from `from` to `to` for use in a 32 bit PC-relative field.
+fn pc_relative_offset(to: u64, from: u64) -> Result<i32> {
+    (to.wrapping_sub(from) as i64)
+        .try_into()
+        .map_err(|_| anyhow!("PLT is more than 2GiB away from GOT"))
+}
+
 impl crate::arch::Arch for X86_64 {
     type Relaxation = Relaxation;
 
@@ -60,18 +108,66 @@ impl crate::arch::Arch for X86_64 {
         plt_entry: &mut [u8],
         got_address: u64,
         plt_address: u64,
+        _got_base: u64,
+        _features: PltFeatures,
     ) -> crate::error::Result {
+        if plt_entry.len() as u64 == X86_64_COMPACT_PLT_ENTRY_SIZE {
+            plt_entry.copy_from_slice(COMPACT_PLT_ENTRY_TEMPLATE);
+            let offset = pc_relative_offset(got_address, plt_address + 0x6)?;
+            plt_entry[2..6].copy_from_slice(&offset.to_le_bytes());
+            return Ok(());
+        }
         plt_entry.copy_from_slice(PLT_ENTRY_TEMPLATE);
-        let offset: i32 = ((got_address.wrapping_sub(plt_address + 0xb)) as i64)
-            .try_into()
-            .map_err(|_| anyhow!("PLT is more than 2GiB away from GOT"))?;
+        let offset = pc_relative_offset(got_address, plt_address + 0xb)?;
         plt_entry[7..11].copy_from_slice(&offset.to_le_bytes());
         Ok(())
     }
 
+    fn write_lazy_plt_entry(
+        plt_entry: &mut [u8],
+        got_address: u64,
+        plt_address: u64,
+        plt_header_address: u64,
+        rela_plt_index: u64,
+        features: PltFeatures,
+    ) -> crate::error::Result<u64> {
+        let index = u32::try_from(rela_plt_index).context("Too many PLT entries")?;
+        if features.ibt {
+            plt_entry.copy_from_slice(IBT_LAZY_PLT_ENTRY_TEMPLATE);
+            plt_entry[5..9].copy_from_slice(&index.to_le_bytes());
+            let header_offset = pc_relative_offset(plt_header_address, plt_address + 0xe)?;
+            plt_entry[10..14].copy_from_slice(&header_offset.to_le_bytes());
+            return Ok(plt_address);
+        }
+        plt_entry.copy_from_slice(LAZY_PLT_ENTRY_TEMPLATE);
+        let got_offset = pc_relative_offset(got_address, plt_address + 0x6)?;
+        plt_entry[2..6].copy_from_slice(&got_offset.to_le_bytes());
+        plt_entry[7..11].copy_from_slice(&index.to_le_bytes());
+        let header_offset = pc_relative_offset(plt_header_address, plt_address + 0x10)?;
+        plt_entry[12..16].copy_from_slice(&header_offset.to_le_bytes());
+        Ok(plt_address + 0x6)
+    }
+
+    fn write_plt_header(
+        plt_header: &mut [u8],
+        got_address: u64,
+        plt_header_address: u64,
+    ) -> crate::error::Result {
+        plt_header.copy_from_slice(PLT_HEADER_TEMPLATE);
+        let got1_offset = pc_relative_offset(got_address + 0x8, plt_header_address + 0x6)?;
+        plt_header[2..6].copy_from_slice(&got1_offset.to_le_bytes());
+        let got2_offset = pc_relative_offset(got_address + 0x10, plt_header_address + 0xc)?;
+        plt_header[8..12].copy_from_slice(&got2_offset.to_le_bytes());
+        Ok(())
+    }
+
     fn rel_type_to_string(r_type: u32) -> std::borrow::Cow<'static, str> {
         x86_64_rel_type_to_string(r_type)
     }
+
+    fn merge_e_flags(_input_flags: impl Iterator<Item = u32>) -> crate::error::Result<u32> {
+        Ok(0)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -241,9 +337,47 @@ impl crate::arch::Relaxation for Relaxation {
                             object::elf::R_X86_64_TPOFF32,
                         )
                     }
+                    // add *x(%rip), reg
+                    [0x48 | 0x4c, 0x03] => {
+                        return create(
+                            RelaxationKind::RexAddIndirectToAbsolute,
+                            object::elf::R_X86_64_TPOFF32,
+                        )
+                    }
                     _ => {}
                 }
             }
+            // The APX forms of the above. The prefix byte is followed by the REX2 payload and the
+            // opcode, so everything that we rewrite is at the same offset as with a REX prefix.
+            linker_utils::elf::R_X86_64_CODE_4_GOTPCRELX => {
+                let (rex2, opcode) = rex2_payload_and_opcode(section_bytes, offset)?;
+                match opcode {
+                    // mov *x(%rip), reg
+                    0x8b if (is_absolute || is_absolute_address) && rex2 & REX2_W != 0 => {
+                        return create(
+                            RelaxationKind::Rex2MovIndirectToAbsolute,
+                            object::elf::R_X86_64_32,
+                        );
+                    }
+                    0x8b if can_bypass_got => {
+                        return create(
+                            RelaxationKind::MovIndirectToLea,
+                            object::elf::R_X86_64_PC32,
+                        );
+                    }
+                    _ => return None,
+                }
+            }
+            linker_utils::elf::R_X86_64_CODE_4_GOTTPOFF if can_bypass_got => {
+                let (rex2, opcode) = rex2_payload_and_opcode(section_bytes, offset)?;
+                // mov *x(%rip), reg
+                if opcode == 0x8b && rex2 & REX2_W != 0 {
+                    return create(
+                        RelaxationKind::Rex2MovIndirectToAbsolute,
+                        object::elf::R_X86_64_TPOFF32,
+                    );
+                }
+            }
             object::elf::R_X86_64_PLT32 if can_bypass_got => {
                 return create(RelaxationKind::NoOp, object::elf::R_X86_64_PC32);
             }
@@ -281,17 +415,46 @@ impl crate::arch::Relaxation for Relaxation {
                     return create(RelaxationKind::TlsLdToLocalExec, object::elf::R_X86_64_NONE);
                 }
             }
-            object::elf::R_X86_64_GOTPC32_TLSDESC
-                if can_bypass_got && output_kind.is_static_executable() =>
-            {
+            // The descriptor load and the call through it are relaxed separately, since the
+            // compiler isn't required to put them next to each other. Both are relaxed whenever
+            // we're producing an executable, so they always agree.
+            object::elf::R_X86_64_GOTPC32_TLSDESC if output_kind.is_executable() => {
                 // lea    0x0(%rip),%rax
                 if section_bytes.get(offset - 3..offset)? == [0x48, 0x8d, 0x05] {
+                    if can_bypass_got {
+                        return create(
+                            RelaxationKind::TlsDescToLocalExec,
+                            object::elf::R_X86_64_TPOFF32,
+                        );
+                    }
                     return create(
-                        RelaxationKind::TlsDescToLocalExec,
-                        object::elf::R_X86_64_TPOFF32,
+                        RelaxationKind::TlsDescToInitialExec,
+                        object::elf::R_X86_64_GOTTPOFF,
+                    );
+                }
+            }
+            linker_utils::elf::R_X86_64_CODE_4_GOTPC32_TLSDESC if output_kind.is_executable() => {
+                let (rex2, opcode) = rex2_payload_and_opcode(section_bytes, offset)?;
+                // lea    0x0(%rip),reg
+                if opcode == 0x8d && rex2 & REX2_W != 0 {
+                    if can_bypass_got {
+                        return create(
+                            RelaxationKind::Rex2MovIndirectToAbsolute,
+                            object::elf::R_X86_64_TPOFF32,
+                        );
+                    }
+                    return create(
+                        RelaxationKind::Rex2TlsDescToInitialExec,
+                        linker_utils::elf::R_X86_64_CODE_4_GOTTPOFF,
                     );
                 }
             }
+            object::elf::R_X86_64_TLSDESC_CALL if output_kind.is_executable() => {
+                // call   *(%rax)
+                if section_bytes.get(offset..offset + 2)? == [0xff, 0x10] {
+                    return create(RelaxationKind::SkipTlsDescCall, object::elf::R_X86_64_NONE);
+                }
+            }
             _ => return None,
         };
         None
@@ -305,7 +468,7 @@ impl crate::arch::Relaxation for Relaxation {
         self.rel_info
     }
 
-    fn debug_kind(&self) -> impl std::fmt::Debug {
+    fn debug_kind(&self) -> &dyn std::fmt::Debug {
         &self.kind
     }
 
@@ -314,6 +477,18 @@ impl crate::arch::Relaxation for Relaxation {
     }
 }
 
+/// The W bit of a REX2 payload, which selects a 64 bit operand size.
+const REX2_W: u8 = 0x08;
+
+/// Returns the REX2 payload and the opcode of an instruction with an APX REX2 prefix, given the
+/// offset of a relocation that's immediately after the ModRM byte.
+fn rex2_payload_and_opcode(section_bytes: &[u8], offset: usize) -> Option<(u8, u8)> {
+    match section_bytes.get(offset.checked_sub(4)?..offset - 1)? {
+        [0xd5, rex2, opcode] => Some((*rex2, *opcode)),
+        _ => None,
+    }
+}
+
 enum TlsGdForm {
     Regular,
     Large,
@@ -391,4 +566,205 @@ fn test_relaxation() {
         &[0x48, 0x8d, 0xae],
         &[0x48, 0xc7, 0xc5],
     );
+    // The same, but with a REX2 prefix and %r16 as the destination.
+    check(
+        linker_utils::elf::R_X86_64_CODE_4_GOTPCRELX,
+        &[0xd5, 0x48, 0x8b, 0x05],
+        &[0xd5, 0x48, 0x8d, 0x05],
+        &[0xd5, 0x18, 0xc7, 0xc0],
+    );
+}
+
+#[test]
+fn test_tlsdesc_relaxation() {
+    use crate::arch::Relaxation as _;
+    use crate::args::RelocationModel;
+
+    // lea 0x0(%rip),%rax; call *(%rax)
+    let code = [0x48, 0x8d, 0x05, 0, 0, 0, 0, 0xff, 0x10];
+    let relax = |relocation_kind, offset, value_flags, output_kind| {
+        let r = Relaxation::new(
+            relocation_kind,
+            &code,
+            offset,
+            value_flags,
+            output_kind,
+            shf::EXECINSTR,
+        )?;
+        let mut out = code;
+        let mut offset = offset;
+        r.apply(&mut out, &mut offset, &mut -4);
+        Some((out, r.rel_info().kind))
+    };
+    let exe = OutputKind::DynamicExecutable(RelocationModel::Relocatable);
+    let local = ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT;
+
+    // mov $0x0,%rax
+    let (out, kind) = relax(object::elf::R_X86_64_GOTPC32_TLSDESC, 3, local, exe).unwrap();
+    assert_eq!(out[..3], [0x48, 0xc7, 0xc0]);
+    assert_eq!(kind, linker_utils::elf::RelocationKind::TpOff);
+
+    // mov 0x0(%rip),%rax
+    let (out, kind) = relax(
+        object::elf::R_X86_64_GOTPC32_TLSDESC,
+        3,
+        ValueFlags::DYNAMIC,
+        exe,
+    )
+    .unwrap();
+    assert_eq!(out[..3], [0x48, 0x8b, 0x05]);
+    assert_eq!(kind, linker_utils::elf::RelocationKind::GotTpOff);
+
+    // xchg %ax,%ax
+    let (out, _) = relax(
+        object::elf::R_X86_64_TLSDESC_CALL,
+        7,
+        ValueFlags::DYNAMIC,
+        exe,
+    )
+    .unwrap();
+    assert_eq!(out[7..], [0x66, 0x90]);
+
+    // lea 0x0(%rip),%r16 -> mov $0x0,%r16 or mov 0x0(%rip),%r16
+    let apx_code = [0xd5, 0x48, 0x8d, 0x05, 0, 0, 0, 0];
+    let apx_relax = |value_flags| {
+        let r = Relaxation::new(
+            linker_utils::elf::R_X86_64_CODE_4_GOTPC32_TLSDESC,
+            &apx_code,
+            4,
+            value_flags,
+            exe,
+            shf::EXECINSTR,
+        )?;
+        let mut out = apx_code;
+        r.apply(&mut out, &mut 4, &mut -4);
+        Some(out)
+    };
+    assert_eq!(apx_relax(local).unwrap()[..4], [0xd5, 0x18, 0xc7, 0xc0]);
+    assert_eq!(
+        apx_relax(ValueFlags::DYNAMIC).unwrap()[..4],
+        [0xd5, 0x48, 0x8b, 0x05]
+    );
+
+    // Shared objects need to keep the descriptor.
+    assert!(relax(
+        object::elf::R_X86_64_GOTPC32_TLSDESC,
+        3,
+        local,
+        OutputKind::SharedObject
+    )
+    .is_none());
+    assert!(relax(
+        object::elf::R_X86_64_TLSDESC_CALL,
+        7,
+        local,
+        OutputKind::SharedObject
+    )
+    .is_none());
+}
+
+#[test]
+fn test_gottpoff_relaxation() {
+    use crate::arch::Relaxation as _;
+    use crate::args::RelocationModel;
+
+    let relax = |code: &[u8], value_flags| {
+        let r = Relaxation::new(
+            object::elf::R_X86_64_GOTTPOFF,
+            code,
+            3,
+            value_flags,
+            OutputKind::StaticExecutable(RelocationModel::NonRelocatable),
+            shf::EXECINSTR,
+        )?;
+        let mut out = code.to_vec();
+        r.apply(&mut out, &mut 3, &mut -4);
+        assert_eq!(r.rel_info().kind, linker_utils::elf::RelocationKind::TpOff);
+        Some(out)
+    };
+    let local = ValueFlags::ADDRESS | ValueFlags::CAN_BYPASS_GOT;
+
+    // mov 0x0(%rip),%rax -> mov $0x0,%rax
+    assert_eq!(
+        relax(&[0x48, 0x8b, 0x05, 0, 0, 0, 0], local).unwrap()[..3],
+        [0x48, 0xc7, 0xc0]
+    );
+    // mov 0x0(%rip),%r12 -> mov $0x0,%r12
+    assert_eq!(
+        relax(&[0x4c, 0x8b, 0x25, 0, 0, 0, 0], local).unwrap()[..3],
+        [0x49, 0xc7, 0xc4]
+    );
+    // add 0x0(%rip),%rdx -> add $0x0,%rdx
+    assert_eq!(
+        relax(&[0x48, 0x03, 0x15, 0, 0, 0, 0], local).unwrap()[..3],
+        [0x48, 0x81, 0xc2]
+    );
+    // add 0x0(%rip),%r9 -> add $0x0,%r9
+    assert_eq!(
+        relax(&[0x4c, 0x03, 0x0d, 0, 0, 0, 0], local).unwrap()[..3],
+        [0x49, 0x81, 0xc1]
+    );
+
+    assert!(relax(&[0x48, 0x03, 0x15, 0, 0, 0, 0], ValueFlags::DYNAMIC).is_none());
+}
+
+#[test]
+fn test_lazy_plt() {
+    let mut header = [0; PLT_HEADER_SIZE as usize];
+    X86_64::write_plt_header(&mut header, 0x3000, 0x1000).unwrap();
+    // push 0x3008(%rip); jmp *0x3010(%rip)
+    assert_eq!(header[..6], [0xff, 0x35, 0x02, 0x20, 0x00, 0x00]);
+    assert_eq!(header[6..12], [0xff, 0x25, 0x04, 0x20, 0x00, 0x00]);
+
+    let mut entry = [0; PLT_ENTRY_SIZE as usize];
+    let initial_got_value = X86_64::write_lazy_plt_entry(
+        &mut entry,
+        0x3018,
+        0x1010,
+        0x1000,
+        2,
+        PltFeatures::default(),
+    )
+    .unwrap();
+    // The first call should continue with the push.
+    assert_eq!(initial_got_value, 0x1016);
+    assert_eq!(
+        entry,
+        [
+            0xff, 0x25, 0x02, 0x20, 0x00, 0x00, // jmp *0x3018(%rip)
+            0x68, 0x02, 0x00, 0x00, 0x00, // push $2
+            0xe9, 0xe0, 0xff, 0xff, 0xff, // jmp 0x1000
+        ]
+    );
+
+    // With IBT, the GOT entry points at the start of the entry, which is a landing pad.
+    let ibt = PltFeatures {
+        ibt: true,
+        ..PltFeatures::default()
+    };
+    let initial_got_value =
+        X86_64::write_lazy_plt_entry(&mut entry, 0x3018, 0x1010, 0x1000, 2, ibt).unwrap();
+    assert_eq!(initial_got_value, 0x1010);
+    assert_eq!(
+        entry,
+        [
+            0xf3, 0x0f, 0x1e, 0xfa, // endbr64
+            0x68, 0x02, 0x00, 0x00, 0x00, // push $2
+            0xe9, 0xe2, 0xff, 0xff, 0xff, // jmp 0x1000
+            0x66, 0x90, // xchg %ax,%ax
+        ]
+    );
+}
+
+#[test]
+fn test_compact_plt() {
+    let mut entry = [0; X86_64_COMPACT_PLT_ENTRY_SIZE as usize];
+    X86_64::write_plt_entry(&mut entry, 0x3000, 0x1008, 0, PltFeatures::default()).unwrap();
+    assert_eq!(
+        entry,
+        [
+            0xff, 0x25, 0xf2, 0x1f, 0x00, 0x00, // jmp *0x3000(%rip)
+            0x66, 0x90, // xchg %ax,%ax
+        ]
+    );
 }
@@ -1325,7 +1325,7 @@ impl<'data> RelaxationTester<'data> {
         let mut referent = None;
 
         match relocation_info.kind {
-            RelocationKind::Relative => {
+            RelocationKind::Relative | RelocationKind::RelativeLocalEntry => {
                 relative_to = self.section_address + offset;
             }
             RelocationKind::PltRelative
@@ -1349,6 +1349,10 @@ impl<'data> RelaxationTester<'data> {
                     .got_base_address
                     .context("Missing GOT base address")?;
             }
+            // Only produced by relaxation, so never present in input files.
+            RelocationKind::SymRelGlobalPointer => {
+                bail!("Unexpected gp-relative relocation in input");
+            }
             RelocationKind::Absolute
             | RelocationKind::Got
             | RelocationKind::TlsGdGot
@@ -1358,9 +1362,16 @@ impl<'data> RelaxationTester<'data> {
             | RelocationKind::TlsLdGot => {
                 // This is an absolute address, no adjustment to value is necessary.
             }
+            // The place of a signed pointer only holds the signing schema.
             RelocationKind::DtpOff
             | RelocationKind::TpOff
             | RelocationKind::TpOffAArch64
+            | RelocationKind::AuthAbsolute
+            | RelocationKind::AbsoluteSet
+            | RelocationKind::AbsoluteAddition
+            | RelocationKind::AbsoluteSubtraction
+            | RelocationKind::PairedLow
+            | RelocationKind::SymbolSize
             | RelocationKind::None => {
                 is_pointer = false;
             }
@@ -90,17 +90,25 @@ pub(crate) fn check_object(obj: &Binary) -> Result {
         }
         let name = sym.name()?;
         let name_bytes = sym.name_bytes()?;
-        let symbol_index = lookup_symbol(name_bytes, header, bloom_values, buckets, chains, dynsym)
-            .with_context(|| {
-                let hash = object::elf::gnu_hash(name_bytes);
-                format!(
-                    "Hash lookup of symbol `{name}` failed. \
+        let symbol_index = lookup_symbol(
+            name_bytes,
+            sym.index().0,
+            header,
+            bloom_values,
+            buckets,
+            chains,
+            dynsym,
+        )
+        .with_context(|| {
+            let hash = object::elf::gnu_hash(name_bytes);
+            format!(
+                "Hash lookup of symbol `{name}` failed. \
                         hash=0x{hash:x} \
                         buckets={buckets:?} \
                         symbol_base={symbol_base} \
                         chains={chains:x?}"
-                )
-            })?;
+            )
+        })?;
         if symbol_index != sym.index().0 {
             bail!(
                 "Dynamic symbol `{}` hash lookup found {symbol_index}, expected {}",
@@ -113,8 +121,12 @@ pub(crate) fn check_object(obj: &Binary) -> Result {
     Ok(())
 }
 
+/// Looks up `sym_name` and returns the index of the symbol found. There can be several symbols with
+/// the same name if they have different versions, so we return `expected_index` if it's one of
+/// them.
 fn lookup_symbol(
     sym_name: &[u8],
+    expected_index: usize,
     header: &object::elf::GnuHashHeader<LittleEndian>,
     bloom_values: &[u64],
     buckets: &[u32],
@@ -139,6 +151,7 @@ fn lookup_symbol(
     if symbol_index < symbol_base {
         bail!("symbol_index ({symbol_index}) < symbol_base ({symbol_base}). bucket={bucket}");
     }
+    let mut first_match = None;
     loop {
         let chain_value = chains[symbol_index - symbol_base];
         if chain_value & !1 == hash & !1
@@ -147,10 +160,13 @@ fn lookup_symbol(
                 .and_then(|sym| sym.name_bytes())
                 .is_ok_and(|n| n == sym_name)
         {
-            return Ok(symbol_index);
+            if symbol_index == expected_index {
+                return Ok(symbol_index);
+            }
+            first_match.get_or_insert(symbol_index);
         }
         if chain_value & 1 == 1 {
-            bail!("Symbol not found");
+            return first_match.context("Symbol not found");
         }
         symbol_index += 1;
     }
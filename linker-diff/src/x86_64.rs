@@ -39,7 +39,9 @@ impl Arch for X86_64 {
             Self::RelaxationKind::MovIndirectToLea => RelaxationByteRange::new(2, 6),
             Self::RelaxationKind::MovIndirectToAbsolute => RelaxationByteRange::new(2, 6),
             Self::RelaxationKind::RexMovIndirectToAbsolute => RelaxationByteRange::new(3, 7),
+            Self::RelaxationKind::Rex2MovIndirectToAbsolute => RelaxationByteRange::new(3, 7),
             Self::RelaxationKind::RexSubIndirectToAbsolute => RelaxationByteRange::new(3, 7),
+            Self::RelaxationKind::RexAddIndirectToAbsolute => RelaxationByteRange::new(3, 7),
             Self::RelaxationKind::RexCmpIndirectToAbsolute => RelaxationByteRange::new(3, 7),
             Self::RelaxationKind::CallIndirectToRelative => RelaxationByteRange::new(2, 6),
             Self::RelaxationKind::JmpIndirectToRelative => RelaxationByteRange::new(2, 6),
@@ -50,6 +52,8 @@ impl Arch for X86_64 {
             Self::RelaxationKind::TlsLdToLocalExec64 => RelaxationByteRange::new(3, 22),
             Self::RelaxationKind::SkipTlsDescCall => RelaxationByteRange::new(0, 2),
             Self::RelaxationKind::TlsDescToLocalExec => RelaxationByteRange::new(3, 7),
+            Self::RelaxationKind::TlsDescToInitialExec => RelaxationByteRange::new(3, 7),
+            Self::RelaxationKind::Rex2TlsDescToInitialExec => RelaxationByteRange::new(2, 6),
             Self::RelaxationKind::NoOp => match relaxation.new_r_type.0 {
                 // TLSDESC_CALL is a relocation that does nothing unless it's optimised away. To
                 // verify that it hasn't been optimised away, we need to make sure that we compare
@@ -119,6 +123,36 @@ impl Arch for X86_64 {
                     Self::RelaxationKind::RexMovIndirectToAbsolute,
                     object::elf::R_X86_64_TPOFF32,
                 );
+                relax(
+                    Self::RelaxationKind::RexAddIndirectToAbsolute,
+                    object::elf::R_X86_64_TPOFF32,
+                );
+            }
+            (SectionKind::Text, linker_utils::elf::R_X86_64_CODE_4_GOTPCRELX) => {
+                relax(
+                    Self::RelaxationKind::Rex2MovIndirectToAbsolute,
+                    object::elf::R_X86_64_32,
+                );
+                relax(
+                    Self::RelaxationKind::MovIndirectToLea,
+                    object::elf::R_X86_64_PC32,
+                );
+            }
+            (SectionKind::Text, linker_utils::elf::R_X86_64_CODE_4_GOTTPOFF) => {
+                relax(
+                    Self::RelaxationKind::Rex2MovIndirectToAbsolute,
+                    object::elf::R_X86_64_TPOFF32,
+                );
+            }
+            (SectionKind::Text, linker_utils::elf::R_X86_64_CODE_4_GOTPC32_TLSDESC) => {
+                relax(
+                    Self::RelaxationKind::Rex2MovIndirectToAbsolute,
+                    object::elf::R_X86_64_TPOFF32,
+                );
+                relax(
+                    Self::RelaxationKind::Rex2TlsDescToInitialExec,
+                    linker_utils::elf::R_X86_64_CODE_4_GOTTPOFF,
+                );
             }
             (SectionKind::Text, object::elf::R_X86_64_PLT32) => {
                 relax(Self::RelaxationKind::NoOp, object::elf::R_X86_64_PC32);
@@ -146,6 +180,16 @@ impl Arch for X86_64 {
                     object::elf::R_X86_64_NONE,
                 );
             }
+            (SectionKind::Text, object::elf::R_X86_64_GOTPC32_TLSDESC) => {
+                relax(
+                    Self::RelaxationKind::TlsDescToLocalExec,
+                    object::elf::R_X86_64_TPOFF32,
+                );
+                relax(
+                    Self::RelaxationKind::TlsDescToInitialExec,
+                    object::elf::R_X86_64_GOTTPOFF,
+                );
+            }
             (SectionKind::Text, object::elf::R_X86_64_TLSDESC_CALL) => {
                 relax(
                     Self::RelaxationKind::SkipTlsDescCall,
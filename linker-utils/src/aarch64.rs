@@ -6,6 +6,10 @@ use crate::elf::RelocationKindInfo;
 use crate::elf::RelocationSize;
 use crate::relaxation::RelocationModifier;
 
+/// PAuth ABI relocation types, which the object crate doesn't yet define.
+pub const R_AARCH64_AUTH_ABS64: u32 = 0x244;
+pub const R_AARCH64_AUTH_RELATIVE: u32 = 0x411;
+
 #[derive(Debug, Clone, Copy)]
 pub enum RelaxationKind {
     /// Leave the instruction alone. Used when we only want to change the kind of relocation used.
@@ -25,8 +29,26 @@ pub enum RelaxationKind {
 
     /// Replace with movk xn
     MovkXn,
+
+    /// Replace with ldr x0, [x0]
+    LdrX0,
+
+    /// Replace with movk x0 and replace the following call to `__tls_get_addr` and nop with an add
+    /// of the thread pointer.
+    MovkX0AddThreadPointer,
+
+    /// Replace with ldr x0, [x0] and replace the following call to `__tls_get_addr` and nop with an
+    /// add of the thread pointer.
+    LdrX0AddThreadPointer,
+
+    /// Replace ldr xt, [xn, ${offset}] with add xt, xn, ${offset}, keeping the registers. Used when
+    /// we load an address from the GOT, but we know the address, so can compute it directly.
+    LdrToAdd,
 }
 
+const MRS_X1_TPIDR_EL0: [u8; 4] = [0x41, 0xd0, 0x3b, 0xd5];
+const ADD_X0_X1_X0: [u8; 4] = [0x20, 0x00, 0x00, 0x8b];
+
 impl RelaxationKind {
     pub fn apply(self, section_bytes: &mut [u8], offset_in_section: &mut u64, _addend: &mut i64) {
         let offset = *offset_in_section as usize;
@@ -77,12 +99,44 @@ impl RelaxationKind {
                     dst_reg, 0x0, 0x80, 0xf2, // movk x{dst}, ${offset}
                 ]);
             }
+            RelaxationKind::LdrX0 => {
+                section_bytes[offset..offset + 4].copy_from_slice(&[
+                    0x0, 0x0, 0x40, 0xf9, // ldr x0, [x0, ${offset}]
+                ]);
+            }
+            RelaxationKind::MovkX0AddThreadPointer => {
+                section_bytes[offset..offset + 4].copy_from_slice(&[
+                    0x0, 0x0, 0x80, 0xf2, // movk x0, ${offset}
+                ]);
+                section_bytes[offset + 4..offset + 8].copy_from_slice(&MRS_X1_TPIDR_EL0);
+                section_bytes[offset + 8..offset + 12].copy_from_slice(&ADD_X0_X1_X0);
+            }
+            RelaxationKind::LdrX0AddThreadPointer => {
+                section_bytes[offset..offset + 4].copy_from_slice(&[
+                    0x0, 0x0, 0x40, 0xf9, // ldr x0, [x0, ${offset}]
+                ]);
+                section_bytes[offset + 4..offset + 8].copy_from_slice(&MRS_X1_TPIDR_EL0);
+                section_bytes[offset + 8..offset + 12].copy_from_slice(&ADD_X0_X1_X0);
+            }
+            RelaxationKind::LdrToAdd => {
+                const REGISTERS_MASK: u32 = 0x3ff;
+                const ADD_IMMEDIATE: u32 = 0x9100_0000;
+                let ldr = u32::from_le_bytes(*section_bytes[offset..].first_chunk::<4>().unwrap());
+                section_bytes[offset..offset + 4]
+                    .copy_from_slice(&(ADD_IMMEDIATE | (ldr & REGISTERS_MASK)).to_le_bytes());
+            }
         }
     }
 
     #[must_use]
     pub fn next_modifier(&self) -> RelocationModifier {
-        RelocationModifier::Normal
+        match self {
+            // The call to `__tls_get_addr` that we replaced has a relocation that we need to skip.
+            RelaxationKind::MovkX0AddThreadPointer | RelaxationKind::LdrX0AddThreadPointer => {
+                RelocationModifier::SkipNextRelocation
+            }
+            _ => RelocationModifier::Normal,
+        }
     }
 }
 
@@ -97,6 +151,11 @@ pub fn relocation_type_from_raw(r_type: u32) -> Option<RelocationKindInfo> {
         object::elf::R_AARCH64_ABS64 => {
             (RelocationKind::Absolute, RelocationSize::ByteSize(8), None)
         }
+        R_AARCH64_AUTH_ABS64 => (
+            RelocationKind::AuthAbsolute,
+            RelocationSize::ByteSize(8),
+            None,
+        ),
         object::elf::R_AARCH64_ABS32 => {
             (RelocationKind::Absolute, RelocationSize::ByteSize(4), None)
         }
@@ -661,6 +720,14 @@ impl RelocationInstruction {
             RelocationInstruction::JumpCall => {
                 mask = extracted_value as u32;
             }
+            RelocationInstruction::RiscV(instruction) => {
+                instruction.write_to_value(extracted_value, dest);
+                return;
+            }
+            RelocationInstruction::PowerPc(instruction) => {
+                instruction.write_to_value(extracted_value, dest);
+                return;
+            }
         }
         // Read the original value and combine it with the prepared mask.
         let mask_bytes = &mask.to_le_bytes();
@@ -2,6 +2,25 @@ use object::read::elf::SectionHeader;
 use object::LittleEndian;
 use std::borrow::Cow;
 
+/// The dynamic relocation type for TLS descriptors on RISC-V. Not yet provided by the object crate.
+pub const R_RISCV_TLSDESC: u32 = 12;
+
+/// A hint on the global entry point of a PowerPC64 function that doesn't set up its TOC pointer in
+/// the usual way. Not yet provided by the object crate.
+pub const R_PPC64_ENTRY: u32 = 118;
+
+/// Relocations on instructions that use the longer prefixes introduced by Intel APX. The number is
+/// how many bytes of prefix and opcode precede the ModRM byte. Not yet provided by the object crate.
+pub const R_X86_64_CODE_4_GOTPCRELX: u32 = 43;
+pub const R_X86_64_CODE_4_GOTTPOFF: u32 = 44;
+pub const R_X86_64_CODE_4_GOTPC32_TLSDESC: u32 = 45;
+pub const R_X86_64_CODE_5_GOTPCRELX: u32 = 46;
+pub const R_X86_64_CODE_5_GOTTPOFF: u32 = 47;
+pub const R_X86_64_CODE_5_GOTPC32_TLSDESC: u32 = 48;
+pub const R_X86_64_CODE_6_GOTPCRELX: u32 = 49;
+pub const R_X86_64_CODE_6_GOTTPOFF: u32 = 50;
+pub const R_X86_64_CODE_6_GOTPC32_TLSDESC: u32 = 51;
+
 macro_rules! const_name_by_value {
     ($needle: expr, $( $const:ident ),*) => {
         match $needle {
@@ -58,11 +77,29 @@ pub fn x86_64_rel_type_to_string(r_type: u32) -> Cow<'static, str> {
         R_X86_64_REX_GOTPCRELX
     ] {
         Cow::Borrowed(name)
+    } else if let Some(name) = apx_rel_type_name(r_type) {
+        Cow::Borrowed(name)
     } else {
         Cow::Owned(format!("Unknown x86_64 relocation type 0x{r_type:x}"))
     }
 }
 
+fn apx_rel_type_name(r_type: u32) -> Option<&'static str> {
+    let name = match r_type {
+        R_X86_64_CODE_4_GOTPCRELX => stringify!(R_X86_64_CODE_4_GOTPCRELX),
+        R_X86_64_CODE_4_GOTTPOFF => stringify!(R_X86_64_CODE_4_GOTTPOFF),
+        R_X86_64_CODE_4_GOTPC32_TLSDESC => stringify!(R_X86_64_CODE_4_GOTPC32_TLSDESC),
+        R_X86_64_CODE_5_GOTPCRELX => stringify!(R_X86_64_CODE_5_GOTPCRELX),
+        R_X86_64_CODE_5_GOTTPOFF => stringify!(R_X86_64_CODE_5_GOTTPOFF),
+        R_X86_64_CODE_5_GOTPC32_TLSDESC => stringify!(R_X86_64_CODE_5_GOTPC32_TLSDESC),
+        R_X86_64_CODE_6_GOTPCRELX => stringify!(R_X86_64_CODE_6_GOTPCRELX),
+        R_X86_64_CODE_6_GOTTPOFF => stringify!(R_X86_64_CODE_6_GOTTPOFF),
+        R_X86_64_CODE_6_GOTPC32_TLSDESC => stringify!(R_X86_64_CODE_6_GOTPC32_TLSDESC),
+        _ => return None,
+    };
+    Some(name)
+}
+
 #[must_use]
 pub fn aarch64_rel_type_to_string(r_type: u32) -> Cow<'static, str> {
     if let Some(name) = const_name_by_value![
@@ -202,11 +239,189 @@ pub fn aarch64_rel_type_to_string(r_type: u32) -> Cow<'static, str> {
         R_AARCH64_IRELATIVE
     ] {
         Cow::Borrowed(name)
+    } else if r_type == crate::aarch64::R_AARCH64_AUTH_ABS64 {
+        Cow::Borrowed("R_AARCH64_AUTH_ABS64")
+    } else if r_type == crate::aarch64::R_AARCH64_AUTH_RELATIVE {
+        Cow::Borrowed("R_AARCH64_AUTH_RELATIVE")
     } else {
         Cow::Owned(format!("Unknown aarch64 relocation type 0x{r_type:x}"))
     }
 }
 
+#[must_use]
+pub fn riscv64_rel_type_to_string(r_type: u32) -> Cow<'static, str> {
+    if let Some(name) = const_name_by_value![
+        r_type,
+        R_RISCV_NONE,
+        R_RISCV_32,
+        R_RISCV_64,
+        R_RISCV_RELATIVE,
+        R_RISCV_COPY,
+        R_RISCV_JUMP_SLOT,
+        R_RISCV_TLS_DTPMOD32,
+        R_RISCV_TLS_DTPMOD64,
+        R_RISCV_TLS_DTPREL32,
+        R_RISCV_TLS_DTPREL64,
+        R_RISCV_TLS_TPREL32,
+        R_RISCV_TLS_TPREL64,
+        R_RISCV_BRANCH,
+        R_RISCV_JAL,
+        R_RISCV_CALL,
+        R_RISCV_CALL_PLT,
+        R_RISCV_GOT_HI20,
+        R_RISCV_TLS_GOT_HI20,
+        R_RISCV_TLS_GD_HI20,
+        R_RISCV_PCREL_HI20,
+        R_RISCV_PCREL_LO12_I,
+        R_RISCV_PCREL_LO12_S,
+        R_RISCV_HI20,
+        R_RISCV_LO12_I,
+        R_RISCV_LO12_S,
+        R_RISCV_TPREL_HI20,
+        R_RISCV_TPREL_LO12_I,
+        R_RISCV_TPREL_LO12_S,
+        R_RISCV_TPREL_ADD,
+        R_RISCV_ADD8,
+        R_RISCV_ADD16,
+        R_RISCV_ADD32,
+        R_RISCV_ADD64,
+        R_RISCV_SUB8,
+        R_RISCV_SUB16,
+        R_RISCV_SUB32,
+        R_RISCV_SUB64,
+        R_RISCV_GNU_VTINHERIT,
+        R_RISCV_GNU_VTENTRY,
+        R_RISCV_ALIGN,
+        R_RISCV_RVC_BRANCH,
+        R_RISCV_RVC_JUMP,
+        R_RISCV_RVC_LUI,
+        R_RISCV_GPREL_I,
+        R_RISCV_GPREL_S,
+        R_RISCV_TPREL_I,
+        R_RISCV_TPREL_S,
+        R_RISCV_RELAX,
+        R_RISCV_SUB6,
+        R_RISCV_SET6,
+        R_RISCV_SET8,
+        R_RISCV_SET16,
+        R_RISCV_SET32,
+        R_RISCV_32_PCREL,
+        R_RISCV_IRELATIVE,
+        R_RISCV_PLT32,
+        R_RISCV_SET_ULEB128,
+        R_RISCV_SUB_ULEB128,
+        R_RISCV_TLSDESC_HI20,
+        R_RISCV_TLSDESC_LOAD_LO12,
+        R_RISCV_TLSDESC_ADD_LO12,
+        R_RISCV_TLSDESC_CALL
+    ] {
+        Cow::Borrowed(name)
+    } else if r_type == R_RISCV_TLSDESC {
+        Cow::Borrowed(stringify!(R_RISCV_TLSDESC))
+    } else {
+        Cow::Owned(format!("Unknown riscv64 relocation type 0x{r_type:x}"))
+    }
+}
+
+#[must_use]
+pub fn ppc64_rel_type_to_string(r_type: u32) -> Cow<'static, str> {
+    if let Some(name) = const_name_by_value![
+        r_type,
+        R_PPC64_NONE,
+        R_PPC64_ADDR32,
+        R_PPC64_ADDR24,
+        R_PPC64_ADDR16,
+        R_PPC64_ADDR16_LO,
+        R_PPC64_ADDR16_HI,
+        R_PPC64_ADDR16_HA,
+        R_PPC64_ADDR14,
+        R_PPC64_ADDR14_BRTAKEN,
+        R_PPC64_ADDR14_BRNTAKEN,
+        R_PPC64_REL24,
+        R_PPC64_REL14,
+        R_PPC64_REL14_BRTAKEN,
+        R_PPC64_REL14_BRNTAKEN,
+        R_PPC64_GOT16,
+        R_PPC64_GOT16_LO,
+        R_PPC64_GOT16_HI,
+        R_PPC64_GOT16_HA,
+        R_PPC64_COPY,
+        R_PPC64_GLOB_DAT,
+        R_PPC64_JMP_SLOT,
+        R_PPC64_RELATIVE,
+        R_PPC64_REL32,
+        R_PPC64_ADDR64,
+        R_PPC64_ADDR16_HIGHER,
+        R_PPC64_ADDR16_HIGHERA,
+        R_PPC64_ADDR16_HIGHEST,
+        R_PPC64_ADDR16_HIGHESTA,
+        R_PPC64_REL64,
+        R_PPC64_TOC16,
+        R_PPC64_TOC16_LO,
+        R_PPC64_TOC16_HI,
+        R_PPC64_TOC16_HA,
+        R_PPC64_TOC,
+        R_PPC64_ADDR16_DS,
+        R_PPC64_ADDR16_LO_DS,
+        R_PPC64_GOT16_DS,
+        R_PPC64_GOT16_LO_DS,
+        R_PPC64_TOC16_DS,
+        R_PPC64_TOC16_LO_DS,
+        R_PPC64_TLS,
+        R_PPC64_DTPMOD64,
+        R_PPC64_TPREL16,
+        R_PPC64_TPREL16_LO,
+        R_PPC64_TPREL16_HI,
+        R_PPC64_TPREL16_HA,
+        R_PPC64_TPREL64,
+        R_PPC64_DTPREL16,
+        R_PPC64_DTPREL16_LO,
+        R_PPC64_DTPREL16_HI,
+        R_PPC64_DTPREL16_HA,
+        R_PPC64_DTPREL64,
+        R_PPC64_GOT_TLSGD16,
+        R_PPC64_GOT_TLSGD16_LO,
+        R_PPC64_GOT_TLSGD16_HI,
+        R_PPC64_GOT_TLSGD16_HA,
+        R_PPC64_GOT_TLSLD16,
+        R_PPC64_GOT_TLSLD16_LO,
+        R_PPC64_GOT_TLSLD16_HI,
+        R_PPC64_GOT_TLSLD16_HA,
+        R_PPC64_GOT_TPREL16_DS,
+        R_PPC64_GOT_TPREL16_LO_DS,
+        R_PPC64_GOT_TPREL16_HI,
+        R_PPC64_GOT_TPREL16_HA,
+        R_PPC64_GOT_DTPREL16_DS,
+        R_PPC64_GOT_DTPREL16_LO_DS,
+        R_PPC64_GOT_DTPREL16_HI,
+        R_PPC64_GOT_DTPREL16_HA,
+        R_PPC64_TPREL16_DS,
+        R_PPC64_TPREL16_LO_DS,
+        R_PPC64_DTPREL16_DS,
+        R_PPC64_DTPREL16_LO_DS,
+        R_PPC64_TLSGD,
+        R_PPC64_TLSLD,
+        R_PPC64_TOCSAVE,
+        R_PPC64_ADDR16_HIGH,
+        R_PPC64_ADDR16_HIGHA,
+        R_PPC64_TPREL16_HIGH,
+        R_PPC64_TPREL16_HIGHA,
+        R_PPC64_DTPREL16_HIGH,
+        R_PPC64_DTPREL16_HIGHA,
+        R_PPC64_IRELATIVE,
+        R_PPC64_REL16,
+        R_PPC64_REL16_LO,
+        R_PPC64_REL16_HI,
+        R_PPC64_REL16_HA
+    ] {
+        Cow::Borrowed(name)
+    } else if r_type == R_PPC64_ENTRY {
+        Cow::Borrowed(stringify!(R_PPC64_ENTRY))
+    } else {
+        Cow::Owned(format!("Unknown ppc64 relocation type 0x{r_type:x}"))
+    }
+}
+
 /// Section flag bit values.
 pub mod shf {
     use super::SectionFlags;
@@ -263,6 +478,18 @@ pub mod sht {
     pub const HIPROC: SectionType = SectionType(object::elf::SHT_HIPROC);
     pub const LOUSER: SectionType = SectionType(object::elf::SHT_LOUSER);
     pub const HIUSER: SectionType = SectionType(object::elf::SHT_HIUSER);
+
+    /// Emitted by LLVM when `-fsymbol-partition` is used. Not currently defined by the object crate.
+    pub const LLVM_SYMPART: SectionType = SectionType(0x6fff_4c05);
+
+    /// Emitted by LLVM to list the symbols whose addresses are significant, for use by identical
+    /// code folding. Not currently defined by the object crate.
+    pub const LLVM_ADDRSIG: SectionType = SectionType(0x6fff_4c03);
+
+    /// AArch64 memory tagging of globals. Not currently defined by the object crate.
+    pub const AARCH64_MEMTAG_GLOBALS_STATIC: SectionType = SectionType(0x7000_0007);
+    pub const AARCH64_MEMTAG_GLOBALS_DYNAMIC: SectionType = SectionType(0x7000_0008);
+    pub const RISCV_ATTRIBUTES: SectionType = SectionType(object::elf::SHT_RISCV_ATTRIBUTES);
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -446,6 +673,8 @@ pub mod secnames {
     pub const GNU_VERSION_SECTION_NAME: &[u8] = GNU_VERSION_SECTION_NAME_STR.as_bytes();
     pub const GNU_VERSION_R_SECTION_NAME_STR: &str = ".gnu.version_r";
     pub const GNU_VERSION_R_SECTION_NAME: &[u8] = GNU_VERSION_R_SECTION_NAME_STR.as_bytes();
+    pub const GNU_VERSION_D_SECTION_NAME_STR: &str = ".gnu.version_d";
+    pub const GNU_VERSION_D_SECTION_NAME: &[u8] = GNU_VERSION_D_SECTION_NAME_STR.as_bytes();
     pub const PROGRAM_HEADERS_SECTION_NAME_STR: &str = ".phdr";
     pub const PROGRAM_HEADERS_SECTION_NAME: &[u8] = PROGRAM_HEADERS_SECTION_NAME_STR.as_bytes();
     pub const SECTION_HEADERS_SECTION_NAME_STR: &str = ".shdr";
@@ -466,6 +695,16 @@ pub mod secnames {
     pub const NOTE_GNU_PROPERTY_SECTION_NAME: &[u8] = NOTE_GNU_PROPERTY_SECTION_NAME_STR.as_bytes();
     pub const NOTE_GNU_BUILD_ID_SECTION_NAME_STR: &str = ".note.gnu.build-id";
     pub const NOTE_GNU_BUILD_ID_SECTION_NAME: &[u8] = NOTE_GNU_BUILD_ID_SECTION_NAME_STR.as_bytes();
+    pub const GNU_BUILD_ATTRIBUTES_SECTION_NAME_STR: &str = ".gnu.build.attributes";
+    pub const GNU_BUILD_ATTRIBUTES_SECTION_NAME: &[u8] =
+        GNU_BUILD_ATTRIBUTES_SECTION_NAME_STR.as_bytes();
+    pub const RISCV_ATTRIBUTES_SECTION_NAME_STR: &str = ".riscv.attributes";
+    pub const RISCV_ATTRIBUTES_SECTION_NAME: &[u8] = RISCV_ATTRIBUTES_SECTION_NAME_STR.as_bytes();
+    pub const GNU_DEBUGLINK_SECTION_NAME_STR: &str = ".gnu_debuglink";
+    pub const GNU_DEBUGLINK_SECTION_NAME: &[u8] = GNU_DEBUGLINK_SECTION_NAME_STR.as_bytes();
+    pub const MEMTAG_GLOBALS_DYNAMIC_SECTION_NAME_STR: &str = ".memtag.globals.dynamic";
+    pub const MEMTAG_GLOBALS_DYNAMIC_SECTION_NAME: &[u8] =
+        MEMTAG_GLOBALS_DYNAMIC_SECTION_NAME_STR.as_bytes();
     pub const DEBUG_LOC_SECTION_NAME_STR: &str = ".debug.loc";
     pub const DEBUG_LOC_SECTION_NAME: &[u8] = DEBUG_LOC_SECTION_NAME_STR.as_bytes();
     pub const DEBUG_RANGES_SECTION_NAME_STR: &str = ".debug.ranges";
@@ -486,12 +725,46 @@ pub enum RelocationKind {
     /// within a page, so dynamic relocation creation must be skipped.
     AbsoluteAArch64,
 
+    /// The absolute address of a symbol or section, signed by the dynamic loader using the schema
+    /// encoded in the place. Used by the AArch64 PAuth ABI. Since the signature is only known at
+    /// runtime, this always needs a dynamic relocation.
+    AuthAbsolute,
+
     /// The address of the symbol, relative to the place of the relocation.
     Relative,
 
+    /// The absolute address of a symbol, which replaces the value at the place. Unlike `Absolute`,
+    /// this never needs a dynamic relocation, since it's only used as the first half of a
+    /// difference between two labels, with the other label subtracted by an `AbsoluteSubtraction`.
+    AbsoluteSet,
+
+    /// The absolute address of a symbol, added to the value at the place. Used with
+    /// `AbsoluteSubtraction` to compute the difference between two labels.
+    AbsoluteAddition,
+
+    /// The absolute address of a symbol, subtracted from the value at the place.
+    AbsoluteSubtraction,
+
+    /// The low bits of the value computed by another relocation, the high part, whose place is the
+    /// address of our symbol. RISC-V splits PC-relative values across two instructions that may be
+    /// some distance apart, so the low part needs to know where the high part was.
+    PairedLow,
+
+    /// The address of the symbol's local entry point, relative to the place of the relocation.
+    /// PowerPC64 functions that set up a TOC pointer have a second entry point that skips doing
+    /// so, which calls from code that shares the same TOC should use.
+    RelativeLocalEntry,
+
+    /// The size of the symbol (its st_size). If the symbol is defined by a shared object, its size
+    /// may differ at runtime, so this needs a dynamic relocation.
+    SymbolSize,
+
     /// The address of the symbol, relative to the base address of the GOT.
     SymRelGotBase,
 
+    /// The address of the symbol, relative to the RISC-V global pointer, `__global_pointer$`.
+    SymRelGlobalPointer,
+
     /// The offset of the symbol's GOT entry, relative to the start of the GOT.
     GotRelGotBase,
 
@@ -629,6 +902,38 @@ impl DynamicRelocationKind {
             DynamicRelocationKind::JumpSlot => object::elf::R_AARCH64_JUMP_SLOT,
         }
     }
+
+    #[must_use]
+    pub fn riscv64_r_type(&self) -> u32 {
+        match self {
+            DynamicRelocationKind::Copy => object::elf::R_RISCV_COPY,
+            DynamicRelocationKind::Irelative => object::elf::R_RISCV_IRELATIVE,
+            DynamicRelocationKind::DtpMod => object::elf::R_RISCV_TLS_DTPMOD64,
+            DynamicRelocationKind::DtpOff => object::elf::R_RISCV_TLS_DTPREL64,
+            DynamicRelocationKind::TpOff => object::elf::R_RISCV_TLS_TPREL64,
+            DynamicRelocationKind::Relative => object::elf::R_RISCV_RELATIVE,
+            // RISC-V doesn't have a separate relocation type for GOT entries.
+            DynamicRelocationKind::DynamicSymbol => object::elf::R_RISCV_64,
+            DynamicRelocationKind::TlsDesc => R_RISCV_TLSDESC,
+            DynamicRelocationKind::JumpSlot => object::elf::R_RISCV_JUMP_SLOT,
+        }
+    }
+
+    #[must_use]
+    pub fn ppc64_r_type(&self) -> u32 {
+        match self {
+            DynamicRelocationKind::Copy => object::elf::R_PPC64_COPY,
+            DynamicRelocationKind::Irelative => object::elf::R_PPC64_IRELATIVE,
+            DynamicRelocationKind::DtpMod => object::elf::R_PPC64_DTPMOD64,
+            DynamicRelocationKind::DtpOff => object::elf::R_PPC64_DTPREL64,
+            DynamicRelocationKind::TpOff => object::elf::R_PPC64_TPREL64,
+            DynamicRelocationKind::Relative => object::elf::R_PPC64_RELATIVE,
+            DynamicRelocationKind::DynamicSymbol => object::elf::R_PPC64_GLOB_DAT,
+            // PowerPC64 doesn't have TLS descriptors, so nothing should ask for this.
+            DynamicRelocationKind::TlsDesc => object::elf::R_PPC64_NONE,
+            DynamicRelocationKind::JumpSlot => object::elf::R_PPC64_JMP_SLOT,
+        }
+    }
 }
 
 // Half-opened range bounded inclusively below and exclusively above: [`start``, `end`)
@@ -650,6 +955,8 @@ pub enum RelocationInstruction {
     TstBr,
     Bcond,
     JumpCall,
+    RiscV(crate::riscv64::RiscVInstruction),
+    PowerPc(crate::ppc64::PowerPcInstruction),
 }
 
 impl RelocationInstruction {
@@ -724,7 +1031,7 @@ impl BitMask {
 #[must_use]
 pub fn extract_bits(value: u64, start: u32, end: u32) -> u64 {
     debug_assert!(start < end);
-    (value >> (start)) & ((1 << (end - start)) - 1)
+    (value >> (start)) & (u64::MAX >> (64 - (end - start)))
 }
 
 #[cfg(test)]
@@ -747,10 +1054,25 @@ mod tests {
             "Unknown x86_64 relocation type 0x40"
         );
 
+        assert_eq!(
+            &x86_64_rel_type_to_string(R_X86_64_CODE_4_GOTPCRELX),
+            stringify!(R_X86_64_CODE_4_GOTPCRELX)
+        );
+
         assert_eq!(
             &aarch64_rel_type_to_string(64),
             "Unknown aarch64 relocation type 0x40"
         );
+
+        assert_eq!(
+            &riscv64_rel_type_to_string(R_RISCV_SET_ULEB128),
+            stringify!(R_RISCV_SET_ULEB128)
+        );
+
+        assert_eq!(
+            &ppc64_rel_type_to_string(R_PPC64_ENTRY),
+            stringify!(R_PPC64_ENTRY)
+        );
     }
 
     #[test]
@@ -1,5 +1,8 @@
 pub mod aarch64;
 pub mod elf;
+pub mod ppc64;
 pub mod relaxation;
+pub mod riscv64;
+pub mod riscv_attributes;
 pub mod utils;
 pub mod x86_64;
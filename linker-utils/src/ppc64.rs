@@ -0,0 +1,326 @@
+use crate::elf::RelocationInstruction;
+use crate::elf::RelocationKind;
+use crate::elf::RelocationKindInfo;
+use crate::elf::RelocationSize;
+use crate::elf::R_PPC64_ENTRY;
+use crate::relaxation::RelocationModifier;
+
+#[derive(Debug, Clone, Copy)]
+pub enum RelaxationKind {
+    /// Leave the instructions alone. Used when we only want to change the kind of relocation used.
+    NoOp,
+}
+
+/// The instruction fields that PowerPC relocations are applied to. Instructions are big-endian
+/// 32 bit words in the ISA manual, which means that on a little-endian target the 16 bit immediate
+/// field of D and DS form instructions comes first in memory.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum PowerPcInstruction {
+    /// The 16 bit immediate of a D form instruction, e.g. addi, lwz. Receives the low 16 bits of
+    /// the extracted value, so is also used for the @h, @higher and @highest variants.
+    Lo16,
+
+    /// The 14 bit immediate of a DS form instruction, e.g. ld, std. The value must be a multiple
+    /// of 4, since the bottom two bits of the field are part of the opcode.
+    Lo16Ds,
+
+    /// The 16 bit immediate of a D form instruction that receives the high 16 bits of the value,
+    /// rounded to account for the low 16 bits being sign-extended by the instruction that uses
+    /// them, e.g. addis.
+    Ha16,
+
+    /// The 24 bit word offset of an I form branch, e.g. b, bl.
+    Branch24,
+
+    /// The 14 bit word offset of a B form conditional branch.
+    Branch14,
+
+    /// A call that goes via a PLT call stub. The stub saves the caller's TOC pointer in the stack
+    /// frame before jumping to the callee, so the nop that follows the call gets replaced with a
+    /// load that restores it.
+    CallViaStub,
+}
+
+const NOP: u32 = 0x6000_0000;
+
+/// ld r2, 24(r1). The ELFv2 ABI reserves that stack slot for saving the TOC pointer.
+const RESTORE_TOC: u32 = 0xe841_0018;
+
+/// How far `.TOC.` is from the start of the GOT. This allows signed 16 bit offsets from the TOC
+/// pointer to reach 64KiB of GOT entries.
+pub const TOC_BASE_OFFSET: u64 = 0x8000;
+
+impl RelaxationKind {
+    pub fn apply(self, _section_bytes: &mut [u8], _offset_in_section: &mut u64, _addend: &mut i64) {
+        match self {
+            RelaxationKind::NoOp => {}
+        }
+    }
+
+    #[must_use]
+    pub fn next_modifier(&self) -> RelocationModifier {
+        RelocationModifier::Normal
+    }
+}
+
+/// Returns the offset of a function's local entry point from its global entry point, given the
+/// function symbol's `st_other`. The global entry point sets up the TOC pointer from r12, while
+/// the local entry point expects the TOC pointer to already be set up by the caller.
+#[must_use]
+pub fn local_entry_offset(st_other: u8) -> u64 {
+    // Values 0 and 1 mean that the two entry points are the same. Other values give the offset as
+    // a power of two number of bytes.
+    match (st_other & object::elf::STO_PPC64_LOCAL_MASK) >> object::elf::STO_PPC64_LOCAL_BIT {
+        0 | 1 => 0,
+        n => 1 << n,
+    }
+}
+
+#[must_use]
+pub fn relocation_type_from_raw(r_type: u32) -> Option<RelocationKindInfo> {
+    let instruction = |insn, start, end| {
+        RelocationSize::bit_mask(start, end, RelocationInstruction::PowerPc(insn))
+    };
+    let lo = instruction(PowerPcInstruction::Lo16, 0, 16);
+    let lo_ds = instruction(PowerPcInstruction::Lo16Ds, 0, 16);
+    let hi = instruction(PowerPcInstruction::Lo16, 16, 32);
+    let ha = instruction(PowerPcInstruction::Ha16, 0, 32);
+    let higher = instruction(PowerPcInstruction::Lo16, 32, 48);
+    let highest = instruction(PowerPcInstruction::Lo16, 48, 64);
+
+    let (kind, size) = match r_type {
+        object::elf::R_PPC64_NONE
+        | object::elf::R_PPC64_TLS
+        | object::elf::R_PPC64_TLSGD
+        | object::elf::R_PPC64_TLSLD
+        | object::elf::R_PPC64_TOCSAVE
+        | R_PPC64_ENTRY => (RelocationKind::None, RelocationSize::ByteSize(0)),
+
+        // Data relocations
+        object::elf::R_PPC64_ADDR16 => (RelocationKind::Absolute, RelocationSize::ByteSize(2)),
+        object::elf::R_PPC64_ADDR32 => (RelocationKind::Absolute, RelocationSize::ByteSize(4)),
+        object::elf::R_PPC64_ADDR64 => (RelocationKind::Absolute, RelocationSize::ByteSize(8)),
+        object::elf::R_PPC64_REL32 => (RelocationKind::Relative, RelocationSize::ByteSize(4)),
+        object::elf::R_PPC64_REL64 => (RelocationKind::Relative, RelocationSize::ByteSize(8)),
+        object::elf::R_PPC64_DTPREL64 => (RelocationKind::DtpOff, RelocationSize::ByteSize(8)),
+
+        // Absolute addressing
+        object::elf::R_PPC64_ADDR16_LO => (RelocationKind::Absolute, lo),
+        object::elf::R_PPC64_ADDR16_LO_DS => (RelocationKind::Absolute, lo_ds),
+        object::elf::R_PPC64_ADDR16_HI | object::elf::R_PPC64_ADDR16_HIGH => {
+            (RelocationKind::Absolute, hi)
+        }
+        object::elf::R_PPC64_ADDR16_HA | object::elf::R_PPC64_ADDR16_HIGHA => {
+            (RelocationKind::Absolute, ha)
+        }
+        object::elf::R_PPC64_ADDR16_HIGHER => (RelocationKind::Absolute, higher),
+        object::elf::R_PPC64_ADDR16_HIGHEST => (RelocationKind::Absolute, highest),
+
+        // Control transfer
+        object::elf::R_PPC64_REL24 => (
+            RelocationKind::PltRelative,
+            instruction(PowerPcInstruction::CallViaStub, 0, 26),
+        ),
+        object::elf::R_PPC64_REL14
+        | object::elf::R_PPC64_REL14_BRTAKEN
+        | object::elf::R_PPC64_REL14_BRNTAKEN => (
+            RelocationKind::Relative,
+            instruction(PowerPcInstruction::Branch14, 0, 16),
+        ),
+
+        // PC-relative addressing, mostly used by global entry points to compute the TOC pointer.
+        object::elf::R_PPC64_REL16_LO => (RelocationKind::Relative, lo),
+        object::elf::R_PPC64_REL16_HI => (RelocationKind::Relative, hi),
+        object::elf::R_PPC64_REL16_HA => (RelocationKind::Relative, ha),
+
+        // TOC-relative addressing
+        object::elf::R_PPC64_TOC16 | object::elf::R_PPC64_TOC16_LO => {
+            (RelocationKind::SymRelGotBase, lo)
+        }
+        object::elf::R_PPC64_TOC16_DS | object::elf::R_PPC64_TOC16_LO_DS => {
+            (RelocationKind::SymRelGotBase, lo_ds)
+        }
+        object::elf::R_PPC64_TOC16_HI => (RelocationKind::SymRelGotBase, hi),
+        object::elf::R_PPC64_TOC16_HA => (RelocationKind::SymRelGotBase, ha),
+        object::elf::R_PPC64_GOT16 | object::elf::R_PPC64_GOT16_LO => {
+            (RelocationKind::GotRelGotBase, lo)
+        }
+        object::elf::R_PPC64_GOT16_DS | object::elf::R_PPC64_GOT16_LO_DS => {
+            (RelocationKind::GotRelGotBase, lo_ds)
+        }
+        object::elf::R_PPC64_GOT16_HI => (RelocationKind::GotRelGotBase, hi),
+        object::elf::R_PPC64_GOT16_HA => (RelocationKind::GotRelGotBase, ha),
+
+        // Thread-local storage
+        object::elf::R_PPC64_TPREL16 | object::elf::R_PPC64_TPREL16_LO => {
+            (RelocationKind::TpOff, lo)
+        }
+        object::elf::R_PPC64_TPREL16_DS | object::elf::R_PPC64_TPREL16_LO_DS => {
+            (RelocationKind::TpOff, lo_ds)
+        }
+        object::elf::R_PPC64_TPREL16_HI | object::elf::R_PPC64_TPREL16_HIGH => {
+            (RelocationKind::TpOff, hi)
+        }
+        object::elf::R_PPC64_TPREL16_HA | object::elf::R_PPC64_TPREL16_HIGHA => {
+            (RelocationKind::TpOff, ha)
+        }
+        object::elf::R_PPC64_DTPREL16 | object::elf::R_PPC64_DTPREL16_LO => {
+            (RelocationKind::DtpOff, lo)
+        }
+        object::elf::R_PPC64_DTPREL16_DS | object::elf::R_PPC64_DTPREL16_LO_DS => {
+            (RelocationKind::DtpOff, lo_ds)
+        }
+        object::elf::R_PPC64_DTPREL16_HI | object::elf::R_PPC64_DTPREL16_HIGH => {
+            (RelocationKind::DtpOff, hi)
+        }
+        object::elf::R_PPC64_DTPREL16_HA | object::elf::R_PPC64_DTPREL16_HIGHA => {
+            (RelocationKind::DtpOff, ha)
+        }
+        object::elf::R_PPC64_GOT_TPREL16_DS | object::elf::R_PPC64_GOT_TPREL16_LO_DS => {
+            (RelocationKind::GotTpOffGotBase, lo_ds)
+        }
+        object::elf::R_PPC64_GOT_TPREL16_HI => (RelocationKind::GotTpOffGotBase, hi),
+        object::elf::R_PPC64_GOT_TPREL16_HA => (RelocationKind::GotTpOffGotBase, ha),
+        object::elf::R_PPC64_GOT_TLSGD16 | object::elf::R_PPC64_GOT_TLSGD16_LO => {
+            (RelocationKind::TlsGdGotBase, lo)
+        }
+        object::elf::R_PPC64_GOT_TLSGD16_HI => (RelocationKind::TlsGdGotBase, hi),
+        object::elf::R_PPC64_GOT_TLSGD16_HA => (RelocationKind::TlsGdGotBase, ha),
+        object::elf::R_PPC64_GOT_TLSLD16 | object::elf::R_PPC64_GOT_TLSLD16_LO => {
+            (RelocationKind::TlsLdGotBase, lo)
+        }
+        object::elf::R_PPC64_GOT_TLSLD16_HI => (RelocationKind::TlsLdGotBase, hi),
+        object::elf::R_PPC64_GOT_TLSLD16_HA => (RelocationKind::TlsLdGotBase, ha),
+
+        _ => return None,
+    };
+
+    Some(RelocationKindInfo {
+        kind,
+        size,
+        mask: None,
+    })
+}
+
+/// Returns the relocation info to use for a call that doesn't need to go via a PLT call stub.
+#[must_use]
+pub fn direct_call_relocation() -> RelocationKindInfo {
+    RelocationKindInfo {
+        kind: RelocationKind::RelativeLocalEntry,
+        size: RelocationSize::bit_mask(
+            0,
+            26,
+            RelocationInstruction::PowerPc(PowerPcInstruction::Branch24),
+        ),
+        mask: None,
+    }
+}
+
+impl PowerPcInstruction {
+    /// Encodes `value` into the instruction at the start of `dest`, replacing whatever was in the
+    /// field previously.
+    pub fn write_to_value(self, value: u64, dest: &mut [u8]) {
+        let value = value as u32;
+        let insn = read_u32(dest);
+        match self {
+            PowerPcInstruction::Lo16 => write_u32(dest, (insn & !0xffff) | (value & 0xffff)),
+            PowerPcInstruction::Lo16Ds => write_u32(dest, (insn & !0xfffc) | (value & 0xfffc)),
+            PowerPcInstruction::Ha16 => {
+                write_u32(dest, (insn & !0xffff) | (value.wrapping_add(0x8000) >> 16));
+            }
+            PowerPcInstruction::Branch24 => {
+                write_u32(dest, (insn & !0x03ff_fffc) | (value & 0x03ff_fffc));
+            }
+            PowerPcInstruction::Branch14 => write_u32(dest, (insn & !0xfffc) | (value & 0xfffc)),
+            PowerPcInstruction::CallViaStub => {
+                PowerPcInstruction::Branch24.write_to_value(value.into(), dest);
+                if let Some(next) = dest.get_mut(4..8) {
+                    if read_u32(next) == NOP {
+                        write_u32(next, RESTORE_TOC);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(*bytes.first_chunk::<4>().unwrap())
+}
+
+fn write_u32(bytes: &mut [u8], value: u32) {
+    bytes[..4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn check(instruction: PowerPcInstruction, input: &[u8], value: u64, expected: &[u8]) {
+        let mut out = input.to_owned();
+        instruction.write_to_value(value, &mut out);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_write_to_value() {
+        // addis r2, r12, 0 -> addis r2, r12, 1 (0x8000 rounds up)
+        check(
+            PowerPcInstruction::Ha16,
+            &0x3c4c_0000_u32.to_le_bytes(),
+            0x1_8000,
+            &0x3c4c_0002_u32.to_le_bytes(),
+        );
+        // addi r2, r2, 0 -> addi r2, r2, -32768
+        check(
+            PowerPcInstruction::Lo16,
+            &0x3842_0000_u32.to_le_bytes(),
+            0x1_8000,
+            &0x3842_8000_u32.to_le_bytes(),
+        );
+        // ld r3, 0(r3) -> ld r3, 0x7ffc(r3)
+        check(
+            PowerPcInstruction::Lo16Ds,
+            &0xe863_0000_u32.to_le_bytes(),
+            0x7ffc,
+            &0xe863_7ffc_u32.to_le_bytes(),
+        );
+        // bl . -> bl .-4
+        check(
+            PowerPcInstruction::Branch24,
+            &0x4800_0001_u32.to_le_bytes(),
+            -4_i64 as u64,
+            &0x4bff_fffd_u32.to_le_bytes(),
+        );
+        // beq . -> beq .+8
+        check(
+            PowerPcInstruction::Branch14,
+            &0x4182_0000_u32.to_le_bytes(),
+            8,
+            &0x4182_0008_u32.to_le_bytes(),
+        );
+    }
+
+    #[test]
+    fn test_call_via_stub() {
+        // bl .; nop -> bl .+0x10; ld r2, 24(r1)
+        let mut bytes = [0x01, 0x00, 0x00, 0x48, 0x00, 0x00, 0x00, 0x60];
+        PowerPcInstruction::CallViaStub.write_to_value(0x10, &mut bytes);
+        assert_eq!(bytes, [0x11, 0x00, 0x00, 0x48, 0x18, 0x00, 0x41, 0xe8]);
+
+        // Tail calls aren't followed by a nop, so the following instruction is left alone.
+        let mut bytes = [0x00, 0x00, 0x00, 0x48, 0xa6, 0x02, 0x08, 0x7c];
+        PowerPcInstruction::CallViaStub.write_to_value(0x10, &mut bytes);
+        assert_eq!(bytes, [0x10, 0x00, 0x00, 0x48, 0xa6, 0x02, 0x08, 0x7c]);
+    }
+
+    #[test]
+    fn test_local_entry_offset() {
+        assert_eq!(local_entry_offset(0), 0);
+        assert_eq!(local_entry_offset(0x20), 0);
+        assert_eq!(local_entry_offset(0x60), 8);
+        // Visibility bits don't affect the offset.
+        assert_eq!(local_entry_offset(0x62), 8);
+    }
+}
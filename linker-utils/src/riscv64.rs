@@ -0,0 +1,672 @@
+use crate::elf::RelocationInstruction;
+use crate::elf::RelocationKind;
+use crate::elf::RelocationKindInfo;
+use crate::elf::RelocationSize;
+use crate::relaxation::RelocationModifier;
+
+#[derive(Debug, Clone, Copy)]
+pub enum RelaxationKind {
+    /// Leave the instruction alone. Used when we only want to change the kind of relocation used.
+    NoOp,
+
+    /// Keep the auipc, but replace the ld that follows it with an addi that uses the same
+    /// registers. Used when we'd load an address from the GOT, but can compute it directly.
+    GotLoadToAddi,
+}
+
+/// The instruction formats and data fields that RISC-V relocations are applied to. See the
+/// "Immediate Encoding Variants" section of the RISC-V unprivileged ISA manual.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum RiscVInstruction {
+    /// lui, auipc. Receives the upper 20 bits of the value, rounded to account for the low 12 bits
+    /// being sign-extended by the instruction that uses them.
+    UType,
+
+    /// addi, ld, jalr etc.
+    IType,
+
+    /// sd etc.
+    SType,
+
+    /// Conditional branches.
+    BType,
+
+    /// jal.
+    JType,
+
+    /// An auipc followed by a jalr, as used for calls.
+    AuipcJalr,
+
+    /// c.beqz, c.bnez.
+    CbType,
+
+    /// c.j, c.jal.
+    CjType,
+
+    /// The low 6 bits of a byte of data.
+    Low6Bits,
+
+    /// An unsigned LEB128 value in data. The encoded length of the value from the input is kept.
+    Uleb128,
+}
+
+const OPCODE_MASK: u32 = 0x7f;
+const OPCODE_AUIPC: u32 = 0x17;
+const OPCODE_LUI: u32 = 0x37;
+const OPCODE_JAL: u32 = 0x6f;
+const OPCODE_JALR: u32 = 0x67;
+const OPCODE_LOAD: u32 = 0x03;
+const OPCODE_LOAD_FP: u32 = 0x07;
+const OPCODE_OP_IMM: u32 = 0x13;
+const OPCODE_OP_IMM_32: u32 = 0x1b;
+const OPCODE_STORE: u32 = 0x23;
+const OPCODE_STORE_FP: u32 = 0x27;
+const RD_SHIFT: u32 = 7;
+const RS1_SHIFT: u32 = 15;
+const REGISTER_MASK: u32 = 0x1f;
+
+/// The register that's always zero.
+pub const ZERO_REGISTER: u32 = 0;
+
+/// The register that holds the global pointer.
+pub const GLOBAL_POINTER_REGISTER: u32 = 3;
+
+/// addi x0, x0, 0
+const NOP: u32 = 0x13;
+
+/// c.nop
+const C_NOP: u32 = 0x01;
+
+/// c.j with an offset of 0.
+const C_J: u32 = 0xa001;
+
+/// How far `__global_pointer$` is placed after the start of the data that it's used to access.
+/// Instructions that use gp as their base have a signed 12 bit offset, so this lets them reach the
+/// first 4KiB of data.
+pub const GLOBAL_POINTER_OFFSET: u64 = 0x800;
+
+/// ld: opcode LOAD with a funct3 of 3.
+const LD_MASK: u32 = 0x707f;
+const LD: u32 = 0x3003;
+const ADDI: u32 = 0x13;
+
+impl RelaxationKind {
+    pub fn apply(self, section_bytes: &mut [u8], offset_in_section: &mut u64, _addend: &mut i64) {
+        let offset = *offset_in_section as usize;
+        match self {
+            RelaxationKind::NoOp => {}
+            RelaxationKind::GotLoadToAddi => {
+                let ld = read_u32(&section_bytes[offset + 4..]);
+                write_u32(&mut section_bytes[offset + 4..], (ld & !LD_MASK) | ADDI);
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn next_modifier(&self) -> RelocationModifier {
+        RelocationModifier::Normal
+    }
+}
+
+/// Returns whether `bytes` start with an auipc followed by an ld that uses the register that the
+/// auipc wrote as its base. This is the sequence that compilers emit to load an address from the
+/// GOT.
+#[must_use]
+pub fn is_auipc_ld_pair(bytes: &[u8]) -> bool {
+    let (Some(auipc), Some(ld)) = (bytes.get(..4), bytes.get(4..8)) else {
+        return false;
+    };
+    let auipc = read_u32(auipc);
+    let ld = read_u32(ld);
+    auipc & OPCODE_MASK == OPCODE_AUIPC
+        && ld & LD_MASK == LD
+        && (ld >> RS1_SHIFT) & REGISTER_MASK == (auipc >> RD_SHIFT) & REGISTER_MASK
+}
+
+/// If `bytes` start with an auipc followed by a jalr that jumps relative to the register that the
+/// auipc wrote, which is how calls and tail calls are emitted, returns the register that the jalr
+/// writes the return address to. This is x0 for tail calls.
+#[must_use]
+pub fn call_link_register(bytes: &[u8]) -> Option<u32> {
+    let (Some(auipc), Some(jalr)) = (bytes.get(..4), bytes.get(4..8)) else {
+        return None;
+    };
+    let auipc = read_u32(auipc);
+    let jalr = read_u32(jalr);
+    (auipc & OPCODE_MASK == OPCODE_AUIPC
+        && jalr & OPCODE_MASK == OPCODE_JALR
+        && (jalr >> RS1_SHIFT) & REGISTER_MASK == (auipc >> RD_SHIFT) & REGISTER_MASK)
+        .then_some((jalr >> RD_SHIFT) & REGISTER_MASK)
+}
+
+/// Writes a jal with an offset of 0 that puts the return address in `link_register` to the start of
+/// `dest`. The offset gets filled in by an `R_RISCV_JAL` relocation.
+pub fn write_jal(dest: &mut [u8], link_register: u32) {
+    write_u32(dest, OPCODE_JAL | (link_register << RD_SHIFT));
+}
+
+/// Writes a c.j with an offset of 0 to the start of `dest`. The offset gets filled in by an
+/// `R_RISCV_RVC_JUMP` relocation.
+pub fn write_c_j(dest: &mut [u8]) {
+    write_u16(dest, C_J);
+}
+
+/// Fills `dest` with nops. Its length must be a multiple of 2, which it always is for the padding
+/// that we get from `R_RISCV_ALIGN`, with a single c.nop used if it isn't a multiple of 4.
+pub fn write_nops(dest: &mut [u8]) {
+    let mut chunks = dest.chunks_exact_mut(4);
+    for chunk in &mut chunks {
+        write_u32(chunk, NOP);
+    }
+    let remainder = chunks.into_remainder();
+    if !remainder.is_empty() {
+        write_u16(remainder, C_NOP);
+    }
+}
+
+/// Returns whether `bytes` start with a lui.
+#[must_use]
+pub fn is_lui(bytes: &[u8]) -> bool {
+    bytes
+        .get(..4)
+        .is_some_and(|insn| read_u32(insn) & OPCODE_MASK == OPCODE_LUI)
+}
+
+/// Returns whether `bytes` start with an auipc.
+#[must_use]
+pub fn is_auipc(bytes: &[u8]) -> bool {
+    bytes
+        .get(..4)
+        .is_some_and(|insn| read_u32(insn) & OPCODE_MASK == OPCODE_AUIPC)
+}
+
+/// Returns whether `bytes` start with an instruction that adds a 12 bit immediate to a base register,
+/// i.e. an addi, load, store or jalr. These are what `R_RISCV_LO12_I` and `R_RISCV_LO12_S` apply
+/// to.
+#[must_use]
+pub fn has_base_register(bytes: &[u8]) -> bool {
+    bytes.get(..4).is_some_and(|insn| {
+        matches!(
+            read_u32(insn) & OPCODE_MASK,
+            OPCODE_OP_IMM
+                | OPCODE_OP_IMM_32
+                | OPCODE_LOAD
+                | OPCODE_LOAD_FP
+                | OPCODE_STORE
+                | OPCODE_STORE_FP
+                | OPCODE_JALR
+        )
+    })
+}
+
+/// Changes the base register of the instruction at the start of `bytes` to `register`. The
+/// instruction must be one for which `has_base_register` returns true.
+pub fn set_base_register(bytes: &mut [u8], register: u32) {
+    let insn = read_u32(bytes) & !(REGISTER_MASK << RS1_SHIFT);
+    write_u32(bytes, insn | (register << RS1_SHIFT));
+}
+
+/// Returns whether `value` can be represented as a signed 12 bit immediate.
+#[must_use]
+pub fn fits_in_i12(value: u64) -> bool {
+    (-0x800..0x800).contains(&(value as i64))
+}
+
+#[must_use]
+pub fn relocation_type_from_raw(r_type: u32) -> Option<RelocationKindInfo> {
+    let instruction =
+        |insn, bits| RelocationSize::bit_mask(0, bits, RelocationInstruction::RiscV(insn));
+
+    let (kind, size) = match r_type {
+        // R_RISCV_RELAX and R_RISCV_ALIGN are acted on by layout, which decides which bytes to
+        // delete, so there's nothing for them to write.
+        object::elf::R_RISCV_NONE
+        | object::elf::R_RISCV_RELAX
+        | object::elf::R_RISCV_ALIGN
+        | object::elf::R_RISCV_TPREL_ADD => (RelocationKind::None, RelocationSize::ByteSize(0)),
+
+        // Data relocations
+        object::elf::R_RISCV_32 => (RelocationKind::Absolute, RelocationSize::ByteSize(4)),
+        object::elf::R_RISCV_64 => (RelocationKind::Absolute, RelocationSize::ByteSize(8)),
+        object::elf::R_RISCV_32_PCREL => (RelocationKind::Relative, RelocationSize::ByteSize(4)),
+        object::elf::R_RISCV_PLT32 => (RelocationKind::PltRelative, RelocationSize::ByteSize(4)),
+        object::elf::R_RISCV_TLS_DTPREL32 => (RelocationKind::DtpOff, RelocationSize::ByteSize(4)),
+        object::elf::R_RISCV_TLS_DTPREL64 => (RelocationKind::DtpOff, RelocationSize::ByteSize(8)),
+
+        // Label differences. These are emitted in pairs, since the final distance between the
+        // labels isn't known until after relaxation.
+        object::elf::R_RISCV_ADD8 => (
+            RelocationKind::AbsoluteAddition,
+            RelocationSize::ByteSize(1),
+        ),
+        object::elf::R_RISCV_ADD16 => (
+            RelocationKind::AbsoluteAddition,
+            RelocationSize::ByteSize(2),
+        ),
+        object::elf::R_RISCV_ADD32 => (
+            RelocationKind::AbsoluteAddition,
+            RelocationSize::ByteSize(4),
+        ),
+        object::elf::R_RISCV_ADD64 => (
+            RelocationKind::AbsoluteAddition,
+            RelocationSize::ByteSize(8),
+        ),
+        object::elf::R_RISCV_SUB6 => (
+            RelocationKind::AbsoluteSubtraction,
+            instruction(RiscVInstruction::Low6Bits, 6),
+        ),
+        object::elf::R_RISCV_SUB8 => (
+            RelocationKind::AbsoluteSubtraction,
+            RelocationSize::ByteSize(1),
+        ),
+        object::elf::R_RISCV_SUB16 => (
+            RelocationKind::AbsoluteSubtraction,
+            RelocationSize::ByteSize(2),
+        ),
+        object::elf::R_RISCV_SUB32 => (
+            RelocationKind::AbsoluteSubtraction,
+            RelocationSize::ByteSize(4),
+        ),
+        object::elf::R_RISCV_SUB64 => (
+            RelocationKind::AbsoluteSubtraction,
+            RelocationSize::ByteSize(8),
+        ),
+        object::elf::R_RISCV_SET6 => (
+            RelocationKind::AbsoluteSet,
+            instruction(RiscVInstruction::Low6Bits, 6),
+        ),
+        object::elf::R_RISCV_SET8 => (RelocationKind::AbsoluteSet, RelocationSize::ByteSize(1)),
+        object::elf::R_RISCV_SET16 => (RelocationKind::AbsoluteSet, RelocationSize::ByteSize(2)),
+        object::elf::R_RISCV_SET32 => (RelocationKind::AbsoluteSet, RelocationSize::ByteSize(4)),
+        object::elf::R_RISCV_SET_ULEB128 => (
+            RelocationKind::AbsoluteSet,
+            instruction(RiscVInstruction::Uleb128, 64),
+        ),
+        object::elf::R_RISCV_SUB_ULEB128 => (
+            RelocationKind::AbsoluteSubtraction,
+            instruction(RiscVInstruction::Uleb128, 64),
+        ),
+
+        // Control transfer
+        object::elf::R_RISCV_BRANCH => (
+            RelocationKind::Relative,
+            instruction(RiscVInstruction::BType, 13),
+        ),
+        object::elf::R_RISCV_JAL => (
+            RelocationKind::Relative,
+            instruction(RiscVInstruction::JType, 21),
+        ),
+        object::elf::R_RISCV_CALL | object::elf::R_RISCV_CALL_PLT => (
+            RelocationKind::PltRelative,
+            instruction(RiscVInstruction::AuipcJalr, 32),
+        ),
+        object::elf::R_RISCV_RVC_BRANCH => (
+            RelocationKind::Relative,
+            instruction(RiscVInstruction::CbType, 9),
+        ),
+        object::elf::R_RISCV_RVC_JUMP => (
+            RelocationKind::Relative,
+            instruction(RiscVInstruction::CjType, 12),
+        ),
+
+        // PC-relative addressing. The low part is applied to a different instruction from the high
+        // part and its symbol points at the instruction with the high part.
+        object::elf::R_RISCV_PCREL_HI20 => (
+            RelocationKind::Relative,
+            instruction(RiscVInstruction::UType, 32),
+        ),
+        object::elf::R_RISCV_GOT_HI20 => (
+            RelocationKind::GotRelative,
+            instruction(RiscVInstruction::UType, 32),
+        ),
+        object::elf::R_RISCV_PCREL_LO12_I
+        | object::elf::R_RISCV_TLSDESC_LOAD_LO12
+        | object::elf::R_RISCV_TLSDESC_ADD_LO12 => (
+            RelocationKind::PairedLow,
+            instruction(RiscVInstruction::IType, 12),
+        ),
+        object::elf::R_RISCV_PCREL_LO12_S => (
+            RelocationKind::PairedLow,
+            instruction(RiscVInstruction::SType, 12),
+        ),
+
+        // Absolute addressing
+        object::elf::R_RISCV_HI20 => (
+            RelocationKind::Absolute,
+            instruction(RiscVInstruction::UType, 32),
+        ),
+        object::elf::R_RISCV_LO12_I => (
+            RelocationKind::Absolute,
+            instruction(RiscVInstruction::IType, 12),
+        ),
+        object::elf::R_RISCV_LO12_S => (
+            RelocationKind::Absolute,
+            instruction(RiscVInstruction::SType, 12),
+        ),
+
+        // Assemblers don't emit these. As in GNU ld, relaxation changes the low part of an address
+        // to one of these when it makes the instruction use gp as its base.
+        object::elf::R_RISCV_GPREL_I => (
+            RelocationKind::SymRelGlobalPointer,
+            instruction(RiscVInstruction::IType, 12),
+        ),
+        object::elf::R_RISCV_GPREL_S => (
+            RelocationKind::SymRelGlobalPointer,
+            instruction(RiscVInstruction::SType, 12),
+        ),
+
+        // Thread-local storage
+        object::elf::R_RISCV_TPREL_HI20 => (
+            RelocationKind::TpOff,
+            instruction(RiscVInstruction::UType, 32),
+        ),
+        object::elf::R_RISCV_TPREL_LO12_I => (
+            RelocationKind::TpOff,
+            instruction(RiscVInstruction::IType, 12),
+        ),
+        object::elf::R_RISCV_TPREL_LO12_S => (
+            RelocationKind::TpOff,
+            instruction(RiscVInstruction::SType, 12),
+        ),
+        object::elf::R_RISCV_TLS_GOT_HI20 => (
+            RelocationKind::GotTpOff,
+            instruction(RiscVInstruction::UType, 32),
+        ),
+        object::elf::R_RISCV_TLS_GD_HI20 => (
+            RelocationKind::TlsGd,
+            instruction(RiscVInstruction::UType, 32),
+        ),
+        object::elf::R_RISCV_TLSDESC_HI20 => (
+            RelocationKind::TlsDesc,
+            instruction(RiscVInstruction::UType, 32),
+        ),
+        object::elf::R_RISCV_TLSDESC_CALL => {
+            (RelocationKind::TlsDescCall, RelocationSize::ByteSize(0))
+        }
+
+        _ => return None,
+    };
+
+    Some(RelocationKindInfo {
+        kind,
+        size,
+        mask: None,
+    })
+}
+
+impl RiscVInstruction {
+    /// Encodes `value` into the instruction or data at the start of `dest`, replacing whatever
+    /// was in the field previously.
+    pub fn write_to_value(self, full_value: u64, dest: &mut [u8]) {
+        let value = full_value as u32;
+        match self {
+            RiscVInstruction::UType => {
+                let insn = read_u32(dest) & 0xfff;
+                write_u32(dest, insn | (value.wrapping_add(0x800) & 0xffff_f000));
+            }
+            RiscVInstruction::IType => {
+                let insn = read_u32(dest) & 0x000f_ffff;
+                write_u32(dest, insn | (value << 20));
+            }
+            RiscVInstruction::SType => {
+                let insn = read_u32(dest) & 0x01ff_f07f;
+                write_u32(
+                    dest,
+                    insn | (bits(value, 5, 12) << 25) | (bits(value, 0, 5) << 7),
+                );
+            }
+            RiscVInstruction::BType => {
+                let insn = read_u32(dest) & 0x01ff_f07f;
+                write_u32(
+                    dest,
+                    insn | (bits(value, 12, 13) << 31)
+                        | (bits(value, 5, 11) << 25)
+                        | (bits(value, 1, 5) << 8)
+                        | (bits(value, 11, 12) << 7),
+                );
+            }
+            RiscVInstruction::JType => {
+                let insn = read_u32(dest) & 0xfff;
+                write_u32(
+                    dest,
+                    insn | (bits(value, 20, 21) << 31)
+                        | (bits(value, 1, 11) << 21)
+                        | (bits(value, 11, 12) << 20)
+                        | (bits(value, 12, 20) << 12),
+                );
+            }
+            RiscVInstruction::AuipcJalr => {
+                RiscVInstruction::UType.write_to_value(value.into(), dest);
+                RiscVInstruction::IType.write_to_value(value.into(), &mut dest[4..]);
+            }
+            RiscVInstruction::CbType => {
+                let insn = read_u16(dest) & 0xe383;
+                write_u16(
+                    dest,
+                    insn | (bits(value, 8, 9) << 12)
+                        | (bits(value, 3, 5) << 10)
+                        | (bits(value, 6, 8) << 5)
+                        | (bits(value, 1, 3) << 3)
+                        | (bits(value, 5, 6) << 2),
+                );
+            }
+            RiscVInstruction::CjType => {
+                let insn = read_u16(dest) & 0xe003;
+                write_u16(
+                    dest,
+                    insn | (bits(value, 11, 12) << 12)
+                        | (bits(value, 4, 5) << 11)
+                        | (bits(value, 8, 10) << 9)
+                        | (bits(value, 10, 11) << 8)
+                        | (bits(value, 6, 7) << 7)
+                        | (bits(value, 7, 8) << 6)
+                        | (bits(value, 1, 4) << 3)
+                        | (bits(value, 5, 6) << 2),
+                );
+            }
+            RiscVInstruction::Low6Bits => {
+                dest[0] = (dest[0] & 0xc0) | (value as u8 & 0x3f);
+            }
+            RiscVInstruction::Uleb128 => {
+                let len = uleb128_len(dest);
+                let mut value = full_value;
+                for (i, byte) in dest[..len].iter_mut().enumerate() {
+                    *byte = (value & 0x7f) as u8;
+                    value >>= 7;
+                    if i + 1 < len {
+                        *byte |= 0x80;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the value of a data field. Label differences are computed by adding to or subtracting
+    /// from what's already in the field.
+    #[must_use]
+    pub fn read_value(self, data: &[u8]) -> Option<u64> {
+        match self {
+            RiscVInstruction::Low6Bits => Some(u64::from(*data.first()? & 0x3f)),
+            RiscVInstruction::Uleb128 => {
+                let len = uleb128_len(data);
+                Some(
+                    data[..len]
+                        .iter()
+                        .rev()
+                        .fold(0, |value, byte| (value << 7) | u64::from(byte & 0x7f)),
+                )
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the number of bytes in the LEB128 value at the start of `data`.
+fn uleb128_len(data: &[u8]) -> usize {
+    data.iter()
+        .position(|byte| byte & 0x80 == 0)
+        .map_or(data.len(), |pos| pos + 1)
+}
+
+/// Extracts bits `start..end` of `value`.
+fn bits(value: u32, start: u32, end: u32) -> u32 {
+    (value >> start) & ((1 << (end - start)) - 1)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(*bytes.first_chunk::<4>().unwrap())
+}
+
+fn write_u32(bytes: &mut [u8], value: u32) {
+    bytes[..4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn read_u16(bytes: &[u8]) -> u32 {
+    u32::from(u16::from_le_bytes(*bytes.first_chunk::<2>().unwrap()))
+}
+
+fn write_u16(bytes: &mut [u8], value: u32) {
+    bytes[..2].copy_from_slice(&(value as u16).to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn check(instruction: RiscVInstruction, input: &[u8], value: u64, expected: &[u8]) {
+        let mut out = input.to_owned();
+        instruction.write_to_value(value, &mut out);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_write_to_value() {
+        // auipc a0, 0 -> auipc a0, 0x1 (0x800 rounds up)
+        check(
+            RiscVInstruction::UType,
+            &0x0000_0517_u32.to_le_bytes(),
+            0x800,
+            &0x0000_1517_u32.to_le_bytes(),
+        );
+        // addi a0, a0, 0 -> addi a0, a0, -2048
+        check(
+            RiscVInstruction::IType,
+            &0x0005_0513_u32.to_le_bytes(),
+            0x800,
+            &0x8005_0513_u32.to_le_bytes(),
+        );
+        // sd a1, 0(a0) -> sd a1, 0x7ff(a0)
+        check(
+            RiscVInstruction::SType,
+            &0x00b5_3023_u32.to_le_bytes(),
+            0x7ff,
+            &0x7eb5_3fa3_u32.to_le_bytes(),
+        );
+        // beq a0, a1, . -> beq a0, a1, .-4
+        check(
+            RiscVInstruction::BType,
+            &0x00b5_0063_u32.to_le_bytes(),
+            -4_i64 as u64,
+            &0xfeb5_0ee3_u32.to_le_bytes(),
+        );
+        // jal ra, . -> jal ra, .+0x800
+        check(
+            RiscVInstruction::JType,
+            &0x0000_00ef_u32.to_le_bytes(),
+            0x800,
+            &0x0010_00ef_u32.to_le_bytes(),
+        );
+        // c.j . -> c.j .-2
+        check(
+            RiscVInstruction::CjType,
+            &0xa001_u16.to_le_bytes(),
+            -2_i64 as u64,
+            &0xbffd_u16.to_le_bytes(),
+        );
+        // c.beqz a0, . -> c.beqz a0, .+8
+        check(
+            RiscVInstruction::CbType,
+            &0xc101_u16.to_le_bytes(),
+            8,
+            &0xc501_u16.to_le_bytes(),
+        );
+        check(RiscVInstruction::Low6Bits, &[0xc5], 0x41, &[0xc1]);
+        check(
+            RiscVInstruction::Uleb128,
+            &[0x80, 0x00],
+            0x81,
+            &[0x81, 0x01],
+        );
+    }
+
+    #[test]
+    fn test_read_value() {
+        assert_eq!(
+            RiscVInstruction::Uleb128.read_value(&[0xe5, 0x8e, 0x26]),
+            Some(624_485)
+        );
+        assert_eq!(RiscVInstruction::Low6Bits.read_value(&[0xc5]), Some(5));
+    }
+
+    #[test]
+    fn test_got_load_to_addi() {
+        // auipc a0, 0; ld a1, 0(a0)
+        let mut bytes = [0x17, 0x05, 0x00, 0x00, 0x83, 0x35, 0x05, 0x00];
+        assert!(is_auipc_ld_pair(&bytes));
+        RelaxationKind::GotLoadToAddi.apply(&mut bytes, &mut 0, &mut 0);
+        // auipc a0, 0; addi a1, a0, 0
+        assert_eq!(bytes, [0x17, 0x05, 0x00, 0x00, 0x93, 0x05, 0x05, 0x00]);
+
+        // auipc a0, 0; ld a1, 0(a2)
+        let bytes = [0x17, 0x05, 0x00, 0x00, 0x83, 0x35, 0x06, 0x00];
+        assert!(!is_auipc_ld_pair(&bytes));
+    }
+
+    #[test]
+    fn test_call_relaxation_helpers() {
+        // auipc ra, 0; jalr ra, 0(ra)
+        let call = [0x97, 0x00, 0x00, 0x00, 0xe7, 0x80, 0x00, 0x00];
+        assert_eq!(call_link_register(&call), Some(1));
+        // auipc t1, 0; jalr x0, 0(t1)
+        let tail = [0x17, 0x03, 0x00, 0x00, 0x67, 0x00, 0x03, 0x00];
+        assert_eq!(call_link_register(&tail), Some(0));
+        // auipc t1, 0; jalr x0, 0(ra)
+        let other_base = [0x17, 0x03, 0x00, 0x00, 0x67, 0x80, 0x00, 0x00];
+        assert_eq!(call_link_register(&other_base), None);
+
+        let mut out = [0; 4];
+        write_jal(&mut out, 1);
+        // jal ra, .
+        assert_eq!(out, 0x0000_00ef_u32.to_le_bytes());
+        let mut out = [0; 2];
+        write_c_j(&mut out);
+        assert_eq!(out, 0xa001_u16.to_le_bytes());
+
+        let mut out = [0xff; 6];
+        write_nops(&mut out);
+        assert_eq!(out, [0x13, 0x00, 0x00, 0x00, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_base_register() {
+        // lui a0, 0x12345
+        assert!(is_lui(&[0x37, 0x55, 0x34, 0x12]));
+        assert!(!is_auipc(&[0x37, 0x55, 0x34, 0x12]));
+        assert!(!has_base_register(&[0x37, 0x55, 0x34, 0x12]));
+        // auipc a0, 0
+        assert!(is_auipc(&[0x17, 0x05, 0x00, 0x00]));
+
+        // sd a1, 8(a0) -> sd a1, 8(zero)
+        let mut bytes = [0x23, 0x34, 0xb5, 0x00];
+        assert!(has_base_register(&bytes));
+        set_base_register(&mut bytes, ZERO_REGISTER);
+        assert_eq!(bytes, [0x23, 0x34, 0xb0, 0x00]);
+        // -> sd a1, 8(gp)
+        set_base_register(&mut bytes, GLOBAL_POINTER_REGISTER);
+        assert_eq!(bytes, [0x23, 0xb4, 0xb1, 0x00]);
+
+        assert!(fits_in_i12(-1_i64 as u64));
+        assert!(!fits_in_i12(0x800));
+    }
+}
@@ -0,0 +1,658 @@
+//! Parsing, merging and writing of RISC-V build attributes, which are stored in
+//! `.riscv.attributes` sections. See the "Attributes" chapter of the RISC-V ELF psABI:
+//! https://github.com/riscv-non-isa/riscv-elf-psabi-doc/blob/master/riscv-elf.adoc
+//!
+//! The encoding is the same as that used for ARM build attributes. The section starts with a format
+//! version byte, followed by vendor subsections, each of which contains one or more sub-subsections
+//! of tagged attributes. We only understand the "riscv" vendor and attributes that apply to the
+//! whole file.
+
+use std::fmt::Display;
+use std::fmt::Write as _;
+
+pub const FORMAT_VERSION: u8 = b'A';
+pub const VENDOR_NAME: &str = "riscv";
+
+pub const TAG_FILE: u64 = 1;
+pub const TAG_RISCV_STACK_ALIGN: u64 = 4;
+pub const TAG_RISCV_ARCH: u64 = 5;
+pub const TAG_RISCV_UNALIGNED_ACCESS: u64 = 6;
+pub const TAG_RISCV_PRIV_SPEC: u64 = 8;
+pub const TAG_RISCV_PRIV_SPEC_MINOR: u64 = 10;
+pub const TAG_RISCV_PRIV_SPEC_REVISION: u64 = 12;
+pub const TAG_RISCV_ATOMIC_ABI: u64 = 14;
+pub const TAG_RISCV_X3_REG_USAGE: u64 = 16;
+
+/// Values for `Tag_RISCV_atomic_abi`.
+pub const ATOMIC_ABI_UNKNOWN: u64 = 0;
+pub const ATOMIC_ABI_A6C: u64 = 1;
+pub const ATOMIC_ABI_A6S: u64 = 2;
+pub const ATOMIC_ABI_A7: u64 = 3;
+
+/// The order in which single-letter extensions must appear in an ISA string. This is also used to
+/// order `z` extensions, based on the letter following the `z`.
+const CANONICAL_ORDER: &[u8] = b"iemafdqlcbkjtpvh";
+
+/// The attributes that apply to a whole file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attributes {
+    pub stack_align: Option<u64>,
+    pub arch: Option<Isa>,
+    pub unaligned_access: Option<u64>,
+    pub priv_spec: Option<u64>,
+    pub priv_spec_minor: Option<u64>,
+    pub priv_spec_revision: Option<u64>,
+    pub atomic_abi: Option<u64>,
+    pub x3_reg_usage: Option<u64>,
+}
+
+/// A parsed ISA string such as `rv64i2p1_m2p0_a2p1_zicsr2p0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Isa {
+    /// Either 32 or 64.
+    pub xlen: u32,
+
+    /// All extensions, including the base ISA (`i` or `e`), in canonical order.
+    pub extensions: Vec<Extension>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    pub name: String,
+
+    /// The major and minor version, or `None` if the ISA string didn't give one, e.g. the `c` in
+    /// `rv64gc`.
+    pub version: Option<(u32, u32)>,
+}
+
+/// The extensions that `g` is shorthand for.
+const G_EXTENSIONS: &[&str] = &["i", "m", "a", "f", "d", "zicsr", "zifencei"];
+
+impl Attributes {
+    /// Parses the contents of a `.riscv.attributes` section.
+    pub fn parse(data: &[u8]) -> Result<Attributes, String> {
+        let mut attributes = Attributes::default();
+        let Some((&version, mut rest)) = data.split_first() else {
+            return Ok(attributes);
+        };
+        if version != FORMAT_VERSION {
+            return Err(format!(
+                "Unsupported attributes format version 0x{version:x}"
+            ));
+        }
+        while !rest.is_empty() {
+            let subsection = take_length_prefixed(&mut rest, 0)?;
+            let mut subsection_rest = subsection;
+            let vendor = take_ntbs(&mut subsection_rest)?;
+            if vendor != VENDOR_NAME {
+                // Attributes for other vendors can't be interpreted, so are ignored.
+                continue;
+            }
+            while !subsection_rest.is_empty() {
+                let tag = take_uleb128(&mut subsection_rest)?;
+                let tag_len = uleb128_len(tag);
+                let mut content = take_length_prefixed(&mut subsection_rest, tag_len)?;
+                if tag != TAG_FILE {
+                    // Section and symbol attributes are deprecated and not emitted by toolchains.
+                    continue;
+                }
+                while !content.is_empty() {
+                    attributes.parse_attribute(&mut content)?;
+                }
+            }
+        }
+        Ok(attributes)
+    }
+
+    fn parse_attribute(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        let tag = take_uleb128(data)?;
+        match tag {
+            TAG_RISCV_ARCH => self.arch = Some(Isa::parse(take_ntbs(data)?)?),
+            TAG_RISCV_STACK_ALIGN => self.stack_align = Some(take_uleb128(data)?),
+            TAG_RISCV_UNALIGNED_ACCESS => self.unaligned_access = Some(take_uleb128(data)?),
+            TAG_RISCV_PRIV_SPEC => self.priv_spec = Some(take_uleb128(data)?),
+            TAG_RISCV_PRIV_SPEC_MINOR => self.priv_spec_minor = Some(take_uleb128(data)?),
+            TAG_RISCV_PRIV_SPEC_REVISION => self.priv_spec_revision = Some(take_uleb128(data)?),
+            TAG_RISCV_ATOMIC_ABI => self.atomic_abi = Some(take_uleb128(data)?),
+            TAG_RISCV_X3_REG_USAGE => self.x3_reg_usage = Some(take_uleb128(data)?),
+            // The psABI says that unknown attributes with even tags have integer values and those
+            // with odd tags have string values, so we can skip them.
+            _ if tag % 2 == 0 => {
+                take_uleb128(data)?;
+            }
+            _ => {
+                take_ntbs(data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges attributes from multiple input files. Each input is paired with a name that is used
+    /// when reporting incompatibilities.
+    pub fn merge<'a, N: Display + 'a>(
+        inputs: impl IntoIterator<Item = (N, &'a Attributes)>,
+    ) -> Result<Attributes, String> {
+        let mut merged = Attributes::default();
+        // For each attribute that must match, the name of the first file that set it.
+        let mut first = MergeSources::default();
+        for (name, input) in inputs {
+            let name = name.to_string();
+            merge_equal(
+                "Tag_RISCV_stack_align",
+                &mut merged.stack_align,
+                input.stack_align,
+                &mut first.stack_align,
+                &name,
+            )?;
+            merge_equal(
+                "Tag_RISCV_priv_spec",
+                &mut merged.priv_spec,
+                input.priv_spec,
+                &mut first.priv_spec,
+                &name,
+            )?;
+            merge_equal(
+                "Tag_RISCV_priv_spec_minor",
+                &mut merged.priv_spec_minor,
+                input.priv_spec_minor,
+                &mut first.priv_spec_minor,
+                &name,
+            )?;
+            merge_equal(
+                "Tag_RISCV_priv_spec_revision",
+                &mut merged.priv_spec_revision,
+                input.priv_spec_revision,
+                &mut first.priv_spec_revision,
+                &name,
+            )?;
+
+            if let Some(value) = input.unaligned_access {
+                *merged.unaligned_access.get_or_insert(0) |= value;
+            }
+
+            if let Some(isa) = input.arch.as_ref() {
+                match merged.arch.as_mut() {
+                    Some(merged_isa) => {
+                        if merged_isa.xlen != isa.xlen {
+                            return Err(format!(
+                                "Cannot link RV{} file `{name}` with RV{} file `{}`",
+                                isa.xlen, merged_isa.xlen, first.arch
+                            ));
+                        }
+                        merged_isa.merge(isa);
+                    }
+                    None => {
+                        merged.arch = Some(isa.clone());
+                        first.arch = name.clone();
+                    }
+                }
+            }
+
+            if let Some(value) = input.atomic_abi {
+                let previous = merged.atomic_abi.unwrap_or(ATOMIC_ABI_UNKNOWN);
+                let Some(result) = merge_atomic_abi(previous, value) else {
+                    return Err(format!(
+                        "Atomic ABI {} of `{name}` is incompatible with atomic ABI {} of `{}`",
+                        atomic_abi_name(value),
+                        atomic_abi_name(previous),
+                        first.atomic_abi
+                    ));
+                };
+                if value != ATOMIC_ABI_UNKNOWN && previous == ATOMIC_ABI_UNKNOWN {
+                    first.atomic_abi = name.clone();
+                }
+                merged.atomic_abi = Some(result);
+            }
+
+            if let Some(value) = input.x3_reg_usage {
+                match merged.x3_reg_usage {
+                    Some(previous) if previous != value && previous != 0 && value != 0 => {
+                        return Err(format!(
+                            "Tag_RISCV_x3_reg_usage {value} of `{name}` conflicts with {previous} \
+                             of `{}`",
+                            first.x3_reg_usage
+                        ));
+                    }
+                    Some(previous) if previous != 0 => {}
+                    _ => {
+                        merged.x3_reg_usage = Some(value);
+                        first.x3_reg_usage = name.clone();
+                    }
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Returns whether there are no attributes to write.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Attributes::default()
+    }
+
+    /// Encodes these attributes as the contents of a `.riscv.attributes` section.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut attributes = Vec::new();
+        let mut write_int = |tag: u64, value: Option<u64>| {
+            if let Some(value) = value {
+                write_uleb128(&mut attributes, tag);
+                write_uleb128(&mut attributes, value);
+            }
+        };
+        write_int(TAG_RISCV_STACK_ALIGN, self.stack_align);
+        write_int(TAG_RISCV_UNALIGNED_ACCESS, self.unaligned_access);
+        write_int(TAG_RISCV_PRIV_SPEC, self.priv_spec);
+        write_int(TAG_RISCV_PRIV_SPEC_MINOR, self.priv_spec_minor);
+        write_int(TAG_RISCV_PRIV_SPEC_REVISION, self.priv_spec_revision);
+        write_int(TAG_RISCV_ATOMIC_ABI, self.atomic_abi);
+        write_int(TAG_RISCV_X3_REG_USAGE, self.x3_reg_usage);
+        if let Some(isa) = self.arch.as_ref() {
+            write_uleb128(&mut attributes, TAG_RISCV_ARCH);
+            attributes.extend_from_slice(isa.to_string().as_bytes());
+            attributes.push(0);
+        }
+
+        let file_len = uleb128_len(TAG_FILE) + 4 + attributes.len();
+        let subsection_len = 4 + VENDOR_NAME.len() + 1 + file_len;
+
+        let mut out = Vec::with_capacity(1 + subsection_len);
+        out.push(FORMAT_VERSION);
+        out.extend_from_slice(&(subsection_len as u32).to_le_bytes());
+        out.extend_from_slice(VENDOR_NAME.as_bytes());
+        out.push(0);
+        write_uleb128(&mut out, TAG_FILE);
+        out.extend_from_slice(&(file_len as u32).to_le_bytes());
+        out.extend_from_slice(&attributes);
+        out
+    }
+}
+
+#[derive(Default)]
+struct MergeSources {
+    stack_align: String,
+    priv_spec: String,
+    priv_spec_minor: String,
+    priv_spec_revision: String,
+    arch: String,
+    atomic_abi: String,
+    x3_reg_usage: String,
+}
+
+fn merge_equal(
+    tag_name: &str,
+    merged: &mut Option<u64>,
+    value: Option<u64>,
+    first: &mut String,
+    name: &str,
+) -> Result<(), String> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+    match *merged {
+        Some(previous) if previous != value => Err(format!(
+            "{tag_name} {value} of `{name}` conflicts with {previous} of `{first}`"
+        )),
+        Some(_) => Ok(()),
+        None => {
+            *merged = Some(value);
+            name.clone_into(first);
+            Ok(())
+        }
+    }
+}
+
+/// Returns the merged atomic ABI, or `None` if the two ABIs can't be mixed. A6C and A6S are
+/// compatible, as are A6S and A7, but A6C and A7 use incompatible mappings for sequentially
+/// consistent atomics.
+fn merge_atomic_abi(a: u64, b: u64) -> Option<u64> {
+    match (a, b) {
+        (ATOMIC_ABI_UNKNOWN, other) | (other, ATOMIC_ABI_UNKNOWN) => Some(other),
+        (a, b) if a == b => Some(a),
+        (ATOMIC_ABI_A6C, ATOMIC_ABI_A6S) | (ATOMIC_ABI_A6S, ATOMIC_ABI_A6C) => Some(ATOMIC_ABI_A6C),
+        (ATOMIC_ABI_A6S, ATOMIC_ABI_A7) | (ATOMIC_ABI_A7, ATOMIC_ABI_A6S) => Some(ATOMIC_ABI_A7),
+        _ => None,
+    }
+}
+
+fn atomic_abi_name(value: u64) -> String {
+    match value {
+        ATOMIC_ABI_UNKNOWN => "unknown".to_owned(),
+        ATOMIC_ABI_A6C => "A6C".to_owned(),
+        ATOMIC_ABI_A6S => "A6S".to_owned(),
+        ATOMIC_ABI_A7 => "A7".to_owned(),
+        other => other.to_string(),
+    }
+}
+
+impl Isa {
+    pub fn parse(s: &str) -> Result<Isa, String> {
+        let lower = s.to_ascii_lowercase();
+        let (xlen, rest) = if let Some(rest) = lower.strip_prefix("rv32") {
+            (32, rest)
+        } else if let Some(rest) = lower.strip_prefix("rv64") {
+            (64, rest)
+        } else {
+            return Err(format!("Invalid RISC-V ISA string `{s}`"));
+        };
+
+        let invalid_version = || format!("Invalid version in RISC-V ISA string `{s}`");
+        let mut extensions = Vec::new();
+        for component in rest.split('_').filter(|c| !c.is_empty()) {
+            if component.starts_with(['z', 's', 'x']) {
+                // A multi-letter extension takes up the whole component. Its name may contain
+                // digits, e.g. `zve32x`, so its version, if any, is whatever digits and `p` are at
+                // the end.
+                let (name, version) =
+                    split_trailing_version(component).ok_or_else(invalid_version)?;
+                extensions.push(Extension {
+                    name: name.to_owned(),
+                    version,
+                });
+                continue;
+            }
+            // Otherwise, the component is a run of single-letter extensions, each optionally
+            // followed by a version.
+            let mut chars = component;
+            while !chars.is_empty() {
+                let (name, after_name) = chars.split_at(1);
+                let (version, after_version) =
+                    parse_version(after_name).ok_or_else(invalid_version)?;
+                if name == "g" {
+                    extensions.extend(G_EXTENSIONS.iter().map(|name| Extension {
+                        name: (*name).to_owned(),
+                        version: None,
+                    }));
+                } else {
+                    extensions.push(Extension {
+                        name: name.to_owned(),
+                        version,
+                    });
+                }
+                chars = after_version;
+            }
+        }
+        let mut isa = Isa {
+            xlen,
+            extensions: Vec::new(),
+        };
+        for extension in &extensions {
+            isa.add_extension(extension);
+        }
+        Ok(isa)
+    }
+
+    /// Adds all extensions from `other`, keeping the higher version of any extensions that are
+    /// present in both.
+    pub fn merge(&mut self, other: &Isa) {
+        for extension in &other.extensions {
+            self.add_extension(extension);
+        }
+    }
+
+    fn add_extension(&mut self, extension: &Extension) {
+        if let Some(existing) = self
+            .extensions
+            .iter_mut()
+            .find(|e| e.name == extension.name)
+        {
+            // An explicit version beats no version, since `None` sorts first.
+            if extension.version > existing.version {
+                existing.version = extension.version;
+            }
+            return;
+        }
+        let key = canonical_sort_key(&extension.name);
+        let position = self
+            .extensions
+            .iter()
+            .position(|e| canonical_sort_key(&e.name) > key)
+            .unwrap_or(self.extensions.len());
+        self.extensions.insert(position, extension.clone());
+    }
+}
+
+impl Display for Isa {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = format!("rv{}", self.xlen);
+        for (i, extension) in self.extensions.iter().enumerate() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push_str(&extension.name);
+            if let Some((major, minor)) = extension.version {
+                let _ = write!(out, "{major}p{minor}");
+            }
+        }
+        Display::fmt(&out, f)
+    }
+}
+
+/// Returns a key that sorts extensions into canonical order: the base ISA, then single-letter
+/// extensions in the order defined by the ISA manual, then `z`, `s` and `x` extensions. `z`
+/// extensions are ordered by the category indicated by their first letter, then alphabetically.
+fn canonical_sort_key(name: &str) -> (u8, usize, &str) {
+    let single_letter_rank = |c: u8| {
+        CANONICAL_ORDER
+            .iter()
+            .position(|&o| o == c)
+            .unwrap_or(CANONICAL_ORDER.len())
+    };
+    let bytes = name.as_bytes();
+    match bytes {
+        [b'i' | b'e'] => (0, 0, name),
+        [c] => (1, single_letter_rank(*c), name),
+        [b'z', category, ..] => (2, single_letter_rank(*category), name),
+        [b's', ..] => (3, 0, name),
+        _ => (4, 0, name),
+    }
+}
+
+/// Parses a version like `2p1` or `2` from the start of `s`, returning the major and minor versions,
+/// if there was a version, and the unparsed remainder.
+fn parse_version(s: &str) -> Option<(Option<(u32, u32)>, &str)> {
+    let major_len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if major_len == 0 {
+        return Some((None, s));
+    }
+    let major = s[..major_len].parse().ok()?;
+    let rest = &s[major_len..];
+    let Some(after_p) = rest.strip_prefix('p') else {
+        return Some((Some((major, 0)), rest));
+    };
+    let minor_len = after_p
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(after_p.len());
+    if minor_len == 0 {
+        // The `p` was actually the `p` extension.
+        return Some((Some((major, 0)), rest));
+    }
+    let minor = after_p[..minor_len].parse().ok()?;
+    Some((Some((major, minor)), &after_p[minor_len..]))
+}
+
+/// Splits a multi-letter extension such as `zve32x1p0` into its name and its version, if it ends
+/// with one.
+fn split_trailing_version(component: &str) -> Option<(&str, Option<(u32, u32)>)> {
+    let without_digits = |s: &str| s.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let last_digits_start = without_digits(component);
+    if last_digits_start == component.len() {
+        return Some((component, None));
+    }
+    let last_number = component[last_digits_start..].parse().ok()?;
+    // If the trailing number is preceded by `p` and another number, then it's the minor version.
+    if let Some(before_p) = component[..last_digits_start].strip_suffix('p') {
+        let major_start = without_digits(before_p);
+        if major_start < before_p.len() && major_start > 0 {
+            let major = before_p[major_start..].parse().ok()?;
+            return Some((&before_p[..major_start], Some((major, last_number))));
+        }
+    }
+    if last_digits_start == 0 {
+        return None;
+    }
+    Some((&component[..last_digits_start], Some((last_number, 0))))
+}
+
+fn take_length_prefixed<'data>(
+    data: &mut &'data [u8],
+    already_consumed: usize,
+) -> Result<&'data [u8], String> {
+    let Some((len_bytes, rest)) = data.split_first_chunk::<4>() else {
+        return Err("Truncated attributes section".to_owned());
+    };
+    let len = u32::from_le_bytes(*len_bytes) as usize;
+    let Some(content_len) = len.checked_sub(4 + already_consumed) else {
+        return Err(format!("Invalid attributes subsection length {len}"));
+    };
+    if content_len > rest.len() {
+        return Err(format!(
+            "Attributes subsection length {len} exceeds section size"
+        ));
+    }
+    let (content, rest) = rest.split_at(content_len);
+    *data = rest;
+    Ok(content)
+}
+
+fn take_ntbs<'data>(data: &mut &'data [u8]) -> Result<&'data str, String> {
+    let Some(end) = data.iter().position(|&b| b == 0) else {
+        return Err("Unterminated string in attributes section".to_owned());
+    };
+    let s = std::str::from_utf8(&data[..end])
+        .map_err(|_| "Invalid UTF-8 in attributes section".to_owned())?;
+    *data = &data[end + 1..];
+    Ok(s)
+}
+
+fn take_uleb128(data: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0_u64;
+    let mut shift = 0;
+    loop {
+        let Some((&byte, rest)) = data.split_first() else {
+            return Err("Truncated ULEB128 in attributes section".to_owned());
+        };
+        *data = rest;
+        if shift >= 64 {
+            return Err("ULEB128 in attributes section is too large".to_owned());
+        }
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+fn write_uleb128(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn uleb128_len(value: u64) -> usize {
+    let mut out = Vec::new();
+    write_uleb128(&mut out, value);
+    out.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(arch: &str) -> Attributes {
+        Attributes {
+            arch: Some(Isa::parse(arch).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_isa_round_trip() {
+        let isa = Isa::parse("rv64i2p1_m2p0_a2p1_f2p2_d2p2_c2p0_zicsr2p0_zifencei2p0").unwrap();
+        assert_eq!(isa.xlen, 64);
+        assert_eq!(
+            isa.to_string(),
+            "rv64i2p1_m2p0_a2p1_f2p2_d2p2_c2p0_zicsr2p0_zifencei2p0"
+        );
+    }
+
+    #[test]
+    fn test_isa_multi_letter_extensions_with_digits() {
+        let isa = Isa::parse("rv64i2p1_m2p0_v1p0_zve32x1p0_zvl128b1p0").unwrap();
+        assert_eq!(isa.to_string(), "rv64i2p1_m2p0_v1p0_zve32x1p0_zvl128b1p0");
+        assert_eq!(
+            Isa::parse("rv64i_zve32x_zvl128b").unwrap().to_string(),
+            "rv64i_zve32x_zvl128b"
+        );
+    }
+
+    #[test]
+    fn test_isa_without_versions() {
+        let isa = Isa::parse("rv64gc").unwrap();
+        assert_eq!(isa.to_string(), "rv64i_m_a_f_d_c_zicsr_zifencei");
+
+        // Merging with an explicit version keeps the version.
+        let mut isa = Isa::parse("rv64imac").unwrap();
+        isa.merge(&Isa::parse("rv64i2p1_m2p0").unwrap());
+        assert_eq!(isa.to_string(), "rv64i2p1_m2p0_a_c");
+    }
+
+    #[test]
+    fn test_merge_takes_superset_of_extensions() {
+        let a = attributes("rv64i2p1_m2p0_zicsr2p0");
+        let b = attributes("rv64i2p1_a2p1_c2p0_zba1p0_zicsr2p0");
+        let merged = Attributes::merge([("a.o", &a), ("b.o", &b)]).unwrap();
+        assert_eq!(
+            merged.arch.unwrap().to_string(),
+            "rv64i2p1_m2p0_a2p1_c2p0_zicsr2p0_zba1p0"
+        );
+    }
+
+    #[test]
+    fn test_merge_conflicts_name_files() {
+        let a = attributes("rv64i2p1");
+        let b = attributes("rv32i2p1");
+        let err = Attributes::merge([("a.o", &a), ("b.o", &b)]).unwrap_err();
+        assert!(err.contains("a.o") && err.contains("b.o"), "{err}");
+
+        let a = Attributes {
+            stack_align: Some(16),
+            ..Default::default()
+        };
+        let b = Attributes {
+            stack_align: Some(8),
+            ..Default::default()
+        };
+        let err = Attributes::merge([("a.o", &a), ("b.o", &b)]).unwrap_err();
+        assert!(err.contains("a.o") && err.contains("b.o"), "{err}");
+
+        let a = Attributes {
+            atomic_abi: Some(ATOMIC_ABI_A6C),
+            ..Default::default()
+        };
+        let b = Attributes {
+            atomic_abi: Some(ATOMIC_ABI_A7),
+            ..Default::default()
+        };
+        assert!(Attributes::merge([("a.o", &a), ("b.o", &b)]).is_err());
+    }
+
+    #[test]
+    fn test_section_round_trip() {
+        let attributes = Attributes {
+            stack_align: Some(16),
+            unaligned_access: Some(0),
+            atomic_abi: Some(ATOMIC_ABI_A6S),
+            ..attributes("rv64i2p1_m2p0_a2p1_c2p0")
+        };
+        let bytes = attributes.to_bytes();
+        assert_eq!(Attributes::parse(&bytes).unwrap(), attributes);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::elf;
 use crate::elf::RelocationKind;
 use crate::relaxation::RelocationModifier;
 
@@ -15,9 +16,16 @@ pub enum RelaxationKind {
     /// The transformation will look like `mov *x(%rip), reg` ->  `mov x, reg`.
     RexMovIndirectToAbsolute,
 
+    /// As for `RexMovIndirectToAbsolute`, but for an instruction with an APX REX2 prefix. Also
+    /// used to turn a TLSDESC `lea` into loading the offset from the thread pointer.
+    Rex2MovIndirectToAbsolute,
+
     // Transforms an indirect sub to an absolute sub.
     RexSubIndirectToAbsolute,
 
+    // Transforms an indirect add to an absolute add.
+    RexAddIndirectToAbsolute,
+
     // Transforms an indirect cmp to an absolute cmp.
     RexCmpIndirectToAbsolute,
 
@@ -46,9 +54,15 @@ pub enum RelaxationKind {
     /// Transform general dynamic (GD) into initial exec
     TlsGdToInitialExec,
 
-    // Transform TLSDESC to local exec for a statically linked executables.
+    /// Transform TLSDESC into local exec.
     TlsDescToLocalExec,
 
+    /// Transform TLSDESC into initial exec.
+    TlsDescToInitialExec,
+
+    /// Transform TLSDESC into initial exec for an instruction with an APX REX2 prefix.
+    Rex2TlsDescToInitialExec,
+
     /// Convert a TLSDESC_CALL to a no-op.
     SkipTlsDescCall,
 }
@@ -78,6 +92,16 @@ impl RelaxationKind {
                 *mod_rm = (*mod_rm >> 3) & 0x7 | 0xc0;
                 *addend = 0;
             }
+            RelaxationKind::Rex2MovIndirectToAbsolute => {
+                // Turn a PC-relative mov into an absolute mov. REX2 has two bits for each
+                // register, so both of the R bits need to move to the B bits.
+                let rex2 = section_bytes[offset - 3];
+                section_bytes[offset - 3] = (rex2 & !0x44) | ((rex2 & 0x44) >> 2);
+                section_bytes[offset - 2] = 0xc7;
+                let mod_rm = &mut section_bytes[offset - 1];
+                *mod_rm = (*mod_rm >> 3) & 0x7 | 0xc0;
+                *addend = 0;
+            }
             RelaxationKind::RexSubIndirectToAbsolute => {
                 // Turn a PC-relative sub into an absolute sub.
                 let rex = section_bytes[offset - 3];
@@ -87,6 +111,15 @@ impl RelaxationKind {
                 *mod_rm = (*mod_rm >> 3) & 0x7 | 0xe8;
                 *addend = 0;
             }
+            RelaxationKind::RexAddIndirectToAbsolute => {
+                // Turn a PC-relative add into an absolute add.
+                let rex = section_bytes[offset - 3];
+                section_bytes[offset - 3] = (rex & !4) | ((rex & 4) >> 2);
+                section_bytes[offset - 2] = 0x81;
+                let mod_rm = &mut section_bytes[offset - 1];
+                *mod_rm = (*mod_rm >> 3) & 0x7 | 0xc0;
+                *addend = 0;
+            }
             RelaxationKind::RexCmpIndirectToAbsolute => {
                 // Turn a PC-relative cmp into an absolute cmp.
                 let rex = section_bytes[offset - 3];
@@ -145,11 +178,21 @@ impl RelaxationKind {
                 *offset_in_section += 15;
             }
             RelaxationKind::TlsDescToLocalExec => {
-                section_bytes[offset - 3..offset + 6].copy_from_slice(&[
+                section_bytes[offset - 3..offset].copy_from_slice(&[
                     // mov {offset},%rax
-                    0x48, 0xc7, 0xc0, 0, 0, 0, 0, // xchg   %ax,%ax
-                    0x66, 0x90,
+                    0x48, 0xc7, 0xc0,
                 ]);
+                *addend = 0;
+            }
+            RelaxationKind::TlsDescToInitialExec => {
+                section_bytes[offset - 3..offset].copy_from_slice(&[
+                    // mov *x(%rip),%rax
+                    0x48, 0x8b, 0x05,
+                ]);
+            }
+            RelaxationKind::Rex2TlsDescToInitialExec => {
+                // lea *x(%rip),reg -> mov *x(%rip),reg
+                section_bytes[offset - 2] = 0x8b;
             }
             RelaxationKind::SkipTlsDescCall => {
                 section_bytes[offset..offset + 2].copy_from_slice(&[
@@ -168,8 +211,7 @@ impl RelaxationKind {
             | RelaxationKind::TlsGdToLocalExec
             | RelaxationKind::TlsGdToLocalExecLarge
             | RelaxationKind::TlsLdToLocalExec
-            | RelaxationKind::TlsLdToLocalExec64
-            | RelaxationKind::TlsDescToLocalExec => RelocationModifier::SkipNextRelocation,
+            | RelaxationKind::TlsLdToLocalExec64 => RelocationModifier::SkipNextRelocation,
             _ => RelocationModifier::Normal,
         }
     }
@@ -184,11 +226,16 @@ pub fn relocation_kind_and_size(r_type: u32) -> Option<(RelocationKind, usize)>
         object::elf::R_X86_64_PC32 => (RelocationKind::Relative, 4),
         object::elf::R_X86_64_PC64 => (RelocationKind::Relative, 8),
         object::elf::R_X86_64_GOT32 => (RelocationKind::GotRelGotBase, 4),
-        object::elf::R_X86_64_GOT64 => (RelocationKind::GotRelGotBase, 8),
+        // GOTPLT64 is for a GOT entry that may also be used by a PLT entry. We don't share entries
+        // between the two, so it's the same as GOT64.
+        object::elf::R_X86_64_GOT64 | object::elf::R_X86_64_GOTPLT64 => {
+            (RelocationKind::GotRelGotBase, 8)
+        }
         object::elf::R_X86_64_GOTOFF64 => (RelocationKind::SymRelGotBase, 8),
         object::elf::R_X86_64_PLT32 => (RelocationKind::PltRelative, 4),
         object::elf::R_X86_64_PLTOFF64 => (RelocationKind::PltRelGotBase, 8),
         object::elf::R_X86_64_GOTPCREL => (RelocationKind::GotRelative, 4),
+        object::elf::R_X86_64_GOTPCREL64 => (RelocationKind::GotRelative, 8),
 
         // For now, we rely on GOTPC64 and GOTPC32 always referencing the symbol
         // _GLOBAL_OFFSET_TABLE_, which means that we can just treat these a normal relative
@@ -205,13 +252,23 @@ pub fn relocation_kind_and_size(r_type: u32) -> Option<(RelocationKind, usize)>
         object::elf::R_X86_64_TLSLD => (RelocationKind::TlsLd, 4),
         object::elf::R_X86_64_DTPOFF32 => (RelocationKind::DtpOff, 4),
         object::elf::R_X86_64_DTPOFF64 => (RelocationKind::DtpOff, 8),
-        object::elf::R_X86_64_GOTTPOFF => (RelocationKind::GotTpOff, 4),
-        object::elf::R_X86_64_GOTPCRELX | object::elf::R_X86_64_REX_GOTPCRELX => {
-            (RelocationKind::GotRelative, 4)
-        }
+        object::elf::R_X86_64_GOTTPOFF
+        | elf::R_X86_64_CODE_4_GOTTPOFF
+        | elf::R_X86_64_CODE_5_GOTTPOFF
+        | elf::R_X86_64_CODE_6_GOTTPOFF => (RelocationKind::GotTpOff, 4),
+        object::elf::R_X86_64_GOTPCRELX
+        | object::elf::R_X86_64_REX_GOTPCRELX
+        | elf::R_X86_64_CODE_4_GOTPCRELX
+        | elf::R_X86_64_CODE_5_GOTPCRELX
+        | elf::R_X86_64_CODE_6_GOTPCRELX => (RelocationKind::GotRelative, 4),
         object::elf::R_X86_64_TPOFF32 => (RelocationKind::TpOff, 4),
-        object::elf::R_X86_64_GOTPC32_TLSDESC => (RelocationKind::TlsDesc, 4),
+        object::elf::R_X86_64_GOTPC32_TLSDESC
+        | elf::R_X86_64_CODE_4_GOTPC32_TLSDESC
+        | elf::R_X86_64_CODE_5_GOTPC32_TLSDESC
+        | elf::R_X86_64_CODE_6_GOTPC32_TLSDESC => (RelocationKind::TlsDesc, 4),
         object::elf::R_X86_64_TLSDESC_CALL => (RelocationKind::TlsDescCall, 0),
+        object::elf::R_X86_64_SIZE32 => (RelocationKind::SymbolSize, 4),
+        object::elf::R_X86_64_SIZE64 => (RelocationKind::SymbolSize, 8),
         object::elf::R_X86_64_NONE => (RelocationKind::None, 0),
         _ => return None,
     };
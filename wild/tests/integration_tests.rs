@@ -5,16 +5,60 @@
 //! that are tested by examining the resulting binaries. Directives have the format '//#Directive:
 //! Args'.
 //!
+//! ExpectSym: Checks that the symbol table contains the supplied symbol in the supplied section,
+//! e.g. `_start .text`. An address may follow, in which case the symbol's value is checked too.
+//!
+//! NoSym: Checks that the symbol table doesn't contain the supplied symbol.
+//!
 //! ExpectComment: Checks that the comment in the .comment section is equal to the supplied
 //! argument. If no ExpectComment directives are given then .comment isn't checked. The argument may
 //! end with '*' which matches anything.
 //!
+//! ExpectReloc: Checks that the output has a relocation in the supplied section that refers to the
+//! supplied symbol, e.g. `.text exit_syscall`. This is for outputs that keep their relocations, such
+//! as with `--emit-relocs`.
+//!
+//! ExpectLoadSegments: Checks the permissions of each PT_LOAD segment, in order, e.g. `R RX RW`.
+//!
+//! ExpectHeadersLoaded: Checks whether our file and program headers are in a PT_LOAD segment.
+//!
+//! ExpectLoadAlignment: Checks that each PT_LOAD segment has the supplied alignment and that its
+//! file offset and address are congruent modulo it, e.g. `0x10000`.
+//!
+//! ExpectSeparateCode: Checks whether the executable PT_LOAD segment is on pages of its own, both
+//! in the file and in memory.
+//!
+//! ExpectSeparateLoads: Checks whether every PT_LOAD segment is on pages of its own.
+//!
+//! ExpectRelroAligned: Checks that there's a PT_GNU_RELRO segment that ends on a multiple of the
+//! supplied page size and that no section straddles its end.
+//!
+//! OutputFormat: Set to `binary` when the output is a raw binary rather than ELF. Only the string
+//! and file contents assertions are then checked.
+//!
+//! ExpectFileBytes: Checks that the output contains the supplied hex bytes at the supplied file
+//! offset, e.g. `0x1000 1122`.
+//!
+//! ExpectFileSize: Checks that the output is the supplied number of bytes long.
+//!
+//! Assertions are checked whether or not the output is run (see RunEnabled), provided that the
+//! output was written to the usual place.
+//!
+//! Relocatable: Compiles the supplied source file, links it with `-r` using the linker under test
+//! and uses the resulting relocatable object as an input to the main link.
+//!
+//! WildRelocatable: Like Relocatable, but the file is always linked with `-r` by wild, even when the
+//! main link uses another linker. This checks that other linkers can use our relocatable objects.
+//!
 //! TODO: Document the rest of the directives.
 
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Context;
 use itertools::Itertools;
+use object::elf::ProgramHeader64;
+use object::read::elf::ProgramHeader as _;
+use object::read::elf::SectionHeader as _;
 use object::LittleEndian;
 use object::Object;
 use object::ObjectSection;
@@ -108,13 +152,47 @@ impl Linker {
         )?;
 
         if self.is_wild() || !is_newer(so_path, obj_path) || !command.can_skip {
-            command.run(config)?;
+            // Any error that the config expects is from linking the program, not its shared
+            // objects.
+            command.run(None)?;
             write_cmd_file(so_path, &command.to_string())?;
         }
 
         Ok(LinkerInput::with_command(so_path.to_owned(), command))
     }
 
+    /// Links `obj_path` into a relocatable object (`-r`) that can then be used as an input to the
+    /// final link.
+    fn link_relocatable(
+        &self,
+        obj_path: &Path,
+        out_path: &Path,
+        config: &Config,
+        cross_arch: Option<Architecture>,
+    ) -> Result<LinkerInput> {
+        // Direct invocations pass `--gc-sections`, which would discard everything, since a
+        // relocatable object has no entry point to keep sections alive from.
+        let linker_args = ArgumentSet {
+            args: vec!["-r".to_owned(), "--no-gc-sections".to_owned()],
+        };
+
+        let mut command = LinkCommand::new(
+            self,
+            &[LinkerInput::new(obj_path.to_owned())],
+            out_path,
+            &linker_args,
+            config,
+            cross_arch,
+        )?;
+
+        if self.is_wild() || !is_newer(out_path, obj_path) || !command.can_skip {
+            command.run(None)?;
+            write_cmd_file(out_path, &command.to_string())?;
+        }
+
+        Ok(LinkerInput::with_command(out_path.to_owned(), command))
+    }
+
     fn is_wild(&self) -> bool {
         *self == Linker::Wild
     }
@@ -284,35 +362,124 @@ struct Dep {
 #[derive(Default, Clone, PartialEq, Eq)]
 struct Assertions {
     expected_symtab_entries: Vec<ExpectedSymtabEntry>,
+    unexpected_symbols: Vec<String>,
     expected_comments: Vec<String>,
     does_not_contain: Vec<String>,
     contains_strings: Vec<String>,
+    expected_relocations: Vec<ExpectedRelocation>,
+    expected_load_segments: Option<Vec<String>>,
+    expected_headers_loaded: Option<bool>,
+    expected_load_alignment: Option<u64>,
+    expected_relro_alignment: Option<u64>,
+    expected_separate_code: Option<bool>,
+    expected_separate_loads: Option<bool>,
+    expected_x86_features: Option<u32>,
+    output_format_binary: bool,
+    expected_file_bytes: Vec<ExpectedFileBytes>,
+    expected_file_size: Option<usize>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct ExpectedRelocation {
+    section_name: String,
+    symbol_name: String,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct ExpectedFileBytes {
+    offset: usize,
+    bytes: Vec<u8>,
 }
 
 #[derive(Clone, PartialEq, Eq)]
 struct ExpectedSymtabEntry {
     name: String,
     section_name: String,
+    address: Option<u64>,
 }
 
 impl ExpectedSymtabEntry {
     fn parse(s: &str) -> Result<Self> {
         let mut parts = s.split(' ').map(str::to_owned);
-        let (Some(name), Some(section), None) = (parts.next(), parts.next(), parts.next()) else {
-            bail!("ExpectSym requires {{symbol name}}, {{symbol section}}");
+        let (Some(name), Some(section), address, None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("ExpectSym requires {{symbol name}}, {{symbol section}} [{{address}}]");
         };
         Ok(Self {
             name,
             section_name: section,
+            address: address.as_deref().map(parse_number).transpose()?,
+        })
+    }
+}
+
+impl ExpectedRelocation {
+    fn parse(s: &str) -> Result<Self> {
+        let mut parts = s.split(' ').map(str::to_owned);
+        let (Some(section_name), Some(symbol_name), None) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            bail!("ExpectReloc requires {{section name}} {{symbol name}}");
+        };
+        Ok(Self {
+            section_name,
+            symbol_name,
+        })
+    }
+}
+
+impl ExpectedFileBytes {
+    fn parse(s: &str) -> Result<Self> {
+        let Some((offset, hex)) = s.split_once(' ') else {
+            bail!("ExpectFileBytes requires {{file offset}} {{hex bytes}}");
+        };
+        let hex = hex.replace(' ', "");
+        if hex.len() % 2 != 0 {
+            bail!("ExpectFileBytes has an odd number of hex digits");
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .with_context(|| format!("Invalid hex bytes `{hex}`"))?;
+        Ok(Self {
+            offset: parse_number(offset)? as usize,
+            bytes,
         })
     }
 }
 
+/// Parses a number that's either decimal or hex with a 0x prefix.
+fn parse_number(s: &str) -> Result<u64> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    }
+    .with_context(|| format!("Invalid number `{s}`"))
+}
+
+/// Parses a comma-separated list of x86 features, e.g. `IBT,SHSTK`, into the bits of
+/// `GNU_PROPERTY_X86_FEATURE_1_AND`.
+fn parse_x86_features(s: &str) -> Result<u32> {
+    s.split(',')
+        .map(|feature| match feature.trim() {
+            "IBT" => Ok(object::elf::GNU_PROPERTY_X86_FEATURE_1_IBT),
+            "SHSTK" => Ok(object::elf::GNU_PROPERTY_X86_FEATURE_1_SHSTK),
+            other => bail!("Unknown x86 feature `{other}`"),
+        })
+        .try_fold(0, |features, feature| Ok(features | feature?))
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum InputType {
     Object,
     Archive,
     SharedObject,
+    Relocatable,
+    WildRelocatable,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -445,6 +612,10 @@ fn parse_configs(src_filename: &Path) -> Result<Vec<Config>> {
                     .assertions
                     .expected_symtab_entries
                     .push(ExpectedSymtabEntry::parse(arg.trim())?),
+                "NoSym" => config
+                    .assertions
+                    .unexpected_symbols
+                    .push(arg.trim().to_owned()),
                 "ExpectComment" => config
                     .assertions
                     .expected_comments
@@ -457,6 +628,53 @@ fn parse_configs(src_filename: &Path) -> Result<Vec<Config>> {
                     .assertions
                     .contains_strings
                     .push(arg.trim().to_owned()),
+                "ExpectReloc" => config
+                    .assertions
+                    .expected_relocations
+                    .push(ExpectedRelocation::parse(arg)?),
+                "ExpectLoadSegments" => {
+                    config.assertions.expected_load_segments =
+                        Some(arg.split(' ').map(str::to_owned).collect());
+                }
+                "ExpectHeadersLoaded" => {
+                    config.assertions.expected_headers_loaded = Some(
+                        arg.parse()
+                            .context("Invalid bool for ExpectHeadersLoaded")?,
+                    );
+                }
+                "ExpectLoadAlignment" => {
+                    config.assertions.expected_load_alignment = Some(parse_number(arg)?);
+                }
+                "ExpectSeparateCode" => {
+                    config.assertions.expected_separate_code =
+                        Some(arg.parse().context("Invalid bool for ExpectSeparateCode")?);
+                }
+                "ExpectSeparateLoads" => {
+                    config.assertions.expected_separate_loads = Some(
+                        arg.parse()
+                            .context("Invalid bool for ExpectSeparateLoads")?,
+                    );
+                }
+                "ExpectRelroAligned" => {
+                    config.assertions.expected_relro_alignment = Some(parse_number(arg)?);
+                }
+                "ExpectX86Features" => {
+                    config.assertions.expected_x86_features = Some(parse_x86_features(arg)?);
+                }
+                "OutputFormat" => {
+                    config.assertions.output_format_binary = match arg {
+                        "binary" => true,
+                        "elf" => false,
+                        other => bail!("Unsupported value for OutputFormat '{other}'"),
+                    }
+                }
+                "ExpectFileBytes" => config
+                    .assertions
+                    .expected_file_bytes
+                    .push(ExpectedFileBytes::parse(arg)?),
+                "ExpectFileSize" => {
+                    config.assertions.expected_file_size = Some(parse_number(arg)? as usize);
+                }
                 "DiffIgnore" => config.diff_ignore.push(arg.trim().to_owned()),
                 "DiffEnabled" => {
                     config.should_diff = arg.parse().context("Invalid bool for DiffEnabled")?
@@ -498,6 +716,14 @@ fn parse_configs(src_filename: &Path) -> Result<Vec<Config>> {
                     filename: arg.to_owned(),
                     input_type: InputType::SharedObject,
                 }),
+                "Relocatable" => config.deps.push(Dep {
+                    filename: arg.to_owned(),
+                    input_type: InputType::Relocatable,
+                }),
+                "WildRelocatable" => config.deps.push(Dep {
+                    filename: arg.to_owned(),
+                    input_type: InputType::WildRelocatable,
+                }),
                 "Compiler" => config.compiler = arg.trim().to_owned(),
                 "Arch" => {
                     config.support_architectures = arg
@@ -583,10 +809,6 @@ impl ProgramInputs {
 
 impl Program<'_> {
     fn run(&self, cross_arch: Option<Architecture>) -> Result {
-        self.assertions
-            .check(&self.link_output)
-            .context("Output binary assertions failed")?;
-
         let mut command = if let Some(arch) = cross_arch {
             let mut c = Command::new(format!("qemu-{arch}"));
             c.arg("-L");
@@ -662,7 +884,8 @@ impl LinkerInput {
     }
 }
 
-/// Creates a linker input from a source file. This will be either an object file or an archive.
+/// Creates a linker input from a source file. This will be an object file, an archive, a shared
+/// object or a relocatable object produced by `linker`.
 fn build_linker_input(
     dep: &Dep,
     config: &Config,
@@ -693,6 +916,14 @@ fn build_linker_input(
                 .with_context(|| format!("Assertions failed for `{}`", out.path.display()))?;
             Ok(out)
         }
+        InputType::Relocatable => {
+            let out_path = obj_path.with_extension(format!("{linker}.r.o"));
+            linker.link_relocatable(&obj_path, &out_path, config, cross_arch)
+        }
+        InputType::WildRelocatable => {
+            let out_path = obj_path.with_extension(format!("{}.r.o", Linker::Wild));
+            Linker::Wild.link_relocatable(&obj_path, &out_path, config, cross_arch)
+        }
     }
 }
 
@@ -1004,7 +1235,7 @@ impl Linker {
         let mut command =
             LinkCommand::new(self, inputs, &output_path, &linker_args, config, cross_arch)?;
         if !command.can_skip {
-            command.run(config)?;
+            command.run(config.expect_error.as_deref())?;
             write_cmd_file(&output_path, &command.to_string())?;
         }
         Ok(LinkOutput {
@@ -1167,8 +1398,8 @@ impl LinkCommand {
         Ok(link_command)
     }
 
-    fn run(&mut self, config: &Config) -> Result {
-        if let Some(expected_error) = config.expect_error.as_ref() {
+    fn run(&mut self, expected_error: Option<&str>) -> Result {
+        if let Some(expected_error) = expected_error {
             let output = self
                 .command
                 .output()
@@ -1221,11 +1452,41 @@ impl Assertions {
 
     fn check_path(&self, path: &PathBuf, linker_used: &Linker) -> Result {
         let bytes = std::fs::read(path)?;
+        self.verify_strings(&bytes)?;
+        self.verify_file_contents(&bytes)?;
+        if self.output_format_binary {
+            return Ok(());
+        }
         let obj = ElfFile64::parse(bytes.as_slice())?;
 
         self.verify_symbol_assertions(&obj)?;
+        self.verify_relocations(&obj)?;
+        self.verify_segments(&obj)?;
+        self.verify_x86_features(&obj)?;
         self.verify_comment_section(&obj, linker_used)?;
-        self.verify_strings(&bytes)?;
+        Ok(())
+    }
+
+    fn verify_file_contents(&self, bytes: &[u8]) -> Result {
+        if let Some(expected_size) = self.expected_file_size {
+            if bytes.len() != expected_size {
+                bail!(
+                    "Expected file size 0x{expected_size:x}, but got 0x{:x}",
+                    bytes.len()
+                );
+            }
+        }
+        for expected in &self.expected_file_bytes {
+            let actual = bytes.get(expected.offset..expected.offset + expected.bytes.len());
+            if actual != Some(expected.bytes.as_slice()) {
+                bail!(
+                    "Expected bytes {:02x?} at file offset 0x{:x}, but got {:02x?}",
+                    expected.bytes,
+                    expected.offset,
+                    actual.unwrap_or_default()
+                );
+            }
+        }
         Ok(())
     }
 
@@ -1237,6 +1498,13 @@ impl Assertions {
             .collect::<HashMap<_, _>>();
         for sym in obj.symbols() {
             if let Ok(name) = sym.name() {
+                if self
+                    .unexpected_symbols
+                    .iter()
+                    .any(|unexpected| unexpected == name)
+                {
+                    bail!("Symbol `{name}` shouldn't be in the symbol table");
+                }
                 if let Some(exp) = missing.remove(name) {
                     if let object::SymbolSection::Section(index) = sym.section() {
                         let section = obj.section_by_index(index)?;
@@ -1249,6 +1517,15 @@ impl Assertions {
                             );
                         }
                     }
+                    if let Some(address) = exp.address {
+                        if sym.address() != address {
+                            bail!(
+                                "Expected symbol `{name}` to be at 0x{address:x}, but it was at \
+                                 0x{:x}",
+                                sym.address()
+                            );
+                        }
+                    }
                 }
             }
         }
@@ -1259,6 +1536,189 @@ impl Assertions {
         Ok(())
     }
 
+    fn verify_relocations(&self, obj: &ElfFile64) -> Result {
+        for expected in &self.expected_relocations {
+            let section = obj
+                .section_by_name(&expected.section_name)
+                .with_context(|| format!("Missing section `{}`", expected.section_name))?;
+            let section_range = section.address()..section.address() + section.size();
+            let found = section.relocations().any(|(offset, rel)| {
+                let object::RelocationTarget::Symbol(symbol_index) = rel.target() else {
+                    return false;
+                };
+                section_range.contains(&offset)
+                    && obj
+                        .symbol_by_index(symbol_index)
+                        .and_then(|symbol| symbol.name())
+                        .is_ok_and(|name| name == expected.symbol_name)
+            });
+            if !found {
+                bail!(
+                    "Missing relocation in `{}` referring to `{}`",
+                    expected.section_name,
+                    expected.symbol_name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_segments(&self, obj: &ElfFile64) -> Result {
+        let e = LittleEndian;
+        let load_segments = obj
+            .elf_program_headers()
+            .iter()
+            .filter(|header| header.p_type(e) == object::elf::PT_LOAD)
+            .collect_vec();
+        if let Some(expected) = &self.expected_load_segments {
+            let actual = load_segments
+                .iter()
+                .map(|header| {
+                    let flags = header.p_flags(e);
+                    [
+                        (object::elf::PF_R, 'R'),
+                        (object::elf::PF_W, 'W'),
+                        (object::elf::PF_X, 'X'),
+                    ]
+                    .iter()
+                    .filter(|(flag, _)| flags & flag != 0)
+                    .map(|(_, c)| *c)
+                    .collect::<String>()
+                })
+                .collect_vec();
+            if &actual != expected {
+                bail!(
+                    "Expected PT_LOAD segments `{}`, but got `{}`",
+                    expected.join(" "),
+                    actual.join(" ")
+                );
+            }
+        }
+        if let Some(expected) = self.expected_headers_loaded {
+            let actual = load_segments
+                .iter()
+                .any(|header| header.p_offset(e) == 0 && header.p_filesz(e) > 0);
+            if actual != expected {
+                bail!("Expected headers loaded to be {expected}, but it was {actual}");
+            }
+        }
+        if let Some(alignment) = self.expected_load_alignment {
+            for header in &load_segments {
+                let (offset, address) = (header.p_offset(e), header.p_vaddr(e));
+                if header.p_align(e) != alignment || offset % alignment != address % alignment {
+                    bail!(
+                        "PT_LOAD at 0x{address:x} (offset 0x{offset:x}) has alignment 0x{:x}, \
+                         expected 0x{alignment:x}",
+                        header.p_align(e)
+                    );
+                }
+            }
+        }
+        // Whether two segments have any page in common, either in the file or in memory.
+        let share_page = |a: &&ProgramHeader64<LittleEndian>,
+                          b: &&ProgramHeader64<LittleEndian>| {
+            let pages = |start: u64, size: u64| {
+                let page = a.p_align(e).max(b.p_align(e)).max(1);
+                (size > 0).then(|| (start / page, (start + size - 1) / page))
+            };
+            let overlap = |x: Option<(u64, u64)>, y: Option<(u64, u64)>| {
+                x.zip(y).is_some_and(|(x, y)| x.0 <= y.1 && y.0 <= x.1)
+            };
+            overlap(
+                pages(a.p_offset(e), a.p_filesz(e)),
+                pages(b.p_offset(e), b.p_filesz(e)),
+            ) || overlap(
+                pages(a.p_vaddr(e), a.p_memsz(e)),
+                pages(b.p_vaddr(e), b.p_memsz(e)),
+            )
+        };
+        let shared_pairs = load_segments
+            .iter()
+            .tuple_combinations()
+            .filter(|(a, b)| share_page(a, b))
+            .collect_vec();
+        if let Some(expected) = self.expected_separate_code {
+            let is_exec = |header: &&ProgramHeader64<LittleEndian>| {
+                header.p_flags(e) & object::elf::PF_X != 0
+            };
+            // Besides not sharing pages with other segments, the executable segment mustn't map
+            // our headers or any sections that aren't code.
+            let maps_other_content = load_segments.iter().copied().filter(is_exec).any(|header| {
+                let page = header.p_align(e).max(1);
+                let start = header.p_vaddr(e) / page * page;
+                let end = (header.p_vaddr(e) + header.p_memsz(e)).next_multiple_of(page);
+                header.p_offset(e) < page
+                    || obj.sections().any(|section| {
+                        section.kind() != object::SectionKind::Text
+                            && section.address() != 0
+                            && section.size() > 0
+                            && section.address() < end
+                            && section.address() + section.size() > start
+                    })
+            });
+            let actual =
+                !maps_other_content && !shared_pairs.iter().any(|(a, b)| is_exec(a) || is_exec(b));
+            if actual != expected {
+                bail!("Expected separate code to be {expected}, but it was {actual}");
+            }
+        }
+        if let Some(expected) = self.expected_separate_loads {
+            let actual = shared_pairs.is_empty();
+            if actual != expected {
+                bail!("Expected separate loadable segments to be {expected}, but it was {actual}");
+            }
+        }
+        if let Some(page_size) = self.expected_relro_alignment {
+            let relro = obj
+                .elf_program_headers()
+                .iter()
+                .find(|header| header.p_type(e) == object::elf::PT_GNU_RELRO)
+                .context("Missing PT_GNU_RELRO")?;
+            let start = relro.p_vaddr(e);
+            let end = start + relro.p_memsz(e);
+            if end % page_size != 0 {
+                bail!("PT_GNU_RELRO ends at 0x{end:x}, which isn't a multiple of 0x{page_size:x}");
+            }
+            for section in obj.sections() {
+                let address = section.address();
+                if address != 0 && address < end && address + section.size() > end {
+                    bail!(
+                        "Section `{}` straddles the end of PT_GNU_RELRO at 0x{end:x}",
+                        section.name().unwrap_or_default()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_x86_features(&self, obj: &ElfFile64) -> Result {
+        let Some(expected) = self.expected_x86_features else {
+            return Ok(());
+        };
+        let e = LittleEndian;
+        let mut actual = 0;
+        for section in obj.elf_section_table().iter() {
+            let Some(mut notes) = section.notes(e, obj.data())? else {
+                continue;
+            };
+            while let Some(note) = notes.next()? {
+                let Some(mut properties) = note.gnu_properties(e) else {
+                    continue;
+                };
+                while let Some(property) = properties.next()? {
+                    if property.pr_type() == object::elf::GNU_PROPERTY_X86_FEATURE_1_AND {
+                        actual |= property.data_u32(e)?;
+                    }
+                }
+            }
+        }
+        if actual != expected {
+            bail!("Expected x86 features 0x{expected:x}, but got 0x{actual:x}");
+        }
+        Ok(())
+    }
+
     fn verify_comment_section(&self, obj: &ElfFile64, linker_used: &Linker) -> Result {
         if self.expected_comments.is_empty() {
             match linker_used {
@@ -1418,6 +1878,8 @@ impl Display for InputType {
             InputType::Object => write!(f, "object"),
             InputType::Archive => write!(f, "archive"),
             InputType::SharedObject => write!(f, "shared"),
+            InputType::Relocatable => write!(f, "relocatable"),
+            InputType::WildRelocatable => write!(f, "wild-relocatable"),
         }
     }
 }
@@ -1613,11 +2075,33 @@ fn integration_test(
         "rust-integration.rs",
         "rust-integration-dynamic.rs",
         "cpp-integration.cc",
+        "unique-symbols.cc",
+        "protected-symbols.c",
+        "indirect-extern-access.c",
+        "lazy-binding.c",
         "rust-tls.rs",
         "input_does_not_exist.c",
         "ifunc2.c",
         "tls-local-exec.c",
-        "undefined_symbols.c"
+        "undefined_symbols.c",
+        "split_stack.c",
+        "relocatable.c",
+        "oformat_binary.s",
+        "section_start.c",
+        "emit_relocs.c",
+        "omagic.c",
+        "page_size.c",
+        "separate_code.c",
+        "discard_locals.c",
+        "symbol-size.c",
+        "cet_report.c",
+        "range_extension_thunks.s",
+        "cortex_a53_errata.s",
+        "got_too_large.s",
+        "tlsdesc-dynamic.c",
+        "comdat-relink.cc",
+        "linker_script_locations.c",
+        "symver_shared.c"
     )]
     program_name: &'static str,
     #[allow(unused_variables)] setup_symlink: (),
@@ -1743,6 +2227,18 @@ fn integration_test(
                 );
             }
 
+            for program in &programs {
+                // Some configs send the output elsewhere, e.g. `-o /dev/null`, so there's nothing to
+                // check.
+                if !program.link_output.binary.exists() {
+                    continue;
+                }
+                program
+                    .assertions
+                    .check(&program.link_output)
+                    .with_context(|| format!("Output binary assertions failed. {program}"))?;
+            }
+
             if config.should_run {
                 for program in programs {
                     program
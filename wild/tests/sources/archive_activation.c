@@ -5,6 +5,10 @@
 //#Archive:empty.a
 //#EnableLinker:lld
 
+//#Config:undefined:default
+//#CompArgs:-ffunction-sections -DFORCE_ARCHIVE1
+//#LinkArgs:-z now -u archive1_data --require-defined=is_archive0_loaded
+
 #include "exit.h"
 
 int bar(void);
@@ -22,9 +26,15 @@ void _start(void) {
     if (!is_archive0_loaded()) {
         exit_syscall(101);
     }
+#ifdef FORCE_ARCHIVE1
+    if (!is_archive1_loaded()) {
+        exit_syscall(103);
+    }
+#else
     if (is_archive1_loaded()) {
         exit_syscall(102);
     }
+#endif
     exit_syscall(42);
 }
 
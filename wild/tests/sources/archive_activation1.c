@@ -5,3 +5,6 @@ __attribute__ ((weak)) int bar(void) {
 int is_archive1_loaded(void) {
     return 1;
 }
+
+// Not referenced by anything. Used with `-u` to load this archive member.
+int archive1_data = 1;
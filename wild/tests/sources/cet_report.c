@@ -0,0 +1,32 @@
+// Checks that -z cet-report=error rejects inputs that aren't IBT and shadow stack compatible and
+// that -z force-ibt and -z shstk mark the output regardless.
+
+//#AbstractConfig:default
+//#Object:exit.c
+//#Arch: x86_64
+
+//#Config:compatible:default
+//#CompArgs:-fcf-protection=full
+//#LinkArgs:-z noexecstack -z cet-report=error
+//#ExpectX86Features:IBT,SHSTK
+
+//#Config:incompatible:default
+//#CompArgs:-fcf-protection=none
+//#LinkArgs:-z noexecstack -z cet-report=error
+//#SkipLinker:ld
+//#DiffEnabled:false
+//#RunEnabled:false
+//#ExpectError:-z cet-report: file does not have GNU_PROPERTY_X86_FEATURE_1_IBT property
+
+//#Config:forced:default
+//#CompArgs:-fcf-protection=none
+//#LinkArgs:-z noexecstack -z force-ibt -z shstk
+//#SkipLinker:ld
+//#DiffEnabled:false
+//#ExpectX86Features:IBT,SHSTK
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
@@ -0,0 +1,5 @@
+#include "comdat-relink.h"
+
+int bump_0() {
+    return bump_counter();
+}
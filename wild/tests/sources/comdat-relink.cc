@@ -0,0 +1,30 @@
+// Links a C++ object that we produced with `-r` against another that uses the same inline function.
+// Both copies of the function and of its static local are in COMDAT groups, which we need to keep
+// with `-r` so that the final link, whichever linker does it, can discard the duplicates.
+
+//#AbstractConfig:default
+//#WildRelocatable:comdat-relink-0.cc
+// Every output has the .comment that we put in the relocatable object, so we can't use that to tell
+// which linker did the final link.
+//#ExpectComment:GCC*
+//#DiffIgnore:.dynamic.DT_NEEDED
+//#DiffIgnore:section.rodata
+//#DiffIgnore:section.data
+//#DiffIgnore:section.bss.alignment
+
+//#Config:inlined:default
+//#CompArgs:-O1
+//#LinkArgs:--cc=g++ -no-pie -Wl,-z,now
+
+//#Config:not-inlined:default
+//#CompArgs:-O0 -g
+//#LinkArgs:--cc=g++ -no-pie -Wl,-z,now
+
+#include "comdat-relink.h"
+
+int main() {
+    if (bump_0() != 1 || bump_counter() != 2 || bump_0() != 3) {
+        return 1;
+    }
+    return 42;
+}
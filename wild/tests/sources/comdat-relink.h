@@ -0,0 +1,6 @@
+inline int bump_counter() {
+    static int counter = 0;
+    return ++counter;
+}
+
+int bump_0();
@@ -10,6 +10,8 @@ int a;
 extern int data[];
 extern int q[];
 extern int z[];
+extern int big_first[];
+extern int aligned[];
 
 void _start(void) {
     a = 30;
@@ -27,6 +29,17 @@ void _start(void) {
         exit_syscall(101);
     }
     data[100] = 10;
+    for (int i = 0; i < 1000; i++) {
+        big_first[i] = 7;
+    }
+    if (a != 30 || q[0] != 20 || z[0] != 40) {
+        exit_syscall(102);
+    }
+    // The alignment of a common symbol is the largest of any of its declarations.
+    if ((unsigned long)aligned % 64 != 0) {
+        exit_syscall(103);
+    }
+    aligned[3] = 1;
     exit_syscall(42);
 }
 
@@ -1,6 +1,12 @@
 extern int data[];
 int data[10];
 
+// The larger declaration comes first this time.
+int big_first[1000];
+
+// The other declaration is smaller, but needs more alignment.
+int aligned[4];
+
 // Not referenced.
 extern int data2[];
 int data2[10];
@@ -0,0 +1,33 @@
+// Checks that instruction sequences affected by Cortex-A53 errata 843419 and 835769 get patched and
+// still compute the right result. The `adrp` is placed in the second last instruction slot of a
+// page, followed by a load and then a load using the address computed by the `adrp`. The final load
+// of that sequence is also followed by a multiply-accumulate.
+
+//#LinkArgs:-z noexecstack --fix-cortex-a53-843419 --fix-cortex-a53-835769
+//#Object:exit.c
+//#Arch: aarch64
+//#DiffEnabled:false
+
+.section .text._start,"ax",@progbits
+.balign 4096
+.globl _start
+.type _start, @function
+_start:
+    mov     x2, sp
+    mov     x5, #1
+    .rept 1020
+    nop
+    .endr
+    adrp    x0, value
+    ldr     x1, [x2]
+    ldr     x3, [x0, :lo12:value]
+    ldr     x4, [x2]
+    madd    x0, x5, x5, x3
+    add     x0, x0, #1
+    bl      exit_syscall
+.size _start, .-_start
+
+.section .data,"aw",@progbits
+.quad 0
+value:
+    .quad 40
@@ -0,0 +1,33 @@
+// Checks that -X discards temporary local symbols, that -x discards all local symbols and that
+// --discard-none keeps them all. We assemble with -L so that our `.L` label gets a symbol.
+
+//#AbstractConfig:default
+//#Object:exit.c
+//#CompArgs:-Wa,-L
+//#ExpectSym: _start .text
+
+//#Config:discard-locals:default
+//#LinkArgs:-X
+//#ExpectSym: local_fn .text
+//#NoSym: .Ltemp_label
+
+//#Config:discard-all:default
+//#LinkArgs:-x
+//#NoSym: local_fn
+//#NoSym: .Ltemp_label
+
+//#Config:discard-none:default
+//#LinkArgs:--discard-none
+//#ExpectSym: local_fn .text
+//#ExpectSym: .Ltemp_label .text
+
+#include "exit.h"
+
+static int __attribute__((noinline)) local_fn(int x) {
+    return x + 2;
+}
+
+void _start(void) {
+    __asm__ volatile(".Ltemp_label:");
+    exit_syscall(local_fn(40));
+}
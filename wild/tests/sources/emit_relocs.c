@@ -0,0 +1,48 @@
+// Checks that --emit-relocs keeps the relocations from our inputs, with offsets that are addresses
+// in the output.
+
+//#AbstractConfig:default
+// We rebuild .eh_frame rather than copying it, so we don't yet keep its relocations.
+//#DiffIgnore:section.rela.eh_frame
+
+//#Config:static:default
+//#Object:exit.c
+//#LinkArgs:--emit-relocs
+//#ExpectReloc: .text exit_syscall
+
+// An ordinary dynamic link with the C runtime's startup files, which is how post-link optimisers
+// such as BOLT usually get their inputs.
+//#Config:crt:default
+//#CompArgs:-O2 -DWITH_CRT
+//#LinkArgs:--cc=gcc -dynamic -Wl,--emit-relocs -Wl,-z,now
+//#ExpectReloc: .text compute
+//#DiffIgnore:section.rela.text
+//#DiffIgnore:section.rela.data
+//#DiffIgnore:section.rodata
+//#DiffIgnore:section.rodata.alignment
+
+#ifdef WITH_CRT
+
+#include <stdio.h>
+
+int compute(int x);
+
+int main(void) {
+    // A PC-relative reference to a merged string, which may be at the very start of .rodata.
+    puts("emit-relocs");
+    return compute(14);
+}
+
+__attribute__((noinline)) int compute(int x) {
+    return x * 3;
+}
+
+#else
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(42);
+}
+
+#endif
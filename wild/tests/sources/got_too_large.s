@@ -0,0 +1,38 @@
+// Checks that we report an error rather than silently truncating GOT offsets when code compiled
+// with -fpic refers to more GOT entries than its 15 bit GOT offsets can reach.
+
+//#LinkArgs:-pie -z noexecstack
+//#Arch: aarch64
+//#SkipLinker:ld
+//#DiffEnabled:false
+//#ExpectError:Multiple GOTs aren't supported
+
+.altmacro
+
+.macro access_got n
+    ldr     x0, [x28, #:gotpage_lo15:sym\n]
+.endm
+
+.macro define_sym n
+.globl sym\n
+sym\n:
+    .quad 0
+.endm
+
+.text
+.globl _start
+_start:
+    adrp    x28, _GLOBAL_OFFSET_TABLE_
+.set i, 0
+.rept 5000
+    access_got %i
+    .set i, i + 1
+.endr
+    ret
+
+.data
+.set i, 0
+.rept 5000
+    define_sym %i
+    .set i, i + 1
+.endr
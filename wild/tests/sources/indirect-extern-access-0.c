@@ -0,0 +1,5 @@
+int lib_data = 5;
+
+int lib_fn(void) {
+    return 7;
+}
@@ -0,0 +1,50 @@
+// Checks that we refuse to make copy relocations or canonical PLT entries for symbols of a shared
+// object that was marked as needing indirect access to its symbols via
+// GNU_PROPERTY_1_NEEDED_INDIRECT_EXTERN_ACCESS.
+
+//#AbstractConfig:default
+//#CompArgs:-fPIC -mno-direct-extern-access
+//#LinkArgs:--cc=gcc -dynamic -Wl,-rpath,$ORIGIN -Wl,-z,now
+//#Shared:indirect-extern-access-0.c
+//#Arch: x86_64
+
+//#Config:got:default
+//#DiffIgnore:.dynamic.DT_NEEDED
+//#DiffIgnore:section.rodata
+
+//#Config:copy-relocation:default
+//#CompArgs:-fPIC -mno-direct-extern-access -DCOPY_DATA
+//#DiffEnabled:false
+//#RunEnabled:false
+//#ExpectError:which needs indirect extern access
+// GNU ld leaves this for the dynamic loader to reject.
+//#SkipLinker:ld
+
+//#Config:canonical-plt:default
+//#CompArgs:-fPIC -mno-direct-extern-access -DTAKE_FUNCTION_ADDRESS
+//#DiffEnabled:false
+//#RunEnabled:false
+//#ExpectError:which needs indirect extern access
+//#SkipLinker:ld
+
+extern int lib_data;
+int lib_fn(void);
+
+int main(void) {
+#if defined(COPY_DATA)
+    // A PC-relative reference from non-PIC code, which would need a copy relocation.
+    __asm__ volatile("movl $9, lib_data(%%rip)" ::: "memory");
+#elif defined(TAKE_FUNCTION_ADDRESS)
+    // A direct reference to the function's address, which would need a canonical PLT entry.
+    void *address;
+    __asm__ volatile("leaq lib_fn(%%rip), %0" : "=r"(address));
+#endif
+
+    if (lib_data != 5) {
+        return 101;
+    }
+    if (lib_fn() != 7) {
+        return 102;
+    }
+    return 42;
+}
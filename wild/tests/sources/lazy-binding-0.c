@@ -0,0 +1,11 @@
+int lazy_fn(void) {
+    return 3;
+}
+
+int called_fn(void) {
+    return 4;
+}
+
+void *lazy_fn_address(void) {
+    return (void *)lazy_fn;
+}
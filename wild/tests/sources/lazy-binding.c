@@ -0,0 +1,30 @@
+// Checks that with -z lazy, a function that we both call via the PLT and load the address of from
+// the GOT gets its real address in the GOT from the start, rather than the address of the PLT stub
+// that does the lazy binding.
+
+//#Config:default
+//#CompArgs:-fPIC
+//#LinkArgs:--cc=gcc -dynamic -Wl,-z,lazy -Wl,-rpath,$ORIGIN
+//#Shared:lazy-binding-0.c
+//#DiffIgnore:.dynamic.DT_NEEDED
+//#DiffIgnore:section.rodata
+//#Arch: x86_64
+
+int lazy_fn(void);
+int called_fn(void);
+void *lazy_fn_address(void);
+
+int main(void) {
+    void *address;
+    __asm__("movq lazy_fn@GOTPCREL(%%rip), %0" : "=r"(address));
+    if (lazy_fn() != 3) {
+        return 100;
+    }
+    if (address != lazy_fn_address()) {
+        return 101;
+    }
+    if (called_fn() != 4) {
+        return 102;
+    }
+    return 42;
+}
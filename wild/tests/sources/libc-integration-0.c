@@ -72,3 +72,11 @@ int sometimes_weak_fn(void) {
 int black_box(int v) {
     return v;
 }
+
+int get_42(void);
+
+// When the main executable isn't position-independent, it uses the address of a PLT entry as the
+// address of `get_42`, so this should return that too.
+void* get_42_address(void) {
+    return get_42;
+}
@@ -72,6 +72,14 @@
 //#Shared:libc-integration-0.c
 //#Shared:libc-integration-1.c
 
+//#Config:gcc-dynamic-no-pic:default
+//#CompArgs:-g -fno-pic -DDYNAMIC_DEP
+//#CompSoArgs:-g -fPIC -ftls-model=global-dynamic
+//#LinkArgs:--cc=gcc -dynamic -no-pie -Wl,--strip-debug -Wl,--gc-sections -Wl,-z,now
+//#Shared:libc-integration-0.c
+//#Shared:libc-integration-1.c
+//#Arch: x86_64
+
 //#Config:gcc-dynamic-pie-large:default
 //#CompArgs:-g -fpie -DDYNAMIC_DEP -mcmodel=large
 //#CompSoArgs:-g -fPIC -ftls-model=global-dynamic
@@ -81,6 +89,15 @@
 // TODO: cc1plus: sorry, unimplemented: code model 'large' with '-fPIC'
 //#Arch: x86_64
 
+//#Config:clang-dynamic-pie-large:default
+//#Compiler:clang
+//#CompArgs:-g -fpie -DDYNAMIC_DEP -mcmodel=large
+//#CompSoArgs:-g -fPIC -ftls-model=global-dynamic -mcmodel=large
+//#LinkArgs:--cc=clang -dynamic -Wl,--strip-debug -Wl,--gc-sections -Wl,-z,now
+//#Shared:libc-integration-0.c
+//#Shared:libc-integration-1.c
+//#Arch: x86_64
+
 #include <stdlib.h>
 #include <string.h>
 #include <pthread.h>
@@ -119,6 +136,7 @@ int get_weak_tvar2(void);
 int compute_value10(void);
 int black_box(int v);
 int get_42(void);
+void* get_42_address(void);
 
 typedef int(*get_int_fn_t)(void);
 
@@ -272,6 +290,9 @@ int main() {
     if (strcmp(str1, "This is str1") != 0) {
         return 122;
     }
+    if (get_42_address() != (void*)get_42) {
+        return 123;
+    }
 #ifdef VERIFY_CTORS
     if (ctors_init_val != 42) {
         return ctors_init_val;
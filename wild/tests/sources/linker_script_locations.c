@@ -0,0 +1,44 @@
+// Checks that assignments to `.` within the output sections of a linker script passed with `-T`
+// move the start of a section or pad its end, and that sections with assignments are output even
+// if nothing goes in them.
+
+//#Object:exit.c
+//#LinkArgs:-static -T tests/sources/linker_script_locations.ld
+//#DiffEnabled:false
+//#ExpectSym: data_start .data 0x601010
+//#ExpectSym: data_value .data 0x601010
+//#ExpectSym: data_end .data 0x601018
+//#ExpectSym: data_padded .data 0x60101d
+//#ExpectSym: bss_value .bss 0x601020
+//#ExpectSym: bss_end .bss 0x601124
+//#ExpectSym: stack_start .stack 0x601130
+//#ExpectSym: stack_end .stack 0x601530
+//#ExpectSym: empty_start .empty 0x601530
+
+#include "exit.h"
+
+extern char data_start[], data_end[], data_padded[], bss_end[];
+extern char stack_start[], stack_end[], empty_start[], empty_load_address[];
+
+long data_value = 5;
+int bss_value;
+
+void _start(void) {
+    if ((unsigned long)data_start % 16 != 0) {
+        exit_syscall(1);
+    }
+    if (data_end - data_start != sizeof(data_value)) {
+        exit_syscall(2);
+    }
+    if (data_padded - data_end != 5) {
+        exit_syscall(3);
+    }
+    if (bss_end - (char *)&bss_value != sizeof(bss_value) + 0x100) {
+        exit_syscall(4);
+    }
+    if (stack_end - stack_start != 0x400 || empty_load_address != empty_start) {
+        exit_syscall(5);
+    }
+    bss_value = 37;
+    exit_syscall(data_value + bss_value);
+}
@@ -0,0 +1,23 @@
+// Checks that --oformat binary writes just the loadable bytes, with the gap between .text and .data
+// filled with zeros.
+
+//#LinkArgs:-z noexecstack --oformat binary -Ttext=0x401000 -Tdata=0x402000
+//#OutputFormat:binary
+//#RunEnabled:false
+//#DiffEnabled:false
+//#Arch: x86_64
+//#ExpectFileSize:0x1002
+//#ExpectFileBytes:0 deadbeef fc0f0000
+//#ExpectFileBytes:0xff8 0000000000000000
+//#ExpectFileBytes:0x1000 1122
+
+.section .text, "ax", @progbits
+.globl _start
+_start:
+    .byte 0xde, 0xad, 0xbe, 0xef
+    // Referencing .data keeps it from being garbage collected.
+    .long data_bytes - .
+
+.section .data, "aw", @progbits
+data_bytes:
+    .byte 0x11, 0x22
@@ -0,0 +1,22 @@
+// Checks that -N and -n put everything in a single segment that doesn't include our headers.
+
+//#AbstractConfig:default
+//#Object:exit.c
+//#ExpectLoadSegments:RWX
+//#ExpectHeadersLoaded:false
+
+//#Config:omagic:default
+//#LinkArgs:-N
+
+//#Config:nmagic:default
+//#LinkArgs:-n
+
+#include "exit.h"
+
+int data_value = 5;
+int bss_value;
+
+void _start(void) {
+    bss_value = 37;
+    exit_syscall(data_value + bss_value);
+}
@@ -0,0 +1,29 @@
+// Checks that -z max-page-size sets the alignment of our loadable segments and that RELRO gets
+// padded to -z common-page-size, so that the page that's made read-only doesn't contain anything
+// that's written at runtime.
+
+//#AbstractConfig:default
+//#Object:exit.c
+
+//#Config:default-page-size:default
+//#ExpectLoadAlignment:0x1000
+//#ExpectRelroAligned:0x1000
+
+//#Config:large-pages:default
+//#LinkArgs:-z max-page-size=0x10000 -z common-page-size=0x2000
+//#ExpectLoadAlignment:0x10000
+//#ExpectRelroAligned:0x2000
+
+#include "exit.h"
+
+int data_value = 5;
+
+static void init(void) {}
+
+// Goes in .init_array, which is read-only once relocations have been applied.
+__attribute__((used, section(".init_array"))) static void (*init_fn)(void) = init;
+
+void _start(void) {
+    data_value += 37;
+    exit_syscall(data_value);
+}
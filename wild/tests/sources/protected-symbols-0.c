@@ -0,0 +1,13 @@
+__attribute__((visibility("protected"))) int protected_data = 5;
+
+__attribute__((visibility("protected"))) int protected_fn(void) {
+    return 7;
+}
+
+int get_protected_data(void) {
+    return protected_data;
+}
+
+void *get_protected_fn(void) {
+    return (void *)protected_fn;
+}
@@ -0,0 +1,57 @@
+// Checks that references from an executable to protected symbols in a shared object bind to the
+// shared object's definitions and that we refuse to copy them into the executable.
+
+//#AbstractConfig:default
+//#CompArgs:-fPIC
+//#LinkArgs:--cc=gcc -dynamic -Wl,-rpath,$ORIGIN -Wl,-z,now
+//#Shared:protected-symbols-0.c
+//#Arch: x86_64
+
+//#Config:got:default
+//#DiffIgnore:.dynamic.DT_NEEDED
+//#DiffIgnore:section.rodata
+
+//#Config:copy-relocation:default
+//#CompArgs:-fPIC -DCOPY_DATA
+//#DiffEnabled:false
+//#RunEnabled:false
+//#ExpectError:relocation against non-copyable protected symbol
+
+//#Config:canonical-plt:default
+//#CompArgs:-fPIC -DTAKE_FUNCTION_ADDRESS
+//#DiffEnabled:false
+//#RunEnabled:false
+//#ExpectError:reference to canonical protected function
+// GNU ld reports this as a copy relocation against the function.
+//#SkipLinker:ld
+
+extern int protected_data;
+int protected_fn(void);
+int get_protected_data(void);
+void *get_protected_fn(void);
+
+int main(void) {
+#if defined(COPY_DATA)
+    // A PC-relative reference from non-PIC code, which would need a copy relocation.
+    __asm__ volatile("movl $9, protected_data(%%rip)" ::: "memory");
+#elif defined(TAKE_FUNCTION_ADDRESS)
+    // A direct reference to the function's address, which would need a canonical PLT entry.
+    void *address;
+    __asm__ volatile("leaq protected_fn(%%rip), %0" : "=r"(address));
+    if (address != get_protected_fn()) {
+        return 100;
+    }
+#endif
+
+    protected_data = 9;
+    if (get_protected_data() != 9) {
+        return 101;
+    }
+    if ((void *)protected_fn != get_protected_fn()) {
+        return 102;
+    }
+    if (protected_fn() != 7) {
+        return 103;
+    }
+    return 42;
+}
@@ -0,0 +1,46 @@
+// Checks that calls to functions that are further away than a branch instruction can reach go via
+// range-extension thunks. `far_func` is placed more than 128MiB after `_start`, so the calls to it
+// can't reach it directly.
+
+//#LinkArgs:-z noexecstack --no-gc-sections
+//#Object:exit.c
+//#Arch: aarch64
+//#DiffEnabled:false
+
+.section .text._start,"ax",@progbits
+.globl _start
+.type _start, @function
+_start:
+    bl      far_func
+    cmp     x0, #40
+    b.ne    fail
+    bl      far_func
+    cmp     x0, #41
+    b.ne    fail
+    b       far_func
+fail:
+    mov     x0, #1
+    bl      exit_syscall
+.size _start, .-_start
+
+.section .text.pad,"ax",@progbits
+    .space 0x8100000
+
+.section .data,"aw",@progbits
+count:
+    .quad 40
+
+.section .text.far,"ax",@progbits
+.globl far_func
+.type far_func, @function
+far_func:
+    adrp    x1, count
+    ldr     x0, [x1, :lo12:count]
+    add     x2, x0, #1
+    str     x2, [x1, :lo12:count]
+    cmp     x0, #42
+    b.ne    1f
+    b       exit_syscall
+1:
+    ret
+.size far_func, .-far_func
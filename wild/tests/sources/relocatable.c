@@ -0,0 +1,28 @@
+// Links a relocatable object produced by the linker under test into an executable, which checks
+// that the relocations and symbols that we write with `-r` are usable by a later link.
+
+//#Relocatable:relocatable0.c
+//#Object:exit.c
+//#ExpectSym:get_value .text
+
+#include "exit.h"
+
+extern int get_value(void);
+extern const char* message;
+
+// These are left undefined by `-r`, so should get defined by the final link.
+extern long __start_rel_sec[];
+extern long __stop_rel_sec[];
+
+void _start(void) {
+    if (get_value() != 17) {
+        exit_syscall(101);
+    }
+    if (message[0] != 'r') {
+        exit_syscall(102);
+    }
+    if (__stop_rel_sec - __start_rel_sec != 2 || __start_rel_sec[1] != 20) {
+        exit_syscall(103);
+    }
+    exit_syscall(42);
+}
@@ -0,0 +1,16 @@
+static int counter = 10;
+
+int value_in_data = 7;
+
+const char* message = "relocatable";
+
+static long rel_sec_items[2] __attribute__ ((used, retain, section ("rel_sec"))) = {10, 20};
+
+static int bump(int by) {
+    counter += by;
+    return counter;
+}
+
+int get_value(void) {
+    return bump(value_in_data);
+}
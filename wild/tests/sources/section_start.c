@@ -0,0 +1,23 @@
+// Checks that -Ttext, -Tbss and --section-start put sections at the requested addresses.
+
+//#Object:exit.c
+//#LinkArgs:-Ttext=0x401000 --section-start=.data=0x600000 -Tbss=0x700000
+//#ExpectSym: _start .text 0x401000
+//#ExpectSym: data_value .data 0x600000
+//#ExpectSym: bss_value .bss 0x700000
+
+// A section below our usual base address takes our headers down with it.
+//#Config:below-base
+//#Object:exit.c
+//#LinkArgs:-Ttext=0x20000
+//#ExpectSym: _start .text 0x20000
+
+#include "exit.h"
+
+int data_value = 5;
+int bss_value;
+
+void _start(void) {
+    bss_value = 37;
+    exit_syscall(data_value + bss_value);
+}
@@ -0,0 +1,32 @@
+// Checks that -z separate-code puts our executable segment on pages of its own, that
+// -z separate-loadable-segments does the same for every loadable segment and that
+// -z noseparate-code packs them together.
+
+//#AbstractConfig:default
+//#Object:exit.c
+
+//#Config:separate-code:default
+//#LinkArgs:-z separate-code
+//#ExpectSeparateCode:true
+
+//#Config:noseparate-code:default
+//#LinkArgs:-z noseparate-code
+//#ExpectSeparateCode:false
+
+// GNU ld ignores -z separate-loadable-segments.
+//#Config:separate-loadable-segments:default
+//#LinkArgs:-z separate-loadable-segments
+//#ExpectSeparateCode:true
+//#ExpectSeparateLoads:true
+//#SkipLinker:ld
+//#DiffEnabled:false
+
+#include "exit.h"
+
+const char message[] = "read-only data";
+int data_value = 5;
+
+void _start(void) {
+    data_value += 37 + (message[0] - 'r');
+    exit_syscall(data_value);
+}
@@ -0,0 +1,31 @@
+// Checks that when split-stack code calls code that isn't split-stack, we rewrite the caller so
+// that it allocates a large enough stack segment. `uses_lots_of_stack` is written in assembly so
+// that it doesn't get a `.note.GNU-split-stack` section. GNU ld doesn't support this, so we only
+// compare against gold.
+
+//#CompArgs:-fsplit-stack -O1
+//#LinkArgs:--cc=gcc -Wl,-z,now
+//#Object:split_stack0.s
+//#RequiresGlibc:true
+//#Arch: x86_64
+//#SkipLinker:ld
+//#EnableLinker:gold
+//#DiffEnabled:false
+
+int uses_lots_of_stack(int);
+
+int recurse(int n) {
+    volatile int buf[64];
+    buf[0] = n;
+    if (n == 0) {
+        return uses_lots_of_stack(buf[0]);
+    }
+    return recurse(n - 1) + buf[0] - n + 1;
+}
+
+int main() {
+    if (recurse(1000) != 1002) {
+        return 1;
+    }
+    return 42;
+}
@@ -0,0 +1,15 @@
+// A function that isn't split-stack and which needs more stack than a split-stack segment would
+// provide.
+
+.globl uses_lots_of_stack
+.type uses_lots_of_stack, @function
+uses_lots_of_stack:
+    sub $100000, %rsp
+    movl %edi, (%rsp)
+    movl $2, 99996(%rsp)
+    movl 99996(%rsp), %eax
+    add $100000, %rsp
+    ret
+.size uses_lots_of_stack, .-uses_lots_of_stack
+
+.section .note.GNU-stack,"",@progbits
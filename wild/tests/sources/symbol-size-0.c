@@ -0,0 +1,15 @@
+char shared_data[25];
+
+#ifdef DYNAMIC_DEP
+// The executable defines its own, larger, `interposed_data`, which takes precedence.
+char interposed_data[8];
+#endif
+
+__asm__(".pushsection .data\n"
+        "interposed_data_size:\n"
+        "    .quad interposed_data@SIZE\n"
+        ".popsection");
+
+extern unsigned long interposed_data_size;
+
+unsigned long get_interposed_data_size(void) { return interposed_data_size; }
@@ -0,0 +1,24 @@
+.section .text,"ax",@progbits
+
+.globl get_local_data_size
+.type get_local_data_size, @function
+get_local_data_size:
+    movabs  $local_data@SIZE, %rax
+    ret
+
+.section .data,"aw",@progbits
+
+.type local_data, @object
+.size local_data, 10
+local_data:
+    .zero 10
+
+.globl local_data_size
+local_data_size:
+    .quad   local_data@SIZE + 1
+
+.globl shared_data_size
+shared_data_size:
+    .quad   shared_data@SIZE + 3
+
+.section .note.GNU-stack,"",@progbits
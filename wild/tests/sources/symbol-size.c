@@ -0,0 +1,42 @@
+// Checks that R_X86_64_SIZE32 and R_X86_64_SIZE64 resolve to the size of the symbol. When the
+// symbol is defined by a shared object, or the shared object that refers to it can have the symbol
+// interposed, its size is only known at runtime, so we need a dynamic relocation.
+
+//#AbstractConfig:default
+//#Object:symbol-size-1.s
+//#Arch: x86_64
+
+//#Config:static:default
+//#LinkArgs:--cc=gcc -static -Wl,-z,now
+//#Object:symbol-size-0.c
+
+//#Config:dynamic:default
+//#LinkArgs:--cc=gcc -dynamic -Wl,-rpath,$ORIGIN -Wl,-z,now
+//#CompArgs:-DDYNAMIC_DEP
+//#Shared:symbol-size-0.c
+//#DiffIgnore:.dynamic.DT_NEEDED
+//#DiffIgnore:section.rodata
+
+extern unsigned long local_data_size;
+extern unsigned long shared_data_size;
+
+char interposed_data[40];
+
+unsigned long get_local_data_size(void);
+unsigned long get_interposed_data_size(void);
+
+int main(void) {
+    if (local_data_size != 11) {
+        return 10;
+    }
+    if (get_local_data_size() != 10) {
+        return 11;
+    }
+    if (shared_data_size != 28) {
+        return 12;
+    }
+    if (get_interposed_data_size() != 40) {
+        return 13;
+    }
+    return 42;
+}
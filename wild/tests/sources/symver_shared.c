@@ -0,0 +1,27 @@
+//#AbstractConfig:default
+//#RunEnabled:false
+//#DiffIgnore:.dynamic.*
+
+//#Config:version-script:default
+//#LinkArgs:--shared --version-script ./tests/sources/symver_shared.map
+
+//#Config:no-version-script:default
+//#LinkArgs:--shared
+//#ExpectError:node not found for symbol
+
+// `foo@V1` should be exported as a hidden version, `foo@@V2` as the default version and `baz@V1`
+// should be local, since V1 has `local: *`.
+
+#define SYMVER(a, b) __asm__(".symver " a "," b)
+
+SYMVER("foo_v1", "foo@V1");
+SYMVER("foo_v2", "foo@@V2");
+SYMVER("baz_v1", "baz@V1");
+
+int foo_v1(void) { return 1; }
+
+int foo_v2(void) { return 2; }
+
+int baz_v1(void) { return 3; }
+
+int bar(void) { return 4; }
@@ -0,0 +1,5 @@
+__thread int ext_tv = 7;
+
+int get_ext_tv(void) {
+    return ext_tv;
+}
@@ -0,0 +1,27 @@
+// Checks TLSDESC accesses from a non-PIE executable to a TLS variable defined by a shared object.
+// The access gets relaxed to initial-exec, which leaves the TLSDESC call with a relocation that
+// writes nothing. That relocation mustn't cause a copy relocation to be emitted for the variable.
+
+//#Config:default
+//#CompArgs:-O1 -fPIC -mtls-dialect=gnu2
+//#LinkArgs:--cc=gcc -dynamic -no-pie -Wl,-z,now -Wl,-rpath,$ORIGIN
+//#Shared:tlsdesc-dynamic-0.c
+//#DiffIgnore:.dynamic.DT_NEEDED
+//#DiffIgnore:section.data
+//#DiffIgnore:section.rodata
+//#Arch: x86_64
+
+extern __thread int ext_tv;
+
+int get_ext_tv(void);
+
+int main(void) {
+    if (ext_tv != 7) {
+        return 100;
+    }
+    ext_tv = 8;
+    if (get_ext_tv() != 8) {
+        return 101;
+    }
+    return 42;
+}
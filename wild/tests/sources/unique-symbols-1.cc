@@ -0,0 +1,6 @@
+#include "unique-symbols.h"
+
+int bump_1() {
+    Holder<long>::value++;
+    return ++counter();
+}
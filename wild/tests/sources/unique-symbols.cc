@@ -0,0 +1,41 @@
+// Checks that STB_GNU_UNIQUE symbols resolve to a single definition, both between objects and
+// between an executable and the shared object it links against.
+
+//#AbstractConfig:default
+//#Object:unique-symbols-1.cc
+//#Arch: x86_64
+
+//#Config:static:default
+//#LinkArgs:--cc=g++ -static -Wl,-z,now
+//#Object:unique-symbols-0.cc
+
+//#Config:dynamic:default
+//#CompArgs:-fPIC
+//#LinkArgs:--cc=g++ -dynamic -Wl,-rpath,$ORIGIN -Wl,-z,now
+//#Shared:unique-symbols-0.cc
+//#DiffIgnore:.dynamic.DT_NEEDED
+//#DiffIgnore:.dynamic.DT_FLAGS_1
+//#DiffIgnore:section.rodata
+
+#include "unique-symbols.h"
+
+int bump_0();
+int bump_1();
+
+int main() {
+    counter() = 10;
+    Holder<long>::value = 40;
+    if (bump_0() != 11) {
+        return 10;
+    }
+    if (bump_1() != 12) {
+        return 11;
+    }
+    if (counter() != 12) {
+        return 12;
+    }
+    if (Holder<long>::value != 42) {
+        return 13;
+    }
+    return 42;
+}
@@ -0,0 +1,10 @@
+inline int& counter() {
+    static int c;
+    return c;
+}
+
+template <typename T> struct Holder {
+    static T value;
+};
+
+template <typename T> T Holder<T>::value;